@@ -7,7 +7,7 @@
 
 CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
 CREATE EXTENSION IF NOT EXISTS timescaledb;
--- Note: pgvector is optional, installed via 002_embeddings.sql if available
+-- Note: pgvector is installed by 002_embeddings.sql
 
 -- =============================================================================
 -- WORKSPACES
@@ -21,7 +21,7 @@ CREATE TABLE IF NOT EXISTS workspaces (
     updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
 );
 
-CREATE INDEX idx_workspaces_api_key ON workspaces(api_key);
+CREATE INDEX IF NOT EXISTS idx_workspaces_api_key ON workspaces(api_key);
 
 -- =============================================================================
 -- SERVICES
@@ -37,7 +37,7 @@ CREATE TABLE IF NOT EXISTS services (
     UNIQUE(workspace_id, name)
 );
 
-CREATE INDEX idx_services_workspace ON services(workspace_id);
+CREATE INDEX IF NOT EXISTS idx_services_workspace ON services(workspace_id);
 
 -- =============================================================================
 -- QUERY METRICS (TimescaleDB Hypertable)
@@ -68,11 +68,11 @@ SELECT create_hypertable('query_metrics', 'created_at',
 );
 
 -- Performance indexes
-CREATE INDEX idx_metrics_workspace_time ON query_metrics(workspace_id, created_at DESC);
-CREATE INDEX idx_metrics_slow_queries ON query_metrics(workspace_id, duration_ms DESC) 
+CREATE INDEX IF NOT EXISTS idx_metrics_workspace_time ON query_metrics(workspace_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_metrics_slow_queries ON query_metrics(workspace_id, duration_ms DESC) 
     WHERE duration_ms > 1000;
-CREATE INDEX idx_metrics_status ON query_metrics(workspace_id, status, created_at DESC);
-CREATE INDEX idx_metrics_service ON query_metrics(service_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_metrics_status ON query_metrics(workspace_id, status, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_metrics_service ON query_metrics(service_id, created_at DESC);
 
 -- =============================================================================
 -- CONTINUOUS AGGREGATES (5s, 1m, 5m windows)
@@ -1,6 +1,8 @@
 -- QueryVault Phase 3: Vector Embeddings
 -- pgvector extension and query_embeddings table
 
+CREATE EXTENSION IF NOT EXISTS vector;
+
 -- =============================================================================
 -- QUERY EMBEDDINGS TABLE
 -- =============================================================================
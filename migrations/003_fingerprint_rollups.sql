@@ -0,0 +1,25 @@
+-- QueryVault: Hourly per-fingerprint rollups
+-- Maintained by the rollup task so top-N style reads avoid scanning query_metrics
+
+-- =============================================================================
+-- FINGERPRINT ROLLUPS
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS fingerprint_rollups (
+    workspace_id UUID NOT NULL,
+    fingerprint VARCHAR(64) NOT NULL,   -- md5(normalize_sql(query_text))
+    bucket TIMESTAMPTZ NOT NULL,        -- start of the hour
+    query_text TEXT NOT NULL,           -- representative query text
+    call_count BIGINT NOT NULL,
+    total_duration_ms BIGINT NOT NULL,
+    min_duration_ms BIGINT NOT NULL,
+    max_duration_ms BIGINT NOT NULL,
+    p95_duration_ms BIGINT NOT NULL,
+    failed_count BIGINT NOT NULL,
+    total_rows_affected BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (workspace_id, fingerprint, bucket)
+);
+
+CREATE INDEX IF NOT EXISTS idx_fingerprint_rollups_workspace_bucket
+ON fingerprint_rollups(workspace_id, bucket DESC);
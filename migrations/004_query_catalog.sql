@@ -0,0 +1,20 @@
+-- QueryVault: Query catalog
+-- One row per (workspace, fingerprint), updated on every buffer flush
+
+-- =============================================================================
+-- QUERY CATALOG
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS query_catalog (
+    workspace_id UUID NOT NULL,
+    fingerprint VARCHAR(64) NOT NULL,   -- md5(normalize_sql(query_text))
+    query_text TEXT NOT NULL,           -- representative query text
+    first_seen TIMESTAMPTZ NOT NULL,
+    last_seen TIMESTAMPTZ NOT NULL,
+    total_count BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (workspace_id, fingerprint)
+);
+
+-- Newly-appeared queries lookup
+CREATE INDEX IF NOT EXISTS idx_query_catalog_first_seen
+ON query_catalog(workspace_id, first_seen DESC);
@@ -0,0 +1,17 @@
+-- QueryVault: Query ownership annotations
+-- Owner team, ticket links, and notes per (workspace, fingerprint)
+
+-- =============================================================================
+-- QUERY OWNERSHIP
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS query_ownership (
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    fingerprint VARCHAR(64) NOT NULL,
+    owner_team VARCHAR(255),
+    ticket_links TEXT[] NOT NULL DEFAULT '{}',
+    notes TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (workspace_id, fingerprint)
+);
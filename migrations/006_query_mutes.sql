@@ -0,0 +1,15 @@
+-- QueryVault: Mute list for known-slow queries
+-- Muted fingerprints are ignored by anomaly detection until they expire
+
+-- =============================================================================
+-- QUERY MUTES
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS query_mutes (
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    fingerprint VARCHAR(64) NOT NULL,
+    reason TEXT,
+    expires_at TIMESTAMPTZ,             -- NULL = muted indefinitely
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (workspace_id, fingerprint)
+);
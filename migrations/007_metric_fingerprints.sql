@@ -0,0 +1,25 @@
+-- QueryVault: Store fingerprints alongside metrics and anomalies
+-- Fingerprints are computed by the server at ingest (see services/fingerprint.rs),
+-- which collapses IN-lists and OR chains that the SQL-side normalize_sql() cannot
+
+-- =============================================================================
+-- QUERY METRICS
+-- =============================================================================
+
+-- NULL for rows stored before this migration
+ALTER TABLE query_metrics ADD COLUMN IF NOT EXISTS fingerprint VARCHAR(64);
+
+CREATE INDEX IF NOT EXISTS idx_query_metrics_fingerprint
+ON query_metrics(workspace_id, fingerprint, created_at DESC);
+
+-- =============================================================================
+-- QUERY ANOMALIES
+-- =============================================================================
+
+ALTER TABLE query_anomalies ADD COLUMN IF NOT EXISTS fingerprint VARCHAR(64);
+
+UPDATE query_anomalies
+SET fingerprint = md5(normalize_sql(query_text))
+WHERE fingerprint IS NULL;
+
+ALTER TABLE query_anomalies ALTER COLUMN fingerprint SET NOT NULL;
@@ -0,0 +1,20 @@
+-- QueryVault: Dead-letter queue for metrics the database rejected
+-- Rows land here instead of being dropped, and can be inspected and reprocessed
+-- through the admin API
+
+-- =============================================================================
+-- QUERY METRICS DLQ
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS query_metrics_dlq (
+    metric_id UUID PRIMARY KEY,
+    workspace_id UUID NOT NULL,         -- no FK: a missing workspace may be why the row failed
+    metric JSONB NOT NULL,              -- the metric as ingested
+    error TEXT NOT NULL,                -- most recent failure
+    attempts INTEGER NOT NULL DEFAULT 1,
+    first_failed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_failed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_query_metrics_dlq_workspace
+ON query_metrics_dlq(workspace_id, last_failed_at DESC);
@@ -0,0 +1,22 @@
+-- QueryVault: Workspace event annotations
+-- Maintenance windows, incidents, and config changes overlaid on dashboards
+
+-- =============================================================================
+-- WORKSPACE ANNOTATIONS
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS workspace_annotations (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    kind VARCHAR(32) NOT NULL,          -- maintenance, incident, config_change, other
+    title TEXT NOT NULL,
+    description TEXT,
+    starts_at TIMESTAMPTZ NOT NULL,
+    ends_at TIMESTAMPTZ,                -- NULL = point-in-time event
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (ends_at IS NULL OR ends_at >= starts_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_workspace_annotations_time
+ON workspace_annotations(workspace_id, starts_at DESC);
@@ -0,0 +1,5 @@
+-- QueryVault: Per-workspace JSON field casing
+-- Lets a workspace opt its API responses into camelCase while existing consumers keep snake_case
+
+ALTER TABLE workspaces
+    ADD COLUMN IF NOT EXISTS json_casing VARCHAR(16) NOT NULL DEFAULT 'snake';  -- snake, camel
@@ -0,0 +1,5 @@
+-- QueryVault: Service registry
+-- Services are registered on first ingest and track when they last reported
+
+ALTER TABLE services
+    ADD COLUMN IF NOT EXISTS last_seen_at TIMESTAMPTZ;   -- NULL = never reported metrics
@@ -0,0 +1,35 @@
+-- QueryVault: Scoped API keys
+-- A workspace can hold several keys, each limited to some of ingest, read, and admin and optionally expiring
+
+CREATE TABLE IF NOT EXISTS api_keys (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    api_key VARCHAR(255) NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,                 -- ingest, read, admin
+    expires_at TIMESTAMPTZ,                 -- NULL = never expires
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_workspace ON api_keys(workspace_id);
+
+-- Each workspace's original key keeps full access
+INSERT INTO api_keys (workspace_id, name, api_key, scopes)
+SELECT id, 'primary', api_key, ARRAY['ingest', 'read', 'admin']
+FROM workspaces
+ON CONFLICT (api_key) DO NOTHING;
+
+CREATE OR REPLACE FUNCTION create_primary_api_key()
+RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO api_keys (workspace_id, name, api_key, scopes)
+    VALUES (NEW.id, 'primary', NEW.api_key, ARRAY['ingest', 'read', 'admin'])
+    ON CONFLICT (api_key) DO NOTHING;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS workspaces_primary_api_key ON workspaces;
+CREATE TRIGGER workspaces_primary_api_key
+    AFTER INSERT ON workspaces
+    FOR EACH ROW EXECUTE FUNCTION create_primary_api_key();
@@ -0,0 +1,15 @@
+-- QueryVault: Hash API keys at rest
+-- Keys are stored as SHA-256 hashes; existing plaintext keys are hashed (and cleared) the first time they are used
+
+ALTER TABLE api_keys
+    ADD COLUMN IF NOT EXISTS key_hash VARCHAR(64) UNIQUE;   -- hex SHA-256 of the key
+
+ALTER TABLE api_keys
+    ALTER COLUMN api_key DROP NOT NULL;                     -- plaintext, NULL once hashed
+
+-- Workspace keys live in api_keys since 012, so drop the plaintext copy
+DROP TRIGGER IF EXISTS workspaces_primary_api_key ON workspaces;
+DROP FUNCTION IF EXISTS create_primary_api_key();
+
+ALTER TABLE workspaces
+    DROP COLUMN IF EXISTS api_key;
@@ -0,0 +1,15 @@
+-- QueryVault: Anomaly severity
+-- Severity (0-100) blends z-score, call frequency, and extra database time
+-- (see services/severity.rs) so the anomalies list can lead with what matters
+
+ALTER TABLE query_anomalies
+    ADD COLUMN IF NOT EXISTS severity DOUBLE PRECISION NOT NULL DEFAULT 0;
+
+-- Call rates weren't recorded for older anomalies; score them on the
+-- z-score component alone
+UPDATE query_anomalies
+SET severity = 25 * LEAST(LOG(GREATEST(z_score / 3.0, 1.0)), 1.0)
+WHERE severity = 0;
+
+CREATE INDEX IF NOT EXISTS idx_anomalies_workspace_severity
+ON query_anomalies(workspace_id, severity DESC, detected_at DESC);
@@ -0,0 +1,6 @@
+-- QueryVault: Anomaly impact estimates
+-- Extra database time an anomaly caused: (duration - mean) x the fingerprint's
+-- calls over the detector's 24 hour history window
+
+-- NULL for anomalies recorded before this migration
+ALTER TABLE query_anomalies ADD COLUMN IF NOT EXISTS estimated_extra_ms BIGINT;
@@ -0,0 +1,21 @@
+-- QueryVault: Audit log
+-- Administrative changes with the API key that made them, for compliance
+
+-- =============================================================================
+-- AUDIT LOG
+-- =============================================================================
+
+-- No foreign keys: entries must outlive the keys and workspaces they mention
+CREATE TABLE IF NOT EXISTS audit_log (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    workspace_id UUID NOT NULL,
+    action VARCHAR(64) NOT NULL,        -- e.g. api_key.create, service.delete
+    actor_key_id UUID NOT NULL,
+    actor_name VARCHAR(255) NOT NULL,
+    target TEXT,                        -- affected resource ID, if any
+    payload JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_workspace_time
+ON audit_log(workspace_id, created_at DESC);
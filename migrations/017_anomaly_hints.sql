@@ -0,0 +1,6 @@
+-- QueryVault: Root-cause hints on anomalies
+-- Correlated annotations, error spikes, and related anomalies found by the
+-- detection task, as a JSON array of {kind, message, reference}
+
+ALTER TABLE query_anomalies
+    ADD COLUMN IF NOT EXISTS hints JSONB NOT NULL DEFAULT '[]';
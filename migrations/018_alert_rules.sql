@@ -0,0 +1,27 @@
+-- QueryVault: Alert rules
+-- Composite threshold rules evaluated over the 1m/5m continuous aggregates
+
+-- =============================================================================
+-- ALERT RULES
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS alert_rules (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    service_id UUID,                    -- NULL = whole workspace
+    window_size VARCHAR(8) NOT NULL,    -- 1m, 5m
+    conditions JSONB NOT NULL,          -- [{signal, comparison, threshold, clear_threshold}]
+    combine VARCHAR(8) NOT NULL DEFAULT 'all',  -- all, any
+    for_windows INTEGER NOT NULL DEFAULT 1 CHECK (for_windows > 0),
+    resolve_windows INTEGER NOT NULL DEFAULT 1 CHECK (resolve_windows > 0),
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_rules_workspace
+ON alert_rules(workspace_id, created_at);
+
+CREATE INDEX IF NOT EXISTS idx_alert_rules_enabled
+ON alert_rules(enabled) WHERE enabled;
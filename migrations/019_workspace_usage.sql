@@ -0,0 +1,18 @@
+-- QueryVault: Workspace usage metering
+-- Stored metric counts and estimated bytes per workspace per day, for chargeback and quotas
+
+-- =============================================================================
+-- WORKSPACE USAGE
+-- =============================================================================
+
+-- No foreign key: usage history is kept for billing after a workspace is removed
+CREATE TABLE IF NOT EXISTS workspace_usage (
+    workspace_id UUID NOT NULL,
+    day DATE NOT NULL,                  -- UTC day the metrics were stored
+    metric_count BIGINT NOT NULL DEFAULT 0,
+    stored_bytes BIGINT NOT NULL DEFAULT 0,  -- estimated row size
+    PRIMARY KEY (workspace_id, day)
+);
+
+ALTER TABLE workspaces
+    ADD COLUMN IF NOT EXISTS monthly_metric_quota BIGINT;  -- NULL = unlimited
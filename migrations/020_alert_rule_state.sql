@@ -0,0 +1,16 @@
+-- QueryVault: Alert rule evaluation state
+-- Firing state and consecutive-window counters, so restarts don't re-fire or forget alerts
+
+-- =============================================================================
+-- ALERT RULE STATE
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS alert_rule_state (
+    rule_id UUID PRIMARY KEY REFERENCES alert_rules(id) ON DELETE CASCADE,
+    rule_version TIMESTAMPTZ NOT NULL,  -- rule's updated_at when the state was built
+    firing BOOLEAN NOT NULL DEFAULT FALSE,
+    breaches INTEGER NOT NULL DEFAULT 0,  -- consecutive breaching windows while not firing
+    clears INTEGER NOT NULL DEFAULT 0,    -- consecutive clear windows while firing
+    last_bucket TIMESTAMPTZ,            -- last window evaluated
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,19 @@
+-- QueryVault: Alert destinations
+-- Per-workspace endpoints that alerts are delivered to
+
+-- =============================================================================
+-- ALERT DESTINATIONS
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS alert_destinations (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    kind VARCHAR(32) NOT NULL DEFAULT 'webhook',  -- webhook
+    url TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_destinations_workspace
+ON alert_destinations(workspace_id, created_at);
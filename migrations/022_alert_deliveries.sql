@@ -0,0 +1,26 @@
+-- QueryVault: Alert deliveries
+-- Every attempt to send an alert to a destination, with what the destination answered
+
+-- =============================================================================
+-- ALERT DELIVERIES
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS alert_deliveries (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    destination_id UUID NOT NULL,       -- kept after the destination is deleted
+    event VARCHAR(64) NOT NULL,         -- anomaly.detected
+    test BOOLEAN NOT NULL DEFAULT FALSE,
+    delivered BOOLEAN NOT NULL,         -- destination answered 2xx
+    status INTEGER,                     -- NULL = no response
+    latency_ms BIGINT NOT NULL,
+    response TEXT,                      -- start of the response body
+    error TEXT,                         -- why the request got no response
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_deliveries_workspace_time
+ON alert_deliveries(workspace_id, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_alert_deliveries_destination_time
+ON alert_deliveries(destination_id, created_at DESC);
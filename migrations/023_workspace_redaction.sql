@@ -0,0 +1,5 @@
+-- QueryVault: Per-workspace literal redaction
+-- Opt-in replacement of string and numeric literals in ingested query text with placeholders
+
+ALTER TABLE workspaces
+    ADD COLUMN IF NOT EXISTS redact_literals BOOLEAN NOT NULL DEFAULT FALSE;
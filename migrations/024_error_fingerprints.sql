@@ -0,0 +1,10 @@
+-- QueryVault: Error classes for failed metrics
+-- Failed and timed-out metrics store the fingerprint of their normalized error
+-- message (see services/error_fingerprint.rs), so errors can be grouped by class
+
+-- NULL for successful executions and rows stored before this migration
+ALTER TABLE query_metrics ADD COLUMN IF NOT EXISTS error_fingerprint VARCHAR(64);
+
+CREATE INDEX IF NOT EXISTS idx_query_metrics_error_fingerprint
+ON query_metrics(workspace_id, error_fingerprint, created_at DESC)
+WHERE error_fingerprint IS NOT NULL;
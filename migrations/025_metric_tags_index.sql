@@ -0,0 +1,6 @@
+-- QueryVault: Tag filters
+-- Aggregations and recent metrics filtered by tags (`tags @> ...`) scan the raw
+-- metrics, since the continuous aggregates don't keep tags
+
+CREATE INDEX IF NOT EXISTS idx_query_metrics_tags
+ON query_metrics USING GIN (tags);
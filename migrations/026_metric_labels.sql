@@ -0,0 +1,8 @@
+-- QueryVault: Key/value labels on metrics
+-- Labels (`{"env": "prod"}`) complement the flat `tags` list; the read APIs
+-- filter on them with `labels @> ...`, which the GIN index serves
+
+ALTER TABLE query_metrics ADD COLUMN IF NOT EXISTS labels JSONB NOT NULL DEFAULT '{}';
+
+CREATE INDEX IF NOT EXISTS idx_query_metrics_labels
+ON query_metrics USING GIN (labels jsonb_path_ops);
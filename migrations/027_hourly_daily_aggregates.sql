@@ -0,0 +1,66 @@
+-- QueryVault: 1-hour and 1-day continuous aggregates
+-- Long-range dashboards read these instead of tens of thousands of 5m buckets.
+-- Like the finer aggregates they are computed from the raw metrics, since
+-- percentiles can't be rolled up from smaller buckets.
+--
+-- The refresh policies only materialize recent buckets; to backfill the raw
+-- metrics still retained, run once per view outside a transaction:
+--   CALL refresh_continuous_aggregate('metrics_1h', NULL, NOW() - INTERVAL '1 hour');
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS metrics_1h
+WITH (timescaledb.continuous) AS
+SELECT
+    workspace_id,
+    service_id,
+    time_bucket('1 hour', created_at) AS bucket,
+    COUNT(*) AS query_count,
+    AVG(duration_ms)::BIGINT AS avg_duration_ms,
+    MIN(duration_ms) AS min_duration_ms,
+    MAX(duration_ms) AS max_duration_ms,
+    PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY duration_ms)::BIGINT AS p95_duration_ms,
+    PERCENTILE_CONT(0.99) WITHIN GROUP (ORDER BY duration_ms)::BIGINT AS p99_duration_ms,
+    SUM(CASE WHEN status = 'success' THEN 1 ELSE 0 END) AS success_count,
+    SUM(CASE WHEN status = 'failed' THEN 1 ELSE 0 END) AS failed_count,
+    SUM(COALESCE(rows_affected, 0)) AS total_rows_affected
+FROM query_metrics
+GROUP BY workspace_id, service_id, bucket
+WITH NO DATA;
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS metrics_1d
+WITH (timescaledb.continuous) AS
+SELECT
+    workspace_id,
+    service_id,
+    time_bucket('1 day', created_at) AS bucket,
+    COUNT(*) AS query_count,
+    AVG(duration_ms)::BIGINT AS avg_duration_ms,
+    MIN(duration_ms) AS min_duration_ms,
+    MAX(duration_ms) AS max_duration_ms,
+    PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY duration_ms)::BIGINT AS p95_duration_ms,
+    PERCENTILE_CONT(0.99) WITHIN GROUP (ORDER BY duration_ms)::BIGINT AS p99_duration_ms,
+    SUM(CASE WHEN status = 'success' THEN 1 ELSE 0 END) AS success_count,
+    SUM(CASE WHEN status = 'failed' THEN 1 ELSE 0 END) AS failed_count,
+    SUM(COALESCE(rows_affected, 0)) AS total_rows_affected
+FROM query_metrics
+GROUP BY workspace_id, service_id, bucket
+WITH NO DATA;
+
+SELECT add_continuous_aggregate_policy('metrics_1h',
+    start_offset => INTERVAL '3 days',
+    end_offset => INTERVAL '1 hour',
+    schedule_interval => INTERVAL '15 minutes',
+    if_not_exists => TRUE
+);
+
+SELECT add_continuous_aggregate_policy('metrics_1d',
+    start_offset => INTERVAL '7 days',
+    end_offset => INTERVAL '1 day',
+    schedule_interval => INTERVAL '1 hour',
+    if_not_exists => TRUE
+);
+
+-- 1h aggregates: keep 2 years
+SELECT add_retention_policy('metrics_1h', INTERVAL '730 days', if_not_exists => TRUE);
+
+-- 1d aggregates: keep 5 years
+SELECT add_retention_policy('metrics_1d', INTERVAL '1825 days', if_not_exists => TRUE);
@@ -0,0 +1,17 @@
+-- QueryVault: Ingestion statistics
+-- Per-minute counts of what the ingest path did with each workspace's metrics,
+-- so tenants can check their agents against what arrived
+
+-- =============================================================================
+-- INGESTION STATS
+-- =============================================================================
+
+-- Instances add their counts every few seconds; rows older than a day are pruned
+CREATE TABLE IF NOT EXISTS ingestion_stats (
+    workspace_id UUID NOT NULL,
+    minute TIMESTAMPTZ NOT NULL,            -- UTC minute the metrics arrived
+    accepted BIGINT NOT NULL DEFAULT 0,     -- buffered for storage
+    dropped BIGINT NOT NULL DEFAULT 0,      -- lost to a full buffer
+    deduplicated BIGINT NOT NULL DEFAULT 0, -- skipped as resends of a recent metric ID
+    PRIMARY KEY (workspace_id, minute)
+);
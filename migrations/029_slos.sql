@@ -0,0 +1,38 @@
+-- QueryVault: Service level objectives
+-- Latency targets over a rolling window, with the last computed compliance and burn state
+
+-- =============================================================================
+-- SLOS
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS slos (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    service_id UUID,                    -- NULL = whole workspace
+    threshold_ms BIGINT NOT NULL CHECK (threshold_ms > 0),
+    target_percent DOUBLE PRECISION NOT NULL CHECK (target_percent > 0 AND target_percent < 100),
+    window_days INTEGER NOT NULL CHECK (window_days > 0),
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_slos_workspace
+ON slos(workspace_id, created_at);
+
+-- =============================================================================
+-- SLO STATUS
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS slo_status (
+    slo_id UUID PRIMARY KEY REFERENCES slos(id) ON DELETE CASCADE,
+    computed_at TIMESTAMPTZ NOT NULL,
+    total BIGINT NOT NULL,              -- queries in the SLO window
+    satisfied BIGINT NOT NULL,          -- succeeded within the threshold
+    tolerating BIGINT NOT NULL,         -- succeeded within 4x the threshold
+    burn_rate_1h DOUBLE PRECISION,
+    burn_rate_6h DOUBLE PRECISION,
+    burn_state VARCHAR(16) NOT NULL DEFAULT 'ok',  -- ok, slow_burn, fast_burn
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
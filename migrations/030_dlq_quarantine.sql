@@ -0,0 +1,7 @@
+-- QueryVault: Schema drift quarantine
+-- Dead letters record why the metric failed, so metrics quarantined because they
+-- didn't fit the schema can be told apart from rejected values and reprocessed on
+-- their own once migrations have caught up
+
+ALTER TABLE query_metrics_dlq
+    ADD COLUMN IF NOT EXISTS reason TEXT NOT NULL DEFAULT 'rejected';  -- 'rejected' or 'schema_drift'
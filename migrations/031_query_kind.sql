@@ -0,0 +1,10 @@
+-- QueryVault: Statement kind on metrics
+-- Each metric is classified at ingest as select, insert, update, delete, ddl,
+-- or other (see services/fingerprint.rs), so writes and schema changes can be
+-- charted apart from reads. Metrics stored before this column have no kind.
+
+ALTER TABLE query_metrics ADD COLUMN IF NOT EXISTS query_kind VARCHAR(16);
+
+CREATE INDEX IF NOT EXISTS idx_query_metrics_query_kind
+ON query_metrics(workspace_id, query_kind, created_at DESC)
+WHERE query_kind IS NOT NULL;
@@ -0,0 +1,50 @@
+-- QueryVault: Data lifecycle policies
+-- Ordered per-workspace stages applied by the lifecycle task to data older
+-- than each stage's age (see services/lifecycle.rs), with how each last ran.
+-- Workspaces without stages keep raw metrics for 30 days, as before.
+
+-- =============================================================================
+-- LIFECYCLE POLICIES
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS lifecycle_policies (
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    action VARCHAR(16) NOT NULL,        -- compress, archive, downsample, delete
+    after_days INTEGER NOT NULL CHECK (after_days > 0),
+    last_run_at TIMESTAMPTZ,
+    last_affected BIGINT,               -- rows changed or removed by the last run
+    last_error TEXT,                    -- NULL after a successful run
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (workspace_id, action)
+);
+
+-- =============================================================================
+-- METRICS ARCHIVE
+-- =============================================================================
+
+-- Raw metrics copied by `archive` stages, kept until a `delete` stage removes
+-- them. A plain table, as it is only read by ID or workspace and age.
+CREATE TABLE IF NOT EXISTS query_metrics_archive (
+    id UUID NOT NULL,
+    workspace_id UUID NOT NULL,
+    service_id UUID NOT NULL,
+    query_text TEXT NOT NULL,
+    status VARCHAR(20) NOT NULL,
+    duration_ms BIGINT NOT NULL,
+    rows_affected BIGINT,
+    error_message TEXT,
+    started_at TIMESTAMPTZ NOT NULL,
+    completed_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    tags TEXT[] DEFAULT '{}',
+    labels JSONB NOT NULL DEFAULT '{}',
+    fingerprint VARCHAR(64),
+    error_fingerprint VARCHAR(64),
+    query_kind VARCHAR(16),
+    archived_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (workspace_id, id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_query_metrics_archive_age
+ON query_metrics_archive(workspace_id, created_at);
@@ -0,0 +1,21 @@
+-- QueryVault: Throughput-drop anomalies
+-- A service whose query volume falls far below its recent baseline is recorded
+-- as a drop, open until its volume recovers, so it is reported once per drop and
+-- a restart doesn't report an open drop again.
+
+CREATE TABLE IF NOT EXISTS throughput_drops (
+    id UUID PRIMARY KEY,
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    service_id UUID NOT NULL,
+    baseline_per_minute DOUBLE PRECISION NOT NULL,  -- before the drop
+    recent_per_minute DOUBLE PRECISION NOT NULL,    -- when it was detected
+    started_at TIMESTAMPTZ NOT NULL,
+    recovered_at TIMESTAMPTZ                        -- NULL while open
+);
+
+CREATE INDEX IF NOT EXISTS idx_throughput_drops_workspace_time
+ON throughput_drops(workspace_id, started_at DESC);
+
+-- At most one open drop per service
+CREATE UNIQUE INDEX IF NOT EXISTS idx_throughput_drops_open
+ON throughput_drops(workspace_id, service_id) WHERE recovered_at IS NULL;
@@ -0,0 +1,9 @@
+-- QueryVault: Per-workspace anomaly detection method
+-- Workspaces choose between z-scores against the mean and median absolute
+-- deviations from the median; each anomaly records the method that found it
+
+ALTER TABLE workspaces
+    ADD COLUMN IF NOT EXISTS anomaly_method VARCHAR(16) NOT NULL DEFAULT 'z_score';
+
+ALTER TABLE query_anomalies
+    ADD COLUMN IF NOT EXISTS method VARCHAR(16) NOT NULL DEFAULT 'z_score';
@@ -0,0 +1,10 @@
+-- QueryVault: Per-workspace anomaly detection settings
+-- Each column overrides the server's default for one workspace; NULL keeps
+-- the default
+
+ALTER TABLE workspaces
+    ADD COLUMN IF NOT EXISTS anomaly_z_threshold DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS anomaly_mad_threshold DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS anomaly_interval_secs INTEGER,
+    ADD COLUMN IF NOT EXISTS anomaly_lookback INTEGER,
+    ADD COLUMN IF NOT EXISTS anomaly_min_samples INTEGER;
@@ -0,0 +1,21 @@
+-- QueryVault: Seasonal anomaly baselines
+-- Workspaces can have anomalies judged against the usual durations of the
+-- same hour of the day or week (UTC) instead of their most recent metrics;
+-- the baseline task recomputes each slot's statistics every hour
+
+ALTER TABLE workspaces
+    ADD COLUMN IF NOT EXISTS anomaly_seasonality VARCHAR(16);
+
+CREATE TABLE IF NOT EXISTS anomaly_baselines (
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    -- 'daily' (slot = hour of the day) or 'weekly' (slot = hour of the week, from Monday)
+    seasonality VARCHAR(16) NOT NULL,
+    slot SMALLINT NOT NULL,
+    mean DOUBLE PRECISION NOT NULL,
+    stddev DOUBLE PRECISION NOT NULL,
+    median DOUBLE PRECISION NOT NULL,
+    mad DOUBLE PRECISION NOT NULL,
+    count BIGINT NOT NULL,
+    computed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (workspace_id, slot)
+);
@@ -0,0 +1,12 @@
+-- QueryVault: Anomaly triage
+-- Anomalies start open, can be acknowledged by whoever is looking into them,
+-- and are resolved once dealt with; the list can be filtered by status
+
+ALTER TABLE query_anomalies
+    ADD COLUMN IF NOT EXISTS status VARCHAR(16) NOT NULL DEFAULT 'open',
+    -- Name of the API key that acknowledged (or resolved) the anomaly
+    ADD COLUMN IF NOT EXISTS acknowledged_by VARCHAR(255),
+    ADD COLUMN IF NOT EXISTS resolved_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_anomalies_workspace_status
+ON query_anomalies(workspace_id, status, detected_at DESC);
@@ -0,0 +1,9 @@
+-- QueryVault: Signed, retried alert deliveries
+-- Each destination gets a secret its requests are signed with (NULL for
+-- destinations created before signing, whose deliveries stay unsigned), and
+-- each delivery records how many requests it took
+
+ALTER TABLE alert_destinations ADD COLUMN IF NOT EXISTS signing_secret TEXT;
+
+ALTER TABLE alert_deliveries
+    ADD COLUMN IF NOT EXISTS attempts INTEGER NOT NULL DEFAULT 1;
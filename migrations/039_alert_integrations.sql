@@ -0,0 +1,6 @@
+-- QueryVault: Slack and PagerDuty alert destinations
+-- Slack destinations post to an incoming webhook URL; PagerDuty destinations
+-- send Events API v2 events routed by an integration key, encrypted at rest
+-- like the URL
+
+ALTER TABLE alert_destinations ADD COLUMN IF NOT EXISTS routing_key TEXT;
@@ -0,0 +1,23 @@
+-- QueryVault: Stored anomaly severity levels
+-- An anomaly's level is its score's bucket, raised to high (a warning) or
+-- critical when its z-score or duration alone is in the warning or critical
+-- band (see services/severity.rs), so a 40-second query can't pass for a
+-- blip. Alert destinations only receive anomalies at or above their
+-- min_severity.
+
+ALTER TABLE query_anomalies
+    ADD COLUMN IF NOT EXISTS severity_level VARCHAR(16) NOT NULL DEFAULT 'low';
+
+UPDATE query_anomalies
+SET severity_level = CASE
+    WHEN severity >= 75 OR z_score >= 30 OR duration_ms >= 30000 THEN 'critical'
+    WHEN severity >= 50 OR z_score >= 10 OR duration_ms >= 5000 THEN 'high'
+    WHEN severity >= 25 THEN 'medium'
+    ELSE 'low'
+END;
+
+CREATE INDEX IF NOT EXISTS idx_anomalies_workspace_severity_level
+ON query_anomalies(workspace_id, severity_level, detected_at DESC);
+
+ALTER TABLE alert_destinations
+    ADD COLUMN IF NOT EXISTS min_severity VARCHAR(16) NOT NULL DEFAULT 'high';
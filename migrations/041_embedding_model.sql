@@ -0,0 +1,13 @@
+-- QueryVault: Embedding model versioning
+-- Vectors from different models aren't comparable, so each embedding records
+-- the model and version that produced it. Similarity search only compares
+-- vectors of one model, and the re-embedding task rewrites rows whose model
+-- isn't the configured one. Rows from before this migration have an empty
+-- model and are re-embedded.
+
+ALTER TABLE query_embeddings
+    ADD COLUMN IF NOT EXISTS model_name TEXT NOT NULL DEFAULT '',
+    ADD COLUMN IF NOT EXISTS model_version TEXT NOT NULL DEFAULT '';
+
+CREATE INDEX IF NOT EXISTS idx_query_embeddings_model
+ON query_embeddings(workspace_id, model_name, model_version);
@@ -0,0 +1,13 @@
+-- QueryVault: Queries too long to embed
+-- The embedding task skips queries over EMBEDDING_MAX_QUERY_BYTES (multi-KB
+-- generated IN lists and the like) and records them here, so they aren't
+-- fetched again on every run. Skips only apply while the query is still over
+-- the configured limit; raising it makes them eligible again.
+
+CREATE TABLE IF NOT EXISTS query_embedding_skips (
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    query_hash VARCHAR(64) NOT NULL,
+    query_bytes INTEGER NOT NULL,
+    skipped_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (workspace_id, query_hash)
+);
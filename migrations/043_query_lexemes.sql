@@ -0,0 +1,14 @@
+-- QueryVault: Full-text index for hybrid similarity search
+-- Hybrid search matches identifiers in the search text against stored
+-- queries as well as comparing embeddings. The default parser reads names
+-- like `users.email` as a single host name, so queries are split on every
+-- non-alphanumeric character first: each part of a qualified or snake_case
+-- name is its own lexeme, in order, and phrase queries match whole names.
+
+CREATE OR REPLACE FUNCTION sql_lexemes(query TEXT)
+RETURNS tsvector AS $$
+    SELECT to_tsvector('simple', regexp_replace(query, '[^[:alnum:]]+', ' ', 'g'))
+$$ LANGUAGE SQL IMMUTABLE;
+
+CREATE INDEX IF NOT EXISTS idx_query_embeddings_lexemes
+    ON query_embeddings USING gin (sql_lexemes(sql_query));
@@ -0,0 +1,13 @@
+-- QueryVault: Text search over the query catalog
+-- Backs GET /search/text, which works without an embedding model: words in
+-- the search text match the catalog's lexemes (split like hybrid search, see
+-- 043_query_lexemes.sql), and the trigram index serves plain substring
+-- matches such as `status = 'active'`.
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS idx_query_catalog_lexemes
+    ON query_catalog USING gin (sql_lexemes(query_text));
+
+CREATE INDEX IF NOT EXISTS idx_query_catalog_text_trgm
+    ON query_catalog USING gin (query_text gin_trgm_ops);
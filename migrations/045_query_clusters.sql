@@ -0,0 +1,29 @@
+-- QueryVault: Query clusters
+-- The clustering task groups each workspace's embedded queries into families
+-- (k-means over the current model's embeddings) and replaces these rows on
+-- every run. Traffic per cluster is computed on read from the raw metrics,
+-- joined through the members' query hashes.
+
+CREATE TABLE IF NOT EXISTS query_clusters (
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    cluster_id INTEGER NOT NULL,            -- 0 is the largest
+    label TEXT,                             -- tables the members reference most
+    representative_hash VARCHAR(64) NOT NULL,
+    representative_query TEXT NOT NULL,     -- member nearest the cluster's center
+    model_name TEXT NOT NULL,
+    model_version TEXT NOT NULL,
+    computed_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (workspace_id, cluster_id)
+);
+
+CREATE TABLE IF NOT EXISTS query_cluster_members (
+    workspace_id UUID NOT NULL,
+    query_hash VARCHAR(64) NOT NULL,
+    cluster_id INTEGER NOT NULL,
+    PRIMARY KEY (workspace_id, query_hash),
+    FOREIGN KEY (workspace_id, cluster_id)
+        REFERENCES query_clusters(workspace_id, cluster_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_query_cluster_members_cluster
+    ON query_cluster_members(workspace_id, cluster_id);
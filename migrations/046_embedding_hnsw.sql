@@ -0,0 +1,13 @@
+-- QueryVault: HNSW index for similarity search
+-- The IVFFlat index from 002 was built with lists = 100 over an empty table,
+-- so its centroids never matched the data and recall fell as embeddings
+-- accumulated; the planner often preferred a brute-force scan instead.
+-- HNSW needs no training data and keeps recall high as the table grows.
+-- Requires pgvector 0.5.0 or later.
+--
+-- Nothing is built here: a plain CREATE INDEX inside the migration would
+-- block embedding writes for as long as the build takes. After migrations
+-- run, Database::ensure_vector_index sees the index doesn't match the
+-- EMBEDDING_INDEX* settings (HNSW with m = 16, ef_construction = 64 by
+-- default) and rebuilds it concurrently, keeping the old one for searches
+-- until the new one is ready.
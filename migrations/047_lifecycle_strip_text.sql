@@ -0,0 +1,11 @@
+-- QueryVault: Lifecycle stages named for what they do, at ages the rollups allow
+-- The `compress` stage only ever cleared raw metrics' query text, so it is
+-- renamed `strip_text`. Stages deleting raw metrics must also wait until the
+-- continuous aggregates stop refreshing them (7 days back for metrics_1d, see
+-- 027_hourly_daily_aggregates.sql), or a refresh would drop the deleted rows
+-- from the rollups; existing stages younger than that are moved out to 8 days.
+
+UPDATE lifecycle_policies SET action = 'strip_text' WHERE action = 'compress';
+
+UPDATE lifecycle_policies SET after_days = 8
+WHERE action IN ('downsample', 'delete') AND after_days < 8;
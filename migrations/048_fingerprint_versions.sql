@@ -0,0 +1,20 @@
+-- QueryVault: Fingerprint algorithm versions
+-- Fingerprints are computed by the server (see services/fingerprint.rs), so
+-- when the algorithm changes the tables keyed by them are re-keyed at startup
+-- by Database::rekey_fingerprints, which records each version it finished
+-- here. Version 2 folds literals into placeholders. Installs with no queries
+-- catalogued yet have nothing to re-key.
+
+-- =============================================================================
+-- FINGERPRINT VERSIONS
+-- =============================================================================
+
+CREATE TABLE IF NOT EXISTS fingerprint_versions (
+    version INTEGER PRIMARY KEY,
+    rekeyed INTEGER NOT NULL DEFAULT 0,  -- fingerprints moved to a new key
+    completed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO fingerprint_versions (version)
+SELECT 2 WHERE NOT EXISTS (SELECT 1 FROM query_catalog)
+ON CONFLICT DO NOTHING;
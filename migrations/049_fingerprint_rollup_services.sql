@@ -0,0 +1,45 @@
+-- QueryVault: Per-service fingerprint rollups
+-- The top queries list, service overviews, and table analytics read the
+-- hourly rollups instead of scanning query_metrics, so each rollup is now
+-- kept per service and also records errors (failed or timed out), the
+-- median and p99 durations, and when the fingerprint first and last ran in
+-- the hour. Rollups written before this were workspace-wide: they keep the
+-- nil service, their error count is their failure count, and their p50 and
+-- p99 are their p95. Database::backfill_rollup_services rebuilds those the
+-- raw metrics still cover.
+
+-- =============================================================================
+-- FINGERPRINT ROLLUPS
+-- =============================================================================
+
+ALTER TABLE fingerprint_rollups
+    ADD COLUMN IF NOT EXISTS service_id UUID NOT NULL
+        DEFAULT '00000000-0000-0000-0000-000000000000',
+    ADD COLUMN IF NOT EXISTS error_count BIGINT,
+    ADD COLUMN IF NOT EXISTS p50_duration_ms BIGINT,
+    ADD COLUMN IF NOT EXISTS p99_duration_ms BIGINT,
+    ADD COLUMN IF NOT EXISTS first_seen TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS last_seen TIMESTAMPTZ;
+
+UPDATE fingerprint_rollups SET
+    error_count = failed_count,
+    p50_duration_ms = p95_duration_ms,
+    p99_duration_ms = p95_duration_ms,
+    first_seen = bucket,
+    last_seen = bucket
+WHERE error_count IS NULL;
+
+ALTER TABLE fingerprint_rollups
+    ALTER COLUMN service_id DROP DEFAULT,
+    ALTER COLUMN error_count SET NOT NULL,
+    ALTER COLUMN p50_duration_ms SET NOT NULL,
+    ALTER COLUMN p99_duration_ms SET NOT NULL,
+    ALTER COLUMN first_seen SET NOT NULL,
+    ALTER COLUMN last_seen SET NOT NULL;
+
+ALTER TABLE fingerprint_rollups DROP CONSTRAINT IF EXISTS fingerprint_rollups_pkey;
+ALTER TABLE fingerprint_rollups
+    ADD PRIMARY KEY (workspace_id, service_id, fingerprint, bucket);
+
+CREATE INDEX IF NOT EXISTS idx_fingerprint_rollups_service_bucket
+ON fingerprint_rollups(workspace_id, service_id, bucket DESC);
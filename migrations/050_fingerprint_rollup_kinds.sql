@@ -0,0 +1,7 @@
+-- QueryVault: Statement kind of fingerprint rollups
+-- The top queries list, service overviews, and table analytics filter by
+-- query_kind, and read the rollups, so each rollup records the kind its
+-- fingerprint's statements were classified as. Rollups of metrics stored
+-- before classification have none, and match no kind.
+
+ALTER TABLE fingerprint_rollups ADD COLUMN IF NOT EXISTS query_kind VARCHAR(16);
@@ -0,0 +1,405 @@
+//! HTTP application: every route with its middleware
+//!
+//! Shared by the server binary and the `testkit` feature, so tests exercise
+//! the same routing, auth, casing, and versioning as production.
+
+use axum::{
+    extract::{DefaultBodyLimit, Request},
+    http::{header, Method},
+    middleware,
+    response::Response,
+    routing::{delete, get, patch, post, put},
+    Router,
+};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::{Layer, Service};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tower_http::trace::TraceLayer;
+
+use crate::auth;
+use crate::casing;
+use crate::routes::{
+    admin, aggregations, alerts, annotations, api_keys, audit, beacon, destinations, erasure,
+    errors, health, ingest, metrics, overview, poll, queries, registry, schemas, search, settings,
+    slo, sse, stats, tables, throughput, usage, ws,
+};
+use crate::state::AppState;
+use crate::versioning;
+
+/// The full application for `state`, ready to serve
+pub fn app(
+    state: AppState,
+) -> impl Service<Request, Response = Response, Error = Infallible, Future: Send> + Clone + Send + 'static
+{
+    let metrics = Arc::clone(&state.metrics);
+    let app = Router::new()
+        // Health and metrics (Kubernetes probes + Prometheus)
+        .route("/health", get(health::health))
+        .route("/ready", get(health::ready))
+        .route("/metrics", get(metrics::prometheus_metrics))
+        // Versioned API
+        .nest(
+            "/api/v1",
+            api_routes(&state)
+                .merge(ingest_route(&state, ingest::ingest_metrics))
+                .route("/schemas/ingest", get(schemas::get_ingest_schema_v1)),
+        )
+        .nest(
+            "/api/v2",
+            api_routes(&state)
+                .merge(ingest_route(&state, ingest::ingest_metrics_v2))
+                .route("/schemas/ingest", get(schemas::get_ingest_schema_v2)),
+        )
+        // State and middleware
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            casing::json_casing,
+        ))
+        .with_state(state.clone())
+        .layer(
+            CorsLayer::new()
+                .allow_origin(Any)
+                .allow_methods(Any)
+                .allow_headers(Any),
+        )
+        // Browser beacons bring their own CORS policy
+        .merge(beacon_routes(state))
+        .layer(middleware::from_fn_with_state(
+            metrics,
+            metrics::record_requests,
+        ))
+        .layer(TraceLayer::new_for_http());
+    // Resolves unversioned /api paths, so it must run before routing
+    middleware::from_fn(versioning::negotiate_version).layer(app)
+}
+
+/// Routes shared by every API version, relative to `/api/v{n}`
+///
+/// Each group requires an API key with its scope. Ingestion and its schema
+/// are added per version (ingestion with [`ingest_route`]) since the
+/// payload differs.
+fn api_routes(state: &AppState) -> Router<AppState> {
+    let read = Router::new()
+        // Aggregations & metrics
+        .route(
+            "/workspaces/:workspace_id/aggregations",
+            get(aggregations::get_aggregations),
+        )
+        .route(
+            "/workspaces/:workspace_id/throughput",
+            get(throughput::get_throughput),
+        )
+        .route(
+            "/workspaces/:workspace_id/metrics",
+            get(aggregations::get_recent_metrics),
+        )
+        .route(
+            "/workspaces/:workspace_id/metrics/poll",
+            get(poll::poll_metrics),
+        )
+        // Annotations
+        .route(
+            "/workspaces/:workspace_id/annotations",
+            get(annotations::list_annotations),
+        )
+        // Query catalog
+        .route(
+            "/workspaces/:workspace_id/queries",
+            get(queries::list_query_stats),
+        )
+        .route(
+            "/workspaces/:workspace_id/queries/new",
+            get(queries::get_new_queries),
+        )
+        .route(
+            "/workspaces/:workspace_id/queries/clusters",
+            get(queries::list_query_clusters),
+        )
+        .route(
+            "/workspaces/:workspace_id/queries/:fingerprint/ownership",
+            get(queries::get_ownership),
+        )
+        .route(
+            "/workspaces/:workspace_id/ownership",
+            get(queries::list_ownership),
+        )
+        .route("/workspaces/:workspace_id/mutes", get(queries::list_mutes))
+        .route("/workspaces/:workspace_id/tables", get(tables::list_tables))
+        // Error analytics
+        .route("/workspaces/:workspace_id/errors", get(errors::list_errors))
+        // Service registry and dashboards
+        .route(
+            "/workspaces/:workspace_id/services",
+            get(registry::list_services),
+        )
+        .route(
+            "/workspaces/:workspace_id/services/:service_id",
+            get(registry::get_service),
+        )
+        .route(
+            "/workspaces/:workspace_id/services/:service_id/overview",
+            get(overview::get_service_overview),
+        )
+        // Search
+        .route(
+            "/workspaces/:workspace_id/search/text",
+            get(search::search_text),
+        )
+        .route(
+            "/workspaces/:workspace_id/search/similar",
+            post(search::search_similar),
+        )
+        .route(
+            "/workspaces/:workspace_id/search/landscape",
+            get(search::get_landscape),
+        )
+        .route(
+            "/workspaces/:workspace_id/search/duplicates",
+            get(search::get_duplicates),
+        )
+        .route(
+            "/workspaces/:workspace_id/metrics/:metric_id/similar",
+            get(search::get_similar_to_metric),
+        )
+        // Anomalies
+        .route(
+            "/workspaces/:workspace_id/anomalies",
+            get(search::get_anomalies),
+        )
+        .route(
+            "/workspaces/:workspace_id/anomalies/throughput-drops",
+            get(search::get_throughput_drops),
+        )
+        .route(
+            "/workspaces/:workspace_id/anomalies/:anomaly_id/context",
+            get(search::get_anomaly_context),
+        )
+        // Alert rules
+        .route(
+            "/workspaces/:workspace_id/alert-rules",
+            get(alerts::list_alert_rules),
+        )
+        .route(
+            "/workspaces/:workspace_id/alert-rules/:rule_id",
+            get(alerts::get_alert_rule),
+        )
+        .route(
+            "/workspaces/:workspace_id/alert-deliveries",
+            get(destinations::list_alert_deliveries),
+        )
+        // SLOs
+        .route("/workspaces/:workspace_id/slo", get(slo::list_slos))
+        .route("/workspaces/:workspace_id/slo/:slo_id", get(slo::get_slo))
+        // Workspace settings
+        .route(
+            "/workspaces/:workspace_id/settings/json-casing",
+            get(settings::get_json_casing),
+        )
+        .route(
+            "/workspaces/:workspace_id/settings/quota",
+            get(settings::get_quota),
+        )
+        .route(
+            "/workspaces/:workspace_id/settings/redaction",
+            get(settings::get_redaction),
+        )
+        .route(
+            "/workspaces/:workspace_id/settings/lifecycle",
+            get(settings::get_lifecycle),
+        )
+        .route(
+            "/workspaces/:workspace_id/settings/anomaly-detection",
+            get(settings::get_anomaly_detection),
+        )
+        .route(
+            "/workspaces/:workspace_id/settings/anomaly",
+            get(settings::get_anomaly_settings),
+        )
+        // Usage metering
+        .route("/workspaces/:workspace_id/usage", get(usage::get_usage))
+        .route(
+            "/workspaces/:workspace_id/stats/ingestion",
+            get(stats::get_ingestion_stats),
+        )
+        .route(
+            "/workspaces/:workspace_id/stats/durations",
+            get(stats::get_duration_stats),
+        )
+        // WebSocket and Server-Sent Events streaming
+        .route("/workspaces/:workspace_id/ws", get(ws::ws_handler))
+        .route(
+            "/workspaces/:workspace_id/anomalies/ws",
+            get(ws::anomaly_ws_handler),
+        )
+        .route("/workspaces/:workspace_id/events", get(sse::metric_events))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_read,
+        ));
+
+    let admin = Router::new()
+        // Metrics
+        .route(
+            "/workspaces/:workspace_id/metrics",
+            delete(erasure::erase_metrics),
+        )
+        // Annotations
+        .route(
+            "/workspaces/:workspace_id/annotations",
+            post(annotations::create_annotation),
+        )
+        .route(
+            "/workspaces/:workspace_id/annotations/:annotation_id",
+            delete(annotations::delete_annotation),
+        )
+        // Anomalies
+        .route(
+            "/workspaces/:workspace_id/anomalies/:anomaly_id/ack",
+            post(search::acknowledge_anomaly),
+        )
+        .route(
+            "/workspaces/:workspace_id/anomalies/:anomaly_id/resolve",
+            post(search::resolve_anomaly),
+        )
+        // Query catalog
+        .route(
+            "/workspaces/:workspace_id/queries/:fingerprint/ownership",
+            put(queries::put_ownership).delete(queries::delete_ownership),
+        )
+        .route(
+            "/workspaces/:workspace_id/queries/:fingerprint/mute",
+            put(queries::put_mute).delete(queries::delete_mute),
+        )
+        // Service registry
+        .route(
+            "/workspaces/:workspace_id/services",
+            post(registry::create_service),
+        )
+        .route(
+            "/workspaces/:workspace_id/services/:service_id",
+            put(registry::update_service).delete(registry::delete_service),
+        )
+        // Alert rules
+        .route(
+            "/workspaces/:workspace_id/alert-rules",
+            post(alerts::create_alert_rule),
+        )
+        .route(
+            "/workspaces/:workspace_id/alert-rules/:rule_id",
+            put(alerts::update_alert_rule).delete(alerts::delete_alert_rule),
+        )
+        // Alert destinations
+        .route(
+            "/workspaces/:workspace_id/alert-destinations",
+            get(destinations::list_alert_destinations).post(destinations::create_alert_destination),
+        )
+        .route(
+            "/workspaces/:workspace_id/alert-destinations/:destination_id",
+            delete(destinations::delete_alert_destination),
+        )
+        .route(
+            "/workspaces/:workspace_id/alert-destinations/:destination_id/test",
+            post(destinations::test_alert_destination),
+        )
+        // SLOs
+        .route("/workspaces/:workspace_id/slo", post(slo::create_slo))
+        .route(
+            "/workspaces/:workspace_id/slo/:slo_id",
+            put(slo::update_slo).delete(slo::delete_slo),
+        )
+        // Workspace settings
+        .route(
+            "/workspaces/:workspace_id/settings/json-casing",
+            put(settings::put_json_casing),
+        )
+        .route(
+            "/workspaces/:workspace_id/settings/quota",
+            put(settings::put_quota),
+        )
+        .route(
+            "/workspaces/:workspace_id/settings/redaction",
+            put(settings::put_redaction),
+        )
+        .route(
+            "/workspaces/:workspace_id/settings/lifecycle",
+            put(settings::put_lifecycle),
+        )
+        .route(
+            "/workspaces/:workspace_id/settings/anomaly-detection",
+            put(settings::put_anomaly_detection),
+        )
+        .route(
+            "/workspaces/:workspace_id/settings/anomaly",
+            patch(settings::patch_anomaly_settings),
+        )
+        // API keys
+        .route(
+            "/workspaces/:workspace_id/api-keys",
+            get(api_keys::list_api_keys).post(api_keys::create_api_key),
+        )
+        .route(
+            "/workspaces/:workspace_id/api-keys/:key_id",
+            delete(api_keys::delete_api_key),
+        )
+        // Audit log
+        .route(
+            "/workspaces/:workspace_id/audit",
+            get(audit::list_audit_log),
+        )
+        // Dead-letter queue
+        .route("/admin/dead-letters", get(admin::list_dead_letters))
+        .route(
+            "/admin/dead-letters/reprocess",
+            post(admin::reprocess_dead_letters),
+        )
+        // Embeddings
+        .route("/admin/embed", post(admin::embed_text))
+        .route("/admin/embeddings/reembed", post(admin::reembed_embeddings))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_admin,
+        ));
+
+    read.merge(admin)
+}
+
+/// Browser beacon route under every API version
+///
+/// `sendBeacon` always sends credentials, which browsers reject with a
+/// wildcard origin, so this route mirrors the caller's origin instead of
+/// sharing the API's CORS layer. It also skips JSON casing: beacons are
+/// parsed from raw bytes.
+fn beacon_routes(state: AppState) -> Router {
+    let routes = Router::new()
+        .route("/beacon", post(beacon::collect_beacon))
+        .layer(DefaultBodyLimit::max(beacon::MAX_BEACON_BYTES))
+        .with_state(state);
+
+    Router::new()
+        .nest("/api/v1", routes.clone())
+        .nest("/api/v2", routes)
+        .layer(
+            CorsLayer::new()
+                .allow_origin(AllowOrigin::mirror_request())
+                .allow_credentials(true)
+                .allow_methods([Method::POST])
+                .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION])
+                .max_age(Duration::from_secs(86400)),
+        )
+}
+
+/// Ingestion route for one API version's handler
+fn ingest_route<H, T>(state: &AppState, handler: H) -> Router<AppState>
+where
+    H: axum::handler::Handler<T, AppState>,
+    T: 'static,
+{
+    Router::new()
+        .route("/metrics/ingest", post(handler))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_ingest,
+        ))
+}
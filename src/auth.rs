@@ -0,0 +1,274 @@
+//! API key authentication and scope checks
+//!
+//! Each route group is wrapped in one of the `require_*` middlewares, which
+//! verify the Bearer key, check its scope and workspace, and make the key
+//! available to handlers as an `Extension<ApiKey>`. Verified keys are cached
+//! briefly in a [`KeyCache`] so hot ingest paths don't query the database on
+//! every request.
+//!
+//! When a JWT verifier is configured, read routes also accept signed JWTs
+//! (see [`crate::services::jwt`]); a valid token is presented to handlers as
+//! a read-only `ApiKey` for the token's workspace, with a nil ID.
+
+use axum::{
+    extract::{OriginalUri, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::casing::path_workspace_id;
+use crate::error::{AppError, Result};
+use crate::models::{ApiKey, ApiScope};
+use crate::services::jwt::{self, JwtVerifier};
+use crate::services::keys::hash_secret;
+use crate::state::AppState;
+
+struct CachedKey {
+    key: ApiKey,
+    cached_at: Instant,
+    /// Value of [`KeyCache::clock`] when last read, for LRU eviction
+    last_used: AtomicU64,
+}
+
+/// Recently verified API keys, indexed by the SHA-256 of their secret
+///
+/// Entries live for `ttl`; once `capacity` is reached the least recently
+/// used entry is evicted. Revoking a key removes it from this process's cache
+/// immediately, while other replicas stop accepting it within `ttl`. A zero
+/// `ttl` or `capacity` disables caching.
+pub struct KeyCache {
+    entries: RwLock<HashMap<String, CachedKey>>,
+    ttl: Duration,
+    capacity: usize,
+    clock: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl KeyCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            ttl,
+            capacity,
+            clock: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    fn get(&self, key_hash: &str) -> Option<ApiKey> {
+        let found = self
+            .entries
+            .read()
+            .get(key_hash)
+            .filter(|entry| entry.cached_at.elapsed() < self.ttl)
+            .map(|entry| {
+                let tick = self.clock.fetch_add(1, Ordering::Relaxed);
+                entry.last_used.store(tick, Ordering::Relaxed);
+                entry.key.clone()
+            });
+        let counter = if found.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    fn insert(&self, key_hash: String, key: ApiKey) {
+        if self.ttl.is_zero() || self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.write();
+        if entries.len() >= self.capacity && !entries.contains_key(&key_hash) {
+            entries.retain(|_, entry| entry.cached_at.elapsed() < self.ttl);
+            if entries.len() >= self.capacity {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used.load(Ordering::Relaxed))
+                    .map(|(hash, _)| hash.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        let tick = self.clock.fetch_add(1, Ordering::Relaxed);
+        entries.insert(
+            key_hash,
+            CachedKey {
+                key,
+                cached_at: Instant::now(),
+                last_used: AtomicU64::new(tick),
+            },
+        );
+    }
+
+    /// Drop a key from the cache, e.g. right after it is revoked
+    pub fn invalidate(&self, key_id: Uuid) {
+        self.entries
+            .write()
+            .retain(|_, entry| entry.key.id != key_id);
+    }
+
+    /// `(hits, misses)` since startup
+    pub fn stats(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Extract Bearer token from Authorization header
+pub fn extract_bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+/// Middleware admitting keys with the `ingest` scope
+pub async fn require_ingest(state: State<AppState>, request: Request, next: Next) -> Response {
+    require_scope(state, request, next, ApiScope::Ingest).await
+}
+
+/// Middleware admitting keys with the `read` scope
+pub async fn require_read(state: State<AppState>, request: Request, next: Next) -> Response {
+    require_scope(state, request, next, ApiScope::Read).await
+}
+
+/// Middleware admitting keys with the `admin` scope
+pub async fn require_admin(state: State<AppState>, request: Request, next: Next) -> Response {
+    require_scope(state, request, next, ApiScope::Admin).await
+}
+
+async fn require_scope(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+    scope: ApiScope,
+) -> Response {
+    let token = extract_bearer_token(request.headers()).map(str::to_owned);
+    // Nested routers see the path without its /api/v{n} prefix
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or_else(|| request.uri().path(), |uri| uri.path())
+        .to_owned();
+
+    match authorize(&state, token.as_deref(), &path, scope).await {
+        Ok(key) => {
+            request.extensions_mut().insert(key);
+            next.run(request).await
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Verify a key and check it grants `scope` on the workspace in `path`, if any
+pub(crate) async fn authorize(
+    state: &AppState,
+    token: Option<&str>,
+    path: &str,
+    scope: ApiScope,
+) -> Result<ApiKey> {
+    let token =
+        token.ok_or_else(|| AppError::Unauthorized("Missing Authorization header".into()))?;
+    let key = match &state.jwt {
+        Some(verifier) if jwt::looks_like_jwt(token) => jwt_key(verifier, token)?,
+        _ => verify(state, token).await?,
+    };
+
+    if !key.has_scope(scope) {
+        return Err(AppError::Forbidden(format!(
+            "API key lacks the '{}' scope",
+            scope.as_str()
+        )));
+    }
+    if matches!(path_workspace_id(path), Some(workspace_id) if workspace_id != key.workspace_id) {
+        return Err(AppError::Forbidden(
+            "API key does not belong to this workspace".into(),
+        ));
+    }
+    Ok(key)
+}
+
+/// Verify a key through the cache, falling back to the store
+async fn verify(state: &AppState, token: &str) -> Result<ApiKey> {
+    let key_hash = hash_secret(token);
+    if let Some(key) = state.key_cache.get(&key_hash) {
+        // Keys can expire while cached
+        if key.is_expired(Utc::now()) {
+            state.key_cache.invalidate(key.id);
+            return Err(AppError::Unauthorized("API key has expired".into()));
+        }
+        return Ok(key);
+    }
+
+    let key = state.db.verify_api_key(token).await?;
+    state.key_cache.insert(key_hash, key.clone());
+    Ok(key)
+}
+
+/// Verify a JWT, returning a read-only key for its workspace
+fn jwt_key(verifier: &JwtVerifier, token: &str) -> Result<ApiKey> {
+    let claims = verifier.verify(token).map_err(AppError::Unauthorized)?;
+    Ok(ApiKey {
+        id: Uuid::nil(),
+        workspace_id: claims.workspace_id,
+        name: format!("jwt:{}", claims.sub.unwrap_or_default()),
+        scopes: vec![ApiScope::Read],
+        expires_at: DateTime::from_timestamp(claims.exp, 0),
+        created_at: Utc::now(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_key(name: &str) -> ApiKey {
+        ApiKey {
+            id: Uuid::new_v4(),
+            workspace_id: Uuid::new_v4(),
+            name: name.into(),
+            scopes: vec![ApiScope::Ingest],
+            expires_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_key_cache_evicts_least_recently_used() {
+        let cache = KeyCache::new(Duration::from_secs(60), 2);
+        let (a, b, c) = (api_key("a"), api_key("b"), api_key("c"));
+        cache.insert("a".into(), a.clone());
+        cache.insert("b".into(), b);
+        assert_eq!(cache.get("a").map(|k| k.id), Some(a.id));
+
+        // "b" is now the least recently used
+        cache.insert("c".into(), c.clone());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+
+        cache.invalidate(c.id);
+        assert!(cache.get("c").is_none());
+        assert_eq!(cache.stats(), (2, 2));
+    }
+
+    #[test]
+    fn test_key_cache_disabled_by_zero_ttl() {
+        let cache = KeyCache::new(Duration::ZERO, 100);
+        cache.insert("a".into(), api_key("a"));
+        assert!(cache.get("a").is_none());
+    }
+}
@@ -0,0 +1,291 @@
+//! JSON field-casing compatibility layer
+//!
+//! Handlers always speak `snake_case`. When a request asks for `camelCase`
+//! (via the `X-Json-Casing` header or its workspace's setting), the
+//! middleware rewrites JSON request bodies to `snake_case` before the handler
+//! sees them and rewrites JSON responses to `camelCase` on the way out.
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use parking_lot::RwLock;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::models::JsonCasing;
+use crate::state::AppState;
+
+/// Request header selecting the casing for one request
+pub const CASING_HEADER: &str = "x-json-casing";
+
+/// Largest body the middleware will buffer for rewriting
+const MAX_BODY_BYTES: usize = 32 * 1024 * 1024;
+
+/// Short-lived cache of workspace casing settings, so the setting isn't
+/// looked up on every request
+pub struct CasingCache {
+    entries: RwLock<HashMap<Uuid, (JsonCasing, Instant)>>,
+    ttl: Duration,
+}
+
+impl CasingCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            ttl,
+        }
+    }
+
+    fn get(&self, workspace_id: Uuid) -> Option<JsonCasing> {
+        self.entries
+            .read()
+            .get(&workspace_id)
+            .filter(|(_, cached_at)| cached_at.elapsed() < self.ttl)
+            .map(|(casing, _)| *casing)
+    }
+
+    /// Record a workspace's casing, e.g. right after it changes
+    pub fn insert(&self, workspace_id: Uuid, casing: JsonCasing) {
+        self.entries
+            .write()
+            .insert(workspace_id, (casing, Instant::now()));
+    }
+}
+
+/// Middleware applying the requested JSON casing to request and response bodies
+pub async fn json_casing(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let header = request.headers().get(CASING_HEADER).cloned();
+    let workspace_id = path_workspace_id(request.uri().path());
+    let casing = match resolve_casing(&state, header, workspace_id).await {
+        Ok(casing) => casing,
+        Err(e) => return e.into_response(),
+    };
+    if casing == JsonCasing::Snake {
+        return next.run(request).await;
+    }
+
+    let request = match rewrite_request(request).await {
+        Ok(request) => request,
+        Err(e) => return e.into_response(),
+    };
+    let response = next.run(request).await;
+    match rewrite_response(response).await {
+        Ok(response) => response,
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Casing from the request header, else the workspace setting, else `snake_case`
+async fn resolve_casing(
+    state: &AppState,
+    header: Option<HeaderValue>,
+    workspace_id: Option<Uuid>,
+) -> Result<JsonCasing, AppError> {
+    if let Some(value) = header {
+        return value
+            .to_str()
+            .map_err(|_| AppError::InvalidRequest("Invalid X-Json-Casing header".into()))?
+            .parse()
+            .map_err(AppError::InvalidRequest);
+    }
+
+    let Some(workspace_id) = workspace_id else {
+        return Ok(JsonCasing::default());
+    };
+    if let Some(casing) = state.casing_cache.get(workspace_id) {
+        return Ok(casing);
+    }
+    let casing = match state.db.get_json_casing(workspace_id).await {
+        Ok(casing) => casing.unwrap_or_default(),
+        Err(e) => {
+            // Fall back to the historical format rather than failing the request
+            warn!(error = %e, workspace_id = %workspace_id, "Failed to load JSON casing setting");
+            return Ok(JsonCasing::default());
+        }
+    };
+    state.casing_cache.insert(workspace_id, casing);
+    Ok(casing)
+}
+
+/// Workspace ID from a `/api/v{n}/workspaces/{workspace_id}/...` path
+pub(crate) fn path_workspace_id(path: &str) -> Option<Uuid> {
+    let mut segments = path.strip_prefix("/api/")?.split('/').skip(1);
+    if segments.next()? != "workspaces" {
+        return None;
+    }
+    segments.next()?.parse().ok()
+}
+
+/// Rename every object key in a JSON body
+///
+/// Returns the new body and its length, or the original body and `None` when
+/// it is not JSON.
+async fn rewrite_json(
+    headers: &HeaderMap,
+    body: Body,
+    rename: fn(&str) -> String,
+) -> Result<(Body, Option<usize>), AppError> {
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !is_json {
+        return Ok((body, None));
+    }
+
+    let bytes = to_bytes(body, MAX_BODY_BYTES)
+        .await
+        .map_err(|e| AppError::InvalidRequest(format!("Failed to read body: {}", e)))?;
+    let Ok(value) = serde_json::from_slice::<Value>(&bytes) else {
+        // Let the handler report malformed JSON as usual
+        return Ok((Body::from(bytes), None));
+    };
+    let bytes = serde_json::to_vec(&rename_keys(value, rename))
+        .map_err(|e| AppError::InternalError(format!("Failed to encode body: {}", e)))?;
+    let len = bytes.len();
+    Ok((Body::from(bytes), Some(len)))
+}
+
+/// Rewrite a request body to `snake_case` keys
+async fn rewrite_request(request: Request) -> Result<Request, AppError> {
+    let (mut parts, body) = request.into_parts();
+    let (body, len) = rewrite_json(&parts.headers, body, to_snake_case).await?;
+    if let Some(len) = len {
+        parts
+            .headers
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+    }
+    Ok(Request::from_parts(parts, body))
+}
+
+/// Rewrite a response body to `camelCase` keys
+async fn rewrite_response(response: Response) -> Result<Response, AppError> {
+    let (mut parts, body) = response.into_parts();
+    let (body, len) = rewrite_json(&parts.headers, body, to_camel_case).await?;
+    if let Some(len) = len {
+        parts
+            .headers
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+    }
+    Ok(Response::from_parts(parts, body))
+}
+
+/// Fields holding user-defined keys (metric labels), which are never renamed
+const VERBATIM_FIELDS: &[&str] = &["labels"];
+
+/// Recursively rename the keys of every object in a JSON value
+pub fn rename_keys(value: Value, rename: fn(&str) -> String) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| {
+                    let value = if VERBATIM_FIELDS.contains(&key.as_str()) {
+                        value
+                    } else {
+                        rename_keys(value, rename)
+                    };
+                    (rename(&key), value)
+                })
+                .collect::<Map<_, _>>(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| rename_keys(item, rename))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// `p95_duration_ms` -> `p95DurationMs`
+pub fn to_camel_case(key: &str) -> String {
+    let mut out = String::with_capacity(key.len());
+    let mut upper_next = false;
+    for c in key.chars() {
+        if c == '_' && !out.is_empty() {
+            upper_next = true;
+        } else if upper_next {
+            out.extend(c.to_uppercase());
+            upper_next = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// `p95DurationMs` -> `p95_duration_ms`
+pub fn to_snake_case(key: &str) -> String {
+    let mut out = String::with_capacity(key.len() + 4);
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            if !out.is_empty() {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_case_conversion_round_trips() {
+        for key in [
+            "workspace_id",
+            "p95_duration_ms",
+            "count",
+            "owner_ticket_links",
+        ] {
+            assert_eq!(to_snake_case(&to_camel_case(key)), key);
+        }
+        assert_eq!(to_camel_case("p95_duration_ms"), "p95DurationMs");
+        assert_eq!(to_snake_case("startedAt"), "started_at");
+    }
+
+    #[test]
+    fn test_rename_keys_is_recursive_and_leaves_values() {
+        let value = json!({
+            "workspace_id": "a_b",
+            "buckets": [{ "query_count": 1, "tags": ["snake_value"] }],
+            "metrics": [{ "duration_ms": 1, "labels": { "build_id": "x" } }],
+        });
+        assert_eq!(
+            rename_keys(value, to_camel_case),
+            json!({
+                "workspaceId": "a_b",
+                "buckets": [{ "queryCount": 1, "tags": ["snake_value"] }],
+                "metrics": [{ "durationMs": 1, "labels": { "build_id": "x" } }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_path_workspace_id() {
+        let id = Uuid::new_v4();
+        assert_eq!(
+            path_workspace_id(&format!("/api/v1/workspaces/{}/aggregations", id)),
+            Some(id)
+        );
+        assert_eq!(
+            path_workspace_id(&format!("/api/v2/workspaces/{}", id)),
+            Some(id)
+        );
+        assert_eq!(path_workspace_id("/api/v1/metrics/ingest"), None);
+    }
+}
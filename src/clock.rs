@@ -0,0 +1,188 @@
+//! Wall clock and timers that tests can stop and move
+//!
+//! Background tasks wait on [`Clock::interval`] and [`Clock::sleep`] and read
+//! the time from [`Clock::now`] instead of calling tokio and `Utc::now()`
+//! directly, and the in-memory backend stamps rows and applies cutoffs with
+//! it. In production this is the system clock. A simulated clock only moves
+//! when `Clock::advance` or `Clock::set` is called, waking every timer
+//! that came due, so tests can check intervals, retries, and retention
+//! cutoffs without sleeping.
+
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+#[cfg(any(test, feature = "testkit"))]
+use {chrono::TimeDelta, std::sync::Arc, tokio::sync::watch};
+
+/// The system clock, or a simulated time shared by every clone
+///
+/// Only tests and the `testkit` feature build the simulated clock.
+#[derive(Clone, Default)]
+pub enum Clock {
+    #[default]
+    System,
+    #[cfg(any(test, feature = "testkit"))]
+    Simulated(Arc<watch::Sender<DateTime<Utc>>>),
+}
+
+impl Clock {
+    /// A clock stopped at `at` until set or advanced
+    #[cfg(any(test, feature = "testkit"))]
+    pub fn simulated(at: DateTime<Utc>) -> Self {
+        Self::Simulated(Arc::new(watch::Sender::new(at)))
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        match self {
+            Self::System => Utc::now(),
+            #[cfg(any(test, feature = "testkit"))]
+            Self::Simulated(now) => *now.borrow(),
+        }
+    }
+
+    /// Move a simulated clock to `at`; the system clock ignores this
+    #[cfg(any(test, feature = "testkit"))]
+    pub fn set(&self, at: DateTime<Utc>) {
+        if let Self::Simulated(now) = self {
+            now.send_replace(at);
+        }
+    }
+
+    /// Move a simulated clock forward by `by`; the system clock ignores this
+    #[cfg(any(test, feature = "testkit"))]
+    pub fn advance(&self, by: TimeDelta) {
+        if let Self::Simulated(now) = self {
+            now.send_modify(|now| *now += by);
+        }
+    }
+
+    /// Wait until `duration` has passed on this clock
+    pub async fn sleep(&self, duration: Duration) {
+        match self {
+            Self::System => tokio::time::sleep(duration).await,
+            #[cfg(any(test, feature = "testkit"))]
+            Self::Simulated(now) => {
+                let deadline = *now.borrow() + delta(duration);
+                wait_until(now, deadline).await;
+            }
+        }
+    }
+
+    /// Ticks every `period`, the first immediately
+    ///
+    /// Like tokio's interval, ticks missed while the task was busy, or
+    /// skipped over by advancing a simulated clock, fire back to back.
+    pub fn interval(&self, period: Duration) -> Interval {
+        match self {
+            Self::System => Interval::System(tokio::time::interval(period)),
+            #[cfg(any(test, feature = "testkit"))]
+            Self::Simulated(now) => Interval::Simulated {
+                next: *now.borrow(),
+                period: delta(period),
+                now: Arc::clone(now),
+            },
+        }
+    }
+}
+
+/// Periodic ticks from [`Clock::interval`]
+pub enum Interval {
+    System(tokio::time::Interval),
+    #[cfg(any(test, feature = "testkit"))]
+    Simulated {
+        now: Arc<watch::Sender<DateTime<Utc>>>,
+        period: TimeDelta,
+        next: DateTime<Utc>,
+    },
+}
+
+impl Interval {
+    /// Wait for the next tick
+    pub async fn tick(&mut self) {
+        match self {
+            Self::System(interval) => {
+                interval.tick().await;
+            }
+            #[cfg(any(test, feature = "testkit"))]
+            Self::Simulated { now, period, next } => {
+                wait_until(now, *next).await;
+                *next += *period;
+            }
+        }
+    }
+}
+
+#[cfg(any(test, feature = "testkit"))]
+fn delta(duration: Duration) -> TimeDelta {
+    TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX)
+}
+
+#[cfg(any(test, feature = "testkit"))]
+async fn wait_until(now: &watch::Sender<DateTime<Utc>>, deadline: DateTime<Utc>) {
+    // The sender outlives the receiver, so waiting can't fail
+    let _ = now.subscribe().wait_for(|now| *now >= deadline).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Let spawned tasks run until they wait on the clock again
+    async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[test]
+    fn test_simulated_clock_is_shared_by_clones() {
+        let start: DateTime<Utc> = "2026-01-01T00:00:00Z".parse().unwrap();
+        let clock = Clock::simulated(start);
+        let other = clock.clone();
+
+        other.advance(TimeDelta::minutes(5));
+        assert_eq!(clock.now(), start + TimeDelta::minutes(5));
+        clock.set(start);
+        assert_eq!(other.now(), start);
+
+        let system = Clock::System;
+        system.set(start);
+        assert!(system.now() > start);
+    }
+
+    #[tokio::test]
+    async fn test_timers_fire_when_advanced() {
+        let clock = Clock::simulated("2026-01-01T00:00:00Z".parse().unwrap());
+        let mut interval = clock.interval(Duration::from_secs(60));
+        let ticks = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&ticks);
+        let task = tokio::spawn(async move {
+            loop {
+                interval.tick().await;
+                counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        });
+        let count = || async {
+            settle().await;
+            ticks.load(std::sync::atomic::Ordering::SeqCst)
+        };
+
+        assert_eq!(count().await, 1);
+        clock.advance(TimeDelta::seconds(59));
+        assert_eq!(count().await, 1);
+        clock.advance(TimeDelta::seconds(1));
+        assert_eq!(count().await, 2);
+        // Skipped ticks catch up
+        clock.advance(TimeDelta::minutes(3));
+        assert_eq!(count().await, 5);
+        task.abort();
+
+        let sleeper = tokio::spawn({
+            let clock = clock.clone();
+            async move { clock.sleep(Duration::from_secs(30)).await }
+        });
+        settle().await;
+        assert!(!sleeper.is_finished());
+        clock.advance(TimeDelta::seconds(30));
+        sleeper.await.unwrap();
+    }
+}
@@ -1,527 +1,5488 @@
 //! Database access layer with SQLx and PostgreSQL/TimescaleDB
 
-use crate::error::{AppError, Result};
-use crate::models::{QueryMetric, QueryStatus, Workspace};
-use chrono::{DateTime, Utc};
-use sqlx::postgres::{PgPool, PgPoolOptions};
-use sqlx::Row;
+use crate::clock::Clock;
+use crate::error::{is_schema_drift, AppError, Result};
+use crate::models::{
+    AlertCondition, AlertDelivery, AlertDestination, AlertRule, Annotation, AnnotationKind,
+    AnomalyMethod, AnomalyOverrides, AnomalyStatus, ApiKey, AuditAction, AuditEntry, JsonCasing,
+    LifecycleAction, LifecycleStage, QueryKind, QueryMetric, QueryMute, QueryOwnership,
+    QueryStatus, Seasonality, Service, Slo,
+};
+use crate::services::alerting::RuleState;
+use crate::services::buckets::{utc_aligned, whole_hour_offsets, window_width};
+use crate::services::error_fingerprint::{metric_error_fingerprint, normalize_error};
+use crate::services::fingerprint::{
+    fingerprint, metric_fingerprint, DialectConfig, FINGERPRINT_VERSION,
+};
+use crate::services::hybrid_search;
+use crate::services::keys::{hash_matches, hash_secret};
+use crate::services::secrets::{self, SecretBox};
+use crate::services::severity::SeverityLevel;
+use crate::services::slo::{BurnState, SloCounts};
+use crate::services::usage;
+use crate::store::cache::AggregationCache;
+use crate::store::{MetricsStore, PoolStats};
+use async_trait::async_trait;
+use chrono::{DateTime, DurationRound, NaiveDate, TimeDelta, Utc};
+use chrono_tz::Tz;
+use sqlx::postgres::{PgConnectOptions, PgConnection, PgPool, PgPoolOptions, PgRow};
+use sqlx::types::Json;
+use sqlx::{Acquire, Row};
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
+/// How long raw metrics are kept, by the TimescaleDB retention policy and
+/// the retention task
+pub const RAW_METRICS_RETENTION_DAYS: i64 = 30;
+
+/// A `created_at` range of `query_metrics` a read may scan
+///
+/// Made by [`Database::raw_range`], which never starts it before the
+/// retention edge on the database's clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RawRange {
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+}
+
+/// A statement reading raw metrics, which it can only do through
+/// [`RawRange`]s
+///
+/// The statement names the table as `{raw}` (always with an alias). Each
+/// [`RawRead::range`] replaces the next one with the rows of `query_metrics`
+/// inside a range, whose bounds become parameters after the statement's own
+/// and are bound by [`RawRead::bind`]. Every read is therefore bounded on
+/// `created_at`, and TimescaleDB excludes the chunks outside it instead of
+/// scanning whatever history hasn't been dropped yet.
+struct RawRead {
+    sql: String,
+    /// Parameters of the statement itself
+    params: usize,
+    bounds: Vec<DateTime<Utc>>,
+}
+
+impl RawRead {
+    fn new(sql: &str) -> Self {
+        Self {
+            sql: sql.to_string(),
+            params: max_param(sql),
+            bounds: Vec::new(),
+        }
+    }
+
+    /// Read the next `{raw}` from `range`
+    fn range(mut self, range: RawRange) -> Self {
+        let first = self.params + self.bounds.len() + 1;
+        let table = format!(
+            "(SELECT * FROM query_metrics WHERE created_at >= ${} AND created_at < ${})",
+            first,
+            first + 1
+        );
+        assert!(
+            self.sql.contains(RAW_TABLE),
+            "no `{RAW_TABLE}` left to read"
+        );
+        self.sql = self.sql.replacen(RAW_TABLE, &table, 1);
+        self.bounds.extend([range.from, range.to]);
+        self
+    }
+
+    fn sql(&self) -> &str {
+        assert!(
+            !self.sql.contains(RAW_TABLE),
+            "`{RAW_TABLE}` read without a range"
+        );
+        &self.sql
+    }
+
+    /// Bind the ranges, after the statement's own parameters
+    fn bind<'q>(&self, query: PgQuery<'q>) -> PgQuery<'q> {
+        self.bounds
+            .iter()
+            .fold(query, |query, bound| query.bind(*bound))
+    }
+}
+
+type PgQuery<'q> = sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>;
+
+/// Placeholder for `query_metrics` in a [`RawRead`]
+const RAW_TABLE: &str = "{raw}";
+
+/// Highest `$n` parameter in `sql`
+fn max_param(sql: &str) -> usize {
+    sql.split('$')
+        .skip(1)
+        .filter_map(|rest| {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            rest[..digits].parse().ok()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Advisory lock key held while the similarity search index is rebuilt
+const VECTOR_INDEX_LOCK: i64 = 0x7176_7665_6374_6f72;
+
+/// Advisory lock key held while stored fingerprints are re-keyed
+const FINGERPRINT_REKEY_LOCK: i64 = 0x7176_6670_7265_6b79;
+
+/// Advisory lock key held while workspace-wide rollups are rebuilt per service
+const ROLLUP_BACKFILL_LOCK: i64 = 0x7176_726f_6c6c_7570;
+
+/// Days of rollups [`Database::backfill_rollup_services`] rebuilds: those
+/// every lifecycle policy keeps the raw metrics of
+const ROLLUP_BACKFILL_DAYS: i64 = 7;
+
+/// Connection pool settings
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Maximum number of open connections
+    pub max_connections: u32,
+    /// Connections kept open even when idle
+    pub min_connections: u32,
+    /// How long to wait for a free connection before failing
+    pub acquire_timeout: Duration,
+    /// How long an idle connection is kept before being closed
+    pub idle_timeout: Duration,
+    /// Server-side `statement_timeout` for every connection (`None` = no limit)
+    pub statement_timeout: Option<Duration>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 50,
+            min_connections: 5,
+            acquire_timeout: Duration::from_secs(5),
+            idle_timeout: Duration::from_secs(600),
+            statement_timeout: None,
+        }
+    }
+}
+
+/// pgvector index access method for `query_embeddings.embedding`, as named
+/// in `EMBEDDING_INDEX`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VectorIndexKind {
+    /// Graph index; best recall for the latency, needs no training data
+    #[default]
+    Hnsw,
+    /// Inverted lists over k-means centroids; faster to build and smaller,
+    /// but recall depends on the data the lists were built from
+    IvfFlat,
+}
+
+impl VectorIndexKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VectorIndexKind::Hnsw => "hnsw",
+            VectorIndexKind::IvfFlat => "ivfflat",
+        }
+    }
+}
+
+impl FromStr for VectorIndexKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "hnsw" => Ok(VectorIndexKind::Hnsw),
+            "ivfflat" => Ok(VectorIndexKind::IvfFlat),
+            other => Err(format!("Unknown vector index: {}", other)),
+        }
+    }
+}
+
+/// Approximate nearest-neighbor index settings for similarity search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VectorIndexConfig {
+    pub kind: VectorIndexKind,
+    /// HNSW links per node (`EMBEDDING_HNSW_M`)
+    pub m: u32,
+    /// HNSW candidate list size while building (`EMBEDDING_HNSW_EF_CONSTRUCTION`)
+    pub ef_construction: u32,
+    /// IVFFlat list count (`EMBEDDING_IVFFLAT_LISTS`); about rows / 1000 up
+    /// to a million rows, `sqrt(rows)` beyond
+    pub lists: u32,
+}
+
+impl Default for VectorIndexConfig {
+    fn default() -> Self {
+        // pgvector's own HNSW defaults
+        Self {
+            kind: VectorIndexKind::Hnsw,
+            m: 16,
+            ef_construction: 64,
+            lists: 100,
+        }
+    }
+}
+
+impl VectorIndexConfig {
+    /// Storage parameters of the index, as `pg_class.reloptions` lists them
+    fn options(&self) -> Vec<String> {
+        match self.kind {
+            VectorIndexKind::Hnsw => vec![
+                format!("m={}", self.m),
+                format!("ef_construction={}", self.ef_construction),
+            ],
+            VectorIndexKind::IvfFlat => vec![format!("lists={}", self.lists)],
+        }
+    }
+}
+
+/// Per-search accuracy settings of the nearest-neighbor index
+///
+/// Unset values keep pgvector's defaults (`hnsw.ef_search = 40`,
+/// `ivfflat.probes = 1`). Raising them trades latency for recall; each only
+/// affects the index kind it belongs to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AnnTuning {
+    /// HNSW candidate list size; also caps how many rows a search can return
+    pub ef_search: Option<u32>,
+    /// IVFFlat lists scanned
+    pub probes: Option<u32>,
+}
+
 /// Database connection pool and operations
 #[derive(Clone)]
 pub struct Database {
     pool: PgPool,
+    /// Pool for read-heavy analytics queries; the primary pool unless a replica is configured
+    read_pool: PgPool,
+    has_replica: bool,
+    /// Seals integration secrets at rest (`None` = stored in plaintext)
+    secrets: Option<SecretBox>,
+    /// Caches aggregation results (`None` = every request queries the views)
+    aggregation_cache: Option<Arc<AggregationCache>>,
+    /// Ends ranges of raw metrics and places the retention edge
+    clock: Clock,
 }
 
 impl Database {
     /// Create a new database connection pool
-    pub async fn new(connection_string: &str) -> Result<Self> {
-        let pool = PgPoolOptions::new()
-            .max_connections(50)
-            .min_connections(5)
-            .acquire_timeout(Duration::from_secs(5))
-            .idle_timeout(Duration::from_secs(600))
-            .connect(connection_string)
+    pub async fn new(connection_string: &str, config: &PoolConfig) -> Result<Self> {
+        let pool = connect_pool(connection_string, config).await?;
+        info!(
+            max_connections = config.max_connections,
+            min_connections = config.min_connections,
+            "Database connection pool established"
+        );
+        Ok(Self {
+            read_pool: pool.clone(),
+            pool,
+            has_replica: false,
+            secrets: None,
+            aggregation_cache: None,
+            clock: Clock::System,
+        })
+    }
+
+    /// Place the retention edge, and end ranges of raw metrics read up to
+    /// now, on `clock`
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Earliest `created_at` raw metrics are still kept from
+    fn raw_cutoff(&self) -> DateTime<Utc> {
+        self.clock.now() - TimeDelta::days(RAW_METRICS_RETENTION_DAYS)
+    }
+
+    /// Raw metrics created within `from..to` that are still kept
+    fn raw_range(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> RawRange {
+        RawRange {
+            from: from.max(self.raw_cutoff()),
+            to,
+        }
+    }
+
+    /// Raw metrics created since `from` that are still kept
+    fn raw_since(&self, from: DateTime<Utc>) -> RawRange {
+        self.raw_range(from, self.clock.now())
+    }
+
+    /// Every raw metric still kept
+    fn raw_retained(&self) -> RawRange {
+        self.raw_since(self.raw_cutoff())
+    }
+
+    /// Whole hours of rollups to rebuild from `since` through the current
+    /// one: from the hour holding it, or from the first one whose raw
+    /// metrics are all still kept
+    fn rollup_range(&self, since: DateTime<Utc>) -> RawRange {
+        let hour = TimeDelta::hours(1);
+        let floor = |at: DateTime<Utc>| at.duration_trunc(hour).unwrap_or(at);
+        let cutoff = self.raw_cutoff();
+        let from = if floor(since) >= cutoff {
+            floor(since)
+        } else if floor(cutoff) == cutoff {
+            cutoff
+        } else {
+            floor(cutoff) + hour
+        };
+        RawRange {
+            from,
+            to: floor(self.clock.now()) + hour,
+        }
+    }
+
+    /// Encrypt integration secrets, such as alert destination URLs, under a
+    /// master key
+    pub fn with_secrets(mut self, secrets: SecretBox) -> Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
+    /// Serve repeated aggregation queries from a short-lived cache
+    pub fn with_aggregation_cache(mut self, cache: AggregationCache) -> Self {
+        self.aggregation_cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Route analytics reads (aggregations, recent metrics, similarity search,
+    /// service overviews) to a read replica; writes stay on the primary
+    pub async fn with_read_replica(
+        mut self,
+        connection_string: &str,
+        config: &PoolConfig,
+    ) -> Result<Self> {
+        self.read_pool = connect_pool(connection_string, config).await?;
+        self.has_replica = true;
+        info!(
+            max_connections = config.max_connections,
+            "Read replica connection pool established"
+        );
+        Ok(self)
+    }
+
+    /// Apply embedded schema migrations from `migrations/`
+    ///
+    /// Safe to call from multiple replicas at once: the migrator holds an
+    /// advisory lock while it runs.
+    pub async fn migrate(&self) -> Result<()> {
+        sqlx::migrate!("./migrations")
+            .run(&self.pool)
             .await
-            .map_err(|e| AppError::DatabaseError(format!("Failed to connect: {}", e)))?;
+            .map_err(|e| AppError::DatabaseError(format!("Migration failed: {}", e)))?;
 
-        info!("Database connection pool established");
-        Ok(Self { pool })
+        info!("Database migrations applied");
+        Ok(())
     }
 
-    /// Get the underlying connection pool
-    pub fn pool(&self) -> &PgPool {
-        &self.pool
+    /// Rebuild the similarity search index when its kind or build parameters
+    /// differ from `config`, returning whether it was rebuilt
+    ///
+    /// The new index is built concurrently beside the old one, so searches
+    /// and embedding writes carry on while it builds, which can take minutes
+    /// on a large table. When several replicas start at once, only the one
+    /// holding the advisory lock rebuilds; the others keep the current index.
+    pub async fn ensure_vector_index(&self, config: &VectorIndexConfig) -> Result<bool> {
+        // A connection of its own: the lock and the lifted statement timeout
+        // are session state, and closing it releases both
+        let mut conn = self.pool.acquire().await?.detach();
+
+        let current = sqlx::query(
+            r#"
+            SELECT am.amname, COALESCE(c.reloptions, '{}') AS options
+            FROM pg_class c
+            JOIN pg_am am ON am.oid = c.relam
+            WHERE c.oid = to_regclass('idx_query_embeddings_vector')
+            "#,
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+        if let Some(row) = &current {
+            let kind: String = row.get("amname");
+            let options: Vec<String> = row.get("options");
+            if kind == config.kind.as_str() && options == config.options() {
+                return Ok(false);
+            }
+        }
+
+        let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(VECTOR_INDEX_LOCK)
+            .fetch_one(&mut conn)
+            .await?;
+        if !locked {
+            info!("Another instance is rebuilding the similarity search index");
+            return Ok(false);
+        }
+
+        info!(
+            index = config.kind.as_str(),
+            options = %config.options().join(", "),
+            "Rebuilding the similarity search index"
+        );
+        // Values are integers and the kind is one of two names, so nothing
+        // user-supplied reaches the DDL
+        let statements = [
+            "SET statement_timeout = 0".to_string(),
+            // Left invalid by a build that was interrupted
+            "DROP INDEX CONCURRENTLY IF EXISTS idx_query_embeddings_vector_new".to_string(),
+            format!(
+                "CREATE INDEX CONCURRENTLY idx_query_embeddings_vector_new \
+                 ON query_embeddings USING {} (embedding vector_cosine_ops) WITH ({})",
+                config.kind.as_str(),
+                config.options().join(", "),
+            ),
+            "DROP INDEX CONCURRENTLY IF EXISTS idx_query_embeddings_vector".to_string(),
+            "ALTER INDEX idx_query_embeddings_vector_new RENAME TO idx_query_embeddings_vector"
+                .to_string(),
+        ];
+        for statement in &statements {
+            sqlx::query(statement).execute(&mut conn).await?;
+        }
+
+        info!(
+            index = config.kind.as_str(),
+            "Similarity search index rebuilt"
+        );
+        Ok(true)
     }
 
-    /// Verify an API key and return the associated workspace
-    pub async fn verify_api_key(&self, api_key: &str) -> Result<Workspace> {
-        let row = sqlx::query(
+    /// Move everything keyed by a fingerprint from an older version of the
+    /// algorithm to the current one, returning how many fingerprints moved
+    ///
+    /// Each catalogued fingerprint is recomputed from its representative
+    /// text, with the dialect of a service that recently ran it. Those that
+    /// change are re-keyed one workspace per transaction: catalog entries
+    /// and hourly rollups that now share a key are merged (a merged bucket's
+    /// percentiles are the largest of the merged ones), ownership and mutes move over
+    /// unless the new key already has its own, and raw, archived, and
+    /// anomalous metrics are relabeled. An interrupted run picks up where it
+    /// stopped, as re-keyed entries recompute to themselves. Only the
+    /// instance holding the advisory lock re-keys.
+    pub async fn rekey_fingerprints(&self, dialects: &DialectConfig) -> Result<u64> {
+        let done: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM fingerprint_versions WHERE version = $1)",
+        )
+        .bind(FINGERPRINT_VERSION)
+        .fetch_one(&self.pool)
+        .await?;
+        if done {
+            return Ok(0);
+        }
+
+        // A connection of its own, so closing it releases the lock
+        let mut conn = self.pool.acquire().await?.detach();
+        let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(FINGERPRINT_REKEY_LOCK)
+            .fetch_one(&mut conn)
+            .await?;
+        if !locked {
+            info!("Another instance is re-keying query fingerprints");
+            return Ok(0);
+        }
+        // Relabeling a month of raw metrics can outlast the pool's timeout
+        sqlx::query("SET statement_timeout = 0")
+            .execute(&mut conn)
+            .await?;
+
+        let read = RawRead::new(
             r#"
-            SELECT id, name, api_key, created_at, updated_at
-            FROM workspaces
-            WHERE api_key = $1
+            SELECT c.workspace_id, c.fingerprint, c.query_text,
+                (
+                    SELECT m.service_id FROM {raw} m
+                    WHERE m.workspace_id = c.workspace_id
+                        AND m.fingerprint = c.fingerprint
+                    LIMIT 1
+                ) AS service_id
+            FROM query_catalog c
+            "#,
+        )
+        .range(self.raw_retained());
+        let rows = read
+            .bind(sqlx::query(read.sql()))
+            .fetch_all(&mut conn)
+            .await?;
+
+        let mut moves: BTreeMap<Uuid, (Vec<String>, Vec<String>)> = BTreeMap::new();
+        for row in &rows {
+            let old: String = row.get("fingerprint");
+            let service_id: Option<Uuid> = row.get("service_id");
+            let text: String = row.get("query_text");
+            let new = match service_id {
+                Some(service_id) => dialects.fingerprint(service_id, &text),
+                None => fingerprint(&text, dialects.default),
+            };
+            if new != old {
+                let (olds, news) = moves.entry(row.get("workspace_id")).or_default();
+                olds.push(old);
+                news.push(new);
+            }
+        }
+
+        info!(
+            version = FINGERPRINT_VERSION,
+            workspaces = moves.len(),
+            "Re-keying query fingerprints"
+        );
+        let mut rekeyed = 0;
+        for (workspace_id, (olds, news)) in &moves {
+            let mut tx = conn.begin().await?;
+            sqlx::query(
+                r#"
+                CREATE TEMPORARY TABLE rekeyed (old_fingerprint TEXT, new_fingerprint TEXT)
+                ON COMMIT DROP
+                "#,
+            )
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query("INSERT INTO rekeyed SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[])")
+                .bind(olds)
+                .bind(news)
+                .execute(&mut *tx)
+                .await?;
+
+            let statements = [
+                r#"
+                INSERT INTO query_catalog (
+                    workspace_id, fingerprint, query_text, first_seen, last_seen, total_count
+                )
+                SELECT $1, r.new_fingerprint, MIN(c.query_text), MIN(c.first_seen), MAX(c.last_seen),
+                    SUM(c.total_count)
+                FROM query_catalog c JOIN rekeyed r ON r.old_fingerprint = c.fingerprint
+                WHERE c.workspace_id = $1
+                GROUP BY r.new_fingerprint
+                ON CONFLICT (workspace_id, fingerprint) DO UPDATE SET
+                    first_seen = LEAST(query_catalog.first_seen, EXCLUDED.first_seen),
+                    last_seen = GREATEST(query_catalog.last_seen, EXCLUDED.last_seen),
+                    total_count = query_catalog.total_count + EXCLUDED.total_count
+                "#,
+                r#"
+                INSERT INTO fingerprint_rollups (
+                    workspace_id, service_id, fingerprint, bucket, query_text,
+                    call_count, total_duration_ms, min_duration_ms, max_duration_ms,
+                    p50_duration_ms, p95_duration_ms, p99_duration_ms,
+                    failed_count, error_count, total_rows_affected, first_seen, last_seen,
+                    query_kind
+                )
+                SELECT $1, f.service_id, r.new_fingerprint, f.bucket, MIN(f.query_text),
+                    SUM(f.call_count)::BIGINT, SUM(f.total_duration_ms)::BIGINT,
+                    MIN(f.min_duration_ms), MAX(f.max_duration_ms), MAX(f.p50_duration_ms),
+                    MAX(f.p95_duration_ms), MAX(f.p99_duration_ms),
+                    SUM(f.failed_count)::BIGINT, SUM(f.error_count)::BIGINT,
+                    SUM(f.total_rows_affected)::BIGINT, MIN(f.first_seen), MAX(f.last_seen),
+                    MAX(f.query_kind)
+                FROM fingerprint_rollups f JOIN rekeyed r ON r.old_fingerprint = f.fingerprint
+                WHERE f.workspace_id = $1
+                GROUP BY f.service_id, r.new_fingerprint, f.bucket
+                ON CONFLICT (workspace_id, service_id, fingerprint, bucket) DO UPDATE SET
+                    call_count = fingerprint_rollups.call_count + EXCLUDED.call_count,
+                    total_duration_ms =
+                        fingerprint_rollups.total_duration_ms + EXCLUDED.total_duration_ms,
+                    min_duration_ms =
+                        LEAST(fingerprint_rollups.min_duration_ms, EXCLUDED.min_duration_ms),
+                    max_duration_ms =
+                        GREATEST(fingerprint_rollups.max_duration_ms, EXCLUDED.max_duration_ms),
+                    p50_duration_ms =
+                        GREATEST(fingerprint_rollups.p50_duration_ms, EXCLUDED.p50_duration_ms),
+                    p95_duration_ms =
+                        GREATEST(fingerprint_rollups.p95_duration_ms, EXCLUDED.p95_duration_ms),
+                    p99_duration_ms =
+                        GREATEST(fingerprint_rollups.p99_duration_ms, EXCLUDED.p99_duration_ms),
+                    failed_count = fingerprint_rollups.failed_count + EXCLUDED.failed_count,
+                    error_count = fingerprint_rollups.error_count + EXCLUDED.error_count,
+                    total_rows_affected =
+                        fingerprint_rollups.total_rows_affected + EXCLUDED.total_rows_affected,
+                    first_seen = LEAST(fingerprint_rollups.first_seen, EXCLUDED.first_seen),
+                    last_seen = GREATEST(fingerprint_rollups.last_seen, EXCLUDED.last_seen),
+                    query_kind = COALESCE(fingerprint_rollups.query_kind, EXCLUDED.query_kind),
+                    updated_at = NOW()
+                "#,
+                r#"
+                INSERT INTO query_ownership (
+                    workspace_id, fingerprint, owner_team, ticket_links, notes,
+                    created_at, updated_at
+                )
+                SELECT DISTINCT ON (r.new_fingerprint) $1, r.new_fingerprint, o.owner_team, o.ticket_links, o.notes,
+                    o.created_at, o.updated_at
+                FROM query_ownership o JOIN rekeyed r ON r.old_fingerprint = o.fingerprint
+                WHERE o.workspace_id = $1
+                ORDER BY r.new_fingerprint, o.updated_at DESC
+                ON CONFLICT (workspace_id, fingerprint) DO NOTHING
+                "#,
+                r#"
+                INSERT INTO query_mutes (workspace_id, fingerprint, reason, expires_at, created_at)
+                SELECT DISTINCT ON (r.new_fingerprint) $1, r.new_fingerprint, m.reason, m.expires_at, m.created_at
+                FROM query_mutes m JOIN rekeyed r ON r.old_fingerprint = m.fingerprint
+                WHERE m.workspace_id = $1
+                -- The longest mute wins; NULL mutes indefinitely
+                ORDER BY r.new_fingerprint, m.expires_at DESC NULLS FIRST
+                ON CONFLICT (workspace_id, fingerprint) DO NOTHING
+                "#,
+                "DELETE FROM query_catalog c USING rekeyed r WHERE c.workspace_id = $1 AND c.fingerprint = r.old_fingerprint",
+                "DELETE FROM fingerprint_rollups f USING rekeyed r WHERE f.workspace_id = $1 AND f.fingerprint = r.old_fingerprint",
+                "DELETE FROM query_ownership o USING rekeyed r WHERE o.workspace_id = $1 AND o.fingerprint = r.old_fingerprint",
+                "DELETE FROM query_mutes m USING rekeyed r WHERE m.workspace_id = $1 AND m.fingerprint = r.old_fingerprint",
+                "UPDATE query_anomalies a SET fingerprint = r.new_fingerprint FROM rekeyed r WHERE a.workspace_id = $1 AND a.fingerprint = r.old_fingerprint",
+                "UPDATE query_metrics_archive a SET fingerprint = r.new_fingerprint FROM rekeyed r WHERE a.workspace_id = $1 AND a.fingerprint = r.old_fingerprint",
+            ];
+            for statement in statements {
+                sqlx::query(statement)
+                    .bind(workspace_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            sqlx::query(
+                r#"
+                UPDATE query_metrics m SET fingerprint = r.new_fingerprint
+                FROM rekeyed r
+                WHERE m.workspace_id = $1 AND m.fingerprint = r.old_fingerprint AND m.created_at >= $2
+                "#,
+            )
+            .bind(workspace_id)
+            .bind(self.raw_cutoff())
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            rekeyed += olds.len() as u64;
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO fingerprint_versions (version, rekeyed) VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(FINGERPRINT_VERSION)
+        .bind(rekeyed as i32)
+        .execute(&mut conn)
+        .await?;
+        info!(
+            version = FINGERPRINT_VERSION,
+            rekeyed, "Query fingerprints re-keyed"
+        );
+        Ok(rekeyed)
+    }
+
+    /// Rebuild the rollups of the last [`ROLLUP_BACKFILL_DAYS`] per service,
+    /// returning how many were written
+    ///
+    /// Rollups written before they were kept per service are workspace-wide,
+    /// so they can't serve the service overview. While any of those are
+    /// recent enough for the raw metrics to still be there, the instance
+    /// holding the advisory lock rebuilds the days since from raw metrics.
+    /// Older ones stay workspace-wide.
+    pub async fn backfill_rollup_services(&self) -> Result<u64> {
+        let since = self.clock.now() - TimeDelta::days(ROLLUP_BACKFILL_DAYS);
+        let pending: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM fingerprint_rollups WHERE service_id = $1 AND bucket >= $2)",
+        )
+        .bind(Uuid::nil())
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await?;
+        if !pending {
+            return Ok(0);
+        }
+
+        // A connection of its own, so closing it releases the lock
+        let mut conn = self.pool.acquire().await?.detach();
+        let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(ROLLUP_BACKFILL_LOCK)
+            .fetch_one(&mut conn)
+            .await?;
+        if !locked {
+            info!("Another instance is rebuilding the fingerprint rollups");
+            return Ok(0);
+        }
+        // A week of raw metrics can outlast the pool's timeout
+        sqlx::query("SET statement_timeout = 0")
+            .execute(&mut conn)
+            .await?;
+
+        info!(
+            days = ROLLUP_BACKFILL_DAYS,
+            "Rebuilding fingerprint rollups per service"
+        );
+        let rows = rebuild_rollups(&mut conn, self.rollup_range(since)).await?;
+        info!(rows, "Fingerprint rollups rebuilt per service");
+        Ok(rows)
+    }
+
+    /// Postgres extensions the server offers among `names`, with the installed
+    /// version (`None` when available but not yet created)
+    pub async fn available_extensions(
+        &self,
+        names: &[&str],
+    ) -> Result<HashMap<String, Option<String>>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT name, installed_version
+            FROM pg_available_extensions
+            WHERE name = ANY($1)
+            "#,
+        )
+        .bind(names)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get("name"), row.get("installed_version")))
+            .collect())
+    }
+
+    /// Dimension of the `query_embeddings.embedding` vector column, or `None`
+    /// before migrations create it or if it has no fixed dimension
+    pub async fn embedding_dimension(&self) -> Result<Option<usize>> {
+        // pgvector keeps the declared dimension as the column's type modifier
+        let typmod: Option<i32> = sqlx::query_scalar(
+            r#"
+            SELECT atttypmod
+            FROM pg_attribute
+            WHERE attrelid = to_regclass('query_embeddings')
+              AND attname = 'embedding'
+              AND NOT attisdropped
             "#,
         )
-        .bind(api_key)
         .fetch_optional(&self.pool)
-        .await?
-        .ok_or_else(|| AppError::Unauthorized("Invalid API key".into()))?;
+        .await?;
+
+        Ok(typmod.filter(|dim| *dim > 0).map(|dim| dim as usize))
+    }
+
+    /// Seal secrets stored in plaintext before a master key was configured,
+    /// returning how many were sealed
+    pub async fn seal_stored_secrets(&self) -> Result<u64> {
+        let Some(secrets) = &self.secrets else {
+            return Ok(0);
+        };
+        let rows = sqlx::query(
+            r#"
+            SELECT id, url, signing_secret, routing_key
+            FROM alert_destinations
+            WHERE url NOT LIKE 'enc:%' OR signing_secret NOT LIKE 'enc:%'
+                OR routing_key NOT LIKE 'enc:%'
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let seal = |value: &str| {
+            if secrets::is_sealed(value) {
+                value.to_string()
+            } else {
+                secrets.seal(value)
+            }
+        };
+        let mut sealed = 0;
+        for row in &rows {
+            let url: String = row.get("url");
+            let signing_secret: Option<String> = row.get("signing_secret");
+            let routing_key: Option<String> = row.get("routing_key");
+            sqlx::query(
+                r#"
+                UPDATE alert_destinations
+                SET url = $2, signing_secret = $3, routing_key = $4
+                WHERE id = $1 AND url = $5
+                    AND signing_secret IS NOT DISTINCT FROM $6
+                    AND routing_key IS NOT DISTINCT FROM $7
+                "#,
+            )
+            .bind(row.get::<Uuid, _>("id"))
+            .bind(seal(&url))
+            .bind(signing_secret.as_deref().map(seal))
+            .bind(routing_key.as_deref().map(seal))
+            .bind(&url)
+            .bind(&signing_secret)
+            .bind(&routing_key)
+            .execute(&self.pool)
+            .await?;
+            sealed += 1;
+        }
+        Ok(sealed)
+    }
+
+    /// Encrypt a secret for storage, if a master key is configured
+    fn seal_secret(&self, value: &str) -> String {
+        match &self.secrets {
+            Some(secrets) => secrets.seal(value),
+            None => value.to_string(),
+        }
+    }
 
-        Ok(Workspace {
+    /// Decrypt a stored secret
+    fn open_secret(&self, stored: &str) -> Result<String> {
+        match &self.secrets {
+            Some(secrets) => secrets.open(stored).map_err(AppError::InternalError),
+            None if secrets::is_sealed(stored) => Err(AppError::InternalError(
+                "Secret is encrypted but no secrets master key is configured".into(),
+            )),
+            None => Ok(stored.to_string()),
+        }
+    }
+
+    /// Read an alert destination row, decrypting its URL, signing secret, and
+    /// routing key
+    fn alert_destination_from_row(&self, row: &PgRow) -> Result<AlertDestination> {
+        Ok(AlertDestination {
             id: row.get("id"),
+            workspace_id: row.get("workspace_id"),
             name: row.get("name"),
-            api_key: row.get("api_key"),
+            kind: row.get::<String, _>("kind").parse().unwrap_or_default(),
+            url: self.open_secret(row.get("url"))?,
+            signing_secret: row
+                .get::<Option<&str>, _>("signing_secret")
+                .map(|secret| self.open_secret(secret))
+                .transpose()?,
+            routing_key: row
+                .get::<Option<&str>, _>("routing_key")
+                .map(|key| self.open_secret(key))
+                .transpose()?,
+            min_severity: row
+                .get::<String, _>("min_severity")
+                .parse()
+                .unwrap_or(SeverityLevel::High),
+            enabled: row.get("enabled"),
             created_at: row.get("created_at"),
-            updated_at: row.get("updated_at"),
         })
     }
 
+    /// Get the underlying connection pool
+    #[allow(dead_code)]
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+
     /// Insert a single metric
     #[allow(dead_code)]
     pub async fn insert_metric(&self, metric: &QueryMetric) -> Result<()> {
-        sqlx::query(
-            r#"
-            INSERT INTO query_metrics (
-                id, workspace_id, service_id, query_text, status,
-                duration_ms, rows_affected, error_message,
-                started_at, completed_at, tags
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
-            "#,
-        )
-        .bind(metric.id)
-        .bind(metric.workspace_id)
-        .bind(metric.service_id)
-        .bind(&metric.query_text)
-        .bind(status_to_string(&metric.status))
-        .bind(metric.duration_ms as i64)
-        .bind(metric.rows_affected)
-        .bind(&metric.error_message)
-        .bind(metric.started_at)
-        .bind(metric.completed_at)
-        .bind(&metric.tags)
-        .execute(&self.pool)
-        .await?;
-
+        let mut conn = self.pool.acquire().await?;
+        insert_metric_row(&mut conn, metric, &metric_fingerprint(metric)).await?;
         Ok(())
     }
 
-    /// Batch insert metrics for better performance
-    pub async fn insert_metrics_batch(&self, metrics: &[QueryMetric]) -> Result<usize> {
-        if metrics.is_empty() {
-            return Ok(0);
-        }
-
+    /// Insert a batch in one transaction, updating the query catalog alongside
+    ///
+    /// With `isolate_rows`, each row runs in its own savepoint so a rejected row
+    /// is dead-lettered instead of aborting the batch, and one that doesn't fit
+    /// the schema is quarantined there. Transient errors always fail the whole
+    /// batch so it can be retried.
+    async fn insert_batch(
+        &self,
+        metrics: &[QueryMetric],
+        isolate_rows: bool,
+    ) -> Result<BatchInsert> {
         let mut tx = self.pool.begin().await?;
-        let mut inserted = 0;
+        let mut outcome = BatchInsert::default();
+
+        // Inserted rows, column-wise, for the catalog upsert below
+        let mut catalog_workspaces = Vec::with_capacity(metrics.len());
+        let mut catalog_fingerprints = Vec::with_capacity(metrics.len());
+        let mut catalog_texts = Vec::with_capacity(metrics.len());
+        let mut catalog_seen = Vec::with_capacity(metrics.len());
+        // Reporting services, column-wise, for auto-registration
+        let mut service_ids = Vec::with_capacity(metrics.len());
+        let mut service_workspaces = Vec::with_capacity(metrics.len());
+        let mut service_names = Vec::with_capacity(metrics.len());
+        // Estimated size of each inserted row, for usage metering
+        let mut stored_bytes = Vec::with_capacity(metrics.len());
 
         for metric in metrics {
-            match sqlx::query(
-                r#"
-                INSERT INTO query_metrics (
-                    id, workspace_id, service_id, query_text, status,
-                    duration_ms, rows_affected, error_message,
-                    started_at, completed_at, tags
-                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
-                "#,
-            )
-            .bind(metric.id)
-            .bind(metric.workspace_id)
-            .bind(metric.service_id)
-            .bind(&metric.query_text)
-            .bind(status_to_string(&metric.status))
-            .bind(metric.duration_ms as i64)
-            .bind(metric.rows_affected)
-            .bind(&metric.error_message)
-            .bind(metric.started_at)
-            .bind(metric.completed_at)
-            .bind(&metric.tags)
-            .execute(&mut *tx)
-            .await
-            {
-                Ok(_) => inserted += 1,
-                Err(e) => {
-                    error!(error = %e, metric_id = %metric.id, "Failed to insert metric");
+            let fingerprint = metric_fingerprint(metric);
+
+            let result = if isolate_rows {
+                let mut savepoint = Acquire::begin(&mut tx).await?;
+                let result = insert_metric_row(&mut savepoint, metric, &fingerprint).await;
+                if result.is_ok() {
+                    savepoint.commit().await?;
+                } else {
+                    savepoint.rollback().await?;
+                }
+                result
+            } else {
+                insert_metric_row(&mut tx, metric, &fingerprint).await
+            };
+
+            let drift = result.as_ref().is_err_and(is_schema_drift);
+            match result.map_err(AppError::from) {
+                Ok(()) => {
+                    outcome.inserted += 1;
+                    catalog_workspaces.push(metric.workspace_id);
+                    catalog_fingerprints.push(fingerprint);
+                    catalog_texts.push(metric.query_text.as_str());
+                    catalog_seen.push(metric.started_at);
+                    service_ids.push(metric.service_id);
+                    service_workspaces.push(metric.workspace_id);
+                    service_names.push(metric.service_name.as_deref());
+                    stored_bytes.push(usage::estimated_bytes(metric));
+                }
+                Err(e @ AppError::DatabaseError(_)) if isolate_rows && drift => {
+                    error!(error = %e, metric_id = %metric.id, "Metric doesn't fit the schema, quarantined in dead-letter queue");
+                    upsert_dead_letter(
+                        &mut tx,
+                        metric,
+                        &e.to_string(),
+                        DeadLetterReason::SchemaDrift,
+                    )
+                    .await?;
+                    outcome.quarantined.push(QuarantinedMetric {
+                        workspace_id: metric.workspace_id,
+                        error: e.to_string(),
+                    });
                 }
+                Err(e @ AppError::DatabaseError(_)) if isolate_rows => {
+                    warn!(error = %e, metric_id = %metric.id, "Metric rejected, moved to dead-letter queue");
+                    upsert_dead_letter(&mut tx, metric, &e.to_string(), DeadLetterReason::Rejected)
+                        .await?;
+                    outcome.rejected += 1;
+                }
+                Err(e) => return Err(e),
             }
         }
 
-        tx.commit().await?;
-        Ok(inserted)
+        // Track first/last seen per fingerprint, returning the fingerprints
+        // new to workspaces past their learning period. The EXISTS sees the
+        // catalog as it was before this statement.
+        let new_shapes = sqlx::query(
+            r#"
+            WITH upserted AS (
+                INSERT INTO query_catalog (
+                    workspace_id, fingerprint, query_text, first_seen, last_seen, total_count
+                )
+                SELECT
+                    workspace_id,
+                    fingerprint,
+                    MIN(query_text),
+                    MIN(seen_at),
+                    MAX(seen_at),
+                    COUNT(*)
+                FROM UNNEST($1::UUID[], $2::TEXT[], $3::TEXT[], $4::TIMESTAMPTZ[])
+                    AS batch(workspace_id, fingerprint, query_text, seen_at)
+                GROUP BY 1, 2
+                ON CONFLICT (workspace_id, fingerprint) DO UPDATE SET
+                    first_seen = LEAST(query_catalog.first_seen, EXCLUDED.first_seen),
+                    last_seen = GREATEST(query_catalog.last_seen, EXCLUDED.last_seen),
+                    total_count = query_catalog.total_count + EXCLUDED.total_count
+                RETURNING workspace_id, fingerprint, query_text, first_seen,
+                    xmax = 0 AS inserted
+            )
+            SELECT u.workspace_id, u.fingerprint, u.query_text, u.first_seen
+            FROM upserted u
+            WHERE u.inserted AND EXISTS (
+                SELECT 1 FROM query_catalog c
+                WHERE c.workspace_id = u.workspace_id AND c.first_seen < $5
+            )
+            "#,
+        )
+        .bind(&catalog_workspaces)
+        .bind(&catalog_fingerprints)
+        .bind(&catalog_texts)
+        .bind(&catalog_seen)
+        .bind(Utc::now() - NEW_SHAPE_LEARNING_PERIOD)
+        .fetch_all(&mut *tx)
+        .await?;
+        outcome.new_shapes = new_shapes
+            .iter()
+            .map(|row| NewQueryShape {
+                workspace_id: row.get("workspace_id"),
+                fingerprint: row.get("fingerprint"),
+                query_text: row.get("query_text"),
+                first_seen: row.get("first_seen"),
+            })
+            .collect();
+
+        // Register unseen services and bump last_seen_at for known ones. Services
+        // whose name is taken or whose workspace doesn't exist are skipped rather
+        // than failing the batch.
+        sqlx::query(
+            r#"
+            WITH batch AS (
+                SELECT service_id, workspace_id, MAX(name) AS name, MAX(seen_at) AS seen_at
+                FROM UNNEST($1::UUID[], $2::UUID[], $3::TEXT[], $4::TIMESTAMPTZ[])
+                    AS b(service_id, workspace_id, name, seen_at)
+                GROUP BY 1, 2
+            ),
+            seen AS (
+                UPDATE services s
+                SET last_seen_at = GREATEST(s.last_seen_at, b.seen_at)
+                FROM batch b
+                WHERE s.id = b.service_id AND s.workspace_id = b.workspace_id
+                RETURNING s.id
+            )
+            INSERT INTO services (id, workspace_id, name, last_seen_at)
+            SELECT b.service_id, b.workspace_id, COALESCE(b.name, b.service_id::TEXT), b.seen_at
+            FROM batch b
+            WHERE b.service_id NOT IN (SELECT id FROM seen)
+                AND EXISTS (SELECT 1 FROM workspaces w WHERE w.id = b.workspace_id)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(&service_ids)
+        .bind(&service_workspaces)
+        .bind(&service_names)
+        .bind(&catalog_seen)
+        .execute(&mut *tx)
+        .await?;
+
+        // Meter stored metrics per workspace and day
+        sqlx::query(
+            r#"
+            INSERT INTO workspace_usage (workspace_id, day, metric_count, stored_bytes)
+            SELECT workspace_id, (NOW() AT TIME ZONE 'UTC')::DATE, COUNT(*), SUM(bytes)::BIGINT
+            FROM UNNEST($1::UUID[], $2::BIGINT[]) AS batch(workspace_id, bytes)
+            GROUP BY 1
+            ON CONFLICT (workspace_id, day) DO UPDATE SET
+                metric_count = workspace_usage.metric_count + EXCLUDED.metric_count,
+                stored_bytes = workspace_usage.stored_bytes + EXCLUDED.stored_bytes
+            "#,
+        )
+        .bind(&catalog_workspaces)
+        .bind(&stored_bytes)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        if let Some(cache) = &self.aggregation_cache {
+            cache.invalidate_since(&catalog_workspaces.iter().copied().collect(), Utc::now());
+        }
+        Ok(outcome)
+    }
+
+    /// Check if a query embedding exists
+    #[allow(dead_code)]
+    pub async fn embedding_exists(&self, workspace_id: Uuid, query_hash: &str) -> Result<bool> {
+        let row = sqlx::query(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM query_embeddings 
+                WHERE workspace_id = $1 AND query_hash = $2
+            ) as exists
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(query_hash)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get::<bool, _>("exists"))
+    }
+}
+
+#[async_trait]
+impl MetricsStore for Database {
+    /// Run a trivial query to check connectivity
+    async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").fetch_one(&self.pool).await?;
+        Ok(())
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        Some(PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle() as u32,
+            max: self.pool.options().get_max_connections(),
+        })
+    }
+
+    fn read_pool_stats(&self) -> Option<PoolStats> {
+        self.has_replica.then(|| PoolStats {
+            size: self.read_pool.size(),
+            idle: self.read_pool.num_idle() as u32,
+            max: self.read_pool.options().get_max_connections(),
+        })
+    }
+
+    fn aggregation_cache_stats(&self) -> Option<(u64, u64)> {
+        self.aggregation_cache.as_ref().map(|cache| cache.stats())
+    }
+
+    /// Verify an API key, rejecting unknown and expired keys
+    ///
+    /// Keys are looked up by hash. A key stored before hashing was introduced
+    /// is matched on its plaintext once, then hashed and its plaintext cleared.
+    async fn verify_api_key(&self, api_key: &str) -> Result<ApiKey> {
+        let key_hash = hash_secret(api_key);
+        let row = sqlx::query(
+            r#"
+            SELECT id, workspace_id, name, scopes, expires_at, created_at, key_hash
+            FROM api_keys
+            WHERE key_hash = $1
+            "#,
+        )
+        .bind(&key_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let row = match row {
+            Some(row) => row,
+            None => sqlx::query(
+                r#"
+                UPDATE api_keys
+                SET key_hash = $2, api_key = NULL
+                WHERE api_key = $1 AND key_hash IS NULL
+                RETURNING id, workspace_id, name, scopes, expires_at, created_at, key_hash
+                "#,
+            )
+            .bind(api_key)
+            .bind(&key_hash)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| AppError::Unauthorized("Invalid API key".into()))?,
+        };
+
+        let stored: String = row.get("key_hash");
+        if !hash_matches(api_key, &stored) {
+            return Err(AppError::Unauthorized("Invalid API key".into()));
+        }
+
+        let key = api_key_from_row(&row);
+        if key.is_expired(Utc::now()) {
+            return Err(AppError::Unauthorized("API key has expired".into()));
+        }
+        Ok(key)
+    }
+
+    /// Get a workspace's JSON casing setting
+    async fn get_json_casing(&self, workspace_id: Uuid) -> Result<Option<JsonCasing>> {
+        let casing: Option<String> =
+            sqlx::query_scalar("SELECT json_casing FROM workspaces WHERE id = $1")
+                .bind(workspace_id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(casing.map(|c| c.parse().unwrap_or_default()))
+    }
+
+    /// Set a workspace's JSON casing
+    async fn set_json_casing(&self, workspace_id: Uuid, casing: JsonCasing) -> Result<bool> {
+        let result =
+            sqlx::query("UPDATE workspaces SET json_casing = $2, updated_at = NOW() WHERE id = $1")
+                .bind(workspace_id)
+                .bind(casing.as_str())
+                .execute(&self.pool)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Get a workspace's monthly metric quota
+    async fn get_monthly_quota(&self, workspace_id: Uuid) -> Result<Option<i64>> {
+        let quota: Option<Option<i64>> =
+            sqlx::query_scalar("SELECT monthly_metric_quota FROM workspaces WHERE id = $1")
+                .bind(workspace_id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(quota.flatten())
+    }
+
+    /// Set or clear a workspace's monthly metric quota
+    async fn set_monthly_quota(&self, workspace_id: Uuid, quota: Option<i64>) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE workspaces SET monthly_metric_quota = $2, updated_at = NOW() WHERE id = $1",
+        )
+        .bind(workspace_id)
+        .bind(quota)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Get whether a workspace redacts literals in ingested query text
+    async fn get_literal_redaction(&self, workspace_id: Uuid) -> Result<Option<bool>> {
+        let enabled: Option<bool> =
+            sqlx::query_scalar("SELECT redact_literals FROM workspaces WHERE id = $1")
+                .bind(workspace_id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(enabled)
+    }
+
+    /// Turn literal redaction on or off for a workspace
+    async fn set_literal_redaction(&self, workspace_id: Uuid, enabled: bool) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE workspaces SET redact_literals = $2, updated_at = NOW() WHERE id = $1",
+        )
+        .bind(workspace_id)
+        .bind(enabled)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Get a workspace's anomaly detection method
+    async fn get_anomaly_method(&self, workspace_id: Uuid) -> Result<Option<AnomalyMethod>> {
+        let method: Option<String> =
+            sqlx::query_scalar("SELECT anomaly_method FROM workspaces WHERE id = $1")
+                .bind(workspace_id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(method.map(|m| m.parse().unwrap_or_default()))
+    }
+
+    /// Set a workspace's anomaly detection method
+    async fn set_anomaly_method(&self, workspace_id: Uuid, method: AnomalyMethod) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE workspaces SET anomaly_method = $2, updated_at = NOW() WHERE id = $1",
+        )
+        .bind(workspace_id)
+        .bind(method.as_str())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Get the anomaly detection settings workspaces override
+    async fn get_anomaly_overrides(
+        &self,
+        workspace_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, AnomalyOverrides>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                id, anomaly_z_threshold, anomaly_mad_threshold,
+                anomaly_interval_secs, anomaly_lookback, anomaly_min_samples,
+                anomaly_seasonality
+            FROM workspaces
+            WHERE id = ANY($1)
+            "#,
+        )
+        .bind(workspace_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let overrides = AnomalyOverrides {
+                    z_threshold: row.get("anomaly_z_threshold"),
+                    mad_threshold: row.get("anomaly_mad_threshold"),
+                    interval_secs: row.get("anomaly_interval_secs"),
+                    lookback: row.get("anomaly_lookback"),
+                    min_samples: row.get("anomaly_min_samples"),
+                    seasonality: row
+                        .get::<Option<String>, _>("anomaly_seasonality")
+                        .map(|s| s.parse().unwrap_or_default()),
+                };
+                (row.get("id"), overrides)
+            })
+            .collect())
+    }
+
+    /// Replace a workspace's anomaly detection overrides
+    async fn set_anomaly_overrides(
+        &self,
+        workspace_id: Uuid,
+        overrides: &AnomalyOverrides,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE workspaces SET
+                anomaly_z_threshold = $2,
+                anomaly_mad_threshold = $3,
+                anomaly_interval_secs = $4,
+                anomaly_lookback = $5,
+                anomaly_min_samples = $6,
+                anomaly_seasonality = $7,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(overrides.z_threshold)
+        .bind(overrides.mad_threshold)
+        .bind(overrides.interval_secs)
+        .bind(overrides.lookback)
+        .bind(overrides.min_samples)
+        .bind(overrides.seasonality.map(|s| s.as_str()))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // =========================================================================
+    // API KEY METHODS
+    // =========================================================================
+
+    /// Store a new API key, keeping only the hash of its secret
+    async fn create_api_key(&self, key: &ApiKey, secret: &str) -> Result<()> {
+        let scopes: Vec<&str> = key.scopes.iter().map(|s| s.as_str()).collect();
+        sqlx::query(
+            r#"
+            INSERT INTO api_keys (id, workspace_id, name, key_hash, scopes, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(key.id)
+        .bind(key.workspace_id)
+        .bind(&key.name)
+        .bind(hash_secret(secret))
+        .bind(&scopes)
+        .bind(key.expires_at)
+        .bind(key.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// List a workspace's API keys
+    async fn list_api_keys(&self, workspace_id: Uuid) -> Result<Vec<ApiKey>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, workspace_id, name, scopes, expires_at, created_at
+            FROM api_keys
+            WHERE workspace_id = $1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(api_key_from_row).collect())
+    }
+
+    /// Revoke an API key
+    async fn delete_api_key(&self, workspace_id: Uuid, key_id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM api_keys WHERE workspace_id = $1 AND id = $2")
+            .bind(workspace_id)
+            .bind(key_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Batch insert metrics for better performance
+    ///
+    /// Rows the database rejects (constraint violations, oversized values, ...)
+    /// are moved to `query_metrics_dlq` rather than failing the batch.
+    async fn insert_metrics_batch(&self, metrics: &[QueryMetric]) -> Result<BatchInsert> {
+        if metrics.is_empty() {
+            return Ok(BatchInsert::default());
+        }
+
+        match self.insert_batch(metrics, false).await {
+            // Fast path failed on a bad row; redo the batch row by row to isolate it
+            Err(AppError::DatabaseError(e)) => {
+                warn!(error = %e, batch_size = metrics.len(), "Metrics batch rejected, retrying row by row");
+                self.insert_batch(metrics, true).await
+            }
+            result => result,
+        }
+    }
+
+    /// Get recent metrics for a workspace
+    async fn get_recent_metrics(
+        &self,
+        workspace_id: Uuid,
+        filter: &MetricFilter,
+        limit: i64,
+    ) -> Result<Vec<QueryMetric>> {
+        let read = RawRead::new(
+            r#"
+            SELECT 
+                id, workspace_id, service_id, query_text, status,
+                duration_ms, rows_affected, error_message,
+                started_at, completed_at, tags, labels, fingerprint, query_kind
+            FROM {raw} m
+            WHERE workspace_id = $1
+              AND ($2::uuid IS NULL OR service_id = $2)
+              AND tags @> $3
+              AND labels @> $4
+              AND ($6::TEXT IS NULL OR query_kind = $6)
+            ORDER BY created_at DESC
+            LIMIT $5
+            "#,
+        )
+        .range(self.raw_retained());
+        let rows = read
+            .bind(
+                sqlx::query(read.sql())
+                    .bind(workspace_id)
+                    .bind(filter.service_id)
+                    .bind(&filter.tags)
+                    .bind(Json(&filter.labels))
+                    .bind(limit)
+                    .bind(filter.query_kind.map(|k| k.as_str())),
+            )
+            .fetch_all(&self.read_pool)
+            .await?;
+
+        let metrics = rows
+            .into_iter()
+            .map(|row| QueryMetric {
+                id: row.get("id"),
+                workspace_id: row.get("workspace_id"),
+                service_id: row.get("service_id"),
+                service_name: None,
+                query_text: row.get("query_text"),
+                status: string_to_status(row.get("status")),
+                duration_ms: row.get::<i64, _>("duration_ms") as u64,
+                rows_affected: row.get("rows_affected"),
+                error_message: row.get("error_message"),
+                started_at: row.get("started_at"),
+                completed_at: row.get("completed_at"),
+                tags: row
+                    .get::<Option<Vec<String>>, _>("tags")
+                    .unwrap_or_default(),
+                labels: row.get::<Json<HashMap<String, String>>, _>("labels").0,
+                fingerprint: row.get("fingerprint"),
+                query_kind: row
+                    .get::<Option<&str>, _>("query_kind")
+                    .and_then(|k| k.parse().ok()),
+            })
+            .collect();
+
+        Ok(metrics)
+    }
+
+    /// Get aggregated metrics from continuous aggregate views
+    ///
+    /// Local days in whole-hour offsets are summed from the hourly view. The
+    /// views don't keep tags, labels, or query kinds, so filtering on them
+    /// (or on buckets no view lines up with) aggregates the raw metrics
+    /// instead, with the same buckets and columns.
+    async fn get_aggregations(
+        &self,
+        workspace_id: Uuid,
+        window: &str,
+        tz: Tz,
+        filter: &MetricFilter,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<AggregatedMetric>> {
+        let (view_name, interval) = match window {
+            "5s" => ("metrics_5s", "5 seconds"),
+            "1m" => ("metrics_1m", "1 minute"),
+            "5m" => ("metrics_5m", "5 minutes"),
+            "1h" => ("metrics_1h", "1 hour"),
+            "1d" => ("metrics_1d", "1 day"),
+            _ => {
+                return Err(AppError::InvalidRequest(format!(
+                    "Invalid window: {}",
+                    window
+                )))
+            }
+        };
+
+        if let Some(buckets) = self
+            .aggregation_cache
+            .as_ref()
+            .and_then(|cache| cache.get(workspace_id, window, tz, filter, from, to))
+        {
+            return Ok(buckets);
+        }
+
+        let width = window_width(window).unwrap_or_default();
+        let unfiltered =
+            filter.tags.is_empty() && filter.labels.is_empty() && filter.query_kind.is_none();
+        // The views hold UTC buckets, so local buckets that start elsewhere
+        // are summed from UTC hours when they're made of whole ones (local
+        // days in whole-hour offsets), and built from raw rows otherwise
+        let by_view = unfiltered && utc_aligned(width, tz, from, to);
+        let by_hours = unfiltered
+            && !by_view
+            && width > TimeDelta::hours(1)
+            && whole_hour_offsets(tz, from, to);
+        // Using dynamic query since view name can't be parameterized
+        let read = if by_view {
+            RawRead::new(&format!(
+                r#"
+                SELECT 
+                    workspace_id, service_id, bucket,
+                    query_count, avg_duration_ms, min_duration_ms, max_duration_ms,
+                    p95_duration_ms, p99_duration_ms,
+                    success_count, failed_count, total_rows_affected
+                FROM {}
+                WHERE workspace_id = $1 AND bucket >= $2 AND bucket < $3
+                  AND ($4::uuid IS NULL OR service_id = $4)
+                ORDER BY bucket ASC
+                "#,
+                view_name
+            ))
+        } else if by_hours {
+            // Percentiles can't be merged, so a day's are the highest of its
+            // hours': an upper bound rather than the exact value. Grouping by
+            // position, since `bucket` alone names the view's hourly column.
+            RawRead::new(&format!(
+                r#"
+                SELECT
+                    workspace_id, service_id,
+                    time_bucket('{interval}', bucket, $5::text) AS bucket,
+                    SUM(query_count)::BIGINT AS query_count,
+                    (SUM(avg_duration_ms * query_count) / NULLIF(SUM(query_count), 0))::BIGINT
+                        AS avg_duration_ms,
+                    MIN(min_duration_ms)::BIGINT AS min_duration_ms,
+                    MAX(max_duration_ms)::BIGINT AS max_duration_ms,
+                    MAX(p95_duration_ms)::BIGINT AS p95_duration_ms,
+                    MAX(p99_duration_ms)::BIGINT AS p99_duration_ms,
+                    SUM(success_count)::BIGINT AS success_count,
+                    SUM(failed_count)::BIGINT AS failed_count,
+                    SUM(total_rows_affected)::BIGINT AS total_rows_affected
+                FROM metrics_1h
+                WHERE workspace_id = $1
+                  AND bucket >= $2 - INTERVAL '{interval}' AND bucket < $3 + INTERVAL '{interval}'
+                  AND time_bucket('{interval}', bucket, $5::text) >= $2
+                  AND time_bucket('{interval}', bucket, $5::text) < $3
+                  AND ($4::uuid IS NULL OR service_id = $4)
+                GROUP BY workspace_id, service_id, 3
+                ORDER BY bucket ASC
+                "#
+            ))
+        } else {
+            // Truncating at retention would pass off partial buckets as whole
+            let cutoff = self.raw_cutoff();
+            if from < cutoff {
+                return Err(AppError::InvalidRequest(format!(
+                    "{} buckets in {} with these filters are computed from raw metrics, which \
+                     are kept since {}; start the range later",
+                    window,
+                    tz.name(),
+                    cutoff.to_rfc3339()
+                )));
+            }
+            // A local day runs up to 25 hours across a DST change
+            let range = self.raw_range(from - width, to + width + TimeDelta::hours(1));
+            RawRead::new(&format!(
+                r#"
+                SELECT
+                    workspace_id, service_id,
+                    time_bucket('{interval}', created_at, $7::text) AS bucket,
+                    COUNT(*) AS query_count,
+                    AVG(duration_ms)::BIGINT AS avg_duration_ms,
+                    MIN(duration_ms) AS min_duration_ms,
+                    MAX(duration_ms) AS max_duration_ms,
+                    PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY duration_ms)::BIGINT AS p95_duration_ms,
+                    PERCENTILE_CONT(0.99) WITHIN GROUP (ORDER BY duration_ms)::BIGINT AS p99_duration_ms,
+                    SUM(CASE WHEN status = 'success' THEN 1 ELSE 0 END) AS success_count,
+                    SUM(CASE WHEN status = 'failed' THEN 1 ELSE 0 END) AS failed_count,
+                    SUM(COALESCE(rows_affected, 0)) AS total_rows_affected
+                FROM {{raw}} m
+                WHERE workspace_id = $1
+                  AND time_bucket('{interval}', created_at, $7::text) >= $2
+                  AND time_bucket('{interval}', created_at, $7::text) < $3
+                  AND ($4::uuid IS NULL OR service_id = $4)
+                  AND tags @> $5
+                  AND labels @> $6
+                  AND ($8::TEXT IS NULL OR query_kind = $8)
+                GROUP BY workspace_id, service_id, bucket
+                ORDER BY bucket ASC
+                "#
+            ))
+            .range(range)
+        };
+
+        let mut statement = sqlx::query(read.sql())
+            .bind(workspace_id)
+            .bind(from)
+            .bind(to)
+            .bind(filter.service_id);
+        if by_hours {
+            statement = statement.bind(tz.name());
+        } else if !by_view {
+            statement = statement
+                .bind(&filter.tags)
+                .bind(Json(&filter.labels))
+                .bind(tz.name())
+                .bind(filter.query_kind.map(|k| k.as_str()));
+        }
+        let rows = read.bind(statement).fetch_all(&self.read_pool).await?;
+
+        let aggregations = rows
+            .into_iter()
+            .map(|row| AggregatedMetric {
+                workspace_id: row.get("workspace_id"),
+                service_id: row.get("service_id"),
+                bucket: row.get("bucket"),
+                query_count: row.get("query_count"),
+                avg_duration_ms: row.get("avg_duration_ms"),
+                min_duration_ms: row.get("min_duration_ms"),
+                max_duration_ms: row.get("max_duration_ms"),
+                p95_duration_ms: row.get("p95_duration_ms"),
+                p99_duration_ms: row.get("p99_duration_ms"),
+                success_count: row.get("success_count"),
+                failed_count: row.get("failed_count"),
+                total_rows_affected: row.get("total_rows_affected"),
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(cache) = &self.aggregation_cache {
+            cache.insert(
+                workspace_id,
+                window,
+                tz,
+                filter,
+                from,
+                to,
+                aggregations.clone(),
+            );
+        }
+        Ok(aggregations)
+    }
+
+    /// Manually prune old data (backup for TimescaleDB retention policies)
+    async fn prune_old_metrics(&self, older_than_days: i32) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM query_metrics
+            WHERE created_at < NOW() - make_interval(days => $1)
+            "#,
+        )
+        .bind(older_than_days)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Erase a workspace's matching metrics with their anomalies, embeddings,
+    /// catalog entries, rollups, and dead letters
+    async fn erase_metrics(
+        &self,
+        workspace_id: Uuid,
+        filter: &MetricErasure,
+    ) -> Result<ErasureCounts> {
+        // One statement, so the deletions commit or fail together.
+        // Anomalies match on the metric they were raised for, or on the
+        // filter directly for those whose metric is already gone; embeddings
+        // match on the deleted metrics' query hashes or on the text filter.
+        // Query clusters lose the erased embeddings as members, and a cluster
+        // represented by one is dropped until the next clustering run.
+        // Catalog entries of the erased fingerprints, and their rollups in
+        // the hours the filter covers, go too, since either may hold an
+        // erased text as its representative; the next flush and rollup
+        // refresh rebuild them from what's left. Dead letters match the
+        // filter on their payload, with the time range on when they failed.
+        let row = sqlx::query(
+            r#"
+            WITH deleted AS (
+                DELETE FROM query_metrics
+                WHERE workspace_id = $1
+                    AND ($2::timestamptz IS NULL OR created_at >= $2)
+                    AND ($3::timestamptz IS NULL OR created_at < $3)
+                    AND ($4::uuid IS NULL OR service_id = $4)
+                    AND ($5::text IS NULL OR strpos(lower(query_text), lower($5)) > 0)
+                RETURNING id, query_text,
+                    COALESCE(fingerprint, md5(normalize_sql(query_text))) AS fingerprint
+            ),
+            catalog AS (
+                DELETE FROM query_catalog
+                WHERE workspace_id = $1
+                    AND (
+                        fingerprint IN (SELECT fingerprint FROM deleted)
+                        OR strpos(lower(query_text), lower($5)) > 0
+                    )
+                RETURNING 1
+            ),
+            rollups AS (
+                DELETE FROM fingerprint_rollups
+                WHERE workspace_id = $1
+                    AND ($2::timestamptz IS NULL OR bucket >= date_trunc('hour', $2))
+                    AND ($3::timestamptz IS NULL OR bucket < $3)
+                    AND ($4::uuid IS NULL OR service_id = $4)
+                    AND (
+                        fingerprint IN (SELECT fingerprint FROM deleted)
+                        OR strpos(lower(query_text), lower($5)) > 0
+                    )
+            ),
+            dead_letters AS (
+                DELETE FROM query_metrics_dlq
+                WHERE workspace_id = $1
+                    AND ($2::timestamptz IS NULL OR first_failed_at >= $2)
+                    AND ($3::timestamptz IS NULL OR first_failed_at < $3)
+                    AND ($4::uuid IS NULL OR metric->>'service_id' = $4::text)
+                    AND ($5::text IS NULL OR strpos(lower(metric->>'query_text'), lower($5)) > 0)
+                RETURNING 1
+            ),
+            embeddings AS (
+                DELETE FROM query_embeddings
+                WHERE workspace_id = $1
+                    AND (
+                        query_hash IN (
+                            SELECT md5(lower(regexp_replace(trim(query_text), '\s+', ' ', 'g')))
+                            FROM deleted
+                        )
+                        OR strpos(lower(sql_query), lower($5)) > 0
+                    )
+                RETURNING query_hash
+            ),
+            cluster_members AS (
+                DELETE FROM query_cluster_members
+                WHERE workspace_id = $1 AND query_hash IN (SELECT query_hash FROM embeddings)
+            ),
+            clusters AS (
+                DELETE FROM query_clusters
+                WHERE workspace_id = $1
+                    AND representative_hash IN (SELECT query_hash FROM embeddings)
+            ),
+            anomalies AS (
+                DELETE FROM query_anomalies
+                WHERE workspace_id = $1
+                    AND (
+                        metric_id IN (SELECT id FROM deleted)
+                        OR (
+                            ($2::timestamptz IS NULL OR detected_at >= $2)
+                            AND ($3::timestamptz IS NULL OR detected_at < $3)
+                            AND ($4::uuid IS NULL OR service_id = $4)
+                            AND ($5::text IS NULL OR strpos(lower(query_text), lower($5)) > 0)
+                        )
+                    )
+                RETURNING 1
+            )
+            SELECT
+                (SELECT COUNT(*) FROM deleted) AS metrics,
+                (SELECT COUNT(*) FROM embeddings) AS embeddings,
+                (SELECT COUNT(*) FROM anomalies) AS anomalies,
+                (SELECT COUNT(*) FROM catalog) AS catalog_entries,
+                (SELECT COUNT(*) FROM dead_letters) AS dead_letters
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(filter.from)
+        .bind(filter.to)
+        .bind(filter.service_id)
+        .bind(filter.query_contains.as_deref())
+        .fetch_one(&self.pool)
+        .await?;
+
+        if let Some(cache) = &self.aggregation_cache {
+            cache.invalidate_workspace(workspace_id);
+        }
+        Ok(ErasureCounts {
+            metrics: row.get::<i64, _>("metrics") as u64,
+            embeddings: row.get::<i64, _>("embeddings") as u64,
+            anomalies: row.get::<i64, _>("anomalies") as u64,
+            catalog_entries: row.get::<i64, _>("catalog_entries") as u64,
+            dead_letters: row.get::<i64, _>("dead_letters") as u64,
+        })
+    }
+
+    // =========================================================================
+    // DEAD-LETTER METHODS
+    // =========================================================================
+
+    /// Record metrics that could not be inserted, bumping attempts for known ones
+    async fn insert_dead_letters(&self, metrics: &[QueryMetric], error: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for metric in metrics {
+            upsert_dead_letter(&mut tx, metric, error, DeadLetterReason::Rejected).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// List dead letters, most recently failed first
+    async fn list_dead_letters(
+        &self,
+        workspace_id: Option<Uuid>,
+        reason: Option<DeadLetterReason>,
+        limit: i64,
+    ) -> Result<Vec<DeadLetter>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT metric_id, workspace_id, metric, error, reason, attempts,
+                   first_failed_at, last_failed_at
+            FROM query_metrics_dlq
+            WHERE ($1::UUID IS NULL OR workspace_id = $1)
+                AND ($3::TEXT IS NULL OR reason = $3)
+            ORDER BY last_failed_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(limit)
+        .bind(reason.map(|r| r.as_str()))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(dead_letter_from_row).collect())
+    }
+
+    /// Get dead letters by metric ID
+    async fn get_dead_letters(&self, metric_ids: &[Uuid]) -> Result<Vec<DeadLetter>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT metric_id, workspace_id, metric, error, reason, attempts,
+                   first_failed_at, last_failed_at
+            FROM query_metrics_dlq
+            WHERE metric_id = ANY($1)
+            "#,
+        )
+        .bind(metric_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(dead_letter_from_row).collect())
+    }
+
+    /// Delete dead letters that have not failed again since `failed_before`
+    async fn delete_dead_letters(&self, letters: &[(Uuid, i32)]) -> Result<u64> {
+        let (metric_ids, attempts): (Vec<Uuid>, Vec<i32>) = letters.iter().copied().unzip();
+        let result = sqlx::query(
+            r#"
+            DELETE FROM query_metrics_dlq d
+            USING UNNEST($1::UUID[], $2::INTEGER[]) AS r(metric_id, attempts)
+            WHERE d.metric_id = r.metric_id AND d.attempts = r.attempts
+            "#,
+        )
+        .bind(&metric_ids)
+        .bind(&attempts)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // =========================================================================
+    // ROLLUP METHODS
+    // =========================================================================
+
+    /// Recompute hourly fingerprint rollups for every bucket starting at or after `since`
+    ///
+    /// Buckets are rebuilt from scratch, so re-running over the same range is
+    /// idempotent. Rollups in the range that no longer have raw metrics are
+    /// dropped, so `since` must be within the raw metrics every lifecycle
+    /// policy keeps.
+    async fn refresh_fingerprint_rollups(&self, since: DateTime<Utc>) -> Result<u64> {
+        let mut conn = self.pool.acquire().await?;
+        rebuild_rollups(&mut conn, self.rollup_range(since)).await
+    }
+
+    /// Get catalog entries first seen since the given time
+    async fn get_new_queries(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<CatalogEntry>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                c.fingerprint, c.query_text, c.first_seen, c.last_seen, c.total_count,
+                o.fingerprint AS owner_fingerprint, o.owner_team,
+                o.ticket_links AS owner_ticket_links, o.notes AS owner_notes,
+                o.updated_at AS owner_updated_at
+            FROM query_catalog c
+            LEFT JOIN query_ownership o
+                ON o.workspace_id = c.workspace_id AND o.fingerprint = c.fingerprint
+            WHERE c.workspace_id = $1 AND c.first_seen >= $2
+            ORDER BY c.first_seen DESC
+            LIMIT $3
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let entries = rows
+            .into_iter()
+            .map(|row| CatalogEntry {
+                fingerprint: row.get("fingerprint"),
+                query_text: row.get("query_text"),
+                first_seen: row.get("first_seen"),
+                last_seen: row.get("last_seen"),
+                total_count: row.get("total_count"),
+                ownership: ownership_from_row(&row),
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// Search the query catalog by text
+    async fn search_query_text(
+        &self,
+        workspace_id: Uuid,
+        search: &QueryTextSearch,
+    ) -> Result<Vec<CatalogEntry>> {
+        // Substring matches first, then by how well the words match
+        let read = RawRead::new(
+            r#"
+            SELECT
+                c.fingerprint, c.query_text, c.first_seen, c.last_seen, c.total_count,
+                o.fingerprint AS owner_fingerprint, o.owner_team,
+                o.ticket_links AS owner_ticket_links, o.notes AS owner_notes,
+                o.updated_at AS owner_updated_at
+            FROM query_catalog c
+            LEFT JOIN query_ownership o
+                ON o.workspace_id = c.workspace_id AND o.fingerprint = c.fingerprint
+            WHERE c.workspace_id = $1
+                AND (
+                    c.fingerprint = $2
+                    OR c.query_text ILIKE $3 ESCAPE '\'
+                    OR ($4 <> '' AND sql_lexemes(c.query_text) @@ to_tsquery('simple', $4))
+                )
+                AND ($5::TIMESTAMPTZ IS NULL OR c.last_seen >= $5)
+                AND ($6::TIMESTAMPTZ IS NULL OR c.first_seen < $6)
+                AND ($7::UUID IS NULL OR EXISTS (
+                    SELECT 1 FROM {raw} m
+                    WHERE m.workspace_id = $1 AND m.fingerprint = c.fingerprint
+                        AND m.service_id = $7
+                ))
+            ORDER BY
+                c.query_text ILIKE $3 ESCAPE '\' DESC,
+                CASE WHEN $4 = '' THEN 0
+                    ELSE ts_rank_cd(sql_lexemes(c.query_text), to_tsquery('simple', $4))
+                END DESC,
+                c.last_seen DESC,
+                c.fingerprint
+            LIMIT $8 OFFSET $9
+            "#,
+        )
+        .range(self.raw_range(
+            search.from.unwrap_or(DateTime::<Utc>::MIN_UTC),
+            search.to.unwrap_or_else(|| self.clock.now()),
+        ));
+        let rows = read
+            .bind(
+                sqlx::query(read.sql())
+                    .bind(workspace_id)
+                    .bind(&search.text)
+                    .bind(search.like_pattern())
+                    .bind(search.tsquery())
+                    .bind(search.from)
+                    .bind(search.to)
+                    .bind(search.service_id)
+                    .bind(search.limit)
+                    .bind(search.offset),
+            )
+            .fetch_all(&self.read_pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| CatalogEntry {
+                fingerprint: row.get("fingerprint"),
+                query_text: row.get("query_text"),
+                first_seen: row.get("first_seen"),
+                last_seen: row.get("last_seen"),
+                total_count: row.get("total_count"),
+                ownership: ownership_from_row(&row),
+            })
+            .collect())
+    }
+
+    /// Get per-fingerprint execution statistics from the hourly rollups,
+    /// leaving out muted fingerprints
+    ///
+    /// The range is widened to whole hours. Percentiles spanning several
+    /// hours are the call-weighted mean of the hourly ones. Rows stored before
+    /// fingerprinting existed are keyed by the fingerprint of their
+    /// normalized text.
+    async fn get_fingerprint_stats(
+        &self,
+        workspace_id: Uuid,
+        query: &FingerprintStatsQuery,
+    ) -> Result<Vec<FingerprintStats>> {
+        // The sort column comes from a fixed list, never from the request
+        let sql = format!(
+            r#"
+            WITH stats AS (
+                SELECT
+                    fingerprint,
+                    MIN(query_text) AS query_text,
+                    SUM(call_count)::BIGINT AS call_count,
+                    SUM(error_count)::BIGINT AS error_count,
+                    SUM(total_duration_ms)::BIGINT AS total_duration_ms,
+                    (SUM(p50_duration_ms * call_count) / SUM(call_count))::BIGINT
+                        AS p50_duration_ms,
+                    (SUM(p95_duration_ms * call_count) / SUM(call_count))::BIGINT
+                        AS p95_duration_ms,
+                    (SUM(p99_duration_ms * call_count) / SUM(call_count))::BIGINT
+                        AS p99_duration_ms,
+                    MIN(first_seen) AS first_seen,
+                    MAX(last_seen) AS last_seen
+                FROM fingerprint_rollups f
+                WHERE workspace_id = $1
+                    AND bucket >= time_bucket('1 hour', $2::TIMESTAMPTZ) AND bucket < $3
+                    AND ($4::UUID IS NULL OR service_id = $4)
+                    AND ($7::TEXT IS NULL OR query_kind = $7)
+                    AND NOT EXISTS (
+                        SELECT 1 FROM query_mutes q
+                        WHERE q.workspace_id = f.workspace_id
+                            AND q.fingerprint = f.fingerprint
+                            AND (q.expires_at IS NULL OR q.expires_at > NOW())
+                    )
+                GROUP BY fingerprint
+            )
+            SELECT
+                s.*,
+                s.error_count::FLOAT8 / s.call_count AS error_rate,
+                o.fingerprint AS owner_fingerprint, o.owner_team,
+                o.ticket_links AS owner_ticket_links, o.notes AS owner_notes,
+                o.updated_at AS owner_updated_at
+            FROM stats s
+            LEFT JOIN query_ownership o
+                ON o.workspace_id = $1 AND o.fingerprint = s.fingerprint
+            ORDER BY {} DESC, s.fingerprint
+            LIMIT $5 OFFSET $6
+            "#,
+            query.sort.column()
+        );
+        let rows = sqlx::query(&sql)
+            .bind(workspace_id)
+            .bind(query.from)
+            .bind(query.to)
+            .bind(query.service_id)
+            .bind(query.limit)
+            .bind(query.offset)
+            .bind(query.query_kind.map(|k| k.as_str()))
+            .fetch_all(&self.read_pool)
+            .await?;
+
+        let stats = rows
+            .into_iter()
+            .map(|row| FingerprintStats {
+                fingerprint: row.get("fingerprint"),
+                query_text: row.get("query_text"),
+                call_count: row.get("call_count"),
+                error_count: row.get("error_count"),
+                error_rate: row.get("error_rate"),
+                total_duration_ms: row.get("total_duration_ms"),
+                p50_duration_ms: row.get("p50_duration_ms"),
+                p95_duration_ms: row.get("p95_duration_ms"),
+                p99_duration_ms: row.get("p99_duration_ms"),
+                first_seen: row.get("first_seen"),
+                last_seen: row.get("last_seen"),
+                ownership: ownership_from_row(&row),
+            })
+            .collect();
+
+        Ok(stats)
+    }
+
+    /// Get execution totals per fingerprint and service from the hourly
+    /// rollups, over the range widened to whole hours
+    async fn get_statement_totals(
+        &self,
+        workspace_id: Uuid,
+        service_id: Option<Uuid>,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<StatementTotals>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                fingerprint,
+                service_id,
+                MIN(query_text) AS query_text,
+                SUM(call_count)::BIGINT AS call_count,
+                SUM(error_count)::BIGINT AS error_count,
+                SUM(total_duration_ms)::BIGINT AS total_duration_ms,
+                MAX(max_duration_ms) AS max_duration_ms
+            FROM fingerprint_rollups
+            WHERE workspace_id = $1
+                AND bucket >= time_bucket('1 hour', $2::TIMESTAMPTZ) AND bucket < $3
+                AND ($4::UUID IS NULL OR service_id = $4)
+                AND ($6::TEXT IS NULL OR query_kind = $6)
+            GROUP BY 1, 2
+            ORDER BY call_count DESC, 1, 2
+            LIMIT $5
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(from)
+        .bind(to)
+        .bind(service_id)
+        .bind(limit)
+        .bind(query_kind.map(|k| k.as_str()))
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        let totals = rows
+            .into_iter()
+            .map(|row| StatementTotals {
+                fingerprint: row.get("fingerprint"),
+                service_id: row.get("service_id"),
+                query_text: row.get("query_text"),
+                call_count: row.get("call_count"),
+                error_count: row.get("error_count"),
+                total_duration_ms: row.get("total_duration_ms"),
+                max_duration_ms: row.get("max_duration_ms"),
+            })
+            .collect();
+
+        Ok(totals)
+    }
+
+    /// Get robust duration statistics per service or fingerprint from the raw
+    /// metrics, slowest maximum first
+    async fn get_duration_stats(
+        &self,
+        workspace_id: Uuid,
+        query: &DurationStatsQuery,
+    ) -> Result<Vec<DurationStats>> {
+        // Only the returned groups get the second pass for their MAD and
+        // slowest execution
+        let read = RawRead::new(&format!(
+            r#"
+            WITH m AS (
+                SELECT {} AS grp, id, duration_ms
+                FROM {{raw}} r
+                WHERE workspace_id = $1
+                    AND ($2::UUID IS NULL OR service_id = $2)
+                    AND ($4::TEXT IS NULL OR query_kind = $4)
+            ),
+            s AS (
+                SELECT
+                    grp,
+                    COUNT(*) AS query_count,
+                    PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY duration_ms) AS median_ms,
+                    PERCENTILE_CONT(0.999) WITHIN GROUP (ORDER BY duration_ms) AS p999_ms,
+                    MAX(duration_ms) AS max_ms
+                FROM m
+                GROUP BY grp
+                ORDER BY max_ms DESC, grp
+                LIMIT $3
+            )
+            SELECT s.grp, s.query_count, s.median_ms, s.p999_ms, s.max_ms, d.mad_ms,
+                slowest.id AS max_metric_id
+            FROM s
+            CROSS JOIN LATERAL (
+                SELECT PERCENTILE_CONT(0.5) WITHIN GROUP (
+                    ORDER BY ABS(m.duration_ms - s.median_ms)
+                ) AS mad_ms
+                FROM m
+                WHERE m.grp = s.grp
+            ) d
+            CROSS JOIN LATERAL (
+                SELECT m.id FROM m WHERE m.grp = s.grp ORDER BY m.duration_ms DESC, m.id LIMIT 1
+            ) slowest
+            ORDER BY s.max_ms DESC, s.grp
+            "#,
+            query.group_by.column()
+        ))
+        .range(self.raw_range(query.from, query.to));
+        let rows = read
+            .bind(
+                sqlx::query(read.sql())
+                    .bind(workspace_id)
+                    .bind(query.service_id)
+                    .bind(query.limit)
+                    .bind(query.query_kind.map(|k| k.as_str())),
+            )
+            .fetch_all(&self.read_pool)
+            .await?;
+
+        let stats = rows
+            .into_iter()
+            .map(|row| {
+                let median_ms: f64 = row.get("median_ms");
+                let mad_ms: f64 = row.get("mad_ms");
+                let max_ms: i64 = row.get("max_ms");
+                DurationStats {
+                    group: row.get("grp"),
+                    query_count: row.get("query_count"),
+                    median_ms,
+                    mad_ms,
+                    p999_ms: row.get("p999_ms"),
+                    max_ms,
+                    max_metric_id: row.get("max_metric_id"),
+                    max_score: modified_z_score(max_ms as f64, median_ms, mad_ms),
+                }
+            })
+            .collect();
+
+        Ok(stats)
+    }
+
+    /// Group failed metrics by error fingerprint, with the prior range's counts
+    async fn get_error_groups(
+        &self,
+        workspace_id: Uuid,
+        query: &ErrorGroupsQuery,
+    ) -> Result<Vec<ErrorGroup>> {
+        // Rows stored before error fingerprints were kept are grouped by their
+        // exact message instead
+        let read = RawRead::new(
+            r#"
+            WITH errors AS (
+                SELECT
+                    COALESCE(error_fingerprint, md5(COALESCE(error_message, '')))
+                        AS error_fingerprint,
+                    error_message,
+                    COALESCE(fingerprint, md5(normalize_sql(query_text))) AS fingerprint,
+                    created_at,
+                    created_at >= $2 AS in_range
+                FROM {raw} m
+                WHERE workspace_id = $1
+                    AND status IN ('failed', 'timeout')
+                    AND ($3::UUID IS NULL OR service_id = $3)
+                    AND ($6::TEXT IS NULL OR query_kind = $6)
+            ),
+            queries AS (
+                SELECT error_fingerprint, fingerprint, COUNT(*) AS error_count
+                FROM errors
+                WHERE in_range
+                GROUP BY 1, 2
+            ),
+            groups AS (
+                SELECT
+                    error_fingerprint,
+                    MIN(error_message) FILTER (WHERE in_range) AS example,
+                    COUNT(*) FILTER (WHERE in_range) AS error_count,
+                    COUNT(*) FILTER (WHERE NOT in_range) AS previous_count,
+                    MIN(created_at) AS earliest,
+                    MAX(created_at) AS last_seen
+                FROM errors
+                GROUP BY 1
+                HAVING COUNT(*) FILTER (WHERE in_range) > 0
+            )
+            SELECT
+                g.*,
+                COALESCE(
+                    (SELECT MIN(m.created_at) FROM {raw} m
+                     WHERE m.workspace_id = $1
+                        AND m.error_fingerprint = g.error_fingerprint),
+                    g.earliest
+                ) AS first_seen,
+                (SELECT COUNT(*) FROM queries q
+                 WHERE q.error_fingerprint = g.error_fingerprint) AS query_count,
+                ARRAY(
+                    SELECT q.fingerprint FROM queries q
+                    WHERE q.error_fingerprint = g.error_fingerprint
+                    ORDER BY q.error_count DESC, q.fingerprint
+                    LIMIT $5
+                ) AS fingerprints
+            FROM groups g
+            ORDER BY g.error_count DESC, g.last_seen DESC
+            LIMIT $4
+            "#,
+        )
+        .range(self.raw_range(query.from - (query.to - query.from), query.to))
+        .range(self.raw_retained());
+        let rows = read
+            .bind(
+                sqlx::query(read.sql())
+                    .bind(workspace_id)
+                    .bind(query.from)
+                    .bind(query.service_id)
+                    .bind(query.limit)
+                    .bind(ERROR_GROUP_FINGERPRINTS as i64)
+                    .bind(query.query_kind.map(|k| k.as_str())),
+            )
+            .fetch_all(&self.read_pool)
+            .await?;
+
+        let groups = rows
+            .into_iter()
+            .map(|row| {
+                let example: Option<String> = row.get("example");
+                ErrorGroup {
+                    error_fingerprint: row.get("error_fingerprint"),
+                    message: normalize_error(example.as_deref().unwrap_or_default()),
+                    example,
+                    count: row.get("error_count"),
+                    previous_count: row.get("previous_count"),
+                    first_seen: row.get("first_seen"),
+                    last_seen: row.get("last_seen"),
+                    query_count: row.get("query_count"),
+                    fingerprints: row.get("fingerprints"),
+                }
+            })
+            .collect();
+
+        Ok(groups)
+    }
+
+    // =========================================================================
+    // SERVICE REGISTRY METHODS
+    // =========================================================================
+
+    /// List registered services, most recently seen first
+    async fn list_services(&self, workspace_id: Uuid) -> Result<Vec<Service>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, workspace_id, name, description, created_at, updated_at, last_seen_at
+            FROM services
+            WHERE workspace_id = $1
+            ORDER BY last_seen_at DESC NULLS LAST, name ASC
+            "#,
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        Ok(rows.iter().map(service_from_row).collect())
+    }
+
+    /// Get a registered service
+    async fn get_service(&self, workspace_id: Uuid, service_id: Uuid) -> Result<Option<Service>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, workspace_id, name, description, created_at, updated_at, last_seen_at
+            FROM services
+            WHERE workspace_id = $1 AND id = $2
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(service_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(service_from_row))
+    }
+
+    /// Register a service
+    async fn create_service(&self, service: &Service) -> Result<Service> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO services (id, workspace_id, name, description)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, workspace_id, name, description, created_at, updated_at, last_seen_at
+            "#,
+        )
+        .bind(service.id)
+        .bind(service.workspace_id)
+        .bind(&service.name)
+        .bind(&service.description)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| service_conflict(e, &service.name))?;
+
+        Ok(service_from_row(&row))
+    }
+
+    /// Rename or re-describe a service
+    async fn update_service(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<Option<Service>> {
+        let row = sqlx::query(
+            r#"
+            UPDATE services
+            SET name = $3, description = $4, updated_at = NOW()
+            WHERE workspace_id = $1 AND id = $2
+            RETURNING id, workspace_id, name, description, created_at, updated_at, last_seen_at
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(service_id)
+        .bind(name)
+        .bind(description)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| service_conflict(e, name))?;
+
+        Ok(row.as_ref().map(service_from_row))
+    }
+
+    /// Delete a service registration
+    async fn delete_service(&self, workspace_id: Uuid, service_id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM services WHERE workspace_id = $1 AND id = $2")
+            .bind(workspace_id)
+            .bind(service_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // =========================================================================
+    // SERVICE OVERVIEW METHODS
+    // =========================================================================
+
+    /// Get the unmuted fingerprints with the most total execution time for a
+    /// service, from the hourly rollups over the range widened to whole hours
+    async fn get_top_fingerprints(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<FingerprintSummary>> {
+        let rows = sqlx::query(
+            r#"
+            WITH top AS (
+                SELECT
+                    fingerprint,
+                    MIN(query_text) AS query_text,
+                    SUM(call_count)::BIGINT AS call_count,
+                    SUM(total_duration_ms)::BIGINT AS total_duration_ms,
+                    (SUM(total_duration_ms) / SUM(call_count))::BIGINT AS avg_duration_ms,
+                    (SUM(p95_duration_ms * call_count) / SUM(call_count))::BIGINT
+                        AS p95_duration_ms,
+                    SUM(failed_count)::BIGINT AS failed_count
+                FROM fingerprint_rollups f
+                WHERE workspace_id = $1 AND service_id = $2
+                    AND bucket >= time_bucket('1 hour', $3::TIMESTAMPTZ) AND bucket < $4
+                    AND ($6::TEXT IS NULL OR query_kind = $6)
+                    AND NOT EXISTS (
+                        SELECT 1 FROM query_mutes q
+                        WHERE q.workspace_id = f.workspace_id
+                            AND q.fingerprint = f.fingerprint
+                            AND (q.expires_at IS NULL OR q.expires_at > NOW())
+                    )
+                GROUP BY fingerprint
+                ORDER BY total_duration_ms DESC
+                LIMIT $5
+            )
+            SELECT
+                t.*,
+                o.fingerprint AS owner_fingerprint, o.owner_team,
+                o.ticket_links AS owner_ticket_links, o.notes AS owner_notes,
+                o.updated_at AS owner_updated_at
+            FROM top t
+            LEFT JOIN query_ownership o
+                ON o.workspace_id = $1 AND o.fingerprint = t.fingerprint
+            ORDER BY t.total_duration_ms DESC
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(service_id)
+        .bind(from)
+        .bind(to)
+        .bind(limit)
+        .bind(query_kind.map(|k| k.as_str()))
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        let summaries = rows
+            .into_iter()
+            .map(|row| FingerprintSummary {
+                fingerprint: row.get("fingerprint"),
+                query_text: row.get("query_text"),
+                call_count: row.get("call_count"),
+                total_duration_ms: row.get("total_duration_ms"),
+                avg_duration_ms: row.get("avg_duration_ms"),
+                p95_duration_ms: row.get("p95_duration_ms"),
+                failed_count: row.get("failed_count"),
+                ownership: ownership_from_row(&row),
+            })
+            .collect();
+
+        Ok(summaries)
+    }
+
+    /// Get the most frequent failures for a service, grouped by fingerprint and error
+    async fn get_top_errors(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ErrorSummary>> {
+        let read = RawRead::new(
+            r#"
+            SELECT
+                COALESCE(fingerprint, md5(normalize_sql(query_text))) AS fingerprint,
+                MIN(query_text) AS query_text,
+                error_message,
+                COUNT(*) AS error_count,
+                MAX(created_at) AS last_seen
+            FROM {raw} m
+            WHERE workspace_id = $1 AND service_id = $2
+                AND status IN ('failed', 'timeout')
+                AND ($4::TEXT IS NULL OR query_kind = $4)
+            GROUP BY 1, error_message
+            ORDER BY error_count DESC, last_seen DESC
+            LIMIT $3
+            "#,
+        )
+        .range(self.raw_range(from, to));
+        let rows = read
+            .bind(
+                sqlx::query(read.sql())
+                    .bind(workspace_id)
+                    .bind(service_id)
+                    .bind(limit)
+                    .bind(query_kind.map(|k| k.as_str())),
+            )
+            .fetch_all(&self.read_pool)
+            .await?;
+
+        let errors = rows
+            .into_iter()
+            .map(|row| ErrorSummary {
+                fingerprint: row.get("fingerprint"),
+                query_text: row.get("query_text"),
+                error_message: row.get("error_message"),
+                count: row.get("error_count"),
+                last_seen: row.get("last_seen"),
+            })
+            .collect();
+
+        Ok(errors)
+    }
+
+    /// Get the most recent anomalies for a service detected in the range;
+    /// a kind matches those whose metric is still stored
+    async fn get_service_anomalies(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<AnomalyRecord>> {
+        let read = RawRead::new(
+            r#"
+            SELECT
+                a.id, a.workspace_id, a.service_id, a.metric_id,
+                a.fingerprint, a.query_text,
+                a.duration_ms, a.mean_duration_ms, a.stddev_duration_ms, a.z_score, a.method,
+                a.severity, a.severity_level, a.estimated_extra_ms,
+                a.detected_at, a.status, a.acknowledged_by, a.resolved_at,
+                o.fingerprint AS owner_fingerprint, o.owner_team,
+                o.ticket_links AS owner_ticket_links, o.notes AS owner_notes,
+                o.updated_at AS owner_updated_at
+            FROM query_anomalies a
+            LEFT JOIN query_ownership o
+                ON o.workspace_id = a.workspace_id
+                AND o.fingerprint = a.fingerprint
+            WHERE a.workspace_id = $1 AND a.service_id = $2
+                AND a.detected_at >= $3 AND a.detected_at < $6
+                AND (
+                    $5::TEXT IS NULL
+                    OR EXISTS (
+                        SELECT 1 FROM {raw} m
+                        WHERE m.id = a.metric_id AND m.query_kind = $5
+                    )
+                )
+            ORDER BY a.detected_at DESC
+            LIMIT $4
+            "#,
+        )
+        .range(self.raw_retained());
+        let rows = read
+            .bind(
+                sqlx::query(read.sql())
+                    .bind(workspace_id)
+                    .bind(service_id)
+                    .bind(from)
+                    .bind(limit)
+                    .bind(query_kind.map(|k| k.as_str()))
+                    .bind(to),
+            )
+            .fetch_all(&self.read_pool)
+            .await?;
+
+        Ok(rows.iter().map(anomaly_from_row).collect())
+    }
+
+    // =========================================================================
+    // OWNERSHIP METHODS
+    // =========================================================================
+
+    /// Create or replace the ownership annotation for a fingerprint
+    async fn upsert_ownership(
+        &self,
+        workspace_id: Uuid,
+        ownership: &QueryOwnership,
+    ) -> Result<QueryOwnership> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO query_ownership (workspace_id, fingerprint, owner_team, ticket_links, notes)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (workspace_id, fingerprint) DO UPDATE SET
+                owner_team = EXCLUDED.owner_team,
+                ticket_links = EXCLUDED.ticket_links,
+                notes = EXCLUDED.notes,
+                updated_at = NOW()
+            RETURNING fingerprint AS owner_fingerprint, owner_team,
+                ticket_links AS owner_ticket_links, notes AS owner_notes,
+                updated_at AS owner_updated_at
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(&ownership.fingerprint)
+        .bind(&ownership.owner_team)
+        .bind(&ownership.ticket_links)
+        .bind(&ownership.notes)
+        .fetch_one(&self.pool)
+        .await?;
+
+        ownership_from_row(&row)
+            .ok_or_else(|| AppError::InternalError("Ownership upsert returned no row".into()))
+    }
+
+    /// Get the ownership annotation for a fingerprint
+    async fn get_ownership(
+        &self,
+        workspace_id: Uuid,
+        fingerprint: &str,
+    ) -> Result<Option<QueryOwnership>> {
+        let row = sqlx::query(
+            r#"
+            SELECT fingerprint AS owner_fingerprint, owner_team,
+                ticket_links AS owner_ticket_links, notes AS owner_notes,
+                updated_at AS owner_updated_at
+            FROM query_ownership
+            WHERE workspace_id = $1 AND fingerprint = $2
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(fingerprint)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().and_then(ownership_from_row))
+    }
+
+    /// List all ownership annotations in a workspace
+    async fn list_ownership(&self, workspace_id: Uuid) -> Result<Vec<QueryOwnership>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT fingerprint AS owner_fingerprint, owner_team,
+                ticket_links AS owner_ticket_links, notes AS owner_notes,
+                updated_at AS owner_updated_at
+            FROM query_ownership
+            WHERE workspace_id = $1
+            ORDER BY updated_at DESC
+            "#,
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().filter_map(ownership_from_row).collect())
+    }
+
+    /// Delete the ownership annotation for a fingerprint, returning whether it existed
+    async fn delete_ownership(&self, workspace_id: Uuid, fingerprint: &str) -> Result<bool> {
+        let result =
+            sqlx::query("DELETE FROM query_ownership WHERE workspace_id = $1 AND fingerprint = $2")
+                .bind(workspace_id)
+                .bind(fingerprint)
+                .execute(&self.pool)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // =========================================================================
+    // MUTE METHODS
+    // =========================================================================
+
+    /// Mute a fingerprint, replacing any existing mute
+    async fn upsert_mute(&self, workspace_id: Uuid, mute: &QueryMute) -> Result<QueryMute> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO query_mutes (workspace_id, fingerprint, reason, expires_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (workspace_id, fingerprint) DO UPDATE SET
+                reason = EXCLUDED.reason,
+                expires_at = EXCLUDED.expires_at,
+                created_at = NOW()
+            RETURNING fingerprint, reason, expires_at, created_at
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(&mute.fingerprint)
+        .bind(&mute.reason)
+        .bind(mute.expires_at)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(QueryMute {
+            fingerprint: row.get("fingerprint"),
+            reason: row.get("reason"),
+            expires_at: row.get("expires_at"),
+            created_at: row.get("created_at"),
+        })
+    }
+
+    /// List mutes in a workspace that have not expired
+    async fn list_active_mutes(&self, workspace_id: Uuid) -> Result<Vec<QueryMute>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT fingerprint, reason, expires_at, created_at
+            FROM query_mutes
+            WHERE workspace_id = $1 AND (expires_at IS NULL OR expires_at > NOW())
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mutes = rows
+            .into_iter()
+            .map(|row| QueryMute {
+                fingerprint: row.get("fingerprint"),
+                reason: row.get("reason"),
+                expires_at: row.get("expires_at"),
+                created_at: row.get("created_at"),
+            })
+            .collect();
+
+        Ok(mutes)
+    }
+
+    /// Remove a mute, returning whether it existed
+    async fn delete_mute(&self, workspace_id: Uuid, fingerprint: &str) -> Result<bool> {
+        let result =
+            sqlx::query("DELETE FROM query_mutes WHERE workspace_id = $1 AND fingerprint = $2")
+                .bind(workspace_id)
+                .bind(fingerprint)
+                .execute(&self.pool)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // =========================================================================
+    // ANNOTATION METHODS
+    // =========================================================================
+
+    /// Record a workspace event annotation
+    async fn insert_annotation(
+        &self,
+        workspace_id: Uuid,
+        annotation: &Annotation,
+    ) -> Result<Annotation> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO workspace_annotations
+                (id, workspace_id, kind, title, description, starts_at, ends_at, tags)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, kind, title, description, starts_at, ends_at, tags, created_at
+            "#,
+        )
+        .bind(annotation.id)
+        .bind(workspace_id)
+        .bind(kind_to_string(annotation.kind))
+        .bind(&annotation.title)
+        .bind(&annotation.description)
+        .bind(annotation.starts_at)
+        .bind(annotation.ends_at)
+        .bind(&annotation.tags)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(annotation_from_row(&row))
+    }
+
+    /// List annotations overlapping the given time range
+    async fn list_annotations(
+        &self,
+        workspace_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Annotation>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, kind, title, description, starts_at, ends_at, tags, created_at
+            FROM workspace_annotations
+            WHERE workspace_id = $1
+                AND starts_at < $3
+                AND COALESCE(ends_at, starts_at) >= $2
+            ORDER BY starts_at ASC
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        Ok(rows.iter().map(annotation_from_row).collect())
+    }
+
+    /// Delete an annotation
+    async fn delete_annotation(&self, workspace_id: Uuid, annotation_id: Uuid) -> Result<bool> {
+        let result =
+            sqlx::query("DELETE FROM workspace_annotations WHERE workspace_id = $1 AND id = $2")
+                .bind(workspace_id)
+                .bind(annotation_id)
+                .execute(&self.pool)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // =========================================================================
+    // AUDIT LOG METHODS
+    // =========================================================================
+
+    /// Record an administrative change in the audit log
+    async fn insert_audit_entry(&self, entry: &AuditEntry) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO audit_log
+                (id, workspace_id, action, actor_key_id, actor_name, target, payload, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(entry.id)
+        .bind(entry.workspace_id)
+        .bind(entry.action.as_str())
+        .bind(entry.actor_key_id)
+        .bind(&entry.actor_name)
+        .bind(&entry.target)
+        .bind(Json(&entry.payload))
+        .bind(entry.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// List audit entries created within a time range, newest first
+    async fn list_audit_entries(
+        &self,
+        workspace_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        action: Option<AuditAction>,
+        limit: i64,
+    ) -> Result<Vec<AuditEntry>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, workspace_id, action, actor_key_id, actor_name, target, payload, created_at
+            FROM audit_log
+            WHERE workspace_id = $1
+                AND created_at >= $2 AND created_at < $3
+                AND ($4::TEXT IS NULL OR action = $4)
+            ORDER BY created_at DESC
+            LIMIT $5
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(from)
+        .bind(to)
+        .bind(action.map(|a| a.as_str()))
+        .bind(limit)
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        // Actions added by a newer release are skipped rather than failing the listing
+        Ok(rows.iter().filter_map(audit_from_row).collect())
+    }
+
+    // =========================================================================
+    // USAGE METHODS
+    // =========================================================================
+
+    /// Get daily usage for the days in `[from, to]`, oldest first
+    async fn get_usage(
+        &self,
+        workspace_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<UsageDay>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT day, metric_count, stored_bytes
+            FROM workspace_usage
+            WHERE workspace_id = $1 AND day >= $2 AND day <= $3
+            ORDER BY day ASC
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| UsageDay {
+                day: row.get("day"),
+                metric_count: row.get("metric_count"),
+                stored_bytes: row.get("stored_bytes"),
+            })
+            .collect())
+    }
+
+    // =========================================================================
+    // INGESTION STATS METHODS
+    // =========================================================================
+
+    /// Add per-minute ingest counts to the stored ones
+    async fn record_ingestion_stats(&self, rows: &[(Uuid, IngestionMinute)]) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let workspace_ids: Vec<Uuid> = rows.iter().map(|(ws, _)| *ws).collect();
+        let minutes: Vec<DateTime<Utc>> = rows.iter().map(|(_, r)| r.minute).collect();
+        let accepted: Vec<i64> = rows.iter().map(|(_, r)| r.accepted).collect();
+        let dropped: Vec<i64> = rows.iter().map(|(_, r)| r.dropped).collect();
+        let deduplicated: Vec<i64> = rows.iter().map(|(_, r)| r.deduplicated).collect();
+
+        sqlx::query(
+            r#"
+            INSERT INTO ingestion_stats (workspace_id, minute, accepted, dropped, deduplicated)
+            SELECT workspace_id, minute, SUM(accepted), SUM(dropped), SUM(deduplicated)
+            FROM UNNEST($1::UUID[], $2::TIMESTAMPTZ[], $3::BIGINT[], $4::BIGINT[], $5::BIGINT[])
+                AS batch(workspace_id, minute, accepted, dropped, deduplicated)
+            GROUP BY 1, 2
+            ON CONFLICT (workspace_id, minute) DO UPDATE SET
+                accepted = ingestion_stats.accepted + EXCLUDED.accepted,
+                dropped = ingestion_stats.dropped + EXCLUDED.dropped,
+                deduplicated = ingestion_stats.deduplicated + EXCLUDED.deduplicated
+            "#,
+        )
+        .bind(&workspace_ids)
+        .bind(&minutes)
+        .bind(&accepted)
+        .bind(&dropped)
+        .bind(&deduplicated)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get a workspace's ingest counts for minutes at or after `since`
+    async fn get_ingestion_stats(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<IngestionMinute>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT minute, accepted, dropped, deduplicated
+            FROM ingestion_stats
+            WHERE workspace_id = $1 AND minute >= $2
+            ORDER BY minute ASC
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| IngestionMinute {
+                minute: row.get("minute"),
+                accepted: row.get("accepted"),
+                dropped: row.get("dropped"),
+                deduplicated: row.get("deduplicated"),
+            })
+            .collect())
+    }
+
+    /// Get the last minute each workspace had metrics accepted
+    async fn get_last_ingest(&self, since: DateTime<Utc>) -> Result<HashMap<Uuid, DateTime<Utc>>> {
+        let rows: Vec<(Uuid, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT workspace_id, MAX(minute)
+            FROM ingestion_stats
+            WHERE minute >= $1 AND accepted > 0
+            GROUP BY workspace_id
+            "#,
+        )
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().collect())
+    }
+
+    /// Delete ingest counts older than the given number of days
+    async fn prune_ingestion_stats(&self, older_than_days: i32) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM ingestion_stats
+            WHERE minute < NOW() - make_interval(days => $1)
+            "#,
+        )
+        .bind(older_than_days)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // =========================================================================
+    // ALERT RULE METHODS
+    // =========================================================================
+
+    /// Store a new alert rule
+    async fn create_alert_rule(&self, rule: &AlertRule) -> Result<AlertRule> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO alert_rules
+                (id, workspace_id, name, service_id, window_size, conditions, combine,
+                 for_windows, resolve_windows, enabled)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            RETURNING id, workspace_id, name, service_id, window_size, conditions, combine,
+                for_windows, resolve_windows, enabled, created_at, updated_at
+            "#,
+        )
+        .bind(rule.id)
+        .bind(rule.workspace_id)
+        .bind(&rule.name)
+        .bind(rule.service_id)
+        .bind(&rule.window)
+        .bind(Json(&rule.conditions))
+        .bind(rule.combine.as_str())
+        .bind(rule.for_windows as i32)
+        .bind(rule.resolve_windows as i32)
+        .bind(rule.enabled)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(alert_rule_from_row(&row))
+    }
+
+    /// List a workspace's alert rules, oldest first
+    async fn list_alert_rules(&self, workspace_id: Uuid) -> Result<Vec<AlertRule>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, workspace_id, name, service_id, window_size, conditions, combine,
+                for_windows, resolve_windows, enabled, created_at, updated_at
+            FROM alert_rules
+            WHERE workspace_id = $1
+            ORDER BY created_at ASC
+            "#,
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        Ok(rows.iter().map(alert_rule_from_row).collect())
+    }
+
+    /// Get an alert rule
+    async fn get_alert_rule(&self, workspace_id: Uuid, rule_id: Uuid) -> Result<Option<AlertRule>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, workspace_id, name, service_id, window_size, conditions, combine,
+                for_windows, resolve_windows, enabled, created_at, updated_at
+            FROM alert_rules
+            WHERE workspace_id = $1 AND id = $2
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(rule_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(alert_rule_from_row))
+    }
+
+    /// Replace an alert rule's definition
+    async fn update_alert_rule(&self, rule: &AlertRule) -> Result<Option<AlertRule>> {
+        let row = sqlx::query(
+            r#"
+            UPDATE alert_rules
+            SET name = $3, service_id = $4, window_size = $5, conditions = $6, combine = $7,
+                for_windows = $8, resolve_windows = $9, enabled = $10, updated_at = NOW()
+            WHERE workspace_id = $1 AND id = $2
+            RETURNING id, workspace_id, name, service_id, window_size, conditions, combine,
+                for_windows, resolve_windows, enabled, created_at, updated_at
+            "#,
+        )
+        .bind(rule.workspace_id)
+        .bind(rule.id)
+        .bind(&rule.name)
+        .bind(rule.service_id)
+        .bind(&rule.window)
+        .bind(Json(&rule.conditions))
+        .bind(rule.combine.as_str())
+        .bind(rule.for_windows as i32)
+        .bind(rule.resolve_windows as i32)
+        .bind(rule.enabled)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(alert_rule_from_row))
+    }
+
+    /// Delete an alert rule
+    async fn delete_alert_rule(&self, workspace_id: Uuid, rule_id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM alert_rules WHERE workspace_id = $1 AND id = $2")
+            .bind(workspace_id)
+            .bind(rule_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// List enabled alert rules across all workspaces
+    async fn list_enabled_alert_rules(&self) -> Result<Vec<AlertRule>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, workspace_id, name, service_id, window_size, conditions, combine,
+                for_windows, resolve_windows, enabled, created_at, updated_at
+            FROM alert_rules
+            WHERE enabled
+            "#,
+        )
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        Ok(rows.iter().map(alert_rule_from_row).collect())
+    }
+
+    /// Get the persisted evaluation state of every alert rule
+    async fn list_alert_rule_states(&self) -> Result<Vec<AlertRuleState>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT rule_id, rule_version, firing, breaches, clears, last_bucket
+            FROM alert_rule_state
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| AlertRuleState {
+                rule_id: row.get("rule_id"),
+                rule_version: row.get("rule_version"),
+                state: RuleState {
+                    firing: row.get("firing"),
+                    breaches: row.get::<i32, _>("breaches") as u32,
+                    clears: row.get::<i32, _>("clears") as u32,
+                    last_bucket: row.get("last_bucket"),
+                },
+            })
+            .collect())
+    }
+
+    /// Save an alert rule's evaluation state
+    async fn upsert_alert_rule_state(&self, state: &AlertRuleState) -> Result<()> {
+        // The rule may have been deleted since it was evaluated
+        sqlx::query(
+            r#"
+            INSERT INTO alert_rule_state
+                (rule_id, rule_version, firing, breaches, clears, last_bucket)
+            SELECT $1, $2, $3, $4, $5, $6
+            WHERE EXISTS (SELECT 1 FROM alert_rules WHERE id = $1)
+            ON CONFLICT (rule_id) DO UPDATE SET
+                rule_version = EXCLUDED.rule_version,
+                firing = EXCLUDED.firing,
+                breaches = EXCLUDED.breaches,
+                clears = EXCLUDED.clears,
+                last_bucket = EXCLUDED.last_bucket,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(state.rule_id)
+        .bind(state.rule_version)
+        .bind(state.state.firing)
+        .bind(state.state.breaches as i32)
+        .bind(state.state.clears as i32)
+        .bind(state.state.last_bucket)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // =========================================================================
+    // ALERT DESTINATION METHODS
+    // =========================================================================
+
+    /// Store a new alert destination
+    async fn create_alert_destination(
+        &self,
+        destination: &AlertDestination,
+    ) -> Result<AlertDestination> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO alert_destinations
+                (id, workspace_id, name, kind, url, signing_secret, routing_key, min_severity,
+                enabled)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING id, workspace_id, name, kind, url, signing_secret, routing_key,
+                min_severity, enabled, created_at
+            "#,
+        )
+        .bind(destination.id)
+        .bind(destination.workspace_id)
+        .bind(&destination.name)
+        .bind(destination.kind.as_str())
+        .bind(self.seal_secret(&destination.url))
+        .bind(
+            destination
+                .signing_secret
+                .as_deref()
+                .map(|secret| self.seal_secret(secret)),
+        )
+        .bind(
+            destination
+                .routing_key
+                .as_deref()
+                .map(|key| self.seal_secret(key)),
+        )
+        .bind(destination.min_severity.as_str())
+        .bind(destination.enabled)
+        .fetch_one(&self.pool)
+        .await?;
+
+        self.alert_destination_from_row(&row)
+    }
+
+    /// List a workspace's alert destinations, oldest first
+    async fn list_alert_destinations(&self, workspace_id: Uuid) -> Result<Vec<AlertDestination>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, workspace_id, name, kind, url, signing_secret, routing_key,
+                min_severity, enabled, created_at
+            FROM alert_destinations
+            WHERE workspace_id = $1
+            ORDER BY created_at ASC
+            "#,
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| self.alert_destination_from_row(row))
+            .collect()
+    }
+
+    /// Get an alert destination
+    async fn get_alert_destination(
+        &self,
+        workspace_id: Uuid,
+        destination_id: Uuid,
+    ) -> Result<Option<AlertDestination>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, workspace_id, name, kind, url, signing_secret, routing_key,
+                min_severity, enabled, created_at
+            FROM alert_destinations
+            WHERE workspace_id = $1 AND id = $2
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(destination_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.as_ref()
+            .map(|row| self.alert_destination_from_row(row))
+            .transpose()
+    }
+
+    /// Delete an alert destination
+    async fn delete_alert_destination(
+        &self,
+        workspace_id: Uuid,
+        destination_id: Uuid,
+    ) -> Result<bool> {
+        let result =
+            sqlx::query("DELETE FROM alert_destinations WHERE workspace_id = $1 AND id = $2")
+                .bind(workspace_id)
+                .bind(destination_id)
+                .execute(&self.pool)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record an attempt to send an alert
+    async fn insert_alert_delivery(&self, delivery: &AlertDelivery) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO alert_deliveries
+                (id, workspace_id, destination_id, event, test, delivered, status,
+                 latency_ms, response, error, attempts, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            "#,
+        )
+        .bind(delivery.id)
+        .bind(delivery.workspace_id)
+        .bind(delivery.destination_id)
+        .bind(&delivery.event)
+        .bind(delivery.test)
+        .bind(delivery.delivered)
+        .bind(delivery.status.map(i32::from))
+        .bind(delivery.latency_ms as i64)
+        .bind(&delivery.response)
+        .bind(&delivery.error)
+        .bind(delivery.attempts as i32)
+        .bind(delivery.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// List delivery attempts, newest first
+    async fn list_alert_deliveries(
+        &self,
+        workspace_id: Uuid,
+        destination_id: Option<Uuid>,
+        failed_only: bool,
+        limit: i64,
+    ) -> Result<Vec<AlertDelivery>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, workspace_id, destination_id, event, test, delivered, status,
+                latency_ms, response, error, attempts, created_at
+            FROM alert_deliveries
+            WHERE workspace_id = $1
+                AND ($2::uuid IS NULL OR destination_id = $2)
+                AND (NOT $3 OR NOT delivered)
+            ORDER BY created_at DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(destination_id)
+        .bind(failed_only)
+        .bind(limit)
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| AlertDelivery {
+                id: row.get("id"),
+                workspace_id: row.get("workspace_id"),
+                destination_id: row.get("destination_id"),
+                event: row.get("event"),
+                test: row.get("test"),
+                delivered: row.get("delivered"),
+                status: row.get::<Option<i32>, _>("status").map(|s| s as u16),
+                latency_ms: row.get::<i64, _>("latency_ms") as u64,
+                response: row.get("response"),
+                error: row.get("error"),
+                attempts: row.get::<i32, _>("attempts") as u32,
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+
+    /// Delete delivery attempts older than the given number of days
+    async fn prune_alert_deliveries(&self, older_than_days: i32) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM alert_deliveries
+            WHERE created_at < NOW() - make_interval(days => $1)
+            "#,
+        )
+        .bind(older_than_days)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    // =========================================================================
+    // SLO METHODS
+    // =========================================================================
+
+    /// Store a new SLO
+    async fn create_slo(&self, slo: &Slo) -> Result<Slo> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO slos
+                (id, workspace_id, name, service_id, threshold_ms, target_percent, window_days,
+                 enabled)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, workspace_id, name, service_id, threshold_ms, target_percent,
+                window_days, enabled, created_at, updated_at
+            "#,
+        )
+        .bind(slo.id)
+        .bind(slo.workspace_id)
+        .bind(&slo.name)
+        .bind(slo.service_id)
+        .bind(slo.threshold_ms)
+        .bind(slo.target_percent)
+        .bind(slo.window_days as i32)
+        .bind(slo.enabled)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(slo_from_row(&row))
+    }
+
+    /// List a workspace's SLOs, oldest first
+    async fn list_slos(&self, workspace_id: Uuid) -> Result<Vec<Slo>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, workspace_id, name, service_id, threshold_ms, target_percent,
+                window_days, enabled, created_at, updated_at
+            FROM slos
+            WHERE workspace_id = $1
+            ORDER BY created_at ASC
+            "#,
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        Ok(rows.iter().map(slo_from_row).collect())
+    }
+
+    /// Get an SLO
+    async fn get_slo(&self, workspace_id: Uuid, slo_id: Uuid) -> Result<Option<Slo>> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, workspace_id, name, service_id, threshold_ms, target_percent,
+                window_days, enabled, created_at, updated_at
+            FROM slos
+            WHERE workspace_id = $1 AND id = $2
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(slo_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(slo_from_row))
+    }
+
+    /// Replace an SLO's definition
+    async fn update_slo(&self, slo: &Slo) -> Result<Option<Slo>> {
+        let row = sqlx::query(
+            r#"
+            UPDATE slos
+            SET name = $3, service_id = $4, threshold_ms = $5, target_percent = $6,
+                window_days = $7, enabled = $8, updated_at = NOW()
+            WHERE workspace_id = $1 AND id = $2
+            RETURNING id, workspace_id, name, service_id, threshold_ms, target_percent,
+                window_days, enabled, created_at, updated_at
+            "#,
+        )
+        .bind(slo.workspace_id)
+        .bind(slo.id)
+        .bind(&slo.name)
+        .bind(slo.service_id)
+        .bind(slo.threshold_ms)
+        .bind(slo.target_percent)
+        .bind(slo.window_days as i32)
+        .bind(slo.enabled)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(slo_from_row))
+    }
+
+    /// Delete an SLO
+    async fn delete_slo(&self, workspace_id: Uuid, slo_id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM slos WHERE workspace_id = $1 AND id = $2")
+            .bind(workspace_id)
+            .bind(slo_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// List enabled SLOs across all workspaces
+    async fn list_enabled_slos(&self) -> Result<Vec<Slo>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, workspace_id, name, service_id, threshold_ms, target_percent,
+                window_days, enabled, created_at, updated_at
+            FROM slos
+            WHERE enabled
+            "#,
+        )
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        Ok(rows.iter().map(slo_from_row).collect())
+    }
+
+    /// Count queries created within `[from, to)` by how they met a latency threshold
+    async fn get_slo_counts(
+        &self,
+        workspace_id: Uuid,
+        service_id: Option<Uuid>,
+        threshold_ms: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<SloCounts> {
+        let read = RawRead::new(
+            r#"
+            SELECT
+                COUNT(*) AS total,
+                COUNT(*) FILTER (WHERE status = 'success' AND duration_ms <= $3) AS satisfied,
+                COUNT(*) FILTER (
+                    WHERE status = 'success' AND duration_ms > $3 AND duration_ms <= $3 * 4
+                ) AS tolerating
+            FROM {raw} m
+            WHERE workspace_id = $1
+              AND ($2::uuid IS NULL OR service_id = $2)
+            "#,
+        )
+        .range(self.raw_range(from, to));
+        let row = read
+            .bind(
+                sqlx::query(read.sql())
+                    .bind(workspace_id)
+                    .bind(service_id)
+                    .bind(threshold_ms),
+            )
+            .fetch_one(&self.read_pool)
+            .await?;
+
+        Ok(SloCounts {
+            total: row.get("total"),
+            satisfied: row.get("satisfied"),
+            tolerating: row.get("tolerating"),
+        })
+    }
+
+    /// Get the last computed status of a workspace's SLOs
+    async fn list_slo_statuses(&self, workspace_id: Uuid) -> Result<Vec<SloStatus>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT st.slo_id, st.computed_at, st.total, st.satisfied, st.tolerating,
+                st.burn_rate_1h, st.burn_rate_6h, st.burn_state
+            FROM slo_status st
+            JOIN slos s ON s.id = st.slo_id
+            WHERE s.workspace_id = $1
+            "#,
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        Ok(rows.iter().map(slo_status_from_row).collect())
+    }
+
+    /// Get the last computed status of every SLO
+    async fn list_all_slo_statuses(&self) -> Result<Vec<SloStatus>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT slo_id, computed_at, total, satisfied, tolerating,
+                burn_rate_1h, burn_rate_6h, burn_state
+            FROM slo_status
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(slo_status_from_row).collect())
+    }
+
+    /// Save an SLO's computed status
+    async fn upsert_slo_status(&self, status: &SloStatus) -> Result<()> {
+        // The SLO may have been deleted since it was computed
+        sqlx::query(
+            r#"
+            INSERT INTO slo_status
+                (slo_id, computed_at, total, satisfied, tolerating, burn_rate_1h, burn_rate_6h,
+                 burn_state)
+            SELECT $1, $2, $3, $4, $5, $6, $7, $8
+            WHERE EXISTS (SELECT 1 FROM slos WHERE id = $1)
+            ON CONFLICT (slo_id) DO UPDATE SET
+                computed_at = EXCLUDED.computed_at,
+                total = EXCLUDED.total,
+                satisfied = EXCLUDED.satisfied,
+                tolerating = EXCLUDED.tolerating,
+                burn_rate_1h = EXCLUDED.burn_rate_1h,
+                burn_rate_6h = EXCLUDED.burn_rate_6h,
+                burn_state = EXCLUDED.burn_state,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(status.slo_id)
+        .bind(status.computed_at)
+        .bind(status.counts.total)
+        .bind(status.counts.satisfied)
+        .bind(status.counts.tolerating)
+        .bind(status.burn_rate_1h)
+        .bind(status.burn_rate_6h)
+        .bind(status.burn_state.as_str())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // =========================================================================
+    // LIFECYCLE POLICY METHODS
+    // =========================================================================
+
+    /// Get a workspace's lifecycle stages in the order they run
+    async fn get_lifecycle_policy(
+        &self,
+        workspace_id: Uuid,
+    ) -> Result<Option<Vec<LifecycleStage>>> {
+        // One row per stage, or a single row without one for a workspace on
+        // the default policy
+        let rows = sqlx::query(
+            r#"
+            SELECT p.action, p.after_days, p.last_run_at, p.last_affected, p.last_error
+            FROM workspaces w
+            LEFT JOIN lifecycle_policies p ON p.workspace_id = w.id
+            WHERE w.id = $1
+            "#,
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        if rows.is_empty() {
+            return Ok(None);
+        }
+        let mut stages: Vec<LifecycleStage> =
+            rows.iter().filter_map(lifecycle_stage_from_row).collect();
+        stages.sort_by_key(|stage| stage.action);
+        Ok(Some(stages))
+    }
+
+    /// Replace a workspace's lifecycle stages, keeping the run status of
+    /// stages whose action stays
+    async fn set_lifecycle_policy(
+        &self,
+        workspace_id: Uuid,
+        stages: &[LifecycleStage],
+    ) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        // Locking the workspace serializes concurrent replacements
+        let exists = sqlx::query("SELECT 1 FROM workspaces WHERE id = $1 FOR UPDATE")
+            .bind(workspace_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .is_some();
+        if !exists {
+            return Ok(false);
+        }
+
+        let actions: Vec<&str> = stages.iter().map(|s| s.action.as_str()).collect();
+        let after_days: Vec<i32> = stages.iter().map(|s| s.after_days).collect();
+        sqlx::query("DELETE FROM lifecycle_policies WHERE workspace_id = $1 AND action <> ALL($2)")
+            .bind(workspace_id)
+            .bind(&actions)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO lifecycle_policies (workspace_id, action, after_days)
+            SELECT $1, action, after_days
+            FROM UNNEST($2::TEXT[], $3::INT[]) AS s(action, after_days)
+            ON CONFLICT (workspace_id, action) DO UPDATE SET
+                after_days = EXCLUDED.after_days,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(&actions)
+        .bind(&after_days)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Get the lifecycle stages of every workspace with a policy of its own
+    async fn list_lifecycle_policies(&self) -> Result<HashMap<Uuid, Vec<LifecycleStage>>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT workspace_id, action, after_days, last_run_at, last_affected, last_error
+            FROM lifecycle_policies
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut policies: HashMap<Uuid, Vec<LifecycleStage>> = HashMap::new();
+        for row in &rows {
+            if let Some(stage) = lifecycle_stage_from_row(row) {
+                policies
+                    .entry(row.get("workspace_id"))
+                    .or_default()
+                    .push(stage);
+            }
+        }
+        for stages in policies.values_mut() {
+            stages.sort_by_key(|stage| stage.action);
+        }
+        Ok(policies)
+    }
+
+    /// Apply a lifecycle action to a workspace's data stored before `before`
+    async fn apply_lifecycle_action(
+        &self,
+        workspace_id: Uuid,
+        action: LifecycleAction,
+        before: DateTime<Utc>,
+    ) -> Result<u64> {
+        let query = match action {
+            // Only fingerprinted rows: the catalog holds their text
+            LifecycleAction::StripText => sqlx::query(
+                r#"
+                UPDATE query_metrics SET query_text = ''
+                WHERE workspace_id = $1 AND created_at < $2 AND created_at >= $3
+                    AND fingerprint IS NOT NULL AND query_text <> ''
+                "#,
+            ),
+            LifecycleAction::Archive => {
+                let read = RawRead::new(
+                    r#"
+                INSERT INTO query_metrics_archive (
+                    id, workspace_id, service_id, query_text, status,
+                    duration_ms, rows_affected, error_message,
+                    started_at, completed_at, created_at, tags, labels,
+                    fingerprint, error_fingerprint, query_kind
+                )
+                SELECT id, workspace_id, service_id, query_text, status,
+                    duration_ms, rows_affected, error_message,
+                    started_at, completed_at, created_at, tags, labels,
+                    fingerprint, error_fingerprint, query_kind
+                FROM {raw} m
+                WHERE workspace_id = $1
+                ON CONFLICT (workspace_id, id) DO NOTHING
+                "#,
+                )
+                .range(self.raw_range(DateTime::<Utc>::MIN_UTC, before));
+                let result = read
+                    .bind(sqlx::query(read.sql()).bind(workspace_id))
+                    .execute(&self.pool)
+                    .await?;
+                return Ok(result.rows_affected());
+            }
+            LifecycleAction::Downsample => sqlx::query(
+                r#"
+                DELETE FROM query_metrics
+                WHERE workspace_id = $1 AND created_at < $2 AND created_at >= $3
+                "#,
+            ),
+            LifecycleAction::Delete => {
+                let removed: i64 = sqlx::query_scalar(
+                    r#"
+                    WITH raw AS (
+                        DELETE FROM query_metrics
+                        WHERE workspace_id = $1 AND created_at < $2 AND created_at >= $3
+                        RETURNING 1
+                    ), archived AS (
+                        DELETE FROM query_metrics_archive
+                        WHERE workspace_id = $1 AND created_at < $2
+                        RETURNING 1
+                    ), anomalies AS (
+                        DELETE FROM query_anomalies
+                        WHERE workspace_id = $1 AND detected_at < $2
+                        RETURNING 1
+                    ), drops AS (
+                        DELETE FROM throughput_drops
+                        WHERE workspace_id = $1 AND recovered_at < $2
+                        RETURNING 1
+                    )
+                    SELECT (SELECT COUNT(*) FROM raw)
+                        + (SELECT COUNT(*) FROM archived)
+                        + (SELECT COUNT(*) FROM anomalies)
+                        + (SELECT COUNT(*) FROM drops)
+                    "#,
+                )
+                .bind(workspace_id)
+                .bind(before)
+                .bind(self.raw_cutoff())
+                .fetch_one(&self.pool)
+                .await?;
+                return Ok(removed as u64);
+            }
+        };
+
+        let result = query
+            .bind(workspace_id)
+            .bind(before)
+            .bind(self.raw_cutoff())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Save how a lifecycle stage last ran
+    async fn record_lifecycle_run(&self, workspace_id: Uuid, stage: &LifecycleStage) -> Result<()> {
+        // The stage may have been removed while it ran
+        sqlx::query(
+            r#"
+            UPDATE lifecycle_policies SET
+                last_run_at = $3,
+                last_affected = $4,
+                last_error = $5,
+                updated_at = NOW()
+            WHERE workspace_id = $1 AND action = $2
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(stage.action.as_str())
+        .bind(stage.last_run_at)
+        .bind(stage.last_affected)
+        .bind(stage.last_error.as_deref())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // =========================================================================
+    // EMBEDDING METHODS
+    // =========================================================================
+
+    /// Insert or update a query embedding
+    async fn insert_query_embeddings(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        embeddings: &[NewQueryEmbedding],
+    ) -> Result<()> {
+        // A statement can't update the same row twice; the last one wins
+        let mut latest: HashMap<&str, &NewQueryEmbedding> = HashMap::new();
+        for embedding in embeddings {
+            latest.insert(&embedding.query_hash, embedding);
+        }
+        if latest.is_empty() {
+            return Ok(());
+        }
+
+        let mut hashes = Vec::with_capacity(latest.len());
+        let mut queries = Vec::with_capacity(latest.len());
+        let mut vectors = Vec::with_capacity(latest.len());
+        for embedding in latest.into_values() {
+            hashes.push(embedding.query_hash.as_str());
+            queries.push(embedding.sql_query.as_str());
+            // pgvector text format
+            vectors.push(format!(
+                "[{}]",
+                embedding
+                    .embedding
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ));
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO query_embeddings
+                (workspace_id, query_hash, sql_query, embedding, model_name, model_version)
+            SELECT $1, e.query_hash, e.sql_query, e.embedding::vector, $5, $6
+            FROM UNNEST($2::TEXT[], $3::TEXT[], $4::TEXT[])
+                AS e(query_hash, sql_query, embedding)
+            ON CONFLICT (workspace_id, query_hash)
+            DO UPDATE SET
+                embedding = EXCLUDED.embedding,
+                model_name = EXCLUDED.model_name,
+                model_version = EXCLUDED.model_version,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(&hashes)
+        .bind(&queries)
+        .bind(&vectors)
+        .bind(&model.name)
+        .bind(&model.version)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Search for similar queries using cosine similarity
+    async fn search_similar_queries(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        embedding: &[f32],
+        limit: i32,
+        threshold: f32,
+        tuning: AnnTuning,
+    ) -> Result<Vec<SimilarQuery>> {
+        let embedding_str = format!(
+            "[{}]",
+            embedding
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+
+        // The index settings are scoped to this transaction so they don't
+        // outlive the search on a pooled connection
+        let mut tx = self.read_pool.begin().await?;
+        for (setting, value) in [
+            ("hnsw.ef_search", tuning.ef_search),
+            ("ivfflat.probes", tuning.probes),
+        ] {
+            if let Some(value) = value {
+                sqlx::query("SELECT set_config($1, $2, true)")
+                    .bind(setting)
+                    .bind(value.to_string())
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        let rows = sqlx::query(
+            r#"
+            SELECT 
+                id,
+                sql_query,
+                1 - (embedding <=> $2::vector) as similarity
+            FROM query_embeddings
+            WHERE workspace_id = $1
+                AND model_name = $5
+                AND model_version = $6
+                AND 1 - (embedding <=> $2::vector) >= $4
+            ORDER BY embedding <=> $2::vector
+            LIMIT $3
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(&embedding_str)
+        .bind(limit)
+        .bind(threshold)
+        .bind(&model.name)
+        .bind(&model.version)
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        let results = rows
+            .into_iter()
+            .map(|row| SimilarQuery {
+                id: row.get("id"),
+                sql_query: row.get("sql_query"),
+                similarity: row.get("similarity"),
+            })
+            .collect();
+
+        Ok(results)
+    }
+
+    /// Search for queries containing the given identifiers using full-text
+    /// search
+    async fn search_lexical_queries(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        embedding: &[f32],
+        terms: &[String],
+        limit: i32,
+    ) -> Result<Vec<SimilarQuery>> {
+        // Each identifier is a phrase of its parts, as `sql_lexemes` splits
+        // them, and any identifier may match
+        let tsquery = terms
+            .iter()
+            .map(|term| hybrid_search::term_parts(term).collect::<Vec<_>>())
+            .filter(|parts| !parts.is_empty())
+            .map(|parts| format!("({})", parts.join(" <-> ")))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        if tsquery.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let embedding_str = format!(
+            "[{}]",
+            embedding
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                id,
+                sql_query,
+                1 - (embedding <=> $2::vector) as similarity
+            FROM query_embeddings
+            WHERE workspace_id = $1
+                AND model_name = $5
+                AND model_version = $6
+                AND sql_lexemes(sql_query) @@ to_tsquery('simple', $3)
+            ORDER BY ts_rank_cd(sql_lexemes(sql_query), to_tsquery('simple', $3)) DESC, id
+            LIMIT $4
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(&embedding_str)
+        .bind(&tsquery)
+        .bind(limit)
+        .bind(&model.name)
+        .bind(&model.version)
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| SimilarQuery {
+                id: row.get("id"),
+                sql_query: row.get("sql_query"),
+                similarity: row.get("similarity"),
+            })
+            .collect())
+    }
+
+    /// Get queries that haven't been embedded yet
+    async fn get_unembedded_queries(
+        &self,
+        workspace_id: Uuid,
+        limit: i64,
+        max_bytes: usize,
+    ) -> Result<Vec<(String, String)>> {
+        let read = RawRead::new(
+            r#"
+            WITH executed AS (
+                SELECT md5(lower(regexp_replace(trim(query_text), '\s+', ' ', 'g'))) AS query_hash,
+                       MIN(query_text) AS query_text,
+                       COUNT(*) AS calls,
+                       MAX(duration_ms) AS max_duration_ms
+                FROM {raw} m
+                WHERE workspace_id = $1
+                GROUP BY 1
+            )
+            SELECT x.query_text, x.query_hash
+            FROM executed x
+            WHERE NOT EXISTS (
+                    SELECT 1 FROM query_embeddings e
+                    WHERE e.workspace_id = $1
+                    AND e.query_hash = x.query_hash
+                )
+                AND NOT EXISTS (
+                    SELECT 1 FROM query_embedding_skips s
+                    WHERE s.workspace_id = $1
+                    AND s.query_hash = x.query_hash
+                    AND s.query_bytes > $3
+                )
+            ORDER BY x.calls DESC, x.max_duration_ms DESC, x.query_hash
+            LIMIT $2
+            "#,
+        )
+        .range(self.raw_retained());
+        let rows = read
+            .bind(
+                sqlx::query(read.sql())
+                    .bind(workspace_id)
+                    .bind(limit)
+                    .bind(max_bytes.min(i32::MAX as usize) as i32),
+            )
+            .fetch_all(&self.pool)
+            .await?;
+
+        let results = rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.get::<String, _>("query_text"),
+                    row.get::<String, _>("query_hash"),
+                )
+            })
+            .collect();
+
+        Ok(results)
+    }
+
+    /// Record queries left unembedded for being too long
+    async fn insert_embedding_skips(
+        &self,
+        workspace_id: Uuid,
+        skips: &[EmbeddingSkip],
+    ) -> Result<()> {
+        if skips.is_empty() {
+            return Ok(());
+        }
+        let hashes: Vec<&str> = skips.iter().map(|s| s.query_hash.as_str()).collect();
+        let bytes: Vec<i32> = skips
+            .iter()
+            .map(|s| s.query_bytes.min(i32::MAX as usize) as i32)
+            .collect();
+
+        sqlx::query(
+            r#"
+            INSERT INTO query_embedding_skips (workspace_id, query_hash, query_bytes)
+            SELECT $1, s.query_hash, s.query_bytes
+            FROM UNNEST($2::TEXT[], $3::INT[]) AS s(query_hash, query_bytes)
+            ON CONFLICT (workspace_id, query_hash)
+            DO UPDATE SET query_bytes = EXCLUDED.query_bytes, skipped_at = NOW()
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(&hashes)
+        .bind(&bytes)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get workspaces with embeddings from a model other than `model`
+    async fn get_stale_embedding_workspaces(&self, model: &EmbeddingModel) -> Result<Vec<Uuid>> {
+        let workspaces = sqlx::query_scalar(
+            r#"
+            SELECT DISTINCT workspace_id
+            FROM query_embeddings
+            WHERE model_name <> $1 OR model_version <> $2
+            "#,
+        )
+        .bind(&model.name)
+        .bind(&model.version)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(workspaces)
+    }
+
+    /// Get `(sql_query, query_hash)` pairs embedded by a model other than `model`
+    async fn get_stale_embeddings(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        limit: i64,
+    ) -> Result<Vec<(String, String)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT sql_query, query_hash
+            FROM query_embeddings
+            WHERE workspace_id = $1
+                AND (model_name <> $2 OR model_version <> $3)
+            ORDER BY updated_at
+            LIMIT $4
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(&model.name)
+        .bind(&model.version)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get("sql_query"), row.get("query_hash")))
+            .collect())
+    }
+
+    /// Get a query's stored embedding, looked up by its hash
+    async fn get_query_embedding(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        query_text: &str,
+    ) -> Result<Option<Vec<f32>>> {
+        let embedding: Option<String> = sqlx::query_scalar(
+            r#"
+            SELECT embedding::text
+            FROM query_embeddings
+            WHERE workspace_id = $1
+                AND query_hash = md5(lower(regexp_replace(trim($2), '\s+', ' ', 'g')))
+                AND model_name = $3
+                AND model_version = $4
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(query_text)
+        .bind(&model.name)
+        .bind(&model.version)
+        .fetch_optional(&self.read_pool)
+        .await?;
+
+        embedding.as_deref().map(parse_vector).transpose()
+    }
+
+    /// Get embedded queries with their latency since `since`, most-called first
+    async fn list_query_embeddings(
+        &self,
+        workspace_id: Uuid,
+        model: Option<&EmbeddingModel>,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<EmbeddedQuery>> {
+        let read = RawRead::new(
+            r#"
+            WITH latency AS (
+                SELECT
+                    md5(lower(regexp_replace(trim(query_text), '\s+', ' ', 'g'))) as query_hash,
+                    COUNT(*) as call_count,
+                    AVG(duration_ms)::BIGINT as avg_duration_ms,
+                    PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY duration_ms)::BIGINT as p95_duration_ms
+                FROM {raw} m
+                WHERE workspace_id = $1
+                GROUP BY 1
+            )
+            SELECT
+                e.query_hash,
+                e.sql_query,
+                e.embedding::text as embedding,
+                COALESCE(l.call_count, 0) as call_count,
+                l.avg_duration_ms,
+                l.p95_duration_ms
+            FROM query_embeddings e
+            LEFT JOIN latency l ON l.query_hash = e.query_hash
+            WHERE e.workspace_id = $1
+                AND ($3::TEXT IS NULL OR (e.model_name = $3 AND e.model_version = $4))
+            ORDER BY COALESCE(l.call_count, 0) DESC, e.updated_at DESC
+            LIMIT $2
+            "#,
+        )
+        .range(self.raw_since(since));
+        let rows = read
+            .bind(
+                sqlx::query(read.sql())
+                    .bind(workspace_id)
+                    .bind(limit)
+                    .bind(model.map(|m| m.name.as_str()))
+                    .bind(model.map(|m| m.version.as_str())),
+            )
+            .fetch_all(&self.read_pool)
+            .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let embedding: String = row.get("embedding");
+                Ok(EmbeddedQuery {
+                    query_hash: row.get("query_hash"),
+                    sql_query: row.get("sql_query"),
+                    embedding: parse_vector(&embedding)?,
+                    call_count: row.get("call_count"),
+                    avg_duration_ms: row.get("avg_duration_ms"),
+                    p95_duration_ms: row.get("p95_duration_ms"),
+                })
+            })
+            .collect()
+    }
+
+    /// Find queries similar to a stored metric's by its query's embedding
+    async fn get_metric_similarity(
+        &self,
+        workspace_id: Uuid,
+        metric_id: Uuid,
+        since: DateTime<Utc>,
+        limit: i64,
+        threshold: f32,
+    ) -> Result<Option<MetricSimilarity>> {
+        let read = RawRead::new(
+            r#"
+            SELECT
+                m.query_text,
+                md5(lower(regexp_replace(trim(m.query_text), '\s+', ' ', 'g'))) as query_hash,
+                e.id as embedding_id,
+                e.model_name,
+                e.model_version,
+                e.embedding::text as embedding
+            FROM {raw} m
+            LEFT JOIN query_embeddings e
+                ON e.workspace_id = m.workspace_id
+                AND e.query_hash = md5(lower(regexp_replace(trim(m.query_text), '\s+', ' ', 'g')))
+            WHERE m.workspace_id = $1 AND m.id = $2
+            LIMIT 1
+            "#,
+        )
+        .range(self.raw_since(since));
+        let Some(source) = read
+            .bind(sqlx::query(read.sql()).bind(workspace_id).bind(metric_id))
+            .fetch_optional(&self.read_pool)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let mut similarity = MetricSimilarity {
+            query_hash: source.get("query_hash"),
+            query_text: source.get("query_text"),
+            model: None,
+            similar: Vec::new(),
+        };
+        let Some(embedding_id) = source.get::<Option<Uuid>, _>("embedding_id") else {
+            return Ok(Some(similarity));
+        };
+        let model = EmbeddingModel {
+            name: source.get("model_name"),
+            version: source.get("model_version"),
+        };
+        let embedding: String = source.get("embedding");
+
+        // Search the embedding's own model, whichever is configured now
+        let read = RawRead::new(
+            r#"
+            WITH similar AS (
+                SELECT
+                    id,
+                    query_hash,
+                    sql_query,
+                    1 - (embedding <=> $2::vector) as similarity
+                FROM query_embeddings
+                WHERE workspace_id = $1
+                    AND model_name = $3
+                    AND model_version = $4
+                    AND id <> $5
+                    AND 1 - (embedding <=> $2::vector) >= $6
+                ORDER BY embedding <=> $2::vector
+                LIMIT $7
+            ),
+            stats AS (
+                SELECT
+                    md5(lower(regexp_replace(trim(query_text), '\s+', ' ', 'g'))) as query_hash,
+                    COUNT(*) as call_count,
+                    SUM(CASE WHEN status IN ('failed', 'timeout') THEN 1 ELSE 0 END)
+                        as error_count,
+                    AVG(duration_ms)::BIGINT as avg_duration_ms,
+                    PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY duration_ms)::BIGINT
+                        as p50_duration_ms,
+                    PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY duration_ms)::BIGINT
+                        as p95_duration_ms,
+                    PERCENTILE_CONT(0.99) WITHIN GROUP (ORDER BY duration_ms)::BIGINT
+                        as p99_duration_ms
+                FROM {raw} m
+                WHERE workspace_id = $1
+                    AND md5(lower(regexp_replace(trim(query_text), '\s+', ' ', 'g')))
+                        IN (SELECT query_hash FROM similar)
+                GROUP BY 1
+            )
+            SELECT
+                s.id,
+                s.query_hash,
+                s.sql_query,
+                s.similarity,
+                COALESCE(t.call_count, 0) as call_count,
+                COALESCE(t.error_count, 0) as error_count,
+                t.avg_duration_ms,
+                t.p50_duration_ms,
+                t.p95_duration_ms,
+                t.p99_duration_ms
+            FROM similar s
+            LEFT JOIN stats t ON t.query_hash = s.query_hash
+            ORDER BY s.similarity DESC, s.id
+            "#,
+        )
+        .range(self.raw_since(since));
+        let rows = read
+            .bind(
+                sqlx::query(read.sql())
+                    .bind(workspace_id)
+                    .bind(&embedding)
+                    .bind(&model.name)
+                    .bind(&model.version)
+                    .bind(embedding_id)
+                    .bind(threshold)
+                    .bind(limit),
+            )
+            .fetch_all(&self.read_pool)
+            .await?;
+
+        similarity.similar = rows
+            .into_iter()
+            .map(|row| SimilarQueryStats {
+                query: SimilarQuery {
+                    id: row.get("id"),
+                    sql_query: row.get("sql_query"),
+                    similarity: row.get("similarity"),
+                },
+                query_hash: row.get("query_hash"),
+                call_count: row.get("call_count"),
+                error_count: row.get("error_count"),
+                avg_duration_ms: row.get("avg_duration_ms"),
+                p50_duration_ms: row.get("p50_duration_ms"),
+                p95_duration_ms: row.get("p95_duration_ms"),
+                p99_duration_ms: row.get("p99_duration_ms"),
+            })
+            .collect();
+        similarity.model = Some(model);
+        Ok(Some(similarity))
+    }
+
+    /// Get workspaces with embeddings made by `model`
+    async fn get_embedded_workspaces(&self, model: &EmbeddingModel) -> Result<Vec<Uuid>> {
+        let workspaces = sqlx::query_scalar(
+            r#"
+            SELECT DISTINCT workspace_id
+            FROM query_embeddings
+            WHERE model_name = $1 AND model_version = $2
+            "#,
+        )
+        .bind(&model.name)
+        .bind(&model.version)
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        Ok(workspaces)
+    }
+
+    /// Replace a workspace's query clusters
+    async fn replace_query_clusters(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        computed_at: DateTime<Utc>,
+        clusters: &[NewQueryCluster],
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        // Members go with their clusters
+        sqlx::query("DELETE FROM query_clusters WHERE workspace_id = $1")
+            .bind(workspace_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let ids: Vec<i32> = (0..clusters.len() as i32).collect();
+        let labels: Vec<Option<&str>> = clusters.iter().map(|c| c.label.as_deref()).collect();
+        let hashes: Vec<&str> = clusters
+            .iter()
+            .map(|c| c.representative_hash.as_str())
+            .collect();
+        let queries: Vec<&str> = clusters
+            .iter()
+            .map(|c| c.representative_query.as_str())
+            .collect();
+        sqlx::query(
+            r#"
+            INSERT INTO query_clusters (
+                workspace_id, cluster_id, label, representative_hash, representative_query,
+                model_name, model_version, computed_at
+            )
+            SELECT $1, cluster_id, label, representative_hash, representative_query, $5, $6, $7
+            FROM UNNEST($2::INTEGER[], $3::TEXT[], $4::TEXT[], $8::TEXT[])
+                AS batch(cluster_id, label, representative_hash, representative_query)
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(&ids)
+        .bind(&labels)
+        .bind(&hashes)
+        .bind(&model.name)
+        .bind(&model.version)
+        .bind(computed_at)
+        .bind(&queries)
+        .execute(&mut *tx)
+        .await?;
+
+        let (member_ids, member_hashes): (Vec<i32>, Vec<&str>) = clusters
+            .iter()
+            .zip(&ids)
+            .flat_map(|(c, id)| c.members.iter().map(move |hash| (*id, hash.as_str())))
+            .unzip();
+        sqlx::query(
+            r#"
+            INSERT INTO query_cluster_members (workspace_id, query_hash, cluster_id)
+            SELECT $1, query_hash, cluster_id
+            FROM UNNEST($2::TEXT[], $3::INTEGER[]) AS batch(query_hash, cluster_id)
+            ON CONFLICT (workspace_id, query_hash) DO NOTHING
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(&member_hashes)
+        .bind(&member_ids)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Get a workspace's query clusters with their traffic since `since`
+    async fn get_query_clusters(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Option<QueryClusters>> {
+        let read = RawRead::new(
+            r#"
+            WITH sizes AS (
+                SELECT cluster_id, COUNT(*) as size
+                FROM query_cluster_members
+                WHERE workspace_id = $1
+                GROUP BY 1
+            ),
+            stats AS (
+                SELECT
+                    cm.cluster_id,
+                    COUNT(*) as call_count,
+                    SUM(CASE WHEN m.status IN ('failed', 'timeout') THEN 1 ELSE 0 END)
+                        as error_count,
+                    SUM(m.duration_ms)::BIGINT as total_duration_ms,
+                    AVG(m.duration_ms)::BIGINT as avg_duration_ms,
+                    PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY m.duration_ms)::BIGINT
+                        as p95_duration_ms
+                FROM {raw} m
+                JOIN query_cluster_members cm
+                    ON cm.workspace_id = m.workspace_id
+                    AND cm.query_hash = md5(lower(regexp_replace(trim(m.query_text), '\s+', ' ', 'g')))
+                WHERE m.workspace_id = $1
+                GROUP BY 1
+            )
+            SELECT
+                c.cluster_id,
+                c.label,
+                c.representative_hash,
+                c.representative_query,
+                c.model_name,
+                c.model_version,
+                c.computed_at,
+                COALESCE(z.size, 0) as size,
+                COALESCE(s.call_count, 0) as call_count,
+                COALESCE(s.error_count, 0) as error_count,
+                COALESCE(s.total_duration_ms, 0) as total_duration_ms,
+                s.avg_duration_ms,
+                s.p95_duration_ms
+            FROM query_clusters c
+            LEFT JOIN sizes z ON z.cluster_id = c.cluster_id
+            LEFT JOIN stats s ON s.cluster_id = c.cluster_id
+            WHERE c.workspace_id = $1
+            ORDER BY COALESCE(s.total_duration_ms, 0) DESC, c.cluster_id
+            LIMIT $2
+            "#,
+        )
+        .range(self.raw_since(since));
+        let rows = read
+            .bind(sqlx::query(read.sql()).bind(workspace_id).bind(limit))
+            .fetch_all(&self.read_pool)
+            .await?;
+
+        let Some(first) = rows.first() else {
+            return Ok(None);
+        };
+        let model = EmbeddingModel {
+            name: first.get("model_name"),
+            version: first.get("model_version"),
+        };
+        let computed_at = first.get("computed_at");
+        let clusters = rows
+            .iter()
+            .map(|row| QueryCluster {
+                id: row.get("cluster_id"),
+                label: row.get("label"),
+                size: row.get("size"),
+                representative_hash: row.get("representative_hash"),
+                representative_query: row.get("representative_query"),
+                call_count: row.get("call_count"),
+                error_count: row.get("error_count"),
+                total_duration_ms: row.get("total_duration_ms"),
+                avg_duration_ms: row.get("avg_duration_ms"),
+                p95_duration_ms: row.get("p95_duration_ms"),
+            })
+            .collect();
+
+        Ok(Some(QueryClusters {
+            model,
+            computed_at,
+            clusters,
+        }))
+    }
+
+    // =========================================================================
+    // ANOMALY METHODS
+    // =========================================================================
+
+    /// Get metrics statistics for anomaly detection, per workspace (muted
+    /// fingerprints excluded)
+    async fn get_metrics_stats(
+        &self,
+        limits: &HashMap<Uuid, i64>,
+    ) -> Result<HashMap<Uuid, MetricsStats>> {
+        if limits.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let (workspace_ids, limits): (Vec<Uuid>, Vec<i64>) = limits.iter().unzip();
+        // One round trip, but each workspace still reads only its newest
+        // metrics off the (workspace_id, created_at) index
+        let read = RawRead::new(
+            r#"
+            SELECT
+                w.id AS workspace_id, w.anomaly_method,
+                s.mean, s.stddev, s.median, d.mad, s.count
+            FROM UNNEST($1::UUID[], $2::BIGINT[]) AS l(id, lim)
+            JOIN workspaces w ON w.id = l.id
+            CROSS JOIN LATERAL (
+                SELECT
+                    AVG(duration_ms)::DOUBLE PRECISION AS mean,
+                    STDDEV(duration_ms)::DOUBLE PRECISION AS stddev,
+                    PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY duration_ms) AS median,
+                    ARRAY_AGG(duration_ms) AS durations,
+                    COUNT(*) AS count
+                FROM (
+                    SELECT duration_ms
+                    FROM {raw} m
+                    WHERE m.workspace_id = w.id
+                        AND NOT EXISTS (
+                            SELECT 1 FROM query_mutes q
+                            WHERE q.workspace_id = m.workspace_id
+                                AND q.fingerprint = m.fingerprint
+                                AND (q.expires_at IS NULL OR q.expires_at > NOW())
+                        )
+                    ORDER BY m.created_at DESC
+                    LIMIT l.lim
+                ) recent
+            ) s
+            CROSS JOIN LATERAL (
+                SELECT PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY ABS(x - s.median)) AS mad
+                FROM UNNEST(s.durations) AS x
+            ) d
+            WHERE s.count > 0
+            "#,
+        )
+        .range(self.raw_retained());
+        let rows = read
+            .bind(sqlx::query(read.sql()).bind(&workspace_ids).bind(&limits))
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("workspace_id"), metrics_stats_from_row(row)))
+            .collect())
+    }
+
+    /// Recompute a workspace's seasonal baselines
+    async fn refresh_seasonal_baselines(
+        &self,
+        workspace_id: Uuid,
+        seasonality: Seasonality,
+        since: DateTime<Utc>,
+    ) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM anomaly_baselines WHERE workspace_id = $1")
+            .bind(workspace_id)
+            .execute(&mut *tx)
+            .await?;
+        if seasonality == Seasonality::None {
+            tx.commit().await?;
+            return Ok(0);
+        }
+
+        // Slots match `services::anomaly::seasonal_slot`
+        let read = RawRead::new(
+            r#"
+            WITH recent AS (
+                SELECT
+                    CASE WHEN $2 = 'weekly'
+                        THEN (EXTRACT(ISODOW FROM t) - 1) * 24 + EXTRACT(HOUR FROM t)
+                        ELSE EXTRACT(HOUR FROM t)
+                    END::SMALLINT AS slot,
+                    duration_ms
+                FROM (
+                    SELECT m.created_at AT TIME ZONE 'UTC' AS t, m.duration_ms
+                    FROM {raw} m
+                    WHERE m.workspace_id = $1
+                        AND NOT EXISTS (
+                            SELECT 1 FROM query_mutes q
+                            WHERE q.workspace_id = m.workspace_id
+                                AND q.fingerprint = m.fingerprint
+                                AND (q.expires_at IS NULL OR q.expires_at > NOW())
+                        )
+                ) m
+            ),
+            s AS (
+                SELECT
+                    slot,
+                    AVG(duration_ms)::DOUBLE PRECISION AS mean,
+                    COALESCE(STDDEV(duration_ms), 0)::DOUBLE PRECISION AS stddev,
+                    PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY duration_ms) AS median,
+                    COUNT(*) AS count
+                FROM recent
+                GROUP BY slot
+            )
+            INSERT INTO anomaly_baselines (
+                workspace_id, seasonality, slot, mean, stddev, median, mad, count
+            )
+            SELECT $1, $2, s.slot, s.mean, s.stddev, s.median, d.mad, s.count
+            FROM s
+            JOIN (
+                SELECT
+                    r.slot,
+                    PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY ABS(r.duration_ms - s.median))
+                        AS mad
+                FROM recent r
+                JOIN s ON s.slot = r.slot
+                GROUP BY r.slot
+            ) d ON d.slot = s.slot
+            "#,
+        )
+        .range(self.raw_since(since));
+        let result = read
+            .bind(
+                sqlx::query(read.sql())
+                    .bind(workspace_id)
+                    .bind(seasonality.as_str()),
+            )
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Get the stored baselines of the given workspaces' slots
+    async fn get_seasonal_baselines(
+        &self,
+        slots: &HashMap<Uuid, (Seasonality, i16)>,
+    ) -> Result<HashMap<Uuid, MetricsStats>> {
+        if slots.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let workspace_ids: Vec<Uuid> = slots.keys().copied().collect();
+        let seasonalities: Vec<&str> = workspace_ids
+            .iter()
+            .map(|id| slots[id].0.as_str())
+            .collect();
+        let slot_numbers: Vec<i16> = workspace_ids.iter().map(|id| slots[id].1).collect();
+
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                b.workspace_id, w.anomaly_method,
+                b.mean, b.stddev, b.median, b.mad, b.count
+            FROM UNNEST($1::UUID[], $2::TEXT[], $3::SMALLINT[]) AS l(id, seasonality, slot)
+            JOIN anomaly_baselines b
+                ON b.workspace_id = l.id
+                AND b.seasonality = l.seasonality
+                AND b.slot = l.slot
+            JOIN workspaces w ON w.id = l.id
+            "#,
+        )
+        .bind(&workspace_ids)
+        .bind(&seasonalities)
+        .bind(&slot_numbers)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("workspace_id"), metrics_stats_from_row(row)))
+            .collect())
+    }
+
+    /// Get recent metrics with high duration for anomaly detection, across
+    /// workspaces (muted fingerprints excluded)
+    async fn get_recent_metrics_for_anomaly(
+        &self,
+        thresholds: &HashMap<Uuid, AnomalyThreshold>,
+    ) -> Result<Vec<QueryMetric>> {
+        if thresholds.is_empty() {
+            return Ok(Vec::new());
+        }
+        let workspace_ids: Vec<Uuid> = thresholds.keys().copied().collect();
+        let durations: Vec<i64> = workspace_ids
+            .iter()
+            .map(|id| thresholds[id].duration_ms)
+            .collect();
+        let since: Vec<i64> = workspace_ids
+            .iter()
+            .map(|id| thresholds[id].since_seconds)
+            .collect();
+        let now = self.clock.now();
+        let longest = since.iter().copied().max().unwrap_or(0);
+
+        let read = RawRead::new(
+            r#"
+            SELECT 
+                m.id, m.workspace_id, m.service_id, m.query_text, m.status,
+                m.duration_ms, m.rows_affected, m.error_message,
+                m.started_at, m.completed_at, m.tags, m.labels, m.fingerprint,
+                m.query_kind
+            FROM {raw} m
+            JOIN UNNEST($1::UUID[], $2::BIGINT[], $3::BIGINT[])
+                AS t(workspace_id, threshold_ms, since_seconds)
+                ON t.workspace_id = m.workspace_id
+            WHERE m.created_at > $4 - make_interval(secs => t.since_seconds)
+                AND m.duration_ms > t.threshold_ms
+                AND NOT EXISTS (
+                    SELECT 1 FROM query_mutes q
+                    WHERE q.workspace_id = m.workspace_id
+                        AND q.fingerprint = m.fingerprint
+                        AND (q.expires_at IS NULL OR q.expires_at > NOW())
+                )
+            ORDER BY m.duration_ms DESC
+            "#,
+        )
+        .range(self.raw_range(now - TimeDelta::seconds(longest), now));
+        let rows = read
+            .bind(
+                sqlx::query(read.sql())
+                    .bind(&workspace_ids)
+                    .bind(&durations)
+                    .bind(&since)
+                    .bind(now),
+            )
+            .fetch_all(&self.pool)
+            .await?;
+
+        let metrics = rows
+            .into_iter()
+            .map(|row| QueryMetric {
+                id: row.get("id"),
+                workspace_id: row.get("workspace_id"),
+                service_id: row.get("service_id"),
+                service_name: None,
+                query_text: row.get("query_text"),
+                status: string_to_status(row.get("status")),
+                duration_ms: row.get::<i64, _>("duration_ms") as u64,
+                rows_affected: row.get("rows_affected"),
+                error_message: row.get("error_message"),
+                started_at: row.get("started_at"),
+                completed_at: row.get("completed_at"),
+                tags: row
+                    .get::<Option<Vec<String>>, _>("tags")
+                    .unwrap_or_default(),
+                labels: row.get::<Json<HashMap<String, String>>, _>("labels").0,
+                fingerprint: row.get("fingerprint"),
+                query_kind: row
+                    .get::<Option<&str>, _>("query_kind")
+                    .and_then(|k| k.parse().ok()),
+            })
+            .collect();
+
+        Ok(metrics)
+    }
+
+    /// Record detected anomalies in one statement
+    async fn insert_anomalies(&self, anomalies: &[QueryAnomaly]) -> Result<u64> {
+        if anomalies.is_empty() {
+            return Ok(0);
+        }
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO query_anomalies (
+                id, workspace_id, service_id, metric_id, fingerprint, query_text,
+                duration_ms, mean_duration_ms, stddev_duration_ms, z_score,
+                severity, estimated_extra_ms, hints, method, severity_level
+            )
+            SELECT * FROM UNNEST(
+                $1::UUID[], $2::UUID[], $3::UUID[], $4::UUID[], $5::TEXT[], $6::TEXT[],
+                $7::BIGINT[], $8::BIGINT[], $9::BIGINT[], $10::DOUBLE PRECISION[],
+                $11::DOUBLE PRECISION[], $12::BIGINT[], $13::JSONB[], $14::TEXT[], $15::TEXT[]
+            )
+            "#,
+        )
+        .bind(anomalies.iter().map(|a| a.id).collect::<Vec<_>>())
+        .bind(anomalies.iter().map(|a| a.workspace_id).collect::<Vec<_>>())
+        .bind(anomalies.iter().map(|a| a.service_id).collect::<Vec<_>>())
+        .bind(anomalies.iter().map(|a| a.metric_id).collect::<Vec<_>>())
+        .bind(anomalies.iter().map(|a| &a.fingerprint).collect::<Vec<_>>())
+        .bind(anomalies.iter().map(|a| &a.query_text).collect::<Vec<_>>())
+        .bind(anomalies.iter().map(|a| a.duration_ms).collect::<Vec<_>>())
+        .bind(
+            anomalies
+                .iter()
+                .map(|a| a.mean_duration_ms)
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            anomalies
+                .iter()
+                .map(|a| a.stddev_duration_ms)
+                .collect::<Vec<_>>(),
+        )
+        .bind(anomalies.iter().map(|a| a.z_score).collect::<Vec<_>>())
+        .bind(anomalies.iter().map(|a| a.severity).collect::<Vec<_>>())
+        .bind(
+            anomalies
+                .iter()
+                .map(|a| a.estimated_extra_ms)
+                .collect::<Vec<_>>(),
+        )
+        .bind(anomalies.iter().map(|a| Json(&a.hints)).collect::<Vec<_>>())
+        .bind(
+            anomalies
+                .iter()
+                .map(|a| a.method.as_str())
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            anomalies
+                .iter()
+                .map(|a| a.severity_level.as_str())
+                .collect::<Vec<_>>(),
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Get one anomaly with its root-cause hints
+    async fn get_anomaly_context(
+        &self,
+        workspace_id: Uuid,
+        anomaly_id: Uuid,
+    ) -> Result<Option<AnomalyContext>> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                a.id, a.workspace_id, a.service_id, a.metric_id,
+                a.fingerprint, a.query_text,
+                a.duration_ms, a.mean_duration_ms, a.stddev_duration_ms, a.z_score, a.method,
+                a.severity, a.severity_level, a.estimated_extra_ms,
+                a.detected_at, a.status, a.acknowledged_by, a.resolved_at, a.hints,
+                o.fingerprint AS owner_fingerprint, o.owner_team,
+                o.ticket_links AS owner_ticket_links, o.notes AS owner_notes,
+                o.updated_at AS owner_updated_at
+            FROM query_anomalies a
+            LEFT JOIN query_ownership o
+                ON o.workspace_id = a.workspace_id
+                AND o.fingerprint = a.fingerprint
+            WHERE a.workspace_id = $1 AND a.id = $2
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(anomaly_id)
+        .fetch_optional(&self.read_pool)
+        .await?;
+
+        Ok(row.map(|row| AnomalyContext {
+            anomaly: anomaly_from_row(&row),
+            hints: row.get::<Json<Vec<AnomalyHint>>, _>("hints").0,
+        }))
+    }
+
+    /// Get call counts per fingerprint since `since`, from the hourly rollups
+    async fn get_fingerprint_call_counts(
+        &self,
+        workspace_id: Uuid,
+        fingerprints: &[String],
+        since: DateTime<Utc>,
+    ) -> Result<HashMap<String, i64>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT fingerprint, SUM(call_count)::BIGINT AS calls
+            FROM fingerprint_rollups
+            WHERE workspace_id = $1 AND fingerprint = ANY($2) AND bucket >= $3
+            GROUP BY fingerprint
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(fingerprints)
+        .bind(since)
+        .fetch_all(&self.read_pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| (row.get("fingerprint"), row.get("calls")))
+            .collect())
     }
 
-    /// Get recent metrics for a workspace
-    pub async fn get_recent_metrics(
+    /// Get anomalies detected at or after `since` at `min_severity` or
+    /// above, most severe first
+    async fn get_anomalies(
         &self,
         workspace_id: Uuid,
+        since: DateTime<Utc>,
+        min_severity: SeverityLevel,
+        status: Option<AnomalyStatus>,
         limit: i64,
-    ) -> Result<Vec<QueryMetric>> {
+    ) -> Result<Vec<AnomalyRecord>> {
         let rows = sqlx::query(
             r#"
-            SELECT 
-                id, workspace_id, service_id, query_text, status,
-                duration_ms, rows_affected, error_message,
-                started_at, completed_at, tags
-            FROM query_metrics
-            WHERE workspace_id = $1
-            ORDER BY created_at DESC
-            LIMIT $2
+            SELECT
+                a.id, a.workspace_id, a.service_id, a.metric_id,
+                a.fingerprint, a.query_text,
+                a.duration_ms, a.mean_duration_ms, a.stddev_duration_ms, a.z_score, a.method,
+                a.severity, a.severity_level, a.estimated_extra_ms,
+                a.detected_at, a.status, a.acknowledged_by, a.resolved_at,
+                o.fingerprint AS owner_fingerprint, o.owner_team,
+                o.ticket_links AS owner_ticket_links, o.notes AS owner_notes,
+                o.updated_at AS owner_updated_at
+            FROM query_anomalies a
+            LEFT JOIN query_ownership o
+                ON o.workspace_id = a.workspace_id
+                AND o.fingerprint = a.fingerprint
+            WHERE a.workspace_id = $1 AND a.detected_at >= $2 AND a.severity_level = ANY($3)
+                AND ($4::TEXT IS NULL OR a.status = $4)
+            ORDER BY array_position($6::TEXT[], a.severity_level) DESC,
+                a.severity DESC, a.detected_at DESC
+            LIMIT $5
             "#,
         )
         .bind(workspace_id)
+        .bind(since)
+        .bind(
+            SeverityLevel::ALL
+                .iter()
+                .filter(|level| **level >= min_severity)
+                .map(|level| level.as_str())
+                .collect::<Vec<_>>(),
+        )
+        .bind(status.map(|s| s.as_str()))
         .bind(limit)
+        .bind(SeverityLevel::ALL.map(|level| level.as_str()))
         .fetch_all(&self.pool)
         .await?;
 
-        let metrics = rows
-            .into_iter()
-            .map(|row| QueryMetric {
-                id: row.get("id"),
-                workspace_id: row.get("workspace_id"),
-                service_id: row.get("service_id"),
-                query_text: row.get("query_text"),
-                status: string_to_status(row.get("status")),
-                duration_ms: row.get::<i64, _>("duration_ms") as u64,
-                rows_affected: row.get("rows_affected"),
-                error_message: row.get("error_message"),
-                started_at: row.get("started_at"),
-                completed_at: row.get("completed_at"),
-                tags: row
-                    .get::<Option<Vec<String>>, _>("tags")
-                    .unwrap_or_default(),
-            })
-            .collect();
-
-        Ok(metrics)
+        Ok(rows.iter().map(anomaly_from_row).collect())
     }
 
-    /// Get aggregated metrics from continuous aggregate views
-    pub async fn get_aggregations(
+    /// Acknowledge an open anomaly
+    async fn acknowledge_anomaly(
         &self,
         workspace_id: Uuid,
-        window: &str,
-        from: DateTime<Utc>,
-        to: DateTime<Utc>,
-    ) -> Result<Vec<AggregatedMetric>> {
-        let view_name = match window {
-            "5s" => "metrics_5s",
-            "1m" => "metrics_1m",
-            "5m" => "metrics_5m",
-            _ => {
-                return Err(AppError::InvalidRequest(format!(
-                    "Invalid window: {}",
-                    window
-                )))
-            }
-        };
+        anomaly_id: Uuid,
+        acknowledged_by: &str,
+    ) -> Result<Option<AnomalyRecord>> {
+        let row = sqlx::query(
+            r#"
+            WITH a AS (
+                UPDATE query_anomalies
+                SET status = 'acknowledged', acknowledged_by = $3
+                WHERE workspace_id = $1 AND id = $2 AND status = 'open'
+                RETURNING *
+            )
+            SELECT
+                a.id, a.workspace_id, a.service_id, a.metric_id,
+                a.fingerprint, a.query_text,
+                a.duration_ms, a.mean_duration_ms, a.stddev_duration_ms, a.z_score, a.method,
+                a.severity, a.severity_level, a.estimated_extra_ms,
+                a.detected_at, a.status, a.acknowledged_by, a.resolved_at,
+                o.fingerprint AS owner_fingerprint, o.owner_team,
+                o.ticket_links AS owner_ticket_links, o.notes AS owner_notes,
+                o.updated_at AS owner_updated_at
+            FROM a
+            LEFT JOIN query_ownership o
+                ON o.workspace_id = a.workspace_id
+                AND o.fingerprint = a.fingerprint
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(anomaly_id)
+        .bind(acknowledged_by)
+        .fetch_optional(&self.pool)
+        .await?;
 
-        // Using dynamic query since view name can't be parameterized
-        let query = format!(
+        Ok(row.as_ref().map(anomaly_from_row))
+    }
+
+    /// Resolve an anomaly that isn't resolved yet, keeping whoever
+    /// acknowledged it
+    async fn resolve_anomaly(
+        &self,
+        workspace_id: Uuid,
+        anomaly_id: Uuid,
+        resolved_by: &str,
+        resolved_at: DateTime<Utc>,
+    ) -> Result<Option<AnomalyRecord>> {
+        let row = sqlx::query(
             r#"
-            SELECT 
-                workspace_id, service_id, bucket,
-                query_count, avg_duration_ms, min_duration_ms, max_duration_ms,
-                p95_duration_ms, p99_duration_ms,
-                success_count, failed_count, total_rows_affected
-            FROM {}
-            WHERE workspace_id = $1 AND bucket >= $2 AND bucket < $3
-            ORDER BY bucket ASC
-            "#,
-            view_name
-        );
+            WITH a AS (
+                UPDATE query_anomalies
+                SET status = 'resolved',
+                    acknowledged_by = COALESCE(acknowledged_by, $3),
+                    resolved_at = $4
+                WHERE workspace_id = $1 AND id = $2 AND status <> 'resolved'
+                RETURNING *
+            )
+            SELECT
+                a.id, a.workspace_id, a.service_id, a.metric_id,
+                a.fingerprint, a.query_text,
+                a.duration_ms, a.mean_duration_ms, a.stddev_duration_ms, a.z_score, a.method,
+                a.severity, a.severity_level, a.estimated_extra_ms,
+                a.detected_at, a.status, a.acknowledged_by, a.resolved_at,
+                o.fingerprint AS owner_fingerprint, o.owner_team,
+                o.ticket_links AS owner_ticket_links, o.notes AS owner_notes,
+                o.updated_at AS owner_updated_at
+            FROM a
+            LEFT JOIN query_ownership o
+                ON o.workspace_id = a.workspace_id
+                AND o.fingerprint = a.fingerprint
+            "#,
+        )
+        .bind(workspace_id)
+        .bind(anomaly_id)
+        .bind(resolved_by)
+        .bind(resolved_at)
+        .fetch_optional(&self.pool)
+        .await?;
 
-        let rows = sqlx::query(&query)
-            .bind(workspace_id)
-            .bind(from)
-            .bind(to)
+        Ok(row.as_ref().map(anomaly_from_row))
+    }
+
+    /// Count each service's queries in a baseline window and the recent
+    /// window after it
+    async fn get_service_throughput(
+        &self,
+        workspace_ids: &[Uuid],
+        baseline_from: DateTime<Utc>,
+        recent_from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<ServiceThroughput>> {
+        let read = RawRead::new(
+            r#"
+            SELECT workspace_id, service_id,
+                COUNT(*) FILTER (WHERE created_at < $2) AS baseline,
+                COUNT(*) FILTER (WHERE created_at >= $2) AS recent
+            FROM {raw} m
+            WHERE workspace_id = ANY($1)
+            GROUP BY workspace_id, service_id
+            "#,
+        )
+        .range(self.raw_range(baseline_from, to));
+        let rows = read
+            .bind(
+                sqlx::query(read.sql())
+                    .bind(workspace_ids)
+                    .bind(recent_from),
+            )
             .fetch_all(&self.pool)
             .await?;
 
-        let aggregations = rows
-            .into_iter()
-            .map(|row| AggregatedMetric {
+        Ok(rows
+            .iter()
+            .map(|row| ServiceThroughput {
                 workspace_id: row.get("workspace_id"),
                 service_id: row.get("service_id"),
-                bucket: row.get("bucket"),
-                query_count: row.get("query_count"),
-                avg_duration_ms: row.get("avg_duration_ms"),
-                min_duration_ms: row.get("min_duration_ms"),
-                max_duration_ms: row.get("max_duration_ms"),
-                p95_duration_ms: row.get("p95_duration_ms"),
-                p99_duration_ms: row.get("p99_duration_ms"),
-                success_count: row.get("success_count"),
-                failed_count: row.get("failed_count"),
-                total_rows_affected: row.get("total_rows_affected"),
+                baseline: row.get("baseline"),
+                recent: row.get("recent"),
             })
-            .collect();
-
-        Ok(aggregations)
+            .collect())
     }
 
-    /// Manually prune old data (backup for TimescaleDB retention policies)
-    pub async fn prune_old_metrics(&self, older_than_days: i32) -> Result<u64> {
-        let result = sqlx::query(
+    /// Record a newly detected throughput drop
+    async fn insert_throughput_drop(&self, drop: &ThroughputDrop) -> Result<()> {
+        // A service has at most one open drop; another instance may have
+        // recorded it first
+        sqlx::query(
             r#"
-            DELETE FROM query_metrics
-            WHERE created_at < NOW() - make_interval(days => $1)
+            INSERT INTO throughput_drops
+                (id, workspace_id, service_id, baseline_per_minute, recent_per_minute,
+                 started_at, recovered_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT DO NOTHING
             "#,
         )
-        .bind(older_than_days)
+        .bind(drop.id)
+        .bind(drop.workspace_id)
+        .bind(drop.service_id)
+        .bind(drop.baseline_per_minute)
+        .bind(drop.recent_per_minute)
+        .bind(drop.started_at)
+        .bind(drop.recovered_at)
         .execute(&self.pool)
         .await?;
 
-        Ok(result.rows_affected())
+        Ok(())
     }
 
-    // =========================================================================
-    // EMBEDDING METHODS
-    // =========================================================================
-
-    /// Insert or update a query embedding
-    pub async fn insert_query_embedding(
+    /// Close an open throughput drop
+    async fn resolve_throughput_drop(
         &self,
-        workspace_id: Uuid,
-        query_hash: &str,
-        sql_query: &str,
-        embedding: &[f32],
+        drop_id: Uuid,
+        recovered_at: DateTime<Utc>,
     ) -> Result<()> {
-        // Convert embedding to pgvector format string
-        let embedding_str = format!(
-            "[{}]",
-            embedding
-                .iter()
-                .map(|v| v.to_string())
-                .collect::<Vec<_>>()
-                .join(",")
-        );
-
         sqlx::query(
-            r#"
-            INSERT INTO query_embeddings (workspace_id, query_hash, sql_query, embedding)
-            VALUES ($1, $2, $3, $4::vector)
-            ON CONFLICT (workspace_id, query_hash) 
-            DO UPDATE SET embedding = $4::vector, updated_at = NOW()
-            "#,
+            "UPDATE throughput_drops SET recovered_at = $2 WHERE id = $1 AND recovered_at IS NULL",
         )
-        .bind(workspace_id)
-        .bind(query_hash)
-        .bind(sql_query)
-        .bind(&embedding_str)
+        .bind(drop_id)
+        .bind(recovered_at)
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
-    /// Check if a query embedding exists
-    #[allow(dead_code)]
-    pub async fn embedding_exists(&self, workspace_id: Uuid, query_hash: &str) -> Result<bool> {
-        let row = sqlx::query(
+    /// Get every workspace's open throughput drops
+    async fn list_open_throughput_drops(&self) -> Result<Vec<ThroughputDrop>> {
+        let rows = sqlx::query(
             r#"
-            SELECT EXISTS(
-                SELECT 1 FROM query_embeddings 
-                WHERE workspace_id = $1 AND query_hash = $2
-            ) as exists
+            SELECT id, workspace_id, service_id, baseline_per_minute, recent_per_minute,
+                started_at, recovered_at
+            FROM throughput_drops
+            WHERE recovered_at IS NULL
             "#,
         )
-        .bind(workspace_id)
-        .bind(query_hash)
-        .fetch_one(&self.pool)
+        .fetch_all(&self.pool)
         .await?;
 
-        Ok(row.get::<bool, _>("exists"))
+        Ok(rows.iter().map(throughput_drop_from_row).collect())
     }
 
-    /// Search for similar queries using cosine similarity
-    pub async fn search_similar_queries(
+    /// Get a workspace's recent and open throughput drops, newest first
+    async fn list_throughput_drops(
         &self,
         workspace_id: Uuid,
-        embedding: &[f32],
-        limit: i32,
-        threshold: f32,
-    ) -> Result<Vec<SimilarQuery>> {
-        let embedding_str = format!(
-            "[{}]",
-            embedding
-                .iter()
-                .map(|v| v.to_string())
-                .collect::<Vec<_>>()
-                .join(",")
-        );
-
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ThroughputDrop>> {
         let rows = sqlx::query(
             r#"
-            SELECT 
-                id,
-                sql_query,
-                1 - (embedding <=> $2::vector) as similarity
-            FROM query_embeddings
-            WHERE workspace_id = $1
-                AND 1 - (embedding <=> $2::vector) >= $4
-            ORDER BY embedding <=> $2::vector
+            SELECT id, workspace_id, service_id, baseline_per_minute, recent_per_minute,
+                started_at, recovered_at
+            FROM throughput_drops
+            WHERE workspace_id = $1 AND (started_at >= $2 OR recovered_at IS NULL)
+            ORDER BY started_at DESC
             LIMIT $3
             "#,
         )
         .bind(workspace_id)
-        .bind(&embedding_str)
+        .bind(since)
         .bind(limit)
-        .bind(threshold)
         .fetch_all(&self.pool)
         .await?;
 
-        let results = rows
-            .into_iter()
-            .map(|row| SimilarQuery {
-                id: row.get("id"),
-                sql_query: row.get("sql_query"),
-                similarity: row.get("similarity"),
-            })
-            .collect();
-
-        Ok(results)
+        Ok(rows.iter().map(throughput_drop_from_row).collect())
     }
+}
 
-    /// Get queries that haven't been embedded yet
-    pub async fn get_unembedded_queries(
-        &self,
-        workspace_id: Uuid,
-        limit: i64,
-    ) -> Result<Vec<(String, String)>> {
-        let rows = sqlx::query(
-            r#"
-            SELECT DISTINCT query_text, 
-                   md5(lower(regexp_replace(trim(query_text), '\s+', ' ', 'g'))) as query_hash
-            FROM query_metrics m
-            WHERE m.workspace_id = $1
-                AND NOT EXISTS (
-                    SELECT 1 FROM query_embeddings e 
-                    WHERE e.workspace_id = m.workspace_id 
-                    AND e.query_hash = md5(lower(regexp_replace(trim(m.query_text), '\s+', ' ', 'g')))
-                )
-            LIMIT $2
-            "#,
-        )
-        .bind(workspace_id)
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await?;
+/// Model that produced an embedding; vectors of different models aren't
+/// comparable
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize)]
+pub struct EmbeddingModel {
+    pub name: String,
+    /// Identifies the exact weights, e.g. a hash of the model file
+    pub version: String,
+}
 
-        let results = rows
-            .into_iter()
-            .map(|row| {
-                (
-                    row.get::<String, _>("query_text"),
-                    row.get::<String, _>("query_hash"),
-                )
-            })
-            .collect();
+/// A query embedding to store
+#[derive(Debug, Clone)]
+pub struct NewQueryEmbedding {
+    pub query_hash: String,
+    pub sql_query: String,
+    pub embedding: Vec<f32>,
+}
 
-        Ok(results)
-    }
+/// A query left unembedded for being over the byte limit
+#[derive(Debug, Clone)]
+pub struct EmbeddingSkip {
+    pub query_hash: String,
+    pub query_bytes: usize,
+}
 
-    // =========================================================================
-    // ANOMALY METHODS
-    // =========================================================================
+/// Similar query result from vector search
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SimilarQuery {
+    pub id: Uuid,
+    pub sql_query: String,
+    pub similarity: f64,
+}
 
-    /// Get metrics statistics for anomaly detection
-    pub async fn get_metrics_stats(&self, workspace_id: Uuid, limit: i64) -> Result<MetricsStats> {
-        let row = sqlx::query(
-            r#"
-            SELECT 
-                AVG(duration_ms)::DOUBLE PRECISION as mean,
-                STDDEV(duration_ms)::DOUBLE PRECISION as stddev,
-                COUNT(*) as count
-            FROM (
-                SELECT duration_ms 
-                FROM query_metrics 
-                WHERE workspace_id = $1 
-                ORDER BY created_at DESC 
-                LIMIT $2
-            ) recent
-            "#,
-        )
-        .bind(workspace_id)
-        .bind(limit)
-        .fetch_one(&self.pool)
-        .await?;
+/// A similar query with its performance over a look-back window
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SimilarQueryStats {
+    #[serde(flatten)]
+    pub query: SimilarQuery,
+    pub query_hash: String,
+    /// Calls in the look-back window
+    pub call_count: i64,
+    pub error_count: i64,
+    pub avg_duration_ms: Option<i64>,
+    pub p50_duration_ms: Option<i64>,
+    pub p95_duration_ms: Option<i64>,
+    pub p99_duration_ms: Option<i64>,
+}
+
+/// A stored metric's query and the embedded queries most like it
+#[derive(Debug, Clone)]
+pub struct MetricSimilarity {
+    pub query_hash: String,
+    pub query_text: String,
+    /// Model of the query's embedding; `None` until it's embedded, when
+    /// `similar` is empty
+    pub model: Option<EmbeddingModel>,
+    /// Most similar first, not including the query itself
+    pub similar: Vec<SimilarQueryStats>,
+}
+
+/// A group of similar queries found by the clustering task
+#[derive(Debug, Clone)]
+pub struct NewQueryCluster {
+    /// Tables the members reference most
+    pub label: Option<String>,
+    pub representative_hash: String,
+    pub representative_query: String,
+    /// Query hashes of the members, the representative included
+    pub members: Vec<String>,
+}
+
+/// A query cluster with its members' traffic over a window
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct QueryCluster {
+    /// 0 for the cluster with the most members when it was computed
+    pub id: i32,
+    pub label: Option<String>,
+    /// Embedded queries in the cluster
+    pub size: i64,
+    /// The member nearest the cluster's center
+    pub representative_hash: String,
+    pub representative_query: String,
+    /// Calls to any member in the window
+    pub call_count: i64,
+    pub error_count: i64,
+    pub total_duration_ms: i64,
+    pub avg_duration_ms: Option<i64>,
+    pub p95_duration_ms: Option<i64>,
+}
+
+/// A workspace's latest clustering
+#[derive(Debug, Clone)]
+pub struct QueryClusters {
+    /// Model whose embeddings were clustered
+    pub model: EmbeddingModel,
+    pub computed_at: DateTime<Utc>,
+    /// Most total time in the window first
+    pub clusters: Vec<QueryCluster>,
+}
+
+/// A stored query embedding with the query's recent latency
+#[derive(Debug, Clone)]
+pub struct EmbeddedQuery {
+    pub query_hash: String,
+    pub sql_query: String,
+    pub embedding: Vec<f32>,
+    /// Calls in the look-back window
+    pub call_count: i64,
+    pub avg_duration_ms: Option<i64>,
+    pub p95_duration_ms: Option<i64>,
+}
 
-        Ok(MetricsStats {
-            mean: row.get::<Option<f64>, _>("mean").unwrap_or(0.0),
-            stddev: row.get::<Option<f64>, _>("stddev").unwrap_or(0.0),
-            count: row.get::<i64, _>("count"),
+/// Parse pgvector's text output (`[0.1,0.2,...]`)
+fn parse_vector(text: &str) -> Result<Vec<f32>> {
+    text.trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .filter(|v| !v.is_empty())
+        .map(|v| {
+            v.parse()
+                .map_err(|_| AppError::InternalError(format!("Invalid vector component '{}'", v)))
         })
-    }
-
-    /// Get recent metrics with high duration for anomaly detection
-    pub async fn get_recent_metrics_for_anomaly(
-        &self,
-        workspace_id: Uuid,
-        since_seconds: i64,
-        threshold_ms: i64,
-    ) -> Result<Vec<QueryMetric>> {
-        let rows = sqlx::query(
-            r#"
-            SELECT 
-                id, workspace_id, service_id, query_text, status,
-                duration_ms, rows_affected, error_message,
-                started_at, completed_at, tags
-            FROM query_metrics
-            WHERE workspace_id = $1
-                AND created_at > NOW() - make_interval(secs => $2)
-                AND duration_ms > $3
-            ORDER BY duration_ms DESC
-            "#,
-        )
-        .bind(workspace_id)
-        .bind(since_seconds)
-        .bind(threshold_ms)
-        .fetch_all(&self.pool)
-        .await?;
+        .collect()
+}
 
-        let metrics = rows
-            .into_iter()
-            .map(|row| QueryMetric {
-                id: row.get("id"),
-                workspace_id: row.get("workspace_id"),
-                service_id: row.get("service_id"),
-                query_text: row.get("query_text"),
-                status: string_to_status(row.get("status")),
-                duration_ms: row.get::<i64, _>("duration_ms") as u64,
-                rows_affected: row.get("rows_affected"),
-                error_message: row.get("error_message"),
-                started_at: row.get("started_at"),
-                completed_at: row.get("completed_at"),
-                tags: row
-                    .get::<Option<Vec<String>>, _>("tags")
-                    .unwrap_or_default(),
-            })
-            .collect();
+/// Query catalog entry tracking when a fingerprint was first and last seen
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CatalogEntry {
+    pub fingerprint: String,
+    pub query_text: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub total_count: i64,
+    pub ownership: Option<QueryOwnership>,
+}
 
-        Ok(metrics)
-    }
+/// Metric the database rejected, kept for inspection and reprocessing
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeadLetter {
+    pub metric_id: Uuid,
+    pub workspace_id: Uuid,
+    pub metric: QueryMetric,
+    pub error: String,
+    pub reason: DeadLetterReason,
+    pub attempts: i32,
+    pub first_failed_at: DateTime<Utc>,
+    pub last_failed_at: DateTime<Utc>,
+}
 
-    /// Record a detected anomaly
-    pub async fn insert_anomaly(&self, anomaly: &QueryAnomaly) -> Result<()> {
-        sqlx::query(
-            r#"
-            INSERT INTO query_anomalies (
-                workspace_id, service_id, metric_id, query_text,
-                duration_ms, mean_duration_ms, stddev_duration_ms, z_score
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-            "#,
-        )
-        .bind(anomaly.workspace_id)
-        .bind(anomaly.service_id)
-        .bind(anomaly.metric_id)
-        .bind(&anomaly.query_text)
-        .bind(anomaly.duration_ms)
-        .bind(anomaly.mean_duration_ms)
-        .bind(anomaly.stddev_duration_ms)
-        .bind(anomaly.z_score)
-        .execute(&self.pool)
-        .await?;
+/// Why a metric was dead-lettered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadLetterReason {
+    /// The database rejected the metric's values (constraint violations,
+    /// oversized text) or the whole batch
+    #[default]
+    Rejected,
+    /// Quarantined because the metric didn't fit the schema (unknown column,
+    /// mismatched types), usually a sign of schema drift
+    SchemaDrift,
+}
 
-        Ok(())
+impl DeadLetterReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rejected => "rejected",
+            Self::SchemaDrift => "schema_drift",
+        }
     }
+}
 
-    /// Get all workspace IDs
-    pub async fn get_all_workspace_ids(&self) -> Result<Vec<Uuid>> {
-        let rows = sqlx::query("SELECT id FROM workspaces")
-            .fetch_all(&self.pool)
-            .await?;
+impl FromStr for DeadLetterReason {
+    type Err = String;
 
-        Ok(rows.into_iter().map(|r| r.get("id")).collect())
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "rejected" => Ok(Self::Rejected),
+            "schema_drift" => Ok(Self::SchemaDrift),
+            _ => Err(format!("Unknown dead letter reason: {}", s)),
+        }
     }
 }
 
-/// Similar query result from vector search
+/// What a batch insert did with its metrics
+#[derive(Debug, Clone, Default)]
+pub struct BatchInsert {
+    /// Metrics stored
+    pub inserted: usize,
+    /// Metrics whose values the database rejected, moved to the dead-letter
+    /// queue
+    pub rejected: usize,
+    /// Metrics quarantined in the dead-letter queue on schema drift
+    pub quarantined: Vec<QuarantinedMetric>,
+    /// Fingerprints seen for the first time in workspaces past their
+    /// learning period
+    pub new_shapes: Vec<NewQueryShape>,
+}
+
+/// How long a workspace's first fingerprints are taken as its baseline
+/// rather than reported as new shapes
+pub const NEW_SHAPE_LEARNING_PERIOD: TimeDelta = TimeDelta::days(1);
+
+/// A query fingerprint a workspace had never seen before
 #[derive(Debug, Clone, serde::Serialize)]
-pub struct SimilarQuery {
-    pub id: Uuid,
-    pub sql_query: String,
-    pub similarity: f64,
+pub struct NewQueryShape {
+    pub workspace_id: Uuid,
+    pub fingerprint: String,
+    pub query_text: String,
+    pub first_seen: DateTime<Utc>,
+}
+
+/// A metric quarantined because it didn't fit the schema
+#[derive(Debug, Clone)]
+pub struct QuarantinedMetric {
+    pub workspace_id: Uuid,
+    pub error: String,
+}
+
+/// Narrows aggregations and recent metrics; every set field must match
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct MetricFilter {
+    pub service_id: Option<Uuid>,
+    /// Tags the metric must all carry
+    pub tags: Vec<String>,
+    /// Labels the metric must all carry with these values
+    pub labels: BTreeMap<String, String>,
+    /// Kind of statement the metric must be
+    pub query_kind: Option<QueryKind>,
+}
+
+/// Which of a workspace's metrics to erase; every set field must match
+#[derive(Debug, Clone, Default)]
+pub struct MetricErasure {
+    /// Stored at or after
+    pub from: Option<DateTime<Utc>>,
+    /// Stored before
+    pub to: Option<DateTime<Utc>>,
+    pub service_id: Option<Uuid>,
+    /// Case-insensitive substring of the query text
+    pub query_contains: Option<String>,
+}
+
+/// Rows removed by an erasure
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct ErasureCounts {
+    pub metrics: u64,
+    pub embeddings: u64,
+    pub anomalies: u64,
+    pub catalog_entries: u64,
+    pub dead_letters: u64,
+}
+
+/// How slow, and how recent, a workspace's metrics must be to be checked
+/// for anomalies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnomalyThreshold {
+    pub duration_ms: i64,
+    pub since_seconds: i64,
 }
 
 /// Metrics statistics for anomaly detection
@@ -529,20 +5490,144 @@ pub struct SimilarQuery {
 pub struct MetricsStats {
     pub mean: f64,
     pub stddev: f64,
+    pub median: f64,
+    /// Median absolute deviation from the median
+    pub mad: f64,
     pub count: i64,
+    /// How the workspace detects anomalies
+    pub method: AnomalyMethod,
 }
 
 /// Query anomaly record
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct QueryAnomaly {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub service_id: Uuid,
+    pub metric_id: Uuid,
+    pub fingerprint: String,
+    pub query_text: String,
+    pub duration_ms: i64,
+    /// Median under the MAD method
+    pub mean_duration_ms: i64,
+    /// Median absolute deviation under the MAD method
+    pub stddev_duration_ms: i64,
+    /// Modified z-score under the MAD method
+    pub z_score: f64,
+    /// How the anomaly was detected
+    pub method: AnomalyMethod,
+    /// Severity score from 0 to 100 (see `services::severity`)
+    pub severity: f64,
+    /// Score bucket, escalated by z-score and duration bands
+    pub severity_level: SeverityLevel,
+    /// Extra database time this execution took over the baseline (the mean,
+    /// or the median under MAD); a query's anomalies sum to what its slow
+    /// executions cost
+    pub estimated_extra_ms: i64,
+    /// Correlated signals found when the anomaly was detected
+    pub hints: Vec<AnomalyHint>,
+}
+
+/// What a root-cause hint points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HintKind {
+    /// Workspace annotation (deploy, config change, incident) near the anomaly
+    Annotation,
+    /// Failed queries in the same service well above their usual rate
+    ErrorSpike,
+    /// Another anomalous fingerprint touching the same tables
+    RelatedAnomaly,
+}
+
+/// Signal correlated with an anomaly, suggesting a possible cause
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AnomalyHint {
+    pub kind: HintKind,
+    /// Human-readable explanation
+    pub message: String,
+    /// ID of the annotation, or fingerprint of the related or failing query
+    pub reference: Option<String>,
+}
+
+/// Queries a service ran in a baseline window and in the recent window
+/// right after it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceThroughput {
+    pub workspace_id: Uuid,
+    pub service_id: Uuid,
+    pub baseline: i64,
+    pub recent: i64,
+}
+
+/// A service's query volume falling far below its recent baseline
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ThroughputDrop {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub service_id: Uuid,
+    /// Queries per minute before the drop
+    pub baseline_per_minute: f64,
+    /// Queries per minute when the drop was detected
+    pub recent_per_minute: f64,
+    pub started_at: DateTime<Utc>,
+    /// When volume recovered; `None` while the drop is open
+    pub recovered_at: Option<DateTime<Utc>>,
+}
+
+/// Persisted evaluation state of one alert rule
+#[derive(Debug, Clone)]
+pub struct AlertRuleState {
+    pub rule_id: Uuid,
+    /// The rule's `updated_at` when this state was built; an edited rule
+    /// starts over
+    pub rule_version: DateTime<Utc>,
+    pub state: RuleState,
+}
+
+/// Last computed status of one SLO
+#[derive(Debug, Clone)]
+pub struct SloStatus {
+    pub slo_id: Uuid,
+    pub computed_at: DateTime<Utc>,
+    /// Queries over the SLO's window
+    pub counts: SloCounts,
+    pub burn_rate_1h: Option<f64>,
+    pub burn_rate_6h: Option<f64>,
+    pub burn_state: BurnState,
+}
+
+/// An anomaly together with its root-cause hints
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AnomalyContext {
+    pub anomaly: AnomalyRecord,
+    pub hints: Vec<AnomalyHint>,
+}
+
+/// Stored anomaly as returned by the anomalies API
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AnomalyRecord {
+    pub id: Uuid,
     pub workspace_id: Uuid,
     pub service_id: Uuid,
     pub metric_id: Uuid,
+    pub fingerprint: String,
     pub query_text: String,
     pub duration_ms: i64,
     pub mean_duration_ms: i64,
     pub stddev_duration_ms: i64,
     pub z_score: f64,
+    pub method: AnomalyMethod,
+    pub severity: f64,
+    pub severity_level: SeverityLevel,
+    /// `None` for anomalies recorded before impact estimation
+    pub estimated_extra_ms: Option<i64>,
+    pub detected_at: DateTime<Utc>,
+    pub status: AnomalyStatus,
+    /// Name of the API key that acknowledged or resolved the anomaly
+    pub acknowledged_by: Option<String>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub ownership: Option<QueryOwnership>,
 }
 
 /// Aggregated metric from continuous aggregate views
@@ -562,6 +5647,642 @@ pub struct AggregatedMetric {
     pub total_rows_affected: Option<i64>,
 }
 
+/// Metrics stored by a workspace on one UTC day
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UsageDay {
+    pub day: NaiveDate,
+    pub metric_count: i64,
+    /// Estimated storage used by those metrics
+    pub stored_bytes: i64,
+}
+
+/// What the ingest path did with a workspace's metrics in one minute
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct IngestionMinute {
+    pub minute: DateTime<Utc>,
+    /// Buffered for storage
+    pub accepted: i64,
+    /// Lost because the buffer was full
+    pub dropped: i64,
+    /// Skipped as resends of a recently ingested metric ID
+    pub deduplicated: i64,
+}
+
+/// Per-fingerprint totals for one service over a time range
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FingerprintSummary {
+    pub fingerprint: String,
+    pub query_text: String,
+    pub call_count: i64,
+    pub total_duration_ms: i64,
+    pub avg_duration_ms: i64,
+    pub p95_duration_ms: i64,
+    pub failed_count: i64,
+    pub ownership: Option<QueryOwnership>,
+}
+
+/// Order of a per-fingerprint statistics listing, largest first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuerySort {
+    #[default]
+    TotalTime,
+    Calls,
+    P50,
+    P95,
+    P99,
+    ErrorRate,
+    LastSeen,
+}
+
+impl QuerySort {
+    /// Column of the statistics query to order by
+    fn column(&self) -> &'static str {
+        match self {
+            QuerySort::TotalTime => "total_duration_ms",
+            QuerySort::Calls => "call_count",
+            QuerySort::P50 => "p50_duration_ms",
+            QuerySort::P95 => "p95_duration_ms",
+            QuerySort::P99 => "p99_duration_ms",
+            QuerySort::ErrorRate => "error_rate",
+            QuerySort::LastSeen => "last_seen",
+        }
+    }
+}
+
+/// Which fingerprints to summarize, and which page of them
+#[derive(Debug, Clone)]
+pub struct FingerprintStatsQuery {
+    /// Executions stored at or after
+    pub from: DateTime<Utc>,
+    /// Executions stored before
+    pub to: DateTime<Utc>,
+    pub service_id: Option<Uuid>,
+    /// Kind of statement executions must be
+    pub query_kind: Option<QueryKind>,
+    pub sort: QuerySort,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// Catalog entries to find by their text, and which page of them
+#[derive(Debug, Clone)]
+pub struct QueryTextSearch {
+    /// Words each matching query contains (as word prefixes), a substring of
+    /// its text, or its fingerprint
+    pub text: String,
+    /// Only queries last seen at or after
+    pub from: Option<DateTime<Utc>>,
+    /// Only queries first seen before
+    pub to: Option<DateTime<Utc>>,
+    /// Only queries this service ran within the range
+    pub service_id: Option<Uuid>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl QueryTextSearch {
+    /// Full-text query requiring every word of the search text as a prefix
+    /// of a lexeme, or empty if it has no words
+    fn tsquery(&self) -> String {
+        hybrid_search::term_parts(&self.text.to_lowercase())
+            .map(|word| format!("{}:*", word))
+            .collect::<Vec<_>>()
+            .join(" & ")
+    }
+
+    /// `ILIKE` pattern matching the search text anywhere in a query
+    fn like_pattern(&self) -> String {
+        let escaped = self
+            .text
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        format!("%{}%", escaped)
+    }
+}
+
+/// Execution statistics for one fingerprint over a time range
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FingerprintStats {
+    pub fingerprint: String,
+    pub query_text: String,
+    pub call_count: i64,
+    /// Failed or timed-out executions
+    pub error_count: i64,
+    /// `error_count / call_count`
+    pub error_rate: f64,
+    pub total_duration_ms: i64,
+    pub p50_duration_ms: i64,
+    pub p95_duration_ms: i64,
+    pub p99_duration_ms: i64,
+    /// First execution in the range
+    pub first_seen: DateTime<Utc>,
+    /// Last execution in the range
+    pub last_seen: DateTime<Utc>,
+    pub ownership: Option<QueryOwnership>,
+}
+
+/// Execution totals of one fingerprint as sent by one service
+#[derive(Debug, Clone)]
+pub struct StatementTotals {
+    pub fingerprint: String,
+    pub service_id: Uuid,
+    pub query_text: String,
+    pub call_count: i64,
+    /// Failed or timed-out executions
+    pub error_count: i64,
+    pub total_duration_ms: i64,
+    pub max_duration_ms: i64,
+}
+
+/// Failure count for one fingerprint and error message
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ErrorSummary {
+    pub fingerprint: String,
+    pub query_text: String,
+    pub error_message: Option<String>,
+    pub count: i64,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Most affected query fingerprints listed per error group
+pub const ERROR_GROUP_FINGERPRINTS: usize = 20;
+
+/// Which failures to group into error classes
+#[derive(Debug, Clone)]
+pub struct ErrorGroupsQuery {
+    /// Failures stored at or after
+    pub from: DateTime<Utc>,
+    /// Failures stored before
+    pub to: DateTime<Utc>,
+    pub service_id: Option<Uuid>,
+    /// Kind of statement failures must be
+    pub query_kind: Option<QueryKind>,
+    pub limit: i64,
+}
+
+/// Failures sharing a normalized error message over a time range
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ErrorGroup {
+    pub error_fingerprint: String,
+    /// Normalized message, with varying values replaced by `?`
+    pub message: String,
+    /// One of the messages as reported
+    pub example: Option<String>,
+    pub count: i64,
+    /// Failures in the equally long range just before, for spotting trends
+    pub previous_count: i64,
+    /// Earliest stored failure of this class, in or before the range
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    /// Distinct query fingerprints that failed this way
+    pub query_count: i64,
+    /// The query fingerprints failing this way most often, at most
+    /// [`ERROR_GROUP_FINGERPRINTS`]
+    pub fingerprints: Vec<String>,
+}
+
+/// What duration statistics are grouped by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DurationGroupBy {
+    #[default]
+    Service,
+    Fingerprint,
+}
+
+impl DurationGroupBy {
+    /// Expression of the raw metrics that groups are keyed on
+    fn column(&self) -> &'static str {
+        match self {
+            DurationGroupBy::Service => "service_id::TEXT",
+            DurationGroupBy::Fingerprint => "COALESCE(fingerprint, md5(normalize_sql(query_text)))",
+        }
+    }
+}
+
+/// Which executions to summarize durations of
+#[derive(Debug, Clone)]
+pub struct DurationStatsQuery {
+    /// Executions stored at or after
+    pub from: DateTime<Utc>,
+    /// Executions stored before
+    pub to: DateTime<Utc>,
+    pub group_by: DurationGroupBy,
+    pub service_id: Option<Uuid>,
+    /// Kind of statement executions must be
+    pub query_kind: Option<QueryKind>,
+    pub limit: i64,
+}
+
+/// Robust duration statistics for one group of executions over a time range
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DurationStats {
+    /// Service ID or query fingerprint, as grouped by
+    pub group: String,
+    pub query_count: i64,
+    pub median_ms: f64,
+    /// Median absolute deviation from the median
+    pub mad_ms: f64,
+    pub p999_ms: f64,
+    pub max_ms: i64,
+    /// The slowest execution, so it can be looked up
+    pub max_metric_id: Uuid,
+    /// How far the slowest execution is above the rest, as a modified
+    /// z-score; above 3.5 it is an outlier. Absent when the MAD is zero.
+    pub max_score: Option<f64>,
+}
+
+/// Modified z-score of `value` (Iglewicz and Hoaglin): its distance from the
+/// median in MADs, scaled to match a standard z-score on normal data
+pub fn modified_z_score(value: f64, median: f64, mad: f64) -> Option<f64> {
+    (mad > 0.0).then(|| 0.6745 * (value - median) / mad)
+}
+
+/// Open a connection pool with the given settings
+async fn connect_pool(connection_string: &str, config: &PoolConfig) -> Result<PgPool> {
+    let mut connect_options = PgConnectOptions::from_str(connection_string)
+        .map_err(|e| AppError::DatabaseError(format!("Invalid connection string: {}", e)))?;
+    if let Some(timeout) = config.statement_timeout {
+        connect_options =
+            connect_options.options([("statement_timeout", timeout.as_millis().to_string())]);
+    }
+
+    PgPoolOptions::new()
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .acquire_timeout(config.acquire_timeout)
+        .idle_timeout(config.idle_timeout)
+        .connect_with(connect_options)
+        .await
+        .map_err(|e| AppError::DatabaseError(format!("Failed to connect: {}", e)))
+}
+
+/// Rebuild the hourly fingerprint rollups of `range`'s hours from `range`
+/// on, returning how many were written
+async fn rebuild_rollups(conn: &mut PgConnection, range: RawRange) -> Result<u64> {
+    let mut tx = conn.begin().await?;
+    sqlx::query("DELETE FROM fingerprint_rollups WHERE bucket >= $1")
+        .bind(range.from)
+        .execute(&mut *tx)
+        .await?;
+    // Another instance's refresh may have rebuilt the same buckets meanwhile
+    let read = RawRead::new(
+        r#"
+        INSERT INTO fingerprint_rollups (
+            workspace_id, service_id, fingerprint, bucket, query_text,
+            call_count, total_duration_ms, min_duration_ms, max_duration_ms,
+            p50_duration_ms, p95_duration_ms, p99_duration_ms,
+            failed_count, error_count, total_rows_affected,
+            first_seen, last_seen, query_kind, updated_at
+        )
+        SELECT
+            workspace_id,
+            service_id,
+            -- Rows stored before fingerprints were recorded fall back to the SQL-side form
+            COALESCE(fingerprint, md5(normalize_sql(query_text))) AS fingerprint,
+            time_bucket('1 hour', created_at) AS bucket,
+            MIN(query_text),
+            COUNT(*),
+            SUM(duration_ms)::BIGINT,
+            MIN(duration_ms),
+            MAX(duration_ms),
+            PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY duration_ms)::BIGINT,
+            PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY duration_ms)::BIGINT,
+            PERCENTILE_CONT(0.99) WITHIN GROUP (ORDER BY duration_ms)::BIGINT,
+            SUM(CASE WHEN status = 'failed' THEN 1 ELSE 0 END),
+            SUM(CASE WHEN status IN ('failed', 'timeout') THEN 1 ELSE 0 END),
+            SUM(COALESCE(rows_affected, 0))::BIGINT,
+            MIN(created_at),
+            MAX(created_at),
+            MAX(query_kind),
+            NOW()
+        FROM {raw} m
+        GROUP BY 1, 2, 3, 4
+        ON CONFLICT (workspace_id, service_id, fingerprint, bucket) DO UPDATE SET
+            query_text = EXCLUDED.query_text,
+            call_count = EXCLUDED.call_count,
+            total_duration_ms = EXCLUDED.total_duration_ms,
+            min_duration_ms = EXCLUDED.min_duration_ms,
+            max_duration_ms = EXCLUDED.max_duration_ms,
+            p50_duration_ms = EXCLUDED.p50_duration_ms,
+            p95_duration_ms = EXCLUDED.p95_duration_ms,
+            p99_duration_ms = EXCLUDED.p99_duration_ms,
+            failed_count = EXCLUDED.failed_count,
+            error_count = EXCLUDED.error_count,
+            total_rows_affected = EXCLUDED.total_rows_affected,
+            first_seen = EXCLUDED.first_seen,
+            last_seen = EXCLUDED.last_seen,
+            query_kind = EXCLUDED.query_kind,
+            updated_at = EXCLUDED.updated_at
+        "#,
+    )
+    .range(range);
+    let result = read.bind(sqlx::query(read.sql())).execute(&mut *tx).await?;
+    tx.commit().await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Insert one metric row
+async fn insert_metric_row(
+    conn: &mut PgConnection,
+    metric: &QueryMetric,
+    fingerprint: &str,
+) -> std::result::Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO query_metrics (
+            id, workspace_id, service_id, query_text, status,
+            duration_ms, rows_affected, error_message,
+            started_at, completed_at, tags, labels, fingerprint, error_fingerprint,
+            query_kind
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+        "#,
+    )
+    .bind(metric.id)
+    .bind(metric.workspace_id)
+    .bind(metric.service_id)
+    .bind(&metric.query_text)
+    .bind(status_to_string(&metric.status))
+    .bind(metric.duration_ms as i64)
+    .bind(metric.rows_affected)
+    .bind(&metric.error_message)
+    .bind(metric.started_at)
+    .bind(metric.completed_at)
+    .bind(&metric.tags)
+    .bind(Json(&metric.labels))
+    .bind(fingerprint)
+    .bind(metric_error_fingerprint(metric))
+    .bind(metric.query_kind.map(|k| k.as_str()))
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Add a metric to the dead-letter queue, or bump its attempts if already there
+async fn upsert_dead_letter(
+    conn: &mut PgConnection,
+    metric: &QueryMetric,
+    error: &str,
+    reason: DeadLetterReason,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO query_metrics_dlq (metric_id, workspace_id, metric, error, reason)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (metric_id) DO UPDATE SET
+            metric = EXCLUDED.metric,
+            error = EXCLUDED.error,
+            reason = EXCLUDED.reason,
+            attempts = query_metrics_dlq.attempts + 1,
+            last_failed_at = NOW()
+        "#,
+    )
+    .bind(metric.id)
+    .bind(metric.workspace_id)
+    .bind(Json(metric))
+    .bind(error)
+    .bind(reason.as_str())
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+fn api_key_from_row(row: &PgRow) -> ApiKey {
+    let scopes: Vec<String> = row.get("scopes");
+    ApiKey {
+        id: row.get("id"),
+        workspace_id: row.get("workspace_id"),
+        name: row.get("name"),
+        // Ignore scopes this version doesn't know rather than rejecting the key
+        scopes: scopes.iter().filter_map(|s| s.parse().ok()).collect(),
+        expires_at: row.get("expires_at"),
+        created_at: row.get("created_at"),
+    }
+}
+
+fn dead_letter_from_row(row: &PgRow) -> DeadLetter {
+    DeadLetter {
+        metric_id: row.get("metric_id"),
+        workspace_id: row.get("workspace_id"),
+        metric: row.get::<Json<QueryMetric>, _>("metric").0,
+        error: row.get("error"),
+        reason: row.get::<String, _>("reason").parse().unwrap_or_default(),
+        attempts: row.get("attempts"),
+        first_failed_at: row.get("first_failed_at"),
+        last_failed_at: row.get("last_failed_at"),
+    }
+}
+
+/// Read duration statistics with the workspace's anomaly method
+fn metrics_stats_from_row(row: &PgRow) -> MetricsStats {
+    MetricsStats {
+        mean: row.get::<Option<f64>, _>("mean").unwrap_or(0.0),
+        stddev: row.get::<Option<f64>, _>("stddev").unwrap_or(0.0),
+        median: row.get::<Option<f64>, _>("median").unwrap_or(0.0),
+        mad: row.get::<Option<f64>, _>("mad").unwrap_or(0.0),
+        count: row.get::<i64, _>("count"),
+        method: row
+            .get::<String, _>("anomaly_method")
+            .parse()
+            .unwrap_or_default(),
+    }
+}
+
+/// Read an anomaly row joined with its ownership columns
+fn anomaly_from_row(row: &PgRow) -> AnomalyRecord {
+    AnomalyRecord {
+        id: row.get("id"),
+        workspace_id: row.get("workspace_id"),
+        service_id: row.get("service_id"),
+        metric_id: row.get("metric_id"),
+        fingerprint: row.get("fingerprint"),
+        query_text: row.get("query_text"),
+        duration_ms: row.get("duration_ms"),
+        mean_duration_ms: row.get("mean_duration_ms"),
+        stddev_duration_ms: row.get("stddev_duration_ms"),
+        z_score: row.get("z_score"),
+        method: row.get::<String, _>("method").parse().unwrap_or_default(),
+        severity: row.get("severity"),
+        severity_level: row
+            .get::<String, _>("severity_level")
+            .parse()
+            .unwrap_or(SeverityLevel::Low),
+        estimated_extra_ms: row.get("estimated_extra_ms"),
+        detected_at: row.get("detected_at"),
+        status: row.get::<String, _>("status").parse().unwrap_or_default(),
+        acknowledged_by: row.get("acknowledged_by"),
+        resolved_at: row.get("resolved_at"),
+        ownership: ownership_from_row(row),
+    }
+}
+
+/// Read ownership columns (aliased `owner_*`, possibly from a LEFT JOIN) from a row
+fn ownership_from_row(row: &PgRow) -> Option<QueryOwnership> {
+    let fingerprint: Option<String> = row.get("owner_fingerprint");
+    fingerprint.map(|fingerprint| QueryOwnership {
+        fingerprint,
+        owner_team: row.get("owner_team"),
+        ticket_links: row
+            .get::<Option<Vec<String>>, _>("owner_ticket_links")
+            .unwrap_or_default(),
+        notes: row.get("owner_notes"),
+        updated_at: row.get("owner_updated_at"),
+    })
+}
+
+/// Read a service row
+fn service_from_row(row: &PgRow) -> Service {
+    Service {
+        id: row.get("id"),
+        workspace_id: row.get("workspace_id"),
+        name: row.get("name"),
+        description: row.get("description"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        last_seen_at: row.get("last_seen_at"),
+    }
+}
+
+/// Report a duplicate service ID or name as a conflict
+fn service_conflict(err: sqlx::Error, name: &str) -> AppError {
+    match &err {
+        sqlx::Error::Database(db) if db.is_unique_violation() => {
+            AppError::Conflict(format!("Service '{}' already exists", name))
+        }
+        _ => err.into(),
+    }
+}
+
+/// Read a workspace annotation row
+fn annotation_from_row(row: &PgRow) -> Annotation {
+    Annotation {
+        id: row.get("id"),
+        kind: string_to_kind(row.get("kind")),
+        title: row.get("title"),
+        description: row.get("description"),
+        starts_at: row.get("starts_at"),
+        ends_at: row.get("ends_at"),
+        tags: row.get("tags"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// Read an audit log row, or `None` if its action is unknown
+fn audit_from_row(row: &PgRow) -> Option<AuditEntry> {
+    Some(AuditEntry {
+        id: row.get("id"),
+        workspace_id: row.get("workspace_id"),
+        action: row.get::<String, _>("action").parse().ok()?,
+        actor_key_id: row.get("actor_key_id"),
+        actor_name: row.get("actor_name"),
+        target: row.get("target"),
+        payload: row.get::<Json<serde_json::Value>, _>("payload").0,
+        created_at: row.get("created_at"),
+    })
+}
+
+/// Read an alert rule row
+fn alert_rule_from_row(row: &PgRow) -> AlertRule {
+    AlertRule {
+        id: row.get("id"),
+        workspace_id: row.get("workspace_id"),
+        name: row.get("name"),
+        service_id: row.get("service_id"),
+        window: row.get("window_size"),
+        conditions: row.get::<Json<Vec<AlertCondition>>, _>("conditions").0,
+        combine: row.get::<String, _>("combine").parse().unwrap_or_default(),
+        for_windows: row.get::<i32, _>("for_windows") as u32,
+        resolve_windows: row.get::<i32, _>("resolve_windows") as u32,
+        enabled: row.get("enabled"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+/// Read an SLO row
+fn slo_from_row(row: &PgRow) -> Slo {
+    Slo {
+        id: row.get("id"),
+        workspace_id: row.get("workspace_id"),
+        name: row.get("name"),
+        service_id: row.get("service_id"),
+        threshold_ms: row.get("threshold_ms"),
+        target_percent: row.get("target_percent"),
+        window_days: row.get::<i32, _>("window_days") as u32,
+        enabled: row.get("enabled"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+/// Read an SLO status row
+fn slo_status_from_row(row: &PgRow) -> SloStatus {
+    SloStatus {
+        slo_id: row.get("slo_id"),
+        computed_at: row.get("computed_at"),
+        counts: SloCounts {
+            total: row.get("total"),
+            satisfied: row.get("satisfied"),
+            tolerating: row.get("tolerating"),
+        },
+        burn_rate_1h: row.get("burn_rate_1h"),
+        burn_rate_6h: row.get("burn_rate_6h"),
+        burn_state: row
+            .get::<String, _>("burn_state")
+            .parse()
+            .unwrap_or_default(),
+    }
+}
+
+fn throughput_drop_from_row(row: &PgRow) -> ThroughputDrop {
+    ThroughputDrop {
+        id: row.get("id"),
+        workspace_id: row.get("workspace_id"),
+        service_id: row.get("service_id"),
+        baseline_per_minute: row.get("baseline_per_minute"),
+        recent_per_minute: row.get("recent_per_minute"),
+        started_at: row.get("started_at"),
+        recovered_at: row.get("recovered_at"),
+    }
+}
+
+/// Build a lifecycle stage from a row, skipping rows without one and actions
+/// this version doesn't know
+fn lifecycle_stage_from_row(row: &PgRow) -> Option<LifecycleStage> {
+    Some(LifecycleStage {
+        action: row.get::<Option<String>, _>("action")?.parse().ok()?,
+        after_days: row.get("after_days"),
+        last_run_at: row.get("last_run_at"),
+        last_affected: row.get("last_affected"),
+        last_error: row.get("last_error"),
+    })
+}
+
+/// Convert AnnotationKind to database string
+fn kind_to_string(kind: AnnotationKind) -> &'static str {
+    match kind {
+        AnnotationKind::Maintenance => "maintenance",
+        AnnotationKind::Incident => "incident",
+        AnnotationKind::ConfigChange => "config_change",
+        AnnotationKind::Other => "other",
+    }
+}
+
+/// Convert database string to AnnotationKind
+fn string_to_kind(s: &str) -> AnnotationKind {
+    match s {
+        "maintenance" => AnnotationKind::Maintenance,
+        "incident" => AnnotationKind::Incident,
+        "config_change" => AnnotationKind::ConfigChange,
+        _ => AnnotationKind::Other,
+    }
+}
+
 /// Convert QueryStatus to database string
 fn status_to_string(status: &QueryStatus) -> String {
     match status {
@@ -584,3 +6305,56 @@ fn string_to_status(s: &str) -> QueryStatus {
         _ => QueryStatus::Failed,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn range(from_hour: u32, to_hour: u32) -> RawRange {
+        let at = |hour| Utc.with_ymd_and_hms(2024, 1, 1, hour, 0, 0).unwrap();
+        RawRange {
+            from: at(from_hour),
+            to: at(to_hour),
+        }
+    }
+
+    #[test]
+    fn test_max_param() {
+        assert_eq!(max_param("SELECT 1"), 0);
+        assert_eq!(max_param("WHERE a = $2 AND b = $10 AND c = $1"), 10);
+        assert_eq!(max_param("$3::TEXT IS NULL OR kind = $3"), 3);
+    }
+
+    #[test]
+    fn test_raw_read_bounds_follow_statement_params() {
+        let read = RawRead::new("SELECT * FROM {raw} m WHERE workspace_id = $1 LIMIT $2")
+            .range(range(1, 2));
+        assert_eq!(
+            read.sql(),
+            "SELECT * FROM (SELECT * FROM query_metrics WHERE created_at >= $3 AND created_at < $4) m WHERE workspace_id = $1 LIMIT $2"
+        );
+        assert_eq!(read.bounds, vec![range(1, 2).from, range(1, 2).to]);
+    }
+
+    #[test]
+    fn test_raw_read_numbers_each_range() {
+        let read = RawRead::new("SELECT $1 FROM {raw} a JOIN {raw} b USING (id)")
+            .range(range(1, 2))
+            .range(range(3, 4));
+        assert!(read
+            .sql()
+            .contains("created_at >= $2 AND created_at < $3) a"));
+        assert!(read
+            .sql()
+            .contains("created_at >= $4 AND created_at < $5) b"));
+        assert_eq!(read.bounds.len(), 4);
+        assert_eq!(read.bounds[2], range(3, 4).from);
+    }
+
+    #[test]
+    #[should_panic(expected = "read without a range")]
+    fn test_raw_read_requires_a_range() {
+        RawRead::new("SELECT * FROM {raw} m").sql();
+    }
+}
@@ -0,0 +1,89 @@
+//! Recently ingested metric IDs, for dropping resends
+//!
+//! Agents that time out waiting for a response resend the batch, and without
+//! a check every metric in it would be stored twice. The ingest path skips
+//! metrics whose `(workspace_id, id)` it buffered recently. Only the newest
+//! IDs are remembered, and each process remembers the ones it ingested, so a
+//! resend that lands on another replica or arrives much later isn't caught.
+
+use parking_lot::Mutex;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Default)]
+struct Ids {
+    seen: HashSet<(Uuid, Uuid)>,
+    /// Oldest first
+    order: VecDeque<(Uuid, Uuid)>,
+}
+
+/// The last `capacity` metric IDs ingested per process
+#[derive(Clone)]
+pub struct RecentIds {
+    ids: Arc<Mutex<Ids>>,
+    capacity: usize,
+}
+
+impl RecentIds {
+    /// A zero `capacity` disables deduplication
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            ids: Arc::new(Mutex::new(Ids::default())),
+            capacity,
+        }
+    }
+
+    /// Remember an ID, returning `false` if it was already remembered
+    pub fn insert(&self, workspace_id: Uuid, id: Uuid) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+        let key = (workspace_id, id);
+        let mut ids = self.ids.lock();
+        if !ids.seen.insert(key) {
+            return false;
+        }
+        ids.order.push_back(key);
+        while ids.order.len() > self.capacity {
+            if let Some(oldest) = ids.order.pop_front() {
+                ids.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    /// Forget an ID, e.g. for a metric that didn't fit in the buffer, so
+    /// resending it isn't skipped
+    pub fn remove(&self, workspace_id: Uuid, id: Uuid) {
+        self.ids.lock().seen.remove(&(workspace_id, id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remembers_newest_ids_per_workspace() {
+        let ids = RecentIds::new(2);
+        let (ws, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        assert!(ids.insert(ws, a));
+        assert!(!ids.insert(ws, a));
+        assert!(ids.insert(other, a));
+        // `a` in `ws` is the oldest and is forgotten
+        assert!(ids.insert(ws, b));
+        assert!(ids.insert(ws, a));
+
+        ids.remove(ws, c);
+        assert!(ids.insert(ws, c));
+        ids.remove(ws, c);
+        assert!(ids.insert(ws, c));
+
+        let disabled = RecentIds::new(0);
+        assert!(disabled.insert(ws, a));
+        assert!(disabled.insert(ws, a));
+    }
+}
@@ -5,6 +5,7 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use serde::Serialize;
 use serde_json::json;
 use thiserror::Error;
 
@@ -17,15 +18,37 @@ pub enum AppError {
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
 
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
     #[error("Invalid request: {0}")]
     InvalidRequest(String),
 
+    #[error("Invalid payload: {} field error(s)", .0.len())]
+    InvalidPayload(Vec<FieldError>),
+
     #[error("Internal error: {0}")]
     InternalError(String),
 
     #[error("Not found: {0}")]
-    #[allow(dead_code)]
     NotFound(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Service unavailable: {0}")]
+    ServiceUnavailable(String),
+
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+}
+
+/// A payload field that failed schema validation
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    /// JSON Pointer to the field, e.g. `/metrics/0/status`
+    pub path: String,
+    pub message: String,
 }
 
 /// Result type alias using AppError
@@ -36,9 +59,28 @@ impl IntoResponse for AppError {
         let (status, error_message) = match &self {
             AppError::DatabaseError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
             AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg.clone()),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg.clone()),
             AppError::InvalidRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
+            AppError::InvalidPayload(errors) => {
+                let body = Json(json!({
+                    "error": "Payload does not match the schema",
+                    "code": StatusCode::BAD_REQUEST.as_u16(),
+                    "errors": errors,
+                }));
+                return (StatusCode::BAD_REQUEST, body).into_response();
+            }
             AppError::InternalError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
+            AppError::ServiceUnavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg.clone()),
+            AppError::QuotaExceeded(msg) => {
+                let body = Json(json!({
+                    "error": msg,
+                    "code": StatusCode::TOO_MANY_REQUESTS.as_u16(),
+                    "reason": "quota_exceeded",
+                }));
+                return (StatusCode::TOO_MANY_REQUESTS, body).into_response();
+            }
         };
 
         let body = Json(json!({
@@ -52,10 +94,58 @@ impl IntoResponse for AppError {
 
 impl From<sqlx::Error> for AppError {
     fn from(err: sqlx::Error) -> Self {
-        AppError::DatabaseError(err.to_string())
+        if is_transient(&err) {
+            AppError::ServiceUnavailable(err.to_string())
+        } else {
+            AppError::DatabaseError(err.to_string())
+        }
     }
 }
 
+/// Whether a database error is likely to go away on retry (lost connection, overload, ...)
+fn is_transient(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Io(_)
+        | sqlx::Error::Tls(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed
+        | sqlx::Error::WorkerCrashed => true,
+        // 08: connection exception, 53: insufficient resources, 57P: server shutting down,
+        // 40001/40P01: serialization failure / deadlock
+        sqlx::Error::Database(db) => db.code().is_some_and(|code| {
+            code.starts_with("08")
+                || code.starts_with("53")
+                || code.starts_with("57P")
+                || code == "40001"
+                || code == "40P01"
+        }),
+        _ => false,
+    }
+}
+
+/// Whether a database error means the schema doesn't match what this server
+/// writes (unknown column or table, mismatched types, a new required column),
+/// which usually means migrations and the server version are out of step
+pub fn is_schema_drift(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Database(db) => db.code().is_some_and(|code| schema_drift_code(&code)),
+        _ => false,
+    }
+}
+
+/// Whether a SQLSTATE is one of the schema mismatch errors
+fn schema_drift_code(code: &str) -> bool {
+    matches!(
+        code,
+        // undefined_column, undefined_table, undefined_function
+        "42703" | "42P01" | "42883"
+        // datatype_mismatch, cannot_coerce, invalid_text_representation
+        | "42804" | "42846" | "22P02"
+        // not_null_violation: a required column this server doesn't fill
+        | "23502"
+    )
+}
+
 impl From<serde_json::Error> for AppError {
     fn from(err: serde_json::Error) -> Self {
         AppError::InvalidRequest(err.to_string())
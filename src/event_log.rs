@@ -0,0 +1,200 @@
+//! Bounded log of recently ingested metrics for long-polling clients
+//!
+//! The ingest path appends every buffered metric, so a client walking the
+//! cursor sees the same stream that is stored, not just part of it.
+
+use crate::models::QueryMetric;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+/// Metrics read from the log after a cursor
+#[derive(Debug)]
+pub struct EventsSince {
+    pub metrics: Vec<QueryMetric>,
+    /// Cursor to pass on the next poll
+    pub cursor: u64,
+    /// Whether events after the requested cursor were evicted before being read
+    pub gap: bool,
+}
+
+struct Inner {
+    /// `(sequence, workspace_id, metric)`, oldest first
+    events: VecDeque<(u64, Uuid, QueryMetric)>,
+    /// Sequence number of the most recently pushed event (0 = none yet)
+    last_seq: u64,
+}
+
+/// Ring of the most recent metrics, each tagged with a sequence number that
+/// clients use as a poll cursor.
+///
+/// Sequence numbers are local to this process, so a cursor is only
+/// meaningful to the instance that issued it.
+#[derive(Clone)]
+pub struct EventLog {
+    inner: Arc<Mutex<Inner>>,
+    notify: Arc<Notify>,
+    capacity: usize,
+}
+
+impl EventLog {
+    /// Create a log retaining at most `capacity` events
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                events: VecDeque::with_capacity(capacity),
+                last_seq: 0,
+            })),
+            notify: Arc::new(Notify::new()),
+            capacity,
+        }
+    }
+
+    /// Append a metric, evicting the oldest event when full, and wake pollers
+    pub fn push(&self, workspace_id: Uuid, metric: QueryMetric) {
+        {
+            let mut inner = self.inner.lock();
+            inner.last_seq += 1;
+            let seq = inner.last_seq;
+            if inner.events.len() == self.capacity {
+                inner.events.pop_front();
+            }
+            inner.events.push_back((seq, workspace_id, metric));
+        }
+        self.notify.notify_waiters();
+    }
+
+    /// Sequence number of the most recent event
+    pub fn cursor(&self) -> u64 {
+        self.inner.lock().last_seq
+    }
+
+    /// Up to `limit` metrics for a workspace pushed after `cursor`
+    pub fn since(&self, workspace_id: Uuid, cursor: u64, limit: usize) -> EventsSince {
+        let inner = self.inner.lock();
+        // A cursor ahead of the log was issued before a restart; start over
+        let (cursor, reset) = if cursor > inner.last_seq {
+            (0, true)
+        } else {
+            (cursor, false)
+        };
+        let oldest = inner
+            .events
+            .front()
+            .map_or(inner.last_seq + 1, |(seq, _, _)| *seq);
+
+        let mut metrics = Vec::new();
+        let mut next_cursor = cursor;
+        for (seq, ws, metric) in inner.events.iter().filter(|(seq, _, _)| *seq > cursor) {
+            if metrics.len() == limit {
+                break;
+            }
+            next_cursor = *seq;
+            if *ws == workspace_id {
+                metrics.push(metric.clone());
+            }
+        }
+
+        EventsSince {
+            metrics,
+            cursor: next_cursor,
+            gap: reset || cursor + 1 < oldest,
+        }
+    }
+
+    /// Wait up to `timeout` for metrics for a workspace after `cursor`
+    ///
+    /// Returns as soon as at least one matching metric is available, or with
+    /// an empty result (and an advanced cursor) once the timeout elapses.
+    pub async fn wait_since(
+        &self,
+        workspace_id: Uuid,
+        cursor: u64,
+        limit: usize,
+        timeout: Duration,
+    ) -> EventsSince {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut cursor = cursor;
+        let mut gap = false;
+        loop {
+            // Register before reading so a push in between is not missed
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let mut events = self.since(workspace_id, cursor, limit);
+            gap |= events.gap;
+            if !events.metrics.is_empty() {
+                events.gap = gap;
+                return events;
+            }
+            cursor = events.cursor;
+
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                events.gap = gap;
+                return events;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QueryStatus;
+    use chrono::Utc;
+
+    fn metric(workspace_id: Uuid) -> QueryMetric {
+        QueryMetric::new(
+            workspace_id,
+            Uuid::new_v4(),
+            "SELECT 1".into(),
+            QueryStatus::Success,
+            1,
+            Utc::now(),
+        )
+    }
+
+    #[test]
+    fn test_since_filters_workspace_and_reports_gap() {
+        let log = EventLog::new(3);
+        let (ws, other) = (Uuid::new_v4(), Uuid::new_v4());
+        log.push(ws, metric(ws));
+        log.push(other, metric(other));
+
+        let events = log.since(ws, 0, 10);
+        assert_eq!(events.metrics.len(), 1);
+        assert_eq!(events.cursor, 2);
+        assert!(!events.gap);
+        assert!(log.since(ws, events.cursor, 10).metrics.is_empty());
+
+        // Evicts sequence 1 and 2
+        log.push(ws, metric(ws));
+        log.push(ws, metric(ws));
+        log.push(ws, metric(ws));
+        let events = log.since(ws, 1, 2);
+        assert!(events.gap);
+        assert_eq!(events.metrics.len(), 2);
+        assert_eq!(events.cursor, 4);
+    }
+
+    #[tokio::test]
+    async fn test_wait_since_wakes_on_push() {
+        let log = EventLog::new(10);
+        let ws = Uuid::new_v4();
+        let cursor = log.cursor();
+
+        let writer = log.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            writer.push(ws, metric(ws));
+        });
+
+        let events = log.wait_since(ws, cursor, 10, Duration::from_secs(5)).await;
+        assert_eq!(events.metrics.len(), 1);
+        assert_eq!(events.cursor, 1);
+    }
+}
@@ -0,0 +1,140 @@
+//! Per-minute ingest outcomes, counted in memory until flushed
+//!
+//! The ingest path counts, per workspace and minute, the metrics it accepted
+//! into the buffer, dropped because the buffer was full, and skipped as
+//! resends. Counting is a map update; the ingestion stats task adds the
+//! counts to the `ingestion_stats` table every few seconds, where every
+//! replica's counts for a minute are summed.
+
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::IngestionMinute;
+
+/// Outcomes of one ingest call for a workspace
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IngestCounts {
+    pub accepted: i64,
+    pub dropped: i64,
+    pub deduplicated: i64,
+}
+
+impl IngestCounts {
+    fn add(&mut self, other: IngestCounts) {
+        self.accepted += other.accepted;
+        self.dropped += other.dropped;
+        self.deduplicated += other.deduplicated;
+    }
+}
+
+/// Counts per `(workspace_id, minute)`
+type Pending = HashMap<(Uuid, DateTime<Utc>), IngestCounts>;
+
+/// Ingest counts not yet written to the database
+#[derive(Clone, Default)]
+pub struct IngestStats {
+    pending: Arc<Mutex<Pending>>,
+}
+
+impl IngestStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add counts to the minute holding `now`
+    pub fn record(&self, workspace_id: Uuid, counts: IngestCounts, now: DateTime<Utc>) {
+        if counts == IngestCounts::default() {
+            return;
+        }
+        let minute = now.duration_trunc(TimeDelta::minutes(1)).unwrap_or(now);
+        self.pending
+            .lock()
+            .entry((workspace_id, minute))
+            .or_default()
+            .add(counts);
+    }
+
+    /// Take the counts recorded since the last call
+    pub fn take(&self) -> Vec<(Uuid, IngestionMinute)> {
+        std::mem::take(&mut *self.pending.lock())
+            .into_iter()
+            .map(|((workspace_id, minute), counts)| {
+                (
+                    workspace_id,
+                    IngestionMinute {
+                        minute,
+                        accepted: counts.accepted,
+                        dropped: counts.dropped,
+                        deduplicated: counts.deduplicated,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Put back counts that couldn't be stored, to retry on the next flush
+    pub fn restore(&self, rows: Vec<(Uuid, IngestionMinute)>) {
+        let mut pending = self.pending.lock();
+        for (workspace_id, row) in rows {
+            pending
+                .entry((workspace_id, row.minute))
+                .or_default()
+                .add(IngestCounts {
+                    accepted: row.accepted,
+                    dropped: row.dropped,
+                    deduplicated: row.deduplicated,
+                });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_per_minute_until_taken() {
+        let stats = IngestStats::new();
+        let ws = Uuid::new_v4();
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let accepted = IngestCounts {
+            accepted: 3,
+            ..IngestCounts::default()
+        };
+
+        stats.record(ws, accepted, at("2026-01-01T12:00:10Z"));
+        stats.record(
+            ws,
+            IngestCounts {
+                dropped: 1,
+                deduplicated: 2,
+                ..accepted
+            },
+            at("2026-01-01T12:00:50Z"),
+        );
+        stats.record(ws, accepted, at("2026-01-01T12:01:00Z"));
+        stats.record(ws, IngestCounts::default(), at("2026-01-01T12:02:00Z"));
+
+        let mut rows = stats.take();
+        rows.sort_by_key(|(_, row)| row.minute);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0].1,
+            IngestionMinute {
+                minute: at("2026-01-01T12:00:00Z"),
+                accepted: 6,
+                dropped: 1,
+                deduplicated: 2,
+            }
+        );
+        assert!(stats.take().is_empty());
+
+        stats.restore(rows);
+        stats.record(ws, accepted, at("2026-01-01T12:01:30Z"));
+        let restored: i64 = stats.take().iter().map(|(_, row)| row.accepted).sum();
+        assert_eq!(restored, 12);
+    }
+}
@@ -1,10 +1,23 @@
 //! QueryVault library exports
 
+pub mod app;
+pub mod auth;
 pub mod buffer;
+pub mod casing;
+pub mod clock;
 pub mod db;
+pub mod dedup;
 pub mod error;
+pub mod event_log;
+pub mod ingest_stats;
+pub mod live_rollup;
 pub mod models;
+pub mod preflight;
 pub mod routes;
 pub mod services;
 pub mod state;
+pub mod store;
 pub mod tasks;
+#[cfg(feature = "testkit")]
+pub mod testkit;
+pub mod versioning;
@@ -0,0 +1,380 @@
+//! Rolling 1-minute aggregates of recent metrics, kept in memory
+//!
+//! Continuous aggregates are refreshed once a minute and leave out the
+//! current minute, so a dashboard reading `metrics_1m` sees new traffic a
+//! minute or two late. Metrics are folded into per-(workspace, service)
+//! minute buckets as the flush task stores them, and the last [`RETENTION_MINUTES`] of buckets are served by `?source=live`
+//! on the aggregations endpoint.
+//!
+//! Each bucket keeps up to [`MAX_SAMPLES`] durations, reservoir-sampled beyond
+//! that, for its percentiles. Buckets are local to this process: with several
+//! replicas, each reports only the metrics it ingested.
+//!
+//! Ingested metrics are also folded into 1-second buckets with
+//! [`SecondRollups`], which the broadcast task closes and pushes to WebSocket
+//! clients that subscribe to aggregates instead of individual metrics.
+
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::AggregatedMetric;
+use crate::models::{QueryMetric, QueryStatus};
+
+/// Minutes of buckets kept
+pub const RETENTION_MINUTES: i64 = 60;
+
+/// Durations kept per bucket for percentiles
+pub const MAX_SAMPLES: usize = 2048;
+
+#[derive(Debug, Default)]
+struct Bucket {
+    query_count: i64,
+    total_duration_ms: i64,
+    min_duration_ms: i64,
+    max_duration_ms: i64,
+    success_count: i64,
+    failed_count: i64,
+    total_rows_affected: i64,
+    /// Sampled durations, unordered
+    samples: Vec<i64>,
+}
+
+impl Bucket {
+    fn record(&mut self, metric: &QueryMetric) {
+        let duration = metric.duration_ms as i64;
+        if self.query_count == 0 {
+            self.min_duration_ms = duration;
+            self.max_duration_ms = duration;
+        } else {
+            self.min_duration_ms = self.min_duration_ms.min(duration);
+            self.max_duration_ms = self.max_duration_ms.max(duration);
+        }
+        self.query_count += 1;
+        self.total_duration_ms += duration;
+        match metric.status {
+            QueryStatus::Success => self.success_count += 1,
+            QueryStatus::Failed => self.failed_count += 1,
+            _ => {}
+        }
+        self.total_rows_affected += metric.rows_affected.unwrap_or(0);
+
+        if self.samples.len() < MAX_SAMPLES {
+            self.samples.push(duration);
+        } else {
+            let slot = rand::thread_rng().gen_range(0..self.query_count as usize);
+            if slot < MAX_SAMPLES {
+                self.samples[slot] = duration;
+            }
+        }
+    }
+
+    fn aggregate(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        bucket: DateTime<Utc>,
+    ) -> AggregatedMetric {
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        AggregatedMetric {
+            workspace_id,
+            service_id,
+            bucket,
+            query_count: self.query_count,
+            avg_duration_ms: Some(
+                (self.total_duration_ms as f64 / self.query_count as f64).round() as i64,
+            ),
+            min_duration_ms: Some(self.min_duration_ms),
+            max_duration_ms: Some(self.max_duration_ms),
+            p95_duration_ms: percentile_cont(&sorted, 0.95),
+            p99_duration_ms: percentile_cont(&sorted, 0.99),
+            success_count: Some(self.success_count),
+            failed_count: Some(self.failed_count),
+            total_rows_affected: Some(self.total_rows_affected),
+        }
+    }
+}
+
+/// Continuous percentile of sorted values, like PostgreSQL's `PERCENTILE_CONT`
+fn percentile_cont(sorted: &[i64], p: f64) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = p * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let value =
+        sorted[lower] as f64 + (sorted[upper] - sorted[lower]) as f64 * (rank - lower as f64);
+    Some(value.round() as i64)
+}
+
+/// One service's traffic during a second
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceSecond {
+    pub service_id: Uuid,
+    pub query_count: i64,
+    pub p95_duration_ms: Option<i64>,
+    /// Failed queries as a fraction of all queries
+    pub error_rate: f64,
+}
+
+/// A workspace's traffic during one second, per service
+#[derive(Debug, Clone, Serialize)]
+pub struct LiveAggregate {
+    pub workspace_id: Uuid,
+    /// Start of the second
+    pub bucket: DateTime<Utc>,
+    pub services: Vec<ServiceSecond>,
+}
+
+/// Buckets for the current second per `(workspace_id, service_id)`, folded
+/// into [`LiveAggregate`]s once the second is over
+#[derive(Default)]
+pub struct SecondRollups {
+    second: Option<DateTime<Utc>>,
+    buckets: HashMap<(Uuid, Uuid), Bucket>,
+}
+
+impl SecondRollups {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold metrics into the open second
+    pub fn add(&mut self, metrics: &[QueryMetric]) {
+        for metric in metrics {
+            self.buckets
+                .entry((metric.workspace_id, metric.service_id))
+                .or_default()
+                .record(metric);
+        }
+    }
+
+    /// Open the second containing `now`, returning the previous second's
+    /// aggregates, one per workspace with traffic, when `now` has moved past it
+    pub fn tick(&mut self, now: DateTime<Utc>) -> Vec<LiveAggregate> {
+        let second = now.duration_trunc(TimeDelta::seconds(1)).unwrap_or(now);
+        let finished = match self.second {
+            Some(current) if current != second => self.finish(current),
+            _ => Vec::new(),
+        };
+        self.second = Some(second);
+        finished
+    }
+
+    fn finish(&mut self, second: DateTime<Utc>) -> Vec<LiveAggregate> {
+        let mut workspaces: BTreeMap<Uuid, Vec<ServiceSecond>> = BTreeMap::new();
+        for ((workspace_id, service_id), bucket) in self.buckets.drain() {
+            let aggregate = bucket.aggregate(workspace_id, service_id, second);
+            workspaces
+                .entry(workspace_id)
+                .or_default()
+                .push(ServiceSecond {
+                    service_id,
+                    query_count: aggregate.query_count,
+                    p95_duration_ms: aggregate.p95_duration_ms,
+                    error_rate: bucket.failed_count as f64 / bucket.query_count as f64,
+                });
+        }
+        workspaces
+            .into_iter()
+            .map(|(workspace_id, mut services)| {
+                services.sort_by_key(|s| s.service_id);
+                LiveAggregate {
+                    workspace_id,
+                    bucket: second,
+                    services,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Minute buckets per `(workspace_id, service_id)`
+type Buckets = HashMap<(Uuid, Uuid), BTreeMap<DateTime<Utc>, Bucket>>;
+
+/// In-memory 1-minute aggregates of the last hour of metrics
+#[derive(Clone, Default)]
+pub struct LiveRollups {
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl LiveRollups {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold metrics into the buckets of the minute holding `now` and drop
+    /// expired ones
+    pub fn record(&self, metrics: &[QueryMetric], now: DateTime<Utc>) {
+        if metrics.is_empty() {
+            return;
+        }
+        let minute = now.duration_trunc(TimeDelta::minutes(1)).unwrap_or(now);
+        let cutoff = minute - TimeDelta::minutes(RETENTION_MINUTES);
+
+        let mut buckets = self.buckets.lock();
+        for metric in metrics {
+            buckets
+                .entry((metric.workspace_id, metric.service_id))
+                .or_default()
+                .entry(minute)
+                .or_default()
+                .record(metric);
+        }
+        buckets.retain(|_, series| {
+            series.retain(|bucket, _| *bucket > cutoff);
+            !series.is_empty()
+        });
+    }
+
+    /// A workspace's buckets within `[from, to)`, oldest first, optionally
+    /// for one service
+    pub fn get(
+        &self,
+        workspace_id: Uuid,
+        service_id: Option<Uuid>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<AggregatedMetric> {
+        let buckets = self.buckets.lock();
+        let mut aggregates: Vec<AggregatedMetric> = buckets
+            .iter()
+            .filter(|((ws, service), _)| {
+                *ws == workspace_id && !matches!(service_id, Some(id) if id != *service)
+            })
+            .flat_map(|((ws, service), series)| {
+                series
+                    .range(from..to)
+                    .map(|(bucket, b)| b.aggregate(*ws, *service, *bucket))
+            })
+            .collect();
+        aggregates.sort_by_key(|a| (a.bucket, a.service_id));
+        aggregates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolls_up_per_minute_and_expires() {
+        let rollups = LiveRollups::new();
+        let ws = Uuid::new_v4();
+        let service = Uuid::new_v4();
+        let metric = |duration_ms, status| {
+            let mut metric = QueryMetric::new(
+                ws,
+                service,
+                "SELECT 1".into(),
+                status,
+                duration_ms,
+                Utc::now(),
+            );
+            metric.rows_affected = Some(2);
+            metric
+        };
+        let start = "2026-01-01T12:00:10Z".parse::<DateTime<Utc>>().unwrap();
+
+        rollups.record(
+            &[
+                metric(10, QueryStatus::Success),
+                metric(30, QueryStatus::Failed),
+            ],
+            start,
+        );
+        rollups.record(
+            &[metric(5, QueryStatus::Success)],
+            start + TimeDelta::minutes(1),
+        );
+        let other = QueryMetric::new(
+            Uuid::new_v4(),
+            service,
+            "SELECT 1".into(),
+            QueryStatus::Success,
+            1,
+            Utc::now(),
+        );
+        rollups.record(&[other], start + TimeDelta::minutes(1));
+
+        let all = rollups.get(
+            ws,
+            None,
+            start - TimeDelta::hours(1),
+            start + TimeDelta::hours(1),
+        );
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].bucket, start - TimeDelta::seconds(10));
+        assert_eq!(all[0].query_count, 2);
+        assert_eq!(all[0].avg_duration_ms, Some(20));
+        assert_eq!(all[0].max_duration_ms, Some(30));
+        assert_eq!(all[0].p95_duration_ms, Some(29));
+        assert_eq!(all[0].failed_count, Some(1));
+        assert_eq!(all[0].total_rows_affected, Some(4));
+        assert!(rollups
+            .get(ws, Some(Uuid::new_v4()), start, start + TimeDelta::hours(1))
+            .is_empty());
+
+        // Recording an hour later expires both minutes
+        rollups.record(
+            &[metric(7, QueryStatus::Success)],
+            start + TimeDelta::minutes(RETENTION_MINUTES + 1),
+        );
+        let later = rollups.get(
+            ws,
+            Some(service),
+            start - TimeDelta::hours(1),
+            start + TimeDelta::hours(2),
+        );
+        assert_eq!(later.len(), 1);
+        assert_eq!(later[0].min_duration_ms, Some(7));
+    }
+
+    #[test]
+    fn test_second_rollups_close_each_second() {
+        let mut seconds = SecondRollups::new();
+        let (ws, service) = (Uuid::new_v4(), Uuid::new_v4());
+        let metric = |duration_ms, status| {
+            QueryMetric::new(
+                ws,
+                service,
+                "SELECT 1".into(),
+                status,
+                duration_ms,
+                Utc::now(),
+            )
+        };
+        let start = "2026-01-01T12:00:10.200Z".parse::<DateTime<Utc>>().unwrap();
+
+        let metrics: Vec<QueryMetric> = (1..=19)
+            .map(|d| metric(d, QueryStatus::Success))
+            .chain([metric(100, QueryStatus::Failed)])
+            .collect();
+        assert!(seconds.tick(start).is_empty());
+        seconds.add(&metrics[..10]);
+        assert!(seconds
+            .tick(start + TimeDelta::milliseconds(700))
+            .is_empty());
+        seconds.add(&metrics[10..]);
+
+        // A tick in the next second closes the previous one
+        let closed = seconds.tick(start + TimeDelta::seconds(1));
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].workspace_id, ws);
+        assert_eq!(closed[0].bucket, start - TimeDelta::milliseconds(200));
+        let stats = &closed[0].services[0];
+        assert_eq!(stats.service_id, service);
+        assert_eq!(stats.query_count, 20);
+        assert_eq!(stats.p95_duration_ms, Some(23));
+        assert_eq!(stats.error_rate, 0.05);
+
+        // Seconds without traffic produce nothing
+        assert!(seconds.tick(start + TimeDelta::seconds(2)).is_empty());
+    }
+}
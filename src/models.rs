@@ -2,8 +2,11 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
+use crate::services::severity::SeverityLevel;
+
 /// Status of a query execution
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -20,6 +23,50 @@ pub enum QueryStatus {
     Timeout,
 }
 
+/// Kind of statement a query is, assigned by the server at ingest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryKind {
+    /// Reads rows, including CTEs whose main statement is a SELECT
+    Select,
+    Insert,
+    Update,
+    Delete,
+    /// Changes the schema: CREATE, ALTER, DROP, TRUNCATE, ...
+    Ddl,
+    /// Anything else: MERGE, transaction control, SET, EXPLAIN, ...
+    Other,
+}
+
+impl QueryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueryKind::Select => "select",
+            QueryKind::Insert => "insert",
+            QueryKind::Update => "update",
+            QueryKind::Delete => "delete",
+            QueryKind::Ddl => "ddl",
+            QueryKind::Other => "other",
+        }
+    }
+}
+
+impl std::str::FromStr for QueryKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "select" => Ok(QueryKind::Select),
+            "insert" => Ok(QueryKind::Insert),
+            "update" => Ok(QueryKind::Update),
+            "delete" => Ok(QueryKind::Delete),
+            "ddl" => Ok(QueryKind::Ddl),
+            "other" => Ok(QueryKind::Other),
+            other => Err(format!("Unknown query kind: {}", other)),
+        }
+    }
+}
+
 /// A single query metric event
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryMetric {
@@ -29,6 +76,9 @@ pub struct QueryMetric {
     pub workspace_id: Uuid,
     /// Service that generated this metric
     pub service_id: Uuid,
+    /// Human-readable service name, used to register the service on first ingest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_name: Option<String>,
     /// The SQL query text
     pub query_text: String,
     /// Query execution status
@@ -46,10 +96,20 @@ pub struct QueryMetric {
     /// Optional metadata tags
     #[serde(default)]
     pub tags: Vec<String>,
+    /// Key/value labels, e.g. `{"env": "prod"}`; validated by the ingest schema
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// Query fingerprint, assigned by the server at ingest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// Kind of statement, assigned by the server at ingest; absent on
+    /// metrics stored before classification
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_kind: Option<QueryKind>,
 }
 
 impl QueryMetric {
-    /// Create a new QueryMetric with generated ID
+    /// Create a new QueryMetric with a generated (UUIDv7) ID
     #[allow(dead_code)]
     pub fn new(
         workspace_id: Uuid,
@@ -60,9 +120,10 @@ impl QueryMetric {
         started_at: DateTime<Utc>,
     ) -> Self {
         Self {
-            id: Uuid::new_v4(),
+            id: IdGenerator::default().generate(),
             workspace_id,
             service_id,
+            service_name: None,
             query_text,
             status,
             duration_ms,
@@ -71,23 +132,770 @@ impl QueryMetric {
             started_at,
             completed_at: Utc::now(),
             tags: Vec::new(),
+            labels: HashMap::new(),
+            fingerprint: None,
+            query_kind: None,
+        }
+    }
+}
+
+/// How IDs are generated for metrics and anomalies the server creates
+///
+/// UUIDv7 IDs start with a millisecond timestamp, so rows inserted together
+/// land next to each other in primary-key indexes instead of at random pages.
+/// Agents can use the same generator for the metric IDs they send.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdGenerator {
+    /// Random UUIDv4
+    V4,
+    /// Time-ordered UUIDv7
+    #[default]
+    V7,
+}
+
+impl IdGenerator {
+    /// Generate a new ID
+    pub fn generate(&self) -> Uuid {
+        match self {
+            IdGenerator::V4 => Uuid::new_v4(),
+            IdGenerator::V7 => Uuid::now_v7(),
+        }
+    }
+}
+
+impl std::str::FromStr for IdGenerator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "v4" => Ok(IdGenerator::V4),
+            "v7" => Ok(IdGenerator::V7),
+            other => Err(format!(
+                "Unknown ID generator: {} (expected v4 or v7)",
+                other
+            )),
         }
     }
 }
 
 /// Workspace represents a tenant/organization
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(dead_code)]
 pub struct Workspace {
     pub id: Uuid,
     pub name: String,
-    pub api_key: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// Permission granted by an API key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiScope {
+    /// Submit metrics
+    Ingest,
+    /// Read metrics, aggregations, and workspace configuration
+    Read,
+    /// Change workspace configuration and manage keys
+    Admin,
+    /// Report browser beacons; the only scope safe to embed in frontend code
+    Beacon,
+}
+
+impl ApiScope {
+    /// Name as stored
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::Ingest => "ingest",
+            ApiScope::Read => "read",
+            ApiScope::Admin => "admin",
+            ApiScope::Beacon => "beacon",
+        }
+    }
+}
+
+impl std::str::FromStr for ApiScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ingest" => Ok(ApiScope::Ingest),
+            "read" => Ok(ApiScope::Read),
+            "admin" => Ok(ApiScope::Admin),
+            "beacon" => Ok(ApiScope::Beacon),
+            other => Err(format!("Unknown API scope: {}", other)),
+        }
+    }
+}
+
+/// API key granting scoped access to one workspace
+///
+/// The secret itself is never part of this record; it is only returned once,
+/// when the key is created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    /// When the key stops working (`None` = never)
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiKey {
+    /// Whether the key grants a scope
+    pub fn has_scope(&self, scope: ApiScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    /// Whether the key has expired as of `now`
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= now)
+    }
+}
+
+/// Administrative change recorded in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditAction {
+    #[serde(rename = "api_key.create")]
+    ApiKeyCreate,
+    #[serde(rename = "api_key.delete")]
+    ApiKeyDelete,
+    #[serde(rename = "service.create")]
+    ServiceCreate,
+    #[serde(rename = "service.update")]
+    ServiceUpdate,
+    #[serde(rename = "service.delete")]
+    ServiceDelete,
+    #[serde(rename = "annotation.create")]
+    AnnotationCreate,
+    #[serde(rename = "annotation.delete")]
+    AnnotationDelete,
+    #[serde(rename = "ownership.update")]
+    OwnershipUpdate,
+    #[serde(rename = "ownership.delete")]
+    OwnershipDelete,
+    #[serde(rename = "mute.update")]
+    MuteUpdate,
+    #[serde(rename = "mute.delete")]
+    MuteDelete,
+    #[serde(rename = "settings.json_casing")]
+    JsonCasingUpdate,
+    #[serde(rename = "settings.quota")]
+    QuotaUpdate,
+    #[serde(rename = "settings.redaction")]
+    RedactionUpdate,
+    #[serde(rename = "settings.lifecycle")]
+    LifecycleUpdate,
+    #[serde(rename = "settings.anomaly_detection")]
+    AnomalyMethodUpdate,
+    #[serde(rename = "settings.anomaly")]
+    AnomalySettingsUpdate,
+    #[serde(rename = "dead_letters.reprocess")]
+    DeadLettersReprocess,
+    #[serde(rename = "embeddings.reembed")]
+    EmbeddingsReembed,
+    #[serde(rename = "anomaly.acknowledge")]
+    AnomalyAcknowledge,
+    #[serde(rename = "anomaly.resolve")]
+    AnomalyResolve,
+    #[serde(rename = "alert_rule.create")]
+    AlertRuleCreate,
+    #[serde(rename = "alert_rule.update")]
+    AlertRuleUpdate,
+    #[serde(rename = "alert_rule.delete")]
+    AlertRuleDelete,
+    #[serde(rename = "alert_destination.create")]
+    AlertDestinationCreate,
+    #[serde(rename = "alert_destination.delete")]
+    AlertDestinationDelete,
+    #[serde(rename = "metrics.erase")]
+    MetricsErase,
+    #[serde(rename = "slo.create")]
+    SloCreate,
+    #[serde(rename = "slo.update")]
+    SloUpdate,
+    #[serde(rename = "slo.delete")]
+    SloDelete,
+}
+
+impl AuditAction {
+    /// Name as stored
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::ApiKeyCreate => "api_key.create",
+            AuditAction::ApiKeyDelete => "api_key.delete",
+            AuditAction::ServiceCreate => "service.create",
+            AuditAction::ServiceUpdate => "service.update",
+            AuditAction::ServiceDelete => "service.delete",
+            AuditAction::AnnotationCreate => "annotation.create",
+            AuditAction::AnnotationDelete => "annotation.delete",
+            AuditAction::OwnershipUpdate => "ownership.update",
+            AuditAction::OwnershipDelete => "ownership.delete",
+            AuditAction::MuteUpdate => "mute.update",
+            AuditAction::MuteDelete => "mute.delete",
+            AuditAction::JsonCasingUpdate => "settings.json_casing",
+            AuditAction::QuotaUpdate => "settings.quota",
+            AuditAction::RedactionUpdate => "settings.redaction",
+            AuditAction::LifecycleUpdate => "settings.lifecycle",
+            AuditAction::AnomalyMethodUpdate => "settings.anomaly_detection",
+            AuditAction::AnomalySettingsUpdate => "settings.anomaly",
+            AuditAction::DeadLettersReprocess => "dead_letters.reprocess",
+            AuditAction::EmbeddingsReembed => "embeddings.reembed",
+            AuditAction::AnomalyAcknowledge => "anomaly.acknowledge",
+            AuditAction::AnomalyResolve => "anomaly.resolve",
+            AuditAction::AlertRuleCreate => "alert_rule.create",
+            AuditAction::AlertRuleUpdate => "alert_rule.update",
+            AuditAction::AlertRuleDelete => "alert_rule.delete",
+            AuditAction::AlertDestinationCreate => "alert_destination.create",
+            AuditAction::AlertDestinationDelete => "alert_destination.delete",
+            AuditAction::MetricsErase => "metrics.erase",
+            AuditAction::SloCreate => "slo.create",
+            AuditAction::SloUpdate => "slo.update",
+            AuditAction::SloDelete => "slo.delete",
+        }
+    }
+}
+
+impl std::str::FromStr for AuditAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "api_key.create" => Ok(AuditAction::ApiKeyCreate),
+            "api_key.delete" => Ok(AuditAction::ApiKeyDelete),
+            "service.create" => Ok(AuditAction::ServiceCreate),
+            "service.update" => Ok(AuditAction::ServiceUpdate),
+            "service.delete" => Ok(AuditAction::ServiceDelete),
+            "annotation.create" => Ok(AuditAction::AnnotationCreate),
+            "annotation.delete" => Ok(AuditAction::AnnotationDelete),
+            "ownership.update" => Ok(AuditAction::OwnershipUpdate),
+            "ownership.delete" => Ok(AuditAction::OwnershipDelete),
+            "mute.update" => Ok(AuditAction::MuteUpdate),
+            "mute.delete" => Ok(AuditAction::MuteDelete),
+            "settings.json_casing" => Ok(AuditAction::JsonCasingUpdate),
+            "settings.quota" => Ok(AuditAction::QuotaUpdate),
+            "settings.redaction" => Ok(AuditAction::RedactionUpdate),
+            "settings.lifecycle" => Ok(AuditAction::LifecycleUpdate),
+            "settings.anomaly_detection" => Ok(AuditAction::AnomalyMethodUpdate),
+            "settings.anomaly" => Ok(AuditAction::AnomalySettingsUpdate),
+            "dead_letters.reprocess" => Ok(AuditAction::DeadLettersReprocess),
+            "embeddings.reembed" => Ok(AuditAction::EmbeddingsReembed),
+            "anomaly.acknowledge" => Ok(AuditAction::AnomalyAcknowledge),
+            "anomaly.resolve" => Ok(AuditAction::AnomalyResolve),
+            "alert_rule.create" => Ok(AuditAction::AlertRuleCreate),
+            "alert_rule.update" => Ok(AuditAction::AlertRuleUpdate),
+            "alert_rule.delete" => Ok(AuditAction::AlertRuleDelete),
+            "alert_destination.create" => Ok(AuditAction::AlertDestinationCreate),
+            "alert_destination.delete" => Ok(AuditAction::AlertDestinationDelete),
+            "metrics.erase" => Ok(AuditAction::MetricsErase),
+            "slo.create" => Ok(AuditAction::SloCreate),
+            "slo.update" => Ok(AuditAction::SloUpdate),
+            "slo.delete" => Ok(AuditAction::SloDelete),
+            other => Err(format!("Unknown audit action: {}", other)),
+        }
+    }
+}
+
+/// Audit log entry: who changed what in a workspace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub action: AuditAction,
+    /// API key that made the change; kept after the key is revoked
+    pub actor_key_id: Uuid,
+    /// Name of that key at the time of the change
+    pub actor_name: String,
+    /// ID of the affected resource, e.g. a key, service, or fingerprint
+    pub target: Option<String>,
+    /// Details of the change; never includes secrets
+    pub payload: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Aggregate signal an alert condition is evaluated against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertSignal {
+    QueryCount,
+    ErrorCount,
+    /// Failed share of queries, from 0 to 1
+    ErrorRate,
+    AvgDurationMs,
+    P95DurationMs,
+    P99DurationMs,
+    MaxDurationMs,
+}
+
+impl AlertSignal {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertSignal::QueryCount => "query_count",
+            AlertSignal::ErrorCount => "error_count",
+            AlertSignal::ErrorRate => "error_rate",
+            AlertSignal::AvgDurationMs => "avg_duration_ms",
+            AlertSignal::P95DurationMs => "p95_duration_ms",
+            AlertSignal::P99DurationMs => "p99_duration_ms",
+            AlertSignal::MaxDurationMs => "max_duration_ms",
+        }
+    }
+}
+
+/// How a signal is compared with a condition's threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertComparison {
+    /// Breached above the threshold
+    Gt,
+    /// Breached below the threshold, e.g. for a traffic drop
+    Lt,
+}
+
+/// How a rule's conditions combine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertCombine {
+    /// Every condition must be breached
+    #[default]
+    All,
+    /// Any condition may be breached
+    Any,
+}
+
+impl AlertCombine {
+    /// Name as stored
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertCombine::All => "all",
+            AlertCombine::Any => "any",
+        }
+    }
+}
+
+impl std::str::FromStr for AlertCombine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(AlertCombine::All),
+            "any" => Ok(AlertCombine::Any),
+            other => Err(format!("Unknown alert combine mode: {}", other)),
+        }
+    }
+}
+
+/// One threshold in an alert rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertCondition {
+    pub signal: AlertSignal,
+    pub comparison: AlertComparison,
+    pub threshold: f64,
+    /// Value the signal must get back past before a firing condition counts
+    /// as clear (default: the threshold), so values hovering around the
+    /// threshold don't flap
+    pub clear_threshold: Option<f64>,
+}
+
+/// Alert rule evaluated by the rules task over each aggregation window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub name: String,
+    /// Service to watch (`None` = the whole workspace)
+    pub service_id: Option<Uuid>,
+    /// Aggregation window: "1m" or "5m"
+    pub window: String,
+    pub conditions: Vec<AlertCondition>,
+    pub combine: AlertCombine,
+    /// Consecutive breaching windows before the rule fires
+    pub for_windows: u32,
+    /// Consecutive clear windows before a firing rule resolves
+    pub resolve_windows: u32,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Latency service level objective tracked by the SLO task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Slo {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub name: String,
+    /// Service to track (`None` = the whole workspace)
+    pub service_id: Option<Uuid>,
+    /// A query is good if it succeeds within this many milliseconds
+    pub threshold_ms: i64,
+    /// Share of queries that must be good, in percent
+    pub target_percent: f64,
+    /// Rolling window compliance is measured over
+    pub window_days: u32,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// What a lifecycle stage does to a workspace's data once it reaches the
+/// stage's age; stages run in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LifecycleAction {
+    /// Clear fingerprinted raw metrics' query text, which the query catalog
+    /// keeps per fingerprint. Formerly `compress`, still accepted as such.
+    #[serde(alias = "compress")]
+    StripText,
+    /// Copy raw metrics to the archive, where they outlive raw retention
+    Archive,
+    /// Delete raw metrics, leaving the rollups; only once the continuous
+    /// aggregates no longer refresh them
+    Downsample,
+    /// Delete archived metrics and anomalies as well
+    Delete,
+}
+
+impl LifecycleAction {
+    /// Name as stored
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LifecycleAction::StripText => "strip_text",
+            LifecycleAction::Archive => "archive",
+            LifecycleAction::Downsample => "downsample",
+            LifecycleAction::Delete => "delete",
+        }
+    }
+}
+
+impl std::str::FromStr for LifecycleAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strip_text" => Ok(LifecycleAction::StripText),
+            "archive" => Ok(LifecycleAction::Archive),
+            "downsample" => Ok(LifecycleAction::Downsample),
+            "delete" => Ok(LifecycleAction::Delete),
+            other => Err(format!("Unknown lifecycle action: {}", other)),
+        }
+    }
+}
+
+/// One stage of a workspace's data lifecycle policy, with how its last run
+/// went
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LifecycleStage {
+    pub action: LifecycleAction,
+    /// Days after storage at which data reaches the stage
+    pub after_days: i32,
+    /// When the lifecycle task last ran the stage
+    #[serde(default)]
+    pub last_run_at: Option<DateTime<Utc>>,
+    /// Rows the last run changed or removed
+    #[serde(default)]
+    pub last_affected: Option<i64>,
+    /// Why the last run failed; cleared by the next successful run
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+impl LifecycleStage {
+    /// A stage that hasn't run yet
+    pub fn new(action: LifecycleAction, after_days: i32) -> Self {
+        Self {
+            action,
+            after_days,
+            last_run_at: None,
+            last_affected: None,
+            last_error: None,
+        }
+    }
+}
+
+/// How alerts are delivered to a destination
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertDestinationKind {
+    /// JSON POST to an arbitrary URL
+    #[default]
+    Webhook,
+    /// Block Kit message to a Slack incoming webhook URL
+    Slack,
+    /// PagerDuty Events API v2 event, routed by an integration key
+    Pagerduty,
+}
+
+impl AlertDestinationKind {
+    /// Name as stored
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertDestinationKind::Webhook => "webhook",
+            AlertDestinationKind::Slack => "slack",
+            AlertDestinationKind::Pagerduty => "pagerduty",
+        }
+    }
+}
+
+impl std::str::FromStr for AlertDestinationKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "webhook" => Ok(AlertDestinationKind::Webhook),
+            "slack" => Ok(AlertDestinationKind::Slack),
+            "pagerduty" => Ok(AlertDestinationKind::Pagerduty),
+            other => Err(format!("Unknown alert destination kind: {}", other)),
+        }
+    }
+}
+
+/// Where a workspace's alerts are sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertDestination {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub name: String,
+    pub kind: AlertDestinationKind,
+    pub url: String,
+    /// Key requests are signed with (`None` for destinations created before
+    /// deliveries were signed, and for Slack and PagerDuty)
+    pub signing_secret: Option<String>,
+    /// PagerDuty integration key events are routed by
+    pub routing_key: Option<String>,
+    /// Least severe anomaly sent to the destination
+    pub min_severity: SeverityLevel,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One alert sent to a destination, after any retries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertDelivery {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub destination_id: Uuid,
+    /// What was sent, e.g. "anomaly.detected"
+    pub event: String,
+    /// Sent from the test endpoint rather than for a real alert
+    pub test: bool,
+    /// Whether the destination answered with a 2xx status
+    pub delivered: bool,
+    /// HTTP status (`None` = no response)
+    pub status: Option<u16>,
+    pub latency_ms: u64,
+    /// Start of the response body
+    pub response: Option<String>,
+    /// Why the request got no response
+    pub error: Option<String>,
+    /// Requests made, including retries
+    pub attempts: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Field naming convention for JSON request and response bodies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonCasing {
+    /// `snake_case` fields, as the API has always used
+    #[default]
+    Snake,
+    /// `camelCase` fields
+    Camel,
+}
+
+impl JsonCasing {
+    /// Name as stored and accepted in headers
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JsonCasing::Snake => "snake",
+            JsonCasing::Camel => "camel",
+        }
+    }
+}
+
+impl std::str::FromStr for JsonCasing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "snake" | "snake_case" => Ok(JsonCasing::Snake),
+            "camel" | "camelcase" => Ok(JsonCasing::Camel),
+            other => Err(format!("Unknown JSON casing: {}", other)),
+        }
+    }
+}
+
+/// How anomaly detection measures how far a query is from its workspace's
+/// usual duration
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyMethod {
+    /// Standard deviations from the mean
+    #[default]
+    ZScore,
+    /// Median absolute deviations from the median, which a few extreme
+    /// outliers can't inflate
+    Mad,
+}
+
+impl AnomalyMethod {
+    /// Name as stored
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyMethod::ZScore => "z_score",
+            AnomalyMethod::Mad => "mad",
+        }
+    }
+}
+
+impl std::str::FromStr for AnomalyMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "z_score" => Ok(AnomalyMethod::ZScore),
+            "mad" => Ok(AnomalyMethod::Mad),
+            other => Err(format!("Unknown anomaly method: {}", other)),
+        }
+    }
+}
+
+/// Where an anomaly is in triage
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyStatus {
+    /// Not yet looked at
+    #[default]
+    Open,
+    /// Someone is looking into it
+    Acknowledged,
+    /// Dealt with
+    Resolved,
+}
+
+impl AnomalyStatus {
+    /// Name as stored
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnomalyStatus::Open => "open",
+            AnomalyStatus::Acknowledged => "acknowledged",
+            AnomalyStatus::Resolved => "resolved",
+        }
+    }
+}
+
+impl std::str::FromStr for AnomalyStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(AnomalyStatus::Open),
+            "acknowledged" => Ok(AnomalyStatus::Acknowledged),
+            "resolved" => Ok(AnomalyStatus::Resolved),
+            other => Err(format!("Unknown anomaly status: {}", other)),
+        }
+    }
+}
+
+/// Which time slots anomaly detection keeps separate baselines for, so
+/// queries that are always slower at certain hours aren't flagged then
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Seasonality {
+    /// One baseline from the most recent metrics
+    #[default]
+    None,
+    /// A baseline per hour of the day (UTC)
+    Daily,
+    /// A baseline per hour of the week (UTC)
+    Weekly,
+}
+
+impl Seasonality {
+    /// Name as stored
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Seasonality::None => "none",
+            Seasonality::Daily => "daily",
+            Seasonality::Weekly => "weekly",
+        }
+    }
+}
+
+impl std::str::FromStr for Seasonality {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Seasonality::None),
+            "daily" => Ok(Seasonality::Daily),
+            "weekly" => Ok(Seasonality::Weekly),
+            other => Err(format!("Unknown seasonality: {}", other)),
+        }
+    }
+}
+
+/// Anomaly detection settings in effect for a workspace
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AnomalySettings {
+    /// Standard deviations above the mean at which a query is anomalous
+    pub z_threshold: f64,
+    /// Modified z-score above which a query is anomalous under the MAD method
+    pub mad_threshold: f64,
+    /// Seconds between detection runs
+    pub interval_secs: i32,
+    /// Most recent metrics the statistics are computed over
+    pub lookback: i32,
+    /// Fewest recent metrics needed to detect anomalies at all, and fewest
+    /// in a seasonal slot for its baseline to be used
+    pub min_samples: i32,
+    /// Which time slots get baselines of their own
+    pub seasonality: Seasonality,
+}
+
+impl Default for AnomalySettings {
+    fn default() -> Self {
+        Self {
+            z_threshold: 3.0,
+            mad_threshold: 3.5,
+            interval_secs: 60,
+            lookback: 1000,
+            min_samples: 100,
+            seasonality: Seasonality::None,
+        }
+    }
+}
+
+impl AnomalySettings {
+    /// These settings with a workspace's overrides applied
+    pub fn with_overrides(self, overrides: &AnomalyOverrides) -> Self {
+        Self {
+            z_threshold: overrides.z_threshold.unwrap_or(self.z_threshold),
+            mad_threshold: overrides.mad_threshold.unwrap_or(self.mad_threshold),
+            interval_secs: overrides.interval_secs.unwrap_or(self.interval_secs),
+            lookback: overrides.lookback.unwrap_or(self.lookback),
+            min_samples: overrides.min_samples.unwrap_or(self.min_samples),
+            seasonality: overrides.seasonality.unwrap_or(self.seasonality),
+        }
+    }
+}
+
+/// Anomaly detection settings a workspace sets itself (`None` = the server's
+/// default)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnomalyOverrides {
+    pub z_threshold: Option<f64>,
+    pub mad_threshold: Option<f64>,
+    pub interval_secs: Option<i32>,
+    pub lookback: Option<i32>,
+    pub min_samples: Option<i32>,
+    pub seasonality: Option<Seasonality>,
+}
+
 /// Service represents an application within a workspace
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[allow(dead_code)]
 pub struct Service {
     pub id: Uuid,
     pub workspace_id: Uuid,
@@ -95,6 +903,61 @@ pub struct Service {
     pub description: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// When the service last reported metrics (`None` = never)
+    pub last_seen_at: Option<DateTime<Utc>>,
+}
+
+/// Ownership annotation attached to a query fingerprint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryOwnership {
+    pub fingerprint: String,
+    /// Team responsible for the query
+    pub owner_team: Option<String>,
+    /// Links to tickets tracking work on the query
+    pub ticket_links: Vec<String>,
+    /// Free-form triage notes
+    pub notes: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Mute entry marking a fingerprint as expected-slow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryMute {
+    pub fingerprint: String,
+    /// Why the query is muted (e.g. "nightly ETL")
+    pub reason: Option<String>,
+    /// When the mute lapses; `None` mutes indefinitely
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What an annotation marks on the timeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnotationKind {
+    /// Planned maintenance window
+    Maintenance,
+    /// Production incident
+    Incident,
+    /// Configuration or infrastructure change
+    ConfigChange,
+    /// Anything else worth marking
+    Other,
+}
+
+/// Workspace event annotation overlaid on dashboards
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Annotation {
+    pub id: Uuid,
+    pub kind: AnnotationKind,
+    pub title: String,
+    pub description: Option<String>,
+    pub starts_at: DateTime<Utc>,
+    /// End of the event; `None` marks a single point in time
+    pub ends_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Utc>,
 }
 
 /// Request payload for ingesting metrics
@@ -103,6 +966,76 @@ pub struct IngestRequest {
     pub metrics: Vec<QueryMetric>,
 }
 
+/// A single query metric in the compact v2 ingest format
+///
+/// Field names are shortened to cut payload size, durations are in
+/// microseconds, and agents may send the fingerprint they computed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct QueryMetricV2 {
+    pub id: Uuid,
+    #[serde(rename = "ws")]
+    pub workspace_id: Uuid,
+    #[serde(rename = "svc")]
+    pub service_id: Uuid,
+    #[serde(rename = "svc_name", default)]
+    pub service_name: Option<String>,
+    #[serde(rename = "q")]
+    pub query_text: String,
+    /// Fingerprint computed by the agent (default: computed by the server)
+    #[serde(rename = "fp", default)]
+    pub fingerprint: Option<String>,
+    #[serde(rename = "st")]
+    pub status: QueryStatus,
+    /// Execution duration in microseconds
+    #[serde(rename = "dur_us")]
+    pub duration_us: u64,
+    #[serde(rename = "rows", default)]
+    pub rows_affected: Option<i64>,
+    #[serde(rename = "err", default)]
+    pub error_message: Option<String>,
+    /// When the query started
+    #[serde(rename = "ts")]
+    pub started_at: DateTime<Utc>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+impl From<QueryMetricV2> for QueryMetric {
+    /// Durations are rounded to the nearest millisecond, and the completion
+    /// time is derived from the start time and duration.
+    fn from(metric: QueryMetricV2) -> Self {
+        let duration_us = i64::try_from(metric.duration_us).unwrap_or(i64::MAX);
+        Self {
+            id: metric.id,
+            workspace_id: metric.workspace_id,
+            service_id: metric.service_id,
+            service_name: metric.service_name,
+            query_text: metric.query_text,
+            status: metric.status,
+            duration_ms: metric.duration_us.saturating_add(500) / 1000,
+            rows_affected: metric.rows_affected,
+            error_message: metric.error_message,
+            started_at: metric.started_at,
+            completed_at: metric
+                .started_at
+                .checked_add_signed(chrono::Duration::microseconds(duration_us))
+                .unwrap_or(metric.started_at),
+            tags: metric.tags,
+            labels: metric.labels,
+            fingerprint: metric.fingerprint,
+            query_kind: None,
+        }
+    }
+}
+
+/// Request payload for ingesting metrics in the v2 format
+#[derive(Debug, Clone, Deserialize)]
+pub struct IngestRequestV2 {
+    pub metrics: Vec<QueryMetricV2>,
+}
+
 /// Response payload for ingestion
 #[derive(Debug, Clone, Serialize)]
 pub struct IngestResponse {
@@ -110,6 +1043,8 @@ pub struct IngestResponse {
     pub ingested: usize,
     /// Number of metrics dropped (buffer full)
     pub dropped: usize,
+    /// Number of metrics skipped as resends of recently ingested IDs
+    pub deduplicated: usize,
 }
 
 /// Health check response
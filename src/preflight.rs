@@ -0,0 +1,551 @@
+//! Startup configuration checks
+//!
+//! Catches settings that parse fine on their own but don't fit together: a
+//! buffer too small for the expected ingest rate, a model without its
+//! tokenizer, a database without TimescaleDB. Each finding names the setting
+//! and what to change. The checks run on every startup, and on their own with
+//! `query-vault --check`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::db::{PoolConfig, VectorIndexConfig, VectorIndexKind};
+use crate::models::AnomalySettings;
+use crate::services::anomaly;
+use crate::services::embedder::ProviderKind;
+use crate::services::embedding::{ExecutionProvider, RuntimeOptions};
+use crate::tasks::aggregation::{FLUSH_INTERVAL, MAX_FLUSH_BATCH};
+
+/// Postgres extensions the migrations create
+pub const REQUIRED_EXTENSIONS: &[&str] = &["uuid-ossp", "timescaledb", "vector", "pg_trgm"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// Works, but likely not as intended
+    Warning,
+    /// Startup would fail or lose data
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub level: Level,
+    /// Environment variable (or component) at fault
+    pub setting: &'static str,
+    pub message: String,
+}
+
+impl Finding {
+    fn warning(setting: &'static str, message: String) -> Self {
+        Self {
+            level: Level::Warning,
+            setting,
+            message,
+        }
+    }
+
+    fn error(setting: &'static str, message: String) -> Self {
+        Self {
+            level: Level::Error,
+            setting,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            Level::Warning => "warning",
+            Level::Error => "error",
+        };
+        write!(f, "{}: {}: {}", level, self.setting, self.message)
+    }
+}
+
+/// Settings checked before connecting to the database
+#[derive(Debug, Clone)]
+pub struct StartupConfig {
+    pub buffer_capacity: usize,
+    pub broadcast_capacity: usize,
+    /// Peak ingest rate the deployment should absorb (`EXPECTED_QPS`)
+    pub expected_qps: Option<u64>,
+    pub pool: PoolConfig,
+    pub embedding_model_path: Option<PathBuf>,
+    pub embedding_tokenizer_path: Option<PathBuf>,
+    /// ONNX Runtime settings of the local model
+    pub embedding_runtime: RuntimeOptions,
+    /// Similarity search index built at startup
+    pub vector_index: VectorIndexConfig,
+    /// Shards the per-workspace background tasks split workspaces into
+    pub task_shards: u32,
+    /// Anomaly detection settings of workspaces that don't set their own
+    pub anomaly_defaults: AnomalySettings,
+}
+
+/// Variable holding the default of an anomaly detection setting
+fn anomaly_variable(field: &str) -> &'static str {
+    match field {
+        "z_threshold" => "ANOMALY_Z_THRESHOLD",
+        "mad_threshold" => "ANOMALY_MAD_THRESHOLD",
+        "interval_secs" => "ANOMALY_INTERVAL_SECS",
+        "lookback" => "ANOMALY_LOOKBACK",
+        _ => "ANOMALY_MIN_SAMPLES",
+    }
+}
+
+/// Check that the settings fit together
+pub fn check_config(config: &StartupConfig) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    if config.buffer_capacity == 0 {
+        findings.push(Finding::error(
+            "BUFFER_CAPACITY",
+            "must be at least 1; every ingested metric would be dropped".into(),
+        ));
+    }
+    if config.broadcast_capacity == 0 {
+        findings.push(Finding::error(
+            "BROADCAST_CAPACITY",
+            "must be at least 1 to create the WebSocket broadcast channel".into(),
+        ));
+    }
+
+    if let Some(qps) = config.expected_qps.filter(|qps| *qps > 0) {
+        let flush_secs = FLUSH_INTERVAL.as_secs().max(1);
+        let per_flush = qps.saturating_mul(flush_secs);
+        let max_qps = MAX_FLUSH_BATCH as u64 / flush_secs;
+        if per_flush > MAX_FLUSH_BATCH as u64 {
+            findings.push(Finding::warning(
+                "EXPECTED_QPS",
+                format!(
+                    "each instance writes at most {} metrics every {}s ({}/s); at {}/s the \
+                     buffer keeps growing until metrics are dropped, so spread ingestion \
+                     over at least {} instances",
+                    MAX_FLUSH_BATCH,
+                    flush_secs,
+                    max_qps,
+                    qps,
+                    qps.div_ceil(max_qps.max(1)),
+                ),
+            ));
+        }
+
+        // Room for one missed flush, e.g. a slow insert or a brief outage
+        let needed = per_flush.min(MAX_FLUSH_BATCH as u64) * 2;
+        if (config.buffer_capacity as u64) < needed {
+            findings.push(Finding::warning(
+                "BUFFER_CAPACITY",
+                format!(
+                    "{} holds {:.1}s of metrics at {}/s; raise it to at least {} so a slow \
+                     flush doesn't drop metrics",
+                    config.buffer_capacity,
+                    config.buffer_capacity as f64 / qps as f64,
+                    qps,
+                    needed,
+                ),
+            ));
+        }
+
+        if (config.broadcast_capacity as u64) < qps {
+            findings.push(Finding::warning(
+                "BROADCAST_CAPACITY",
+                format!(
+                    "{} holds under a second of events at {}/s, so WebSocket clients and long \
+                     polls that fall behind miss events; raise it to at least {}",
+                    config.broadcast_capacity, qps, qps,
+                ),
+            ));
+        }
+    }
+
+    if config.task_shards == 0 {
+        findings.push(Finding::error(
+            "TASK_SHARDS",
+            "must be at least 1; set it to 1 to visit every workspace on each tick".into(),
+        ));
+    }
+
+    if let Some((field, problem)) = anomaly::check_settings(&config.anomaly_defaults) {
+        findings.push(Finding::error(anomaly_variable(field), problem));
+    }
+
+    if config.pool.max_connections == 0 {
+        findings.push(Finding::error(
+            "DB_MAX_CONNECTIONS",
+            "must be at least 1".into(),
+        ));
+    } else if config.pool.min_connections > config.pool.max_connections {
+        findings.push(Finding::error(
+            "DB_MIN_CONNECTIONS",
+            format!(
+                "{} is above DB_MAX_CONNECTIONS ({}); lower it or raise the maximum",
+                config.pool.min_connections, config.pool.max_connections,
+            ),
+        ));
+    }
+
+    match (
+        &config.embedding_model_path,
+        &config.embedding_tokenizer_path,
+    ) {
+        (Some(_), None) => findings.push(Finding::warning(
+            "EMBEDDING_TOKENIZER_PATH",
+            "not set, so the model at EMBEDDING_MODEL_PATH isn't loaded; point it at the \
+             model's tokenizer.json"
+                .into(),
+        )),
+        (None, Some(_)) => findings.push(Finding::warning(
+            "EMBEDDING_MODEL_PATH",
+            "not set, so EMBEDDING_TOKENIZER_PATH is ignored; point it at the ONNX model \
+             the tokenizer belongs to"
+                .into(),
+        )),
+        (Some(model), Some(tokenizer)) => {
+            for (setting, path) in [
+                ("EMBEDDING_MODEL_PATH", model),
+                ("EMBEDDING_TOKENIZER_PATH", tokenizer),
+            ] {
+                if !path.is_file() {
+                    findings.push(Finding::warning(
+                        setting,
+                        format!(
+                            "{} is not a readable file; local embeddings stay disabled",
+                            path.display()
+                        ),
+                    ));
+                }
+            }
+        }
+        (None, None) => {}
+    }
+
+    findings.extend(check_runtime(&config.embedding_runtime));
+    findings.extend(check_vector_index(&config.vector_index));
+
+    findings
+}
+
+/// Check the similarity search index parameters are within pgvector's limits
+fn check_vector_index(index: &VectorIndexConfig) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    match index.kind {
+        VectorIndexKind::Hnsw => {
+            if !(2..=100).contains(&index.m) {
+                findings.push(Finding::error(
+                    "EMBEDDING_HNSW_M",
+                    format!("{} is outside pgvector's range of 2 to 100", index.m),
+                ));
+            } else if !(2 * index.m..=1000).contains(&index.ef_construction) {
+                findings.push(Finding::error(
+                    "EMBEDDING_HNSW_EF_CONSTRUCTION",
+                    format!(
+                        "{} must be between twice EMBEDDING_HNSW_M ({}) and 1000",
+                        index.ef_construction,
+                        2 * index.m,
+                    ),
+                ));
+            }
+        }
+        VectorIndexKind::IvfFlat => {
+            if !(1..=32_768).contains(&index.lists) {
+                findings.push(Finding::error(
+                    "EMBEDDING_IVFFLAT_LISTS",
+                    format!("{} is outside pgvector's range of 1 to 32768", index.lists),
+                ));
+            }
+        }
+    }
+
+    findings
+}
+
+/// Check the local model's ONNX Runtime settings fit the host
+fn check_runtime(runtime: &RuntimeOptions) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for (setting, threads) in [
+        ("EMBEDDING_INTRA_OP_THREADS", runtime.intra_op_threads),
+        ("EMBEDDING_INTER_OP_THREADS", runtime.inter_op_threads),
+    ] {
+        if threads == Some(0) {
+            findings.push(Finding::error(
+                setting,
+                "must be at least 1; unset it to use the runtime's default".into(),
+            ));
+        }
+    }
+
+    let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if let (Some(intra), inter) = (runtime.intra_op_threads, runtime.inter_op_threads) {
+        let threads = intra.saturating_mul(inter.unwrap_or(1).max(1));
+        if threads > cpus {
+            findings.push(Finding::warning(
+                "EMBEDDING_INTRA_OP_THREADS",
+                format!(
+                    "{} threads per embedding call on {} CPUs oversubscribes the host and \
+                     slows ingestion; keep intra-op times inter-op threads at or below the \
+                     core count",
+                    threads, cpus
+                ),
+            ));
+        }
+    }
+
+    if runtime.execution_provider == ExecutionProvider::CoreMl && !cfg!(target_os = "macos") {
+        findings.push(Finding::error(
+            "EMBEDDING_EXECUTION_PROVIDER",
+            "coreml is only available on macOS; use cpu or cuda".into(),
+        ));
+    }
+    if runtime.device_id != 0 && runtime.execution_provider != ExecutionProvider::Cuda {
+        findings.push(Finding::warning(
+            "EMBEDDING_DEVICE_ID",
+            format!(
+                "only applies to cuda, but the execution provider is {}",
+                runtime.execution_provider
+            ),
+        ));
+    }
+
+    findings
+}
+
+/// Check the database offers the extensions the migrations need
+///
+/// `available` maps extension names to their installed version, as returned by
+/// `Database::available_extensions`. Extensions that are available but not
+/// yet installed are fine as long as migrations will run.
+pub fn check_extensions(
+    available: &HashMap<String, Option<String>>,
+    will_migrate: bool,
+) -> Vec<Finding> {
+    REQUIRED_EXTENSIONS
+        .iter()
+        .filter_map(|&name| match available.get(name) {
+            None => Some(Finding::error(
+                "DATABASE_URL",
+                format!(
+                    "the server doesn't provide the `{}` extension; use an image that \
+                     bundles it, such as timescale/timescaledb-ha",
+                    name
+                ),
+            )),
+            Some(None) if !will_migrate => Some(Finding::error(
+                "RUN_MIGRATIONS",
+                format!(
+                    "the `{}` extension isn't installed and migrations are disabled; run \
+                     `query-vault migrate` first",
+                    name
+                ),
+            )),
+            Some(_) => None,
+        })
+        .collect()
+}
+
+/// Check every embedding provider produces vectors the database can store
+///
+/// `providers` lists each provider's kind and dimension, as returned by
+/// `Embedder::dimensions`; `column` is the `query_embeddings.embedding`
+/// dimension.
+pub fn check_embedding_dimension(
+    providers: &[(ProviderKind, usize)],
+    column: usize,
+) -> Vec<Finding> {
+    providers
+        .iter()
+        .filter(|(_, dim)| *dim != column)
+        .map(|&(kind, dim)| {
+            let (setting, fix) = match kind {
+                ProviderKind::Local => ("EMBEDDING_MODEL_PATH", "load a model with that dimension"),
+                ProviderKind::Remote => ("EMBEDDING_DIM", "set EMBEDDING_DIM to match"),
+            };
+            Finding::error(
+                setting,
+                format!(
+                    "the {} embedding provider produces {}-dimension vectors but \
+                     query_embeddings.embedding is vector({}); {}, or migrate the column",
+                    kind.as_str(),
+                    dim,
+                    column,
+                    fix,
+                ),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tasks::shards::DEFAULT_SHARDS;
+
+    fn config() -> StartupConfig {
+        StartupConfig {
+            buffer_capacity: 100_000,
+            broadcast_capacity: 10_000,
+            expected_qps: None,
+            pool: PoolConfig::default(),
+            embedding_model_path: None,
+            embedding_tokenizer_path: None,
+            embedding_runtime: RuntimeOptions::default(),
+            vector_index: VectorIndexConfig::default(),
+            task_shards: DEFAULT_SHARDS,
+            anomaly_defaults: AnomalySettings::default(),
+        }
+    }
+
+    fn settings(findings: &[Finding]) -> Vec<&'static str> {
+        findings.iter().map(|f| f.setting).collect()
+    }
+
+    #[test]
+    fn test_defaults_pass() {
+        assert!(check_config(&config()).is_empty());
+        assert!(check_config(&StartupConfig {
+            expected_qps: Some(1_000),
+            ..config()
+        })
+        .is_empty());
+    }
+
+    #[test]
+    fn test_capacities_checked_against_expected_qps() {
+        let findings = check_config(&StartupConfig {
+            buffer_capacity: 5_000,
+            broadcast_capacity: 1_000,
+            expected_qps: Some(5_000),
+            ..config()
+        });
+        assert_eq!(
+            settings(&findings),
+            ["EXPECTED_QPS", "BUFFER_CAPACITY", "BROADCAST_CAPACITY"]
+        );
+        assert!(findings.iter().all(|f| f.level == Level::Warning));
+        assert!(findings[0].message.contains("at least 3 instances"));
+    }
+
+    #[test]
+    fn test_incoherent_settings_are_errors() {
+        let findings = check_config(&StartupConfig {
+            broadcast_capacity: 0,
+            task_shards: 0,
+            pool: PoolConfig {
+                min_connections: 20,
+                max_connections: 10,
+                ..PoolConfig::default()
+            },
+            embedding_model_path: Some("model.onnx".into()),
+            anomaly_defaults: AnomalySettings {
+                interval_secs: 10,
+                ..AnomalySettings::default()
+            },
+            ..config()
+        });
+        assert_eq!(
+            settings(&findings),
+            [
+                "BROADCAST_CAPACITY",
+                "TASK_SHARDS",
+                "ANOMALY_INTERVAL_SECS",
+                "DB_MIN_CONNECTIONS",
+                "EMBEDDING_TOKENIZER_PATH"
+            ]
+        );
+        assert_eq!(findings[2].level, Level::Error);
+        assert_eq!(findings[3].level, Level::Error);
+        assert_eq!(findings[4].level, Level::Warning);
+    }
+
+    #[test]
+    fn test_embedding_runtime_checked() {
+        let findings = check_config(&StartupConfig {
+            embedding_runtime: RuntimeOptions {
+                intra_op_threads: Some(100_000),
+                inter_op_threads: Some(0),
+                execution_provider: ExecutionProvider::Cpu,
+                device_id: 1,
+            },
+            ..config()
+        });
+        assert_eq!(
+            settings(&findings),
+            [
+                "EMBEDDING_INTER_OP_THREADS",
+                "EMBEDDING_INTRA_OP_THREADS",
+                "EMBEDDING_DEVICE_ID"
+            ]
+        );
+        assert_eq!(findings[0].level, Level::Error);
+        assert_eq!(findings[1].level, Level::Warning);
+
+        let cuda = RuntimeOptions {
+            intra_op_threads: Some(1),
+            execution_provider: ExecutionProvider::Cuda,
+            device_id: 1,
+            ..Default::default()
+        };
+        assert!(check_runtime(&cuda).is_empty());
+    }
+
+    #[test]
+    fn test_vector_index_parameters_checked() {
+        let hnsw = VectorIndexConfig {
+            m: 32,
+            ef_construction: 40,
+            ..VectorIndexConfig::default()
+        };
+        assert_eq!(
+            settings(&check_vector_index(&hnsw)),
+            ["EMBEDDING_HNSW_EF_CONSTRUCTION"]
+        );
+
+        // Parameters of the other kind aren't used, so aren't checked
+        let ivfflat = VectorIndexConfig {
+            kind: VectorIndexKind::IvfFlat,
+            m: 0,
+            lists: 0,
+            ..VectorIndexConfig::default()
+        };
+        assert_eq!(
+            settings(&check_vector_index(&ivfflat)),
+            ["EMBEDDING_IVFFLAT_LISTS"]
+        );
+        assert_eq!("ivfflat".parse(), Ok(VectorIndexKind::IvfFlat));
+    }
+
+    #[test]
+    fn test_missing_extensions() {
+        let available = HashMap::from([
+            ("uuid-ossp".to_string(), Some("1.1".to_string())),
+            ("timescaledb".to_string(), None),
+            ("pg_trgm".to_string(), Some("1.6".to_string())),
+        ]);
+        assert_eq!(
+            settings(&check_extensions(&available, true)),
+            ["DATABASE_URL"]
+        );
+        assert_eq!(
+            settings(&check_extensions(&available, false)),
+            ["RUN_MIGRATIONS", "DATABASE_URL"]
+        );
+    }
+
+    #[test]
+    fn test_embedding_dimension_must_match_column() {
+        let providers = [(ProviderKind::Local, 384), (ProviderKind::Remote, 1536)];
+        assert!(check_embedding_dimension(&providers[..1], 384).is_empty());
+
+        let findings = check_embedding_dimension(&providers, 384);
+        assert_eq!(settings(&findings), ["EMBEDDING_DIM"]);
+        assert_eq!(findings[0].level, Level::Error);
+        assert!(findings[0].message.contains("1536-dimension"));
+        assert_eq!(
+            settings(&check_embedding_dimension(&providers, 1536)),
+            ["EMBEDDING_MODEL_PATH"]
+        );
+    }
+}
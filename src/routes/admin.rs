@@ -0,0 +1,286 @@
+//! Admin API endpoints for the metrics dead-letter queue and embeddings
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::db::{DeadLetter, DeadLetterReason, EmbeddingModel};
+use crate::error::{AppError, Result};
+use crate::models::{ApiKey, AuditAction};
+use crate::routes::audit;
+use crate::services::embedder::Embedding;
+use crate::state::AppState;
+
+/// Longest text the embed endpoint accepts
+const MAX_EMBED_TEXT_BYTES: usize = 64 * 1024;
+
+/// Query parameters for listing dead letters
+#[derive(Debug, Deserialize)]
+pub struct DeadLetterQuery {
+    /// Workspace to list (default: the API key's workspace, the only one allowed)
+    pub workspace_id: Option<Uuid>,
+    /// Only entries dead-lettered for this reason (default: any)
+    pub reason: Option<DeadLetterReason>,
+    /// Maximum number of entries to return (default: 100, max: 1000)
+    pub limit: Option<i64>,
+}
+
+/// Response listing dead letters
+#[derive(Debug, Serialize)]
+pub struct DeadLetterListResponse {
+    pub count: usize,
+    pub dead_letters: Vec<DeadLetter>,
+}
+
+/// Request body for reprocessing dead letters
+#[derive(Debug, Deserialize)]
+pub struct ReprocessRequest {
+    /// Specific metric IDs to reprocess; when omitted the most recent
+    /// failures are taken instead
+    pub ids: Option<Vec<Uuid>>,
+    /// Workspace to reprocess (default: the API key's workspace, the only one allowed)
+    pub workspace_id: Option<Uuid>,
+    /// Only entries dead-lettered for this reason, when `ids` is omitted
+    /// (default: any)
+    pub reason: Option<DeadLetterReason>,
+    /// Maximum number of entries to reprocess (default: 100, max: 1000)
+    pub limit: Option<i64>,
+}
+
+/// Response for a reprocess run
+#[derive(Debug, Serialize)]
+pub struct ReprocessResponse {
+    /// Dead letters selected for reprocessing
+    pub reprocessed: usize,
+    /// Metrics inserted into query_metrics
+    pub inserted: usize,
+    /// Dead letters removed because they no longer fail
+    pub resolved: u64,
+}
+
+/// Request body for embedding text
+#[derive(Debug, Deserialize)]
+pub struct EmbedRequest {
+    pub text: String,
+}
+
+/// Response carrying an embedding vector
+#[derive(Debug, Serialize)]
+pub struct EmbedResponse {
+    pub dimensions: usize,
+    #[serde(flatten)]
+    pub embedding: Embedding,
+}
+
+/// Response for a re-embedding trigger
+#[derive(Debug, Serialize)]
+pub struct ReembedResponse {
+    /// Model stored embeddings are being brought to
+    pub model: EmbeddingModel,
+    /// The API key's workspace's embeddings from other models, at most 1000
+    pub stale: usize,
+}
+
+/// GET /api/v1/admin/dead-letters
+///
+/// Lists the API key's workspace's metrics that failed insertion, most
+/// recent failure first.
+///
+/// Query parameters:
+/// - workspace_id: Must be the API key's workspace (optional)
+/// - reason: `rejected` or `schema_drift` (optional)
+/// - limit: Maximum results (default: 100, max: 1000)
+pub async fn list_dead_letters(
+    State(state): State<AppState>,
+    Extension(key): Extension<ApiKey>,
+    Query(params): Query<DeadLetterQuery>,
+) -> Result<Json<DeadLetterListResponse>> {
+    let workspace_id = key_workspace(&key, params.workspace_id)?;
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+    let dead_letters = state
+        .db
+        .list_dead_letters(Some(workspace_id), params.reason, limit)
+        .await?;
+
+    Ok(Json(DeadLetterListResponse {
+        count: dead_letters.len(),
+        dead_letters,
+    }))
+}
+
+/// POST /api/v1/admin/dead-letters/reprocess
+///
+/// Re-inserts the API key's workspace's dead-lettered metrics. Rows that
+/// fail again stay in the queue with their attempt count bumped; the rest
+/// are removed.
+///
+/// Request body:
+/// - ids: Metric IDs to reprocess (optional)
+/// - workspace_id: Must be the API key's workspace (optional)
+/// - reason: `rejected` or `schema_drift`, when ids are omitted (optional)
+/// - limit: Maximum entries (default: 100, max: 1000)
+pub async fn reprocess_dead_letters(
+    State(state): State<AppState>,
+    Extension(key): Extension<ApiKey>,
+    Json(request): Json<ReprocessRequest>,
+) -> Result<Json<ReprocessResponse>> {
+    let workspace_id = key_workspace(&key, request.workspace_id)?;
+    let limit = request.limit.unwrap_or(100).clamp(1, 1000);
+
+    let mut letters = match &request.ids {
+        Some(ids) => {
+            if ids.len() as i64 > limit {
+                return Err(AppError::InvalidRequest(format!(
+                    "At most {} 'ids' can be reprocessed at once",
+                    limit
+                )));
+            }
+            state.db.get_dead_letters(ids).await?
+        }
+        None => {
+            state
+                .db
+                .list_dead_letters(Some(workspace_id), request.reason, limit)
+                .await?
+        }
+    };
+    letters.retain(|l| l.workspace_id == workspace_id);
+
+    if letters.is_empty() {
+        return Ok(Json(ReprocessResponse {
+            reprocessed: 0,
+            inserted: 0,
+            resolved: 0,
+        }));
+    }
+
+    let ids: Vec<(Uuid, i32)> = letters.iter().map(|l| (l.metric_id, l.attempts)).collect();
+    let metrics: Vec<_> = letters.into_iter().map(|l| l.metric).collect();
+
+    // Rows rejected again are re-dead-lettered by the insert itself, which
+    // bumps their attempt count past the one read, so they survive the
+    // delete whatever the clocks of this instance and the database say
+    let inserted = match state.db.insert_metrics_batch(&metrics).await {
+        Ok(outcome) => outcome.inserted,
+        Err(e) => {
+            warn!(error = %e, count = metrics.len(), "Dead-letter reprocess failed");
+            state
+                .db
+                .insert_dead_letters(&metrics, &e.to_string())
+                .await?;
+            return Err(e);
+        }
+    };
+    let resolved = state.db.delete_dead_letters(&ids).await?;
+
+    info!(
+        reprocessed = ids.len(),
+        inserted = inserted,
+        resolved = resolved,
+        "Reprocessed dead-lettered metrics"
+    );
+    audit::record(
+        &state,
+        &key,
+        workspace_id,
+        AuditAction::DeadLettersReprocess,
+        None,
+        json!({ "reprocessed": ids.len(), "inserted": inserted, "resolved": resolved }),
+    )
+    .await;
+
+    Ok(Json(ReprocessResponse {
+        reprocessed: ids.len(),
+        inserted,
+        resolved,
+    }))
+}
+
+/// POST /api/v1/admin/embed
+///
+/// Embeds text with the same providers and model the server uses for
+/// similarity search, returning the raw vector with the provider and model
+/// that produced it. For debugging relevance and for offline evaluation.
+/// Nothing is stored.
+///
+/// Request body:
+/// - text: Text to embed (max 64 KiB)
+pub async fn embed_text(
+    State(state): State<AppState>,
+    Json(request): Json<EmbedRequest>,
+) -> Result<Json<EmbedResponse>> {
+    if request.text.is_empty() || request.text.len() > MAX_EMBED_TEXT_BYTES {
+        return Err(AppError::InvalidRequest(format!(
+            "'text' must be 1-{} bytes",
+            MAX_EMBED_TEXT_BYTES
+        )));
+    }
+    let embedder = state
+        .embedder
+        .as_ref()
+        .ok_or_else(|| AppError::InternalError("Embedding service not configured".into()))?;
+
+    let embedding = embedder.embed(&request.text).await?;
+    Ok(Json(EmbedResponse {
+        dimensions: embedding.embedding.len(),
+        embedding,
+    }))
+}
+
+/// POST /api/v1/admin/embeddings/reembed
+///
+/// Wakes the re-embedding task, which rewrites every embedding made by a
+/// model other than the primary provider's, without waiting for its hourly
+/// run. Returns 202 with the target model and how many of the API key's
+/// workspace's embeddings are stale; the work happens in the background.
+pub async fn reembed_embeddings(
+    State(state): State<AppState>,
+    Extension(key): Extension<ApiKey>,
+) -> Result<(StatusCode, Json<ReembedResponse>)> {
+    let embedder = state
+        .embedder
+        .as_ref()
+        .ok_or_else(|| AppError::InternalError("Embedding service not configured".into()))?;
+
+    let model = embedder.model();
+    let stale = state
+        .db
+        .get_stale_embeddings(key.workspace_id, &model, 1000)
+        .await?
+        .len();
+    state.reembed.notify_one();
+
+    info!(
+        model = %model.name,
+        version = %model.version,
+        stale,
+        "Re-embedding requested"
+    );
+    audit::record(
+        &state,
+        &key,
+        key.workspace_id,
+        AuditAction::EmbeddingsReembed,
+        None,
+        json!({ "model": model.name, "version": model.version, "stale": stale }),
+    )
+    .await;
+
+    Ok((StatusCode::ACCEPTED, Json(ReembedResponse { model, stale })))
+}
+
+/// Workspace an admin request acts on, which must be the API key's own
+fn key_workspace(key: &ApiKey, requested: Option<Uuid>) -> Result<Uuid> {
+    match requested {
+        Some(workspace_id) if workspace_id != key.workspace_id => Err(AppError::Forbidden(
+            "API key does not belong to this workspace".into(),
+        )),
+        _ => Ok(key.workspace_id),
+    }
+}
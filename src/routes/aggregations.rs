@@ -5,17 +5,24 @@ use axum::{
     Json,
 };
 use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
-use crate::db::AggregatedMetric;
+use crate::db::{AggregatedMetric, MetricFilter};
 use crate::error::{AppError, Result};
+use crate::models::{Annotation, QueryKind};
+use crate::services::buckets::{
+    bucket_starts, fill_gaps, utc_aligned, window_width, Fill, MAX_FILLED_BUCKETS,
+};
+use crate::services::comparison::{Compare, PeriodDeltas, PeriodTotals};
 use crate::state::AppState;
 
 /// Query parameters for aggregations endpoint
 #[derive(Debug, Deserialize)]
 pub struct AggregationsQuery {
-    /// Aggregation window: "5s", "1m", "5m"
+    /// Aggregation window: "5s", "1m", "5m", "1h", "1d"
     #[serde(default = "default_window")]
     pub window: String,
     /// Start time (defaults to 1 hour ago)
@@ -24,20 +31,111 @@ pub struct AggregationsQuery {
     pub to: Option<DateTime<Utc>>,
     /// Optional service_id filter
     pub service_id: Option<Uuid>,
+    /// Comma-separated tags every counted metric must carry
+    pub tags: Option<String>,
+    /// Only count metrics of this kind of statement
+    pub query_kind: Option<QueryKind>,
+    /// Where buckets come from (default: stored)
+    #[serde(default)]
+    pub source: AggregationSource,
+    /// Add empty buckets for periods without queries
+    pub fill: Option<Fill>,
+    /// IANA timezone buckets are aligned to (default: UTC)
+    pub timezone: Option<String>,
+    /// Also aggregate an earlier range to compare against
+    pub compare: Option<Compare>,
 }
 
 fn default_window() -> String {
     "1m".to_string()
 }
 
+/// Parse a comma-separated `tags` parameter, sorted so equivalent filters
+/// share cache entries
+fn parse_tags(tags: Option<&str>) -> Vec<String> {
+    let mut tags: Vec<String> = tags
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Parse a `timezone` parameter
+fn parse_timezone(timezone: Option<&str>) -> Result<Tz> {
+    match timezone {
+        None => Ok(Tz::UTC),
+        Some(name) => name.parse().map_err(|_| {
+            AppError::InvalidRequest(format!(
+                "Unknown timezone '{}'. Use an IANA name such as 'Europe/Berlin'",
+                name
+            ))
+        }),
+    }
+}
+
+/// Collect `labels.<key>=<value>` query parameters
+fn parse_labels(pairs: &[(String, String)]) -> Result<BTreeMap<String, String>> {
+    pairs
+        .iter()
+        .filter_map(|(name, value)| Some((name.strip_prefix("labels.")?, value)))
+        .map(|(key, value)| {
+            if key.is_empty() {
+                return Err(AppError::InvalidRequest(
+                    "Label filters must name a key, e.g. 'labels.env=prod'".into(),
+                ));
+            }
+            Ok((key.to_string(), value.clone()))
+        })
+        .collect()
+}
+
+/// Where aggregation buckets are read from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregationSource {
+    /// TimescaleDB continuous aggregates
+    #[default]
+    Stored,
+    /// This instance's in-memory rollups of the last hour ("1m" window only),
+    /// which include the current minute
+    Live,
+}
+
 /// Response for aggregations endpoint
 #[derive(Debug, Serialize)]
 pub struct AggregationsResponse {
     pub workspace_id: Uuid,
     pub window: String,
+    /// Timezone buckets are aligned to
+    pub timezone: String,
     pub from: DateTime<Utc>,
     pub to: DateTime<Utc>,
     pub buckets: Vec<AggregatedMetric>,
+    /// Workspace annotations overlapping the time range
+    pub annotations: Vec<Annotation>,
+    /// The earlier range, when `compare` was given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comparison: Option<AggregationComparison>,
+}
+
+/// An earlier range's buckets and how the requested range differs from it
+#[derive(Debug, Serialize)]
+pub struct AggregationComparison {
+    pub compare: Compare,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub buckets: Vec<AggregatedMetric>,
+    /// Totals of the requested range
+    pub current: PeriodTotals,
+    /// Totals of the comparison range
+    pub previous: PeriodTotals,
+    /// Requested range minus comparison range
+    pub deltas: PeriodDeltas,
 }
 
 /// GET /api/v1/workspaces/:workspace_id/aggregations
@@ -45,26 +143,46 @@ pub struct AggregationsResponse {
 /// Returns aggregated metrics for the specified workspace and time window.
 ///
 /// Query parameters:
-/// - window: "5s", "1m", or "5m" (default: "1m")
+/// - window: "5s", "1m", "5m", "1h", or "1d" (default: "1m")
 /// - from: Start time (default: 1 hour ago)
 /// - to: End time (default: now)
 /// - service_id: Optional filter by service
+/// - tags: Optional comma-separated tags; only metrics carrying all of them
+///   are counted
+/// - labels.<key>: Optional label filters, e.g. `labels.env=prod`; only
+///   metrics carrying every given label value are counted
+/// - query_kind: Optional kind of statement ("select", "insert", "update",
+///   "delete", "ddl", "other"); only metrics of that kind are counted
+/// - source: "stored" (default) or "live" for this instance's in-memory 1-minute
+///   rollups of the last hour, which don't wait for the continuous aggregates
+///   to refresh
+/// - timezone: Optional IANA timezone, e.g. `Europe/Berlin`; buckets start at
+///   multiples of the window in local time, so "1d" buckets start at local
+///   midnight (default: UTC)
+/// - fill: Optional "zero", "null", or "previous"; adds a bucket with no
+///   queries for each empty period, per service, whose duration statistics
+///   are zero, missing, or carried over from the previous bucket
+/// - compare: Optional "previous_period" (the range of the same length just
+///   before) or "previous_week" (the same range a week earlier in local
+///   time); the response then also holds that range's buckets and the
+///   change in totals between the two
 pub async fn get_aggregations(
     State(state): State<AppState>,
     Path(workspace_id): Path<Uuid>,
     Query(params): Query<AggregationsQuery>,
+    Query(pairs): Query<Vec<(String, String)>>,
 ) -> Result<Json<AggregationsResponse>> {
     // Validate window parameter
-    let valid_windows = ["5s", "1m", "5m"];
+    let valid_windows = ["5s", "1m", "5m", "1h", "1d"];
     if !valid_windows.contains(&params.window.as_str()) {
         return Err(AppError::InvalidRequest(format!(
-            "Invalid window '{}'. Valid options: 5s, 1m, 5m",
+            "Invalid window '{}'. Valid options: 5s, 1m, 5m, 1h, 1d",
             params.window
         )));
     }
 
     // Set default time range
-    let now = Utc::now();
+    let now = state.clock.now();
     let from = params.from.unwrap_or_else(|| now - Duration::hours(1));
     let to = params.to.unwrap_or(now);
 
@@ -75,37 +193,149 @@ pub async fn get_aggregations(
         ));
     }
 
-    // Query aggregations from database
-    let mut buckets = state
-        .db
-        .get_aggregations(workspace_id, &params.window, from, to)
-        .await?;
+    if params.source == AggregationSource::Live && params.window != "1m" {
+        return Err(AppError::InvalidRequest(
+            "Live aggregations are only available for the '1m' window".into(),
+        ));
+    }
+
+    let tz = parse_timezone(params.timezone.as_deref())?;
+    let width = window_width(&params.window)
+        .ok_or_else(|| AppError::InvalidRequest(format!("Invalid window: {}", params.window)))?;
 
-    // Filter by service_id if provided
-    if let Some(service_id) = params.service_id {
-        buckets.retain(|b| b.service_id == service_id);
+    let filter = MetricFilter {
+        service_id: params.service_id,
+        tags: parse_tags(params.tags.as_deref()),
+        labels: parse_labels(&pairs)?,
+        query_kind: params.query_kind,
+    };
+    if params.source == AggregationSource::Live
+        && !(filter.tags.is_empty() && filter.labels.is_empty() && filter.query_kind.is_none())
+    {
+        return Err(AppError::InvalidRequest(
+            "Live aggregations can't be filtered by tags, labels, or query kind".into(),
+        ));
+    }
+    if params.source == AggregationSource::Live && !utc_aligned(width, tz, from, to) {
+        return Err(AppError::InvalidRequest(format!(
+            "Live aggregations can't be aligned to {}",
+            tz
+        )));
     }
 
+    if params.source == AggregationSource::Live && params.compare.is_some() {
+        return Err(AppError::InvalidRequest(
+            "Live aggregations only cover the last hour and can't be compared".into(),
+        ));
+    }
+    let previous_range = params.compare.map(|compare| compare.shift(from, to, tz));
+
+    // Check the ranges before querying, as filling them may be too large
+    let fill_starts = |from, to| match params.fill {
+        Some(_) => bucket_starts(from, to, width, tz, MAX_FILLED_BUCKETS)
+            .map(Some)
+            .ok_or_else(|| {
+                AppError::InvalidRequest(format!(
+                    "Filling gaps is limited to {} buckets; narrow the range or use a wider window",
+                    MAX_FILLED_BUCKETS
+                ))
+            }),
+        None => Ok(None),
+    };
+    let starts = fill_starts(from, to)?;
+    let previous_starts = match previous_range {
+        Some((from, to)) => fill_starts(from, to)?,
+        None => None,
+    };
+
+    let (buckets, annotations, previous) = match params.source {
+        AggregationSource::Live => (
+            state
+                .live_rollups
+                .get(workspace_id, params.service_id, from, to),
+            state.db.list_annotations(workspace_id, from, to).await?,
+            None,
+        ),
+        // Query aggregations, the annotations to overlay on them, and the
+        // comparison range
+        AggregationSource::Stored => tokio::try_join!(
+            state
+                .db
+                .get_aggregations(workspace_id, &params.window, tz, &filter, from, to),
+            state.db.list_annotations(workspace_id, from, to),
+            async {
+                match previous_range {
+                    Some((from, to)) => state
+                        .db
+                        .get_aggregations(workspace_id, &params.window, tz, &filter, from, to)
+                        .await
+                        .map(Some),
+                    None => Ok(None),
+                }
+            },
+        )?,
+    };
+    let fill = |buckets, starts: Option<Vec<DateTime<Utc>>>| match (params.fill, starts) {
+        (Some(fill), Some(starts)) => {
+            fill_gaps(buckets, &starts, fill, workspace_id, params.service_id)
+        }
+        _ => buckets,
+    };
+    let buckets = fill(buckets, starts);
+
+    let comparison = match (params.compare, previous_range, previous) {
+        (Some(compare), Some((previous_from, previous_to)), Some(previous)) => {
+            let previous = fill(previous, previous_starts);
+            let (current_totals, previous_totals) =
+                (PeriodTotals::of(&buckets), PeriodTotals::of(&previous));
+            Some(AggregationComparison {
+                compare,
+                from: previous_from,
+                to: previous_to,
+                buckets: previous,
+                current: current_totals,
+                previous: previous_totals,
+                deltas: PeriodDeltas::between(&current_totals, &previous_totals),
+            })
+        }
+        _ => None,
+    };
+
     Ok(Json(AggregationsResponse {
         workspace_id,
         window: params.window,
+        timezone: tz.name().to_string(),
         from,
         to,
         buckets,
+        annotations,
+        comparison,
     }))
 }
 
 /// GET /api/v1/workspaces/:workspace_id/metrics
 ///
-/// Returns recent raw metrics for the specified workspace.
+/// Returns recent raw metrics for the specified workspace, optionally
+/// filtered by `service_id`, comma-separated `tags`, `labels.<key>` values,
+/// and `query_kind`.
 pub async fn get_recent_metrics(
     State(state): State<AppState>,
     Path(workspace_id): Path<Uuid>,
     Query(params): Query<RecentMetricsQuery>,
+    Query(pairs): Query<Vec<(String, String)>>,
 ) -> Result<Json<RecentMetricsResponse>> {
     let limit = params.limit.unwrap_or(100).min(1000);
 
-    let metrics = state.db.get_recent_metrics(workspace_id, limit).await?;
+    let filter = MetricFilter {
+        service_id: params.service_id,
+        tags: parse_tags(params.tags.as_deref()),
+        labels: parse_labels(&pairs)?,
+        query_kind: params.query_kind,
+    };
+    let metrics = state
+        .db
+        .get_recent_metrics(workspace_id, &filter, limit)
+        .await?;
 
     Ok(Json(RecentMetricsResponse {
         workspace_id,
@@ -118,6 +348,12 @@ pub async fn get_recent_metrics(
 pub struct RecentMetricsQuery {
     /// Maximum number of metrics to return (default: 100, max: 1000)
     pub limit: Option<i64>,
+    /// Optional service_id filter
+    pub service_id: Option<Uuid>,
+    /// Comma-separated tags every returned metric must carry
+    pub tags: Option<String>,
+    /// Only return metrics of this kind of statement
+    pub query_kind: Option<QueryKind>,
 }
 
 #[derive(Debug, Serialize)]
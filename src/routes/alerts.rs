@@ -0,0 +1,258 @@
+//! Alert rule API endpoints
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::error::{AppError, Result};
+use crate::models::{
+    AlertCombine, AlertComparison, AlertCondition, AlertRule, ApiKey, AuditAction,
+};
+use crate::routes::audit;
+use crate::services::alerting;
+use crate::state::AppState;
+
+/// Most conditions one rule may combine
+const MAX_CONDITIONS: usize = 10;
+
+/// Most consecutive windows a rule may wait to fire or resolve
+const MAX_WINDOWS: u32 = 60;
+
+fn default_windows() -> u32 {
+    1
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Request body for creating or replacing an alert rule
+#[derive(Debug, Deserialize)]
+pub struct AlertRuleRequest {
+    /// Rule name (max 255 characters)
+    pub name: String,
+    /// Service to watch (default: the whole workspace)
+    pub service_id: Option<Uuid>,
+    /// Aggregation window: "1m" or "5m"
+    pub window: String,
+    pub conditions: Vec<AlertCondition>,
+    #[serde(default)]
+    pub combine: AlertCombine,
+    /// Consecutive breaching windows before firing (default: 1)
+    #[serde(default = "default_windows")]
+    pub for_windows: u32,
+    /// Consecutive clear windows before resolving (default: 1)
+    #[serde(default = "default_windows")]
+    pub resolve_windows: u32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+/// Response listing alert rules
+#[derive(Debug, Serialize)]
+pub struct AlertRuleListResponse {
+    pub workspace_id: Uuid,
+    pub count: usize,
+    pub rules: Vec<AlertRule>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/alert-rules
+///
+/// Lists alert rules, oldest first.
+pub async fn list_alert_rules(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+) -> Result<Json<AlertRuleListResponse>> {
+    let rules = state.db.list_alert_rules(workspace_id).await?;
+
+    Ok(Json(AlertRuleListResponse {
+        workspace_id,
+        count: rules.len(),
+        rules,
+    }))
+}
+
+/// POST /api/v1/workspaces/:workspace_id/alert-rules
+///
+/// Creates an alert rule. Its conditions are combined with `combine`
+/// ("all" or "any") and evaluated once per `window`.
+///
+/// Request body:
+/// - name: Rule name (max 255 characters)
+/// - service_id: Service to watch (optional, default: whole workspace)
+/// - window: "1m" or "5m"
+/// - conditions: 1-10 of `{signal, comparison, threshold, clear_threshold}`
+/// - combine: "all" (default) or "any"
+/// - for_windows: Consecutive breaching windows before firing (1-60, default: 1)
+/// - resolve_windows: Consecutive clear windows before resolving (1-60, default: 1)
+/// - enabled: Whether the rule is evaluated (default: true)
+pub async fn create_alert_rule(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path(workspace_id): Path<Uuid>,
+    Json(request): Json<AlertRuleRequest>,
+) -> Result<(StatusCode, Json<AlertRule>)> {
+    let rule = build_rule(Uuid::new_v4(), workspace_id, request, state.clock.now())?;
+
+    let stored = state.db.create_alert_rule(&rule).await?;
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::AlertRuleCreate,
+        Some(stored.id.to_string()),
+        json!(stored),
+    )
+    .await;
+    Ok((StatusCode::CREATED, Json(stored)))
+}
+
+/// GET /api/v1/workspaces/:workspace_id/alert-rules/:rule_id
+///
+/// Returns an alert rule.
+pub async fn get_alert_rule(
+    State(state): State<AppState>,
+    Path((workspace_id, rule_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<AlertRule>> {
+    state
+        .db
+        .get_alert_rule(workspace_id, rule_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound(format!("No alert rule '{}'", rule_id)))
+}
+
+/// PUT /api/v1/workspaces/:workspace_id/alert-rules/:rule_id
+///
+/// Replaces an alert rule's definition. The rule's firing state is reset.
+///
+/// Request body: as for creating a rule
+pub async fn update_alert_rule(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path((workspace_id, rule_id)): Path<(Uuid, Uuid)>,
+    Json(request): Json<AlertRuleRequest>,
+) -> Result<Json<AlertRule>> {
+    let rule = build_rule(rule_id, workspace_id, request, state.clock.now())?;
+
+    let stored = state
+        .db
+        .update_alert_rule(&rule)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No alert rule '{}'", rule_id)))?;
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::AlertRuleUpdate,
+        Some(rule_id.to_string()),
+        json!(stored),
+    )
+    .await;
+    Ok(Json(stored))
+}
+
+/// DELETE /api/v1/workspaces/:workspace_id/alert-rules/:rule_id
+///
+/// Deletes an alert rule.
+pub async fn delete_alert_rule(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path((workspace_id, rule_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode> {
+    if state.db.delete_alert_rule(workspace_id, rule_id).await? {
+        audit::record(
+            &state,
+            &actor,
+            workspace_id,
+            AuditAction::AlertRuleDelete,
+            Some(rule_id.to_string()),
+            json!({}),
+        )
+        .await;
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound(format!("No alert rule '{}'", rule_id)))
+    }
+}
+
+/// Validate a request and build the rule it describes
+fn build_rule(
+    id: Uuid,
+    workspace_id: Uuid,
+    request: AlertRuleRequest,
+    now: DateTime<Utc>,
+) -> Result<AlertRule> {
+    if request.name.is_empty() || request.name.len() > 255 {
+        return Err(AppError::InvalidRequest(
+            "'name' must be 1-255 characters".into(),
+        ));
+    }
+    if alerting::window_seconds(&request.window).is_none() {
+        return Err(AppError::InvalidRequest(
+            "'window' must be one of: 1m, 5m".into(),
+        ));
+    }
+    if request.conditions.is_empty() || request.conditions.len() > MAX_CONDITIONS {
+        return Err(AppError::InvalidRequest(format!(
+            "'conditions' must have 1-{} entries",
+            MAX_CONDITIONS
+        )));
+    }
+    for windows in [request.for_windows, request.resolve_windows] {
+        if !(1..=MAX_WINDOWS).contains(&windows) {
+            return Err(AppError::InvalidRequest(format!(
+                "'for_windows' and 'resolve_windows' must be between 1 and {}",
+                MAX_WINDOWS
+            )));
+        }
+    }
+    for condition in &request.conditions {
+        validate_condition(condition)?;
+    }
+
+    Ok(AlertRule {
+        id,
+        workspace_id,
+        name: request.name,
+        service_id: request.service_id,
+        window: request.window,
+        conditions: request.conditions,
+        combine: request.combine,
+        for_windows: request.for_windows,
+        resolve_windows: request.resolve_windows,
+        enabled: request.enabled,
+        created_at: now,
+        updated_at: now,
+    })
+}
+
+/// Validate one condition's thresholds
+fn validate_condition(condition: &AlertCondition) -> Result<()> {
+    if !condition.threshold.is_finite() {
+        return Err(AppError::InvalidRequest(
+            "'threshold' must be a finite number".into(),
+        ));
+    }
+    let Some(clear) = condition.clear_threshold else {
+        return Ok(());
+    };
+    // The clear threshold must sit on the non-breaching side
+    let valid = clear.is_finite()
+        && match condition.comparison {
+            AlertComparison::Gt => clear <= condition.threshold,
+            AlertComparison::Lt => clear >= condition.threshold,
+        };
+    if !valid {
+        return Err(AppError::InvalidRequest(
+            "'clear_threshold' must not be past 'threshold' in the breaching direction".into(),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,192 @@
+//! Workspace event annotation API endpoints
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::error::{AppError, Result};
+use crate::models::{Annotation, AnnotationKind, ApiKey, AuditAction};
+use crate::routes::audit;
+use crate::state::AppState;
+
+/// Request body for creating an annotation
+#[derive(Debug, Deserialize)]
+pub struct AnnotationRequest {
+    pub kind: AnnotationKind,
+    /// Short label shown on the dashboard (max 255 characters)
+    pub title: String,
+    /// Longer free-form details (max 4000 characters)
+    pub description: Option<String>,
+    /// When the event started (default: now)
+    pub starts_at: Option<DateTime<Utc>>,
+    /// When the event ended; omit for a point-in-time event
+    pub ends_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Query parameters for listing annotations
+#[derive(Debug, Deserialize)]
+pub struct AnnotationsQuery {
+    /// Start time (defaults to 24 hours ago)
+    pub from: Option<DateTime<Utc>>,
+    /// End time (defaults to now)
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Response listing annotations
+#[derive(Debug, Serialize)]
+pub struct AnnotationListResponse {
+    pub workspace_id: Uuid,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub count: usize,
+    pub annotations: Vec<Annotation>,
+}
+
+/// POST /api/v1/workspaces/:workspace_id/annotations
+///
+/// Records a maintenance window, incident, config change, or other event.
+///
+/// Request body:
+/// - kind: "maintenance", "incident", "config_change", or "other"
+/// - title: Short label (max 255 characters)
+/// - description: Optional details (max 4000 characters)
+/// - starts_at: Event start (default: now)
+/// - ends_at: Optional event end
+/// - tags: Optional tags (max 20)
+pub async fn create_annotation(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path(workspace_id): Path<Uuid>,
+    Json(request): Json<AnnotationRequest>,
+) -> Result<(StatusCode, Json<Annotation>)> {
+    let now = state.clock.now();
+    let annotation = Annotation {
+        id: Uuid::new_v4(),
+        kind: request.kind,
+        title: request.title,
+        description: request.description,
+        starts_at: request.starts_at.unwrap_or(now),
+        ends_at: request.ends_at,
+        tags: request.tags,
+        created_at: now,
+    };
+    validate_annotation(&annotation)?;
+
+    let stored = state
+        .db
+        .insert_annotation(workspace_id, &annotation)
+        .await?;
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::AnnotationCreate,
+        Some(stored.id.to_string()),
+        json!({ "kind": stored.kind, "title": stored.title }),
+    )
+    .await;
+    Ok((StatusCode::CREATED, Json(stored)))
+}
+
+/// GET /api/v1/workspaces/:workspace_id/annotations
+///
+/// Lists annotations overlapping the time range, oldest first.
+///
+/// Query parameters:
+/// - from: Start time (default: 24 hours ago)
+/// - to: End time (default: now)
+pub async fn list_annotations(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<AnnotationsQuery>,
+) -> Result<Json<AnnotationListResponse>> {
+    let now = state.clock.now();
+    let from = params.from.unwrap_or_else(|| now - Duration::hours(24));
+    let to = params.to.unwrap_or(now);
+    if from >= to {
+        return Err(AppError::InvalidRequest(
+            "'from' must be before 'to'".into(),
+        ));
+    }
+
+    let annotations = state.db.list_annotations(workspace_id, from, to).await?;
+
+    Ok(Json(AnnotationListResponse {
+        workspace_id,
+        from,
+        to,
+        count: annotations.len(),
+        annotations,
+    }))
+}
+
+/// DELETE /api/v1/workspaces/:workspace_id/annotations/:annotation_id
+///
+/// Removes an annotation.
+pub async fn delete_annotation(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path((workspace_id, annotation_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode> {
+    if state
+        .db
+        .delete_annotation(workspace_id, annotation_id)
+        .await?
+    {
+        audit::record(
+            &state,
+            &actor,
+            workspace_id,
+            AuditAction::AnnotationDelete,
+            Some(annotation_id.to_string()),
+            json!({}),
+        )
+        .await;
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound(format!(
+            "No annotation '{}'",
+            annotation_id
+        )))
+    }
+}
+
+/// Validate annotation limits
+fn validate_annotation(annotation: &Annotation) -> Result<()> {
+    if annotation.title.is_empty() || annotation.title.len() > 255 {
+        return Err(AppError::InvalidRequest(
+            "'title' must be 1-255 characters".into(),
+        ));
+    }
+    if annotation
+        .description
+        .as_ref()
+        .is_some_and(|d| d.len() > 4000)
+    {
+        return Err(AppError::InvalidRequest(
+            "'description' must be at most 4000 characters".into(),
+        ));
+    }
+    if annotation
+        .ends_at
+        .is_some_and(|ends_at| ends_at < annotation.starts_at)
+    {
+        return Err(AppError::InvalidRequest(
+            "'ends_at' must not be before 'starts_at'".into(),
+        ));
+    }
+    if annotation.tags.len() > 20 {
+        return Err(AppError::InvalidRequest(
+            "At most 20 'tags' are allowed".into(),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,158 @@
+//! API key management endpoints
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::{AppError, Result};
+use crate::models::{ApiKey, ApiScope, AuditAction};
+use crate::routes::audit;
+use crate::services::keys;
+use crate::state::AppState;
+
+/// Request body for creating an API key
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    /// Label for the key, e.g. where it is used (max 255 characters)
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    /// When the key stops working (default: never)
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Response for a newly created API key
+#[derive(Debug, Serialize)]
+pub struct CreateApiKeyResponse {
+    /// The secret to send as a Bearer token; it cannot be retrieved again
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+}
+
+/// Response listing API keys
+#[derive(Debug, Serialize)]
+pub struct ApiKeyListResponse {
+    pub workspace_id: Uuid,
+    pub count: usize,
+    pub api_keys: Vec<ApiKey>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/api-keys
+///
+/// Lists the workspace's API keys, newest first. Secrets are not included.
+pub async fn list_api_keys(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+) -> Result<Json<ApiKeyListResponse>> {
+    let api_keys = state.db.list_api_keys(workspace_id).await?;
+
+    Ok(Json(ApiKeyListResponse {
+        workspace_id,
+        count: api_keys.len(),
+        api_keys,
+    }))
+}
+
+/// POST /api/v1/workspaces/:workspace_id/api-keys
+///
+/// Creates an API key and returns its secret, which is only shown once.
+///
+/// Request body:
+/// - name: Label for the key (max 255 characters)
+/// - scopes: Any of "ingest", "read", "admin", "beacon" (at least one)
+/// - expires_at: Expiry time (optional, must be in the future)
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path(workspace_id): Path<Uuid>,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> Result<(StatusCode, Json<CreateApiKeyResponse>)> {
+    let now = state.clock.now();
+    if request.name.is_empty() || request.name.len() > 255 {
+        return Err(AppError::InvalidRequest(
+            "'name' must be 1-255 characters".into(),
+        ));
+    }
+    if request.scopes.is_empty() {
+        return Err(AppError::InvalidRequest(
+            "'scopes' must include at least one scope".into(),
+        ));
+    }
+    if matches!(request.expires_at, Some(expires_at) if expires_at <= now) {
+        return Err(AppError::InvalidRequest(
+            "'expires_at' must be in the future".into(),
+        ));
+    }
+
+    let mut scopes = request.scopes;
+    scopes.sort_by_key(|scope| scope.as_str());
+    scopes.dedup();
+
+    let api_key = ApiKey {
+        id: Uuid::new_v4(),
+        workspace_id,
+        name: request.name,
+        scopes,
+        expires_at: request.expires_at,
+        created_at: now,
+    };
+    let key = keys::generate_secret();
+    state.db.create_api_key(&api_key, &key).await?;
+
+    info!(
+        workspace_id = %workspace_id,
+        key_id = %api_key.id,
+        "Created API key"
+    );
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::ApiKeyCreate,
+        Some(api_key.id.to_string()),
+        json!({
+            "name": api_key.name,
+            "scopes": api_key.scopes,
+            "expires_at": api_key.expires_at,
+        }),
+    )
+    .await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateApiKeyResponse { key, api_key }),
+    ))
+}
+
+/// DELETE /api/v1/workspaces/:workspace_id/api-keys/:key_id
+///
+/// Revokes an API key immediately.
+pub async fn delete_api_key(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path((workspace_id, key_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode> {
+    if state.db.delete_api_key(workspace_id, key_id).await? {
+        state.key_cache.invalidate(key_id);
+        info!(workspace_id = %workspace_id, key_id = %key_id, "Revoked API key");
+        audit::record(
+            &state,
+            &actor,
+            workspace_id,
+            AuditAction::ApiKeyDelete,
+            Some(key_id.to_string()),
+            json!({}),
+        )
+        .await;
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound(format!("No API key '{}'", key_id)))
+    }
+}
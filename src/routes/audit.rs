@@ -0,0 +1,114 @@
+//! Audit log of administrative changes
+//!
+//! Admin handlers call [`record`] after a change succeeds; the log is read
+//! back through the admin-scoped audit endpoint.
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::error::{AppError, Result};
+use crate::models::{ApiKey, AuditAction, AuditEntry};
+use crate::state::AppState;
+
+/// Record a change made by `key` in `workspace_id`
+///
+/// The change has already happened, so a failed write is logged with the
+/// full entry rather than failing the request.
+pub(crate) async fn record(
+    state: &AppState,
+    key: &ApiKey,
+    workspace_id: Uuid,
+    action: AuditAction,
+    target: Option<String>,
+    payload: Value,
+) {
+    let entry = AuditEntry {
+        id: Uuid::new_v4(),
+        workspace_id,
+        action,
+        actor_key_id: key.id,
+        actor_name: key.name.clone(),
+        target,
+        payload,
+        created_at: state.clock.now(),
+    };
+    if let Err(e) = state.db.insert_audit_entry(&entry).await {
+        error!(
+            error = %e,
+            workspace_id = %entry.workspace_id,
+            action = entry.action.as_str(),
+            actor_key_id = %entry.actor_key_id,
+            target = ?entry.target,
+            payload = %entry.payload,
+            "Failed to write audit log entry"
+        );
+    }
+}
+
+/// Query parameters for the audit log endpoint
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    /// Start time (defaults to 30 days ago)
+    pub from: Option<DateTime<Utc>>,
+    /// End time (defaults to now)
+    pub to: Option<DateTime<Utc>>,
+    /// Only entries for this action
+    pub action: Option<AuditAction>,
+    /// Maximum number of entries to return (default: 100, max: 1000)
+    pub limit: Option<i64>,
+}
+
+/// Response listing audit log entries
+#[derive(Debug, Serialize)]
+pub struct AuditLogResponse {
+    pub workspace_id: Uuid,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub count: usize,
+    pub entries: Vec<AuditEntry>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/audit
+///
+/// Lists administrative changes in the workspace, newest first.
+///
+/// Query parameters:
+/// - from: Start time (default: 30 days ago)
+/// - to: End time (default: now)
+/// - action: Only this action, e.g. "api_key.create" (optional)
+/// - limit: Maximum results (default: 100, max: 1000)
+pub async fn list_audit_log(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<AuditQuery>,
+) -> Result<Json<AuditLogResponse>> {
+    let now = state.clock.now();
+    let from = params.from.unwrap_or_else(|| now - Duration::days(30));
+    let to = params.to.unwrap_or(now);
+    if from >= to {
+        return Err(AppError::InvalidRequest(
+            "'from' must be before 'to'".into(),
+        ));
+    }
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+
+    let entries = state
+        .db
+        .list_audit_entries(workspace_id, from, to, params.action, limit)
+        .await?;
+
+    Ok(Json(AuditLogResponse {
+        workspace_id,
+        from,
+        to,
+        count: entries.len(),
+        entries,
+    }))
+}
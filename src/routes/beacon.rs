@@ -0,0 +1,149 @@
+//! Browser beacon endpoint
+//!
+//! Frontends report the timings of client-issued GraphQL or SQL-over-HTTP
+//! requests one event at a time, typically with `navigator.sendBeacon`.
+//! Beacons can't set headers, so the key comes in the `token` query
+//! parameter; it must have the `beacon` scope, which grants nothing else and
+//! is safe to ship in public JavaScript. Events join the shared ingest
+//! buffer and are written in the aggregation task's batches.
+
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::auth::{authorize, extract_bearer_token};
+use crate::error::{AppError, Result};
+use crate::models::{ApiScope, QueryMetric, QueryStatus};
+use crate::routes::ingest;
+use crate::services::schema;
+use crate::state::AppState;
+
+/// Largest beacon body accepted
+pub const MAX_BEACON_BYTES: usize = 16 * 1024;
+
+/// Tag added to every metric reported by a beacon
+const BEACON_TAG: &str = "source:beacon";
+
+/// Query parameters for the beacon endpoint
+#[derive(Debug, Deserialize)]
+pub struct BeaconQuery {
+    /// API key with the `beacon` scope (or send it as a Bearer token)
+    pub token: Option<String>,
+}
+
+/// A single timing reported by a browser
+#[derive(Debug, Deserialize)]
+pub struct BeaconEvent {
+    pub service_id: Uuid,
+    pub service_name: Option<String>,
+    /// GraphQL document, SQL text, or operation name
+    pub operation: String,
+    /// Client-measured duration, e.g. from `performance.now()`
+    pub duration_ms: f64,
+    /// Defaults to `success`
+    pub status: Option<QueryStatus>,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl BeaconEvent {
+    /// Convert to metric `id` in `workspace_id` that completed at `received_at`
+    ///
+    /// Browser clocks can't be trusted, so the server's receive time stands
+    /// in for the completion time.
+    fn into_metric(
+        self,
+        id: Uuid,
+        workspace_id: Uuid,
+        received_at: chrono::DateTime<Utc>,
+    ) -> QueryMetric {
+        let duration_ms = self.duration_ms.round() as u64;
+        let mut tags = self.tags;
+        tags.push(BEACON_TAG.to_string());
+        QueryMetric {
+            id,
+            workspace_id,
+            service_id: self.service_id,
+            service_name: self.service_name,
+            query_text: self.operation,
+            status: self.status.unwrap_or(QueryStatus::Success),
+            duration_ms,
+            rows_affected: None,
+            error_message: self.error,
+            started_at: received_at
+                .checked_sub_signed(Duration::milliseconds(
+                    i64::try_from(duration_ms).unwrap_or(i64::MAX),
+                ))
+                .unwrap_or(received_at),
+            completed_at: received_at,
+            tags,
+            labels: Default::default(),
+            fingerprint: None,
+            query_kind: None,
+        }
+    }
+}
+
+/// POST /api/v1/beacon?token=...
+///
+/// Accepts one beacon event. The body is parsed as JSON whatever its content
+/// type, since `sendBeacon` sends strings as `text/plain`.
+///
+/// Returns 204 No Content (the browser discards the response), or 503 when
+/// the ingest buffer is full.
+pub async fn collect_beacon(
+    State(state): State<AppState>,
+    Query(params): Query<BeaconQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode> {
+    let token = extract_bearer_token(&headers).or(params.token.as_deref());
+    let key = authorize(&state, token, "", ApiScope::Beacon).await?;
+
+    let payload: Value = serde_json::from_slice(&body)
+        .map_err(|e| AppError::InvalidRequest(format!("Invalid beacon JSON: {}", e)))?;
+    schema::validate(schema::beacon_schema(), &payload)?;
+    let event: BeaconEvent = serde_json::from_value(payload)?;
+
+    let metric = event.into_metric(state.ids.generate(), key.workspace_id, state.clock.now());
+    let (_, Json(response)) = ingest::ingest(&state, &key, vec![metric]).await?;
+    if response.dropped > 0 {
+        return Err(AppError::ServiceUnavailable("Ingest buffer is full".into()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_beacon_becomes_tagged_metric() {
+        let event: BeaconEvent = serde_json::from_value(json!({
+            "service_id": "550e8400-e29b-41d4-a716-446655440002",
+            "operation": "query GetUser { user { id } }",
+            "duration_ms": 41.6,
+            "tags": ["page:/profile"]
+        }))
+        .unwrap();
+        let workspace_id = Uuid::new_v4();
+        let now = Utc::now();
+
+        let metric = event.into_metric(Uuid::now_v7(), workspace_id, now);
+        assert_eq!(metric.workspace_id, workspace_id);
+        assert_eq!(metric.status, QueryStatus::Success);
+        assert_eq!(metric.duration_ms, 42);
+        assert_eq!(metric.completed_at, now);
+        assert_eq!(metric.started_at, now - Duration::milliseconds(42));
+        assert_eq!(metric.tags, vec!["page:/profile", BEACON_TAG]);
+    }
+}
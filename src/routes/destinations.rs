@@ -0,0 +1,285 @@
+//! Alert destination API endpoints
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::{AppError, Result};
+use crate::models::{AlertDelivery, AlertDestination, AlertDestinationKind, ApiKey, AuditAction};
+use crate::routes::audit;
+use crate::services::integrations::PAGERDUTY_EVENTS_URL;
+use crate::services::notify::{self, DeliveryResult};
+use crate::services::severity::SeverityLevel;
+use crate::state::AppState;
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_min_severity() -> SeverityLevel {
+    SeverityLevel::High
+}
+
+/// Request body for creating an alert destination
+#[derive(Debug, Deserialize)]
+pub struct AlertDestinationRequest {
+    /// Destination name (max 255 characters)
+    pub name: String,
+    #[serde(default)]
+    pub kind: AlertDestinationKind,
+    /// http(s) URL alerts are POSTed to (PagerDuty: defaults to the Events
+    /// API v2 endpoint)
+    pub url: Option<String>,
+    /// PagerDuty integration key (PagerDuty only, required)
+    pub routing_key: Option<String>,
+    /// Least severe anomaly sent to the destination (default: high)
+    #[serde(default = "default_min_severity")]
+    pub min_severity: SeverityLevel,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+/// Response listing alert destinations
+#[derive(Debug, Serialize)]
+pub struct AlertDestinationListResponse {
+    pub workspace_id: Uuid,
+    pub count: usize,
+    pub destinations: Vec<AlertDestination>,
+}
+
+/// Response describing a test delivery
+#[derive(Debug, Serialize)]
+pub struct TestDeliveryResponse {
+    pub destination_id: Uuid,
+    #[serde(flatten)]
+    pub result: DeliveryResult,
+}
+
+/// Query parameters for the deliveries endpoint
+#[derive(Debug, Deserialize)]
+pub struct DeliveriesQuery {
+    /// Only attempts to this destination
+    pub destination_id: Option<Uuid>,
+    /// Only attempts that weren't delivered
+    #[serde(default)]
+    pub failed: bool,
+    /// Maximum number of attempts to return (default: 100, max: 1000)
+    pub limit: Option<i64>,
+}
+
+/// Response listing delivery attempts
+#[derive(Debug, Serialize)]
+pub struct DeliveryListResponse {
+    pub workspace_id: Uuid,
+    pub count: usize,
+    pub deliveries: Vec<AlertDelivery>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/alert-destinations
+///
+/// Lists alert destinations, oldest first. Admin-only, since destination
+/// URLs often embed credentials.
+pub async fn list_alert_destinations(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+) -> Result<Json<AlertDestinationListResponse>> {
+    let destinations = state.db.list_alert_destinations(workspace_id).await?;
+
+    Ok(Json(AlertDestinationListResponse {
+        workspace_id,
+        count: destinations.len(),
+        destinations,
+    }))
+}
+
+/// POST /api/v1/workspaces/:workspace_id/alert-destinations
+///
+/// Creates an alert destination.
+///
+/// Request body:
+/// - name: Destination name (max 255 characters)
+/// - kind: "webhook" (default), "slack", or "pagerduty"
+/// - url: http(s) URL alerts are POSTed to; a Slack incoming webhook URL for
+///   Slack (optional for PagerDuty)
+/// - routing_key: PagerDuty integration key (required for PagerDuty)
+/// - min_severity: Least severe anomaly sent: low, medium, high (default),
+///   or critical. Other events are always sent, except informational new
+///   query shapes, sent only at low.
+/// - enabled: Whether alerts are sent (default: true)
+///
+/// Webhook destinations are given a secret to sign requests with.
+pub async fn create_alert_destination(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path(workspace_id): Path<Uuid>,
+    Json(request): Json<AlertDestinationRequest>,
+) -> Result<(StatusCode, Json<AlertDestination>)> {
+    if request.name.is_empty() || request.name.len() > 255 {
+        return Err(AppError::InvalidRequest(
+            "'name' must be 1-255 characters".into(),
+        ));
+    }
+    let url = match (request.kind, request.url) {
+        (_, Some(url)) => url,
+        (AlertDestinationKind::Pagerduty, None) => PAGERDUTY_EVENTS_URL.to_string(),
+        (_, None) => return Err(AppError::InvalidRequest("'url' is required".into())),
+    };
+    let scheme_ok = reqwest::Url::parse(&url)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.has_host());
+    if !scheme_ok {
+        return Err(AppError::InvalidRequest(
+            "'url' must be an absolute http or https URL".into(),
+        ));
+    }
+    match (request.kind, &request.routing_key) {
+        (AlertDestinationKind::Pagerduty, None) => {
+            return Err(AppError::InvalidRequest(
+                "'routing_key' is required for pagerduty destinations".into(),
+            ))
+        }
+        (AlertDestinationKind::Pagerduty, Some(key)) if key.is_empty() || key.len() > 255 => {
+            return Err(AppError::InvalidRequest(
+                "'routing_key' must be 1-255 characters".into(),
+            ))
+        }
+        (AlertDestinationKind::Webhook | AlertDestinationKind::Slack, Some(_)) => {
+            return Err(AppError::InvalidRequest(
+                "'routing_key' only applies to pagerduty destinations".into(),
+            ))
+        }
+        _ => {}
+    }
+
+    let destination = AlertDestination {
+        id: Uuid::new_v4(),
+        workspace_id,
+        name: request.name,
+        kind: request.kind,
+        url,
+        signing_secret: (request.kind == AlertDestinationKind::Webhook)
+            .then(notify::generate_signing_secret),
+        routing_key: request.routing_key,
+        min_severity: request.min_severity,
+        enabled: request.enabled,
+        created_at: state.clock.now(),
+    };
+    let stored = state.db.create_alert_destination(&destination).await?;
+    // The URL and routing key are left out of the audit log, as they're
+    // credentials
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::AlertDestinationCreate,
+        Some(stored.id.to_string()),
+        json!({
+            "name": stored.name,
+            "kind": stored.kind,
+            "min_severity": stored.min_severity,
+            "enabled": stored.enabled,
+        }),
+    )
+    .await;
+    Ok((StatusCode::CREATED, Json(stored)))
+}
+
+/// DELETE /api/v1/workspaces/:workspace_id/alert-destinations/:destination_id
+///
+/// Deletes an alert destination.
+pub async fn delete_alert_destination(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path((workspace_id, destination_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode> {
+    if state
+        .db
+        .delete_alert_destination(workspace_id, destination_id)
+        .await?
+    {
+        audit::record(
+            &state,
+            &actor,
+            workspace_id,
+            AuditAction::AlertDestinationDelete,
+            Some(destination_id.to_string()),
+            json!({}),
+        )
+        .await;
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound(format!(
+            "No alert destination '{}'",
+            destination_id
+        )))
+    }
+}
+
+/// POST /api/v1/workspaces/:workspace_id/alert-destinations/:destination_id/test
+///
+/// Sends a synthetic anomaly, marked `"test": true`, to the destination and
+/// reports what it answered. Disabled destinations can be tested too. A
+/// destination that fails or can't be reached is still a 200 response, with
+/// `delivered: false`.
+pub async fn test_alert_destination(
+    State(state): State<AppState>,
+    Path((workspace_id, destination_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<TestDeliveryResponse>> {
+    let destination = state
+        .db
+        .get_alert_destination(workspace_id, destination_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No alert destination '{}'", destination_id)))?;
+
+    let payload = notify::anomaly_payload(&notify::test_anomaly(workspace_id), true);
+    let result = state
+        .notifier
+        .deliver(&destination, notify::ANOMALY_EVENT, &payload, true)
+        .await;
+    info!(
+        workspace_id = %workspace_id,
+        destination_id = %destination_id,
+        delivered = result.delivered,
+        status = ?result.status,
+        latency_ms = result.latency_ms,
+        "Sent test alert"
+    );
+
+    Ok(Json(TestDeliveryResponse {
+        destination_id,
+        result,
+    }))
+}
+
+/// GET /api/v1/workspaces/:workspace_id/alert-deliveries
+///
+/// Lists attempts to send alerts, newest first, with each destination's
+/// status code, latency, and the start of its response. Attempts are kept
+/// for 30 days.
+///
+/// Query parameters:
+/// - destination_id: Only attempts to this destination (optional)
+/// - failed: Only attempts that weren't delivered (default: false)
+/// - limit: Maximum results (default: 100, max: 1000)
+pub async fn list_alert_deliveries(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<DeliveriesQuery>,
+) -> Result<Json<DeliveryListResponse>> {
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+    let deliveries = state
+        .db
+        .list_alert_deliveries(workspace_id, params.destination_id, params.failed, limit)
+        .await?;
+
+    Ok(Json(DeliveryListResponse {
+        workspace_id,
+        count: deliveries.len(),
+        deliveries,
+    }))
+}
@@ -0,0 +1,114 @@
+//! Metric erasure API endpoint
+
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::db::{ErasureCounts, MetricErasure};
+use crate::error::{AppError, Result};
+use crate::models::{ApiKey, AuditAction};
+use crate::routes::audit;
+use crate::state::AppState;
+
+/// Query parameters selecting the metrics to erase
+#[derive(Debug, Deserialize)]
+pub struct ErasureQuery {
+    /// Stored at or after
+    pub from: Option<DateTime<Utc>>,
+    /// Stored before
+    pub to: Option<DateTime<Utc>>,
+    pub service_id: Option<Uuid>,
+    /// Case-insensitive substring of the query text, e.g. an email address
+    pub query_contains: Option<String>,
+}
+
+/// Response counting erased rows
+#[derive(Debug, Serialize)]
+pub struct ErasureResponse {
+    pub workspace_id: Uuid,
+    #[serde(flatten)]
+    pub erased: ErasureCounts,
+}
+
+/// DELETE /api/v1/workspaces/:workspace_id/metrics
+///
+/// Erases matching metrics, the anomalies raised for them, the embeddings
+/// and catalog entries of their query text, and matching dead letters, for
+/// right-to-erasure requests. At least
+/// one filter is required; every given filter must match. Metrics still in
+/// the ingest buffer are written afterwards, so repeat the request after a
+/// flush interval if matching traffic may still be arriving.
+///
+/// Query parameters:
+/// - from: Stored at or after
+/// - to: Stored before
+/// - service_id: Only this service's metrics
+/// - query_contains: Case-insensitive substring of the query text
+pub async fn erase_metrics(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<ErasureQuery>,
+) -> Result<Json<ErasureResponse>> {
+    let filter = MetricErasure {
+        from: params.from,
+        to: params.to,
+        service_id: params.service_id,
+        query_contains: params.query_contains.filter(|text| !text.is_empty()),
+    };
+    if filter.from.is_none()
+        && filter.to.is_none()
+        && filter.service_id.is_none()
+        && filter.query_contains.is_none()
+    {
+        return Err(AppError::InvalidRequest(
+            "At least one of 'from', 'to', 'service_id', or 'query_contains' is required".into(),
+        ));
+    }
+    if let (Some(from), Some(to)) = (filter.from, filter.to) {
+        if from >= to {
+            return Err(AppError::InvalidRequest(
+                "'from' must be before 'to'".into(),
+            ));
+        }
+    }
+
+    let erased = state.db.erase_metrics(workspace_id, &filter).await?;
+    info!(
+        workspace_id = %workspace_id,
+        metrics = erased.metrics,
+        embeddings = erased.embeddings,
+        anomalies = erased.anomalies,
+        catalog_entries = erased.catalog_entries,
+        dead_letters = erased.dead_letters,
+        "Erased metrics"
+    );
+    // The text filter is left out of the audit log, as it is usually the
+    // personal data being erased
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::MetricsErase,
+        None,
+        json!({
+            "from": filter.from,
+            "to": filter.to,
+            "service_id": filter.service_id,
+            "query_filter": filter.query_contains.is_some(),
+            "erased": erased,
+        }),
+    )
+    .await;
+
+    Ok(Json(ErasureResponse {
+        workspace_id,
+        erased,
+    }))
+}
@@ -0,0 +1,91 @@
+//! Error analytics API endpoint
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{ErrorGroup, ErrorGroupsQuery};
+use crate::error::{AppError, Result};
+use crate::models::QueryKind;
+use crate::state::AppState;
+
+/// Query parameters for the errors endpoint
+#[derive(Debug, Deserialize)]
+pub struct ErrorsQuery {
+    /// Start time (defaults to 24 hours ago)
+    pub from: Option<DateTime<Utc>>,
+    /// End time (defaults to now)
+    pub to: Option<DateTime<Utc>>,
+    /// Only this service's failures
+    pub service_id: Option<Uuid>,
+    /// Only failures of this kind of statement
+    pub query_kind: Option<QueryKind>,
+    /// Maximum number of error groups to return (default: 50, max: 500)
+    pub limit: Option<i64>,
+}
+
+/// Response listing error groups
+#[derive(Debug, Serialize)]
+pub struct ErrorsResponse {
+    pub workspace_id: Uuid,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub count: usize,
+    pub errors: Vec<ErrorGroup>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/errors
+///
+/// Groups failed and timed-out executions by the fingerprint of their
+/// normalized error message, most frequent first. Each group has its count,
+/// the count over the equally long range just before (to spot trending error
+/// classes), first and last seen, and the query fingerprints failing that way.
+///
+/// Query parameters:
+/// - from: Start time (default: 24 hours ago)
+/// - to: End time (default: now)
+/// - service_id: Only this service's failures (optional)
+/// - query_kind: Optional kind of statement ("select", "insert", "update",
+///   "delete", "ddl", "other"); only failures of that kind are grouped
+/// - limit: Maximum results (default: 50, max: 500)
+pub async fn list_errors(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<ErrorsQuery>,
+) -> Result<Json<ErrorsResponse>> {
+    let now = state.clock.now();
+    let from = params.from.unwrap_or_else(|| now - Duration::hours(24));
+    let to = params.to.unwrap_or(now);
+    if from >= to {
+        return Err(AppError::InvalidRequest(
+            "'from' must be before 'to'".into(),
+        ));
+    }
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+
+    let errors = state
+        .db
+        .get_error_groups(
+            workspace_id,
+            &ErrorGroupsQuery {
+                from,
+                to,
+                service_id: params.service_id,
+                query_kind: params.query_kind,
+                limit,
+            },
+        )
+        .await?;
+
+    Ok(Json(ErrorsResponse {
+        workspace_id,
+        from,
+        to,
+        count: errors.len(),
+        errors,
+    }))
+}
@@ -47,7 +47,7 @@ pub async fn health() -> Json<HealthResponse> {
 /// Readiness check - verifies all dependencies are available
 pub async fn ready(State(state): State<AppState>) -> (StatusCode, Json<ReadinessResponse>) {
     // Check database connection
-    let db_check = match sqlx::query("SELECT 1").fetch_one(state.db.pool()).await {
+    let db_check = match state.db.ping().await {
         Ok(_) => CheckStatus {
             healthy: true,
             message: "Connected".to_string(),
@@ -64,12 +64,27 @@ pub async fn ready(State(state): State<AppState>) -> (StatusCode, Json<Readiness
         message: format!("Buffer length: {}", state.metrics_buffer.len()),
     };
 
-    // Check embedding service
-    let embedding_check = match &state.embedding_service {
-        Some(_) => CheckStatus {
-            healthy: true,
-            message: "Loaded".to_string(),
-        },
+    // Check embedding providers; one healthy provider is enough
+    let embedding_check = match &state.embedder {
+        Some(embedder) => {
+            let providers = embedder.status();
+            let message = providers
+                .iter()
+                .map(|p| {
+                    format!(
+                        "{} ({}): {}",
+                        p.provider.as_str(),
+                        if p.primary { "primary" } else { "fallback" },
+                        if p.healthy { "healthy" } else { "unavailable" }
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            CheckStatus {
+                healthy: providers.iter().any(|p| p.healthy),
+                message,
+            }
+        }
         None => CheckStatus {
             healthy: true, // Not having embeddings is OK
             message: "Not configured".to_string(),
@@ -1,54 +1,155 @@
 //! HTTP ingestion endpoint for high-throughput metric collection
 
-use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
-    Json,
-};
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use serde_json::Value;
+use std::sync::Arc;
 use tracing::{info, warn};
+use uuid::Uuid;
 
 use crate::error::{AppError, Result};
-use crate::models::{IngestRequest, IngestResponse};
+use crate::ingest_stats::IngestCounts;
+use crate::models::{ApiKey, IngestRequest, IngestRequestV2, IngestResponse, QueryMetric};
+use crate::services::fingerprint::DialectConfig;
+use crate::services::redaction;
+use crate::services::schema;
+use crate::services::usage::{self, QuotaStatus};
 use crate::state::AppState;
-
-/// Extract Bearer token from Authorization header
-fn extract_bearer_token(headers: &HeaderMap) -> Option<&str> {
-    headers
-        .get("Authorization")
-        .and_then(|v| v.to_str().ok())
-        .and_then(|v| v.strip_prefix("Bearer "))
-}
+use crate::versioning::ApiVersion;
 
 /// POST /api/v1/metrics/ingest
 ///
-/// Ingests a batch of query metrics into the buffer.
-/// Requires Bearer token authentication.
+/// Ingests a batch of query metrics into the buffer, fingerprinting and
+/// classifying each query with its service's SQL dialect.
+/// Requires an API key with the `ingest` scope. Payloads are validated
+/// against the v1 ingest schema.
 ///
 /// Returns 202 Accepted with count of ingested metrics.
 pub async fn ingest_metrics(
     State(state): State<AppState>,
-    headers: HeaderMap,
-    Json(payload): Json<IngestRequest>,
+    Extension(key): Extension<ApiKey>,
+    Json(payload): Json<Value>,
+) -> Result<(StatusCode, Json<IngestResponse>)> {
+    schema::validate(schema::ingest_schema(ApiVersion::V1), &payload)?;
+    let payload: IngestRequest = serde_json::from_value(payload)?;
+
+    let metrics = payload
+        .metrics
+        .into_iter()
+        .map(|mut metric| {
+            // v1 agents never fingerprint; ignore anything sent in the field
+            metric.fingerprint = None;
+            metric
+        })
+        .collect();
+
+    ingest(&state, &key, metrics).await
+}
+
+/// POST /api/v2/metrics/ingest
+///
+/// Ingests a batch of metrics in the compact v2 format. Metrics carrying an
+/// agent-computed fingerprint (`fp`) keep it; the rest are fingerprinted
+/// with their service's SQL dialect.
+/// Requires an API key with the `ingest` scope. Payloads are validated
+/// against the v2 ingest schema.
+///
+/// Returns 202 Accepted with count of ingested metrics.
+pub async fn ingest_metrics_v2(
+    State(state): State<AppState>,
+    Extension(key): Extension<ApiKey>,
+    Json(payload): Json<Value>,
+) -> Result<(StatusCode, Json<IngestResponse>)> {
+    schema::validate(schema::ingest_schema(ApiVersion::V2), &payload)?;
+    let payload: IngestRequestV2 = serde_json::from_value(payload)?;
+
+    let metrics = payload.metrics.into_iter().map(QueryMetric::from).collect();
+    ingest(&state, &key, metrics).await
+}
+
+/// Buffer a batch of metrics, fingerprinting those without a fingerprint and
+/// classifying every one
+///
+/// When the workspace redacts literals, query text is redacted first, so
+/// fingerprints are computed from what is stored. Any kind sent by the
+/// client is replaced. Metrics whose ID was
+/// ingested recently are skipped as resends. Fails with `QuotaExceeded`
+/// once the workspace has used its monthly quota.
+pub(crate) async fn ingest(
+    state: &AppState,
+    key: &ApiKey,
+    metrics: Vec<QueryMetric>,
 ) -> Result<(StatusCode, Json<IngestResponse>)> {
-    // Extract and verify API key
-    let api_key = extract_bearer_token(&headers)
-        .ok_or_else(|| AppError::Unauthorized("Missing Authorization header".into()))?;
+    if metrics.iter().any(|m| m.workspace_id != key.workspace_id) {
+        return Err(AppError::Forbidden(
+            "API key does not belong to the metrics' workspace".into(),
+        ));
+    }
 
-    let _workspace = state.db.verify_api_key(api_key).await?;
+    check_quota(state, key.workspace_id).await?;
+    let redact = redacts_literals(state, key.workspace_id).await;
 
-    let total = payload.metrics.len();
+    let total = metrics.len();
+    let mut accepted = Vec::with_capacity(total);
     let mut ingested = 0;
     let mut dropped = 0;
+    let mut deduplicated = 0;
+
+    let mut fresh = Vec::with_capacity(total);
+    for metric in metrics {
+        if state.recent_ids.insert(metric.workspace_id, metric.id) {
+            fresh.push(metric);
+        } else {
+            deduplicated += 1;
+        }
+    }
 
-    for metric in payload.metrics {
+    // Parsing every query is CPU-bound, so keep it off the async workers
+    let ids: Vec<Uuid> = fresh.iter().map(|m| m.id).collect();
+    let dialects = Arc::clone(&state.dialects);
+    let fresh = match tokio::task::spawn_blocking(move || prepare(fresh, &dialects, redact)).await {
+        Ok(fresh) => fresh,
+        Err(e) => {
+            // Nothing was buffered, so let the agent resend the batch
+            for id in ids {
+                state.recent_ids.remove(key.workspace_id, id);
+            }
+            return Err(AppError::InternalError(format!("Ingestion failed: {}", e)));
+        }
+    };
+
+    for metric in fresh {
+        let published = metric.clone();
         match state.metrics_buffer.try_push(metric) {
-            Ok(()) => ingested += 1,
-            Err(_dropped_metric) => {
+            Ok(()) => {
+                ingested += 1;
+                accepted.push(published);
+            }
+            Err(dropped_metric) => {
+                // Let the agent resend it
+                state
+                    .recent_ids
+                    .remove(dropped_metric.workspace_id, dropped_metric.id);
                 dropped += 1;
             }
         }
     }
 
+    state
+        .quota_cache
+        .add_used(key.workspace_id, ingested as i64);
+    state.metrics.inc_ingested(ingested as u64);
+    state.metrics.inc_dropped(dropped as u64);
+    publish(state, accepted);
+    state.ingest_stats.record(
+        key.workspace_id,
+        IngestCounts {
+            accepted: ingested as i64,
+            dropped: dropped as i64,
+            deduplicated: deduplicated as i64,
+        },
+        state.clock.now(),
+    );
+
     if dropped > 0 {
         warn!(
             total = total,
@@ -60,12 +161,128 @@ pub async fn ingest_metrics(
         info!(
             total = total,
             ingested = ingested,
+            deduplicated = deduplicated,
             "Metrics ingested successfully"
         );
     }
 
     Ok((
         StatusCode::ACCEPTED,
-        Json(IngestResponse { ingested, dropped }),
+        Json(IngestResponse {
+            ingested,
+            dropped,
+            deduplicated,
+        }),
     ))
 }
+
+/// Redact, fingerprint, and classify a batch's queries with their services'
+/// dialects
+fn prepare(
+    mut metrics: Vec<QueryMetric>,
+    dialects: &DialectConfig,
+    redact: bool,
+) -> Vec<QueryMetric> {
+    for metric in &mut metrics {
+        if redact {
+            metric.query_text = redaction::redact_literals(
+                &metric.query_text,
+                dialects.dialect_for(metric.service_id),
+            );
+        }
+        if metric.fingerprint.is_none() {
+            metric.fingerprint = Some(dialects.fingerprint(metric.service_id, &metric.query_text));
+        }
+        metric.query_kind = Some(dialects.classify(metric.service_id, &metric.query_text));
+    }
+    metrics
+}
+
+/// Send buffered metrics to live subscribers and the long-poll event log
+///
+/// This happens here rather than by taking them from the buffer, so the flush
+/// task stays its only consumer and every metric is both stored and streamed.
+fn publish(state: &AppState, metrics: Vec<QueryMetric>) {
+    if metrics.is_empty() {
+        return;
+    }
+    state.second_rollups.lock().add(&metrics);
+    state.metrics.inc_broadcast(metrics.len() as u64);
+    for metric in metrics {
+        let workspace_id = metric.workspace_id;
+        state.events.push(workspace_id, metric.clone());
+        // Ignore send errors (no receivers connected)
+        let _ = state.broadcast_tx.send((workspace_id, metric));
+    }
+}
+
+/// Reject ingestion for a workspace that has used its monthly quota
+///
+/// A failed lookup lets the batch through rather than blocking ingestion.
+async fn check_quota(state: &AppState, workspace_id: Uuid) -> Result<()> {
+    let status = match state.quota_cache.get(workspace_id) {
+        Some(status) => status,
+        None => match load_quota(state, workspace_id).await {
+            Ok(status) => {
+                state.quota_cache.insert(workspace_id, status);
+                status
+            }
+            Err(e) => {
+                warn!(error = %e, workspace_id = %workspace_id, "Failed to load workspace quota");
+                return Ok(());
+            }
+        },
+    };
+
+    match status.quota {
+        Some(quota) if status.exceeded() => Err(AppError::QuotaExceeded(format!(
+            "Workspace has stored {} of its {} metrics this month",
+            status.used, quota
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Whether a workspace has literal redaction turned on
+///
+/// A failed lookup redacts the batch rather than risk storing literals the
+/// workspace asked not to keep.
+async fn redacts_literals(state: &AppState, workspace_id: Uuid) -> bool {
+    if let Some(enabled) = state.redaction_cache.get(workspace_id) {
+        return enabled;
+    }
+    match state.db.get_literal_redaction(workspace_id).await {
+        Ok(enabled) => {
+            let enabled = enabled.unwrap_or(false);
+            state.redaction_cache.insert(workspace_id, enabled);
+            enabled
+        }
+        Err(e) => {
+            warn!(error = %e, workspace_id = %workspace_id, "Failed to load workspace redaction setting");
+            true
+        }
+    }
+}
+
+/// Look up a workspace's quota and month-to-date usage
+async fn load_quota(state: &AppState, workspace_id: Uuid) -> Result<QuotaStatus> {
+    let Some(quota) = state.db.get_monthly_quota(workspace_id).await? else {
+        return Ok(QuotaStatus {
+            quota: None,
+            used: 0,
+        });
+    };
+    let today = state.clock.now().date_naive();
+    let used = state
+        .db
+        .get_usage(workspace_id, usage::month_start(today), today)
+        .await?
+        .iter()
+        .map(|d| d.metric_count)
+        .sum();
+
+    Ok(QuotaStatus {
+        quota: Some(quota),
+        used,
+    })
+}
@@ -1,10 +1,116 @@
 //! Prometheus metrics endpoint
 
-use axum::response::IntoResponse;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::fmt::Write;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Bucket bounds of `queryvault_flush_batch_size`, up to `MAX_FLUSH_BATCH`
+const FLUSH_BATCH_BUCKETS: &[f64] = &[1.0, 10.0, 100.0, 1_000.0, 5_000.0, 10_000.0];
+
+/// Bucket bounds of latency histograms, in seconds
+const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// A Prometheus histogram with fixed bucket bounds
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Observations per bucket, not cumulative; the last is `+Inf`
+    buckets: Vec<AtomicU64>,
+    /// Bits of the `f64` sum of observations
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0f64.to_bits()),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        let _ = self
+            .sum
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + value).to_bits())
+            });
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The histogram's series in Prometheus text format
+    fn render(&self, name: &str, help: &str) -> String {
+        let mut output = histogram_header(name, help);
+        self.write_series(&mut output, name, "");
+        output
+    }
+
+    /// Append the histogram's series to `output`, each labeled with `labels`
+    /// (`key="value"` pairs, comma-separated) besides `le`
+    fn write_series(&self, output: &mut String, name: &str, labels: &str) {
+        let sep = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            match self.bounds.get(i) {
+                Some(bound) => writeln!(
+                    output,
+                    "{name}_bucket{{{labels}{sep}le=\"{bound}\"}} {cumulative}"
+                ),
+                None => writeln!(
+                    output,
+                    "{name}_bucket{{{labels}{sep}le=\"+Inf\"}} {cumulative}"
+                ),
+            }
+            .expect("writing to a String can't fail");
+        }
+        let sum = f64::from_bits(self.sum.load(Ordering::Relaxed));
+        let count = self.count.load(Ordering::Relaxed);
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{labels}}}")
+        };
+        writeln!(
+            output,
+            "{name}_sum{labels} {sum}\n{name}_count{labels} {count}"
+        )
+        .expect("writing to a String can't fail");
+    }
+}
+
+fn histogram_header(name: &str, help: &str) -> String {
+    format!("\n# HELP {name} {help}\n# TYPE {name} histogram\n")
+}
+
+/// Label values of `queryvault_http_request_duration_seconds`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct RouteKey {
+    /// The matched route's template (`/api/v1/workspaces/:workspace_id/...`),
+    /// so paths differing only in IDs share a series
+    route: String,
+    method: String,
+    status: u16,
+}
 
 /// Application metrics for Prometheus
-#[derive(Default)]
 pub struct Metrics {
     /// Total metrics ingested
     pub metrics_ingested_total: AtomicU64,
@@ -16,9 +122,47 @@ pub struct Metrics {
     buffer_depth: AtomicU64,
     /// Active WebSocket connections
     ws_connections: AtomicU64,
+    /// Metrics per flush to the database
+    flush_batch_size: Histogram,
+    /// Time taken by each flush's insert
+    flush_duration: Histogram,
+    /// Flushes that failed with the database unavailable, and were requeued
+    insert_errors_unavailable: AtomicU64,
+    /// Flushes the database rejected, and were dead-lettered
+    insert_errors_rejected: AtomicU64,
+    /// Metrics broadcast to WebSocket and long-poll clients
+    metrics_broadcast_total: AtomicU64,
+    /// Workspaces with queries left to embed after their last visit
+    embedding_backlog: AtomicU64,
+    /// Queries embedded and stored
+    queries_embedded_total: AtomicU64,
+    /// Anomalies detected and stored
+    anomalies_detected_total: AtomicU64,
+    /// Request latency per method, route, and status
+    request_durations: RwLock<HashMap<RouteKey, Histogram>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            metrics_ingested_total: AtomicU64::new(0),
+            metrics_dropped_total: AtomicU64::new(0),
+            requests_total: AtomicU64::new(0),
+            buffer_depth: AtomicU64::new(0),
+            ws_connections: AtomicU64::new(0),
+            flush_batch_size: Histogram::new(FLUSH_BATCH_BUCKETS),
+            flush_duration: Histogram::new(DURATION_BUCKETS),
+            insert_errors_unavailable: AtomicU64::new(0),
+            insert_errors_rejected: AtomicU64::new(0),
+            metrics_broadcast_total: AtomicU64::new(0),
+            embedding_backlog: AtomicU64::new(0),
+            queries_embedded_total: AtomicU64::new(0),
+            anomalies_detected_total: AtomicU64::new(0),
+            request_durations: RwLock::new(HashMap::new()),
+        }
+    }
 }
 
-#[allow(dead_code)]
 impl Metrics {
     pub fn new() -> Self {
         Self::default()
@@ -50,6 +194,82 @@ impl Metrics {
         self.ws_connections.fetch_sub(1, Ordering::Relaxed);
     }
 
+    /// Record a flush of `batch_size` metrics whose insert took `elapsed`
+    pub fn observe_flush(&self, batch_size: usize, elapsed: Duration) {
+        self.flush_batch_size.observe(batch_size as f64);
+        self.flush_duration.observe(elapsed.as_secs_f64());
+    }
+
+    /// Count a failed flush, `requeued` when the database was unavailable
+    /// rather than rejecting the batch
+    pub fn inc_insert_errors(&self, requeued: bool) {
+        let counter = if requeued {
+            &self.insert_errors_unavailable
+        } else {
+            &self.insert_errors_rejected
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_broadcast(&self, count: u64) {
+        self.metrics_broadcast_total
+            .fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn set_embedding_backlog(&self, workspaces: u64) {
+        self.embedding_backlog.store(workspaces, Ordering::Relaxed);
+    }
+
+    pub fn inc_embedded(&self, count: u64) {
+        self.queries_embedded_total
+            .fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn inc_anomalies(&self, count: u64) {
+        self.anomalies_detected_total
+            .fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Record a request to `route` answered with `status` after `elapsed`
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let key = RouteKey {
+            method: method.to_string(),
+            route: route.to_string(),
+            status,
+        };
+        let seconds = elapsed.as_secs_f64();
+        if let Some(histogram) = self.request_durations.read().get(&key) {
+            histogram.observe(seconds);
+            return;
+        }
+        self.request_durations
+            .write()
+            .entry(key)
+            .or_insert_with(|| Histogram::new(DURATION_BUCKETS))
+            .observe(seconds);
+    }
+
+    /// `queryvault_http_request_duration_seconds` in Prometheus text format,
+    /// series sorted by route, method, then status
+    fn render_request_durations(&self) -> String {
+        const NAME: &str = "queryvault_http_request_duration_seconds";
+        let mut output = histogram_header(
+            NAME,
+            "HTTP request latency until the response head, by method, route, and status",
+        );
+        let durations = self.request_durations.read();
+        let mut keys: Vec<&RouteKey> = durations.keys().collect();
+        keys.sort();
+        for key in keys {
+            let labels = format!(
+                "method=\"{}\",route=\"{}\",status=\"{}\"",
+                key.method, key.route, key.status
+            );
+            durations[key].write_series(&mut output, NAME, &labels);
+        }
+        output
+    }
+
     pub fn get_metrics(&self) -> MetricsSnapshot {
         MetricsSnapshot {
             metrics_ingested_total: self.metrics_ingested_total.load(Ordering::Relaxed),
@@ -57,18 +277,56 @@ impl Metrics {
             requests_total: self.requests_total.load(Ordering::Relaxed),
             buffer_depth: self.buffer_depth.load(Ordering::Relaxed),
             ws_connections: self.ws_connections.load(Ordering::Relaxed),
+            insert_errors_unavailable: self.insert_errors_unavailable.load(Ordering::Relaxed),
+            insert_errors_rejected: self.insert_errors_rejected.load(Ordering::Relaxed),
+            metrics_broadcast_total: self.metrics_broadcast_total.load(Ordering::Relaxed),
+            embedding_backlog: self.embedding_backlog.load(Ordering::Relaxed),
+            queries_embedded_total: self.queries_embedded_total.load(Ordering::Relaxed),
+            anomalies_detected_total: self.anomalies_detected_total.load(Ordering::Relaxed),
         }
     }
 }
 
 #[derive(Debug)]
-#[allow(dead_code)]
 pub struct MetricsSnapshot {
     pub metrics_ingested_total: u64,
     pub metrics_dropped_total: u64,
     pub requests_total: u64,
     pub buffer_depth: u64,
     pub ws_connections: u64,
+    pub insert_errors_unavailable: u64,
+    pub insert_errors_rejected: u64,
+    pub metrics_broadcast_total: u64,
+    pub embedding_backlog: u64,
+    pub queries_embedded_total: u64,
+    pub anomalies_detected_total: u64,
+}
+
+/// Counts every request served, for `queryvault_requests_total`, and times
+/// it by matched route for `queryvault_http_request_duration_seconds`.
+/// Requests no route matched share the `unmatched` route, so scanners can't
+/// add a series per path.
+pub async fn record_requests(
+    State(metrics): State<Arc<Metrics>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    metrics.inc_requests();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+    let started = Instant::now();
+    let response = next.run(request).await;
+    metrics.observe_request(
+        &method,
+        &route,
+        response.status().as_u16(),
+        started.elapsed(),
+    );
+    response
 }
 
 /// GET /metrics
@@ -77,13 +335,13 @@ pub struct MetricsSnapshot {
 pub async fn prometheus_metrics(
     axum::extract::State(state): axum::extract::State<crate::state::AppState>,
 ) -> impl IntoResponse {
-    let snapshot = state.metrics.get_metrics();
-    let buffer_len = state.metrics_buffer.len() as u64;
-
     // Update buffer depth
-    state.metrics.set_buffer_depth(buffer_len);
+    state
+        .metrics
+        .set_buffer_depth(state.metrics_buffer.len() as u64);
+    let snapshot = state.metrics.get_metrics();
 
-    let output = format!(
+    let mut output = format!(
         r#"# HELP queryvault_metrics_ingested_total Total number of metrics ingested
 # TYPE queryvault_metrics_ingested_total counter
 queryvault_metrics_ingested_total {}
@@ -104,6 +362,27 @@ queryvault_buffer_depth {}
 # TYPE queryvault_websocket_connections gauge
 queryvault_websocket_connections {}
 
+# HELP queryvault_metrics_broadcast_total Total number of metrics broadcast to WebSocket and long-poll clients
+# TYPE queryvault_metrics_broadcast_total counter
+queryvault_metrics_broadcast_total {}
+
+# HELP queryvault_db_insert_errors_total Metrics flushes that failed, by whether the database was unavailable (requeued) or rejected the batch (dead-lettered)
+# TYPE queryvault_db_insert_errors_total counter
+queryvault_db_insert_errors_total{{kind="unavailable"}} {}
+queryvault_db_insert_errors_total{{kind="rejected"}} {}
+
+# HELP queryvault_embedding_backlog_workspaces Workspaces with queries left to embed after their last visit
+# TYPE queryvault_embedding_backlog_workspaces gauge
+queryvault_embedding_backlog_workspaces {}
+
+# HELP queryvault_queries_embedded_total Total number of queries embedded and stored
+# TYPE queryvault_queries_embedded_total counter
+queryvault_queries_embedded_total {}
+
+# HELP queryvault_anomalies_detected_total Total number of anomalies detected and stored
+# TYPE queryvault_anomalies_detected_total counter
+queryvault_anomalies_detected_total {}
+
 # HELP queryvault_info Build information
 # TYPE queryvault_info gauge
 queryvault_info{{version="{}"}} 1
@@ -111,10 +390,153 @@ queryvault_info{{version="{}"}} 1
         snapshot.metrics_ingested_total,
         snapshot.metrics_dropped_total,
         snapshot.requests_total,
-        buffer_len,
+        snapshot.buffer_depth,
         snapshot.ws_connections,
+        snapshot.metrics_broadcast_total,
+        snapshot.insert_errors_unavailable,
+        snapshot.insert_errors_rejected,
+        snapshot.embedding_backlog,
+        snapshot.queries_embedded_total,
+        snapshot.anomalies_detected_total,
         env!("CARGO_PKG_VERSION"),
     );
+    output.push_str(&state.metrics.flush_batch_size.render(
+        "queryvault_flush_batch_size",
+        "Metrics written per flush to the database",
+    ));
+    output.push_str(&state.metrics.flush_duration.render(
+        "queryvault_flush_duration_seconds",
+        "Time taken to write each flush to the database",
+    ));
+    output.push_str(&state.metrics.render_request_durations());
+
+    if let Some(pool) = state.db.pool_stats() {
+        output.push_str(&format!(
+            r#"
+# HELP queryvault_db_pool_connections Database pool connections by state
+# TYPE queryvault_db_pool_connections gauge
+queryvault_db_pool_connections{{state="active"}} {}
+queryvault_db_pool_connections{{state="idle"}} {}
+
+# HELP queryvault_db_pool_max_connections Configured maximum database pool size
+# TYPE queryvault_db_pool_max_connections gauge
+queryvault_db_pool_max_connections {}
+"#,
+            pool.size.saturating_sub(pool.idle),
+            pool.idle,
+            pool.max,
+        ));
+    }
+
+    if let Some(pool) = state.db.read_pool_stats() {
+        output.push_str(&format!(
+            r#"
+# HELP queryvault_db_read_pool_connections Read-replica pool connections by state
+# TYPE queryvault_db_read_pool_connections gauge
+queryvault_db_read_pool_connections{{state="active"}} {}
+queryvault_db_read_pool_connections{{state="idle"}} {}
+
+# HELP queryvault_db_read_pool_max_connections Configured maximum read-replica pool size
+# TYPE queryvault_db_read_pool_max_connections gauge
+queryvault_db_read_pool_max_connections {}
+"#,
+            pool.size.saturating_sub(pool.idle),
+            pool.idle,
+            pool.max,
+        ));
+    }
+
+    let (hits, misses) = state.key_cache.stats();
+    output.push_str(&format!(
+        r#"
+# HELP queryvault_api_key_cache_lookups_total API key verifications by cache result
+# TYPE queryvault_api_key_cache_lookups_total counter
+queryvault_api_key_cache_lookups_total{{result="hit"}} {}
+queryvault_api_key_cache_lookups_total{{result="miss"}} {}
+"#,
+        hits, misses,
+    ));
+
+    let (hits, misses) = state.search_embeddings.stats();
+    output.push_str(&format!(
+        r#"
+# HELP queryvault_search_embedding_cache_lookups_total Similarity search embeddings by cache result
+# TYPE queryvault_search_embedding_cache_lookups_total counter
+queryvault_search_embedding_cache_lookups_total{{result="hit"}} {}
+queryvault_search_embedding_cache_lookups_total{{result="miss"}} {}
+"#,
+        hits, misses,
+    ));
+
+    if let Some((hits, misses)) = state.db.aggregation_cache_stats() {
+        output.push_str(&format!(
+            r#"
+# HELP queryvault_aggregation_cache_lookups_total Aggregation queries by cache result
+# TYPE queryvault_aggregation_cache_lookups_total counter
+queryvault_aggregation_cache_lookups_total{{result="hit"}} {}
+queryvault_aggregation_cache_lookups_total{{result="miss"}} {}
+"#,
+            hits, misses,
+        ));
+    }
+
+    let deliveries = state.notifier.stats();
+    output.push_str(&format!(
+        r#"
+# HELP queryvault_alert_deliveries_total Alert deliveries by final result, after retries
+# TYPE queryvault_alert_deliveries_total counter
+queryvault_alert_deliveries_total{{result="delivered"}} {}
+queryvault_alert_deliveries_total{{result="rejected"}} {}
+queryvault_alert_deliveries_total{{result="unreachable"}} {}
+"#,
+        deliveries.delivered.load(Ordering::Relaxed),
+        deliveries.rejected.load(Ordering::Relaxed),
+        deliveries.unreachable.load(Ordering::Relaxed),
+    ));
+
+    output.push_str(&format!(
+        r#"
+# HELP queryvault_metrics_quarantined_total Metrics moved to the dead-letter queue because they didn't fit the database schema
+# TYPE queryvault_metrics_quarantined_total counter
+queryvault_metrics_quarantined_total {}
+"#,
+        state.quarantine.total(),
+    ));
+
+    if let Some(embedder) = &state.embedder {
+        output.push_str(
+            r#"
+# HELP queryvault_embedding_provider_healthy Whether an embedding provider is being used (0 while it cools down after failures)
+# TYPE queryvault_embedding_provider_healthy gauge
+"#,
+        );
+        for status in embedder.status() {
+            output.push_str(&format!(
+                "queryvault_embedding_provider_healthy{{provider=\"{}\",primary=\"{}\"}} {}\n",
+                status.provider.as_str(),
+                status.primary,
+                u8::from(status.healthy),
+            ));
+        }
+        output.push_str(&format!(
+            r#"
+# HELP queryvault_embedding_failovers_total Queries embedded by the fallback provider
+# TYPE queryvault_embedding_failovers_total counter
+queryvault_embedding_failovers_total {}
+
+# HELP queryvault_embedding_truncated_total Queries truncated to the model's maximum sequence length
+# TYPE queryvault_embedding_truncated_total counter
+queryvault_embedding_truncated_total {}
+
+# HELP queryvault_embedding_skipped_total Queries not embedded for being over EMBEDDING_MAX_QUERY_BYTES
+# TYPE queryvault_embedding_skipped_total counter
+queryvault_embedding_skipped_total {}
+"#,
+            embedder.failovers(),
+            embedder.truncated(),
+            embedder.skipped(),
+        ));
+    }
 
     (
         [(
@@ -124,3 +546,45 @@ queryvault_info{{version="{}"}} 1
         output,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let histogram = Histogram::new(&[1.0, 10.0]);
+        for value in [0.5, 1.0, 5.0, 50.0] {
+            histogram.observe(value);
+        }
+        assert_eq!(
+            histogram.render("batch", "Batch size"),
+            "\n# HELP batch Batch size\n# TYPE batch histogram\n\
+             batch_bucket{le=\"1\"} 2\n\
+             batch_bucket{le=\"10\"} 3\n\
+             batch_bucket{le=\"+Inf\"} 4\n\
+             batch_sum 56.5\n\
+             batch_count 4\n"
+        );
+    }
+
+    #[test]
+    fn test_request_durations_are_labeled_by_route() {
+        let metrics = Metrics::new();
+        let route = "/api/v1/workspaces/:workspace_id/metrics";
+        metrics.observe_request("GET", route, 200, Duration::from_millis(20));
+        metrics.observe_request("GET", route, 200, Duration::from_millis(200));
+        metrics.observe_request("GET", "unmatched", 404, Duration::from_millis(1));
+
+        let output = metrics.render_request_durations();
+        let labels = format!("method=\"GET\",route=\"{route}\",status=\"200\"");
+        assert!(output.contains(&format!(
+            "queryvault_http_request_duration_seconds_bucket{{{labels},le=\"0.025\"}} 1\n"
+        )));
+        assert!(output.contains(&format!(
+            "queryvault_http_request_duration_seconds_count{{{labels}}} 2\n"
+        )));
+        // Sorted by route, so the API's series come before unmatched requests
+        assert!(output.find(route) < output.find("unmatched"));
+    }
+}
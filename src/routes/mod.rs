@@ -1,8 +1,29 @@
 //! Routes module
 
+pub mod admin;
 pub mod aggregations;
+pub mod alerts;
+pub mod annotations;
+pub mod api_keys;
+pub mod audit;
+pub mod beacon;
+pub mod destinations;
+pub mod erasure;
+pub mod errors;
 pub mod health;
 pub mod ingest;
 pub mod metrics;
+pub mod overview;
+pub mod poll;
+pub mod queries;
+pub mod registry;
+pub mod schemas;
 pub mod search;
+pub mod settings;
+pub mod slo;
+pub mod sse;
+pub mod stats;
+pub mod tables;
+pub mod throughput;
+pub mod usage;
 pub mod ws;
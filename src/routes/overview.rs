@@ -0,0 +1,159 @@
+//! Per-service dashboard API endpoint
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{AnomalyRecord, ErrorSummary, FingerprintSummary, MetricFilter};
+use crate::error::{AppError, Result};
+use crate::models::{Annotation, QueryKind};
+use crate::state::AppState;
+
+/// Query parameters for the service overview endpoint
+#[derive(Debug, Deserialize)]
+pub struct ServiceOverviewQuery {
+    /// Series resolution: "5s", "1m", "5m", "1h", "1d"
+    #[serde(default = "default_window")]
+    pub window: String,
+    /// Start time (defaults to 1 hour ago)
+    pub from: Option<DateTime<Utc>>,
+    /// End time (defaults to now)
+    pub to: Option<DateTime<Utc>>,
+    /// Only executions of this kind of statement
+    pub query_kind: Option<QueryKind>,
+    /// Entries per top-list (default: 10, max: 100)
+    pub limit: Option<i64>,
+}
+
+fn default_window() -> String {
+    "1m".to_string()
+}
+
+/// One point of the service's throughput and latency series
+#[derive(Debug, Serialize)]
+pub struct ServiceSeriesPoint {
+    pub bucket: DateTime<Utc>,
+    pub query_count: i64,
+    pub qps: f64,
+    pub p95_duration_ms: Option<i64>,
+    pub failed_count: Option<i64>,
+}
+
+/// Everything the per-service dashboard renders, in one response
+#[derive(Debug, Serialize)]
+pub struct ServiceOverviewResponse {
+    pub workspace_id: Uuid,
+    pub service_id: Uuid,
+    pub window: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub series: Vec<ServiceSeriesPoint>,
+    pub top_fingerprints: Vec<FingerprintSummary>,
+    pub top_errors: Vec<ErrorSummary>,
+    pub recent_anomalies: Vec<AnomalyRecord>,
+    pub annotations: Vec<Annotation>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/services/:service_id/overview
+///
+/// Returns the service's qps/p95 series, the fingerprints with the most total
+/// execution time, the most frequent errors, anomalies detected in the window, and
+/// workspace annotations overlapping it.
+///
+/// Query parameters:
+/// - window: "5s", "1m", "5m", "1h", or "1d" (default: "1m")
+/// - from: Start time (default: 1 hour ago)
+/// - to: End time (default: now)
+/// - query_kind: Optional kind of statement ("select", "insert", "update",
+///   "delete", "ddl", "other"); the series and top-lists count only
+///   executions of that kind, and only anomalies on such executions are shown
+/// - limit: Entries per top-list (default: 10, max: 100)
+pub async fn get_service_overview(
+    State(state): State<AppState>,
+    Path((workspace_id, service_id)): Path<(Uuid, Uuid)>,
+    Query(params): Query<ServiceOverviewQuery>,
+) -> Result<Json<ServiceOverviewResponse>> {
+    let window_secs = match params.window.as_str() {
+        "5s" => 5.0,
+        "1m" => 60.0,
+        "5m" => 300.0,
+        "1h" => 3_600.0,
+        "1d" => 86_400.0,
+        _ => {
+            return Err(AppError::InvalidRequest(format!(
+                "Invalid window '{}'. Valid options: 5s, 1m, 5m, 1h, 1d",
+                params.window
+            )))
+        }
+    };
+
+    let now = state.clock.now();
+    let from = params.from.unwrap_or_else(|| now - Duration::hours(1));
+    let to = params.to.unwrap_or(now);
+    if from >= to {
+        return Err(AppError::InvalidRequest(
+            "'from' must be before 'to'".into(),
+        ));
+    }
+    let limit = params.limit.unwrap_or(10).clamp(1, 100);
+    let series_filter = MetricFilter {
+        service_id: Some(service_id),
+        query_kind: params.query_kind,
+        ..MetricFilter::default()
+    };
+
+    let (buckets, top_fingerprints, top_errors, recent_anomalies, annotations) = tokio::try_join!(
+        state.db.get_aggregations(
+            workspace_id,
+            &params.window,
+            Tz::UTC,
+            &series_filter,
+            from,
+            to
+        ),
+        state
+            .db
+            .get_top_fingerprints(workspace_id, service_id, params.query_kind, from, to, limit),
+        state
+            .db
+            .get_top_errors(workspace_id, service_id, params.query_kind, from, to, limit),
+        state.db.get_service_anomalies(
+            workspace_id,
+            service_id,
+            params.query_kind,
+            from,
+            to,
+            limit
+        ),
+        state.db.list_annotations(workspace_id, from, to),
+    )?;
+
+    let series = buckets
+        .into_iter()
+        .map(|b| ServiceSeriesPoint {
+            bucket: b.bucket,
+            query_count: b.query_count,
+            qps: b.query_count as f64 / window_secs,
+            p95_duration_ms: b.p95_duration_ms,
+            failed_count: b.failed_count,
+        })
+        .collect();
+
+    Ok(Json(ServiceOverviewResponse {
+        workspace_id,
+        service_id,
+        window: params.window,
+        from,
+        to,
+        series,
+        top_fingerprints,
+        top_errors,
+        recent_anomalies,
+        annotations,
+    }))
+}
@@ -0,0 +1,69 @@
+//! Long-poll endpoint for clients that cannot hold a WebSocket open
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::models::QueryMetric;
+use crate::state::AppState;
+
+/// Query parameters for the long-poll endpoint
+#[derive(Debug, Deserialize)]
+pub struct PollQuery {
+    /// Cursor from the previous response (default: only events from now on)
+    pub since: Option<u64>,
+    /// Maximum seconds to wait for new events (default: 25, max: 30)
+    pub timeout: Option<u64>,
+    /// Maximum number of metrics to return (default: 500, max: 1000)
+    pub limit: Option<usize>,
+}
+
+/// Response for the long-poll endpoint
+#[derive(Debug, Serialize)]
+pub struct PollResponse {
+    pub workspace_id: Uuid,
+    /// Pass as `since` on the next poll
+    pub cursor: u64,
+    /// True when events after `since` were evicted before this poll read them
+    pub gap: bool,
+    pub count: usize,
+    pub metrics: Vec<QueryMetric>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/metrics/poll
+///
+/// Waits until metrics newer than the cursor arrive for the workspace, or the
+/// timeout elapses, and returns them with the cursor for the next poll.
+/// Cursors are issued per server instance; use sticky sessions when running
+/// several replicas.
+///
+/// Query parameters:
+/// - since: Cursor from the previous response (default: now)
+/// - timeout: Maximum wait in seconds (default: 25, max: 30)
+/// - limit: Maximum metrics (default: 500, max: 1000)
+pub async fn poll_metrics(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<PollQuery>,
+) -> Json<PollResponse> {
+    let since = params.since.unwrap_or_else(|| state.events.cursor());
+    let timeout = Duration::from_secs(params.timeout.unwrap_or(25).min(30));
+    let limit = params.limit.unwrap_or(500).clamp(1, 1000);
+
+    let events = state
+        .events
+        .wait_since(workspace_id, since, limit, timeout)
+        .await;
+
+    Json(PollResponse {
+        workspace_id,
+        cursor: events.cursor,
+        gap: events.gap,
+        count: events.metrics.len(),
+        metrics: events.metrics,
+    })
+}
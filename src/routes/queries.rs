@@ -0,0 +1,515 @@
+//! Query catalog, statistics, ownership, and mute API endpoints
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::db::{
+    CatalogEntry, EmbeddingModel, FingerprintStats, FingerprintStatsQuery, QueryCluster, QuerySort,
+};
+use crate::error::{AppError, Result};
+use crate::models::{ApiKey, AuditAction, QueryKind, QueryMute, QueryOwnership};
+use crate::routes::audit;
+use crate::state::AppState;
+
+/// Query parameters for the new-queries endpoint
+#[derive(Debug, Deserialize)]
+pub struct NewQueriesQuery {
+    /// Look-back window in hours (default: 24, max: 720)
+    #[serde(default = "default_hours")]
+    pub hours: i64,
+    /// Maximum number of queries to return (default: 100, max: 1000)
+    pub limit: Option<i64>,
+}
+
+fn default_hours() -> i64 {
+    24
+}
+
+/// Response for the new-queries endpoint
+#[derive(Debug, Serialize)]
+pub struct NewQueriesResponse {
+    pub workspace_id: Uuid,
+    pub since: DateTime<Utc>,
+    pub count: usize,
+    pub queries: Vec<CatalogEntry>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/queries/new
+///
+/// Returns query fingerprints first seen within the look-back window,
+/// newest first. Useful for spotting unexpected query shapes after a deploy.
+///
+/// Query parameters:
+/// - hours: Look-back window in hours (default: 24, max: 720)
+/// - limit: Maximum results (default: 100, max: 1000)
+pub async fn get_new_queries(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<NewQueriesQuery>,
+) -> Result<Json<NewQueriesResponse>> {
+    if !(1..=720).contains(&params.hours) {
+        return Err(AppError::InvalidRequest(
+            "'hours' must be between 1 and 720".into(),
+        ));
+    }
+
+    let limit = params.limit.unwrap_or(100).min(1000);
+    let since = state.clock.now() - Duration::hours(params.hours);
+
+    let queries = state.db.get_new_queries(workspace_id, since, limit).await?;
+
+    Ok(Json(NewQueriesResponse {
+        workspace_id,
+        since,
+        count: queries.len(),
+        queries,
+    }))
+}
+
+/// Query parameters for the per-fingerprint statistics endpoint
+#[derive(Debug, Deserialize)]
+pub struct QueryStatsQuery {
+    /// Start time (defaults to 24 hours ago)
+    pub from: Option<DateTime<Utc>>,
+    /// End time (defaults to now)
+    pub to: Option<DateTime<Utc>>,
+    /// Only this service's executions
+    pub service_id: Option<Uuid>,
+    /// Only executions of this kind of statement
+    pub query_kind: Option<QueryKind>,
+    #[serde(default)]
+    pub sort: QuerySort,
+    /// Maximum number of fingerprints to return (default: 50, max: 500)
+    pub limit: Option<i64>,
+    /// Fingerprints to skip, for paging (default: 0)
+    #[serde(default)]
+    pub offset: i64,
+}
+
+/// Response listing per-fingerprint statistics
+#[derive(Debug, Serialize)]
+pub struct QueryStatsResponse {
+    pub workspace_id: Uuid,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub count: usize,
+    /// Offset of the next page, if there is one
+    pub next_offset: Option<i64>,
+    pub queries: Vec<FingerprintStats>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/queries
+///
+/// Returns one row per query fingerprint with its call count, error rate,
+/// total time, p50/p95/p99 latency, and first and last execution in the
+/// range: the "top queries" view. Rows are sorted largest first.
+///
+/// Query parameters:
+/// - from: Start time (default: 24 hours ago)
+/// - to: End time (default: now)
+/// - service_id: Only this service's executions (optional)
+/// - query_kind: Optional kind of statement ("select", "insert", "update",
+///   "delete", "ddl", "other"); only executions of that kind are counted
+/// - sort: "total_time" (default), "calls", "p50", "p95", "p99", "error_rate", or "last_seen"
+/// - limit: Maximum results (default: 50, max: 500)
+/// - offset: Results to skip (default: 0); pass `next_offset` for the next page
+pub async fn list_query_stats(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<QueryStatsQuery>,
+) -> Result<Json<QueryStatsResponse>> {
+    let now = state.clock.now();
+    let from = params.from.unwrap_or_else(|| now - Duration::hours(24));
+    let to = params.to.unwrap_or(now);
+    if from >= to {
+        return Err(AppError::InvalidRequest(
+            "'from' must be before 'to'".into(),
+        ));
+    }
+    if params.offset < 0 {
+        return Err(AppError::InvalidRequest(
+            "'offset' must not be negative".into(),
+        ));
+    }
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+
+    // One extra row tells whether there is another page
+    let mut queries = state
+        .db
+        .get_fingerprint_stats(
+            workspace_id,
+            &FingerprintStatsQuery {
+                from,
+                to,
+                service_id: params.service_id,
+                query_kind: params.query_kind,
+                sort: params.sort,
+                limit: limit + 1,
+                offset: params.offset,
+            },
+        )
+        .await?;
+    let next_offset = (queries.len() as i64 > limit).then(|| params.offset + limit);
+    queries.truncate(limit as usize);
+
+    Ok(Json(QueryStatsResponse {
+        workspace_id,
+        from,
+        to,
+        count: queries.len(),
+        next_offset,
+        queries,
+    }))
+}
+
+/// Query parameters for the query clusters endpoint
+#[derive(Debug, Deserialize)]
+pub struct QueryClustersQuery {
+    /// Window traffic is measured over, in hours (default: 24, max: 720)
+    #[serde(default = "default_hours")]
+    pub hours: i64,
+    /// Maximum number of clusters to return (default: 50, max: 500)
+    pub limit: Option<i64>,
+}
+
+/// Response listing query clusters
+#[derive(Debug, Serialize)]
+pub struct QueryClustersResponse {
+    pub workspace_id: Uuid,
+    pub since: DateTime<Utc>,
+    /// Model whose embeddings were clustered; null until the first run
+    pub model: Option<EmbeddingModel>,
+    /// When the clusters were computed; null until the first run
+    pub computed_at: Option<DateTime<Utc>>,
+    pub count: usize,
+    pub clusters: Vec<QueryCluster>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/queries/clusters
+///
+/// Returns the workspace's query families: embedded queries grouped by the
+/// clustering task (every 6 hours), each with a label, a representative
+/// query, and its members' calls, errors, total time, and latency over the
+/// window. Clusters with the most total time come first. Empty until the
+/// task has run with embeddings in the workspace.
+///
+/// Query parameters:
+/// - hours: Traffic window in hours (default: 24, max: 720)
+/// - limit: Maximum results (default: 50, max: 500)
+pub async fn list_query_clusters(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<QueryClustersQuery>,
+) -> Result<Json<QueryClustersResponse>> {
+    if !(1..=720).contains(&params.hours) {
+        return Err(AppError::InvalidRequest(
+            "'hours' must be between 1 and 720".into(),
+        ));
+    }
+
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    let since = state.clock.now() - Duration::hours(params.hours);
+    let clustered = state
+        .db
+        .get_query_clusters(workspace_id, since, limit)
+        .await?;
+    let (model, computed_at, clusters) = match clustered {
+        Some(c) => (Some(c.model), Some(c.computed_at), c.clusters),
+        None => (None, None, Vec::new()),
+    };
+
+    Ok(Json(QueryClustersResponse {
+        workspace_id,
+        since,
+        model,
+        computed_at,
+        count: clusters.len(),
+        clusters,
+    }))
+}
+
+/// Request body for creating or replacing an ownership annotation
+#[derive(Debug, Deserialize)]
+pub struct OwnershipRequest {
+    /// Team responsible for the query
+    pub owner_team: Option<String>,
+    /// Links to tickets tracking work on the query (max 20)
+    #[serde(default)]
+    pub ticket_links: Vec<String>,
+    /// Free-form triage notes (max 4000 characters)
+    pub notes: Option<String>,
+}
+
+/// Response listing ownership annotations
+#[derive(Debug, Serialize)]
+pub struct OwnershipListResponse {
+    pub workspace_id: Uuid,
+    pub count: usize,
+    pub annotations: Vec<QueryOwnership>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/ownership
+///
+/// Lists all ownership annotations in the workspace.
+pub async fn list_ownership(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+) -> Result<Json<OwnershipListResponse>> {
+    let annotations = state.db.list_ownership(workspace_id).await?;
+
+    Ok(Json(OwnershipListResponse {
+        workspace_id,
+        count: annotations.len(),
+        annotations,
+    }))
+}
+
+/// GET /api/v1/workspaces/:workspace_id/queries/:fingerprint/ownership
+///
+/// Returns the ownership annotation for a query fingerprint.
+pub async fn get_ownership(
+    State(state): State<AppState>,
+    Path((workspace_id, fingerprint)): Path<(Uuid, String)>,
+) -> Result<Json<QueryOwnership>> {
+    state
+        .db
+        .get_ownership(workspace_id, &fingerprint)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound(format!("No ownership for '{}'", fingerprint)))
+}
+
+/// PUT /api/v1/workspaces/:workspace_id/queries/:fingerprint/ownership
+///
+/// Creates or replaces the ownership annotation for a query fingerprint.
+///
+/// Request body:
+/// - owner_team: Team responsible for the query
+/// - ticket_links: Ticket URLs (max 20)
+/// - notes: Free-form notes (max 4000 characters)
+pub async fn put_ownership(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path((workspace_id, fingerprint)): Path<(Uuid, String)>,
+    Json(request): Json<OwnershipRequest>,
+) -> Result<Json<QueryOwnership>> {
+    validate_ownership(&request)?;
+
+    let ownership = QueryOwnership {
+        fingerprint,
+        owner_team: request.owner_team,
+        ticket_links: request.ticket_links,
+        notes: request.notes,
+        updated_at: state.clock.now(),
+    };
+
+    let stored = state.db.upsert_ownership(workspace_id, &ownership).await?;
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::OwnershipUpdate,
+        Some(stored.fingerprint.clone()),
+        json!({
+            "owner_team": stored.owner_team,
+            "ticket_links": stored.ticket_links,
+            "notes": stored.notes,
+        }),
+    )
+    .await;
+    Ok(Json(stored))
+}
+
+/// DELETE /api/v1/workspaces/:workspace_id/queries/:fingerprint/ownership
+///
+/// Removes the ownership annotation for a query fingerprint.
+pub async fn delete_ownership(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path((workspace_id, fingerprint)): Path<(Uuid, String)>,
+) -> Result<StatusCode> {
+    if state
+        .db
+        .delete_ownership(workspace_id, &fingerprint)
+        .await?
+    {
+        audit::record(
+            &state,
+            &actor,
+            workspace_id,
+            AuditAction::OwnershipDelete,
+            Some(fingerprint),
+            json!({}),
+        )
+        .await;
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound(format!(
+            "No ownership for '{}'",
+            fingerprint
+        )))
+    }
+}
+
+/// Validate ownership annotation limits
+fn validate_ownership(request: &OwnershipRequest) -> Result<()> {
+    if let Some(team) = &request.owner_team {
+        if team.is_empty() || team.chars().count() > 255 {
+            return Err(AppError::InvalidRequest(
+                "'owner_team' must be 1-255 characters".into(),
+            ));
+        }
+    }
+    if request.ticket_links.len() > 20 {
+        return Err(AppError::InvalidRequest(
+            "At most 20 'ticket_links' are allowed".into(),
+        ));
+    }
+    if request
+        .ticket_links
+        .iter()
+        .any(|link| !link.starts_with("https://") && !link.starts_with("http://"))
+    {
+        return Err(AppError::InvalidRequest(
+            "'ticket_links' must be http(s) URLs".into(),
+        ));
+    }
+    if request
+        .notes
+        .as_ref()
+        .is_some_and(|n| n.chars().count() > 4000)
+    {
+        return Err(AppError::InvalidRequest(
+            "'notes' must be at most 4000 characters".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Request body for muting a fingerprint
+#[derive(Debug, Deserialize)]
+pub struct MuteRequest {
+    /// Why the query is muted
+    pub reason: Option<String>,
+    /// When the mute lapses (default: never)
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Response listing active mutes
+#[derive(Debug, Serialize)]
+pub struct MuteListResponse {
+    pub workspace_id: Uuid,
+    pub count: usize,
+    pub mutes: Vec<QueryMute>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/mutes
+///
+/// Lists fingerprints that are currently muted.
+pub async fn list_mutes(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+) -> Result<Json<MuteListResponse>> {
+    let mutes = state.db.list_active_mutes(workspace_id).await?;
+
+    Ok(Json(MuteListResponse {
+        workspace_id,
+        count: mutes.len(),
+        mutes,
+    }))
+}
+
+/// PUT /api/v1/workspaces/:workspace_id/queries/:fingerprint/mute
+///
+/// Marks a fingerprint as expected-slow so anomaly detection ignores it.
+///
+/// Request body:
+/// - reason: Why the query is muted
+/// - expires_at: Optional expiry (default: muted until removed)
+pub async fn put_mute(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path((workspace_id, fingerprint)): Path<(Uuid, String)>,
+    Json(request): Json<MuteRequest>,
+) -> Result<Json<QueryMute>> {
+    let now = state.clock.now();
+    if request
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= now)
+    {
+        return Err(AppError::InvalidRequest(
+            "'expires_at' must be in the future".into(),
+        ));
+    }
+
+    let mute = QueryMute {
+        fingerprint,
+        reason: request.reason,
+        expires_at: request.expires_at,
+        created_at: now,
+    };
+
+    let stored = state.db.upsert_mute(workspace_id, &mute).await?;
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::MuteUpdate,
+        Some(stored.fingerprint.clone()),
+        json!({ "reason": stored.reason, "expires_at": stored.expires_at }),
+    )
+    .await;
+    Ok(Json(stored))
+}
+
+/// DELETE /api/v1/workspaces/:workspace_id/queries/:fingerprint/mute
+///
+/// Unmutes a fingerprint.
+pub async fn delete_mute(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path((workspace_id, fingerprint)): Path<(Uuid, String)>,
+) -> Result<StatusCode> {
+    if state.db.delete_mute(workspace_id, &fingerprint).await? {
+        audit::record(
+            &state,
+            &actor,
+            workspace_id,
+            AuditAction::MuteDelete,
+            Some(fingerprint),
+            json!({}),
+        )
+        .await;
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound(format!("No mute for '{}'", fingerprint)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ownership(owner_team: &str, notes: &str) -> OwnershipRequest {
+        OwnershipRequest {
+            owner_team: Some(owner_team.into()),
+            ticket_links: Vec::new(),
+            notes: Some(notes.into()),
+        }
+    }
+
+    #[test]
+    fn test_ownership_limits_count_characters() {
+        // Multi-byte characters count once, as in the database columns
+        assert!(validate_ownership(&ownership(&"é".repeat(255), &"ü".repeat(4000))).is_ok());
+        assert!(validate_ownership(&ownership(&"é".repeat(256), "")).is_err());
+        assert!(validate_ownership(&ownership("db", &"ü".repeat(4001))).is_err());
+    }
+}
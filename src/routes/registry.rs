@@ -0,0 +1,185 @@
+//! Service registry API endpoints
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::error::{AppError, Result};
+use crate::models::{ApiKey, AuditAction, Service};
+use crate::routes::audit;
+use crate::state::AppState;
+
+/// Request body for registering a service
+#[derive(Debug, Deserialize)]
+pub struct CreateServiceRequest {
+    /// Service ID agents report metrics under (default: generated)
+    pub id: Option<Uuid>,
+    /// Unique name within the workspace (max 255 characters)
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// Request body for updating a service
+#[derive(Debug, Deserialize)]
+pub struct UpdateServiceRequest {
+    /// Unique name within the workspace (max 255 characters)
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// Response listing services
+#[derive(Debug, Serialize)]
+pub struct ServiceListResponse {
+    pub workspace_id: Uuid,
+    pub count: usize,
+    pub services: Vec<Service>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/services
+///
+/// Lists registered services, most recently seen first. Services are
+/// registered automatically the first time they report metrics.
+pub async fn list_services(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+) -> Result<Json<ServiceListResponse>> {
+    let services = state.db.list_services(workspace_id).await?;
+
+    Ok(Json(ServiceListResponse {
+        workspace_id,
+        count: services.len(),
+        services,
+    }))
+}
+
+/// POST /api/v1/workspaces/:workspace_id/services
+///
+/// Registers a service ahead of its first ingest.
+///
+/// Request body:
+/// - id: Service ID agents will report (optional, generated if omitted)
+/// - name: Unique name within the workspace (max 255 characters)
+/// - description: Optional description
+pub async fn create_service(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path(workspace_id): Path<Uuid>,
+    Json(request): Json<CreateServiceRequest>,
+) -> Result<(StatusCode, Json<Service>)> {
+    validate_name(&request.name)?;
+
+    let now = state.clock.now();
+    let service = Service {
+        id: request.id.unwrap_or_else(Uuid::new_v4),
+        workspace_id,
+        name: request.name,
+        description: request.description,
+        created_at: now,
+        updated_at: now,
+        last_seen_at: None,
+    };
+
+    let stored = state.db.create_service(&service).await?;
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::ServiceCreate,
+        Some(stored.id.to_string()),
+        json!({ "name": stored.name, "description": stored.description }),
+    )
+    .await;
+    Ok((StatusCode::CREATED, Json(stored)))
+}
+
+/// GET /api/v1/workspaces/:workspace_id/services/:service_id
+///
+/// Returns a registered service.
+pub async fn get_service(
+    State(state): State<AppState>,
+    Path((workspace_id, service_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<Service>> {
+    state
+        .db
+        .get_service(workspace_id, service_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound(format!("No service '{}'", service_id)))
+}
+
+/// PUT /api/v1/workspaces/:workspace_id/services/:service_id
+///
+/// Renames or re-describes a service.
+///
+/// Request body:
+/// - name: Unique name within the workspace (max 255 characters)
+/// - description: Optional description
+pub async fn update_service(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path((workspace_id, service_id)): Path<(Uuid, Uuid)>,
+    Json(request): Json<UpdateServiceRequest>,
+) -> Result<Json<Service>> {
+    validate_name(&request.name)?;
+
+    let service = state
+        .db
+        .update_service(
+            workspace_id,
+            service_id,
+            &request.name,
+            request.description.as_deref(),
+        )
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No service '{}'", service_id)))?;
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::ServiceUpdate,
+        Some(service_id.to_string()),
+        json!({ "name": service.name, "description": service.description }),
+    )
+    .await;
+    Ok(Json(service))
+}
+
+/// DELETE /api/v1/workspaces/:workspace_id/services/:service_id
+///
+/// Removes a service registration. Its metrics are kept, and the service is
+/// registered again if it keeps reporting.
+pub async fn delete_service(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path((workspace_id, service_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode> {
+    if state.db.delete_service(workspace_id, service_id).await? {
+        audit::record(
+            &state,
+            &actor,
+            workspace_id,
+            AuditAction::ServiceDelete,
+            Some(service_id.to_string()),
+            json!({}),
+        )
+        .await;
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound(format!("No service '{}'", service_id)))
+    }
+}
+
+/// Validate a service name
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > 255 {
+        return Err(AppError::InvalidRequest(
+            "'name' must be 1-255 characters".into(),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,21 @@
+//! Schema registry endpoints for agent developers
+
+use axum::Json;
+use serde_json::Value;
+
+use crate::services::schema::ingest_schema;
+use crate::versioning::ApiVersion;
+
+/// GET /api/v1/schemas/ingest
+///
+/// Returns the JSON Schema that v1 ingest payloads are validated against.
+pub async fn get_ingest_schema_v1() -> Json<Value> {
+    Json(ingest_schema(ApiVersion::V1).clone())
+}
+
+/// GET /api/v2/schemas/ingest
+///
+/// Returns the JSON Schema that v2 ingest payloads are validated against.
+pub async fn get_ingest_schema_v2() -> Json<Value> {
+    Json(ingest_schema(ApiVersion::V2).clone())
+}
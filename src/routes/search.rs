@@ -1,27 +1,49 @@
-//! Similarity search API endpoint
+//! Similarity and text search API endpoints
 
 use axum::{
-    extract::{Path, State},
-    Json,
+    extract::{Path, Query, State},
+    Extension, Json,
 };
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use uuid::Uuid;
 
-use crate::db::SimilarQuery;
+use crate::db::{
+    AnnTuning, AnomalyContext, AnomalyRecord, CatalogEntry, EmbeddedQuery, EmbeddingModel,
+    QueryTextSearch, SimilarQueryStats, ThroughputDrop,
+};
 use crate::error::{AppError, Result};
+use crate::models::{AnomalyStatus, ApiKey, AuditAction};
+use crate::routes::audit;
+use crate::services::duplicates;
+use crate::services::embedder::Embedder;
+use crate::services::fingerprint::{fingerprint, SqlDialect};
+use crate::services::hybrid_search::{self, QueryInput, SearchMatch, SearchMode};
+use crate::services::projection;
+use crate::services::severity::SeverityLevel;
 use crate::state::AppState;
 
 /// Request body for similarity search
 #[derive(Debug, Deserialize)]
 pub struct SimilarSearchRequest {
-    /// SQL query to find similar queries for
+    /// SQL query, or a description in prose, to find similar queries for
     pub query: String,
+    /// Whether `query` is SQL or prose (default: detected)
+    pub input: Option<QueryInput>,
     /// Maximum number of results (default: 10)
     #[serde(default = "default_limit")]
     pub limit: i32,
-    /// Minimum similarity threshold (default: 0.85)
-    #[serde(default = "default_threshold")]
-    pub threshold: f32,
+    /// Minimum similarity threshold (default: 0.85 for SQL, 0.3 for prose)
+    pub threshold: Option<f32>,
+    /// How results are ranked (default: vector)
+    #[serde(default)]
+    pub mode: SearchMode,
+    /// HNSW candidate list size (default: pgvector's 40, or the rows
+    /// searched for when more)
+    pub ef_search: Option<u32>,
+    /// IVFFlat lists scanned (default: pgvector's 1)
+    pub probes: Option<u32>,
 }
 
 fn default_limit() -> i32 {
@@ -32,11 +54,93 @@ fn default_threshold() -> f32 {
     0.85
 }
 
+/// Default similarity threshold of prose searches; prose and SQL embed
+/// further apart than two SQL statements do
+const TEXT_THRESHOLD: f32 = 0.3;
+
 /// Response for similarity search
 #[derive(Debug, Serialize)]
 pub struct SimilarSearchResponse {
     pub query: String,
-    pub results: Vec<SimilarQuery>,
+    pub input: QueryInput,
+    pub mode: SearchMode,
+    pub results: Vec<SearchMatch>,
+}
+
+/// Candidates taken from each ranking before hybrid results are fused
+fn hybrid_candidates(limit: i32) -> i32 {
+    limit.saturating_mul(4).clamp(40, 200)
+}
+
+/// pgvector's default `hnsw.ef_search`
+const DEFAULT_EF_SEARCH: u32 = 40;
+
+/// Largest `ef_search` or `probes` a search may ask for
+const MAX_ANN_TUNING: u32 = 1000;
+
+/// Index settings for a vector search returning up to `rows` results
+///
+/// An HNSW search returns at most `ef_search` rows, so unless the caller
+/// chose a value it's raised to cover the rows asked for.
+fn ann_tuning(request: &SimilarSearchRequest, rows: i32) -> Result<AnnTuning> {
+    for (field, value) in [("ef_search", request.ef_search), ("probes", request.probes)] {
+        if value.is_some_and(|v| !(1..=MAX_ANN_TUNING).contains(&v)) {
+            return Err(AppError::InvalidRequest(format!(
+                "'{}' must be between 1 and {}",
+                field, MAX_ANN_TUNING
+            )));
+        }
+    }
+
+    let rows = rows.clamp(0, MAX_ANN_TUNING as i32) as u32;
+    Ok(AnnTuning {
+        ef_search: request
+            .ef_search
+            .or((rows > DEFAULT_EF_SEARCH).then_some(rows)),
+        probes: request.probes,
+    })
+}
+
+/// Embed search text, reusing the primary model's embedding of it when one
+/// is at hand: cached from an earlier search or, for SQL, stored for the
+/// same query
+async fn search_embedding(
+    state: &AppState,
+    embedder: &Embedder,
+    workspace_id: Uuid,
+    text: &str,
+    input: QueryInput,
+) -> Result<(EmbeddingModel, Vec<f32>)> {
+    let primary = embedder.model();
+    if let Some(embedding) = state.search_embeddings.get(text, &primary) {
+        return Ok((primary, embedding));
+    }
+    if input == QueryInput::Sql {
+        if let Some(embedding) = state
+            .db
+            .get_query_embedding(workspace_id, &primary, text)
+            .await?
+        {
+            state
+                .search_embeddings
+                .insert(text, primary.clone(), embedding.clone());
+            return Ok((primary, embedding));
+        }
+    }
+
+    // Falls back to the secondary provider if needed; its embeddings aren't
+    // cached, so searches go back to the primary once it recovers
+    let embedding = embedder.embed(text).await?;
+    let model = EmbeddingModel {
+        name: embedding.model,
+        version: embedding.model_version,
+    };
+    if model == primary {
+        state
+            .search_embeddings
+            .insert(text, model.clone(), embedding.embedding.clone());
+    }
+    Ok((model, embedding.embedding))
 }
 
 /// POST /api/v1/workspaces/:workspace_id/search/similar
@@ -44,80 +148,652 @@ pub struct SimilarSearchResponse {
 /// Searches for queries similar to the provided query text using vector embeddings.
 ///
 /// Request body:
-/// - query: The SQL query to find similar queries for
+/// - query: The SQL query, or a description such as "queries that lock the
+///   accounts table", to find similar queries for; embedded as written
+/// - input: `sql` or `text`; detected from the query when omitted
 /// - limit: Maximum results (default: 10)
-/// - threshold: Minimum cosine similarity (default: 0.85)
+/// - threshold: Minimum cosine similarity (default: 0.85 for SQL, 0.3 for
+///   prose)
+/// - mode: `vector` ranks by cosine similarity alone; `hybrid` also matches
+///   the table and column names in the query, or the keywords of prose,
+///   with full-text search and fuses both rankings, so exact matches rank
+///   first. The threshold only applies to the vector ranking.
+/// - ef_search / probes: how much of the HNSW or IVFFlat index the vector
+///   search visits (1-1000). Higher values find more of the true nearest
+///   neighbors at the cost of latency; raise them when a workspace holds a
+///   small share of all embeddings and searches come back short, since
+///   workspace and threshold filters apply to the candidates the index
+///   returns.
 pub async fn search_similar(
     State(state): State<AppState>,
     Path(workspace_id): Path<Uuid>,
     Json(request): Json<SimilarSearchRequest>,
 ) -> Result<Json<SimilarSearchResponse>> {
+    let candidates = match request.mode {
+        SearchMode::Vector => request.limit,
+        SearchMode::Hybrid => hybrid_candidates(request.limit),
+    };
+    let tuning = ann_tuning(&request, candidates)?;
+    let input = request
+        .input
+        .unwrap_or_else(|| QueryInput::detect(&request.query));
+    let threshold = request.threshold.unwrap_or(match input {
+        QueryInput::Sql => default_threshold(),
+        QueryInput::Text => TEXT_THRESHOLD,
+    });
+
     // Check if embedding service is available
-    let embedding_service = state
-        .embedding_service
+    let embedder = state
+        .embedder
         .as_ref()
         .ok_or_else(|| AppError::InternalError("Embedding service not configured".into()))?;
 
-    // Embed the query
-    let embedding = embedding_service
-        .embed_query(&request.query)
-        .map_err(|e| AppError::InternalError(format!("Failed to embed query: {}", e)))?;
+    let (model, embedding) =
+        search_embedding(&state, embedder, workspace_id, &request.query, input).await?;
 
-    // Search for similar queries
-    let results = state
-        .db
-        .search_similar_queries(workspace_id, &embedding, request.limit, request.threshold)
-        .await?;
+    // Search for similar queries embedded by the same model
+    let results = match request.mode {
+        SearchMode::Vector => state
+            .db
+            .search_similar_queries(
+                workspace_id,
+                &model,
+                &embedding,
+                request.limit,
+                threshold,
+                tuning,
+            )
+            .await?
+            .into_iter()
+            .map(SearchMatch::from)
+            .collect(),
+        SearchMode::Hybrid => {
+            let terms = hybrid_search::search_terms(&request.query, input);
+            let (vector, lexical) = tokio::try_join!(
+                state.db.search_similar_queries(
+                    workspace_id,
+                    &model,
+                    &embedding,
+                    candidates,
+                    threshold,
+                    tuning,
+                ),
+                state.db.search_lexical_queries(
+                    workspace_id,
+                    &model,
+                    &embedding,
+                    &terms,
+                    candidates,
+                ),
+            )?;
+            hybrid_search::reciprocal_rank_fusion(vector, lexical, request.limit.max(0) as usize)
+        }
+    };
 
     Ok(Json(SimilarSearchResponse {
         query: request.query,
+        input,
+        mode: request.mode,
         results,
     }))
 }
 
-/// GET /api/v1/workspaces/:workspace_id/anomalies
+/// Query parameters for the similar-to-metric endpoint
+#[derive(Debug, Deserialize)]
+pub struct MetricSimilarQuery {
+    /// Window the metric is looked up and performance measured in, in hours
+    /// (default: 24, max: 720)
+    #[serde(default = "default_hours")]
+    pub hours: i64,
+    /// Maximum number of results (default: 10, max: 100)
+    pub limit: Option<i64>,
+    /// Minimum similarity threshold (default: 0.85)
+    #[serde(default = "default_threshold")]
+    pub threshold: f32,
+}
+
+/// Response for the similar-to-metric endpoint
+#[derive(Debug, Serialize)]
+pub struct MetricSimilarResponse {
+    pub workspace_id: Uuid,
+    pub metric_id: Uuid,
+    pub query_hash: String,
+    pub query_text: String,
+    /// Model of the query's embedding, which the results share
+    pub model: EmbeddingModel,
+    pub since: DateTime<Utc>,
+    pub count: usize,
+    pub results: Vec<SimilarQueryStats>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/metrics/:metric_id/similar
 ///
-/// Returns recent anomalies detected for the workspace
-pub async fn get_anomalies(
+/// Returns queries similar to a stored metric's, each with its call count,
+/// errors, and latency percentiles over the window: "what else looks like
+/// this slow query" in one call. The metric's query is compared by its
+/// stored embedding, so nothing is embedded and no embedding provider is
+/// needed; it's 404 until the embedding task has embedded the query, or if
+/// the metric was stored before the window.
+///
+/// Query parameters:
+/// - hours: Window for the metric and performance, in hours (default: 24, max: 720)
+/// - limit: Maximum results (default: 10, max: 100)
+/// - threshold: Minimum cosine similarity (default: 0.85)
+pub async fn get_similar_to_metric(
+    State(state): State<AppState>,
+    Path((workspace_id, metric_id)): Path<(Uuid, Uuid)>,
+    Query(params): Query<MetricSimilarQuery>,
+) -> Result<Json<MetricSimilarResponse>> {
+    if !(1..=720).contains(&params.hours) {
+        return Err(AppError::InvalidRequest(
+            "'hours' must be between 1 and 720".into(),
+        ));
+    }
+    if !(0.0..=1.0).contains(&params.threshold) {
+        return Err(AppError::InvalidRequest(
+            "'threshold' must be between 0 and 1".into(),
+        ));
+    }
+
+    let limit = params.limit.unwrap_or(10).clamp(1, 100);
+    let since = state.clock.now() - Duration::hours(params.hours);
+    let similarity = state
+        .db
+        .get_metric_similarity(workspace_id, metric_id, since, limit, params.threshold)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "No metric '{}' in the last {} hours",
+                metric_id, params.hours
+            ))
+        })?;
+    let Some(model) = similarity.model else {
+        return Err(AppError::NotFound(format!(
+            "Query of metric '{}' hasn't been embedded yet",
+            metric_id
+        )));
+    };
+
+    Ok(Json(MetricSimilarResponse {
+        workspace_id,
+        metric_id,
+        query_hash: similarity.query_hash,
+        query_text: similarity.query_text,
+        model,
+        since,
+        count: similarity.similar.len(),
+        results: similarity.similar,
+    }))
+}
+
+/// Query parameters for the text search endpoint
+#[derive(Debug, Deserialize)]
+pub struct TextSearchQuery {
+    /// Text to search for
+    pub q: String,
+    /// Only queries seen at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Only queries seen before this time
+    pub to: Option<DateTime<Utc>>,
+    /// Only queries this service ran in the range
+    pub service_id: Option<Uuid>,
+    /// Maximum number of queries to return (default: 50, max: 500)
+    pub limit: Option<i64>,
+    /// Queries to skip, for paging (default: 0)
+    #[serde(default)]
+    pub offset: i64,
+}
+
+/// Response for text search
+#[derive(Debug, Serialize)]
+pub struct TextSearchResponse {
+    pub workspace_id: Uuid,
+    pub q: String,
+    pub count: usize,
+    /// Offset of the next page, if there is one
+    pub next_offset: Option<i64>,
+    pub queries: Vec<CatalogEntry>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/search/text
+///
+/// Searches the query catalog by text, without the embedding model. A query
+/// matches if it contains the search text, contains every word of it (as
+/// the start of a word, so `ord` finds `orders`), or has it as its
+/// fingerprint. Substring matches come first, then the best word matches,
+/// then the most recently seen.
+///
+/// Query parameters:
+/// - q: Text to search for (required, max 500 characters)
+/// - from: Only queries last seen at or after this time (optional)
+/// - to: Only queries first seen before this time (optional)
+/// - service_id: Only queries this service ran in the range (optional)
+/// - limit: Maximum results (default: 50, max: 500)
+/// - offset: Results to skip (default: 0); pass `next_offset` for the next page
+pub async fn search_text(
     State(state): State<AppState>,
     Path(workspace_id): Path<Uuid>,
-) -> Result<Json<AnomaliesResponse>> {
-    let rows = sqlx::query(
-        r#"
-        SELECT 
-            id, workspace_id, service_id, metric_id, query_text,
-            duration_ms, mean_duration_ms, stddev_duration_ms, z_score,
-            detected_at
-        FROM query_anomalies
-        WHERE workspace_id = $1
-        ORDER BY detected_at DESC
-        LIMIT 100
-        "#,
-    )
-    .bind(workspace_id)
-    .fetch_all(state.db.pool())
-    .await
-    .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Query(params): Query<TextSearchQuery>,
+) -> Result<Json<TextSearchResponse>> {
+    let q = params.q.trim();
+    if q.is_empty() || q.chars().count() > 500 {
+        return Err(AppError::InvalidRequest(
+            "'q' must be between 1 and 500 characters".into(),
+        ));
+    }
+    if let (Some(from), Some(to)) = (params.from, params.to) {
+        if from >= to {
+            return Err(AppError::InvalidRequest(
+                "'from' must be before 'to'".into(),
+            ));
+        }
+    }
+    if params.offset < 0 {
+        return Err(AppError::InvalidRequest(
+            "'offset' must not be negative".into(),
+        ));
+    }
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+
+    // One extra row tells whether there is another page
+    let mut queries = state
+        .db
+        .search_query_text(
+            workspace_id,
+            &QueryTextSearch {
+                text: q.to_string(),
+                from: params.from,
+                to: params.to,
+                service_id: params.service_id,
+                limit: limit + 1,
+                offset: params.offset,
+            },
+        )
+        .await?;
+    let next_offset = (queries.len() as i64 > limit).then(|| params.offset + limit);
+    queries.truncate(limit as usize);
+
+    Ok(Json(TextSearchResponse {
+        workspace_id,
+        q: q.to_string(),
+        count: queries.len(),
+        next_offset,
+        queries,
+    }))
+}
+
+/// Query parameters for the query landscape endpoint
+#[derive(Debug, Deserialize)]
+pub struct LandscapeQuery {
+    /// Window latency is measured over, in hours (default: 24, max: 720)
+    #[serde(default = "default_hours")]
+    pub hours: i64,
+    /// Maximum number of queries to plot (default: 500, max: 2000)
+    pub limit: Option<i64>,
+    /// Number of clusters (default: 8, max: 20)
+    pub clusters: Option<usize>,
+}
+
+/// A query placed on the landscape
+#[derive(Debug, Serialize)]
+pub struct LandscapePoint {
+    pub query_hash: String,
+    pub query_text: String,
+    pub x: f64,
+    pub y: f64,
+    pub cluster: usize,
+    /// Calls in the latency window
+    pub call_count: i64,
+    pub avg_duration_ms: Option<i64>,
+    pub p95_duration_ms: Option<i64>,
+}
+
+/// A group of similar queries
+#[derive(Debug, Serialize)]
+pub struct LandscapeCluster {
+    pub id: usize,
+    pub size: usize,
+    /// Tables the cluster's queries reference most
+    pub label: Option<String>,
+}
+
+/// Range of p95 latencies among the points, for a color scale
+#[derive(Debug, Serialize)]
+pub struct LatencyScale {
+    pub min_ms: i64,
+    pub max_ms: i64,
+}
+
+/// Embedded queries projected and clustered
+#[derive(Debug, Serialize)]
+pub struct Landscape {
+    /// Share of the embeddings' variance along x and y
+    pub explained_variance: [f64; 2],
+    /// Absent when no plotted query ran in the window
+    pub p95_scale: Option<LatencyScale>,
+    pub clusters: Vec<LandscapeCluster>,
+    pub points: Vec<LandscapePoint>,
+}
+
+/// Response for the query landscape
+#[derive(Debug, Serialize)]
+pub struct LandscapeResponse {
+    pub workspace_id: Uuid,
+    pub since: DateTime<Utc>,
+    pub count: usize,
+    #[serde(flatten)]
+    pub landscape: Landscape,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/search/landscape
+///
+/// Returns the workspace's embedded queries projected to 2D (PCA), with a
+/// cluster per point and its recent latency, for plotting a "query
+/// landscape". The most-called queries are plotted first; raw vectors are
+/// never returned.
+///
+/// Query parameters:
+/// - hours: Latency window in hours (default: 24, max: 720)
+/// - limit: Maximum queries plotted (default: 500, max: 2000)
+/// - clusters: Number of clusters (default: 8, max: 20)
+pub async fn get_landscape(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<LandscapeQuery>,
+) -> Result<Json<LandscapeResponse>> {
+    if !(1..=720).contains(&params.hours) {
+        return Err(AppError::InvalidRequest(
+            "'hours' must be between 1 and 720".into(),
+        ));
+    }
+    let k = params.clusters.unwrap_or(8);
+    if !(1..=20).contains(&k) {
+        return Err(AppError::InvalidRequest(
+            "'clusters' must be between 1 and 20".into(),
+        ));
+    }
+
+    let limit = params.limit.unwrap_or(500).clamp(1, 2000);
+    let since = state.clock.now() - Duration::hours(params.hours);
+    // Only the configured model's vectors can be projected together
+    let model = state.embedder.as_ref().map(|e| e.model());
+    let queries = state
+        .db
+        .list_query_embeddings(workspace_id, model.as_ref(), since, limit)
+        .await?;
+
+    // PCA and k-means are CPU-bound, so keep them off the async workers
+    let landscape = tokio::task::spawn_blocking(move || landscape(queries, k))
+        .await
+        .map_err(|e| AppError::InternalError(format!("Projection failed: {}", e)))?;
+
+    Ok(Json(LandscapeResponse {
+        workspace_id,
+        since,
+        count: landscape.points.len(),
+        landscape,
+    }))
+}
+
+/// Project and cluster embedded queries
+fn landscape(queries: Vec<EmbeddedQuery>, k: usize) -> Landscape {
+    let vectors: Vec<Vec<f32>> = queries.iter().map(|q| q.embedding.clone()).collect();
+    let projection = projection::pca_2d(&vectors);
+    let assignments = projection::kmeans(&vectors, k);
+    drop(vectors);
+
+    let cluster_count = assignments.iter().max().map_or(0, |c| c + 1);
+    let clusters = (0..cluster_count)
+        .map(|id| {
+            let members = queries
+                .iter()
+                .zip(&assignments)
+                .filter(|(_, c)| **c == id)
+                .map(|(q, _)| q.sql_query.as_str());
+            LandscapeCluster {
+                id,
+                size: assignments.iter().filter(|c| **c == id).count(),
+                label: projection::cluster_label(members),
+            }
+        })
+        .collect();
+
+    let p95s = queries.iter().filter_map(|q| q.p95_duration_ms);
+    let p95_scale = p95s
+        .clone()
+        .min()
+        .zip(p95s.max())
+        .map(|(min_ms, max_ms)| LatencyScale { min_ms, max_ms });
 
-    use sqlx::Row;
-    let anomalies: Vec<AnomalyRecord> = rows
+    let points = queries
         .into_iter()
-        .map(|row| AnomalyRecord {
-            id: row.get("id"),
-            workspace_id: row.get("workspace_id"),
-            service_id: row.get("service_id"),
-            metric_id: row.get("metric_id"),
-            query_text: row.get("query_text"),
-            duration_ms: row.get("duration_ms"),
-            mean_duration_ms: row.get("mean_duration_ms"),
-            stddev_duration_ms: row.get("stddev_duration_ms"),
-            z_score: row.get("z_score"),
-            detected_at: row.get("detected_at"),
+        .zip(projection.points)
+        .zip(assignments)
+        .map(|((q, [x, y]), cluster)| LandscapePoint {
+            query_hash: q.query_hash,
+            query_text: q.sql_query,
+            x,
+            y,
+            cluster,
+            call_count: q.call_count,
+            avg_duration_ms: q.avg_duration_ms,
+            p95_duration_ms: q.p95_duration_ms,
         })
         .collect();
 
+    Landscape {
+        explained_variance: projection.explained_variance,
+        p95_scale,
+        clusters,
+        points,
+    }
+}
+
+/// Query parameters for the near-duplicate queries endpoint
+#[derive(Debug, Deserialize)]
+pub struct DuplicatesQuery {
+    /// Window queries are picked and latency measured over, in hours
+    /// (default: 24, max: 720)
+    #[serde(default = "default_hours")]
+    pub hours: i64,
+    /// Maximum number of queries compared (default: 1000, max: 5000)
+    pub limit: Option<i64>,
+    /// Minimum cosine similarity of a near-duplicate (default: 0.97)
+    pub threshold: Option<f64>,
+}
+
+/// A query in a near-duplicate group
+#[derive(Debug, Serialize)]
+pub struct DuplicateQuery {
+    pub query_hash: String,
+    pub fingerprint: String,
+    pub query_text: String,
+    /// Calls in the window
+    pub call_count: i64,
+    pub avg_duration_ms: Option<i64>,
+    pub p95_duration_ms: Option<i64>,
+}
+
+/// Two queries found to be near-duplicates
+#[derive(Debug, Serialize)]
+pub struct DuplicatePair {
+    pub query_hash: String,
+    pub other_hash: String,
+    pub similarity: f64,
+}
+
+/// Queries that are near-duplicates of each other
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    pub size: usize,
+    /// Calls of all the group's queries in the window
+    pub call_count: i64,
+    /// Most-called first
+    pub queries: Vec<DuplicateQuery>,
+    /// The pairs that put the queries in one group, most similar first
+    pub pairs: Vec<DuplicatePair>,
+}
+
+/// Response for near-duplicate queries
+#[derive(Debug, Serialize)]
+pub struct DuplicatesResponse {
+    pub workspace_id: Uuid,
+    pub since: DateTime<Utc>,
+    pub threshold: f64,
+    /// Queries compared
+    pub scanned: usize,
+    pub count: usize,
+    pub groups: Vec<DuplicateGroup>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/search/duplicates
+///
+/// Returns groups of embedded queries whose embeddings are nearly identical
+/// but whose fingerprints differ, such as copy-pasted variants of one query
+/// issued from different code paths. Every pair among the most-called
+/// queries is compared; a group holds queries linked by a pair at or above
+/// the threshold, directly or through other members. Groups with the most
+/// called query come first.
+///
+/// Query parameters:
+/// - hours: Window queries are picked and latency measured over (default:
+///   24, max: 720)
+/// - limit: Maximum queries compared (default: 1000, max: 5000)
+/// - threshold: Minimum cosine similarity, between 0.5 and 1 (default: 0.97)
+pub async fn get_duplicates(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<DuplicatesQuery>,
+) -> Result<Json<DuplicatesResponse>> {
+    if !(1..=720).contains(&params.hours) {
+        return Err(AppError::InvalidRequest(
+            "'hours' must be between 1 and 720".into(),
+        ));
+    }
+    let threshold = params.threshold.unwrap_or(duplicates::DEFAULT_THRESHOLD);
+    if !(0.5..=1.0).contains(&threshold) {
+        return Err(AppError::InvalidRequest(
+            "'threshold' must be between 0.5 and 1".into(),
+        ));
+    }
+
+    let limit = params.limit.unwrap_or(1000).clamp(1, 5000);
+    let since = state.clock.now() - Duration::hours(params.hours);
+    // Only the configured model's vectors can be compared
+    let model = state.embedder.as_ref().map(|e| e.model());
+    let queries = state
+        .db
+        .list_query_embeddings(workspace_id, model.as_ref(), since, limit)
+        .await?;
+    let scanned = queries.len();
+
+    // Comparing every pair is CPU-bound, so keep it off the async workers
+    let groups = tokio::task::spawn_blocking(move || duplicate_groups(queries, threshold))
+        .await
+        .map_err(|e| AppError::InternalError(format!("Comparison failed: {}", e)))?;
+
+    Ok(Json(DuplicatesResponse {
+        workspace_id,
+        since,
+        threshold,
+        scanned,
+        count: groups.len(),
+        groups,
+    }))
+}
+
+/// Group embedded queries, most-called first, into near-duplicates
+fn duplicate_groups(queries: Vec<EmbeddedQuery>, threshold: f64) -> Vec<DuplicateGroup> {
+    let vectors: Vec<Vec<f32>> = queries.iter().map(|q| q.embedding.clone()).collect();
+    let fingerprints: Vec<String> = queries
+        .iter()
+        .map(|q| fingerprint(&q.sql_query, SqlDialect::Generic))
+        .collect();
+    let groups = duplicates::near_duplicates(&vectors, &fingerprints, threshold);
+    drop(vectors);
+
+    groups
+        .into_iter()
+        .map(|group| DuplicateGroup {
+            size: group.members.len(),
+            call_count: group.members.iter().map(|i| queries[*i].call_count).sum(),
+            queries: group
+                .members
+                .iter()
+                .map(|i| DuplicateQuery {
+                    query_hash: queries[*i].query_hash.clone(),
+                    fingerprint: fingerprints[*i].clone(),
+                    query_text: queries[*i].sql_query.clone(),
+                    call_count: queries[*i].call_count,
+                    avg_duration_ms: queries[*i].avg_duration_ms,
+                    p95_duration_ms: queries[*i].p95_duration_ms,
+                })
+                .collect(),
+            pairs: group
+                .pairs
+                .into_iter()
+                .map(|(a, b, similarity)| DuplicatePair {
+                    query_hash: queries[a].query_hash.clone(),
+                    other_hash: queries[b].query_hash.clone(),
+                    similarity,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Query parameters for the anomalies endpoint
+#[derive(Debug, Deserialize)]
+pub struct AnomaliesQuery {
+    /// Look-back window in hours (default: 24, max: 720)
+    #[serde(default = "default_hours")]
+    pub hours: i64,
+    /// Lowest severity level to include (default: all)
+    pub min_severity: Option<SeverityLevel>,
+    /// Only anomalies in this triage status (default: all)
+    pub status: Option<AnomalyStatus>,
+    /// Maximum number of anomalies to return (default: 100, max: 1000)
+    pub limit: Option<i64>,
+}
+
+fn default_hours() -> i64 {
+    24
+}
+
+/// GET /api/v1/workspaces/:workspace_id/anomalies
+///
+/// Returns anomalies detected within the look-back window, most severe
+/// first (by level, then score), so a regression in a hot query or a stalled
+/// one leads a one-off blip.
+///
+/// Query parameters:
+/// - hours: Look-back window in hours (default: 24, max: 720)
+/// - min_severity: low, medium, high, or critical (default: low)
+/// - status: open, acknowledged, or resolved (default: all)
+/// - limit: Maximum results (default: 100, max: 1000)
+pub async fn get_anomalies(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<AnomaliesQuery>,
+) -> Result<Json<AnomaliesResponse>> {
+    if !(1..=720).contains(&params.hours) {
+        return Err(AppError::InvalidRequest(
+            "'hours' must be between 1 and 720".into(),
+        ));
+    }
+
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+    let since = state.clock.now() - Duration::hours(params.hours);
+    let min_severity = params.min_severity.unwrap_or(SeverityLevel::Low);
+
+    let anomalies = state
+        .db
+        .get_anomalies(workspace_id, since, min_severity, params.status, limit)
+        .await?;
+
     Ok(Json(AnomaliesResponse {
         workspace_id,
+        since,
         count: anomalies.len(),
         anomalies,
     }))
@@ -126,20 +802,161 @@ pub async fn get_anomalies(
 #[derive(Debug, Serialize)]
 pub struct AnomaliesResponse {
     pub workspace_id: Uuid,
+    pub since: DateTime<Utc>,
     pub count: usize,
     pub anomalies: Vec<AnomalyRecord>,
 }
 
+/// GET /api/v1/workspaces/:workspace_id/anomalies/:anomaly_id/context
+///
+/// Returns an anomaly with the root-cause hints found when it was detected:
+/// annotations in the preceding 30 minutes, error spikes in the same
+/// service, and other anomalous queries touching the same tables.
+pub async fn get_anomaly_context(
+    State(state): State<AppState>,
+    Path((workspace_id, anomaly_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<AnomalyContext>> {
+    state
+        .db
+        .get_anomaly_context(workspace_id, anomaly_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound(format!("No anomaly '{}'", anomaly_id)))
+}
+
+/// POST /api/v1/workspaces/:workspace_id/anomalies/:anomaly_id/ack
+///
+/// Marks an open anomaly as acknowledged by the calling key, so others can
+/// see someone is looking into it. Acknowledging it again is a no-op; a
+/// resolved anomaly can't be acknowledged.
+pub async fn acknowledge_anomaly(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path((workspace_id, anomaly_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<AnomalyRecord>> {
+    let Some(anomaly) = state
+        .db
+        .acknowledge_anomaly(workspace_id, anomaly_id, &actor.name)
+        .await?
+    else {
+        // Not open: already acknowledged, resolved, or missing
+        let anomaly = existing_anomaly(&state, workspace_id, anomaly_id).await?;
+        if anomaly.status == AnomalyStatus::Resolved {
+            return Err(AppError::Conflict(format!(
+                "Anomaly '{}' is already resolved",
+                anomaly_id
+            )));
+        }
+        return Ok(Json(anomaly));
+    };
+
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::AnomalyAcknowledge,
+        Some(anomaly_id.to_string()),
+        json!({ "fingerprint": anomaly.fingerprint }),
+    )
+    .await;
+    Ok(Json(anomaly))
+}
+
+/// POST /api/v1/workspaces/:workspace_id/anomalies/:anomaly_id/resolve
+///
+/// Marks an anomaly as resolved, whether or not it was acknowledged first.
+/// Resolving it again is a no-op.
+pub async fn resolve_anomaly(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path((workspace_id, anomaly_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<AnomalyRecord>> {
+    let Some(anomaly) = state
+        .db
+        .resolve_anomaly(workspace_id, anomaly_id, &actor.name, state.clock.now())
+        .await?
+    else {
+        return existing_anomaly(&state, workspace_id, anomaly_id)
+            .await
+            .map(Json);
+    };
+
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::AnomalyResolve,
+        Some(anomaly_id.to_string()),
+        json!({ "fingerprint": anomaly.fingerprint }),
+    )
+    .await;
+    Ok(Json(anomaly))
+}
+
+/// The anomaly as stored, or not found
+async fn existing_anomaly(
+    state: &AppState,
+    workspace_id: Uuid,
+    anomaly_id: Uuid,
+) -> Result<AnomalyRecord> {
+    state
+        .db
+        .get_anomaly_context(workspace_id, anomaly_id)
+        .await?
+        .map(|context| context.anomaly)
+        .ok_or_else(|| AppError::NotFound(format!("No anomaly '{}'", anomaly_id)))
+}
+
+/// Query parameters for the throughput drops endpoint
+#[derive(Debug, Deserialize)]
+pub struct ThroughputDropsQuery {
+    /// Look-back window in hours (default: 24, max: 720)
+    #[serde(default = "default_hours")]
+    pub hours: i64,
+    /// Maximum number of drops to return (default: 100, max: 1000)
+    pub limit: Option<i64>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/anomalies/throughput-drops
+///
+/// Returns services whose query volume fell far below its recent baseline:
+/// drops that started within the look-back window or are still open
+/// (`recovered_at` null), newest first.
+///
+/// Query parameters:
+/// - hours: Look-back window in hours (default: 24, max: 720)
+/// - limit: Maximum results (default: 100, max: 1000)
+pub async fn get_throughput_drops(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<ThroughputDropsQuery>,
+) -> Result<Json<ThroughputDropsResponse>> {
+    if !(1..=720).contains(&params.hours) {
+        return Err(AppError::InvalidRequest(
+            "'hours' must be between 1 and 720".into(),
+        ));
+    }
+
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+    let since = state.clock.now() - Duration::hours(params.hours);
+
+    let drops = state
+        .db
+        .list_throughput_drops(workspace_id, since, limit)
+        .await?;
+
+    Ok(Json(ThroughputDropsResponse {
+        workspace_id,
+        since,
+        count: drops.len(),
+        drops,
+    }))
+}
+
 #[derive(Debug, Serialize)]
-pub struct AnomalyRecord {
-    pub id: Uuid,
+pub struct ThroughputDropsResponse {
     pub workspace_id: Uuid,
-    pub service_id: Uuid,
-    pub metric_id: Uuid,
-    pub query_text: String,
-    pub duration_ms: i64,
-    pub mean_duration_ms: i64,
-    pub stddev_duration_ms: i64,
-    pub z_score: f64,
-    pub detected_at: chrono::DateTime<chrono::Utc>,
+    pub since: DateTime<Utc>,
+    pub count: usize,
+    pub drops: Vec<ThroughputDrop>,
 }
@@ -0,0 +1,566 @@
+//! Workspace settings API endpoints
+
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::error::{AppError, Result};
+use crate::models::{
+    AnomalyMethod, AnomalyOverrides, AnomalySettings, ApiKey, AuditAction, JsonCasing,
+    LifecycleAction, LifecycleStage, Seasonality,
+};
+use crate::routes::audit;
+use crate::services::{anomaly, lifecycle};
+use crate::state::AppState;
+
+/// Request body for changing the JSON casing
+#[derive(Debug, Deserialize)]
+pub struct JsonCasingRequest {
+    pub casing: JsonCasing,
+}
+
+/// Response describing the JSON casing setting
+#[derive(Debug, Serialize)]
+pub struct JsonCasingResponse {
+    pub workspace_id: Uuid,
+    pub casing: JsonCasing,
+}
+
+/// Request body for changing the monthly metric quota
+#[derive(Debug, Deserialize)]
+pub struct QuotaRequest {
+    /// Metrics allowed per calendar month (`null` = unlimited)
+    pub monthly_metric_quota: Option<i64>,
+}
+
+/// Response describing the monthly metric quota
+#[derive(Debug, Serialize)]
+pub struct QuotaResponse {
+    pub workspace_id: Uuid,
+    pub monthly_metric_quota: Option<i64>,
+}
+
+/// Request body for turning literal redaction on or off
+#[derive(Debug, Deserialize)]
+pub struct RedactionRequest {
+    pub redact_literals: bool,
+}
+
+/// Response describing the literal redaction setting
+#[derive(Debug, Serialize)]
+pub struct RedactionResponse {
+    pub workspace_id: Uuid,
+    pub redact_literals: bool,
+}
+
+/// Request body for changing the anomaly detection method
+#[derive(Debug, Deserialize)]
+pub struct AnomalyMethodRequest {
+    pub method: AnomalyMethod,
+}
+
+/// Response describing the anomaly detection method
+#[derive(Debug, Serialize)]
+pub struct AnomalyMethodResponse {
+    pub workspace_id: Uuid,
+    pub method: AnomalyMethod,
+}
+
+/// Request body for changing anomaly detection settings; fields left out
+/// are unchanged, and `null` reverts a field to the server's default
+#[derive(Debug, Deserialize)]
+pub struct AnomalySettingsRequest {
+    #[serde(default, deserialize_with = "nullable")]
+    pub z_threshold: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub mad_threshold: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub interval_secs: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub lookback: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub min_samples: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub seasonality: Option<Option<Seasonality>>,
+}
+
+/// Response describing the anomaly detection settings
+#[derive(Debug, Serialize)]
+pub struct AnomalySettingsResponse {
+    pub workspace_id: Uuid,
+    /// Settings in effect
+    pub settings: AnomalySettings,
+    /// Settings the workspace sets itself (`null` = server default)
+    pub overrides: AnomalyOverrides,
+}
+
+/// Tell a field sent as `null` (`Some(None)`) from one left out (`None`)
+fn nullable<'de, T, D>(deserializer: D) -> std::result::Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
+}
+
+/// One stage of a lifecycle policy in a request
+#[derive(Debug, Deserialize)]
+pub struct LifecycleStageRequest {
+    pub action: LifecycleAction,
+    pub after_days: i32,
+}
+
+/// Request body for replacing the lifecycle policy
+#[derive(Debug, Deserialize)]
+pub struct LifecycleRequest {
+    /// Stages in the order they run (empty = default policy)
+    pub stages: Vec<LifecycleStageRequest>,
+}
+
+/// Response describing the lifecycle policy
+#[derive(Debug, Serialize)]
+pub struct LifecycleResponse {
+    pub workspace_id: Uuid,
+    /// Whether the workspace is on the default policy
+    pub default: bool,
+    pub stages: Vec<LifecycleStage>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/settings/json-casing
+///
+/// Returns the field casing used for the workspace's API responses when the
+/// request doesn't set `X-Json-Casing`.
+pub async fn get_json_casing(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+) -> Result<Json<JsonCasingResponse>> {
+    let casing = state
+        .db
+        .get_json_casing(workspace_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No workspace '{}'", workspace_id)))?;
+
+    Ok(Json(JsonCasingResponse {
+        workspace_id,
+        casing,
+    }))
+}
+
+/// PUT /api/v1/workspaces/:workspace_id/settings/json-casing
+///
+/// Sets the workspace's default field casing.
+///
+/// Request body:
+/// - casing: "snake" or "camel"
+pub async fn put_json_casing(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path(workspace_id): Path<Uuid>,
+    Json(request): Json<JsonCasingRequest>,
+) -> Result<Json<JsonCasingResponse>> {
+    if !state
+        .db
+        .set_json_casing(workspace_id, request.casing)
+        .await?
+    {
+        return Err(AppError::NotFound(format!(
+            "No workspace '{}'",
+            workspace_id
+        )));
+    }
+    state.casing_cache.insert(workspace_id, request.casing);
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::JsonCasingUpdate,
+        None,
+        json!({ "casing": request.casing }),
+    )
+    .await;
+
+    Ok(Json(JsonCasingResponse {
+        workspace_id,
+        casing: request.casing,
+    }))
+}
+
+/// GET /api/v1/workspaces/:workspace_id/settings/quota
+///
+/// Returns the number of metrics the workspace may store per calendar month.
+pub async fn get_quota(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+) -> Result<Json<QuotaResponse>> {
+    let monthly_metric_quota = state.db.get_monthly_quota(workspace_id).await?;
+
+    Ok(Json(QuotaResponse {
+        workspace_id,
+        monthly_metric_quota,
+    }))
+}
+
+/// PUT /api/v1/workspaces/:workspace_id/settings/quota
+///
+/// Sets or clears the workspace's monthly metric quota. Once the month's
+/// stored metrics reach it, ingestion is rejected with 429 until the next
+/// month (UTC).
+///
+/// Request body:
+/// - monthly_metric_quota: Positive number of metrics, or null for unlimited
+pub async fn put_quota(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path(workspace_id): Path<Uuid>,
+    Json(request): Json<QuotaRequest>,
+) -> Result<Json<QuotaResponse>> {
+    if request.monthly_metric_quota.is_some_and(|q| q < 1) {
+        return Err(AppError::InvalidRequest(
+            "'monthly_metric_quota' must be positive or null".into(),
+        ));
+    }
+    if !state
+        .db
+        .set_monthly_quota(workspace_id, request.monthly_metric_quota)
+        .await?
+    {
+        return Err(AppError::NotFound(format!(
+            "No workspace '{}'",
+            workspace_id
+        )));
+    }
+    state.quota_cache.invalidate(workspace_id);
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::QuotaUpdate,
+        None,
+        json!({ "monthly_metric_quota": request.monthly_metric_quota }),
+    )
+    .await;
+
+    Ok(Json(QuotaResponse {
+        workspace_id,
+        monthly_metric_quota: request.monthly_metric_quota,
+    }))
+}
+
+/// GET /api/v1/workspaces/:workspace_id/settings/redaction
+///
+/// Returns whether literals in the workspace's ingested query text are
+/// replaced with placeholders.
+pub async fn get_redaction(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+) -> Result<Json<RedactionResponse>> {
+    let redact_literals = state
+        .db
+        .get_literal_redaction(workspace_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No workspace '{}'", workspace_id)))?;
+
+    Ok(Json(RedactionResponse {
+        workspace_id,
+        redact_literals,
+    }))
+}
+
+/// PUT /api/v1/workspaces/:workspace_id/settings/redaction
+///
+/// Turns literal redaction on or off. When on, string and numeric literals
+/// in `query_text` are replaced with `?` at ingest, before metrics are
+/// buffered or stored. Metrics already stored are not rewritten.
+///
+/// Request body:
+/// - redact_literals: true or false
+pub async fn put_redaction(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path(workspace_id): Path<Uuid>,
+    Json(request): Json<RedactionRequest>,
+) -> Result<Json<RedactionResponse>> {
+    if !state
+        .db
+        .set_literal_redaction(workspace_id, request.redact_literals)
+        .await?
+    {
+        return Err(AppError::NotFound(format!(
+            "No workspace '{}'",
+            workspace_id
+        )));
+    }
+    state
+        .redaction_cache
+        .insert(workspace_id, request.redact_literals);
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::RedactionUpdate,
+        None,
+        json!({ "redact_literals": request.redact_literals }),
+    )
+    .await;
+
+    Ok(Json(RedactionResponse {
+        workspace_id,
+        redact_literals: request.redact_literals,
+    }))
+}
+
+/// GET /api/v1/workspaces/:workspace_id/settings/anomaly-detection
+///
+/// Returns how the workspace's anomalies are detected.
+pub async fn get_anomaly_detection(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+) -> Result<Json<AnomalyMethodResponse>> {
+    let method = state
+        .db
+        .get_anomaly_method(workspace_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No workspace '{}'", workspace_id)))?;
+
+    Ok(Json(AnomalyMethodResponse {
+        workspace_id,
+        method,
+    }))
+}
+
+/// PUT /api/v1/workspaces/:workspace_id/settings/anomaly-detection
+///
+/// Changes how the workspace's anomalies are detected. `z_score` flags
+/// queries more than 3 standard deviations above the mean duration; `mad`
+/// flags modified z-scores above 3.5, measured from the median and the
+/// median absolute deviation, which a few extreme outliers can't inflate.
+/// Anomalies already recorded keep the method that found them.
+///
+/// Request body:
+/// - method: "z_score" or "mad"
+pub async fn put_anomaly_detection(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path(workspace_id): Path<Uuid>,
+    Json(request): Json<AnomalyMethodRequest>,
+) -> Result<Json<AnomalyMethodResponse>> {
+    if !state
+        .db
+        .set_anomaly_method(workspace_id, request.method)
+        .await?
+    {
+        return Err(AppError::NotFound(format!(
+            "No workspace '{}'",
+            workspace_id
+        )));
+    }
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::AnomalyMethodUpdate,
+        None,
+        json!({ "method": request.method }),
+    )
+    .await;
+
+    Ok(Json(AnomalyMethodResponse {
+        workspace_id,
+        method: request.method,
+    }))
+}
+
+/// GET /api/v1/workspaces/:workspace_id/settings/anomaly
+///
+/// Returns the thresholds, interval, and sample sizes anomaly detection uses
+/// for the workspace, and which of them the workspace sets itself.
+pub async fn get_anomaly_settings(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+) -> Result<Json<AnomalySettingsResponse>> {
+    let overrides = state
+        .db
+        .get_anomaly_overrides(&[workspace_id])
+        .await?
+        .remove(&workspace_id)
+        .ok_or_else(|| AppError::NotFound(format!("No workspace '{}'", workspace_id)))?;
+
+    Ok(Json(anomaly_settings_response(
+        &state,
+        workspace_id,
+        overrides,
+    )))
+}
+
+/// PATCH /api/v1/workspaces/:workspace_id/settings/anomaly
+///
+/// Changes the anomaly detection settings the workspace sets itself. The
+/// rest keep following the server's defaults (the `ANOMALY_*` variables).
+///
+/// Request body (every field optional; `null` reverts to the default):
+/// - z_threshold: standard deviations above the mean at which a query is anomalous
+/// - mad_threshold: modified z-score above which a query is anomalous under `mad`
+/// - interval_secs: seconds between detection runs (60 to 3600)
+/// - lookback: most recent metrics the statistics cover
+/// - min_samples: fewest recent metrics needed to detect anomalies
+/// - seasonality: "none", "daily" (a baseline per hour of the day), or
+///   "weekly" (a baseline per hour of the week)
+pub async fn patch_anomaly_settings(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path(workspace_id): Path<Uuid>,
+    Json(request): Json<AnomalySettingsRequest>,
+) -> Result<Json<AnomalySettingsResponse>> {
+    let not_found = || AppError::NotFound(format!("No workspace '{}'", workspace_id));
+    let mut overrides = state
+        .db
+        .get_anomaly_overrides(&[workspace_id])
+        .await?
+        .remove(&workspace_id)
+        .ok_or_else(not_found)?;
+    if let Some(z_threshold) = request.z_threshold {
+        overrides.z_threshold = z_threshold;
+    }
+    if let Some(mad_threshold) = request.mad_threshold {
+        overrides.mad_threshold = mad_threshold;
+    }
+    if let Some(interval_secs) = request.interval_secs {
+        overrides.interval_secs = interval_secs;
+    }
+    if let Some(lookback) = request.lookback {
+        overrides.lookback = lookback;
+    }
+    if let Some(min_samples) = request.min_samples {
+        overrides.min_samples = min_samples;
+    }
+    if let Some(seasonality) = request.seasonality {
+        overrides.seasonality = seasonality;
+    }
+    anomaly::validate_settings(&state.anomaly_defaults.with_overrides(&overrides))?;
+
+    if !state
+        .db
+        .set_anomaly_overrides(workspace_id, &overrides)
+        .await?
+    {
+        return Err(not_found());
+    }
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::AnomalySettingsUpdate,
+        None,
+        json!({ "overrides": overrides }),
+    )
+    .await;
+
+    Ok(Json(anomaly_settings_response(
+        &state,
+        workspace_id,
+        overrides,
+    )))
+}
+
+/// Describe a workspace's anomaly detection overrides and the settings they
+/// make
+fn anomaly_settings_response(
+    state: &AppState,
+    workspace_id: Uuid,
+    overrides: AnomalyOverrides,
+) -> AnomalySettingsResponse {
+    AnomalySettingsResponse {
+        workspace_id,
+        settings: state.anomaly_defaults.with_overrides(&overrides),
+        overrides,
+    }
+}
+
+/// GET /api/v1/workspaces/:workspace_id/settings/lifecycle
+///
+/// Returns the stages the lifecycle task applies to the workspace's data,
+/// with how each last ran. Workspaces without a policy of their own report
+/// the default policy, which isn't run per workspace.
+pub async fn get_lifecycle(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+) -> Result<Json<LifecycleResponse>> {
+    let stages = state
+        .db
+        .get_lifecycle_policy(workspace_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No workspace '{}'", workspace_id)))?;
+
+    Ok(Json(lifecycle_response(workspace_id, stages)))
+}
+
+/// PUT /api/v1/workspaces/:workspace_id/settings/lifecycle
+///
+/// Replaces the workspace's lifecycle policy. Stages are listed once each in
+/// the order they run (strip_text, archive, downsample, delete), at ages that
+/// don't decrease; stages other than delete act on raw metrics, so must run
+/// within raw retention, and downsample and delete can't run before 8 days,
+/// while the rollups still refresh from raw metrics. Stages kept from the previous policy keep their run
+/// status. An empty list reverts the workspace to the default policy.
+///
+/// Request body:
+/// - stages: list of `{action, after_days}`
+pub async fn put_lifecycle(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path(workspace_id): Path<Uuid>,
+    Json(request): Json<LifecycleRequest>,
+) -> Result<Json<LifecycleResponse>> {
+    let stages: Vec<LifecycleStage> = request
+        .stages
+        .iter()
+        .map(|stage| LifecycleStage::new(stage.action, stage.after_days))
+        .collect();
+    lifecycle::validate_stages(&stages)?;
+
+    if !state.db.set_lifecycle_policy(workspace_id, &stages).await? {
+        return Err(AppError::NotFound(format!(
+            "No workspace '{}'",
+            workspace_id
+        )));
+    }
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::LifecycleUpdate,
+        None,
+        json!({
+            "stages": stages
+                .iter()
+                .map(|s| json!({ "action": s.action, "after_days": s.after_days }))
+                .collect::<Vec<_>>(),
+        }),
+    )
+    .await;
+
+    let stages = state
+        .db
+        .get_lifecycle_policy(workspace_id)
+        .await?
+        .unwrap_or_default();
+    Ok(Json(lifecycle_response(workspace_id, stages)))
+}
+
+/// Describe a workspace's stored stages, falling back to the default policy
+fn lifecycle_response(workspace_id: Uuid, stages: Vec<LifecycleStage>) -> LifecycleResponse {
+    let default = stages.is_empty();
+    LifecycleResponse {
+        workspace_id,
+        default,
+        stages: if default {
+            lifecycle::default_policy()
+        } else {
+            stages
+        },
+    }
+}
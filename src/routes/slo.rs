@@ -0,0 +1,280 @@
+//! SLO API endpoints
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::db::{SloStatus, RAW_METRICS_RETENTION_DAYS};
+use crate::error::{AppError, Result};
+use crate::models::{ApiKey, AuditAction, Slo};
+use crate::routes::audit;
+use crate::services::slo::BurnState;
+use crate::state::AppState;
+
+/// Longest SLO window; raw metrics are only kept this long
+const MAX_WINDOW_DAYS: u32 = RAW_METRICS_RETENTION_DAYS as u32;
+
+fn default_window_days() -> u32 {
+    MAX_WINDOW_DAYS
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Request body for creating or replacing an SLO
+#[derive(Debug, Deserialize)]
+pub struct SloRequest {
+    /// SLO name (max 255 characters)
+    pub name: String,
+    /// Service to track (default: the whole workspace)
+    pub service_id: Option<Uuid>,
+    /// A query is good if it succeeds within this many milliseconds
+    pub threshold_ms: i64,
+    /// Share of queries that must be good, in percent
+    pub target_percent: f64,
+    /// Rolling window in days (default: 30)
+    #[serde(default = "default_window_days")]
+    pub window_days: u32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+/// Compliance and error budget of an SLO as last computed
+#[derive(Debug, Serialize)]
+pub struct SloStatusResponse {
+    pub computed_at: DateTime<Utc>,
+    /// Queries in the SLO window
+    pub total: i64,
+    /// Succeeded within the threshold
+    pub satisfied: i64,
+    /// Succeeded within four times the threshold, but not within it
+    pub tolerating: i64,
+    /// Share of queries that were good, in percent
+    pub compliance_percent: Option<f64>,
+    /// Apdex score from 0 to 1, with the threshold as target time
+    pub apdex: Option<f64>,
+    /// Share of the error budget left, in percent; negative once overspent
+    pub error_budget_remaining_percent: Option<f64>,
+    pub burn_rate_1h: Option<f64>,
+    pub burn_rate_6h: Option<f64>,
+    pub burn_state: BurnState,
+}
+
+impl SloStatusResponse {
+    fn new(slo: &Slo, status: &SloStatus) -> Self {
+        let counts = &status.counts;
+        Self {
+            computed_at: status.computed_at,
+            total: counts.total,
+            satisfied: counts.satisfied,
+            tolerating: counts.tolerating,
+            compliance_percent: counts.compliance(),
+            apdex: counts.apdex(),
+            error_budget_remaining_percent: counts.budget_remaining(slo.target_percent),
+            burn_rate_1h: status.burn_rate_1h,
+            burn_rate_6h: status.burn_rate_6h,
+            burn_state: status.burn_state,
+        }
+    }
+}
+
+/// An SLO and its status
+#[derive(Debug, Serialize)]
+pub struct SloReport {
+    pub slo: Slo,
+    /// `None` until the SLO task has computed it since the SLO was last saved
+    pub status: Option<SloStatusResponse>,
+}
+
+impl SloReport {
+    fn new(slo: Slo, status: Option<&SloStatus>) -> Self {
+        let status = status
+            .filter(|s| s.computed_at >= slo.updated_at)
+            .map(|s| SloStatusResponse::new(&slo, s));
+        Self { slo, status }
+    }
+}
+
+/// Response listing SLOs
+#[derive(Debug, Serialize)]
+pub struct SloListResponse {
+    pub workspace_id: Uuid,
+    pub count: usize,
+    pub slos: Vec<SloReport>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/slo
+///
+/// Lists SLOs, oldest first, with their compliance, Apdex, error budget, and
+/// burn rate as last computed by the SLO task (every minute).
+pub async fn list_slos(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+) -> Result<Json<SloListResponse>> {
+    let (slos, statuses) = tokio::try_join!(
+        state.db.list_slos(workspace_id),
+        state.db.list_slo_statuses(workspace_id),
+    )?;
+
+    let slos: Vec<SloReport> = slos
+        .into_iter()
+        .map(|slo| {
+            let status = statuses.iter().find(|s| s.slo_id == slo.id);
+            SloReport::new(slo, status)
+        })
+        .collect();
+    Ok(Json(SloListResponse {
+        workspace_id,
+        count: slos.len(),
+        slos,
+    }))
+}
+
+/// POST /api/v1/workspaces/:workspace_id/slo
+///
+/// Creates an SLO: `target_percent` of queries must succeed within
+/// `threshold_ms` over the last `window_days`.
+///
+/// Request body:
+/// - name: SLO name (max 255 characters)
+/// - service_id: Service to track (optional, default: whole workspace)
+/// - threshold_ms: Latency threshold (> 0)
+/// - target_percent: Target share of good queries (between 0 and 100, exclusive)
+/// - window_days: Rolling window (1-30, default: 30)
+/// - enabled: Whether the SLO is computed (default: true)
+pub async fn create_slo(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path(workspace_id): Path<Uuid>,
+    Json(request): Json<SloRequest>,
+) -> Result<(StatusCode, Json<Slo>)> {
+    let slo = build_slo(Uuid::new_v4(), workspace_id, request, state.clock.now())?;
+
+    let stored = state.db.create_slo(&slo).await?;
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::SloCreate,
+        Some(stored.id.to_string()),
+        json!(stored),
+    )
+    .await;
+    Ok((StatusCode::CREATED, Json(stored)))
+}
+
+/// GET /api/v1/workspaces/:workspace_id/slo/:slo_id
+///
+/// Returns an SLO and its status.
+pub async fn get_slo(
+    State(state): State<AppState>,
+    Path((workspace_id, slo_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<SloReport>> {
+    let (slo, statuses) = tokio::try_join!(
+        state.db.get_slo(workspace_id, slo_id),
+        state.db.list_slo_statuses(workspace_id),
+    )?;
+    let slo = slo.ok_or_else(|| AppError::NotFound(format!("No SLO '{}'", slo_id)))?;
+
+    let status = statuses.iter().find(|s| s.slo_id == slo_id);
+    Ok(Json(SloReport::new(slo, status)))
+}
+
+/// PUT /api/v1/workspaces/:workspace_id/slo/:slo_id
+///
+/// Replaces an SLO's definition. Its status is hidden until recomputed.
+///
+/// Request body: as for creating an SLO
+pub async fn update_slo(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path((workspace_id, slo_id)): Path<(Uuid, Uuid)>,
+    Json(request): Json<SloRequest>,
+) -> Result<Json<Slo>> {
+    let slo = build_slo(slo_id, workspace_id, request, state.clock.now())?;
+
+    let stored = state
+        .db
+        .update_slo(&slo)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("No SLO '{}'", slo_id)))?;
+    audit::record(
+        &state,
+        &actor,
+        workspace_id,
+        AuditAction::SloUpdate,
+        Some(slo_id.to_string()),
+        json!(stored),
+    )
+    .await;
+    Ok(Json(stored))
+}
+
+/// DELETE /api/v1/workspaces/:workspace_id/slo/:slo_id
+///
+/// Deletes an SLO.
+pub async fn delete_slo(
+    State(state): State<AppState>,
+    Extension(actor): Extension<ApiKey>,
+    Path((workspace_id, slo_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode> {
+    if state.db.delete_slo(workspace_id, slo_id).await? {
+        audit::record(
+            &state,
+            &actor,
+            workspace_id,
+            AuditAction::SloDelete,
+            Some(slo_id.to_string()),
+            json!({}),
+        )
+        .await;
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound(format!("No SLO '{}'", slo_id)))
+    }
+}
+
+/// Validate a request and build the SLO it describes
+fn build_slo(id: Uuid, workspace_id: Uuid, request: SloRequest, now: DateTime<Utc>) -> Result<Slo> {
+    if request.name.is_empty() || request.name.len() > 255 {
+        return Err(AppError::InvalidRequest(
+            "'name' must be 1-255 characters".into(),
+        ));
+    }
+    if request.threshold_ms <= 0 {
+        return Err(AppError::InvalidRequest(
+            "'threshold_ms' must be positive".into(),
+        ));
+    }
+    if !(request.target_percent > 0.0 && request.target_percent < 100.0) {
+        return Err(AppError::InvalidRequest(
+            "'target_percent' must be between 0 and 100, exclusive".into(),
+        ));
+    }
+    if !(1..=MAX_WINDOW_DAYS).contains(&request.window_days) {
+        return Err(AppError::InvalidRequest(format!(
+            "'window_days' must be between 1 and {}",
+            MAX_WINDOW_DAYS
+        )));
+    }
+
+    Ok(Slo {
+        id,
+        workspace_id,
+        name: request.name,
+        service_id: request.service_id,
+        threshold_ms: request.threshold_ms,
+        target_percent: request.target_percent,
+        window_days: request.window_days,
+        enabled: request.enabled,
+        created_at: now,
+        updated_at: now,
+    })
+}
@@ -0,0 +1,90 @@
+//! Server-Sent Events endpoint for clients behind proxies that handle
+//! WebSockets poorly
+
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::stream::{self, Stream};
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::time::Duration;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::routes::poll::PollResponse;
+use crate::state::AppState;
+
+/// How long each read of the event log waits before checking again
+const WAIT: Duration = Duration::from_secs(25);
+
+/// Query parameters for the event stream
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    /// Cursor to resume after when there is no `Last-Event-ID` (default:
+    /// only events from now on)
+    pub since: Option<u64>,
+    /// Maximum number of metrics per event (default: 500, max: 1000)
+    pub limit: Option<usize>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/events
+///
+/// Streams the workspace's metrics as Server-Sent Events, with the same
+/// workspace filtering as the WebSocket stream. Each `metrics` event carries
+/// a batch as the long-poll response body, and its id is the batch's cursor,
+/// so reconnecting clients resume from `Last-Event-ID`; `gap: true` means
+/// events were evicted before they were sent. Cursors are issued per server
+/// instance, like long-poll cursors. Comments keep idle connections alive.
+///
+/// Query parameters:
+/// - since: Cursor to resume after without `Last-Event-ID` (default: now)
+/// - limit: Maximum metrics per event (default: 500, max: 1000)
+pub async fn metric_events(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<EventsQuery>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok());
+    let cursor = last_event_id
+        .or(params.since)
+        .unwrap_or_else(|| state.events.cursor());
+    let limit = params.limit.unwrap_or(500).clamp(1, 1000);
+    let events = state.events;
+
+    let stream = stream::unfold(cursor, move |mut cursor| {
+        let events = events.clone();
+        async move {
+            loop {
+                let batch = events.wait_since(workspace_id, cursor, limit, WAIT).await;
+                cursor = batch.cursor;
+                if batch.metrics.is_empty() && !batch.gap {
+                    continue;
+                }
+
+                let response = PollResponse {
+                    workspace_id,
+                    cursor,
+                    gap: batch.gap,
+                    count: batch.metrics.len(),
+                    metrics: batch.metrics,
+                };
+                match Event::default()
+                    .event("metrics")
+                    .id(cursor.to_string())
+                    .json_data(&response)
+                {
+                    Ok(event) => return Some((Ok(event), cursor)),
+                    Err(e) => warn!(error = %e, "Failed to serialize metrics event"),
+                }
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
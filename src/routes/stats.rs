@@ -0,0 +1,145 @@
+//! Workspace statistics API endpoints: ingestion and duration outliers
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{DurationGroupBy, DurationStats, DurationStatsQuery, IngestionMinute};
+use crate::error::{AppError, Result};
+use crate::models::QueryKind;
+use crate::state::AppState;
+
+/// Hours of per-minute counts returned
+const STATS_HOURS: i64 = 24;
+
+/// Response describing what happened to a workspace's ingested metrics
+#[derive(Debug, Serialize)]
+pub struct IngestionStatsResponse {
+    pub workspace_id: Uuid,
+    /// Start of the first minute covered
+    pub from: DateTime<Utc>,
+    /// Metrics buffered for storage within the range
+    pub accepted: i64,
+    /// Metrics lost because the buffer was full
+    pub dropped: i64,
+    /// Metrics skipped as resends of recently ingested IDs
+    pub deduplicated: i64,
+    /// Per-minute breakdown, oldest first; minutes without ingest calls are
+    /// omitted
+    pub minutes: Vec<IngestionMinute>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/stats/ingestion
+///
+/// Returns per-minute counts of the workspace's metrics accepted, dropped,
+/// and deduplicated by the ingest endpoints over the last 24 hours, summed
+/// across instances. Counts reach the database every few seconds, so the
+/// current minute may still grow.
+pub async fn get_ingestion_stats(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+) -> Result<Json<IngestionStatsResponse>> {
+    let since = state.clock.now() - Duration::hours(STATS_HOURS);
+    let from = since.duration_trunc(Duration::minutes(1)).unwrap_or(since);
+
+    let minutes = state.db.get_ingestion_stats(workspace_id, from).await?;
+
+    Ok(Json(IngestionStatsResponse {
+        workspace_id,
+        from,
+        accepted: minutes.iter().map(|m| m.accepted).sum(),
+        dropped: minutes.iter().map(|m| m.dropped).sum(),
+        deduplicated: minutes.iter().map(|m| m.deduplicated).sum(),
+        minutes,
+    }))
+}
+
+/// Query parameters for the duration statistics endpoint
+#[derive(Debug, Deserialize)]
+pub struct DurationStatsParams {
+    /// What to group executions by (default: service)
+    #[serde(default)]
+    pub group_by: DurationGroupBy,
+    /// Start time (defaults to 24 hours ago)
+    pub from: Option<DateTime<Utc>>,
+    /// End time (defaults to now)
+    pub to: Option<DateTime<Utc>>,
+    /// Only this service's executions
+    pub service_id: Option<Uuid>,
+    /// Only executions of this kind of statement
+    pub query_kind: Option<QueryKind>,
+    /// Maximum number of groups to return (default: 50, max: 500)
+    pub limit: Option<i64>,
+}
+
+/// Response listing per-group duration statistics
+#[derive(Debug, Serialize)]
+pub struct DurationStatsResponse {
+    pub workspace_id: Uuid,
+    pub group_by: DurationGroupBy,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub count: usize,
+    pub groups: Vec<DurationStats>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/stats/durations
+///
+/// Returns robust duration statistics per service or query fingerprint over
+/// a time range: median, median absolute deviation (MAD), p99.9, and the
+/// maximum with the ID of the execution that took it, slowest maximum first.
+/// Unlike mean and standard deviation, median and MAD aren't dragged along
+/// by the outliers being hunted, so `max_score` (the maximum's modified
+/// z-score) says how far the slowest execution stands out.
+///
+/// Query parameters:
+/// - group_by: "service" (default) or "fingerprint"
+/// - from: Start time (default: 24 hours ago)
+/// - to: End time (default: now)
+/// - service_id: Only this service's executions (optional)
+/// - query_kind: Optional kind of statement ("select", "insert", "update",
+///   "delete", "ddl", "other"); only executions of that kind are counted
+/// - limit: Maximum results (default: 50, max: 500)
+pub async fn get_duration_stats(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<DurationStatsParams>,
+) -> Result<Json<DurationStatsResponse>> {
+    let now = state.clock.now();
+    let from = params.from.unwrap_or_else(|| now - Duration::hours(24));
+    let to = params.to.unwrap_or(now);
+    if from >= to {
+        return Err(AppError::InvalidRequest(
+            "'from' must be before 'to'".into(),
+        ));
+    }
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+
+    let groups = state
+        .db
+        .get_duration_stats(
+            workspace_id,
+            &DurationStatsQuery {
+                from,
+                to,
+                group_by: params.group_by,
+                service_id: params.service_id,
+                query_kind: params.query_kind,
+                limit,
+            },
+        )
+        .await?;
+
+    Ok(Json(DurationStatsResponse {
+        workspace_id,
+        group_by: params.group_by,
+        from,
+        to,
+        count: groups.len(),
+        groups,
+    }))
+}
@@ -0,0 +1,110 @@
+//! Table analytics API endpoint
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::{AppError, Result};
+use crate::models::QueryKind;
+use crate::services::tables::{table_stats, TableStats};
+use crate::state::AppState;
+
+/// Most statements whose tables are counted per request, most called first
+const MAX_STATEMENTS: i64 = 10_000;
+
+/// Query parameters for the tables endpoint
+#[derive(Debug, Deserialize)]
+pub struct TablesQuery {
+    /// Start time (defaults to 24 hours ago)
+    pub from: Option<DateTime<Utc>>,
+    /// End time (defaults to now)
+    pub to: Option<DateTime<Utc>>,
+    /// Only this service's executions
+    pub service_id: Option<Uuid>,
+    /// Only executions of this kind of statement
+    pub query_kind: Option<QueryKind>,
+    /// Maximum number of tables to return (default: 100, max: 1000)
+    pub limit: Option<usize>,
+}
+
+/// Response listing per-table statistics
+#[derive(Debug, Serialize)]
+pub struct TablesResponse {
+    pub workspace_id: Uuid,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub count: usize,
+    /// Whether the range held more statements than are analyzed, so the
+    /// least called ones were left out
+    pub truncated: bool,
+    pub tables: Vec<TableStats>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/tables
+///
+/// Returns one row per table referenced by the range's queries, with its
+/// query count, read and write counts, error count, and total, average, and
+/// maximum duration, most queried first. Tables are taken from the parsed
+/// SQL of each statement, using its service's dialect; a statement that
+/// modifies data counts as a write to every table it references. Executions
+/// are counted from the hourly rollups, so the range is widened to whole
+/// hours.
+///
+/// Query parameters:
+/// - from: Start time (default: 24 hours ago)
+/// - to: End time (default: now)
+/// - service_id: Only this service's executions (optional)
+/// - query_kind: Optional kind of statement ("select", "insert", "update",
+///   "delete", "ddl", "other"); only executions of that kind are counted
+/// - limit: Maximum results (default: 100, max: 1000)
+pub async fn list_tables(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<TablesQuery>,
+) -> Result<Json<TablesResponse>> {
+    let now = state.clock.now();
+    let from = params.from.unwrap_or_else(|| now - Duration::hours(24));
+    let to = params.to.unwrap_or(now);
+    if from >= to {
+        return Err(AppError::InvalidRequest(
+            "'from' must be before 'to'".into(),
+        ));
+    }
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+
+    // One extra row tells whether statements were left out
+    let mut statements = state
+        .db
+        .get_statement_totals(
+            workspace_id,
+            params.service_id,
+            params.query_kind,
+            from,
+            to,
+            MAX_STATEMENTS + 1,
+        )
+        .await?;
+    let truncated = statements.len() as i64 > MAX_STATEMENTS;
+    statements.truncate(MAX_STATEMENTS as usize);
+
+    // Parsing thousands of statements is CPU-bound, so keep it off the
+    // async workers
+    let dialects = state.dialects.clone();
+    let mut tables = tokio::task::spawn_blocking(move || table_stats(&statements, &dialects))
+        .await
+        .map_err(|e| AppError::InternalError(format!("Table analysis failed: {}", e)))?;
+    tables.truncate(limit);
+
+    Ok(Json(TablesResponse {
+        workspace_id,
+        from,
+        to,
+        count: tables.len(),
+        truncated,
+        tables,
+    }))
+}
@@ -0,0 +1,230 @@
+//! Throughput API endpoint
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Duration, TimeDelta, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+use crate::db::{AggregatedMetric, MetricFilter};
+use crate::error::{AppError, Result};
+use crate::models::QueryKind;
+use crate::routes::aggregations::AggregationSource;
+use crate::services::buckets::window_width;
+use crate::state::AppState;
+
+/// Query parameters for throughput endpoint
+#[derive(Debug, Deserialize)]
+pub struct ThroughputQuery {
+    /// Bucket width: "5s", "1m", "5m", "1h", "1d"
+    #[serde(default = "default_window")]
+    pub window: String,
+    /// Start time (defaults to 1 hour ago)
+    pub from: Option<DateTime<Utc>>,
+    /// End time (defaults to now)
+    pub to: Option<DateTime<Utc>>,
+    /// Optional service_id filter
+    pub service_id: Option<Uuid>,
+    /// Only count metrics of this kind of statement
+    pub query_kind: Option<QueryKind>,
+    /// Where buckets come from (default: stored)
+    #[serde(default)]
+    pub source: AggregationSource,
+}
+
+fn default_window() -> String {
+    "1m".to_string()
+}
+
+/// Queries and their outcomes in one bucket, across services
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ThroughputBucket {
+    pub bucket: DateTime<Utc>,
+    pub query_count: i64,
+    pub success_count: i64,
+    pub failed_count: i64,
+    /// Queries per second; the bucket holding `to` is only measured up to it
+    pub qps: f64,
+    /// Share of queries that succeeded, in percent
+    pub success_rate_percent: Option<f64>,
+    /// Share of queries that failed, in percent
+    pub failure_rate_percent: Option<f64>,
+}
+
+/// Response for throughput endpoint
+#[derive(Debug, Serialize)]
+pub struct ThroughputResponse {
+    pub workspace_id: Uuid,
+    pub window: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub buckets: Vec<ThroughputBucket>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/throughput
+///
+/// Returns query counts, queries per second, and success and failure rates
+/// per UTC bucket. A lighter alternative to the aggregations endpoint for
+/// dashboards that poll often: services are summed together and there are no
+/// duration statistics or annotations.
+///
+/// Query parameters:
+/// - window: "5s", "1m", "5m", "1h", or "1d" (default: "1m")
+/// - from: Start time (default: 1 hour ago)
+/// - to: End time (default: now)
+/// - service_id: Optional filter by service
+/// - query_kind: Optional filter by kind of statement ("select", "insert",
+///   "update", "delete", "ddl", "other"), e.g. to chart writes apart from reads
+/// - source: "stored" (default) or "live" for this instance's in-memory 1-minute
+///   rollups of the last hour
+pub async fn get_throughput(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<ThroughputQuery>,
+) -> Result<Json<ThroughputResponse>> {
+    let width = window_width(&params.window).ok_or_else(|| {
+        AppError::InvalidRequest(format!(
+            "Invalid window '{}'. Valid options: 5s, 1m, 5m, 1h, 1d",
+            params.window
+        ))
+    })?;
+
+    // Set default time range
+    let now = state.clock.now();
+    let from = params.from.unwrap_or_else(|| now - Duration::hours(1));
+    let to = params.to.unwrap_or(now);
+
+    if from >= to {
+        return Err(AppError::InvalidRequest(
+            "'from' must be before 'to'".into(),
+        ));
+    }
+
+    let buckets = match params.source {
+        AggregationSource::Live if params.window != "1m" => {
+            return Err(AppError::InvalidRequest(
+                "Live throughput is only available for the '1m' window".into(),
+            ));
+        }
+        AggregationSource::Live if params.query_kind.is_some() => {
+            return Err(AppError::InvalidRequest(
+                "Live throughput can't be filtered by query kind".into(),
+            ));
+        }
+        AggregationSource::Live => {
+            state
+                .live_rollups
+                .get(workspace_id, params.service_id, from, to)
+        }
+        AggregationSource::Stored => {
+            let filter = MetricFilter {
+                service_id: params.service_id,
+                query_kind: params.query_kind,
+                ..Default::default()
+            };
+            state
+                .db
+                .get_aggregations(workspace_id, &params.window, Tz::UTC, &filter, from, to)
+                .await?
+        }
+    };
+
+    Ok(Json(ThroughputResponse {
+        workspace_id,
+        window: params.window,
+        from,
+        to,
+        buckets: throughput(&buckets, width, to),
+    }))
+}
+
+/// Sum per-service aggregation buckets into throughput buckets, oldest first
+fn throughput(
+    aggregations: &[AggregatedMetric],
+    width: TimeDelta,
+    to: DateTime<Utc>,
+) -> Vec<ThroughputBucket> {
+    // Bucket start -> (queries, succeeded, failed)
+    let mut counts: BTreeMap<DateTime<Utc>, (i64, i64, i64)> = BTreeMap::new();
+    for aggregation in aggregations {
+        let entry = counts.entry(aggregation.bucket).or_default();
+        entry.0 += aggregation.query_count;
+        entry.1 += aggregation.success_count.unwrap_or(0);
+        entry.2 += aggregation.failed_count.unwrap_or(0);
+    }
+
+    counts
+        .into_iter()
+        .map(|(bucket, (query_count, success_count, failed_count))| {
+            let seconds = ((bucket + width).min(to) - bucket).num_milliseconds() as f64 / 1000.0;
+            let percent =
+                |count: i64| (query_count > 0).then(|| count as f64 / query_count as f64 * 100.0);
+            ThroughputBucket {
+                bucket,
+                query_count,
+                success_count,
+                failed_count,
+                qps: if seconds > 0.0 {
+                    query_count as f64 / seconds
+                } else {
+                    0.0
+                },
+                success_rate_percent: percent(success_count),
+                failure_rate_percent: percent(failed_count),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    fn bucket(start: &str, success: i64, failed: i64) -> AggregatedMetric {
+        AggregatedMetric {
+            workspace_id: Uuid::nil(),
+            service_id: Uuid::new_v4(),
+            bucket: at(start),
+            query_count: success + failed,
+            avg_duration_ms: Some(10),
+            min_duration_ms: Some(1),
+            max_duration_ms: Some(20),
+            p95_duration_ms: Some(18),
+            p99_duration_ms: Some(20),
+            success_count: Some(success),
+            failed_count: Some(failed),
+            total_rows_affected: Some(0),
+        }
+    }
+
+    #[test]
+    fn test_services_summed_per_bucket() {
+        let buckets = throughput(
+            &[
+                bucket("2026-01-01T12:01:00Z", 30, 0),
+                bucket("2026-01-01T12:00:00Z", 45, 15),
+                bucket("2026-01-01T12:00:00Z", 45, 15),
+            ],
+            TimeDelta::minutes(1),
+            at("2026-01-01T12:01:30Z"),
+        );
+
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[0].bucket, at("2026-01-01T12:00:00Z"));
+        assert_eq!(buckets[0].query_count, 120);
+        assert_eq!(buckets[0].qps, 2.0);
+        assert_eq!(buckets[0].success_rate_percent, Some(75.0));
+        assert_eq!(buckets[0].failure_rate_percent, Some(25.0));
+        // Only 30 seconds of the current minute have passed
+        assert_eq!(buckets[1].qps, 1.0);
+        assert_eq!(buckets[1].failure_rate_percent, Some(0.0));
+    }
+}
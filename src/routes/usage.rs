@@ -0,0 +1,95 @@
+//! Workspace usage API endpoint
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::UsageDay;
+use crate::error::{AppError, Result};
+use crate::services::usage;
+use crate::state::AppState;
+
+/// Longest range one request may cover, in days
+const MAX_RANGE_DAYS: i64 = 366;
+
+/// Query parameters for the usage endpoint
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    /// First day, inclusive (defaults to the start of the current month)
+    pub from: Option<NaiveDate>,
+    /// Last day, inclusive (defaults to today)
+    pub to: Option<NaiveDate>,
+}
+
+/// Response describing a workspace's usage
+#[derive(Debug, Serialize)]
+pub struct UsageResponse {
+    pub workspace_id: Uuid,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Metrics stored within the range
+    pub metric_count: i64,
+    /// Estimated bytes stored within the range
+    pub stored_bytes: i64,
+    /// Metrics allowed per calendar month (`None` = unlimited)
+    pub monthly_quota: Option<i64>,
+    /// Metrics stored since the start of the current month
+    pub month_to_date_metric_count: i64,
+    /// Daily breakdown, oldest first; days without metrics are omitted
+    pub days: Vec<UsageDay>,
+}
+
+/// GET /api/v1/workspaces/:workspace_id/usage
+///
+/// Returns metrics stored and estimated storage per UTC day, for chargeback,
+/// with the workspace's monthly quota.
+///
+/// Query parameters:
+/// - from: First day, e.g. 2024-01-01 (default: start of the current month)
+/// - to: Last day, inclusive (default: today)
+pub async fn get_usage(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    Query(params): Query<UsageQuery>,
+) -> Result<Json<UsageResponse>> {
+    let today = state.clock.now().date_naive();
+    let month_start = usage::month_start(today);
+    let from = params.from.unwrap_or(month_start);
+    let to = params.to.unwrap_or(today);
+    if from > to {
+        return Err(AppError::InvalidRequest(
+            "'from' must not be after 'to'".into(),
+        ));
+    }
+    if (to - from).num_days() >= MAX_RANGE_DAYS {
+        return Err(AppError::InvalidRequest(format!(
+            "Range must cover at most {} days",
+            MAX_RANGE_DAYS
+        )));
+    }
+
+    let days = state.db.get_usage(workspace_id, from, to).await?;
+    let month_to_date_metric_count = state
+        .db
+        .get_usage(workspace_id, month_start, today)
+        .await?
+        .iter()
+        .map(|d| d.metric_count)
+        .sum();
+    let monthly_quota = state.db.get_monthly_quota(workspace_id).await?;
+
+    Ok(Json(UsageResponse {
+        workspace_id,
+        from,
+        to,
+        metric_count: days.iter().map(|d| d.metric_count).sum(),
+        stored_bytes: days.iter().map(|d| d.stored_bytes).sum(),
+        monthly_quota,
+        month_to_date_metric_count,
+        days,
+    }))
+}
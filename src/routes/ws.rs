@@ -1,112 +1,589 @@
-//! WebSocket streaming endpoint for real-time metrics
+//! WebSocket streaming endpoints for real-time metrics and anomalies
 
 use axum::extract::ws::{Message, WebSocket};
 use axum::{
-    extract::{Path, State, WebSocketUpgrade},
+    extract::{Path, Query, State, WebSocketUpgrade},
     response::Response,
 };
+use chrono::{DateTime, TimeDelta, Utc};
+use chrono_tz::Tz;
 use futures_util::{SinkExt, StreamExt};
-use tokio::sync::broadcast;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::Instant;
 use tracing::{info, warn};
 use uuid::Uuid;
 
+use crate::clock::Clock;
+use crate::db::{AggregatedMetric, MetricFilter};
+use crate::models::QueryMetric;
+use crate::routes::metrics::Metrics;
+use crate::services::notify::ANOMALY_EVENT;
 use crate::state::AppState;
+use crate::tasks::anomaly_detection::AnomalyEvent;
+
+/// How often each WebSocket client is pinged
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Consecutive unanswered pings after which a client is dropped
+const MAX_MISSED_PONGS: u32 = 2;
+
+/// Longest history a snapshot can cover
+const MAX_SNAPSHOT_MINUTES: i64 = 60;
+
+/// Most metrics a snapshot holds, the most recent kept
+const SNAPSHOT_LIMIT: i64 = 1000;
+
+/// Messages queued per connection while the client reads slowly
+const SEND_QUEUE_CAPACITY: usize = 1024;
+
+/// What a metric stream subscription receives
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamMode {
+    /// Every metric as it is ingested
+    #[default]
+    Metrics,
+    /// Per-service stats for each second with traffic
+    Aggregates,
+}
+
+/// Wire encoding of stream messages
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// JSON text frames
+    #[default]
+    Json,
+    /// CBOR binary frames holding the same fields as the JSON ones, with
+    /// UUIDs as 16-byte strings
+    Cbor,
+}
+
+impl Encoding {
+    /// Encode a message as a frame
+    fn encode<T: Serialize>(self, value: &T) -> Result<Message, String> {
+        match self {
+            Self::Json => serde_json::to_string(value)
+                .map(Message::Text)
+                .map_err(|e| e.to_string()),
+            Self::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(value, &mut bytes).map_err(|e| e.to_string())?;
+                Ok(Message::Binary(bytes))
+            }
+        }
+    }
+}
+
+/// Query parameters for the metric stream
+#[derive(Debug, Deserialize)]
+pub struct StreamQuery {
+    #[serde(default)]
+    pub mode: StreamMode,
+    /// Frame encoding (default: json)
+    #[serde(default)]
+    pub encoding: Encoding,
+    /// Minutes of history to send before live events (max: 60); switches
+    /// the connection to framed messages
+    pub snapshot_minutes: Option<i64>,
+}
+
+/// History sent when a framed subscription starts
+#[derive(Debug, Serialize)]
+pub struct Snapshot {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Metrics completed since `from`, oldest first (metrics mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<Vec<QueryMetric>>,
+    /// 1-minute aggregates per service since `from` (aggregates mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aggregates: Option<Vec<AggregatedMetric>>,
+}
+
+/// Message on a framed connection; `seq` counts the connection's messages
+/// from 0, so a jump means messages were lost on the way to the client
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Frame<'a, U> {
+    Snapshot {
+        seq: u64,
+        #[serde(flatten)]
+        snapshot: &'a Snapshot,
+    },
+    Event {
+        seq: u64,
+        data: &'a U,
+    },
+    /// `dropped` messages were skipped because the server fell behind the
+    /// broadcast (some of which may have been for other workspaces) or the
+    /// client fell behind the server
+    Gap {
+        seq: u64,
+        dropped: u64,
+    },
+}
+
+/// Message telling an unframed client how many messages it missed
+#[derive(Serialize)]
+struct DroppedNotice {
+    dropped: u64,
+}
 
 /// GET /api/v1/workspaces/:workspace_id/ws
 ///
 /// Upgrades connection to WebSocket for real-time metric streaming.
 /// Filters metrics to only those belonging to the specified workspace.
+///
+/// Query parameters:
+/// - mode: `metrics` (default) sends every metric; `aggregates` sends one
+///   message per second with traffic, holding each service's query count,
+///   p95 duration, and error rate, for workspaces too busy to stream raw
+/// - snapshot_minutes: Send that much history first (max: 60), so charts
+///   don't start empty. Messages are then framed as `{"type": "snapshot" |
+///   "event" | "gap", "seq": n, ...}`: the snapshot comes first, each live
+///   item is the `data` of an `event`, and a `gap` reports events the server
+///   skipped after falling behind.
+/// - encoding: `json` (default) for text frames; `cbor` for binary frames
+///   with the same fields, which are smaller and cheaper to decode than JSON
+///   for busy streams
 pub async fn ws_handler(
     State(state): State<AppState>,
     Path(workspace_id): Path<Uuid>,
+    Query(params): Query<StreamQuery>,
     ws: WebSocketUpgrade,
 ) -> Response {
-    ws.on_upgrade(move |socket| handle_socket(socket, state, workspace_id))
+    let metrics = Arc::clone(&state.metrics);
+    let mode = params.mode;
+    let encoding = params.encoding;
+    let max_dropped = state.ws_max_dropped;
+    let minutes = params
+        .snapshot_minutes
+        .map(|m| m.clamp(1, MAX_SNAPSHOT_MINUTES));
+    match mode {
+        StreamMode::Metrics => {
+            // Subscribe before reading the snapshot so nothing falls between
+            let rx = state.broadcast_tx.subscribe();
+            ws.on_upgrade(move |socket| async move {
+                let snapshot = match minutes {
+                    Some(m) => Some(load_snapshot(&state, workspace_id, mode, m).await),
+                    None => None,
+                };
+                handle_socket(
+                    socket,
+                    rx,
+                    workspace_id,
+                    "metrics",
+                    metrics,
+                    snapshot,
+                    encoding,
+                    max_dropped,
+                    move |(ws, metric)| (ws == workspace_id).then_some(metric),
+                )
+                .await
+            })
+        }
+        StreamMode::Aggregates => {
+            let rx = state.aggregate_tx.subscribe();
+            ws.on_upgrade(move |socket| async move {
+                let snapshot = match minutes {
+                    Some(m) => Some(load_snapshot(&state, workspace_id, mode, m).await),
+                    None => None,
+                };
+                handle_socket(
+                    socket,
+                    rx,
+                    workspace_id,
+                    "aggregates",
+                    metrics,
+                    snapshot,
+                    encoding,
+                    max_dropped,
+                    move |aggregate| (aggregate.workspace_id == workspace_id).then_some(aggregate),
+                )
+                .await
+            })
+        }
+    }
+}
+
+/// The workspace's last `minutes` of metrics or 1-minute aggregates.
+///
+/// Metrics come from the store plus the recently broadcast ones not yet
+/// flushed to it. A failed read is logged and leaves the snapshot empty
+/// rather than refusing the subscription.
+async fn load_snapshot(
+    state: &AppState,
+    workspace_id: Uuid,
+    mode: StreamMode,
+    minutes: i64,
+) -> Snapshot {
+    let to = state.clock.now();
+    let from = to - TimeDelta::minutes(minutes);
+    let filter = MetricFilter::default();
+
+    match mode {
+        StreamMode::Metrics => {
+            let mut metrics = state
+                .db
+                .get_recent_metrics(workspace_id, &filter, SNAPSHOT_LIMIT)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!(error = %e, workspace_id = %workspace_id, "Failed to read snapshot metrics");
+                    Vec::new()
+                });
+            let mut seen: HashSet<Uuid> = metrics.iter().map(|m| m.id).collect();
+            let unflushed = state.events.since(workspace_id, 0, usize::MAX).metrics;
+            metrics.extend(unflushed.into_iter().filter(|m| seen.insert(m.id)));
+            metrics.retain(|m| m.completed_at >= from);
+            metrics.sort_by_key(|m| m.completed_at);
+            let excess = metrics.len().saturating_sub(SNAPSHOT_LIMIT as usize);
+            metrics.drain(..excess);
+            Snapshot {
+                from,
+                to,
+                metrics: Some(metrics),
+                aggregates: None,
+            }
+        }
+        StreamMode::Aggregates => {
+            let aggregates = state
+                .db
+                .get_aggregations(workspace_id, "1m", Tz::UTC, &filter, from, to)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!(error = %e, workspace_id = %workspace_id, "Failed to read snapshot aggregates");
+                    Vec::new()
+                });
+            Snapshot {
+                from,
+                to,
+                metrics: None,
+                aggregates: Some(aggregates),
+            }
+        }
+    }
 }
 
-/// Handle WebSocket connection
-async fn handle_socket(socket: WebSocket, state: AppState, workspace_id: Uuid) {
-    info!(workspace_id = %workspace_id, "WebSocket client connected");
+/// GET /api/v1/workspaces/:workspace_id/anomalies/ws
+///
+/// Upgrades connection to WebSocket for real-time anomaly streaming: every
+/// anomaly detected in the workspace is sent as it is stored, as an
+/// `anomaly.detected` event wrapping the anomaly.
+pub async fn anomaly_ws_handler(
+    State(state): State<AppState>,
+    Path(workspace_id): Path<Uuid>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let rx = state.anomaly_tx.subscribe();
+    let metrics = Arc::clone(&state.metrics);
+    ws.on_upgrade(move |socket| {
+        handle_socket(
+            socket,
+            rx,
+            workspace_id,
+            "anomalies",
+            metrics,
+            None,
+            Encoding::Json,
+            state.ws_max_dropped,
+            move |anomaly| {
+                (anomaly.workspace_id == workspace_id).then_some(AnomalyEvent {
+                    event_type: ANOMALY_EVENT,
+                    anomaly,
+                })
+            },
+        )
+    })
+}
+
+/// Handle WebSocket connection, sending each broadcast item `select` keeps
+/// for the workspace as a message in `encoding`.
+///
+/// Messages go through a queue of `SEND_QUEUE_CAPACITY` to a writer task, so
+/// a slow client never holds up reading the broadcast channel. Messages that
+/// don't fit, and broadcast events skipped after falling behind, are counted
+/// as dropped and reported to the client before the next message that fits;
+/// once `max_dropped` (0 = no limit) go unreported, the client is
+/// disconnected.
+///
+/// Pings the client every `HEARTBEAT_INTERVAL` and drops the connection once
+/// `MAX_MISSED_PONGS` pings go unanswered; any message from the client counts
+/// as an answer. When any task ends, the others are stopped too, so a dead
+/// connection doesn't keep its broadcast receiver alive.
+///
+/// With a snapshot, it is sent first and every message is a [`Frame`].
+#[allow(clippy::too_many_arguments)]
+async fn handle_socket<T, U, F>(
+    socket: WebSocket,
+    mut broadcast_rx: broadcast::Receiver<T>,
+    workspace_id: Uuid,
+    stream: &'static str,
+    metrics: Arc<Metrics>,
+    snapshot: Option<Snapshot>,
+    encoding: Encoding,
+    max_dropped: u64,
+    select: F,
+) where
+    T: Clone + Send + 'static,
+    U: Serialize + Send,
+    F: Fn(T) -> Option<U> + Send + 'static,
+{
+    metrics.inc_ws_connections();
+    info!(workspace_id = %workspace_id, stream = stream, "WebSocket client connected");
 
     let (mut sender, mut receiver) = socket.split();
-    let mut broadcast_rx = state.broadcast_tx.subscribe();
+    let (queue_tx, mut queue_rx) = mpsc::channel::<Message>(SEND_QUEUE_CAPACITY);
+    // Pings sent since the client was last heard from
+    let unanswered = Arc::new(AtomicU32::new(0));
+
+    // Task to write queued messages to client
+    let mut write_task = tokio::spawn(async move {
+        while let Some(message) = queue_rx.recv().await {
+            if sender.send(message).await.is_err() {
+                // Client disconnected
+                break;
+            }
+        }
+    });
+
+    // Task to queue items and heartbeats for client
+    let send_unanswered = Arc::clone(&unanswered);
+    let mut send_task = tokio::spawn(async move {
+        let framed = snapshot.is_some();
+        let mut seq = 0;
+        // Messages dropped since the client was last told
+        let mut dropped: u64 = 0;
+
+        if let Some(snapshot) = &snapshot {
+            let message = {
+                let frame: Frame<'_, U> = Frame::Snapshot { seq, snapshot };
+                encoding.encode(&frame)
+            };
+            match message {
+                Ok(message) => {
+                    if queue_tx.send(message).await.is_err() {
+                        return;
+                    }
+                    seq += 1;
+                }
+                Err(e) => warn!(error = %e, stream = stream, "Failed to serialize snapshot"),
+            }
+        }
 
-    // Task to send metrics to client
-    let send_task = tokio::spawn(async move {
+        let mut heartbeat =
+            tokio::time::interval_at(Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
         loop {
-            match broadcast_rx.recv().await {
-                Ok((metric_workspace_id, metric)) => {
-                    // Only send metrics for this workspace
-                    if metric_workspace_id == workspace_id {
-                        let json = match serde_json::to_string(&metric) {
-                            Ok(j) => j,
-                            Err(e) => {
-                                warn!(error = %e, "Failed to serialize metric");
-                                continue;
-                            }
-                        };
-
-                        if sender.send(Message::Text(json)).await.is_err() {
-                            // Client disconnected
-                            break;
+            let item = tokio::select! {
+                _ = heartbeat.tick() => {
+                    if send_unanswered.load(Ordering::Relaxed) >= MAX_MISSED_PONGS {
+                        info!(
+                            workspace_id = %workspace_id,
+                            stream = stream,
+                            missed = MAX_MISSED_PONGS,
+                            "Dropping unresponsive WebSocket client"
+                        );
+                        break;
+                    }
+                    // A client too slow to drain its queue is still alive
+                    match queue_tx.try_send(Message::Ping(Vec::new())) {
+                        Ok(()) => {
+                            send_unanswered.fetch_add(1, Ordering::Relaxed);
                         }
+                        Err(mpsc::error::TrySendError::Full(_)) => {}
+                        Err(mpsc::error::TrySendError::Closed(_)) => break,
                     }
+                    continue;
                 }
-                Err(broadcast::error::RecvError::Lagged(count)) => {
-                    warn!(
-                        lagged = count,
-                        "Broadcast receiver lagged, some metrics dropped"
-                    );
+                received = broadcast_rx.recv() => match received {
+                    // Only send items for this workspace
+                    Ok(item) => match select(item) {
+                        Some(item) => item,
+                        None => continue,
+                    },
+                    Err(broadcast::error::RecvError::Lagged(count)) => {
+                        warn!(
+                            lagged = count,
+                            stream = stream,
+                            "Broadcast receiver lagged, some events dropped"
+                        );
+                        dropped += count;
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            };
+
+            // Room for the item, and first for the drop notice if one is due
+            let needed = if dropped > 0 { 2 } else { 1 };
+            if queue_tx.capacity() < needed {
+                if queue_tx.is_closed() {
+                    break;
                 }
-                Err(broadcast::error::RecvError::Closed) => {
+                // Its sequence number is skipped, so framed clients see a jump
+                seq += u64::from(framed);
+                dropped += 1;
+                if max_dropped > 0 && dropped >= max_dropped {
+                    info!(
+                        workspace_id = %workspace_id,
+                        stream = stream,
+                        dropped = dropped,
+                        "Disconnecting WebSocket client that can't keep up"
+                    );
                     break;
                 }
+                continue;
+            }
+
+            if dropped > 0 {
+                let notice = if framed {
+                    let gap: Frame<'_, U> = Frame::Gap { seq, dropped };
+                    encoding.encode(&gap)
+                } else {
+                    encoding.encode(&DroppedNotice { dropped })
+                };
+                match notice {
+                    Ok(notice) => {
+                        if queue_tx.try_send(notice).is_err() {
+                            break;
+                        }
+                        seq += u64::from(framed);
+                        dropped = 0;
+                    }
+                    Err(e) => warn!(error = %e, stream = stream, "Failed to serialize drop notice"),
+                }
+            }
+
+            let message = if framed {
+                encoding.encode(&Frame::Event { seq, data: &item })
+            } else {
+                encoding.encode(&item)
+            };
+            match message {
+                Ok(message) => {
+                    if queue_tx.try_send(message).is_err() {
+                        break;
+                    }
+                    seq += u64::from(framed);
+                }
+                Err(e) => warn!(error = %e, stream = stream, "Failed to serialize event"),
             }
         }
     });
 
-    // Task to receive pings/messages from client (keep-alive)
-    let recv_task = tokio::spawn(async move {
+    // Task to receive pongs/messages from client (keep-alive)
+    let mut recv_task = tokio::spawn(async move {
         while let Some(result) = receiver.next().await {
             match result {
                 Ok(Message::Close(_)) => break,
-                Ok(Message::Ping(data)) => {
-                    // Pong is handled automatically by axum
-                    let _ = data;
-                }
-                Ok(_) => {} // Ignore other messages
+                // Pongs to our pings, and pings (answered automatically by
+                // axum) or anything else, show the client is alive
+                Ok(_) => unanswered.store(0, Ordering::Relaxed),
                 Err(_) => break,
             }
         }
     });
 
-    // Wait for either task to complete
+    // Wait for any task to complete, then stop the others
     tokio::select! {
-        _ = send_task => {},
-        _ = recv_task => {},
+        _ = &mut send_task => {}
+        _ = &mut write_task => {}
+        _ = &mut recv_task => {}
     }
+    send_task.abort();
+    write_task.abort();
+    recv_task.abort();
 
-    info!(workspace_id = %workspace_id, "WebSocket client disconnected");
+    metrics.dec_ws_connections();
+    info!(workspace_id = %workspace_id, stream = stream, "WebSocket client disconnected");
 }
 
-/// Background task that broadcasts metrics from buffer to WebSocket clients.
+/// Background task that broadcasts each workspace's per-second stats once
+/// its second is over.
 ///
-/// Runs every 100ms, pops batches from buffer and broadcasts to all subscribers.
-pub async fn broadcast_task(state: AppState) {
-    let mut interval = tokio::time::interval(std::time::Duration::from_millis(100));
+/// Runs every 100ms. Metrics themselves are broadcast as they are ingested;
+/// the buffer is left to the flush task.
+pub async fn broadcast_task(state: AppState, clock: Clock) {
+    let mut interval = clock.interval(Duration::from_millis(100));
 
     loop {
         interval.tick().await;
 
-        let batch = state.metrics_buffer.pop_batch(1000);
-        if batch.is_empty() {
-            continue;
-        }
-
-        for metric in batch {
-            let workspace_id = metric.workspace_id;
+        let finished = state.second_rollups.lock().tick(clock.now());
+        for aggregate in finished {
             // Ignore send errors (no receivers connected)
-            let _ = state.broadcast_tx.send((workspace_id, metric));
+            let _ = state.aggregate_tx.send(aggregate);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_frames_are_tagged_and_sequenced() {
+        let at = "2026-01-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let snapshot = Snapshot {
+            from: at,
+            to: at,
+            metrics: Some(Vec::new()),
+            aggregates: None,
+        };
+        let frame: Frame<'_, ()> = Frame::Snapshot {
+            seq: 0,
+            snapshot: &snapshot,
+        };
+        assert_eq!(
+            serde_json::to_value(&frame).unwrap(),
+            json!({
+                "type": "snapshot",
+                "seq": 0,
+                "from": "2026-01-01T12:00:00Z",
+                "to": "2026-01-01T12:00:00Z",
+                "metrics": []
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(Frame::Event { seq: 1, data: &7 }).unwrap(),
+            json!({"type": "event", "seq": 1, "data": 7})
+        );
+        let gap: Frame<'_, ()> = Frame::Gap {
+            seq: 2,
+            dropped: 40,
+        };
+        assert_eq!(
+            serde_json::to_value(&gap).unwrap(),
+            json!({"type": "gap", "seq": 2, "dropped": 40})
+        );
+    }
+
+    #[test]
+    fn test_cbor_frames_hold_the_json_fields() {
+        let metric = QueryMetric::new(
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            "SELECT * FROM users WHERE id = $1".into(),
+            crate::models::QueryStatus::Success,
+            12,
+            Utc::now(),
+        );
+        let Ok(Message::Binary(bytes)) = Encoding::Cbor.encode(&metric) else {
+            panic!("expected a binary frame");
+        };
+        let decoded: QueryMetric = ciborium::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(decoded.id, metric.id);
+        assert_eq!(decoded.query_text, metric.query_text);
+        assert_eq!(decoded.completed_at, metric.completed_at);
+        assert!(matches!(
+            Encoding::Json.encode(&metric),
+            Ok(Message::Text(_))
+        ));
+    }
+}
@@ -0,0 +1,261 @@
+//! Alert rule evaluation
+//!
+//! A rule combines conditions over aggregate signals (p95, error rate,
+//! volume, ...) with `all` or `any`, and is evaluated once per aggregation
+//! window. Two kinds of hysteresis keep it from flapping:
+//!
+//! - in time: it fires after `for_windows` consecutive breaching windows and
+//!   resolves after `resolve_windows` consecutive clear windows
+//! - in value: while firing, a condition only clears once its signal is back
+//!   past its `clear_threshold`, not merely back under the threshold
+
+use chrono::{DateTime, Utc};
+
+use crate::db::AggregatedMetric;
+use crate::models::{AlertCombine, AlertComparison, AlertCondition, AlertRule, AlertSignal};
+
+/// Width of an alert window in seconds
+pub fn window_seconds(window: &str) -> Option<i64> {
+    match window {
+        "1m" => Some(60),
+        "5m" => Some(300),
+        _ => None,
+    }
+}
+
+/// Start of the newest bucket that closed at least one window before `now`,
+/// leaving the continuous aggregates time to refresh it
+pub fn evaluation_bucket(now: DateTime<Utc>, width_secs: i64) -> Option<DateTime<Utc>> {
+    let ts = now.timestamp();
+    DateTime::from_timestamp(ts - ts.rem_euclid(width_secs) - 2 * width_secs, 0)
+}
+
+/// Value of a signal over one window's buckets (one per service)
+///
+/// Counts are summed and the average is weighted by query count. Across
+/// several services, percentiles and the maximum take the highest service's
+/// value. A window without queries has a count of zero and no other value.
+pub fn signal_value(signal: AlertSignal, buckets: &[AggregatedMetric]) -> Option<f64> {
+    let queries: i64 = buckets.iter().map(|b| b.query_count).sum();
+    let errors: i64 = buckets.iter().filter_map(|b| b.failed_count).sum();
+    let highest = |get: fn(&AggregatedMetric) -> Option<i64>| {
+        buckets.iter().filter_map(get).max().map(|v| v as f64)
+    };
+
+    match signal {
+        AlertSignal::QueryCount => Some(queries as f64),
+        AlertSignal::ErrorCount => Some(errors as f64),
+        AlertSignal::ErrorRate => (queries > 0).then(|| errors as f64 / queries as f64),
+        AlertSignal::AvgDurationMs => {
+            let (total, count) = buckets
+                .iter()
+                .filter_map(|b| {
+                    b.avg_duration_ms
+                        .map(|avg| (avg * b.query_count, b.query_count))
+                })
+                .fold((0, 0), |(t, c), (bt, bc)| (t + bt, c + bc));
+            (count > 0).then(|| total as f64 / count as f64)
+        }
+        AlertSignal::P95DurationMs => highest(|b| b.p95_duration_ms),
+        AlertSignal::P99DurationMs => highest(|b| b.p99_duration_ms),
+        AlertSignal::MaxDurationMs => highest(|b| b.max_duration_ms),
+    }
+}
+
+/// Whether a value breaches a condition's threshold
+pub fn is_breached(condition: &AlertCondition, value: f64) -> bool {
+    match condition.comparison {
+        AlertComparison::Gt => value > condition.threshold,
+        AlertComparison::Lt => value < condition.threshold,
+    }
+}
+
+/// Whether a value is back past a condition's clear threshold
+pub fn is_cleared(condition: &AlertCondition, value: f64) -> bool {
+    let clear = condition.clear_threshold.unwrap_or(condition.threshold);
+    match condition.comparison {
+        AlertComparison::Gt => value <= clear,
+        AlertComparison::Lt => value >= clear,
+    }
+}
+
+/// A rule firing or resolving
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Fired,
+    Resolved,
+}
+
+/// Evaluation state of one rule
+#[derive(Debug, Clone, Default)]
+pub struct RuleState {
+    pub firing: bool,
+    /// Consecutive breaching windows while not firing
+    pub breaches: u32,
+    /// Consecutive clear windows while firing
+    pub clears: u32,
+    /// Start of the last window evaluated
+    pub last_bucket: Option<DateTime<Utc>>,
+}
+
+impl RuleState {
+    /// Feed one window's signal values, one per condition (`None` = no
+    /// data), and return the transition if the rule fires or resolves
+    pub fn observe(&mut self, rule: &AlertRule, values: &[Option<f64>]) -> Option<Transition> {
+        if self.firing {
+            // Missing data counts as clear
+            let active = combine(rule, values, |c, v| !is_cleared(c, v));
+            if active {
+                self.clears = 0;
+                return None;
+            }
+            self.clears += 1;
+            if self.clears < rule.resolve_windows {
+                return None;
+            }
+            self.firing = false;
+            self.clears = 0;
+            Some(Transition::Resolved)
+        } else {
+            if !combine(rule, values, is_breached) {
+                self.breaches = 0;
+                return None;
+            }
+            self.breaches += 1;
+            if self.breaches < rule.for_windows {
+                return None;
+            }
+            self.firing = true;
+            self.breaches = 0;
+            Some(Transition::Fired)
+        }
+    }
+
+    /// Whether a window without queries would leave this state as it is,
+    /// so the rule needn't be evaluated while its workspace is idle
+    ///
+    /// Never true while firing or counting breaches, or for rules a quiet
+    /// window breaches, such as a traffic drop.
+    pub fn unchanged_by_idle_window(&self, rule: &AlertRule) -> bool {
+        let values: Vec<Option<f64>> = rule
+            .conditions
+            .iter()
+            .map(|c| signal_value(c.signal, &[]))
+            .collect();
+        !self.firing && self.breaches == 0 && !combine(rule, &values, is_breached)
+    }
+}
+
+/// Combine a per-condition check with the rule's `all`/`any`; a condition
+/// without data never passes
+fn combine(
+    rule: &AlertRule,
+    values: &[Option<f64>],
+    check: impl Fn(&AlertCondition, f64) -> bool,
+) -> bool {
+    let mut passed = rule
+        .conditions
+        .iter()
+        .zip(values)
+        .map(|(c, v)| v.is_some_and(|v| check(c, v)));
+    match rule.combine {
+        AlertCombine::All => passed.all(|p| p),
+        AlertCombine::Any => passed.any(|p| p),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn rule() -> AlertRule {
+        // p95 > 500ms AND error rate > 5% for 3 windows; p95 must drop to
+        // 400ms to clear, for 2 windows
+        AlertRule {
+            id: Uuid::new_v4(),
+            workspace_id: Uuid::new_v4(),
+            name: "checkout degraded".into(),
+            service_id: None,
+            window: "1m".into(),
+            conditions: vec![
+                AlertCondition {
+                    signal: AlertSignal::P95DurationMs,
+                    comparison: AlertComparison::Gt,
+                    threshold: 500.0,
+                    clear_threshold: Some(400.0),
+                },
+                AlertCondition {
+                    signal: AlertSignal::ErrorRate,
+                    comparison: AlertComparison::Gt,
+                    threshold: 0.05,
+                    clear_threshold: None,
+                },
+            ],
+            combine: AlertCombine::All,
+            for_windows: 3,
+            resolve_windows: 2,
+            enabled: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_composite_rule_with_hysteresis() {
+        let rule = rule();
+        let mut state = RuleState::default();
+        let bad = [Some(800.0), Some(0.2)];
+
+        // One condition alone never fires an `all` rule
+        for _ in 0..5 {
+            assert_eq!(state.observe(&rule, &[Some(800.0), Some(0.01)]), None);
+        }
+        // A good window resets the streak
+        assert_eq!(state.observe(&rule, &bad), None);
+        assert_eq!(state.observe(&rule, &bad), None);
+        assert_eq!(state.observe(&rule, &[Some(100.0), Some(0.2)]), None);
+        assert_eq!(state.observe(&rule, &bad), None);
+        assert_eq!(state.observe(&rule, &bad), None);
+        assert_eq!(state.observe(&rule, &bad), Some(Transition::Fired));
+
+        // p95 hovering between the clear threshold and the threshold stays firing
+        for _ in 0..5 {
+            assert_eq!(state.observe(&rule, &[Some(450.0), Some(0.2)]), None);
+        }
+        assert_eq!(state.observe(&rule, &[Some(350.0), Some(0.2)]), None);
+        assert_eq!(
+            state.observe(&rule, &[Some(350.0), Some(0.2)]),
+            Some(Transition::Resolved)
+        );
+        assert!(!state.firing);
+    }
+
+    #[test]
+    fn test_idle_window_only_skipped_when_it_changes_nothing() {
+        let mut rule = rule();
+        let mut state = RuleState::default();
+        assert!(state.unchanged_by_idle_window(&rule));
+        state.breaches = 1;
+        assert!(!state.unchanged_by_idle_window(&rule));
+
+        // A quiet window is itself a traffic drop
+        rule.conditions = vec![AlertCondition {
+            signal: AlertSignal::QueryCount,
+            comparison: AlertComparison::Lt,
+            threshold: 10.0,
+            clear_threshold: None,
+        }];
+        assert!(!RuleState::default().unchanged_by_idle_window(&rule));
+    }
+
+    #[test]
+    fn test_evaluation_bucket() {
+        let now = DateTime::from_timestamp(1_700_000_230, 0).unwrap();
+        // 130s past a 5m boundary: the bucket two windows back
+        assert_eq!(
+            evaluation_bucket(now, 300),
+            DateTime::from_timestamp(1_700_000_100 - 600, 0)
+        );
+    }
+}
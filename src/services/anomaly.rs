@@ -0,0 +1,171 @@
+//! Anomaly detection settings
+//!
+//! The anomaly detection task's thresholds, interval, sample sizes, and
+//! seasonality start from server-wide defaults (the `ANOMALY_*` variables),
+//! which a workspace can override one at a time. Both the defaults and every
+//! workspace's settings after its overrides go through [`check_settings`].
+//!
+//! With seasonality on, durations are also grouped into the UTC hour of the
+//! day or of the week they were recorded in, and a query is judged against
+//! the baseline of the slot it ran in, so a nightly batch window that is
+//! always slow isn't flagged every night.
+
+use crate::error::{AppError, Result};
+use crate::models::{AnomalySettings, Seasonality};
+use chrono::{DateTime, Datelike, TimeDelta, Timelike, Utc};
+
+/// Shortest interval between detection runs, in seconds; the task visits
+/// every workspace this often
+pub const MIN_INTERVAL_SECS: i32 = 60;
+
+/// Longest interval between detection runs, in seconds
+pub const MAX_INTERVAL_SECS: i32 = 3600;
+
+/// Most metrics the statistics can be computed over
+pub const MAX_LOOKBACK: i32 = 100_000;
+
+/// Highest threshold, past which nothing would ever be flagged
+pub const MAX_THRESHOLD: f64 = 100.0;
+
+/// Slot of `seasonality` that `at` falls in: the hour of the day, or the
+/// hour of the week counted from Monday 00:00 UTC (`None` without
+/// seasonality)
+pub fn seasonal_slot(seasonality: Seasonality, at: DateTime<Utc>) -> Option<i16> {
+    let hour = at.hour() as i16;
+    match seasonality {
+        Seasonality::None => None,
+        Seasonality::Daily => Some(hour),
+        Seasonality::Weekly => Some(at.weekday().num_days_from_monday() as i16 * 24 + hour),
+    }
+}
+
+/// How far back a seasonality's baselines reach: two weeks give each hour
+/// of the day 14 days of samples, four each hour of the week 4
+pub fn baseline_window(seasonality: Seasonality) -> TimeDelta {
+    match seasonality {
+        Seasonality::None => TimeDelta::zero(),
+        Seasonality::Daily => TimeDelta::days(14),
+        Seasonality::Weekly => TimeDelta::days(28),
+    }
+}
+
+/// The first setting out of range, as the field's name and what's wrong
+/// with it
+pub fn check_settings(settings: &AnomalySettings) -> Option<(&'static str, String)> {
+    let thresholds = [
+        ("z_threshold", settings.z_threshold),
+        ("mad_threshold", settings.mad_threshold),
+    ];
+    for (field, threshold) in thresholds {
+        if !(threshold > 0.0 && threshold <= MAX_THRESHOLD) {
+            return Some((
+                field,
+                format!("must be above 0 and at most {}", MAX_THRESHOLD),
+            ));
+        }
+    }
+    if !(MIN_INTERVAL_SECS..=MAX_INTERVAL_SECS).contains(&settings.interval_secs) {
+        return Some((
+            "interval_secs",
+            format!(
+                "must be between {} and {}",
+                MIN_INTERVAL_SECS, MAX_INTERVAL_SECS
+            ),
+        ));
+    }
+    if !(2..=MAX_LOOKBACK).contains(&settings.lookback) {
+        return Some((
+            "lookback",
+            format!("must be between 2 and {}", MAX_LOOKBACK),
+        ));
+    }
+    // A standard deviation needs two durations
+    if !(2..=settings.lookback).contains(&settings.min_samples) {
+        return Some((
+            "min_samples",
+            format!("must be between 2 and the lookback ({})", settings.lookback),
+        ));
+    }
+    None
+}
+
+/// Reject a workspace's settings if any is out of range
+pub fn validate_settings(settings: &AnomalySettings) -> Result<()> {
+    match check_settings(settings) {
+        Some((field, problem)) => Err(AppError::InvalidRequest(format!("'{}' {}", field, problem))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seasonal_slots() {
+        // Wednesday
+        let at: DateTime<Utc> = "2026-01-07T02:30:00Z".parse().unwrap();
+        assert_eq!(seasonal_slot(Seasonality::None, at), None);
+        assert_eq!(seasonal_slot(Seasonality::Daily, at), Some(2));
+        assert_eq!(seasonal_slot(Seasonality::Weekly, at), Some(2 * 24 + 2));
+
+        // Sunday's last hour is the week's last slot
+        let at: DateTime<Utc> = "2026-01-11T23:59:59Z".parse().unwrap();
+        assert_eq!(seasonal_slot(Seasonality::Weekly, at), Some(167));
+    }
+
+    #[test]
+    fn test_defaults_are_valid() {
+        assert!(validate_settings(&AnomalySettings::default()).is_ok());
+    }
+
+    #[test]
+    fn test_out_of_range_settings_rejected() {
+        let defaults = AnomalySettings::default();
+        let field = |settings: AnomalySettings| check_settings(&settings).map(|(f, _)| f);
+
+        assert_eq!(
+            field(AnomalySettings {
+                z_threshold: 0.0,
+                ..defaults
+            }),
+            Some("z_threshold")
+        );
+        assert_eq!(
+            field(AnomalySettings {
+                mad_threshold: f64::NAN,
+                ..defaults
+            }),
+            Some("mad_threshold")
+        );
+        assert_eq!(
+            field(AnomalySettings {
+                interval_secs: 30,
+                ..defaults
+            }),
+            Some("interval_secs")
+        );
+        assert_eq!(
+            field(AnomalySettings {
+                lookback: MAX_LOOKBACK + 1,
+                ..defaults
+            }),
+            Some("lookback")
+        );
+
+        // The minimum can't be more than the lookback provides
+        assert_eq!(
+            field(AnomalySettings {
+                lookback: 50,
+                ..defaults
+            }),
+            Some("min_samples")
+        );
+        assert!(validate_settings(&AnomalySettings {
+            lookback: 50,
+            min_samples: 50,
+            ..defaults
+        })
+        .is_ok());
+    }
+}
@@ -0,0 +1,344 @@
+//! Timezone-aligned aggregation buckets and gap filling
+//!
+//! Buckets start at multiples of the window in the caller's local time, like
+//! TimescaleDB's `time_bucket(width, ts, timezone)`: a "1d" bucket starts at
+//! local midnight and runs 23 to 25 hours across DST changes. Sub-day windows
+//! keep the offset of the instant being bucketed, so the repeated hour after
+//! clocks go back gets its own buckets.
+//!
+//! Gap filling adds a bucket for every period without queries, per service,
+//! so charts don't have to synthesize them. Empty buckets always count zero
+//! queries; the fill mode decides what their duration statistics show.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, DurationRound, Offset, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::db::AggregatedMetric;
+
+/// Most buckets one gap-filled response may hold per service
+pub const MAX_FILLED_BUCKETS: usize = 10_000;
+
+/// What empty buckets show for duration statistics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fill {
+    /// Zero
+    Zero,
+    /// No value
+    Null,
+    /// The previous non-empty bucket's values, or no value before the first
+    Previous,
+}
+
+/// Width of an aggregation window's buckets
+pub fn window_width(window: &str) -> Option<TimeDelta> {
+    match window {
+        "5s" => Some(TimeDelta::seconds(5)),
+        "1m" => Some(TimeDelta::minutes(1)),
+        "5m" => Some(TimeDelta::minutes(5)),
+        "1h" => Some(TimeDelta::hours(1)),
+        "1d" => Some(TimeDelta::days(1)),
+        _ => None,
+    }
+}
+
+/// Start of the bucket containing `at`
+pub fn bucket_start(at: DateTime<Utc>, width: TimeDelta, tz: Tz) -> DateTime<Utc> {
+    let local = at.with_timezone(&tz);
+    let offset = TimeDelta::seconds(local.offset().fix().local_minus_utc() as i64);
+    let Ok(start) = local.naive_local().duration_trunc(width) else {
+        return at;
+    };
+    // Prefer the instant's own offset, which keeps a bucket in the repeated
+    // hour from starting an hour early
+    let same_offset = Utc.from_utc_datetime(&(start - offset));
+    if same_offset.with_timezone(&tz).naive_local() == start {
+        return same_offset;
+    }
+    tz.from_local_datetime(&start)
+        .earliest()
+        .map(|start| start.with_timezone(&Utc))
+        .unwrap_or(same_offset)
+}
+
+/// Start of the bucket after the one starting at `start`
+pub fn next_bucket(start: DateTime<Utc>, width: TimeDelta, tz: Tz) -> DateTime<Utc> {
+    // Half a width into the next bucket is inside it however long DST makes
+    // either bucket
+    let next = bucket_start(start + width + width / 2, width, tz);
+    if next > start {
+        next
+    } else {
+        start + width
+    }
+}
+
+/// Round up to the next bucket boundary
+pub fn ceil_bucket(at: DateTime<Utc>, width: TimeDelta, tz: Tz) -> DateTime<Utc> {
+    let start = bucket_start(at, width, tz);
+    if start == at {
+        at
+    } else {
+        next_bucket(start, width, tz)
+    }
+}
+
+/// Whether buckets in `tz` over the range start where UTC buckets do, so the
+/// continuous aggregates can serve them
+pub fn utc_aligned(width: TimeDelta, tz: Tz, from: DateTime<Utc>, to: DateTime<Utc>) -> bool {
+    [from, to].iter().all(|at| {
+        let offset = at.with_timezone(&tz).offset().fix().local_minus_utc() as i64;
+        offset % width.num_seconds() == 0
+    })
+}
+
+/// Whether `tz` stays a whole number of hours from UTC over the range, so
+/// its buckets of an hour or longer are made of whole UTC hours
+///
+/// Offsets are checked once a day, which catches every DST period since
+/// none is that short.
+pub fn whole_hour_offsets(tz: Tz, from: DateTime<Utc>, to: DateTime<Utc>) -> bool {
+    let whole_hours =
+        |at: DateTime<Utc>| at.with_timezone(&tz).offset().fix().local_minus_utc() % 3600 == 0;
+    let mut at = from;
+    while at < to {
+        if !whole_hours(at) {
+            return false;
+        }
+        at += TimeDelta::days(1);
+    }
+    whole_hours(to)
+}
+
+/// Starts of the buckets in `[from, to)`, or `None` past `limit`
+pub fn bucket_starts(
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    width: TimeDelta,
+    tz: Tz,
+    limit: usize,
+) -> Option<Vec<DateTime<Utc>>> {
+    let mut starts = Vec::new();
+    let mut at = ceil_bucket(from, width, tz);
+    while at < to {
+        if starts.len() == limit {
+            return None;
+        }
+        starts.push(at);
+        at = next_bucket(at, width, tz);
+    }
+    Some(starts)
+}
+
+/// Add an empty bucket at each of `starts` missing from a service's series
+///
+/// Every service in `buckets` is filled, plus `service_id` when the caller
+/// filtered by one, so a service that was idle for the whole range still
+/// gets a series.
+pub fn fill_gaps(
+    buckets: Vec<AggregatedMetric>,
+    starts: &[DateTime<Utc>],
+    fill: Fill,
+    workspace_id: Uuid,
+    service_id: Option<Uuid>,
+) -> Vec<AggregatedMetric> {
+    let mut services: BTreeSet<Uuid> = buckets.iter().map(|b| b.service_id).collect();
+    services.extend(service_id);
+
+    let mut series: BTreeMap<(Uuid, DateTime<Utc>), Option<AggregatedMetric>> = buckets
+        .into_iter()
+        .map(|b| ((b.service_id, b.bucket), Some(b)))
+        .collect();
+    for &service in &services {
+        for &start in starts {
+            series.entry((service, start)).or_insert(None);
+        }
+    }
+
+    let mut filled = Vec::with_capacity(series.len());
+    let mut previous: Option<AggregatedMetric> = None;
+    for ((service, bucket), metric) in series {
+        if previous.as_ref().is_some_and(|p| p.service_id != service) {
+            previous = None;
+        }
+        match metric {
+            Some(metric) => {
+                previous = Some(metric.clone());
+                filled.push(metric);
+            }
+            None => filled.push(empty_bucket(
+                workspace_id,
+                service,
+                bucket,
+                fill,
+                previous.as_ref(),
+            )),
+        }
+    }
+    filled.sort_by_key(|b| (b.bucket, b.service_id));
+    filled
+}
+
+fn empty_bucket(
+    workspace_id: Uuid,
+    service_id: Uuid,
+    bucket: DateTime<Utc>,
+    fill: Fill,
+    previous: Option<&AggregatedMetric>,
+) -> AggregatedMetric {
+    let duration = |value: fn(&AggregatedMetric) -> Option<i64>| match fill {
+        Fill::Zero => Some(0),
+        Fill::Null => None,
+        Fill::Previous => previous.and_then(value),
+    };
+    AggregatedMetric {
+        workspace_id,
+        service_id,
+        bucket,
+        query_count: 0,
+        avg_duration_ms: duration(|b| b.avg_duration_ms),
+        min_duration_ms: duration(|b| b.min_duration_ms),
+        max_duration_ms: duration(|b| b.max_duration_ms),
+        p95_duration_ms: duration(|b| b.p95_duration_ms),
+        p99_duration_ms: duration(|b| b.p99_duration_ms),
+        success_count: Some(0),
+        failed_count: Some(0),
+        total_rows_affected: Some(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_days_follow_local_midnight_across_dst() {
+        let tz: Tz = "America/New_York".parse().unwrap();
+        let day = TimeDelta::days(1);
+        assert_eq!(
+            bucket_start(at("2026-03-08T12:00:00Z"), day, tz),
+            at("2026-03-08T05:00:00Z")
+        );
+        // The 23-hour day clocks spring forward, then the 25-hour one they
+        // fall back
+        assert_eq!(
+            bucket_starts(
+                at("2026-03-07T12:00:00Z"),
+                at("2026-03-10T00:00:00Z"),
+                day,
+                tz,
+                10
+            )
+            .unwrap(),
+            [at("2026-03-08T05:00:00Z"), at("2026-03-09T04:00:00Z")]
+        );
+        assert_eq!(
+            next_bucket(at("2026-11-01T04:00:00Z"), day, tz),
+            at("2026-11-02T05:00:00Z")
+        );
+        assert!(bucket_starts(
+            at("2026-01-01T00:00:00Z"),
+            at("2027-01-01T00:00:00Z"),
+            day,
+            tz,
+            300
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_hours_in_half_hour_offsets() {
+        let tz: Tz = "Asia/Kolkata".parse().unwrap();
+        let hour = TimeDelta::hours(1);
+        assert_eq!(
+            bucket_start(at("2026-01-01T10:10:00Z"), hour, tz),
+            at("2026-01-01T09:30:00Z")
+        );
+        assert!(!utc_aligned(
+            hour,
+            tz,
+            at("2026-01-01T00:00:00Z"),
+            at("2026-01-02T00:00:00Z")
+        ));
+        assert!(utc_aligned(
+            TimeDelta::minutes(5),
+            tz,
+            at("2026-01-01T00:00:00Z"),
+            at("2026-01-02T00:00:00Z")
+        ));
+        assert!(utc_aligned(
+            TimeDelta::days(1),
+            Tz::UTC,
+            at("2026-01-01T00:00:00Z"),
+            at("2026-01-02T00:00:00Z")
+        ));
+    }
+
+    #[test]
+    fn test_whole_hour_offsets() {
+        let range = (at("2026-03-01T00:00:00Z"), at("2026-12-01T00:00:00Z"));
+        assert!(whole_hour_offsets(
+            "America/New_York".parse().unwrap(),
+            range.0,
+            range.1
+        ));
+        assert!(!whole_hour_offsets(
+            "Asia/Kolkata".parse().unwrap(),
+            range.0,
+            range.1
+        ));
+        // Lord Howe Island is +11 in summer and +10:30 in winter, so only
+        // the middle of the range is off the hour
+        let lord_howe: Tz = "Australia/Lord_Howe".parse().unwrap();
+        assert!(!whole_hour_offsets(lord_howe, range.0, range.1));
+        assert!(whole_hour_offsets(
+            lord_howe,
+            range.0,
+            at("2026-03-20T00:00:00Z")
+        ));
+    }
+
+    #[test]
+    fn test_fill_gaps_per_service() {
+        let ws = Uuid::new_v4();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let starts: Vec<_> = (0..3)
+            .map(|m| at("2026-01-01T00:00:00Z") + TimeDelta::minutes(m))
+            .collect();
+        let bucket = AggregatedMetric {
+            workspace_id: ws,
+            service_id: a,
+            bucket: starts[0],
+            query_count: 4,
+            avg_duration_ms: Some(20),
+            min_duration_ms: Some(10),
+            max_duration_ms: Some(30),
+            p95_duration_ms: Some(29),
+            p99_duration_ms: Some(30),
+            success_count: Some(4),
+            failed_count: Some(0),
+            total_rows_affected: Some(8),
+        };
+
+        let filled = fill_gaps(vec![bucket.clone()], &starts, Fill::Previous, ws, Some(b));
+        assert_eq!(filled.len(), 6);
+        let series = |service: Uuid| -> Vec<_> {
+            filled.iter().filter(|m| m.service_id == service).collect()
+        };
+        assert_eq!(series(a)[2].query_count, 0);
+        assert_eq!(series(a)[2].p95_duration_ms, Some(29));
+        assert!(series(b).iter().all(|m| m.avg_duration_ms.is_none()));
+
+        let filled = fill_gaps(vec![bucket], &starts, Fill::Zero, ws, None);
+        assert_eq!(filled.len(), 3);
+        assert_eq!(filled[1].max_duration_ms, Some(0));
+    }
+}
@@ -0,0 +1,183 @@
+//! Period-over-period comparison of aggregation buckets
+//!
+//! A range is compared either with the range of the same length just before
+//! it, or with the same range a week earlier. The week is taken in the
+//! caller's local time, so Monday 09:00 lines up with the previous Monday
+//! 09:00 even when DST changed in between.
+
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::db::AggregatedMetric;
+
+/// Which range to compare against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compare {
+    /// The range of the same length ending where the requested one starts
+    PreviousPeriod,
+    /// The requested range one week earlier
+    PreviousWeek,
+}
+
+impl Compare {
+    /// The range compared against `[from, to)`
+    pub fn shift(
+        self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        tz: Tz,
+    ) -> (DateTime<Utc>, DateTime<Utc>) {
+        match self {
+            Compare::PreviousPeriod => (from - (to - from), from),
+            Compare::PreviousWeek => (week_before(from, tz), week_before(to, tz)),
+        }
+    }
+}
+
+/// The same local time seven days earlier, or exactly 168 hours earlier if
+/// that local time was skipped by DST
+fn week_before(at: DateTime<Utc>, tz: Tz) -> DateTime<Utc> {
+    let local = at.with_timezone(&tz).naive_local() - TimeDelta::days(7);
+    tz.from_local_datetime(&local)
+        .earliest()
+        .map(|at| at.with_timezone(&Utc))
+        .unwrap_or(at - TimeDelta::days(7))
+}
+
+/// Summary of one range's buckets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct PeriodTotals {
+    pub query_count: i64,
+    pub failed_count: i64,
+    /// Mean query duration, weighting each bucket by its query count
+    pub avg_duration_ms: Option<f64>,
+    pub max_duration_ms: Option<i64>,
+}
+
+impl PeriodTotals {
+    pub fn of(buckets: &[AggregatedMetric]) -> Self {
+        let mut totals = PeriodTotals::default();
+        let (mut weighted_ms, mut weighted_count) = (0.0, 0i64);
+        for bucket in buckets {
+            totals.query_count += bucket.query_count;
+            totals.failed_count += bucket.failed_count.unwrap_or(0);
+            if let Some(avg) = bucket.avg_duration_ms {
+                weighted_ms += avg as f64 * bucket.query_count as f64;
+                weighted_count += bucket.query_count;
+            }
+            totals.max_duration_ms = totals.max_duration_ms.max(bucket.max_duration_ms);
+        }
+        totals.avg_duration_ms = (weighted_count > 0).then(|| weighted_ms / weighted_count as f64);
+        totals
+    }
+}
+
+/// Requested range minus the comparison range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct PeriodDeltas {
+    pub query_count: i64,
+    /// Change relative to the comparison range, in percent; missing when the
+    /// comparison range had no queries
+    pub query_count_pct: Option<f64>,
+    pub failed_count: i64,
+    pub avg_duration_ms: Option<f64>,
+    /// Change relative to the comparison range, in percent
+    pub avg_duration_pct: Option<f64>,
+    pub max_duration_ms: Option<i64>,
+}
+
+impl PeriodDeltas {
+    pub fn between(current: &PeriodTotals, previous: &PeriodTotals) -> Self {
+        let pct = |current: f64, previous: f64| {
+            (previous != 0.0).then(|| (current - previous) / previous * 100.0)
+        };
+        let both = |current: Option<f64>, previous: Option<f64>| current.zip(previous);
+        PeriodDeltas {
+            query_count: current.query_count - previous.query_count,
+            query_count_pct: pct(current.query_count as f64, previous.query_count as f64),
+            failed_count: current.failed_count - previous.failed_count,
+            avg_duration_ms: both(current.avg_duration_ms, previous.avg_duration_ms)
+                .map(|(current, previous)| current - previous),
+            avg_duration_pct: both(current.avg_duration_ms, previous.avg_duration_ms)
+                .and_then(|(current, previous)| pct(current, previous)),
+            max_duration_ms: current
+                .max_duration_ms
+                .zip(previous.max_duration_ms)
+                .map(|(current, previous)| current - previous),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_shift_ranges() {
+        let (from, to) = (at("2026-03-09T13:00:00Z"), at("2026-03-09T15:00:00Z"));
+        assert_eq!(
+            Compare::PreviousPeriod.shift(from, to, Tz::UTC),
+            (at("2026-03-09T11:00:00Z"), from)
+        );
+        // New York moved to daylight time on March 8, so 09:00 local a week
+        // earlier was 14:00 UTC
+        let tz: Tz = "America/New_York".parse().unwrap();
+        assert_eq!(
+            Compare::PreviousWeek.shift(from, to, tz),
+            (at("2026-03-02T14:00:00Z"), at("2026-03-02T16:00:00Z"))
+        );
+        assert_eq!(
+            Compare::PreviousWeek.shift(from, to, Tz::UTC).0,
+            at("2026-03-02T13:00:00Z")
+        );
+    }
+
+    #[test]
+    fn test_totals_and_deltas() {
+        let bucket = |query_count: i64, avg: Option<i64>, max: Option<i64>| AggregatedMetric {
+            workspace_id: Uuid::nil(),
+            service_id: Uuid::nil(),
+            bucket: at("2026-01-01T00:00:00Z"),
+            query_count,
+            avg_duration_ms: avg,
+            min_duration_ms: None,
+            max_duration_ms: max,
+            p95_duration_ms: None,
+            p99_duration_ms: None,
+            success_count: Some(query_count - 1),
+            failed_count: Some(1),
+            total_rows_affected: None,
+        };
+        let current =
+            PeriodTotals::of(&[bucket(1, Some(40), Some(40)), bucket(3, Some(20), Some(90))]);
+        assert_eq!(
+            current,
+            PeriodTotals {
+                query_count: 4,
+                failed_count: 2,
+                avg_duration_ms: Some(25.0),
+                max_duration_ms: Some(90),
+            }
+        );
+
+        let previous = PeriodTotals::of(&[bucket(2, Some(50), Some(60)), bucket(0, None, None)]);
+        let deltas = PeriodDeltas::between(&current, &previous);
+        assert_eq!(deltas.query_count, 2);
+        assert_eq!(deltas.query_count_pct, Some(100.0));
+        assert_eq!(deltas.failed_count, 0);
+        assert_eq!(deltas.avg_duration_ms, Some(-25.0));
+        assert_eq!(deltas.avg_duration_pct, Some(-50.0));
+        assert_eq!(deltas.max_duration_ms, Some(30));
+
+        let empty = PeriodDeltas::between(&current, &PeriodTotals::default());
+        assert_eq!(empty.query_count_pct, None);
+        assert_eq!(empty.avg_duration_ms, None);
+    }
+}
@@ -0,0 +1,125 @@
+//! Near-duplicate detection over query embeddings
+//!
+//! Copy-pasted variants of a query, issued from different code paths with a
+//! column reordered or an alias renamed, fingerprint differently but embed
+//! almost identically. Every pair of embeddings is compared by cosine
+//! similarity, pairs at or above the threshold whose fingerprints differ are
+//! linked, and linked queries are grouped transitively, so a group holds
+//! every variant even when not every pair in it clears the threshold.
+
+/// Similarity at or above which two queries are reported as near-duplicates
+pub const DEFAULT_THRESHOLD: f64 = 0.97;
+
+/// Queries that are near-duplicates of each other, by index into the input
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    /// In input order
+    pub members: Vec<usize>,
+    /// Linked pairs `(a, b, similarity)` with `a < b`, most similar first
+    pub pairs: Vec<(usize, usize, f64)>,
+}
+
+/// Group embeddings into near-duplicates, in order of each group's first
+/// member, so callers passing the most important queries first get the
+/// groups in that order too
+pub fn near_duplicates(
+    vectors: &[Vec<f32>],
+    fingerprints: &[String],
+    threshold: f64,
+) -> Vec<Group> {
+    let unit: Vec<Vec<f32>> = vectors
+        .iter()
+        .map(|v| {
+            let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+            if norm > 0.0 {
+                v.iter().map(|x| x / norm).collect()
+            } else {
+                v.clone()
+            }
+        })
+        .collect();
+
+    let mut pairs = Vec::new();
+    for a in 0..unit.len() {
+        for b in a + 1..unit.len() {
+            if fingerprints[a] == fingerprints[b] {
+                continue;
+            }
+            let similarity: f32 = unit[a].iter().zip(&unit[b]).map(|(x, y)| x * y).sum();
+            if f64::from(similarity) >= threshold {
+                pairs.push((a, b, f64::from(similarity)));
+            }
+        }
+    }
+
+    // Union-find, each root being its set's smallest index
+    let mut parent: Vec<usize> = (0..unit.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for &(a, b, _) in &pairs {
+        let (ra, rb) = (root(&mut parent, a), root(&mut parent, b));
+        parent[ra.max(rb)] = ra.min(rb);
+    }
+
+    let mut linked = vec![false; unit.len()];
+    for &(a, b, _) in &pairs {
+        linked[a] = true;
+        linked[b] = true;
+    }
+
+    let mut groups: Vec<Group> = Vec::new();
+    let mut group_of = vec![usize::MAX; unit.len()];
+    for i in (0..unit.len()).filter(|i| linked[*i]) {
+        let r = root(&mut parent, i);
+        if group_of[r] == usize::MAX {
+            group_of[r] = groups.len();
+            groups.push(Group {
+                members: Vec::new(),
+                pairs: Vec::new(),
+            });
+        }
+        groups[group_of[r]].members.push(i);
+    }
+    for pair in pairs {
+        let r = root(&mut parent, pair.0);
+        groups[group_of[r]].pairs.push(pair);
+    }
+    for group in &mut groups {
+        group.pairs.sort_by(|x, y| y.2.total_cmp(&x.2));
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variants_are_grouped_transitively() {
+        let vectors = vec![
+            vec![1.0, 0.0, 0.0],
+            vec![0.0, 1.0, 0.0],
+            // Near both 0 and 3, which aren't near each other
+            vec![0.98, 0.2, 0.0],
+            vec![0.9, 0.42, 0.0],
+            // Same fingerprint as 1, so never a near-duplicate of it
+            vec![0.0, 2.0, 0.0],
+        ];
+        let fingerprints: Vec<String> = ["a", "b", "c", "d", "b"].map(String::from).into();
+
+        let groups = near_duplicates(&vectors, &fingerprints, DEFAULT_THRESHOLD);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].members, [0, 2, 3]);
+        let linked: Vec<(usize, usize)> = groups[0].pairs.iter().map(|p| (p.0, p.1)).collect();
+        assert_eq!(linked, [(0, 2), (2, 3)]);
+        assert!(groups[0].pairs.iter().all(|p| p.2 >= DEFAULT_THRESHOLD));
+
+        assert!(near_duplicates(&vectors, &fingerprints, 0.999).is_empty());
+        assert!(near_duplicates(&[], &[], DEFAULT_THRESHOLD).is_empty());
+    }
+}
@@ -0,0 +1,645 @@
+//! Embedding providers with failover
+//!
+//! Providers implement [`EmbeddingProvider`]. Queries are embedded by the
+//! local ONNX model or by a remote embeddings API speaking the OpenAI format
+//! (`POST {"model", "input", "dimensions"}` to `{base}/embeddings`, answered
+//! with `{"data": [{"embedding": [...]}]}`), so nodes without the model
+//! files can still enable similarity search. With both configured,
+//! one is the primary and the other the fallback, so similarity search keeps
+//! working while the GPU node or the external API is down.
+//!
+//! Queries over [`DEFAULT_MAX_QUERY_BYTES`] (or `EMBEDDING_MAX_QUERY_BYTES`)
+//! are refused before reaching a provider, and the local model truncates
+//! inputs to its maximum sequence length.
+//!
+//! Each provider's health is tracked: after [`FAILURE_THRESHOLD`]
+//! consecutive failures it is skipped for [`COOLDOWN`], then tried again.
+//! Providers must produce vectors of the `query_embeddings` column's
+//! dimension so their embeddings are comparable; startup fails when a
+//! provider's [`EmbeddingProvider::dimension`] doesn't match it, and a remote
+//! response of any other length counts as a failure.
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::db::EmbeddingModel;
+use crate::error::{AppError, Result};
+use crate::services::embedding::{EmbeddingService, DEFAULT_EMBEDDING_DIM};
+
+/// Consecutive failures after which a provider is skipped
+pub const FAILURE_THRESHOLD: u32 = 3;
+
+/// How long an unhealthy provider is skipped before it is tried again
+pub const COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Longest query embedded, unless `EMBEDDING_MAX_QUERY_BYTES` says otherwise
+pub const DEFAULT_MAX_QUERY_BYTES: usize = 32 * 1024;
+
+/// How long to wait for a remote embeddings API to answer
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Kind of embedding provider, as named in `EMBEDDING_PRIMARY`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    /// ONNX model loaded from disk
+    Local,
+    /// HTTP embeddings API
+    Remote,
+}
+
+impl ProviderKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProviderKind::Local => "local",
+            ProviderKind::Remote => "remote",
+        }
+    }
+}
+
+impl FromStr for ProviderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(ProviderKind::Local),
+            "remote" => Ok(ProviderKind::Remote),
+            other => Err(format!("Unknown embedding provider: {}", other)),
+        }
+    }
+}
+
+/// A source of query embeddings
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Kind of provider, for logs and health reporting
+    fn kind(&self) -> ProviderKind;
+
+    /// Model the provider embeds with
+    fn model(&self) -> &str;
+
+    /// Version of the model; embeddings are only compared with those of the
+    /// same model and version
+    fn model_version(&self) -> &str;
+
+    /// Length of the vectors the provider produces
+    fn dimension(&self) -> usize;
+
+    /// Inputs cut to the model's maximum sequence length since startup
+    fn truncated(&self) -> u64 {
+        0
+    }
+
+    /// Embed a query as a [`dimension`](Self::dimension)-length vector
+    async fn embed_query(&self, query: &str) -> Result<Vec<f32>>;
+
+    /// Embed queries in one call, in order; by default one at a time
+    async fn embed_batch(&self, queries: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(queries.len());
+        for query in queries {
+            embeddings.push(self.embed_query(query).await?);
+        }
+        Ok(embeddings)
+    }
+}
+
+/// A configured embedding provider
+pub type Provider = Box<dyn EmbeddingProvider>;
+
+#[async_trait]
+impl EmbeddingProvider for EmbeddingService {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::Local
+    }
+
+    fn model(&self) -> &str {
+        EmbeddingService::model(self)
+    }
+
+    fn model_version(&self) -> &str {
+        EmbeddingService::model_version(self)
+    }
+
+    fn truncated(&self) -> u64 {
+        EmbeddingService::truncated(self)
+    }
+
+    fn dimension(&self) -> usize {
+        self.embedding_dim()
+    }
+
+    async fn embed_query(&self, query: &str) -> Result<Vec<f32>> {
+        EmbeddingService::embed_query(self, query)
+    }
+
+    async fn embed_batch(&self, queries: &[&str]) -> Result<Vec<Vec<f32>>> {
+        EmbeddingService::embed_batch(self, queries)
+    }
+}
+
+/// Client for a remote embeddings API
+pub struct RemoteEmbedding {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+    model: String,
+    /// Requested with each call and checked in every response
+    dimensions: usize,
+    /// The requested dimension, since a model shortened to another
+    /// dimension produces incomparable vectors
+    version: String,
+}
+
+#[derive(Deserialize)]
+struct RemoteResponse {
+    data: Vec<RemoteEmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct RemoteEmbeddingData {
+    /// Position of the input this embeds
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+impl RemoteEmbedding {
+    /// `url` is the embeddings endpoint, or an API base URL such as
+    /// `https://api.openai.com/v1` that `/embeddings` is appended to
+    pub fn new(url: String, api_key: Option<String>, model: String) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(REMOTE_TIMEOUT)
+            .user_agent(concat!("QueryVault/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("Failed to build HTTP client");
+        Self {
+            client,
+            url: endpoint(&url),
+            api_key,
+            model,
+            dimensions: DEFAULT_EMBEDDING_DIM,
+            version: remote_version(DEFAULT_EMBEDDING_DIM),
+        }
+    }
+
+    /// Request `dimensions`-length vectors instead of the default 384
+    pub fn with_dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = dimensions;
+        self.version = remote_version(dimensions);
+        self
+    }
+}
+
+/// Version recorded for a remote model's embeddings
+fn remote_version(dimensions: usize) -> String {
+    format!("{}d", dimensions)
+}
+
+/// The embeddings endpoint for a configured URL
+fn endpoint(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    if url.ends_with("/embeddings") {
+        url.to_string()
+    } else {
+        format!("{}/embeddings", url)
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for RemoteEmbedding {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::Remote
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn model_version(&self) -> &str {
+        &self.version
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimensions
+    }
+
+    async fn embed_query(&self, query: &str) -> Result<Vec<f32>> {
+        let mut embeddings = self.request(json!(query), 1).await?;
+        Ok(embeddings.remove(0))
+    }
+
+    async fn embed_batch(&self, queries: &[&str]) -> Result<Vec<Vec<f32>>> {
+        if queries.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.request(json!(queries), queries.len()).await
+    }
+}
+
+impl RemoteEmbedding {
+    /// Embed `input`, a string or an array of `count` strings
+    async fn request(&self, input: serde_json::Value, count: usize) -> Result<Vec<Vec<f32>>> {
+        let mut request = self.client.post(&self.url).json(&json!({
+            "model": self.model,
+            "input": input,
+            "dimensions": self.dimensions,
+        }));
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| {
+                AppError::InternalError(format!("Embeddings API request failed: {}", e))
+            })?;
+        let mut body: RemoteResponse = response.json().await.map_err(|e| {
+            AppError::InternalError(format!("Invalid embeddings API response: {}", e))
+        })?;
+
+        if body.data.len() != count {
+            return Err(AppError::InternalError(format!(
+                "Embeddings API returned {} embeddings for {} inputs",
+                body.data.len(),
+                count
+            )));
+        }
+        body.data.sort_by_key(|d| d.index);
+        let embeddings: Vec<Vec<f32>> = body.data.into_iter().map(|d| d.embedding).collect();
+        if let Some(wrong) = embeddings.iter().find(|e| e.len() != self.dimensions) {
+            return Err(AppError::InternalError(format!(
+                "Embeddings API returned {} dimensions, expected {}",
+                wrong.len(),
+                self.dimensions
+            )));
+        }
+        Ok(embeddings)
+    }
+}
+
+/// Recent outcomes for one provider
+#[derive(Debug, Default)]
+struct Health {
+    consecutive_failures: u32,
+    /// Set once the failure threshold is reached
+    skipped_until: Option<Instant>,
+}
+
+impl Health {
+    fn available(&self, now: Instant) -> bool {
+        !matches!(self.skipped_until, Some(until) if now < until)
+    }
+}
+
+struct Slot {
+    provider: Provider,
+    health: Mutex<Health>,
+}
+
+impl Slot {
+    fn model(&self) -> EmbeddingModel {
+        EmbeddingModel {
+            name: self.provider.model().to_string(),
+            version: self.provider.model_version().to_string(),
+        }
+    }
+}
+
+/// Health of one provider, for readiness checks and Prometheus
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderStatus {
+    pub provider: ProviderKind,
+    /// Whether this is the primary provider
+    pub primary: bool,
+    /// Whether the provider is being tried (not cooling down)
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+}
+
+/// An embedding and the provider and model that produced it
+#[derive(Debug, Clone, Serialize)]
+pub struct Embedding {
+    pub provider: ProviderKind,
+    pub model: String,
+    pub model_version: String,
+    pub embedding: Vec<f32>,
+}
+
+/// Embeds queries with a primary provider and an optional fallback
+pub struct Embedder {
+    /// Primary first
+    slots: Vec<Slot>,
+    /// Queries embedded by the fallback because the primary failed or was skipped
+    failovers: AtomicU64,
+    /// Longer queries are refused before reaching a provider
+    max_query_bytes: usize,
+    /// Queries left unembedded for being over `max_query_bytes`
+    skipped: AtomicU64,
+}
+
+impl Embedder {
+    pub fn new(primary: Provider, fallback: Option<Provider>) -> Self {
+        let slots = std::iter::once(primary)
+            .chain(fallback)
+            .map(|provider| Slot {
+                provider,
+                health: Mutex::new(Health::default()),
+            })
+            .collect();
+        Self {
+            slots,
+            failovers: AtomicU64::new(0),
+            max_query_bytes: DEFAULT_MAX_QUERY_BYTES,
+            skipped: AtomicU64::new(0),
+        }
+    }
+
+    /// Refuse queries over `max_query_bytes` instead of 32 KiB
+    pub fn with_max_query_bytes(mut self, max_query_bytes: usize) -> Self {
+        self.max_query_bytes = max_query_bytes;
+        self
+    }
+
+    /// Longest query embedded, in bytes
+    pub fn max_query_bytes(&self) -> usize {
+        self.max_query_bytes
+    }
+
+    /// Whether a query is short enough to embed
+    pub fn fits(&self, query: &str) -> bool {
+        query.len() <= self.max_query_bytes
+    }
+
+    /// Count queries the embedding task left out for not fitting
+    pub fn record_skipped(&self, count: usize) {
+        self.skipped.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// The primary provider's model, which stored embeddings should all be
+    /// from
+    pub fn model(&self) -> EmbeddingModel {
+        self.slots[0].model()
+    }
+
+    /// Each provider and the length of its vectors, primary first
+    pub fn dimensions(&self) -> Vec<(ProviderKind, usize)> {
+        self.slots
+            .iter()
+            .map(|slot| (slot.provider.kind(), slot.provider.dimension()))
+            .collect()
+    }
+
+    /// Embed a query with the first healthy provider that succeeds, reporting
+    /// which provider and model produced it
+    pub async fn embed(&self, query: &str) -> Result<Embedding> {
+        let (slot, mut embeddings) = self.run(&[query]).await?;
+        Ok(Embedding {
+            provider: slot.provider.kind(),
+            model: slot.provider.model().to_string(),
+            model_version: slot.provider.model_version().to_string(),
+            embedding: embeddings.remove(0),
+        })
+    }
+
+    /// Embed queries, in order, with one call to the first healthy provider
+    /// that succeeds, returning the model that embedded them
+    pub async fn embed_batch(&self, queries: &[&str]) -> Result<(EmbeddingModel, Vec<Vec<f32>>)> {
+        if queries.is_empty() {
+            return Ok((self.model(), Vec::new()));
+        }
+        self.run(queries)
+            .await
+            .map(|(slot, embeddings)| (slot.model(), embeddings))
+    }
+
+    /// Embed queries with the first healthy provider that succeeds,
+    /// returning its slot with the embeddings
+    ///
+    /// If every provider is cooling down they are all tried anyway, so a
+    /// recovered provider isn't ignored until its cooldown ends.
+    async fn run(&self, queries: &[&str]) -> Result<(&Slot, Vec<Vec<f32>>)> {
+        if let Some(long) = queries.iter().find(|q| !self.fits(q)) {
+            return Err(AppError::InvalidRequest(format!(
+                "Query is {} bytes; at most {} can be embedded",
+                long.len(),
+                self.max_query_bytes
+            )));
+        }
+
+        let now = Instant::now();
+        let available: Vec<(usize, &Slot)> = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.health.lock().available(now))
+            .collect();
+        let candidates = if available.is_empty() {
+            self.slots.iter().enumerate().collect()
+        } else {
+            available
+        };
+
+        let mut last_error = None;
+        for (i, slot) in candidates {
+            let kind = slot.provider.kind();
+            let result = match queries {
+                [query] => slot.provider.embed_query(query).await.map(|e| vec![e]),
+                _ => slot.provider.embed_batch(queries).await,
+            };
+            match result {
+                Ok(embeddings) => {
+                    let mut health = slot.health.lock();
+                    if health.consecutive_failures >= FAILURE_THRESHOLD {
+                        info!(provider = kind.as_str(), "Embedding provider recovered");
+                    }
+                    *health = Health::default();
+                    drop(health);
+                    if i > 0 {
+                        self.failovers
+                            .fetch_add(queries.len() as u64, Ordering::Relaxed);
+                    }
+                    return Ok((slot, embeddings));
+                }
+                Err(e) => {
+                    let mut health = slot.health.lock();
+                    health.consecutive_failures += 1;
+                    if health.consecutive_failures >= FAILURE_THRESHOLD {
+                        health.skipped_until = Some(Instant::now() + COOLDOWN);
+                    }
+                    warn!(
+                        provider = kind.as_str(),
+                        error = %e,
+                        consecutive_failures = health.consecutive_failures,
+                        "Embedding provider failed"
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(AppError::ServiceUnavailable(format!(
+            "No embedding provider available: {}",
+            last_error.map(|e| e.to_string()).unwrap_or_default()
+        )))
+    }
+
+    /// Health of each provider, primary first
+    pub fn status(&self) -> Vec<ProviderStatus> {
+        let now = Instant::now();
+        self.slots
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| {
+                let health = slot.health.lock();
+                ProviderStatus {
+                    provider: slot.provider.kind(),
+                    primary: i == 0,
+                    healthy: health.available(now),
+                    consecutive_failures: health.consecutive_failures,
+                }
+            })
+            .collect()
+    }
+
+    /// Queries embedded by the fallback
+    pub fn failovers(&self) -> u64 {
+        self.failovers.load(Ordering::Relaxed)
+    }
+
+    /// Queries truncated to a provider's maximum sequence length
+    pub fn truncated(&self) -> u64 {
+        self.slots
+            .iter()
+            .map(|slot| slot.provider.truncated())
+            .sum()
+    }
+
+    /// Queries the embedding task skipped for being too long
+    pub fn skipped(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, routing::post, Json, Router};
+
+    #[tokio::test]
+    async fn test_fails_over_and_skips_unhealthy_primary() {
+        let app = Router::new()
+            .route(
+                "/down/embeddings",
+                post(|| async { StatusCode::BAD_GATEWAY }),
+            )
+            .route(
+                "/up/embeddings",
+                post(|| async {
+                    Json(json!({ "data": [{ "embedding": vec![0.5f32; DEFAULT_EMBEDDING_DIM] }] }))
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        // Base URLs, with `/embeddings` appended
+        let remote = |path: &str| -> Provider {
+            Box::new(RemoteEmbedding::new(
+                format!("http://{}{}", addr, path),
+                None,
+                "test".into(),
+            ))
+        };
+        let embedder = Embedder::new(remote("/down"), Some(remote("/up")));
+
+        for _ in 0..FAILURE_THRESHOLD {
+            let embedding = embedder.embed("SELECT 1").await.unwrap();
+            assert_eq!(embedding.embedding.len(), DEFAULT_EMBEDDING_DIM);
+        }
+        let status = embedder.status();
+        assert!(!status[0].healthy);
+        assert_eq!(status[0].consecutive_failures, FAILURE_THRESHOLD);
+        assert!(status[1].healthy);
+
+        // The primary is skipped while it cools down
+        let embedding = embedder.embed("SELECT 2").await.unwrap();
+        assert_eq!(embedding.provider, ProviderKind::Remote);
+        assert_eq!(embedding.model, "test");
+        assert_eq!(embedder.status()[0].consecutive_failures, FAILURE_THRESHOLD);
+        assert_eq!(embedder.failovers(), u64::from(FAILURE_THRESHOLD) + 1);
+
+        let alone = Embedder::new(remote("/down"), None);
+        assert!(matches!(
+            alone.embed("SELECT 1").await,
+            Err(AppError::ServiceUnavailable(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_batch_is_one_request_in_input_order() {
+        // Answers in reverse, each embedding filled with its input's length
+        let app = Router::new().route(
+            "/v1/embeddings",
+            post(|Json(body): Json<serde_json::Value>| async move {
+                let inputs = body["input"].as_array().cloned().unwrap_or_default();
+                let data: Vec<_> = inputs
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(i, input)| {
+                        let len = input.as_str().unwrap().len() as f32;
+                        json!({ "index": i, "embedding": vec![len; DEFAULT_EMBEDDING_DIM] })
+                    })
+                    .collect();
+                Json(json!({ "data": data }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let remote: Provider = Box::new(RemoteEmbedding::new(
+            format!("http://{}/v1", addr),
+            None,
+            "test".into(),
+        ));
+        let embedder = Embedder::new(remote, None);
+        let (model, embeddings) = embedder
+            .embed_batch(&["SELECT 1", "SELECT 22"])
+            .await
+            .unwrap();
+        assert_eq!(model, embedder.model());
+        assert_eq!(model.version, "384d");
+        assert_eq!(embeddings.len(), 2);
+        assert_eq!(embeddings[0][0], 8.0);
+        assert_eq!(embeddings[1][0], 9.0);
+        assert!(embedder.embed_batch(&[]).await.unwrap().1.is_empty());
+
+        // Oversized queries never reach the provider
+        let embedder = embedder.with_max_query_bytes(8);
+        assert!(matches!(
+            embedder.embed_batch(&["SELECT 1", "SELECT 22"]).await,
+            Err(AppError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_endpoint_accepts_base_or_full_url() {
+        assert_eq!(
+            endpoint("https://api.openai.com/v1/"),
+            "https://api.openai.com/v1/embeddings"
+        );
+        assert_eq!(
+            endpoint("http://gpu-1:8080/v1/embeddings"),
+            "http://gpu-1:8080/v1/embeddings"
+        );
+    }
+}
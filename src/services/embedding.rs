@@ -7,11 +7,98 @@
 //! The actual ONNX Runtime integration is deferred until the model files are available.
 //! For now, we provide a stub that can be replaced with real ONNX inference.
 
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fmt;
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use tracing::{info, warn};
 
 use crate::error::{AppError, Result};
 
+/// Embedding dimension of all-MiniLM-L6-v2, and of the
+/// `query_embeddings.embedding` column the migrations create. Every provider
+/// must produce vectors of the column's length.
+pub const DEFAULT_EMBEDDING_DIM: usize = 384;
+
+/// Longest input, in tokens, unless `EMBEDDING_MAX_TOKENS` says otherwise;
+/// all-MiniLM-L6-v2 was trained on 256-token sequences
+pub const DEFAULT_MAX_TOKENS: usize = 256;
+
+/// Tokens the tokenizer adds around every input (`[CLS]` and `[SEP]`)
+const SPECIAL_TOKENS: usize = 2;
+
+/// The fields of a HuggingFace `config.json` that give the output dimension
+#[derive(Deserialize)]
+struct ModelConfig {
+    hidden_size: Option<usize>,
+    /// DistilBERT-style configs
+    dim: Option<usize>,
+}
+
+/// Hardware ONNX Runtime runs the model on, as named in
+/// `EMBEDDING_EXECUTION_PROVIDER`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecutionProvider {
+    #[default]
+    Cpu,
+    /// NVIDIA GPU
+    Cuda,
+    /// Apple Neural Engine and GPU; macOS only
+    CoreMl,
+}
+
+impl ExecutionProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExecutionProvider::Cpu => "cpu",
+            ExecutionProvider::Cuda => "cuda",
+            ExecutionProvider::CoreMl => "coreml",
+        }
+    }
+}
+
+impl FromStr for ExecutionProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "cpu" => Ok(ExecutionProvider::Cpu),
+            "cuda" => Ok(ExecutionProvider::Cuda),
+            "coreml" => Ok(ExecutionProvider::CoreMl),
+            other => Err(format!("Unknown execution provider: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for ExecutionProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// ONNX Runtime session settings
+///
+/// Unset thread counts keep the runtime's defaults: an intra-op thread per
+/// physical core and sequential operators, which oversubscribes hosts that
+/// run other work and leaves cores idle when several sessions share a box.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuntimeOptions {
+    /// Threads parallelizing work within an operator
+    /// (`EMBEDDING_INTRA_OP_THREADS`)
+    pub intra_op_threads: Option<usize>,
+    /// Threads running independent operators in parallel
+    /// (`EMBEDDING_INTER_OP_THREADS`); above 1 enables parallel execution
+    pub inter_op_threads: Option<usize>,
+    /// `EMBEDDING_EXECUTION_PROVIDER`; operators the provider can't run fall
+    /// back to the CPU
+    pub execution_provider: ExecutionProvider,
+    /// GPU to use with CUDA (`EMBEDDING_DEVICE_ID`)
+    pub device_id: u32,
+}
+
 /// Embedding service (stub implementation)
 ///
 /// In production, this would use ONNX Runtime for transformer models.
@@ -19,6 +106,16 @@ use crate::error::{AppError, Result};
 #[derive(Clone)]
 pub struct EmbeddingService {
     embedding_dim: usize,
+    /// Model name, from the model file's name
+    model: String,
+    /// Start of the model file's SHA-256, so replacing the weights under the
+    /// same name is noticed
+    version: String,
+    /// Maximum sequence length, special tokens included; longer inputs are
+    /// truncated
+    max_tokens: usize,
+    /// Inputs truncated to `max_tokens`
+    truncated: Arc<AtomicU64>,
 }
 
 impl EmbeddingService {
@@ -27,8 +124,16 @@ impl EmbeddingService {
     /// # Arguments
     /// * `model_path` - Path to the ONNX model file
     /// * `tokenizer_path` - Path to the tokenizer.json file
-    pub fn new(model_path: &Path, tokenizer_path: &Path) -> Result<Self> {
-        info!(model = ?model_path, tokenizer = ?tokenizer_path, "Loading embedding model");
+    /// * `runtime` - Threading and execution provider of the session
+    pub fn new(model_path: &Path, tokenizer_path: &Path, runtime: &RuntimeOptions) -> Result<Self> {
+        info!(
+            model = ?model_path,
+            tokenizer = ?tokenizer_path,
+            execution_provider = %runtime.execution_provider,
+            intra_op_threads = runtime.intra_op_threads,
+            inter_op_threads = runtime.inter_op_threads,
+            "Loading embedding model"
+        );
 
         // Verify paths exist
         if !model_path.exists() {
@@ -47,21 +152,70 @@ impl EmbeddingService {
         // For now, use a simple stub implementation
         // Real implementation would load ONNX model and tokenizer
         warn!("Using stub embedding service - real ONNX inference not implemented");
+        if *runtime != RuntimeOptions::default() {
+            warn!("Stub embedding service runs on the calling thread; runtime options are not applied");
+        }
 
-        let embedding_dim = 384; // Standard for MiniLM-L6-v2
+        let embedding_dim = match model_dimension(model_path)? {
+            Some(dim) => dim,
+            None => {
+                warn!(
+                    model = ?model_path,
+                    "No config.json next to the model, assuming {} dimensions",
+                    DEFAULT_EMBEDDING_DIM
+                );
+                DEFAULT_EMBEDDING_DIM
+            }
+        };
 
         info!(
             embedding_dim = embedding_dim,
             "Embedding service ready (stub mode)"
         );
 
-        Ok(Self { embedding_dim })
+        let model = model_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let version = model_version(model_path)?;
+        info!(model = %model, version = %version, "Identified embedding model");
+
+        Ok(Self {
+            embedding_dim,
+            model,
+            version,
+            max_tokens: DEFAULT_MAX_TOKENS,
+            truncated: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// Truncate inputs to `max_tokens` tokens instead of the default 256
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = max_tokens.max(SPECIAL_TOKENS + 1);
+        self
     }
 
     /// Embed a single query string
     ///
-    /// Returns a normalized embedding vector
+    /// Queries longer than the maximum sequence length are truncated, as the
+    /// model would otherwise reject them or allocate tensors for their full
+    /// length. Returns a normalized embedding vector
     pub fn embed_query(&self, query: &str) -> Result<Vec<f32>> {
+        let query = match truncate_tokens(query, self.max_tokens - SPECIAL_TOKENS) {
+            Some(kept) => {
+                self.truncated.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    bytes = query.len(),
+                    kept_bytes = kept.len(),
+                    max_tokens = self.max_tokens,
+                    "Query truncated to the model's maximum sequence length"
+                );
+                kept
+            }
+            None => query,
+        };
+
         // Stub implementation: generate deterministic embedding from query hash
         let embedding = self.generate_stub_embedding(query);
         Ok(embedding)
@@ -70,7 +224,6 @@ impl EmbeddingService {
     /// Embed a batch of queries
     ///
     /// Returns normalized embedding vectors
-    #[allow(dead_code)]
     pub fn embed_batch(&self, queries: &[&str]) -> Result<Vec<Vec<f32>>> {
         queries.iter().map(|q| self.embed_query(q)).collect()
     }
@@ -110,13 +263,95 @@ impl EmbeddingService {
         embedding
     }
 
+    /// Model name, e.g. `all-MiniLM-L6-v2`
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Model version, the first 12 hex digits of the model file's SHA-256
+    pub fn model_version(&self) -> &str {
+        &self.version
+    }
+
+    /// Inputs truncated to the maximum sequence length since startup
+    pub fn truncated(&self) -> u64 {
+        self.truncated.load(Ordering::Relaxed)
+    }
+
     /// Get the embedding dimension
-    #[allow(dead_code)]
     pub fn embedding_dim(&self) -> usize {
         self.embedding_dim
     }
 }
 
+/// The longest prefix of `query` that fits in `max_tokens` tokens, or `None`
+/// if all of it fits
+///
+/// Tokens are counted like a WordPiece tokenizer splits SQL, closely enough
+/// to bound the sequence: each run of letters, digits, and underscores is one
+/// token per 8 characters, and every other non-space character is a token.
+fn truncate_tokens(query: &str, max_tokens: usize) -> Option<&str> {
+    let mut tokens = 0;
+    let mut word_chars = 0;
+    for (i, c) in query.char_indices() {
+        let starts_token = if c.is_alphanumeric() || c == '_' {
+            let starts = word_chars % 8 == 0;
+            word_chars += 1;
+            starts
+        } else {
+            word_chars = 0;
+            !c.is_whitespace()
+        };
+        if starts_token {
+            if tokens == max_tokens {
+                return Some(query[..i].trim_end());
+            }
+            tokens += 1;
+        }
+    }
+    None
+}
+
+/// Hash the model file, identifying its exact weights
+fn model_version(model_path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(model_path)
+        .map_err(|e| AppError::InternalError(format!("Failed to open {:?}: {}", model_path, e)))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .map_err(|e| AppError::InternalError(format!("Failed to read {:?}: {}", model_path, e)))?;
+    Ok(hasher.finalize()[..6]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Read a model's output dimension from the `config.json` exported next to it
+///
+/// Returns `None` when there is no `config.json`; one that doesn't give a
+/// dimension is an error, so a model isn't loaded with a guessed size.
+fn model_dimension(model_path: &Path) -> Result<Option<usize>> {
+    let path = model_path.with_file_name("config.json");
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(AppError::InternalError(format!(
+                "Failed to read {:?}: {}",
+                path, e
+            )))
+        }
+    };
+    let config: ModelConfig = serde_json::from_str(&contents)
+        .map_err(|e| AppError::InternalError(format!("Invalid {:?}: {}", path, e)))?;
+    match config.hidden_size.or(config.dim) {
+        Some(0) | None => Err(AppError::InternalError(format!(
+            "{:?} doesn't give the model's hidden_size",
+            path
+        ))),
+        Some(dim) => Ok(Some(dim)),
+    }
+}
+
 /// Compute cosine similarity between two normalized vectors
 #[allow(dead_code)]
 pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
@@ -137,7 +372,6 @@ pub fn normalize_query(query: &str) -> String {
 }
 
 /// Compute hash of normalized query
-#[allow(dead_code)]
 pub fn query_hash(query: &str) -> String {
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
@@ -147,3 +381,35 @@ pub fn query_hash(query: &str) -> String {
     normalized.hash(&mut hasher);
     format!("{:016x}", hasher.finish())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_tokens_keeps_whole_tokens() {
+        // select, *, from, t, where, id, in, (, 1, ",", 2, ",", 3, )
+        let query = "SELECT * FROM t WHERE id IN (1, 2, 3)";
+        assert_eq!(truncate_tokens(query, 14), None);
+        assert_eq!(
+            truncate_tokens(query, 9),
+            Some("SELECT * FROM t WHERE id IN (1")
+        );
+        assert_eq!(truncate_tokens(query, 0), Some(""));
+
+        // Long identifiers count a token per 8 characters
+        assert_eq!(truncate_tokens("abcdefghijklmnop", 2), None);
+        assert_eq!(
+            truncate_tokens("abcdefghijklmnopq", 2),
+            Some("abcdefghijklmnop")
+        );
+
+        let in_list = format!(
+            "SELECT 1 FROM t WHERE id IN ({})",
+            vec!["42"; 5000].join(", ")
+        );
+        let kept = truncate_tokens(&in_list, DEFAULT_MAX_TOKENS - SPECIAL_TOKENS).unwrap();
+        assert!(kept.len() < 1000);
+        assert!(in_list.starts_with(kept));
+    }
+}
@@ -0,0 +1,136 @@
+//! In-process cache of similarity search embeddings
+//!
+//! Dashboards re-issue the same search on every refresh, and each one would
+//! otherwise pay for tokenizing and running the model again. Embeddings are
+//! cached by query hash, so text differing only in case and whitespace
+//! shares an entry, along with the model that made them: an entry only
+//! serves searches while that model is the primary provider's.
+
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::db::EmbeddingModel;
+use crate::services::embedding::query_hash;
+
+/// Default number of cached search embeddings
+pub const DEFAULT_CAPACITY: usize = 1_000;
+
+struct CachedEmbedding {
+    model: EmbeddingModel,
+    embedding: Vec<f32>,
+    /// Value of [`EmbeddingCache::clock`] when last read, for LRU eviction
+    last_used: AtomicU64,
+}
+
+/// Recently embedded search text, indexed by query hash
+///
+/// Once `capacity` is reached the least recently used entry is evicted. A
+/// zero `capacity` disables caching.
+pub struct EmbeddingCache {
+    entries: RwLock<HashMap<String, CachedEmbedding>>,
+    capacity: usize,
+    clock: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl EmbeddingCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            capacity,
+            clock: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// The embedding `model` made of `text`, if cached
+    pub fn get(&self, text: &str, model: &EmbeddingModel) -> Option<Vec<f32>> {
+        let found = self
+            .entries
+            .read()
+            .get(&query_hash(text))
+            .filter(|entry| entry.model == *model)
+            .map(|entry| {
+                let tick = self.clock.fetch_add(1, Ordering::Relaxed);
+                entry.last_used.store(tick, Ordering::Relaxed);
+                entry.embedding.clone()
+            });
+        let counter = if found.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    pub fn insert(&self, text: &str, model: EmbeddingModel, embedding: Vec<f32>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let hash = query_hash(text);
+        let mut entries = self.entries.write();
+        if entries.len() >= self.capacity && !entries.contains_key(&hash) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used.load(Ordering::Relaxed))
+                .map(|(hash, _)| hash.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        let tick = self.clock.fetch_add(1, Ordering::Relaxed);
+        entries.insert(
+            hash,
+            CachedEmbedding {
+                model,
+                embedding,
+                last_used: AtomicU64::new(tick),
+            },
+        );
+    }
+
+    /// `(hits, misses)` since startup
+    pub fn stats(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(version: &str) -> EmbeddingModel {
+        EmbeddingModel {
+            name: "test".into(),
+            version: version.into(),
+        }
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let cache = EmbeddingCache::new(2);
+        cache.insert("SELECT 1", model("1"), vec![1.0]);
+        cache.insert("SELECT 2", model("1"), vec![2.0]);
+        assert_eq!(cache.get("select  1", &model("1")), Some(vec![1.0]));
+
+        // "SELECT 2" is now the least recently used
+        cache.insert("SELECT 3", model("1"), vec![3.0]);
+        assert!(cache.get("SELECT 2", &model("1")).is_none());
+        assert!(cache.get("SELECT 1", &model("1")).is_some());
+
+        // Another model's embedding isn't served
+        assert!(cache.get("SELECT 3", &model("2")).is_none());
+        assert_eq!(cache.stats(), (2, 2));
+
+        let disabled = EmbeddingCache::new(0);
+        disabled.insert("SELECT 1", model("1"), vec![1.0]);
+        assert!(disabled.get("SELECT 1", &model("1")).is_none());
+    }
+}
@@ -0,0 +1,101 @@
+//! Error message fingerprinting
+//!
+//! Failed executions are grouped into error classes by the fingerprint of
+//! their normalized `error_message`, so `duplicate key value violates unique
+//! constraint "users_email_key"` raised for a thousand different emails is one
+//! class. Values that vary between occurrences of the same error become `?`:
+//!
+//! - single-quoted strings and `Key (col)=(...)` details
+//! - UUIDs and `0x` hex values
+//! - numbers not part of an identifier (`line 12`, `pid 4711`, `1.5s`)
+//!
+//! Double-quoted identifiers such as constraint and relation names are kept,
+//! as they tell error classes apart. Whitespace is collapsed. A failed
+//! execution without a message is fingerprinted as the empty message.
+
+use md5::{Digest, Md5};
+use regex::Regex;
+use std::sync::OnceLock;
+
+use crate::models::{QueryMetric, QueryStatus};
+
+/// Replacements applied in order; earlier ones protect values the later
+/// number pattern would only partly replace
+fn patterns() -> &'static [(Regex, &'static str)] {
+    static PATTERNS: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        [
+            (r"'(?:[^']|'')*'", "?"),
+            (r"\)=\([^)]*\)", ")=(?)"),
+            (
+                r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b",
+                "?",
+            ),
+            (r"(?i)\b0x[0-9a-f]+\b", "?"),
+            (r"\b\d+(?:\.\d+)?", "?"),
+            (r"\s+", " "),
+        ]
+        .into_iter()
+        .map(|(pattern, replacement)| (Regex::new(pattern).expect("valid pattern"), replacement))
+        .collect()
+    })
+}
+
+/// Normalize an error message to the pattern its fingerprint is computed from
+pub fn normalize_error(message: &str) -> String {
+    let mut normalized = message.trim().to_string();
+    for (pattern, replacement) in patterns() {
+        normalized = pattern.replace_all(&normalized, *replacement).into_owned();
+    }
+    normalized
+}
+
+/// Compute the fingerprint of an error message: the md5 hex digest of
+/// [`normalize_error`]
+pub fn error_fingerprint(message: &str) -> String {
+    format!("{:x}", Md5::digest(normalize_error(message).as_bytes()))
+}
+
+/// Error fingerprint of a failed or timed-out metric, `None` for any other status
+pub fn metric_error_fingerprint(metric: &QueryMetric) -> Option<String> {
+    matches!(metric.status, QueryStatus::Failed | QueryStatus::Timeout)
+        .then(|| error_fingerprint(metric.error_message.as_deref().unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varying_values_share_a_fingerprint() {
+        assert_eq!(
+            normalize_error(
+                "duplicate key value violates unique constraint \"users_email_key\"\n  DETAIL: Key (email)=(jane@example.com) already exists."
+            ),
+            "duplicate key value violates unique constraint \"users_email_key\" DETAIL: Key (email)=(?) already exists."
+        );
+        assert_eq!(
+            normalize_error("invalid input syntax for type uuid: 'abc' at character 42"),
+            "invalid input syntax for type uuid: ? at character ?"
+        );
+        assert_eq!(
+            normalize_error(
+                "row 550e8400-e29b-41d4-a716-446655440000 locked by pid 4711 (0xdeadBEEF)"
+            ),
+            "row ? locked by pid ? (?)"
+        );
+        assert_eq!(
+            normalize_error("relation \"orders_2024\" does not exist"),
+            "relation \"orders_2024\" does not exist"
+        );
+
+        assert_eq!(
+            error_fingerprint("deadlock detected: process 12 waits for ShareLock"),
+            error_fingerprint("deadlock detected: process 873 waits for ShareLock")
+        );
+        assert_ne!(
+            error_fingerprint("relation \"users\" does not exist"),
+            error_fingerprint("relation \"orders\" does not exist")
+        );
+    }
+}
@@ -0,0 +1,737 @@
+//! Query fingerprinting
+//!
+//! A fingerprint groups executions of "the same" query. Queries are tokenized
+//! with sqlparser rather than regex-scanned, so string literals and comments
+//! are handled correctly, and ORM-generated variants collapse together:
+//!
+//! - `IN (1, 2, 3)` lists of literals or placeholders, of any length, become `in (...)`
+//! - `a = 1 OR a = 2 OR ...` chains on a single column become `a in (...)`
+//!
+//! Keywords and identifiers are lowercased with their quotes dropped, string
+//! and numeric literals and bind placeholders (`$1`, `?`, `@p1`) all become
+//! `?`, whitespace and comments are dropped, and a trailing `;` is ignored,
+//! so `WHERE id = 1` and `WHERE id = $1` share a fingerprint.
+//!
+//! Tokenizing uses the dialect of the service that sent the query, so
+//! `[dbo].[users]` from SQL Server and `` `users` `` from MySQL fingerprint
+//! the same as a bare `users`.
+//!
+//! Fingerprints are stored, so changing how they are computed means bumping
+//! [`FINGERPRINT_VERSION`]: stored ones are then re-keyed at startup.
+
+use md5::{Digest, Md5};
+use sqlparser::dialect::{
+    Dialect, GenericDialect, MsSqlDialect, MySqlDialect, PostgreSqlDialect, SQLiteDialect,
+};
+use sqlparser::tokenizer::{Token, Tokenizer};
+use std::collections::HashMap;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::models::{QueryKind, QueryMetric};
+use crate::services::embedding::normalize_query;
+
+/// Version of the fingerprint algorithm; 2 folds literals into placeholders
+pub const FINGERPRINT_VERSION: i32 = 2;
+
+/// SQL dialect used to tokenize a service's queries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SqlDialect {
+    #[default]
+    Generic,
+    Postgres,
+    MySql,
+    SqlServer,
+    Sqlite,
+}
+
+impl SqlDialect {
+    pub(crate) fn parser_dialect(self) -> Box<dyn Dialect> {
+        match self {
+            SqlDialect::Generic => Box::new(GenericDialect {}),
+            SqlDialect::Postgres => Box::new(PostgreSqlDialect {}),
+            SqlDialect::MySql => Box::new(MySqlDialect {}),
+            SqlDialect::SqlServer => Box::new(MsSqlDialect {}),
+            SqlDialect::Sqlite => Box::new(SQLiteDialect {}),
+        }
+    }
+}
+
+impl FromStr for SqlDialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "generic" => Ok(SqlDialect::Generic),
+            "postgres" | "postgresql" => Ok(SqlDialect::Postgres),
+            "mysql" | "mariadb" => Ok(SqlDialect::MySql),
+            "mssql" | "sqlserver" => Ok(SqlDialect::SqlServer),
+            "sqlite" => Ok(SqlDialect::Sqlite),
+            other => Err(format!("Unknown SQL dialect: {}", other)),
+        }
+    }
+}
+
+/// Dialect selection for each service, with a default for unlisted services
+#[derive(Debug, Clone, Default)]
+pub struct DialectConfig {
+    pub default: SqlDialect,
+    pub services: HashMap<Uuid, SqlDialect>,
+}
+
+impl DialectConfig {
+    /// Build a config from a default dialect and `service_id=dialect` pairs separated by commas
+    pub fn new(default: SqlDialect, services: &str) -> Result<Self, String> {
+        let services = services
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (service_id, dialect) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("Expected service_id=dialect, got: {}", pair))?;
+                let service_id = Uuid::parse_str(service_id.trim())
+                    .map_err(|e| format!("Invalid service id {}: {}", service_id, e))?;
+                Ok((service_id, dialect.parse()?))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self { default, services })
+    }
+
+    /// Dialect configured for a service
+    pub fn dialect_for(&self, service_id: Uuid) -> SqlDialect {
+        self.services
+            .get(&service_id)
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Fingerprint a query sent by a service, using that service's dialect
+    pub fn fingerprint(&self, service_id: Uuid, sql: &str) -> String {
+        fingerprint(sql, self.dialect_for(service_id))
+    }
+
+    /// Classify a query sent by a service, using that service's dialect
+    pub fn classify(&self, service_id: Uuid, sql: &str) -> QueryKind {
+        classify(sql, self.dialect_for(service_id))
+    }
+}
+
+/// A normalized token
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    /// Keyword or identifier (lowercased unless quoted)
+    Word(String),
+    /// Literal value or bind placeholder
+    Value(String),
+    /// Operator or punctuation
+    Symbol(String),
+    /// A collapsed list of values
+    ValueList,
+}
+
+impl Part {
+    fn is_word(&self, word: &str) -> bool {
+        matches!(self, Part::Word(w) if w == word)
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self, Part::Symbol(s) if s == symbol)
+    }
+
+    fn text(&self) -> &str {
+        match self {
+            Part::Word(s) | Part::Value(s) | Part::Symbol(s) => s,
+            Part::ValueList => "(...)",
+        }
+    }
+}
+
+/// Compute the fingerprint of a query: the md5 hex digest of [`normalize`]
+pub fn fingerprint(sql: &str, dialect: SqlDialect) -> String {
+    format!("{:x}", Md5::digest(normalize(sql, dialect).as_bytes()))
+}
+
+/// Fingerprint of a metric, as assigned at ingest or computed with the generic dialect
+pub fn metric_fingerprint(metric: &QueryMetric) -> String {
+    metric
+        .fingerprint
+        .clone()
+        .unwrap_or_else(|| fingerprint(&metric.query_text, SqlDialect::Generic))
+}
+
+/// Normalize a query to the canonical text its fingerprint is computed from
+///
+/// Falls back to case and whitespace folding if the query cannot be tokenized.
+pub fn normalize(sql: &str, dialect: SqlDialect) -> String {
+    let tokens = match Tokenizer::new(dialect.parser_dialect().as_ref(), sql).tokenize() {
+        Ok(tokens) => tokens,
+        Err(_) => return normalize_query(sql),
+    };
+
+    let mut parts: Vec<Part> = tokens.into_iter().filter_map(to_part).collect();
+    while parts.last().is_some_and(|p| p.is_symbol(";")) {
+        parts.pop();
+    }
+
+    let parts = collapse_in_lists(parts);
+    let parts = collapse_or_chains(parts);
+    render(&parts)
+}
+
+/// Tables a query reads or writes, lowercased and deduplicated in order of
+/// appearance
+///
+/// A token-level scan for the names following `FROM`, `JOIN`, `UPDATE`, and
+/// `INTO` (including comma-separated `FROM` lists). Subqueries contribute
+/// their own tables; CTE names are reported like tables.
+pub fn referenced_tables(sql: &str, dialect: SqlDialect) -> Vec<String> {
+    let tokens = match Tokenizer::new(dialect.parser_dialect().as_ref(), sql).tokenize() {
+        Ok(tokens) => tokens,
+        Err(_) => return Vec::new(),
+    };
+    let parts: Vec<Part> = tokens.into_iter().filter_map(to_part).collect();
+
+    let mut tables: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < parts.len() {
+        let list = parts[i].is_word("from");
+        if !(list
+            || ["join", "update", "into"]
+                .iter()
+                .any(|k| parts[i].is_word(k)))
+        {
+            i += 1;
+            continue;
+        }
+        i += 1;
+        while let Some((name, next)) = qualified_name(&parts, i) {
+            if !tables.contains(&name) {
+                tables.push(name);
+            }
+            i = next;
+            if !list {
+                break;
+            }
+            // Skip an alias, then continue a comma-separated FROM list
+            if parts.get(i).is_some_and(|p| p.is_word("as")) {
+                i += 1;
+            }
+            if matches!(parts.get(i), Some(Part::Word(w)) if !is_clause_keyword(w)) {
+                i += 1;
+            }
+            if !parts.get(i).is_some_and(|p| p.is_symbol(",")) {
+                break;
+            }
+            i += 1;
+        }
+    }
+    tables
+}
+
+/// Whether a query modifies data: it inserts, updates, deletes, merges, or
+/// truncates, possibly inside a CTE
+///
+/// Row locks (`FOR UPDATE`, `FOR NO KEY UPDATE`) don't make a read a write.
+/// Queries that can't be tokenized count as reads.
+pub fn is_write(sql: &str, dialect: SqlDialect) -> bool {
+    let Ok(tokens) = Tokenizer::new(dialect.parser_dialect().as_ref(), sql).tokenize() else {
+        return false;
+    };
+    let parts: Vec<Part> = tokens.into_iter().filter_map(to_part).collect();
+
+    parts.iter().enumerate().any(|(i, part)| match part {
+        Part::Word(w) if w == "update" => {
+            !(i > 0 && (parts[i - 1].is_word("for") || parts[i - 1].is_word("key")))
+        }
+        Part::Word(w) => matches!(w.as_str(), "insert" | "delete" | "merge" | "truncate"),
+        _ => false,
+    })
+}
+
+/// Kind of statement a query is, from its leading keyword
+///
+/// A `WITH` query takes the kind of the statement after its CTEs, so
+/// `WITH ... DELETE FROM` is a delete even when a CTE selects, and a CTE
+/// that writes doesn't change a final `SELECT`. Queries that can't be
+/// tokenized are `Other`.
+pub fn classify(sql: &str, dialect: SqlDialect) -> QueryKind {
+    let Ok(tokens) = Tokenizer::new(dialect.parser_dialect().as_ref(), sql).tokenize() else {
+        return QueryKind::Other;
+    };
+    let parts: Vec<Part> = tokens.into_iter().filter_map(to_part).collect();
+
+    // A parenthesized leading query, e.g. `(SELECT ...) UNION (SELECT ...)`
+    let mut words = parts.iter().skip_while(|p| p.is_symbol("("));
+    let first = match words.next() {
+        Some(Part::Word(w)) if w == "with" => main_statement(&parts),
+        Some(Part::Word(w)) => Some(w.as_str()),
+        _ => None,
+    };
+    match first {
+        Some("select" | "values" | "table") => QueryKind::Select,
+        Some("insert" | "replace" | "upsert") => QueryKind::Insert,
+        Some("update") => QueryKind::Update,
+        Some("delete") => QueryKind::Delete,
+        Some("create" | "alter" | "drop" | "truncate" | "rename" | "comment") => QueryKind::Ddl,
+        _ => QueryKind::Other,
+    }
+}
+
+/// Leading keyword of the statement a `WITH` query's CTEs feed: the first
+/// statement keyword outside the CTE bodies' parentheses
+fn main_statement(parts: &[Part]) -> Option<&str> {
+    let mut depth = 0usize;
+    for part in parts {
+        match part {
+            Part::Symbol(s) if s == "(" => depth += 1,
+            Part::Symbol(s) if s == ")" => depth = depth.saturating_sub(1),
+            Part::Word(w)
+                if depth == 0
+                    && matches!(
+                        w.as_str(),
+                        "select" | "values" | "table" | "insert" | "update" | "delete" | "merge"
+                    ) =>
+            {
+                return Some(w);
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `name` or `schema.name` starting at `start`, with the index after it
+fn qualified_name(parts: &[Part], start: usize) -> Option<(String, usize)> {
+    let Some(Part::Word(first)) = parts.get(start) else {
+        return None;
+    };
+    if is_clause_keyword(first) {
+        return None;
+    }
+    let mut name = first.clone();
+    let mut i = start + 1;
+    while parts.get(i).is_some_and(|p| p.is_symbol(".")) {
+        match parts.get(i + 1) {
+            Some(Part::Word(w)) => {
+                name.push('.');
+                name.push_str(w);
+                i += 2;
+            }
+            _ => break,
+        }
+    }
+    Some((name, i))
+}
+
+/// Keywords that can follow a table name, so are never an alias or a table
+fn is_clause_keyword(word: &str) -> bool {
+    matches!(
+        word,
+        "select"
+            | "where"
+            | "join"
+            | "inner"
+            | "left"
+            | "right"
+            | "full"
+            | "cross"
+            | "outer"
+            | "on"
+            | "using"
+            | "group"
+            | "order"
+            | "having"
+            | "limit"
+            | "offset"
+            | "union"
+            | "set"
+            | "values"
+            | "returning"
+            | "lateral"
+    )
+}
+
+fn to_part(token: Token) -> Option<Part> {
+    match token {
+        Token::EOF | Token::Whitespace(_) => None,
+        // SQL Server named parameters tokenize as identifiers
+        Token::Word(w) if w.quote_style.is_none() && w.value.starts_with('@') => {
+            Some(Part::Value("?".into()))
+        }
+        Token::Word(w) => {
+            let value = w.value.to_lowercase();
+            if w.quote_style.is_none() || value.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                Some(Part::Word(value))
+            } else {
+                Some(Part::Word(format!("\"{}\"", value)))
+            }
+        }
+        Token::Number(..)
+        | Token::Placeholder(_)
+        | Token::SingleQuotedString(_)
+        | Token::DoubleQuotedString(_)
+        | Token::TripleSingleQuotedString(_)
+        | Token::TripleDoubleQuotedString(_)
+        | Token::DollarQuotedString(_)
+        | Token::SingleQuotedByteStringLiteral(_)
+        | Token::DoubleQuotedByteStringLiteral(_)
+        | Token::TripleSingleQuotedByteStringLiteral(_)
+        | Token::TripleDoubleQuotedByteStringLiteral(_)
+        | Token::SingleQuotedRawStringLiteral(_)
+        | Token::DoubleQuotedRawStringLiteral(_)
+        | Token::TripleSingleQuotedRawStringLiteral(_)
+        | Token::TripleDoubleQuotedRawStringLiteral(_)
+        | Token::NationalStringLiteral(_)
+        | Token::EscapedStringLiteral(_)
+        | Token::UnicodeStringLiteral(_)
+        | Token::HexStringLiteral(_) => Some(Part::Value("?".into())),
+        other => Some(Part::Symbol(other.to_string())),
+    }
+}
+
+/// Replace `in ( <values> )` with `in (...)` when the list holds only literals or placeholders
+fn collapse_in_lists(parts: Vec<Part>) -> Vec<Part> {
+    let mut out = Vec::with_capacity(parts.len());
+    let mut i = 0;
+
+    while i < parts.len() {
+        if parts[i].is_word("in") && parts.get(i + 1).is_some_and(|p| p.is_symbol("(")) {
+            if let Some(close) = value_list_end(&parts, i + 2) {
+                out.push(Part::Word("in".into()));
+                out.push(Part::ValueList);
+                i = close + 1;
+                continue;
+            }
+        }
+        out.push(parts[i].clone());
+        i += 1;
+    }
+
+    out
+}
+
+/// Index of the `)` closing a list of values starting at `start`, if the list is values only
+fn value_list_end(parts: &[Part], start: usize) -> Option<usize> {
+    let mut has_value = false;
+
+    for (offset, part) in parts[start..].iter().enumerate() {
+        match part {
+            Part::Symbol(s) if s == ")" => return has_value.then_some(start + offset),
+            Part::Symbol(s) if s == "," || s == "-" || s == "+" => {}
+            Part::Value(_) | Part::ValueList => has_value = true,
+            Part::Word(w) if w == "null" || w == "true" || w == "false" => has_value = true,
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+/// Replace `col = v1 or col = v2 [or ...]` with `col in (...)`
+fn collapse_or_chains(parts: Vec<Part>) -> Vec<Part> {
+    let mut out: Vec<Part> = Vec::with_capacity(parts.len());
+    let mut i = 0;
+
+    while i < parts.len() {
+        if starts_predicate(out.last()) {
+            if let Some((column_end, mut end)) = equality_term(&parts, i) {
+                let column = &parts[i..column_end];
+                let mut terms = 1;
+
+                while parts.get(end).is_some_and(|p| p.is_word("or")) {
+                    match equality_term(&parts, end + 1) {
+                        Some((next_column_end, next_end))
+                            if parts[end + 1..next_column_end] == *column =>
+                        {
+                            terms += 1;
+                            end = next_end;
+                        }
+                        _ => break,
+                    }
+                }
+
+                // AND binds tighter than OR, so a trailing AND belongs to the last term only
+                if terms > 1 && !parts.get(end).is_some_and(|p| p.is_word("and")) {
+                    out.extend_from_slice(column);
+                    out.push(Part::Word("in".into()));
+                    out.push(Part::ValueList);
+                    i = end;
+                    continue;
+                }
+            }
+        }
+        out.push(parts[i].clone());
+        i += 1;
+    }
+
+    out
+}
+
+/// Whether a predicate may start right after `prev`
+fn starts_predicate(prev: Option<&Part>) -> bool {
+    match prev {
+        None => true,
+        Some(Part::Symbol(s)) => s == "(",
+        // Not after AND, which would bind tighter than the chain's ORs
+        Some(Part::Word(w)) => matches!(w.as_str(), "where" | "or" | "on" | "having"),
+        Some(_) => false,
+    }
+}
+
+/// Match `<column> = <value>` at `start`, returning the end of the column and of the term
+fn equality_term(parts: &[Part], start: usize) -> Option<(usize, usize)> {
+    let mut i = start;
+
+    // Column reference, optionally qualified: a.b.c
+    if !matches!(parts.get(i), Some(Part::Word(_))) {
+        return None;
+    }
+    i += 1;
+    while parts.get(i).is_some_and(|p| p.is_symbol("."))
+        && matches!(parts.get(i + 1), Some(Part::Word(_)))
+    {
+        i += 2;
+    }
+    let column_end = i;
+
+    if !parts.get(i).is_some_and(|p| p.is_symbol("=")) {
+        return None;
+    }
+    i += 1;
+
+    if parts.get(i).is_some_and(|p| p.is_symbol("-")) {
+        i += 1;
+    }
+    if !matches!(parts.get(i), Some(Part::Value(_))) {
+        return None;
+    }
+    i += 1;
+
+    // The value must not continue into a larger expression (casts, arithmetic, ...)
+    match parts.get(i) {
+        None | Some(Part::Word(_)) => Some((column_end, i)),
+        Some(p) if p.is_symbol(")") => Some((column_end, i)),
+        Some(_) => None,
+    }
+}
+
+fn render(parts: &[Part]) -> String {
+    let mut out = String::new();
+    let mut prev: Option<&Part> = None;
+
+    for part in parts {
+        if let Some(prev) = prev {
+            let tight = prev.is_symbol("(")
+                || prev.is_symbol(".")
+                || part.is_symbol(")")
+                || part.is_symbol(",")
+                || part.is_symbol(".");
+            if !tight {
+                out.push(' ');
+            }
+        }
+        out.push_str(part.text());
+        prev = Some(part);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generic(sql: &str) -> String {
+        normalize(sql, SqlDialect::Generic)
+    }
+
+    #[test]
+    fn test_case_whitespace_and_comments_fold() {
+        assert_eq!(
+            generic("SELECT  *\n FROM Users -- all of them\n;"),
+            "select * from users"
+        );
+        assert_eq!(
+            fingerprint("SELECT 1", SqlDialect::Generic),
+            "1fe1379fe2a31b8d16219655761820a2"
+        );
+    }
+
+    #[test]
+    fn test_in_lists_of_any_length_collapse() {
+        let short = "SELECT * FROM users WHERE id IN (1, 2)";
+        let long = "select * from users where id in (1,2,3,4,5,6,7,8)";
+        let params = "SELECT * FROM users WHERE id IN ($1, $2, $3)";
+
+        assert_eq!(generic(short), "select * from users where id in (...)");
+        assert_eq!(generic(short), generic(long));
+        assert_eq!(generic(short), generic(params));
+    }
+
+    #[test]
+    fn test_or_chains_collapse_to_in_list() {
+        let chain = "SELECT * FROM users WHERE (u.id = 1 OR u.id = 2 OR u.id = 3)";
+        let in_list = "SELECT * FROM users WHERE (u.id IN (7, 8))";
+
+        assert_eq!(generic(chain), generic(in_list));
+    }
+
+    #[test]
+    fn test_structurally_different_queries_stay_distinct() {
+        // Subqueries are not value lists
+        assert_ne!(
+            generic("SELECT * FROM t WHERE id IN (SELECT id FROM u)"),
+            generic("SELECT * FROM t WHERE id IN (1, 2)")
+        );
+        // Chains over different columns are not collapsed
+        assert_eq!(
+            generic("SELECT * FROM t WHERE a = 1 OR b = 2"),
+            "select * from t where a = ? or b = ?"
+        );
+        // A trailing AND binds to the last term only
+        assert_eq!(
+            generic("SELECT * FROM t WHERE a = 1 OR a = 2 AND b = 3"),
+            "select * from t where a = ? or a = ? and b = ?"
+        );
+    }
+
+    #[test]
+    fn test_literals_fold_to_placeholders() {
+        let expected = "select * from users where id = ? and email = ?";
+
+        assert_eq!(
+            generic("SELECT * FROM users WHERE id = 1 AND email = 'a@example.com'"),
+            expected
+        );
+        assert_eq!(
+            generic("SELECT * FROM users WHERE id = 2.5 AND email = 'b@example.com'"),
+            expected
+        );
+        assert_eq!(
+            normalize(
+                "SELECT * FROM users WHERE id = $1 AND email = E'c\\'d'",
+                SqlDialect::Postgres
+            ),
+            expected
+        );
+        // Text that looks like SQL inside a string is just a literal
+        assert_eq!(generic("SELECT 'id IN (1, 2)'"), "select ?");
+    }
+
+    #[test]
+    fn test_dialects_agree_on_quoting_and_placeholders() {
+        let expected = "select name from dbo.users where id = ?";
+
+        assert_eq!(
+            normalize(
+                "SELECT name FROM [dbo].[Users] WHERE id = @p1",
+                SqlDialect::SqlServer
+            ),
+            expected
+        );
+        assert_eq!(
+            normalize(
+                "SELECT `name` FROM dbo.`users` WHERE id = ?",
+                SqlDialect::MySql
+            ),
+            expected
+        );
+        assert_eq!(
+            normalize(
+                "SELECT \"name\" FROM dbo.users WHERE id = $1",
+                SqlDialect::Postgres
+            ),
+            expected
+        );
+        assert_eq!(
+            normalize(
+                "SELECT name FROM dbo.users WHERE id = ?1",
+                SqlDialect::Sqlite
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_dialect_config_per_service() {
+        let service = Uuid::new_v4();
+        let config =
+            DialectConfig::new(SqlDialect::Postgres, &format!(" {}=mssql, ", service)).unwrap();
+
+        assert_eq!(config.dialect_for(service), SqlDialect::SqlServer);
+        assert_eq!(config.dialect_for(Uuid::new_v4()), SqlDialect::Postgres);
+        assert!(DialectConfig::new(SqlDialect::Generic, "not-a-pair").is_err());
+        assert!("oracle".parse::<SqlDialect>().is_err());
+    }
+
+    #[test]
+    fn test_referenced_tables() {
+        let tables = |sql| referenced_tables(sql, SqlDialect::Postgres);
+        assert_eq!(
+            tables(
+                "SELECT * FROM public.orders o, customers AS c \
+                 LEFT JOIN \"Items\" i ON i.order_id = o.id \
+                 WHERE o.id IN (SELECT order_id FROM refunds)"
+            ),
+            ["public.orders", "customers", "items", "refunds"]
+        );
+        assert_eq!(
+            tables("UPDATE users SET name = $1 WHERE id = $2"),
+            ["users"]
+        );
+        assert_eq!(
+            tables("INSERT INTO audit (id) SELECT id FROM users"),
+            ["audit", "users"]
+        );
+    }
+
+    #[test]
+    fn test_writes_detected() {
+        let write = |sql| is_write(sql, SqlDialect::Postgres);
+        assert!(write("INSERT INTO audit (id) SELECT id FROM users"));
+        assert!(write("delete from sessions where expires_at < now()"));
+        assert!(write(
+            "WITH moved AS (DELETE FROM queue RETURNING *) SELECT count(*) FROM moved"
+        ));
+        assert!(write("UPDATE users SET name = $1 WHERE id = $2"));
+        assert!(!write(
+            "SELECT * FROM jobs WHERE id = $1 FOR UPDATE SKIP LOCKED"
+        ));
+        assert!(!write("SELECT * FROM jobs FOR NO KEY UPDATE"));
+        assert!(!write("SELECT 'insert'"));
+    }
+
+    #[test]
+    fn test_statements_classified() {
+        let kind = |sql| classify(sql, SqlDialect::Postgres);
+        assert_eq!(kind("select * from users"), QueryKind::Select);
+        assert_eq!(
+            kind("(SELECT id FROM a) UNION (SELECT id FROM b)"),
+            QueryKind::Select
+        );
+        assert_eq!(
+            kind("-- hourly\nINSERT INTO audit (id) SELECT id FROM users"),
+            QueryKind::Insert
+        );
+        assert_eq!(kind("UPDATE users SET name = $1"), QueryKind::Update);
+        assert_eq!(
+            kind("WITH old AS (SELECT id FROM jobs WHERE done) DELETE FROM jobs USING old"),
+            QueryKind::Delete
+        );
+        assert_eq!(
+            kind("WITH moved AS (DELETE FROM queue RETURNING *) SELECT count(*) FROM moved"),
+            QueryKind::Select
+        );
+        assert_eq!(kind("ALTER TABLE users ADD COLUMN age int"), QueryKind::Ddl);
+        assert_eq!(kind("truncate sessions"), QueryKind::Ddl);
+        assert_eq!(kind("BEGIN"), QueryKind::Other);
+        assert_eq!(kind(""), QueryKind::Other);
+    }
+
+    #[test]
+    fn test_untokenizable_query_falls_back() {
+        assert_eq!(generic("SELECT 'unterminated"), "select 'unterminated");
+    }
+}
@@ -0,0 +1,449 @@
+//! Hybrid search: vector and lexical matches fused by rank
+//!
+//! Embeddings capture what a query does but blur exact identifiers, so a
+//! search for `order_items` can rank queries on similar tables above the ones
+//! on `order_items` itself. Hybrid search also matches the identifiers in the
+//! search text against stored queries with Postgres full-text search, then
+//! merges both rankings with reciprocal rank fusion: each result scores
+//! `1 / (RRF_K + rank)` per list it appears in, so results found by both
+//! rank first and neither list's scale dominates.
+//!
+//! Search text can also be prose, such as "queries that lock the accounts
+//! table". It is embedded as written, and its lexical terms are keywords:
+//! the words left once filler is dropped, with words for what a query does
+//! mapped to the SQL that does it (`lock` to `FOR UPDATE`).
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::db::SimilarQuery;
+
+/// Damping constant of reciprocal rank fusion; 60 is the usual choice and
+/// keeps the top few ranks of each list from dominating
+pub const RRF_K: f64 = 60.0;
+
+/// SQL keywords left out of lexical matching, as nearly every query has them
+const SQL_KEYWORDS: &[&str] = &[
+    "all",
+    "and",
+    "as",
+    "asc",
+    "between",
+    "by",
+    "case",
+    "count",
+    "delete",
+    "desc",
+    "distinct",
+    "else",
+    "end",
+    "exists",
+    "false",
+    "from",
+    "full",
+    "group",
+    "having",
+    "in",
+    "inner",
+    "insert",
+    "into",
+    "is",
+    "join",
+    "left",
+    "like",
+    "limit",
+    "not",
+    "null",
+    "offset",
+    "on",
+    "or",
+    "order",
+    "outer",
+    "returning",
+    "right",
+    "select",
+    "set",
+    "then",
+    "true",
+    "union",
+    "update",
+    "using",
+    "values",
+    "when",
+    "where",
+    "with",
+];
+
+/// Keywords that start a SQL statement
+const SQL_STATEMENTS: &[&str] = &[
+    "alter", "call", "copy", "create", "delete", "drop", "explain", "insert", "merge", "select",
+    "show", "truncate", "update", "values", "with",
+];
+
+/// Prose words left out of keywords: English filler and words that describe
+/// every query, like "table"
+const STOPWORDS: &[&str] = &[
+    "a",
+    "about",
+    "all",
+    "an",
+    "and",
+    "any",
+    "are",
+    "as",
+    "at",
+    "be",
+    "by",
+    "column",
+    "columns",
+    "data",
+    "database",
+    "do",
+    "does",
+    "find",
+    "for",
+    "from",
+    "get",
+    "has",
+    "have",
+    "in",
+    "into",
+    "is",
+    "it",
+    "its",
+    "me",
+    "of",
+    "on",
+    "or",
+    "query",
+    "queries",
+    "row",
+    "rows",
+    "show",
+    "sql",
+    "statement",
+    "statements",
+    "table",
+    "tables",
+    "that",
+    "the",
+    "their",
+    "them",
+    "these",
+    "this",
+    "those",
+    "to",
+    "use",
+    "uses",
+    "using",
+    "was",
+    "were",
+    "what",
+    "where",
+    "which",
+    "who",
+    "with",
+];
+
+/// Prose words for what a query does, with the SQL that does it as lexical
+/// terms (`_` joins the words of a phrase)
+const INTENTS: &[(&[&str], &[&str])] = &[
+    (
+        &["lock", "locks", "locking", "locked"],
+        &["for_update", "for_share", "lock"],
+    ),
+    (
+        &[
+            "insert",
+            "inserts",
+            "inserting",
+            "write",
+            "writes",
+            "writing",
+            "create",
+            "creates",
+        ],
+        &["insert"],
+    ),
+    (
+        &[
+            "update", "updates", "updating", "modify", "modifies", "change", "changes",
+        ],
+        &["update"],
+    ),
+    (
+        &[
+            "delete", "deletes", "deleting", "remove", "removes", "removing", "purge", "purges",
+        ],
+        &["delete"],
+    ),
+    (
+        &["upsert", "upserts", "conflict", "conflicts"],
+        &["on_conflict"],
+    ),
+    (&["join", "joins", "joining", "joined"], &["join"]),
+    (
+        &["sort", "sorts", "sorted", "sorting", "ordered"],
+        &["order_by"],
+    ),
+    (
+        &[
+            "group",
+            "groups",
+            "grouped",
+            "grouping",
+            "aggregate",
+            "aggregates",
+        ],
+        &["group_by"],
+    ),
+    (
+        &[
+            "paginate",
+            "paginates",
+            "paginated",
+            "pagination",
+            "page",
+            "pages",
+        ],
+        &["limit", "offset"],
+    ),
+    (&["count", "counts", "counting"], &["count"]),
+];
+
+/// What search text is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryInput {
+    /// A SQL statement, or part of one
+    Sql,
+    /// A description in prose
+    Text,
+}
+
+impl QueryInput {
+    /// Tell SQL from prose: SQL starts with a statement keyword and has an
+    /// operator, a parameter or a clause after it
+    pub fn detect(text: &str) -> Self {
+        let lower = text.trim_start_matches(|c: char| c.is_whitespace() || c == '(');
+        let lower = lower.to_lowercase();
+        let mut words = lower.split(|c: char| !(c.is_alphanumeric() || c == '_'));
+        let starts_with_statement = words
+            .next()
+            .is_some_and(|first| SQL_STATEMENTS.contains(&first));
+        let has_clause = lower.contains(['*', '=', '(', '$', '?', ';'])
+            || words.any(|w| ["from", "into", "set", "where", "as"].contains(&w));
+        if starts_with_statement && has_clause {
+            QueryInput::Sql
+        } else {
+            QueryInput::Text
+        }
+    }
+}
+
+/// How similarity search ranks results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMode {
+    /// Cosine similarity of embeddings only
+    #[default]
+    Vector,
+    /// Cosine similarity fused with full-text matches on identifiers
+    Hybrid,
+}
+
+/// A search result with its place in each ranking
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchMatch {
+    #[serde(flatten)]
+    pub query: SimilarQuery,
+    /// Fused score (hybrid mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    /// 1-based rank by cosine similarity, if the query was among the vector
+    /// matches (hybrid mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vector_rank: Option<usize>,
+    /// 1-based rank by full-text relevance, if the query matched lexically
+    /// (hybrid mode)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lexical_rank: Option<usize>,
+}
+
+impl From<SimilarQuery> for SearchMatch {
+    fn from(query: SimilarQuery) -> Self {
+        Self {
+            query,
+            score: None,
+            vector_rank: None,
+            lexical_rank: None,
+        }
+    }
+}
+
+/// Identifiers in search text worth matching lexically, lowercased and
+/// deduplicated in order: table and column names, not keywords or numbers
+pub fn lexical_terms(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map(|word| word.trim_matches('_').to_lowercase())
+        .filter(|word| {
+            !word.is_empty()
+                && !word.chars().all(|c| c.is_ascii_digit())
+                && !SQL_KEYWORDS.contains(&word.as_str())
+        })
+        .filter(|word| seen.insert(word.clone()))
+        .collect()
+}
+
+/// Keywords of prose search text worth matching lexically, lowercased and
+/// deduplicated in order: words left once filler is dropped, with words for
+/// what a query does replaced by the SQL that does it
+pub fn keywords(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map(|word| word.trim_matches('_').to_lowercase())
+        .filter(|word| {
+            word.len() > 1
+                && !word.chars().all(|c| c.is_ascii_digit())
+                && !STOPWORDS.contains(&word.as_str())
+        })
+        .flat_map(|word| {
+            match INTENTS
+                .iter()
+                .find(|(words, _)| words.contains(&word.as_str()))
+            {
+                Some((_, terms)) => terms.iter().map(|t| t.to_string()).collect(),
+                None => vec![word],
+            }
+        })
+        .filter(|term| seen.insert(term.clone()))
+        .collect()
+}
+
+/// Terms of search text to match lexically: identifiers of SQL, keywords of
+/// prose
+pub fn search_terms(text: &str, input: QueryInput) -> Vec<String> {
+    match input {
+        QueryInput::Sql => lexical_terms(text),
+        QueryInput::Text => keywords(text),
+    }
+}
+
+/// The alphanumeric parts of an identifier, in order, as full-text search
+/// splits it: `order_items` is `order` followed by `items`
+pub fn term_parts(term: &str) -> impl Iterator<Item = &str> {
+    term.split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+}
+
+/// Merge vector and lexical matches, each best first, into the `limit` best
+/// by reciprocal rank fusion
+pub fn reciprocal_rank_fusion(
+    vector: Vec<SimilarQuery>,
+    lexical: Vec<SimilarQuery>,
+    limit: usize,
+) -> Vec<SearchMatch> {
+    let mut matches: HashMap<Uuid, SearchMatch> = HashMap::new();
+    for (list, is_vector) in [(vector, true), (lexical, false)] {
+        for (i, query) in list.into_iter().enumerate() {
+            let rank = i + 1;
+            let entry = matches
+                .entry(query.id)
+                .or_insert_with(|| SearchMatch::from(query));
+            *entry.score.get_or_insert(0.0) += 1.0 / (RRF_K + rank as f64);
+            if is_vector {
+                entry.vector_rank = Some(rank);
+            } else {
+                entry.lexical_rank = Some(rank);
+            }
+        }
+    }
+
+    let mut fused: Vec<SearchMatch> = matches.into_values().collect();
+    fused.sort_by(|a, b| {
+        b.score
+            .unwrap_or(0.0)
+            .total_cmp(&a.score.unwrap_or(0.0))
+            .then_with(|| b.query.similarity.total_cmp(&a.query.similarity))
+            .then_with(|| a.query.id.cmp(&b.query.id))
+    });
+    fused.truncate(limit);
+    fused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(id: u128, similarity: f64) -> SimilarQuery {
+        SimilarQuery {
+            id: Uuid::from_u128(id),
+            sql_query: format!("query {}", id),
+            similarity,
+        }
+    }
+
+    #[test]
+    fn test_lexical_terms_keep_identifiers() {
+        assert_eq!(
+            lexical_terms("SELECT id, Email FROM public.users u JOIN order_items ON u.id = 42"),
+            ["id", "email", "public", "users", "u", "order_items"]
+        );
+        assert!(lexical_terms("SELECT * FROM").is_empty());
+        assert_eq!(
+            term_parts("order_items").collect::<Vec<_>>(),
+            ["order", "items"]
+        );
+    }
+
+    #[test]
+    fn test_prose_is_searched_by_keywords() {
+        assert_eq!(
+            QueryInput::detect("SELECT * FROM users WHERE id = $1"),
+            QueryInput::Sql
+        );
+        assert_eq!(
+            QueryInput::detect("update orders set status = 'paid'"),
+            QueryInput::Sql
+        );
+        assert_eq!(
+            QueryInput::detect("queries that lock the accounts table"),
+            QueryInput::Text
+        );
+        assert_eq!(
+            QueryInput::detect("update statements on invoices"),
+            QueryInput::Text
+        );
+
+        assert_eq!(
+            keywords("Queries that lock the accounts table"),
+            ["for_update", "for_share", "lock", "accounts"]
+        );
+        assert_eq!(
+            search_terms("slow paginated reports on order_items", QueryInput::Text),
+            ["slow", "limit", "offset", "reports", "order_items"]
+        );
+        assert_eq!(
+            search_terms("SELECT * FROM accounts WHERE id = $1", QueryInput::Sql),
+            ["accounts", "id"]
+        );
+    }
+
+    #[test]
+    fn test_matches_in_both_lists_rank_first() {
+        // 3 is only a middling vector match but the sole lexical one
+        let vector = vec![query(1, 0.95), query(2, 0.93), query(3, 0.90)];
+        let lexical = vec![query(3, 0.90), query(4, 0.40)];
+        let fused = reciprocal_rank_fusion(vector, lexical, 3);
+
+        let ids: Vec<u128> = fused.iter().map(|m| m.query.id.as_u128()).collect();
+        assert_eq!(ids, [3, 1, 2]);
+        assert_eq!(fused[0].vector_rank, Some(3));
+        assert_eq!(fused[0].lexical_rank, Some(1));
+        assert_eq!(fused[1].lexical_rank, None);
+        let expected = 1.0 / (RRF_K + 3.0) + 1.0 / (RRF_K + 1.0);
+        assert!((fused[0].score.unwrap() - expected).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,542 @@
+//! Slack and PagerDuty renderings of alerts
+//!
+//! Webhook destinations receive an event's payload as is. Slack and PagerDuty
+//! expect their own formats, so the payload is first boiled down to an
+//! [`Alert`]: a title, a severity, the facts worth showing, and a key that
+//! ties a later recovery to the alert it resolves. Severities map as follows:
+//!
+//! | Event                     | Severity                                      |
+//! |---------------------------|-----------------------------------------------|
+//! | `anomaly.detected`        | The anomaly's severity level                  |
+//! | `anomaly.throughput_drop` | High; resolved when the service recovers      |
+//! | `slo.burn_rate`           | Fast burn critical, slow high; resolved at ok |
+//! | `alert_rule.state`        | High; resolved when the rule resolves         |
+//! | `metrics.quarantined`     | High                                          |
+//! | `query.new_shape`         | Low (informational)                           |
+//! | anything else             | Medium                                        |
+//!
+//! PagerDuty severities are `critical`, `error` (high), `warning` (medium),
+//! and `info` (low).
+
+use serde_json::{json, Map, Value};
+
+use crate::services::notify::{
+    ALERT_RULE_EVENT, ANOMALY_EVENT, NEW_SHAPE_EVENT, QUARANTINE_EVENT, SLO_BURN_EVENT,
+    THROUGHPUT_DROP_EVENT,
+};
+use crate::services::severity::SeverityLevel;
+
+/// PagerDuty Events API v2 endpoint, the default URL of PagerDuty destinations
+pub const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// Longest Slack header text
+const MAX_HEADER_CHARS: usize = 150;
+
+/// Most of a query shown in a Slack message (section text is capped at 3000)
+const MAX_QUERY_CHARS: usize = 2900;
+
+/// Longest PagerDuty event summary
+const MAX_SUMMARY_CHARS: usize = 1024;
+
+/// What an alert is about, independent of where it's sent
+#[derive(Debug, Clone)]
+pub struct Alert {
+    /// Event name, e.g. `anomaly.detected`
+    pub event: String,
+    pub title: String,
+    pub severity: SeverityLevel,
+    /// Identifies the problem across alerts, so a recovery resolves it
+    pub dedup_key: String,
+    /// The problem is over
+    pub resolved: bool,
+    /// Labelled facts, in display order
+    pub fields: Vec<(String, String)>,
+    pub query_text: Option<String>,
+    pub workspace_id: Option<String>,
+    /// Where to look at the problem in QueryVault
+    pub link: Option<String>,
+    pub sent_at: Option<String>,
+    pub test: bool,
+}
+
+/// Summarize an event's payload. Links are only made when `public_url`, the
+/// address QueryVault is reachable at, is known.
+pub fn summarize(event: &str, payload: &Value, public_url: Option<&str>) -> Alert {
+    let mut alert = Alert {
+        event: event.to_string(),
+        title: event.to_string(),
+        severity: SeverityLevel::Medium,
+        dedup_key: event.to_string(),
+        resolved: false,
+        fields: Vec::new(),
+        query_text: None,
+        workspace_id: None,
+        link: None,
+        sent_at: payload["sent_at"].as_str().map(str::to_string),
+        test: payload["test"].as_bool().unwrap_or(false),
+    };
+
+    match event {
+        ANOMALY_EVENT => {
+            let anomaly = &payload["anomaly"];
+            let id = text(&anomaly["id"]);
+            let workspace_id = text(&anomaly["workspace_id"]);
+            alert.title = format!(
+                "Slow query: {} ms against a {} ms baseline",
+                text(&anomaly["duration_ms"]),
+                text(&anomaly["mean_duration_ms"])
+            );
+            alert.severity = anomaly["severity_level"]
+                .as_str()
+                .and_then(|level| level.parse().ok())
+                .unwrap_or_else(|| {
+                    SeverityLevel::from_score(anomaly["severity"].as_f64().unwrap_or_default())
+                });
+            alert.dedup_key = format!("anomaly:{}", id);
+            alert.fields = vec![
+                ("Z-score".into(), number(&anomaly["z_score"])),
+                (
+                    "Duration".into(),
+                    format!("{} ms", text(&anomaly["duration_ms"])),
+                ),
+                (
+                    "Baseline".into(),
+                    format!(
+                        "{} ms ± {} ms",
+                        text(&anomaly["mean_duration_ms"]),
+                        text(&anomaly["stddev_duration_ms"])
+                    ),
+                ),
+                ("Severity".into(), alert.severity.as_str().to_string()),
+                ("Method".into(), text(&anomaly["method"])),
+                ("Fingerprint".into(), text(&anomaly["fingerprint"])),
+            ];
+            alert.query_text = anomaly["query_text"].as_str().map(str::to_string);
+            alert.link = public_url.map(|url| {
+                format!(
+                    "{}/api/v1/workspaces/{}/anomalies/{}/context",
+                    url.trim_end_matches('/'),
+                    workspace_id,
+                    id
+                )
+            });
+            alert.workspace_id = Some(workspace_id);
+        }
+        THROUGHPUT_DROP_EVENT => {
+            let drop = &payload["drop"];
+            let service = payload["service_name"]
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| text(&drop["service_id"]));
+            alert.resolved = payload["recovered"].as_bool().unwrap_or(false);
+            alert.title = if alert.resolved {
+                format!("Query volume recovered for {}", service)
+            } else {
+                format!("Query volume dropped for {}", service)
+            };
+            alert.severity = SeverityLevel::High;
+            alert.dedup_key = format!("throughput_drop:{}", text(&drop["id"]));
+            alert.fields = vec![
+                ("Service".into(), service),
+                (
+                    "Baseline".into(),
+                    format!("{}/min", number(&drop["baseline_per_minute"])),
+                ),
+                (
+                    "Recent".into(),
+                    format!("{}/min", number(&drop["recent_per_minute"])),
+                ),
+                ("Started".into(), text(&drop["started_at"])),
+            ];
+            alert.workspace_id = Some(text(&drop["workspace_id"]));
+        }
+        SLO_BURN_EVENT => {
+            let slo = &payload["slo"];
+            let name = text(&slo["name"]);
+            let (title, severity) = match payload["burn_state"].as_str() {
+                Some("fast_burn") => (
+                    format!("SLO '{}' is burning its error budget fast", name),
+                    SeverityLevel::Critical,
+                ),
+                Some("slow_burn") => (
+                    format!("SLO '{}' is burning its error budget", name),
+                    SeverityLevel::High,
+                ),
+                _ => {
+                    alert.resolved = true;
+                    (
+                        format!("SLO '{}' is back within budget", name),
+                        SeverityLevel::Low,
+                    )
+                }
+            };
+            alert.title = title;
+            alert.severity = severity;
+            alert.dedup_key = format!("slo:{}", text(&slo["id"]));
+            alert.fields = vec![
+                ("Burn rate (1h)".into(), number(&payload["burn_rate_1h"])),
+                ("Burn rate (6h)".into(), number(&payload["burn_rate_6h"])),
+                (
+                    "Compliance".into(),
+                    format!("{}%", number(&payload["compliance_percent"])),
+                ),
+                (
+                    "Budget left".into(),
+                    format!("{}%", number(&payload["error_budget_remaining_percent"])),
+                ),
+            ];
+            alert.workspace_id = Some(text(&slo["workspace_id"]));
+        }
+        ALERT_RULE_EVENT => {
+            let rule = &payload["rule"];
+            let name = text(&rule["name"]);
+            alert.resolved = !payload["firing"].as_bool().unwrap_or(true);
+            alert.title = if alert.resolved {
+                format!("Alert rule '{}' resolved", name)
+            } else {
+                format!("Alert rule '{}' firing", name)
+            };
+            alert.severity = SeverityLevel::High;
+            alert.dedup_key = format!("alert_rule:{}", text(&rule["id"]));
+            alert.fields = payload["signals"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|signal| {
+                    let comparison = match signal["comparison"].as_str() {
+                        Some("lt") => "<",
+                        _ => ">",
+                    };
+                    (
+                        text(&signal["signal"]),
+                        format!(
+                            "{} ({} {})",
+                            number(&signal["value"]),
+                            comparison,
+                            number(&signal["threshold"])
+                        ),
+                    )
+                })
+                .collect();
+            alert.fields.push((
+                "Window".into(),
+                format!(
+                    "{} from {}",
+                    text(&rule["window"]),
+                    text(&payload["window_start"])
+                ),
+            ));
+            alert.workspace_id = Some(text(&rule["workspace_id"]));
+        }
+        QUARANTINE_EVENT => {
+            let workspace_id = text(&payload["workspace_id"]);
+            alert.title = format!(
+                "{} metrics quarantined on schema drift",
+                text(&payload["quarantined_count"])
+            );
+            alert.severity = SeverityLevel::High;
+            alert.dedup_key = format!("quarantine:{}", workspace_id);
+            alert.fields = vec![("Error".into(), text(&payload["error"]))];
+            alert.workspace_id = Some(workspace_id);
+        }
+        NEW_SHAPE_EVENT => {
+            let workspace_id = text(&payload["workspace_id"]);
+            let first = &payload["queries"][0];
+            alert.title = match payload["count"].as_u64() {
+                Some(1) => "New query shape seen".to_string(),
+                _ => format!("{} new query shapes seen", text(&payload["count"])),
+            };
+            alert.severity = SeverityLevel::Low;
+            alert.dedup_key = format!("new_shape:{}:{}", workspace_id, text(&first["fingerprint"]));
+            alert.fields = vec![
+                ("New shapes".into(), text(&payload["count"])),
+                ("Fingerprint".into(), text(&first["fingerprint"])),
+                ("First seen".into(), text(&first["first_seen"])),
+            ];
+            alert.query_text = first["query_text"].as_str().map(str::to_string);
+            alert.link = public_url.map(|url| {
+                format!(
+                    "{}/api/v1/workspaces/{}/queries/new",
+                    url.trim_end_matches('/'),
+                    workspace_id
+                )
+            });
+            alert.workspace_id = Some(workspace_id);
+        }
+        _ => {}
+    }
+
+    if alert.test {
+        alert.title = format!("[Test] {}", alert.title);
+    }
+    alert
+}
+
+/// Slack incoming webhook message: a header, the alert's fields, the query,
+/// and a link, with `text` as the notification fallback
+pub fn slack_message(alert: &Alert) -> Value {
+    let emoji = if alert.resolved {
+        ":white_check_mark:"
+    } else {
+        match alert.severity {
+            SeverityLevel::Critical => ":red_circle:",
+            SeverityLevel::High => ":large_orange_circle:",
+            SeverityLevel::Medium => ":large_yellow_circle:",
+            SeverityLevel::Low => ":white_circle:",
+        }
+    };
+    let header = truncate_chars(&format!("{} {}", emoji, alert.title), MAX_HEADER_CHARS);
+
+    let mut blocks = vec![json!({
+        "type": "header",
+        "text": { "type": "plain_text", "text": header, "emoji": true },
+    })];
+    if !alert.fields.is_empty() {
+        // Sections hold at most 10 fields
+        let fields: Vec<Value> = alert
+            .fields
+            .iter()
+            .take(10)
+            .map(|(label, value)| {
+                json!({ "type": "mrkdwn", "text": format!("*{}*\n{}", label, escape(value)) })
+            })
+            .collect();
+        blocks.push(json!({ "type": "section", "fields": fields }));
+    }
+    if let Some(query) = &alert.query_text {
+        blocks.push(json!({
+            "type": "section",
+            "text": {
+                "type": "mrkdwn",
+                "text": format!("```{}```", escape(&truncate_chars(query, MAX_QUERY_CHARS))),
+            },
+        }));
+    }
+    if let Some(link) = &alert.link {
+        blocks.push(json!({
+            "type": "context",
+            "elements": [{ "type": "mrkdwn", "text": format!("<{}|View in QueryVault>", link) }],
+        }));
+    }
+
+    json!({ "text": header, "blocks": blocks })
+}
+
+/// PagerDuty Events API v2 event: a trigger, or a resolve for the same
+/// dedup key once the problem is over
+pub fn pagerduty_event(alert: &Alert, routing_key: &str) -> Value {
+    if alert.resolved {
+        return json!({
+            "routing_key": routing_key,
+            "event_action": "resolve",
+            "dedup_key": alert.dedup_key,
+        });
+    }
+
+    let mut details: Map<String, Value> = alert
+        .fields
+        .iter()
+        .map(|(label, value)| (label.clone(), Value::from(value.as_str())))
+        .collect();
+    if let Some(query) = &alert.query_text {
+        details.insert("Query".into(), Value::from(query.as_str()));
+    }
+    details.insert("Test".into(), Value::from(alert.test));
+
+    let mut event = json!({
+        "routing_key": routing_key,
+        "event_action": "trigger",
+        "dedup_key": alert.dedup_key,
+        "client": "QueryVault",
+        "payload": {
+            "summary": truncate_chars(&alert.title, MAX_SUMMARY_CHARS),
+            "source": "queryvault",
+            "severity": pagerduty_severity(alert.severity),
+            "timestamp": alert.sent_at,
+            "group": alert.workspace_id,
+            "class": alert.event,
+            "custom_details": details,
+        },
+    });
+    if let Some(link) = &alert.link {
+        event["links"] = json!([{ "href": link, "text": "View in QueryVault" }]);
+    }
+    event
+}
+
+/// PagerDuty's name for a severity level
+pub fn pagerduty_severity(severity: SeverityLevel) -> &'static str {
+    match severity {
+        SeverityLevel::Critical => "critical",
+        SeverityLevel::High => "error",
+        SeverityLevel::Medium => "warning",
+        SeverityLevel::Low => "info",
+    }
+}
+
+/// A JSON value as display text, without quotes around strings
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => "-".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// A JSON number rounded to two decimals
+fn number(value: &Value) -> String {
+    match value.as_f64() {
+        Some(n) => format!("{:.2}", n),
+        None => text(value),
+    }
+}
+
+/// Escape the characters Slack's mrkdwn treats as control sequences
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Cut a string to at most `max` characters, marking the cut with an ellipsis
+fn truncate_chars(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        s.to_string()
+    } else {
+        let mut cut: String = s.chars().take(max - 1).collect();
+        cut.push('…');
+        cut
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AlertRule;
+    use crate::services::notify::{alert_rule_payload, anomaly_payload, test_anomaly};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_anomaly_rendering() {
+        let workspace_id = Uuid::new_v4();
+        let payload = anomaly_payload(&test_anomaly(workspace_id), false);
+        let alert = summarize(ANOMALY_EVENT, &payload, Some("https://qv.example.com/"));
+        assert_eq!(alert.severity, SeverityLevel::Critical);
+        assert_eq!(
+            alert.link.as_deref(),
+            Some(
+                format!(
+                    "https://qv.example.com/api/v1/workspaces/{}/anomalies/{}/context",
+                    workspace_id,
+                    Uuid::nil()
+                )
+                .as_str()
+            )
+        );
+
+        let slack = slack_message(&alert);
+        let blocks = slack["blocks"].as_array().unwrap();
+        assert_eq!(blocks[0]["type"], "header");
+        assert!(slack["text"].as_str().unwrap().starts_with(":red_circle:"));
+        assert_eq!(blocks[1]["fields"][0]["text"], "*Z-score*\n48.00");
+        assert_eq!(
+            blocks[2]["text"]["text"],
+            "```SELECT 1 /* QueryVault test alert */```"
+        );
+        assert!(blocks[3]["elements"][0]["text"]
+            .as_str()
+            .unwrap()
+            .ends_with("|View in QueryVault>"));
+
+        let event = pagerduty_event(&alert, "R0UT1NG");
+        assert_eq!(event["routing_key"], "R0UT1NG");
+        assert_eq!(event["event_action"], "trigger");
+        assert_eq!(event["dedup_key"], format!("anomaly:{}", Uuid::nil()));
+        assert_eq!(event["payload"]["severity"], "critical");
+        assert_eq!(event["payload"]["source"], "queryvault");
+        assert_eq!(event["payload"]["group"], workspace_id.to_string());
+        assert_eq!(event["links"][0]["text"], "View in QueryVault");
+    }
+
+    #[test]
+    fn test_slo_recovery_resolves() {
+        let slo = json!({ "id": "s1", "workspace_id": "w1", "name": "checkout" });
+        let burning = json!({ "slo": slo, "burn_state": "slow_burn", "burn_rate_1h": 3.0 });
+        let alert = summarize(SLO_BURN_EVENT, &burning, None);
+        assert_eq!(alert.severity, SeverityLevel::High);
+        assert!(alert.link.is_none());
+        assert_eq!(pagerduty_event(&alert, "k")["payload"]["severity"], "error");
+
+        let ok = json!({ "slo": slo, "burn_state": "ok" });
+        let alert = summarize(SLO_BURN_EVENT, &ok, None);
+        assert!(alert.resolved);
+        assert_eq!(
+            pagerduty_event(&alert, "k"),
+            json!({ "routing_key": "k", "event_action": "resolve", "dedup_key": "slo:s1" })
+        );
+        assert!(slack_message(&alert)["text"]
+            .as_str()
+            .unwrap()
+            .starts_with(":white_check_mark:"));
+    }
+
+    #[test]
+    fn test_alert_rule_signals() {
+        let rule: AlertRule = serde_json::from_value(json!({
+            "id": Uuid::nil(),
+            "workspace_id": Uuid::nil(),
+            "name": "checkout degraded",
+            "service_id": null,
+            "window": "1m",
+            "conditions": [
+                { "signal": "p95_duration_ms", "comparison": "gt", "threshold": 500.0 },
+                { "signal": "query_count", "comparison": "lt", "threshold": 10.0 },
+            ],
+            "combine": "any",
+            "for_windows": 1,
+            "resolve_windows": 1,
+            "enabled": true,
+            "created_at": "2026-01-01T00:00:00Z",
+            "updated_at": "2026-01-01T00:00:00Z",
+        }))
+        .unwrap();
+        let bucket = "2026-01-01T00:05:00Z".parse().unwrap();
+
+        let firing = alert_rule_payload(&rule, bucket, &[Some(612.0), None], true);
+        let alert = summarize(ALERT_RULE_EVENT, &firing, None);
+        assert_eq!(alert.title, "Alert rule 'checkout degraded' firing");
+        assert_eq!(
+            alert.fields[..2],
+            [
+                (
+                    "p95_duration_ms".to_string(),
+                    "612.00 (> 500.00)".to_string()
+                ),
+                ("query_count".to_string(), "- (< 10.00)".to_string()),
+            ]
+        );
+        let details = &pagerduty_event(&alert, "k")["payload"]["custom_details"];
+        assert_eq!(details["query_count"], "- (< 10.00)");
+
+        let resolved = alert_rule_payload(&rule, bucket, &[Some(100.0), Some(50.0)], false);
+        let alert = summarize(ALERT_RULE_EVENT, &resolved, None);
+        assert!(alert.resolved);
+        assert_eq!(
+            pagerduty_event(&alert, "k")["dedup_key"],
+            format!("alert_rule:{}", Uuid::nil())
+        );
+    }
+
+    #[test]
+    fn test_slack_text_is_escaped_and_truncated() {
+        let mut anomaly = test_anomaly(Uuid::new_v4());
+        anomaly.query_text = format!("SELECT a <> b && c {}", "x".repeat(5000));
+        let alert = summarize(ANOMALY_EVENT, &anomaly_payload(&anomaly, true), None);
+        assert!(alert.title.starts_with("[Test] "));
+
+        let slack = slack_message(&alert);
+        let query = slack["blocks"][2]["text"]["text"].as_str().unwrap();
+        assert!(query.starts_with("```SELECT a &lt;&gt; b &amp;&amp; c"));
+        assert!(query.chars().count() < 3000);
+    }
+}
@@ -0,0 +1,130 @@
+//! JWT bearer tokens for the read API
+//!
+//! Instead of embedding a long-lived API key in a browser, a frontend's
+//! backend can mint short-lived JWTs signed with a shared HS256 secret or an
+//! RS256 private key. A token must carry `exp` and the `workspace_id` it
+//! grants read access to; it never grants ingest or admin access.
+
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use uuid::Uuid;
+
+/// Claims QueryVault reads from a token
+#[derive(Debug, Clone, Deserialize)]
+pub struct JwtClaims {
+    /// Workspace the token grants read access to
+    pub workspace_id: Uuid,
+    /// Expiry as a Unix timestamp
+    pub exp: i64,
+    /// Subject, e.g. the frontend user; used to name the token in logs
+    #[serde(default)]
+    pub sub: Option<String>,
+}
+
+/// Verifies JWT signatures and standard claims
+pub struct JwtVerifier {
+    key: DecodingKey,
+    validation: Validation,
+}
+
+impl JwtVerifier {
+    /// Verifier for tokens signed with a shared HS256 secret
+    pub fn hs256(secret: &[u8]) -> Self {
+        Self {
+            key: DecodingKey::from_secret(secret),
+            validation: Self::validation(Algorithm::HS256),
+        }
+    }
+
+    /// Verifier for tokens signed with the private half of an RS256 key
+    /// pair, given the public key as PEM
+    pub fn rs256(public_key_pem: &[u8]) -> Result<Self, String> {
+        let key = DecodingKey::from_rsa_pem(public_key_pem)
+            .map_err(|e| format!("Invalid RSA public key: {}", e))?;
+        Ok(Self {
+            key,
+            validation: Self::validation(Algorithm::RS256),
+        })
+    }
+
+    fn validation(algorithm: Algorithm) -> Validation {
+        let mut validation = Validation::new(algorithm);
+        validation.validate_aud = false;
+        validation
+    }
+
+    /// Also require the `iss` claim to match
+    pub fn with_issuer(mut self, issuer: &str) -> Self {
+        self.validation.set_issuer(&[issuer]);
+        self
+    }
+
+    /// Also require the `aud` claim to match
+    pub fn with_audience(mut self, audience: &str) -> Self {
+        self.validation.set_audience(&[audience]);
+        self.validation.validate_aud = true;
+        self
+    }
+
+    /// Check a token's signature, expiry, and configured claims
+    pub fn verify(&self, token: &str) -> Result<JwtClaims, String> {
+        decode::<JwtClaims>(token, &self.key, &self.validation)
+            .map(|data| data.claims)
+            .map_err(|e| format!("Invalid token: {}", e))
+    }
+}
+
+/// Whether a bearer token is shaped like a JWT rather than an API key
+pub fn looks_like_jwt(token: &str) -> bool {
+    token.split('.').count() == 3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    fn token(secret: &[u8], claims: serde_json::Value) -> String {
+        encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(secret),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_hs256_tokens() {
+        let verifier = JwtVerifier::hs256(b"secret").with_issuer("frontend");
+        let ws = Uuid::new_v4();
+        let exp = chrono::Utc::now().timestamp() + 300;
+
+        let valid = token(
+            b"secret",
+            json!({ "workspace_id": ws, "exp": exp, "iss": "frontend", "sub": "alice" }),
+        );
+        assert!(looks_like_jwt(&valid));
+        let claims = verifier.verify(&valid).unwrap();
+        assert_eq!(claims.workspace_id, ws);
+        assert_eq!(claims.sub.as_deref(), Some("alice"));
+
+        let forged = token(
+            b"other",
+            json!({ "workspace_id": ws, "exp": exp, "iss": "frontend" }),
+        );
+        assert!(verifier.verify(&forged).is_err());
+        let expired = token(
+            b"secret",
+            json!({ "workspace_id": ws, "exp": exp - 3600, "iss": "frontend" }),
+        );
+        assert!(verifier.verify(&expired).is_err());
+        let wrong_issuer = token(
+            b"secret",
+            json!({ "workspace_id": ws, "exp": exp, "iss": "someone-else" }),
+        );
+        assert!(verifier.verify(&wrong_issuer).is_err());
+
+        assert!(!looks_like_jwt("qv_0123456789abcdef"));
+    }
+}
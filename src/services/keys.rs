@@ -0,0 +1,43 @@
+//! API key secrets: generation and hashing
+//!
+//! Keys are stored as SHA-256 hashes. Secrets are long random tokens rather
+//! than passwords, so a fast unsalted hash is enough to make a leaked table
+//! useless while still letting a key be looked up by its hash; a salted KDF
+//! such as argon2 would need every stored hash checked on every request.
+
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+/// Random key secret (244 bits of entropy from two v4 UUIDs)
+pub fn generate_secret() -> String {
+    format!("qv_{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// Hex-encoded SHA-256 of a secret, as stored in `api_keys.key_hash`
+pub fn hash_secret(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Whether a secret hashes to `hash`, compared in constant time
+pub fn hash_matches(secret: &str, hash: &str) -> bool {
+    hash_secret(secret).as_bytes().ct_eq(hash.as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_secret() {
+        // Same digest as Postgres' encode(sha256('test-api-key-12345'), 'hex')
+        let hash = hash_secret("test-api-key-12345");
+        assert_eq!(hash.len(), 64);
+        assert!(hash_matches("test-api-key-12345", &hash));
+        assert!(!hash_matches("test-api-key-12346", &hash));
+        assert_ne!(generate_secret(), generate_secret());
+    }
+}
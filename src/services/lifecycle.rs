@@ -0,0 +1,150 @@
+//! Data lifecycle policies
+//!
+//! A workspace's policy is a list of stages, each applied by the lifecycle
+//! task to the workspace's data once it is older than the stage's age:
+//! strip text, archive, downsample, then delete. Workspaces without a policy
+//! get [`default_policy`], which only drops raw metrics at the end of raw
+//! retention, as before policies existed. Whatever the policy, raw metrics
+//! don't outlive [`RAW_METRICS_RETENTION_DAYS`], since the TimescaleDB
+//! retention policy drops them then; the rollups age out on their own
+//! retention policies. Stages deleting raw metrics wait until
+//! [`MIN_RAW_DELETE_DAYS`], as the continuous aggregates re-materialize the
+//! days they refresh from whatever raw metrics are left.
+
+use crate::db::RAW_METRICS_RETENTION_DAYS;
+use crate::error::{AppError, Result};
+use crate::models::{LifecycleAction, LifecycleStage};
+
+/// Oldest age a stage can have, in days
+pub const MAX_AFTER_DAYS: i32 = 3650;
+
+/// Youngest age at which a stage can delete raw metrics, in days: one past
+/// the furthest continuous aggregate refresh (metrics_1d's 7-day
+/// `start_offset`), so deleted metrics are already in every rollup for good
+pub const MIN_RAW_DELETE_DAYS: i32 = 8;
+
+/// Stages of workspaces without a policy of their own
+pub fn default_policy() -> Vec<LifecycleStage> {
+    vec![LifecycleStage::new(
+        LifecycleAction::Downsample,
+        RAW_METRICS_RETENTION_DAYS as i32,
+    )]
+}
+
+/// Check a policy's stages: each action at most once, listed in the order
+/// they run, at ages that don't decrease, within raw retention for the
+/// stages that act on raw metrics, and past the rollups' refresh window for
+/// those deleting them
+///
+/// An empty policy is valid and reverts the workspace to the default.
+pub fn validate_stages(stages: &[LifecycleStage]) -> Result<()> {
+    for stage in stages {
+        if !(1..=MAX_AFTER_DAYS).contains(&stage.after_days) {
+            return Err(AppError::InvalidRequest(format!(
+                "'after_days' must be between 1 and {}",
+                MAX_AFTER_DAYS
+            )));
+        }
+        if stage.action != LifecycleAction::Delete
+            && stage.after_days > RAW_METRICS_RETENTION_DAYS as i32
+        {
+            return Err(AppError::InvalidRequest(format!(
+                "The '{}' stage acts on raw metrics, which are kept for {} days",
+                stage.action.as_str(),
+                RAW_METRICS_RETENTION_DAYS
+            )));
+        }
+        if matches!(
+            stage.action,
+            LifecycleAction::Downsample | LifecycleAction::Delete
+        ) && stage.after_days < MIN_RAW_DELETE_DAYS
+        {
+            return Err(AppError::InvalidRequest(format!(
+                "The '{}' stage deletes raw metrics, which the rollups refresh from for 7 days, so 'after_days' must be at least {}",
+                stage.action.as_str(),
+                MIN_RAW_DELETE_DAYS
+            )));
+        }
+    }
+    for pair in stages.windows(2) {
+        if pair[0].action >= pair[1].action {
+            return Err(AppError::InvalidRequest(
+                "Stages must be listed once each, in the order strip_text, archive, downsample, delete"
+                    .into(),
+            ));
+        }
+        if pair[0].after_days > pair[1].after_days {
+            return Err(AppError::InvalidRequest(format!(
+                "The '{}' stage can't come before the '{}' stage it follows",
+                pair[1].action.as_str(),
+                pair[0].action.as_str()
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage(action: LifecycleAction, after_days: i32) -> LifecycleStage {
+        LifecycleStage::new(action, after_days)
+    }
+
+    #[test]
+    fn test_ordered_stages_accepted() {
+        assert!(validate_stages(&[]).is_ok());
+        assert!(validate_stages(&default_policy()).is_ok());
+        assert!(validate_stages(&[
+            stage(LifecycleAction::StripText, 3),
+            stage(LifecycleAction::Archive, 8),
+            stage(LifecycleAction::Downsample, 8),
+            stage(LifecycleAction::Delete, 365),
+        ])
+        .is_ok());
+    }
+
+    #[test]
+    fn test_misordered_stages_rejected() {
+        // Out of pipeline order
+        assert!(validate_stages(&[
+            stage(LifecycleAction::Downsample, 8),
+            stage(LifecycleAction::Archive, 8),
+        ])
+        .is_err());
+        // Repeated
+        assert!(validate_stages(&[
+            stage(LifecycleAction::StripText, 1),
+            stage(LifecycleAction::StripText, 2),
+        ])
+        .is_err());
+        // Younger than the stage before
+        assert!(validate_stages(&[
+            stage(LifecycleAction::Archive, 14),
+            stage(LifecycleAction::Downsample, 8),
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_raw_stages_bounded_by_raw_retention() {
+        assert!(validate_stages(&[stage(LifecycleAction::Archive, 31)]).is_err());
+        assert!(validate_stages(&[stage(LifecycleAction::Delete, 400)]).is_ok());
+        assert!(validate_stages(&[stage(LifecycleAction::Delete, 0)]).is_err());
+    }
+
+    #[test]
+    fn test_raw_deletes_wait_for_rollup_refresh() {
+        for action in [LifecycleAction::Downsample, LifecycleAction::Delete] {
+            assert!(validate_stages(&[stage(action, 1)]).is_err());
+            assert!(validate_stages(&[stage(action, MIN_RAW_DELETE_DAYS - 1)]).is_err());
+            assert!(validate_stages(&[stage(action, MIN_RAW_DELETE_DAYS)]).is_ok());
+        }
+        assert!(validate_stages(&[stage(LifecycleAction::StripText, 1)]).is_ok());
+        assert_eq!(
+            serde_json::from_str::<LifecycleAction>("\"compress\"").unwrap(),
+            LifecycleAction::StripText
+        );
+    }
+}
@@ -1,3 +1,28 @@
 //! Services module
 
+pub mod alerting;
+pub mod anomaly;
+pub mod buckets;
+pub mod comparison;
+pub mod duplicates;
+pub mod embedder;
 pub mod embedding;
+pub mod embedding_cache;
+pub mod error_fingerprint;
+pub mod fingerprint;
+pub mod hybrid_search;
+pub mod integrations;
+pub mod jwt;
+pub mod keys;
+pub mod lifecycle;
+pub mod new_shapes;
+pub mod notify;
+pub mod projection;
+pub mod quarantine;
+pub mod redaction;
+pub mod schema;
+pub mod secrets;
+pub mod severity;
+pub mod slo;
+pub mod tables;
+pub mod usage;
@@ -0,0 +1,65 @@
+//! Reporting of query shapes a workspace has never run before
+//!
+//! A fingerprint showing up for the first time is often the first sign of a
+//! bad deploy or an injection attempt. Once a workspace is past its first day
+//! of traffic, so its baseline isn't all "new", every flush's new
+//! fingerprints are logged and sent to the workspace's alert destinations as
+//! one `query.new_shape` event. The event is informational: it counts as low
+//! severity, so only destinations with a `min_severity` of `low` receive it.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::db::NewQueryShape;
+use crate::services::notify::{self, Notifier};
+use crate::services::severity::SeverityLevel;
+use crate::store::MetricsStore;
+
+/// Log one flush's new shapes per workspace and send them to the
+/// destinations that take informational events
+///
+/// Alerts are sent in the background, so a slow destination doesn't hold up
+/// the flush.
+pub fn report(db: &Arc<dyn MetricsStore>, notifier: &Arc<Notifier>, shapes: &[NewQueryShape]) {
+    let mut by_workspace: HashMap<Uuid, Vec<&NewQueryShape>> = HashMap::new();
+    for shape in shapes {
+        by_workspace
+            .entry(shape.workspace_id)
+            .or_default()
+            .push(shape);
+    }
+
+    for (workspace_id, shapes) in by_workspace {
+        for shape in &shapes {
+            info!(
+                workspace_id = %workspace_id,
+                fingerprint = %shape.fingerprint,
+                query_text = %shape.query_text,
+                "New query shape"
+            );
+        }
+
+        let db = Arc::clone(db);
+        let notifier = Arc::clone(notifier);
+        let payload = notify::new_shape_payload(workspace_id, &shapes);
+        tokio::spawn(async move {
+            let destinations = match db.list_alert_destinations(workspace_id).await {
+                Ok(d) => d,
+                Err(e) => {
+                    error!(error = %e, workspace_id = %workspace_id, "Failed to list alert destinations for new query shapes");
+                    return;
+                }
+            };
+            for destination in destinations
+                .iter()
+                .filter(|d| d.enabled && d.min_severity == SeverityLevel::Low)
+            {
+                notifier
+                    .deliver(destination, notify::NEW_SHAPE_EVENT, &payload, false)
+                    .await;
+            }
+        });
+    }
+}
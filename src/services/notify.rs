@@ -0,0 +1,608 @@
+//! Alert delivery to workspace destinations
+//!
+//! Alerts are POSTed as JSON to a destination's URL: webhooks get the event's
+//! payload, while Slack and PagerDuty destinations get it rendered in their
+//! own format (see [`integrations`]). [`Notifier::deliver`]
+//! never fails: it reports what the destination answered, or why it couldn't
+//! be reached, so callers can log it or show it to the user. Every delivery is
+//! also recorded in the deliveries table and counted for Prometheus, so a
+//! failing destination can't pass for a quiet one.
+//!
+//! Webhook requests are signed with the destination's secret: the
+//! `X-QueryVault-Signature` header is `sha256=` and the hex HMAC-SHA256 of
+//! the `X-QueryVault-Timestamp` header (Unix seconds), a `.`, and the body, so
+//! a receiver can check an alert came from QueryVault and reject replays.
+//! Alerts that get no response, a 5xx, or a 429 are retried with jittered
+//! exponential backoff; test deliveries are sent once.
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::clock::Clock;
+use crate::db::{DeadLetterReason, NewQueryShape, QueryAnomaly, SloStatus, ThroughputDrop};
+use crate::models::{
+    AlertDelivery, AlertDestination, AlertDestinationKind, AlertRule, AnomalyMethod, Slo,
+};
+use crate::services::integrations;
+use crate::services::severity::SeverityLevel;
+use crate::services::slo::BurnState;
+use crate::store::MetricsStore;
+
+/// How long to wait for a destination to answer
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most of a response body kept in a delivery result
+const MAX_RESPONSE_BYTES: usize = 512;
+
+/// Attempts per alert, including the first
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Longest backoff before the first retry; doubles for each one after
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Header carrying the request's HMAC-SHA256 signature
+pub const SIGNATURE_HEADER: &str = "X-QueryVault-Signature";
+
+/// Header carrying the Unix time the signature was made at
+pub const TIMESTAMP_HEADER: &str = "X-QueryVault-Timestamp";
+
+/// What happened when an alert was sent to a destination
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryResult {
+    /// Whether the destination accepted the alert (2xx)
+    pub delivered: bool,
+    /// HTTP status, if the destination answered
+    pub status: Option<u16>,
+    pub latency_ms: u64,
+    /// Start of the response body
+    pub response: Option<String>,
+    /// Why the request failed, if it got no response
+    pub error: Option<String>,
+    /// Requests made, including retries
+    pub attempts: u32,
+}
+
+/// Deliveries by final outcome, for Prometheus
+#[derive(Debug, Default)]
+pub struct DeliveryStats {
+    /// Answered with a 2xx status
+    pub delivered: AtomicU64,
+    /// Answered with any other status
+    pub rejected: AtomicU64,
+    /// No response: connection error or timeout
+    pub unreachable: AtomicU64,
+}
+
+/// Sends alerts to destinations over HTTP and records each attempt
+pub struct Notifier {
+    client: reqwest::Client,
+    db: Arc<dyn MetricsStore>,
+    stats: DeliveryStats,
+    /// Address QueryVault is reachable at, for links in Slack and PagerDuty
+    /// alerts
+    public_url: Option<String>,
+    /// Times retry backoff and stamps deliveries
+    clock: Clock,
+}
+
+impl Notifier {
+    pub fn new(db: Arc<dyn MetricsStore>) -> Self {
+        Self {
+            client: http_client(),
+            db,
+            stats: DeliveryStats::default(),
+            public_url: None,
+            clock: Clock::System,
+        }
+    }
+
+    /// Wait out retry backoff and stamp deliveries on `clock`
+    // Only tests swap in a simulated clock
+    #[allow(dead_code)]
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Link alerts to QueryVault at `public_url`
+    pub fn with_public_url(mut self, public_url: Option<String>) -> Self {
+        self.public_url = public_url;
+        self
+    }
+
+    pub fn stats(&self) -> &DeliveryStats {
+        &self.stats
+    }
+
+    /// Send an event's payload to a destination, retrying unless it's a
+    /// test, and record the outcome
+    pub async fn deliver(
+        &self,
+        destination: &AlertDestination,
+        event: &str,
+        payload: &Value,
+        test: bool,
+    ) -> DeliveryResult {
+        let max_attempts = if test { 1 } else { MAX_ATTEMPTS };
+        let body = request_body(destination, event, payload, self.public_url.as_deref());
+        let mut result = send(&self.client, destination, &body).await;
+        while result.attempts < max_attempts && is_retryable(&result) {
+            self.clock.sleep(retry_backoff(result.attempts - 1)).await;
+            let attempts = result.attempts;
+            result = send(&self.client, destination, &body).await;
+            result.attempts += attempts;
+        }
+
+        let counter = match (result.delivered, result.status) {
+            (true, _) => &self.stats.delivered,
+            (false, Some(_)) => &self.stats.rejected,
+            (false, None) => &self.stats.unreachable,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        if !result.delivered {
+            warn!(
+                workspace_id = %destination.workspace_id,
+                destination_id = %destination.id,
+                event = event,
+                status = ?result.status,
+                error = ?result.error,
+                attempts = result.attempts,
+                "Alert delivery failed"
+            );
+        }
+
+        let delivery = AlertDelivery {
+            id: Uuid::new_v4(),
+            workspace_id: destination.workspace_id,
+            destination_id: destination.id,
+            event: event.to_string(),
+            test,
+            delivered: result.delivered,
+            status: result.status,
+            latency_ms: result.latency_ms,
+            response: result.response.clone(),
+            error: result.error.clone(),
+            attempts: result.attempts,
+            created_at: self.clock.now(),
+        };
+        if let Err(e) = self.db.insert_alert_delivery(&delivery).await {
+            error!(error = %e, destination_id = %destination.id, "Failed to record alert delivery");
+        }
+
+        result
+    }
+}
+
+/// HTTP client with the delivery timeout
+fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .user_agent(concat!("QueryVault/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("Failed to build HTTP client")
+}
+
+/// Whether a failed request may succeed if sent again: the destination
+/// couldn't be reached, failed itself, or asked us to slow down
+fn is_retryable(result: &DeliveryResult) -> bool {
+    match result.status {
+        None => true,
+        Some(status) => status >= 500 || status == 429,
+    }
+}
+
+/// Backoff before retry number `retry` (0-based), with full jitter
+fn retry_backoff(retry: u32) -> Duration {
+    let ceiling = RETRY_BASE_DELAY.saturating_mul(2u32.saturating_pow(retry));
+    let millis = ceiling.as_millis() as u64;
+    Duration::from_millis(rand::thread_rng().gen_range(0..=millis))
+}
+
+/// New random signing secret for a destination
+pub fn generate_signing_secret() -> String {
+    format!(
+        "whsec_{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    )
+}
+
+/// Hex HMAC-SHA256 of `{timestamp}.{body}` under `secret`
+pub fn signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// JSON body sent to a destination for an event: the payload itself for
+/// webhooks, rendered for Slack and PagerDuty
+fn request_body(
+    destination: &AlertDestination,
+    event: &str,
+    payload: &Value,
+    public_url: Option<&str>,
+) -> Vec<u8> {
+    let body = match destination.kind {
+        AlertDestinationKind::Webhook => return to_json(payload),
+        AlertDestinationKind::Slack => {
+            integrations::slack_message(&integrations::summarize(event, payload, public_url))
+        }
+        AlertDestinationKind::Pagerduty => integrations::pagerduty_event(
+            &integrations::summarize(event, payload, public_url),
+            destination.routing_key.as_deref().unwrap_or_default(),
+        ),
+    };
+    to_json(&body)
+}
+
+fn to_json(value: &Value) -> Vec<u8> {
+    serde_json::to_vec(value).expect("JSON values always serialize")
+}
+
+/// Send a body to a destination once, signed if it has a secret
+async fn send(
+    client: &reqwest::Client,
+    destination: &AlertDestination,
+    body: &[u8],
+) -> DeliveryResult {
+    let started = Instant::now();
+    let mut request = client
+        .post(&destination.url)
+        .header(CONTENT_TYPE, "application/json");
+    if let Some(secret) = &destination.signing_secret {
+        let timestamp = Utc::now().timestamp();
+        request = request.header(TIMESTAMP_HEADER, timestamp).header(
+            SIGNATURE_HEADER,
+            format!("sha256={}", signature(secret, timestamp, body)),
+        );
+    }
+    let request = request.body(body.to_vec());
+
+    match request.send().await {
+        Ok(response) => {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            DeliveryResult {
+                delivered: status.is_success(),
+                status: Some(status.as_u16()),
+                latency_ms: started.elapsed().as_millis() as u64,
+                response: (!body.is_empty()).then(|| truncate(body)),
+                error: None,
+                attempts: 1,
+            }
+        }
+        Err(e) => DeliveryResult {
+            delivered: false,
+            status: None,
+            latency_ms: started.elapsed().as_millis() as u64,
+            response: None,
+            error: Some(e.to_string()),
+            attempts: 1,
+        },
+    }
+}
+
+/// Event name for a detected anomaly
+pub const ANOMALY_EVENT: &str = "anomaly.detected";
+
+/// Payload sent for a detected anomaly
+pub fn anomaly_payload(anomaly: &QueryAnomaly, test: bool) -> Value {
+    json!({
+        "event": ANOMALY_EVENT,
+        "test": test,
+        "sent_at": Utc::now(),
+        "anomaly": anomaly,
+    })
+}
+
+/// Event name for a service's query volume dropping or recovering
+pub const THROUGHPUT_DROP_EVENT: &str = "anomaly.throughput_drop";
+
+/// Payload sent when a throughput drop is detected, or when the service
+/// recovers (`recovered_at` set)
+pub fn throughput_drop_payload(drop: &ThroughputDrop, service_name: Option<&str>) -> Value {
+    json!({
+        "event": THROUGHPUT_DROP_EVENT,
+        "test": false,
+        "sent_at": Utc::now(),
+        "recovered": drop.recovered_at.is_some(),
+        "service_name": service_name,
+        "drop": drop,
+    })
+}
+
+/// Event name for a change in how fast an SLO's error budget is burning
+pub const SLO_BURN_EVENT: &str = "slo.burn_rate";
+
+/// Payload sent when an SLO's burn state changes
+pub fn slo_burn_payload(slo: &Slo, status: &SloStatus, previous: BurnState) -> Value {
+    json!({
+        "event": SLO_BURN_EVENT,
+        "test": false,
+        "sent_at": Utc::now(),
+        "slo": slo,
+        "burn_state": status.burn_state,
+        "previous_burn_state": previous,
+        "burn_rate_1h": status.burn_rate_1h,
+        "burn_rate_6h": status.burn_rate_6h,
+        "compliance_percent": status.counts.compliance(),
+        "error_budget_remaining_percent": status.counts.budget_remaining(slo.target_percent),
+    })
+}
+
+/// Event name for an alert rule firing or resolving
+pub const ALERT_RULE_EVENT: &str = "alert_rule.state";
+
+/// Payload sent when an alert rule fires, or resolves (`firing` false), with
+/// each condition's signal value over the window (`None` = no data)
+pub fn alert_rule_payload(
+    rule: &AlertRule,
+    bucket: DateTime<Utc>,
+    values: &[Option<f64>],
+    firing: bool,
+) -> Value {
+    let signals: Vec<Value> = rule
+        .conditions
+        .iter()
+        .zip(values)
+        .map(|(condition, value)| {
+            json!({
+                "signal": condition.signal,
+                "comparison": condition.comparison,
+                "threshold": condition.threshold,
+                "value": value,
+            })
+        })
+        .collect();
+    json!({
+        "event": ALERT_RULE_EVENT,
+        "test": false,
+        "sent_at": Utc::now(),
+        "firing": firing,
+        "rule": rule,
+        "window_start": bucket,
+        "signals": signals,
+    })
+}
+
+/// Event name for metrics quarantined on schema drift
+pub const QUARANTINE_EVENT: &str = "metrics.quarantined";
+
+/// Payload sent when a workspace's metrics are quarantined because they
+/// didn't fit the database schema
+pub fn quarantine_payload(workspace_id: Uuid, count: usize, error: &str) -> Value {
+    json!({
+        "event": QUARANTINE_EVENT,
+        "test": false,
+        "sent_at": Utc::now(),
+        "workspace_id": workspace_id,
+        "reason": DeadLetterReason::SchemaDrift,
+        "quarantined_count": count,
+        "error": error,
+    })
+}
+
+/// Event name for query fingerprints a workspace had never seen before
+pub const NEW_SHAPE_EVENT: &str = "query.new_shape";
+
+/// Most new shapes listed in one event
+const MAX_NEW_SHAPES: usize = 20;
+
+/// Payload sent when one flush stored fingerprints new to a workspace;
+/// `count` is all of them, `queries` the first 20
+pub fn new_shape_payload(workspace_id: Uuid, shapes: &[&NewQueryShape]) -> Value {
+    json!({
+        "event": NEW_SHAPE_EVENT,
+        "test": false,
+        "sent_at": Utc::now(),
+        "workspace_id": workspace_id,
+        "count": shapes.len(),
+        "queries": shapes.iter().take(MAX_NEW_SHAPES).collect::<Vec<_>>(),
+    })
+}
+
+/// Made-up anomaly for checking that a destination is wired up
+pub fn test_anomaly(workspace_id: Uuid) -> QueryAnomaly {
+    QueryAnomaly {
+        id: Uuid::nil(),
+        workspace_id,
+        service_id: Uuid::nil(),
+        metric_id: Uuid::nil(),
+        fingerprint: "test".into(),
+        query_text: "SELECT 1 /* QueryVault test alert */".into(),
+        duration_ms: 2500,
+        mean_duration_ms: 100,
+        stddev_duration_ms: 50,
+        z_score: 48.0,
+        method: AnomalyMethod::ZScore,
+        severity: 90.0,
+        severity_level: SeverityLevel::Critical,
+        estimated_extra_ms: 2400,
+        hints: Vec::new(),
+    }
+}
+
+/// Cut a response body down to [`MAX_RESPONSE_BYTES`] on a character boundary
+fn truncate(mut body: String) -> String {
+    if body.len() > MAX_RESPONSE_BYTES {
+        let mut end = MAX_RESPONSE_BYTES;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.truncate(end);
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::{routing::post, Router};
+
+    fn destination(url: String) -> AlertDestination {
+        AlertDestination {
+            id: Uuid::new_v4(),
+            workspace_id: Uuid::new_v4(),
+            name: "test".into(),
+            kind: AlertDestinationKind::Webhook,
+            url,
+            signing_secret: Some("whsec_test".into()),
+            routing_key: None,
+            min_severity: SeverityLevel::High,
+            enabled: true,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deliver_reports_http_result() {
+        let app = Router::new()
+            .route("/ok", post(|| async { (StatusCode::ACCEPTED, "queued") }))
+            .route("/fail", post(|| async { StatusCode::BAD_REQUEST }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = http_client();
+        let body = to_json(&anomaly_payload(&test_anomaly(Uuid::new_v4()), true));
+
+        let ok = send(&client, &destination(format!("http://{}/ok", addr)), &body).await;
+        assert!(ok.delivered);
+        assert_eq!(ok.status, Some(202));
+        assert_eq!(ok.response.as_deref(), Some("queued"));
+
+        let failed = send(
+            &client,
+            &destination(format!("http://{}/fail", addr)),
+            &body,
+        )
+        .await;
+        assert!(!failed.delivered);
+        assert_eq!(failed.status, Some(400));
+
+        assert_eq!(
+            truncate("é".repeat(MAX_RESPONSE_BYTES)).len(),
+            MAX_RESPONSE_BYTES
+        );
+    }
+
+    #[test]
+    fn test_signature() {
+        // Same digest as Python's hmac.new(b"whsec_test", msg, hashlib.sha256)
+        assert_eq!(
+            signature("whsec_test", 1_700_000_000, br#"{"event":"test"}"#),
+            "21d2d3606ebbdbf9307ee15e83085df2b83c83dd87cc2e6d2ea6b1cb61afdc3c"
+        );
+        assert_ne!(generate_signing_secret(), generate_signing_secret());
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_signed() {
+        // Answers 204 only if the signature matches the body it came with
+        let app = Router::new().route(
+            "/hook",
+            post(|headers: HeaderMap, body: Bytes| async move {
+                let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+                let expected = format!("sha256={}", signature("whsec_test", timestamp, &body));
+                if headers[SIGNATURE_HEADER] == expected.as_str() {
+                    StatusCode::NO_CONTENT
+                } else {
+                    StatusCode::UNAUTHORIZED
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let body = to_json(&anomaly_payload(&test_anomaly(Uuid::new_v4()), true));
+        let result = send(
+            &http_client(),
+            &destination(format!("http://{}/hook", addr)),
+            &body,
+        )
+        .await;
+        assert_eq!(result.status, Some(204));
+    }
+
+    #[cfg(feature = "memory-store")]
+    #[tokio::test]
+    async fn test_transient_failures_retried() {
+        use crate::store::memory::MemoryStore;
+        use std::sync::atomic::AtomicU32;
+
+        // Unavailable the first time, then accepts
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&calls);
+        let app = Router::new()
+            .route(
+                "/flaky",
+                post(move || async move {
+                    if counter.fetch_add(1, Ordering::Relaxed) == 0 {
+                        StatusCode::SERVICE_UNAVAILABLE
+                    } else {
+                        StatusCode::OK
+                    }
+                }),
+            )
+            .route("/reject", post(|| async { StatusCode::BAD_REQUEST }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let db = Arc::new(MemoryStore::new());
+        let clock = Clock::simulated(Utc::now());
+        let notifier = Arc::new(Notifier::new(db.clone()).with_clock(clock.clone()));
+        let flaky = destination(format!("http://{}/flaky", addr));
+        let payload = anomaly_payload(&test_anomaly(flaky.workspace_id), false);
+
+        // The retry waits for the clock, not for real time
+        let delivery = tokio::spawn({
+            let (notifier, flaky, payload) = (notifier.clone(), flaky.clone(), payload.clone());
+            async move {
+                notifier
+                    .deliver(&flaky, ANOMALY_EVENT, &payload, false)
+                    .await
+            }
+        });
+        while !delivery.is_finished() {
+            clock.advance(chrono::TimeDelta::milliseconds(100));
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let result = delivery.await.unwrap();
+        assert!(result.delivered);
+        assert_eq!(result.attempts, 2);
+        let deliveries = db
+            .list_alert_deliveries(flaky.workspace_id, None, false, 10)
+            .await
+            .unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].attempts, 2);
+
+        // Rejections aren't retried
+        let reject = destination(format!("http://{}/reject", addr));
+        let result = notifier
+            .deliver(&reject, ANOMALY_EVENT, &payload, false)
+            .await;
+        assert_eq!((result.delivered, result.attempts), (false, 1));
+    }
+}
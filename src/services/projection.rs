@@ -0,0 +1,320 @@
+//! 2D projection and clustering of query embeddings for the query landscape
+//!
+//! Embeddings are projected onto their first two principal components, found
+//! by power iteration against the covariance without materializing the
+//! `dim × dim` matrix. Clusters are found with k-means in the full embedding
+//! space rather than the plane, so a cluster is a group of similar queries
+//! and not an artifact of the projection.
+//!
+//! Both are deterministic: the same embeddings give the same plot, so the
+//! dashboard doesn't reshuffle on every refresh.
+
+use std::collections::HashMap;
+
+use crate::services::fingerprint::{referenced_tables, SqlDialect};
+
+/// Most power-iteration rounds per principal component
+const PCA_ITERATIONS: usize = 100;
+
+/// Power iteration stops once successive vectors are this close
+const PCA_TOLERANCE: f64 = 1e-9;
+
+/// Most k-means (Lloyd) rounds
+const KMEANS_ITERATIONS: usize = 25;
+
+/// Tables named in a cluster label
+const LABEL_TABLES: usize = 2;
+
+/// Embeddings projected onto the plane
+#[derive(Debug, Clone)]
+pub struct Projection {
+    /// `[x, y]` per embedding, in input order
+    pub points: Vec<[f64; 2]>,
+    /// Share of the total variance captured by each axis
+    pub explained_variance: [f64; 2],
+}
+
+/// Project embeddings onto their first two principal components
+pub fn pca_2d(vectors: &[Vec<f32>]) -> Projection {
+    let n = vectors.len();
+    let dim = vectors.first().map_or(0, Vec::len);
+    if n == 0 || dim == 0 {
+        return Projection {
+            points: vec![[0.0; 2]; n],
+            explained_variance: [0.0; 2],
+        };
+    }
+
+    let mut mean = vec![0.0f64; dim];
+    for v in vectors {
+        for (m, x) in mean.iter_mut().zip(v) {
+            *m += f64::from(*x);
+        }
+    }
+    mean.iter_mut().for_each(|m| *m /= n as f64);
+    let centered: Vec<Vec<f64>> = vectors
+        .iter()
+        .map(|v| {
+            v.iter()
+                .zip(&mean)
+                .map(|(x, m)| f64::from(*x) - m)
+                .collect()
+        })
+        .collect();
+    let total_variance = centered.iter().flatten().map(|x| x * x).sum::<f64>() / n as f64;
+
+    let mut components: Vec<Vec<f64>> = Vec::with_capacity(2);
+    let mut explained_variance = [0.0; 2];
+    for (c, explained) in explained_variance.iter_mut().enumerate() {
+        // Deterministic start, nudged per component so it isn't orthogonal
+        // to the answer by construction
+        let mut v: Vec<f64> = (0..dim).map(|i| 1.0 / (i + c + 1) as f64).collect();
+        orthonormalize(&mut v, &components);
+        for _ in 0..PCA_ITERATIONS {
+            let mut next = covariance_times(&centered, &v);
+            if !orthonormalize(&mut next, &components) {
+                // No variance left in any remaining direction
+                v.iter_mut().for_each(|x| *x = 0.0);
+                break;
+            }
+            let converged = 1.0 - dot(&next, &v).abs() < PCA_TOLERANCE;
+            v = next;
+            if converged {
+                break;
+            }
+        }
+
+        // Power iteration's sign is arbitrary; fix it so the plot doesn't flip
+        let largest = v
+            .iter()
+            .copied()
+            .fold(0.0f64, |a, b| if b.abs() > a.abs() { b } else { a });
+        if largest < 0.0 {
+            v.iter_mut().for_each(|x| *x = -*x);
+        }
+
+        if total_variance > 0.0 {
+            *explained = dot(&covariance_times(&centered, &v), &v) / total_variance;
+        }
+        components.push(v);
+    }
+
+    let points = centered
+        .iter()
+        .map(|x| [dot(x, &components[0]), dot(x, &components[1])])
+        .collect();
+    Projection {
+        points,
+        explained_variance,
+    }
+}
+
+/// `Cv` for the covariance `C` of the centered rows, computed as `Xᵀ(Xv) / n`
+fn covariance_times(centered: &[Vec<f64>], v: &[f64]) -> Vec<f64> {
+    let mut out = vec![0.0; v.len()];
+    for x in centered {
+        let weight = dot(x, v);
+        for (o, xi) in out.iter_mut().zip(x) {
+            *o += weight * xi;
+        }
+    }
+    let n = centered.len() as f64;
+    out.iter_mut().for_each(|o| *o /= n);
+    out
+}
+
+/// Remove the components along `basis` and scale to unit length, returning
+/// false if nothing is left
+fn orthonormalize(v: &mut [f64], basis: &[Vec<f64>]) -> bool {
+    for b in basis {
+        let d = dot(v, b);
+        for (x, bi) in v.iter_mut().zip(b) {
+            *x -= d * bi;
+        }
+    }
+    let norm = dot(v, v).sqrt();
+    if norm < 1e-12 {
+        return false;
+    }
+    v.iter_mut().for_each(|x| *x /= norm);
+    true
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Group embeddings into at most `k` clusters, returning each one's cluster
+///
+/// Centers start at the first embedding and then the farthest-out ones, so
+/// callers should pass the most important embeddings first. Clusters are
+/// numbered largest first.
+pub fn kmeans(vectors: &[Vec<f32>], k: usize) -> Vec<usize> {
+    let k = k.min(vectors.len());
+    if k == 0 {
+        return vec![0; vectors.len()];
+    }
+
+    let distance =
+        |a: &[f32], b: &[f32]| -> f32 { a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum() };
+
+    let mut centers: Vec<Vec<f32>> = vec![vectors[0].clone()];
+    let mut nearest: Vec<f32> = vectors.iter().map(|v| distance(v, &centers[0])).collect();
+    while centers.len() < k {
+        let (far, _) =
+            nearest.iter().enumerate().fold(
+                (0, -1.0f32),
+                |best, (i, d)| if *d > best.1 { (i, *d) } else { best },
+            );
+        centers.push(vectors[far].clone());
+        let center = &centers[centers.len() - 1];
+        for (d, v) in nearest.iter_mut().zip(vectors) {
+            *d = d.min(distance(v, center));
+        }
+    }
+
+    let mut assignments = vec![usize::MAX; vectors.len()];
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut changed = false;
+        for (assignment, v) in assignments.iter_mut().zip(vectors) {
+            let (closest, _) = centers
+                .iter()
+                .enumerate()
+                .map(|(c, center)| (c, distance(v, center)))
+                .fold(
+                    (0, f32::INFINITY),
+                    |best, (c, d)| if d < best.1 { (c, d) } else { best },
+                );
+            if *assignment != closest {
+                *assignment = closest;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        // An emptied cluster keeps its old center
+        for (c, center) in centers.iter_mut().enumerate() {
+            let members: Vec<&Vec<f32>> = vectors
+                .iter()
+                .zip(&assignments)
+                .filter(|(_, a)| **a == c)
+                .map(|(v, _)| v)
+                .collect();
+            if members.is_empty() {
+                continue;
+            }
+            for (i, x) in center.iter_mut().enumerate() {
+                *x = members.iter().map(|m| m[i]).sum::<f32>() / members.len() as f32;
+            }
+        }
+    }
+
+    // Renumber largest first, ties by first appearance
+    let mut sizes = vec![0usize; k];
+    assignments.iter().for_each(|a| sizes[*a] += 1);
+    let mut order: Vec<usize> = (0..k).collect();
+    order.sort_by_key(|c| std::cmp::Reverse(sizes[*c]));
+    let mut renumbered = vec![0; k];
+    for (new, old) in order.into_iter().enumerate() {
+        renumbered[old] = new;
+    }
+    assignments.into_iter().map(|a| renumbered[a]).collect()
+}
+
+/// The member of each cluster nearest its center (the mean of its members),
+/// by index into `vectors`, for clusters numbered from 0 as `kmeans` does
+pub fn representatives(vectors: &[Vec<f32>], assignments: &[usize]) -> Vec<usize> {
+    let cluster_count = assignments.iter().max().map_or(0, |c| c + 1);
+    let dim = vectors.first().map_or(0, Vec::len);
+    let mut centers = vec![vec![0.0f32; dim]; cluster_count];
+    let mut sizes = vec![0usize; cluster_count];
+    for (v, c) in vectors.iter().zip(assignments) {
+        sizes[*c] += 1;
+        for (x, vi) in centers[*c].iter_mut().zip(v) {
+            *x += vi;
+        }
+    }
+    for (center, size) in centers.iter_mut().zip(&sizes) {
+        center.iter_mut().for_each(|x| *x /= (*size).max(1) as f32);
+    }
+
+    let mut best = vec![(usize::MAX, f32::INFINITY); cluster_count];
+    for (i, (v, c)) in vectors.iter().zip(assignments).enumerate() {
+        let d: f32 = v
+            .iter()
+            .zip(&centers[*c])
+            .map(|(x, y)| (x - y) * (x - y))
+            .sum();
+        if d < best[*c].1 {
+            best[*c] = (i, d);
+        }
+    }
+    best.into_iter().map(|(i, _)| i).collect()
+}
+
+/// Name a cluster after the tables its queries reference most, e.g.
+/// `"orders, customers"`
+pub fn cluster_label<'a>(queries: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for query in queries {
+        for table in referenced_tables(query, SqlDialect::Generic) {
+            *counts.entry(table).or_default() += 1;
+        }
+    }
+
+    let mut tables: Vec<(String, usize)> = counts.into_iter().collect();
+    tables.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let names: Vec<String> = tables
+        .into_iter()
+        .take(LABEL_TABLES)
+        .map(|(table, _)| table)
+        .collect();
+    (!names.is_empty()).then(|| names.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_projects_and_clusters_embeddings() {
+        // Two tight groups spread along the first axis, a little noise on the second
+        let vectors: Vec<Vec<f32>> = (0..10)
+            .map(|i| {
+                let side = if i < 6 { 1.0 } else { -1.0 };
+                vec![side * 5.0, (i % 3) as f32 * 0.1, 0.0]
+            })
+            .collect();
+
+        let projection = pca_2d(&vectors);
+        assert_eq!(projection.points.len(), 10);
+        assert!(projection.explained_variance[0] > 0.99);
+        assert!(projection.explained_variance[1] < 0.01);
+        let x = |i: usize| projection.points[i][0];
+        assert!(x(0) * x(9) < 0.0);
+        assert!((x(0) - x(5)).abs() < 0.1);
+        assert_eq!(pca_2d(&vectors).points, projection.points);
+
+        let clusters = kmeans(&vectors, 2);
+        assert_eq!(clusters, [0, 0, 0, 0, 0, 0, 1, 1, 1, 1]);
+        assert_eq!(kmeans(&vectors[..1], 8), [0]);
+        assert!(kmeans(&[], 8).is_empty());
+
+        // The first member at each side's mean noise (0.1 and 0.075)
+        assert_eq!(representatives(&vectors, &clusters), [1, 7]);
+        assert!(representatives(&[], &[]).is_empty());
+
+        assert_eq!(
+            cluster_label([
+                "SELECT * FROM orders o JOIN customers c ON c.id = o.customer_id",
+                "UPDATE orders SET status = $1",
+                "SELECT 1",
+            ])
+            .as_deref(),
+            Some("orders, customers")
+        );
+        assert_eq!(cluster_label(["SELECT 1"]), None);
+    }
+}
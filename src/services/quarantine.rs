@@ -0,0 +1,146 @@
+//! Quarantine of metrics that don't fit the database schema
+//!
+//! A metric whose insert fails because the schema doesn't match what this
+//! server writes (an unknown column, mismatched types) is moved to the
+//! dead-letter queue as `schema_drift` while the rest of its batch is stored.
+//! Unlike one bad value, drift usually means migrations and the server
+//! version are out of step, and it hits every metric until fixed. So
+//! quarantined metrics are also counted for Prometheus, logged at error
+//! level, and reported to the affected workspaces' alert destinations, at
+//! most once per workspace every 15 minutes.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::db::QuarantinedMetric;
+use crate::services::notify::{self, Notifier};
+use crate::store::MetricsStore;
+
+/// Shortest time between two alerts to one workspace's destinations
+const ALERT_COOLDOWN: TimeDelta = TimeDelta::minutes(15);
+
+/// Counts and reports metrics quarantined on schema drift
+pub struct Quarantine {
+    db: Arc<dyn MetricsStore>,
+    notifier: Arc<Notifier>,
+    /// Metrics quarantined since startup
+    total: AtomicU64,
+    /// Workspace -> when its destinations were last alerted
+    last_alerted: Mutex<HashMap<Uuid, DateTime<Utc>>>,
+}
+
+impl Quarantine {
+    pub fn new(db: Arc<dyn MetricsStore>, notifier: Arc<Notifier>) -> Self {
+        Self {
+            db,
+            notifier,
+            total: AtomicU64::new(0),
+            last_alerted: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Metrics quarantined since startup, for Prometheus
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// Count the metrics one insert quarantined, log them per workspace, and
+    /// alert the destinations of workspaces past their cooldown
+    ///
+    /// Alerts are sent in the background, so a slow destination doesn't hold
+    /// up the flush.
+    pub fn report(&self, quarantined: &[QuarantinedMetric], now: DateTime<Utc>) {
+        if quarantined.is_empty() {
+            return;
+        }
+        self.total
+            .fetch_add(quarantined.len() as u64, Ordering::Relaxed);
+
+        // Workspace -> (quarantined metrics, first error)
+        let mut by_workspace: HashMap<Uuid, (usize, &str)> = HashMap::new();
+        for metric in quarantined {
+            by_workspace
+                .entry(metric.workspace_id)
+                .or_insert((0, &metric.error))
+                .0 += 1;
+        }
+
+        for (workspace_id, (count, error)) in by_workspace {
+            error!(
+                workspace_id = %workspace_id,
+                count = count,
+                error = error,
+                "Metrics quarantined: the database schema doesn't match this server, check migrations"
+            );
+            if !self.mark_alerted(workspace_id, now) {
+                continue;
+            }
+
+            let db = Arc::clone(&self.db);
+            let notifier = Arc::clone(&self.notifier);
+            let payload = notify::quarantine_payload(workspace_id, count, error);
+            tokio::spawn(async move {
+                let destinations = match db.list_alert_destinations(workspace_id).await {
+                    Ok(d) => d,
+                    Err(e) => {
+                        error!(error = %e, workspace_id = %workspace_id, "Failed to list alert destinations for quarantine");
+                        return;
+                    }
+                };
+                for destination in destinations.iter().filter(|d| d.enabled) {
+                    notifier
+                        .deliver(destination, notify::QUARANTINE_EVENT, &payload, false)
+                        .await;
+                }
+            });
+        }
+    }
+
+    /// Whether a workspace's destinations may be alerted at `now`, recording
+    /// the alert if so
+    fn mark_alerted(&self, workspace_id: Uuid, now: DateTime<Utc>) -> bool {
+        let mut last_alerted = self.last_alerted.lock();
+        match last_alerted.get(&workspace_id) {
+            Some(at) if now - *at < ALERT_COOLDOWN => false,
+            _ => {
+                last_alerted.insert(workspace_id, now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "memory-store"))]
+mod tests {
+    use super::*;
+    use crate::store::memory::MemoryStore;
+
+    fn quarantined(workspace_id: Uuid) -> QuarantinedMetric {
+        QuarantinedMetric {
+            workspace_id,
+            error: "column \"labels\" does not exist".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_counted_and_alerted_once_per_cooldown() {
+        let db: Arc<dyn MetricsStore> = Arc::new(MemoryStore::new());
+        let quarantine = Quarantine::new(db.clone(), Arc::new(Notifier::new(db)));
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let now: DateTime<Utc> = "2026-01-01T12:00:00Z".parse().unwrap();
+
+        quarantine.report(&[quarantined(a), quarantined(a), quarantined(b)], now);
+        assert_eq!(quarantine.total(), 3);
+
+        // Both were just alerted
+        let soon = now + TimeDelta::minutes(5);
+        assert!(!quarantine.mark_alerted(a, soon));
+        assert!(!quarantine.mark_alerted(b, soon));
+        assert!(quarantine.mark_alerted(a, now + ALERT_COOLDOWN));
+    }
+}
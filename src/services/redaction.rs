@@ -0,0 +1,127 @@
+//! Literal redaction for ingested query text
+//!
+//! Workspaces can opt in to having the string and numeric literals in
+//! `query_text` replaced with `?` placeholders at ingest, before a metric
+//! reaches the buffer, the live stream, or the database. Literals often carry
+//! emails, tokens, and other values that mustn't be stored.
+//!
+//! Queries are tokenized with the service's dialect, so quoted identifiers,
+//! bind placeholders, comments, and whitespace are kept as sent. A query that
+//! can't be tokenized (e.g. an unterminated string) can't be redacted safely,
+//! so its text is replaced entirely with [`UNPARSEABLE`].
+
+use parking_lot::RwLock;
+use sqlparser::tokenizer::{Token, Tokenizer};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::services::fingerprint::SqlDialect;
+
+/// Text stored in place of a query that couldn't be tokenized
+pub const UNPARSEABLE: &str = "/* redacted: query could not be parsed */";
+
+/// Replace the literals in a query with `?`
+pub fn redact_literals(sql: &str, dialect: SqlDialect) -> String {
+    let tokens = match Tokenizer::new(dialect.parser_dialect().as_ref(), sql).tokenize() {
+        Ok(tokens) => tokens,
+        Err(_) => return UNPARSEABLE.to_string(),
+    };
+
+    let mut out = String::with_capacity(sql.len());
+    for token in tokens {
+        if is_literal(&token) {
+            out.push('?');
+        } else if token != Token::EOF {
+            out.push_str(&token.to_string());
+        }
+    }
+    out
+}
+
+fn is_literal(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Number(..)
+            | Token::SingleQuotedString(_)
+            | Token::DoubleQuotedString(_)
+            | Token::TripleSingleQuotedString(_)
+            | Token::TripleDoubleQuotedString(_)
+            | Token::DollarQuotedString(_)
+            | Token::SingleQuotedByteStringLiteral(_)
+            | Token::DoubleQuotedByteStringLiteral(_)
+            | Token::TripleSingleQuotedByteStringLiteral(_)
+            | Token::TripleDoubleQuotedByteStringLiteral(_)
+            | Token::SingleQuotedRawStringLiteral(_)
+            | Token::DoubleQuotedRawStringLiteral(_)
+            | Token::TripleSingleQuotedRawStringLiteral(_)
+            | Token::TripleDoubleQuotedRawStringLiteral(_)
+            | Token::NationalStringLiteral(_)
+            | Token::EscapedStringLiteral(_)
+            | Token::UnicodeStringLiteral(_)
+            | Token::HexStringLiteral(_)
+    )
+}
+
+/// Short-lived cache of workspace redaction settings, so the setting isn't
+/// looked up for every ingested batch
+pub struct RedactionCache {
+    entries: RwLock<HashMap<Uuid, (bool, Instant)>>,
+    ttl: Duration,
+}
+
+impl RedactionCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            ttl,
+        }
+    }
+
+    pub fn get(&self, workspace_id: Uuid) -> Option<bool> {
+        self.entries
+            .read()
+            .get(&workspace_id)
+            .filter(|(_, cached_at)| cached_at.elapsed() < self.ttl)
+            .map(|(enabled, _)| *enabled)
+    }
+
+    /// Record a workspace's setting, e.g. right after it changes
+    pub fn insert(&self, workspace_id: Uuid, enabled: bool) {
+        self.entries
+            .write()
+            .insert(workspace_id, (enabled, Instant::now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literals_become_placeholders() {
+        let sql = "SELECT id, \"Email\" FROM users\n  WHERE email = 'jane@example.com' AND age > 42 -- lookup\n  AND token = $1";
+        assert_eq!(
+            redact_literals(sql, SqlDialect::Postgres),
+            "SELECT id, \"Email\" FROM users\n  WHERE email = ? AND age > ? -- lookup\n  AND token = $1"
+        );
+        assert_eq!(
+            redact_literals(
+                "SELECT * FROM t WHERE k = $$secret$$ OR k = E'a\\'b'",
+                SqlDialect::Postgres
+            ),
+            "SELECT * FROM t WHERE k = ? OR k = ?"
+        );
+        assert_eq!(
+            redact_literals(
+                "SELECT `t1`.* FROM `t1` WHERE n IN (1, 2.5)",
+                SqlDialect::MySql
+            ),
+            "SELECT `t1`.* FROM `t1` WHERE n IN (?, ?)"
+        );
+        assert_eq!(
+            redact_literals("SELECT 'unterminated", SqlDialect::Generic),
+            UNPARSEABLE
+        );
+    }
+}
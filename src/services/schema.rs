@@ -0,0 +1,439 @@
+//! JSON Schemas for agent payloads
+//!
+//! The schemas are served to agent developers and enforced at ingest, so
+//! agents in any language can validate payloads before sending them. The
+//! validator implements the subset of JSON Schema (draft 2020-12) these
+//! schemas use: `type`, `enum`, `format` (`uuid`, `date-time`),
+//! `minLength`, `maxLength`, `minimum`, `pattern`, `properties`, `required`,
+//! `maxProperties`, `propertyNames`, `additionalProperties` (as a schema), and
+//! `items`. Other keywords are ignored.
+
+use chrono::DateTime;
+use parking_lot::RwLock;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+use crate::error::{AppError, FieldError};
+use crate::versioning::ApiVersion;
+
+/// Most errors reported for one payload
+const MAX_ERRORS: usize = 50;
+
+/// Most labels on one metric
+pub const MAX_LABELS: usize = 16;
+
+/// Label keys: identifier-like, so they can be named in `labels.<key>` filters
+pub const LABEL_KEY_PATTERN: &str = "^[A-Za-z_][A-Za-z0-9_.-]{0,62}$";
+
+/// Longest label value, in characters
+pub const MAX_LABEL_VALUE_LENGTH: usize = 256;
+
+/// JSON Schema of the ingest payload for an API version
+pub fn ingest_schema(version: ApiVersion) -> &'static Value {
+    static V1: OnceLock<Value> = OnceLock::new();
+    static V2: OnceLock<Value> = OnceLock::new();
+    match version {
+        ApiVersion::V1 => V1.get_or_init(ingest_schema_v1),
+        ApiVersion::V2 => V2.get_or_init(ingest_schema_v2),
+    }
+}
+
+fn ingest_schema_v1() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": "/api/v1/schemas/ingest",
+        "title": "QueryVault v1 ingest payload",
+        "type": "object",
+        "required": ["metrics"],
+        "properties": {
+            "metrics": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": [
+                        "id", "workspace_id", "service_id", "query_text", "status",
+                        "duration_ms", "started_at", "completed_at"
+                    ],
+                    "properties": {
+                        "id": { "type": "string", "format": "uuid" },
+                        "workspace_id": { "type": "string", "format": "uuid" },
+                        "service_id": { "type": "string", "format": "uuid" },
+                        "service_name": { "type": ["string", "null"], "minLength": 1, "maxLength": 255 },
+                        "query_text": { "type": "string" },
+                        "status": { "enum": status_names() },
+                        "duration_ms": { "type": "integer", "minimum": 0 },
+                        "rows_affected": { "type": ["integer", "null"] },
+                        "error_message": { "type": ["string", "null"] },
+                        "started_at": { "type": "string", "format": "date-time" },
+                        "completed_at": { "type": "string", "format": "date-time" },
+                        "tags": { "type": "array", "items": { "type": "string" } },
+                        "labels": labels_schema()
+                    }
+                }
+            }
+        }
+    })
+}
+
+fn ingest_schema_v2() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": "/api/v2/schemas/ingest",
+        "title": "QueryVault v2 ingest payload",
+        "type": "object",
+        "required": ["metrics"],
+        "properties": {
+            "metrics": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["id", "ws", "svc", "q", "st", "dur_us", "ts"],
+                    "properties": {
+                        "id": { "type": "string", "format": "uuid" },
+                        "ws": { "type": "string", "format": "uuid", "description": "Workspace ID" },
+                        "svc": { "type": "string", "format": "uuid", "description": "Service ID" },
+                        "svc_name": { "type": ["string", "null"], "minLength": 1, "maxLength": 255 },
+                        "q": { "type": "string", "description": "Query text" },
+                        "fp": {
+                            "type": ["string", "null"],
+                            "pattern": "^[A-Za-z0-9_-]{1,64}$",
+                            "description": "Fingerprint computed by the agent"
+                        },
+                        "st": { "enum": status_names(), "description": "Status" },
+                        "dur_us": { "type": "integer", "minimum": 0, "description": "Duration in microseconds" },
+                        "rows": { "type": ["integer", "null"] },
+                        "err": { "type": ["string", "null"], "description": "Error message" },
+                        "ts": { "type": "string", "format": "date-time", "description": "Start time" },
+                        "tags": { "type": "array", "items": { "type": "string" } },
+                        "labels": labels_schema()
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// JSON Schema of a browser beacon event
+pub fn beacon_schema() -> &'static Value {
+    static BEACON: OnceLock<Value> = OnceLock::new();
+    BEACON.get_or_init(|| {
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "QueryVault browser beacon",
+            "type": "object",
+            "required": ["service_id", "operation", "duration_ms"],
+            "properties": {
+                "service_id": { "type": "string", "format": "uuid" },
+                "service_name": { "type": ["string", "null"], "minLength": 1, "maxLength": 255 },
+                "operation": {
+                    "type": "string",
+                    "minLength": 1,
+                    "description": "GraphQL document, SQL text, or operation name"
+                },
+                "duration_ms": { "type": "number", "minimum": 0 },
+                "status": { "enum": status_names() },
+                "error": { "type": ["string", "null"] },
+                "tags": { "type": "array", "items": { "type": "string" } }
+            }
+        })
+    })
+}
+
+fn labels_schema() -> Value {
+    json!({
+        "type": "object",
+        "maxProperties": MAX_LABELS,
+        "propertyNames": { "pattern": LABEL_KEY_PATTERN },
+        "additionalProperties": { "type": "string", "maxLength": MAX_LABEL_VALUE_LENGTH },
+        "description": "Key/value labels, filterable with `labels.<key>=<value>`"
+    })
+}
+
+fn status_names() -> Value {
+    json!(["running", "success", "failed", "cancelled", "timeout"])
+}
+
+/// Validate a payload against a schema, reporting each failing field by its
+/// JSON Pointer
+pub fn validate(schema: &Value, instance: &Value) -> Result<(), AppError> {
+    let mut errors = Vec::new();
+    check(schema, instance, "", &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::InvalidPayload(errors))
+    }
+}
+
+fn check(schema: &Value, instance: &Value, path: &str, errors: &mut Vec<FieldError>) {
+    if errors.len() >= MAX_ERRORS {
+        return;
+    }
+    let mut fail = |message: String| {
+        errors.push(FieldError {
+            path: if path.is_empty() {
+                "/".into()
+            } else {
+                path.into()
+            },
+            message,
+        })
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.iter().any(|t| has_type(instance, t)) {
+            // Further keywords would only repeat the same mistake
+            return fail(format!(
+                "expected {}, found {}",
+                types.join(" or "),
+                type_name(instance)
+            ));
+        }
+    }
+
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(instance) {
+            let names: Vec<String> = allowed.iter().map(Value::to_string).collect();
+            return fail(format!("must be one of {}", names.join(", ")));
+        }
+    }
+
+    if let Value::String(s) = instance {
+        match schema.get("format").and_then(Value::as_str) {
+            Some("uuid") if Uuid::parse_str(s).is_err() => fail("must be a UUID".into()),
+            Some("date-time") if DateTime::parse_from_rfc3339(s).is_err() => {
+                fail("must be an RFC 3339 date-time".into())
+            }
+            _ => {}
+        }
+        let len = s.chars().count() as u64;
+        if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+            if len < min {
+                fail(format!("must be at least {} characters", min));
+            }
+        }
+        if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+            if len > max {
+                fail(format!("must be at most {} characters", max));
+            }
+        }
+        if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+            if !pattern_matches(pattern, s) {
+                fail(format!("must match {}", pattern));
+            }
+        }
+    }
+
+    if let (Some(min), Some(n)) = (
+        schema.get("minimum").and_then(Value::as_f64),
+        instance.as_f64(),
+    ) {
+        if n < min {
+            fail(format!("must be at least {}", min));
+        }
+    }
+
+    if let Value::Object(fields) = instance {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !fields.contains_key(name) {
+                    fail(format!("missing required field '{}'", name));
+                }
+            }
+        }
+        if let Some(max) = schema.get("maxProperties").and_then(Value::as_u64) {
+            if fields.len() as u64 > max {
+                fail(format!("must have at most {} fields", max));
+            }
+        }
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let additional = schema.get("additionalProperties").filter(|s| s.is_object());
+        let names = schema.get("propertyNames");
+        for (name, value) in fields {
+            let field_path = child_path(path, name);
+            if let Some(names) = names {
+                check(names, &Value::String(name.clone()), &field_path, errors);
+            }
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(property) => check(property, value, &field_path, errors),
+                None => {
+                    if let Some(additional) = additional {
+                        check(additional, value, &field_path, errors);
+                    }
+                }
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (instance, schema.get("items")) {
+        for (i, item) in items.iter().enumerate() {
+            check(item_schema, item, &format!("{}/{}", path, i), errors);
+        }
+    }
+}
+
+fn has_type(instance: &Value, name: &str) -> bool {
+    match name {
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "string" => instance.is_string(),
+        "integer" => instance.is_i64() || instance.is_u64(),
+        "number" => instance.is_number(),
+        "boolean" => instance.is_boolean(),
+        "null" => instance.is_null(),
+        _ => false,
+    }
+}
+
+fn type_name(instance: &Value) -> &'static str {
+    match instance {
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::String(_) => "string",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::Bool(_) => "boolean",
+        Value::Null => "null",
+    }
+}
+
+/// JSON Pointer of a field, escaping `~` and `/` in its name
+fn child_path(path: &str, name: &str) -> String {
+    format!("{}/{}", path, name.replace('~', "~0").replace('/', "~1"))
+}
+
+/// Match against a schema pattern, compiling each pattern once
+fn pattern_matches(pattern: &str, s: &str) -> bool {
+    static CACHE: OnceLock<RwLock<HashMap<String, Regex>>> = OnceLock::new();
+    let cache = CACHE.get_or_init(Default::default);
+    if let Some(regex) = cache.read().get(pattern) {
+        return regex.is_match(s);
+    }
+    match Regex::new(pattern) {
+        Ok(regex) => {
+            let matched = regex.is_match(s);
+            cache.write().insert(pattern.to_string(), regex);
+            matched
+        }
+        // The schemas are ours; an invalid pattern is a bug, not a bad payload
+        Err(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(result: Result<(), AppError>) -> Vec<String> {
+        match result {
+            Err(AppError::InvalidPayload(errors)) => {
+                let mut paths: Vec<String> = errors.into_iter().map(|e| e.path).collect();
+                paths.sort();
+                paths
+            }
+            other => panic!("expected InvalidPayload, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_valid_payloads_pass() {
+        let v1 = json!({"metrics": [{
+            "id": "550e8400-e29b-41d4-a716-446655440001",
+            "workspace_id": "550e8400-e29b-41d4-a716-446655440000",
+            "service_id": "550e8400-e29b-41d4-a716-446655440002",
+            "query_text": "SELECT 1",
+            "status": "success",
+            "duration_ms": 42,
+            "started_at": "2026-01-10T00:00:00Z",
+            "completed_at": "2026-01-10T00:00:00Z"
+        }]});
+        assert!(validate(ingest_schema(ApiVersion::V1), &v1).is_ok());
+
+        let v2 = json!({"metrics": [{
+            "id": "550e8400-e29b-41d4-a716-446655440001",
+            "ws": "550e8400-e29b-41d4-a716-446655440000",
+            "svc": "550e8400-e29b-41d4-a716-446655440002",
+            "q": "SELECT 1",
+            "fp": "abc_123",
+            "st": "success",
+            "dur_us": 1500,
+            "ts": "2026-01-10T00:00:00Z"
+        }]});
+        assert!(validate(ingest_schema(ApiVersion::V2), &v2).is_ok());
+    }
+
+    #[test]
+    fn test_labels_are_validated() {
+        let metric = |labels: Value| {
+            json!({"metrics": [{
+                "id": "550e8400-e29b-41d4-a716-446655440001",
+                "ws": "550e8400-e29b-41d4-a716-446655440000",
+                "svc": "550e8400-e29b-41d4-a716-446655440002",
+                "q": "SELECT 1",
+                "st": "success",
+                "dur_us": 1500,
+                "ts": "2026-01-10T00:00:00Z",
+                "labels": labels
+            }]})
+        };
+        let schema = ingest_schema(ApiVersion::V2);
+        assert!(validate(schema, &metric(json!({"env": "prod", "k8s.pod": "api-1"}))).is_ok());
+        assert_eq!(
+            paths(validate(
+                schema,
+                &metric(json!({"env": 1, "has space": "x", "ok": "y".repeat(300)}))
+            )),
+            vec![
+                "/metrics/0/labels/env",
+                "/metrics/0/labels/has space",
+                "/metrics/0/labels/ok"
+            ]
+        );
+
+        let too_many: serde_json::Map<String, Value> = (0..=MAX_LABELS)
+            .map(|i| (format!("k{}", i), json!("v")))
+            .collect();
+        assert_eq!(
+            paths(validate(schema, &metric(Value::Object(too_many)))),
+            vec!["/metrics/0/labels"]
+        );
+    }
+
+    #[test]
+    fn test_errors_point_at_fields() {
+        let payload = json!({"metrics": [{
+            "id": "not-a-uuid",
+            "ws": "550e8400-e29b-41d4-a716-446655440000",
+            "svc": "550e8400-e29b-41d4-a716-446655440002",
+            "q": "SELECT 1",
+            "fp": "has spaces",
+            "st": "done",
+            "dur_us": -1
+        }]});
+        assert_eq!(
+            paths(validate(ingest_schema(ApiVersion::V2), &payload)),
+            vec![
+                "/metrics/0",
+                "/metrics/0/dur_us",
+                "/metrics/0/fp",
+                "/metrics/0/id",
+                "/metrics/0/st"
+            ]
+        );
+        assert_eq!(
+            paths(validate(
+                ingest_schema(ApiVersion::V1),
+                &json!({"metrics": {}})
+            )),
+            vec!["/metrics"]
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! Anomaly severity scoring and impact estimation
+//!
+//! A z-score alone says how unusual one execution was, not how much it
+//! matters: a once-a-day report taking 50x its usual time scores far higher
+//! than a hot query that became 10x slower, yet the hot query is the one
+//! burning database time. Severity blends three components, each scaled to
+//! `0..=1` on a log scale:
+//!
+//! - **deviation**: the z-score, from the detection threshold (3) up to 30
+//! - **frequency**: calls per hour, from 1 up to 10,000
+//! - **impact**: extra database time per hour if every call were this slow,
+//!   from 1 second up to a full hour
+//!
+//! Impact carries half the weight, so frequent slow queries rank first.
+//! The result is a score from 0 to 100, bucketed into [`SeverityLevel`]s.
+//! The same extra-time arithmetic, for the anomalous execution alone, gives
+//! each anomaly's stored impact estimate ([`extra_time_ms`]).
+//!
+//! A low score can still hide an execution nobody should wait through, so
+//! [`level`] escalates on how far and how long an execution ran regardless
+//! of its score: a z-score of 10 or a 5 second duration is at least `high`
+//! (a warning), a z-score of 30 or a 30 second duration is `critical`.
+
+use serde::{Deserialize, Serialize};
+
+/// Z-score at which an execution is flagged as an anomaly
+const Z_THRESHOLD: f64 = 3.0;
+
+/// Z-score at or above which an anomaly is at least high
+const WARNING_Z_SCORE: f64 = 10.0;
+
+/// Z-score at or above which an anomaly is critical
+const CRITICAL_Z_SCORE: f64 = 30.0;
+
+/// Duration at or above which an anomaly is at least high
+const WARNING_DURATION_MS: f64 = 5_000.0;
+
+/// Duration at or above which an anomaly is critical
+const CRITICAL_DURATION_MS: f64 = 30_000.0;
+
+const DEVIATION_WEIGHT: f64 = 0.25;
+const FREQUENCY_WEIGHT: f64 = 0.25;
+const IMPACT_WEIGHT: f64 = 0.5;
+
+/// Coarse severity bucket used for filtering and alert routing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeverityLevel {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl SeverityLevel {
+    /// Every level, least severe first
+    pub const ALL: [SeverityLevel; 4] = [
+        SeverityLevel::Low,
+        SeverityLevel::Medium,
+        SeverityLevel::High,
+        SeverityLevel::Critical,
+    ];
+
+    /// Bucket for a score from [`score`]
+    pub fn from_score(score: f64) -> Self {
+        if score >= 75.0 {
+            SeverityLevel::Critical
+        } else if score >= 50.0 {
+            SeverityLevel::High
+        } else if score >= 25.0 {
+            SeverityLevel::Medium
+        } else {
+            SeverityLevel::Low
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SeverityLevel::Low => "low",
+            SeverityLevel::Medium => "medium",
+            SeverityLevel::High => "high",
+            SeverityLevel::Critical => "critical",
+        }
+    }
+}
+
+impl std::str::FromStr for SeverityLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(SeverityLevel::Low),
+            "medium" => Ok(SeverityLevel::Medium),
+            "high" => Ok(SeverityLevel::High),
+            "critical" => Ok(SeverityLevel::Critical),
+            other => Err(format!("Unknown severity level: {}", other)),
+        }
+    }
+}
+
+/// Level of an anomaly: its score's bucket, raised to `high` or `critical`
+/// when its z-score or duration alone is in the warning or critical band
+pub fn level(score: f64, z_score: f64, duration_ms: f64) -> SeverityLevel {
+    let band = if z_score >= CRITICAL_Z_SCORE || duration_ms >= CRITICAL_DURATION_MS {
+        SeverityLevel::Critical
+    } else if z_score >= WARNING_Z_SCORE || duration_ms >= WARNING_DURATION_MS {
+        SeverityLevel::High
+    } else {
+        SeverityLevel::Low
+    };
+    SeverityLevel::from_score(score).max(band)
+}
+
+/// Extra database time in milliseconds if `occurrences` calls each took
+/// `duration_ms` instead of `baseline_ms`
+pub fn extra_time_ms(duration_ms: f64, baseline_ms: f64, occurrences: f64) -> f64 {
+    (duration_ms - baseline_ms).max(0.0) * occurrences.max(0.0)
+}
+
+/// Severity score from 0 to 100
+///
+/// `calls_per_hour` is the query's recent call rate; `baseline_ms` is the
+/// duration the anomaly is measured against.
+pub fn score(z_score: f64, duration_ms: f64, baseline_ms: f64, calls_per_hour: f64) -> f64 {
+    let calls_per_hour = calls_per_hour.max(0.0);
+    let deviation = (z_score / Z_THRESHOLD).max(1.0).log10();
+    let frequency = (1.0 + calls_per_hour).log10() / 4.0;
+    let extra_secs_per_hour = extra_time_ms(duration_ms, baseline_ms, calls_per_hour) / 1000.0;
+    let impact = (1.0 + extra_secs_per_hour).log10() / 3601f64.log10();
+
+    100.0
+        * (DEVIATION_WEIGHT * deviation.min(1.0)
+            + FREQUENCY_WEIGHT * frequency.min(1.0)
+            + IMPACT_WEIGHT * impact.min(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hot_regression_outranks_rare_blip() {
+        // 20ms query called 10,000 times an hour, now taking 200ms
+        let hot = score(6.0, 200.0, 20.0, 10_000.0);
+        // Daily job normally at 20ms that took 5s once
+        let blip = score(50.0, 5000.0, 20.0, 1.0 / 24.0);
+
+        assert!(hot > blip, "hot {} <= blip {}", hot, blip);
+        assert_eq!(SeverityLevel::from_score(hot), SeverityLevel::Critical);
+        assert_eq!(SeverityLevel::from_score(blip), SeverityLevel::Medium);
+    }
+
+    #[test]
+    fn test_score_bounds() {
+        assert_eq!(score(3.0, 20.0, 20.0, 0.0), 0.0);
+        assert_eq!(score(1e6, 1e9, 0.0, 1e9), 100.0);
+        for (level, min_score) in SeverityLevel::ALL.into_iter().zip([0.0, 25.0, 50.0, 75.0]) {
+            assert_eq!(SeverityLevel::from_score(min_score), level);
+            assert_eq!(level.as_str().parse(), Ok(level));
+        }
+    }
+
+    #[test]
+    fn test_bands_escalate_level() {
+        // A 3.1-sigma blip of a rare query
+        let blip = score(3.1, 130.0, 100.0, 1.0);
+        assert_eq!(level(blip, 3.1, 130.0), SeverityLevel::Low);
+        // A 40 second run of the same query, barely unusual by its z-score
+        let stall = score(3.1, 40_000.0, 100.0, 1.0);
+        assert_eq!(SeverityLevel::from_score(stall), SeverityLevel::Low);
+        assert_eq!(level(stall, 3.1, 40_000.0), SeverityLevel::Critical);
+
+        assert_eq!(level(0.0, 12.0, 200.0), SeverityLevel::High);
+        assert_eq!(level(0.0, 4.0, 6_000.0), SeverityLevel::High);
+        assert_eq!(level(0.0, 45.0, 200.0), SeverityLevel::Critical);
+        // Bands only ever raise the score's level
+        assert_eq!(level(90.0, 3.5, 200.0), SeverityLevel::Critical);
+    }
+
+    #[test]
+    fn test_extra_time() {
+        assert_eq!(extra_time_ms(200.0, 20.0, 50.0), 9000.0);
+        // Faster than baseline costs nothing
+        assert_eq!(extra_time_ms(10.0, 20.0, 50.0), 0.0);
+    }
+}
@@ -0,0 +1,172 @@
+//! Latency SLO compliance, Apdex, error budgets, and burn-rate alerting
+//!
+//! A query is good when it succeeded within the SLO's threshold. Compliance
+//! is the good share of queries over the SLO's rolling window, and the error
+//! budget is how many bad queries the target allows. Apdex uses the
+//! threshold as its target time: successful queries within it are satisfied,
+//! those within four times it are tolerating, and the rest, including every
+//! failure, are frustrated.
+//!
+//! Burn-rate alerts follow the multiwindow scheme from the Google SRE
+//! workbook: the budget must be burning fast over both a long and a short
+//! window, so an alert starts quickly and stops soon after the burn does.
+
+use chrono::TimeDelta;
+use serde::{Deserialize, Serialize};
+
+/// Query outcomes over a time range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SloCounts {
+    pub total: i64,
+    /// Succeeded within the threshold
+    pub satisfied: i64,
+    /// Succeeded within four times the threshold, but not within it
+    pub tolerating: i64,
+}
+
+impl SloCounts {
+    /// Queries that didn't succeed within the threshold
+    pub fn bad(&self) -> i64 {
+        self.total - self.satisfied
+    }
+
+    /// Good share of queries, in percent
+    pub fn compliance(&self) -> Option<f64> {
+        (self.total > 0).then(|| self.satisfied as f64 / self.total as f64 * 100.0)
+    }
+
+    /// Apdex score from 0 to 1
+    pub fn apdex(&self) -> Option<f64> {
+        (self.total > 0)
+            .then(|| (self.satisfied as f64 + self.tolerating as f64 / 2.0) / self.total as f64)
+    }
+
+    /// Share of the error budget left, in percent; negative once overspent
+    pub fn budget_remaining(&self, target_percent: f64) -> Option<f64> {
+        let allowed = self.total as f64 * (1.0 - target_percent / 100.0);
+        (self.total > 0 && allowed > 0.0).then(|| (1.0 - self.bad() as f64 / allowed) * 100.0)
+    }
+
+    /// How many times faster than sustainable the budget is being spent
+    pub fn burn_rate(&self, target_percent: f64) -> Option<f64> {
+        let budget = 1.0 - target_percent / 100.0;
+        (self.total > 0 && budget > 0.0).then(|| self.bad() as f64 / self.total as f64 / budget)
+    }
+}
+
+/// Whether an SLO's error budget is burning fast enough to alert
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BurnState {
+    #[default]
+    Ok,
+    /// Would spend a 30-day budget in about 5 days
+    SlowBurn,
+    /// Would spend a 30-day budget in about 2 days
+    FastBurn,
+}
+
+impl BurnState {
+    /// Name as stored
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BurnState::Ok => "ok",
+            BurnState::SlowBurn => "slow_burn",
+            BurnState::FastBurn => "fast_burn",
+        }
+    }
+}
+
+impl std::str::FromStr for BurnState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ok" => Ok(BurnState::Ok),
+            "slow_burn" => Ok(BurnState::SlowBurn),
+            "fast_burn" => Ok(BurnState::FastBurn),
+            other => Err(format!("Unknown burn state: {}", other)),
+        }
+    }
+}
+
+/// A burn rate that must be exceeded over both windows to alert
+pub struct BurnPolicy {
+    pub state: BurnState,
+    pub long: TimeDelta,
+    pub short: TimeDelta,
+    pub rate: f64,
+}
+
+/// Burn policies, most severe first
+pub const BURN_POLICIES: [BurnPolicy; 2] = [
+    BurnPolicy {
+        state: BurnState::FastBurn,
+        long: TimeDelta::hours(1),
+        short: TimeDelta::minutes(5),
+        rate: 14.4,
+    },
+    BurnPolicy {
+        state: BurnState::SlowBurn,
+        long: TimeDelta::hours(6),
+        short: TimeDelta::minutes(30),
+        rate: 6.0,
+    },
+];
+
+/// The most severe state whose policy `rate_over` exceeds over both windows
+///
+/// `rate_over` gives the burn rate over the last `window`.
+pub fn burn_state(rate_over: impl Fn(TimeDelta) -> Option<f64>) -> BurnState {
+    BURN_POLICIES
+        .iter()
+        .find(|policy| {
+            [policy.long, policy.short]
+                .iter()
+                .all(|&window| rate_over(window).is_some_and(|rate| rate >= policy.rate))
+        })
+        .map_or(BurnState::Ok, |policy| policy.state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compliance_apdex_and_budget() {
+        let counts = SloCounts {
+            total: 1000,
+            satisfied: 985,
+            tolerating: 10,
+        };
+        assert_eq!(counts.compliance(), Some(98.5));
+        assert_eq!(counts.apdex(), Some(0.99));
+        // A 99% target allows 10 bad queries; 15 overspends by half
+        let close = |value: Option<f64>, expected: f64| (value.unwrap() - expected).abs() < 1e-9;
+        assert!(close(counts.budget_remaining(99.0), -50.0));
+        assert!(close(counts.burn_rate(99.0), 1.5));
+
+        let empty = SloCounts::default();
+        assert_eq!(empty.compliance(), None);
+        assert_eq!(empty.burn_rate(99.0), None);
+    }
+
+    #[test]
+    fn test_burn_state_needs_both_windows() {
+        let rates = |long: f64, short: f64| {
+            move |window: TimeDelta| {
+                Some(if window >= TimeDelta::hours(1) {
+                    long
+                } else {
+                    short
+                })
+            }
+        };
+        assert_eq!(burn_state(rates(20.0, 20.0)), BurnState::FastBurn);
+        // Recovered over the short window
+        assert_eq!(burn_state(rates(20.0, 1.0)), BurnState::Ok);
+        assert_eq!(burn_state(rates(8.0, 8.0)), BurnState::SlowBurn);
+        assert_eq!(burn_state(|_| None), BurnState::Ok);
+        assert!(BurnState::FastBurn > BurnState::SlowBurn);
+    }
+}
@@ -0,0 +1,130 @@
+//! Table-level analytics
+//!
+//! Every execution of a fingerprint touches the same tables, so each
+//! statement's text is parsed once, with the dialect of the service that
+//! sent it, and its totals are added to every table it references. A
+//! statement that modifies data counts as a write to all of its tables, so
+//! `INSERT INTO audit SELECT ... FROM users` is a write to both.
+
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::db::StatementTotals;
+use crate::services::fingerprint::{is_write, referenced_tables, DialectConfig};
+
+/// Executions touching one table over a time range
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TableStats {
+    /// Table name, lowercased, with its schema if the query gave one
+    pub table: String,
+    /// Distinct query fingerprints touching the table
+    pub fingerprint_count: i64,
+    pub query_count: i64,
+    pub read_count: i64,
+    pub write_count: i64,
+    /// Failed or timed-out executions
+    pub error_count: i64,
+    pub total_duration_ms: i64,
+    pub avg_duration_ms: f64,
+    /// Slowest execution touching the table
+    pub max_duration_ms: i64,
+}
+
+/// Sum statement totals per referenced table, most queried first
+pub fn table_stats(statements: &[StatementTotals], dialects: &DialectConfig) -> Vec<TableStats> {
+    let mut tables: HashMap<String, TableStats> = HashMap::new();
+    // Table -> fingerprints touching it, as services can share one
+    let mut fingerprints: HashMap<String, HashSet<&str>> = HashMap::new();
+    for statement in statements {
+        let dialect = dialects.dialect_for(statement.service_id);
+        let write = is_write(&statement.query_text, dialect);
+        for table in referenced_tables(&statement.query_text, dialect) {
+            let stats = tables.entry(table.clone()).or_insert_with(|| TableStats {
+                table,
+                fingerprint_count: 0,
+                query_count: 0,
+                read_count: 0,
+                write_count: 0,
+                error_count: 0,
+                total_duration_ms: 0,
+                avg_duration_ms: 0.0,
+                max_duration_ms: 0,
+            });
+            fingerprints
+                .entry(stats.table.clone())
+                .or_default()
+                .insert(&statement.fingerprint);
+            stats.query_count += statement.call_count;
+            if write {
+                stats.write_count += statement.call_count;
+            } else {
+                stats.read_count += statement.call_count;
+            }
+            stats.error_count += statement.error_count;
+            stats.total_duration_ms += statement.total_duration_ms;
+            stats.max_duration_ms = stats.max_duration_ms.max(statement.max_duration_ms);
+        }
+    }
+
+    let mut tables: Vec<TableStats> = tables
+        .into_values()
+        .map(|mut stats| {
+            stats.fingerprint_count = fingerprints[&stats.table].len() as i64;
+            stats.avg_duration_ms = stats.total_duration_ms as f64 / stats.query_count as f64;
+            stats
+        })
+        .collect();
+    tables.sort_by(|a, b| {
+        b.query_count
+            .cmp(&a.query_count)
+            .then_with(|| a.table.cmp(&b.table))
+    });
+    tables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::fingerprint::SqlDialect;
+    use uuid::Uuid;
+
+    fn statement(service_id: Uuid, sql: &str, calls: i64, total_ms: i64) -> StatementTotals {
+        StatementTotals {
+            fingerprint: sql.to_string(),
+            service_id,
+            query_text: sql.to_string(),
+            call_count: calls,
+            error_count: 0,
+            total_duration_ms: total_ms,
+            max_duration_ms: total_ms / calls,
+        }
+    }
+
+    #[test]
+    fn test_statements_summed_per_table() {
+        let (app, mssql) = (Uuid::new_v4(), Uuid::new_v4());
+        let dialects =
+            DialectConfig::new(SqlDialect::Postgres, &format!("{}=mssql", mssql)).unwrap();
+        let stats = table_stats(
+            &[
+                statement(app, "SELECT * FROM users WHERE id = $1", 30, 300),
+                statement(app, "UPDATE users SET name = $1 WHERE id = $2", 10, 500),
+                statement(app, "INSERT INTO audit (id) SELECT id FROM users", 5, 50),
+                // Bracket quoting only parses with the service's dialect
+                statement(mssql, "SELECT * FROM [Orders]", 2, 20),
+            ],
+            &dialects,
+        );
+
+        let tables: Vec<&str> = stats.iter().map(|t| t.table.as_str()).collect();
+        assert_eq!(tables, ["users", "audit", "orders"]);
+        let users = &stats[0];
+        assert_eq!(users.fingerprint_count, 3);
+        assert_eq!(users.query_count, 45);
+        assert_eq!(users.read_count, 30);
+        assert_eq!(users.write_count, 15);
+        assert_eq!(users.total_duration_ms, 850);
+        assert_eq!(users.max_duration_ms, 50);
+        assert_eq!(stats[1].write_count, 5);
+    }
+}
@@ -0,0 +1,124 @@
+//! Workspace usage metering and quota checks
+//!
+//! Usage is counted per workspace and UTC day as metrics are stored. The
+//! ingest endpoint checks a workspace's month-to-date count against its
+//! monthly quota through [`QuotaCache`], so the database isn't queried on
+//! every request. The check is soft: between refreshes a workspace can go
+//! over its quota by what it sends in one cache TTL.
+
+use chrono::{Datelike, NaiveDate};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::models::QueryMetric;
+
+/// Fixed per-row cost on top of the variable-length columns: IDs,
+/// timestamps, numbers, and tuple and index overhead
+const ROW_OVERHEAD_BYTES: i64 = 160;
+
+/// Estimated storage for one metric row
+pub fn estimated_bytes(metric: &QueryMetric) -> i64 {
+    let text = metric.query_text.len()
+        + metric.error_message.as_ref().map_or(0, String::len)
+        + metric.tags.iter().map(String::len).sum::<usize>()
+        + metric
+            .labels
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum::<usize>()
+        + metric.fingerprint.as_ref().map_or(0, String::len);
+    ROW_OVERHEAD_BYTES + text as i64
+}
+
+/// First day of the month containing `day`
+pub fn month_start(day: NaiveDate) -> NaiveDate {
+    day.with_day(1).unwrap_or(day)
+}
+
+/// A workspace's monthly quota and month-to-date usage as last seen
+#[derive(Debug, Clone, Copy)]
+pub struct QuotaStatus {
+    /// Metrics allowed per calendar month (`None` = unlimited)
+    pub quota: Option<i64>,
+    /// Metrics stored this month, plus those accepted since the lookup
+    pub used: i64,
+}
+
+impl QuotaStatus {
+    pub fn exceeded(&self) -> bool {
+        self.quota.is_some_and(|quota| self.used >= quota)
+    }
+}
+
+/// Short-lived cache of workspace quota status
+pub struct QuotaCache {
+    entries: RwLock<HashMap<Uuid, (QuotaStatus, Instant)>>,
+    ttl: Duration,
+}
+
+impl QuotaCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            ttl,
+        }
+    }
+
+    pub fn get(&self, workspace_id: Uuid) -> Option<QuotaStatus> {
+        self.entries
+            .read()
+            .get(&workspace_id)
+            .filter(|(_, cached_at)| cached_at.elapsed() < self.ttl)
+            .map(|(status, _)| *status)
+    }
+
+    pub fn insert(&self, workspace_id: Uuid, status: QuotaStatus) {
+        self.entries
+            .write()
+            .insert(workspace_id, (status, Instant::now()));
+    }
+
+    /// Count metrics accepted since the last lookup
+    pub fn add_used(&self, workspace_id: Uuid, count: i64) {
+        if let Some((status, _)) = self.entries.write().get_mut(&workspace_id) {
+            status.used += count;
+        }
+    }
+
+    /// Forget a workspace, e.g. right after its quota changes
+    pub fn invalidate(&self, workspace_id: Uuid) {
+        self.entries.write().remove(&workspace_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_cache_counts_accepted_metrics() {
+        let cache = QuotaCache::new(Duration::from_secs(60));
+        let ws = Uuid::new_v4();
+        cache.insert(
+            ws,
+            QuotaStatus {
+                quota: Some(100),
+                used: 90,
+            },
+        );
+
+        assert!(!cache.get(ws).unwrap().exceeded());
+        cache.add_used(ws, 10);
+        assert!(cache.get(ws).unwrap().exceeded());
+
+        cache.invalidate(ws);
+        assert!(cache.get(ws).is_none());
+        let unlimited = QuotaStatus {
+            quota: None,
+            used: i64::MAX,
+        };
+        assert!(!unlimited.exceeded());
+    }
+}
@@ -1,50 +1,183 @@
 //! Application state shared across handlers
 
+use crate::auth::KeyCache;
 use crate::buffer::MetricsBuffer;
-use crate::db::Database;
-use crate::models::QueryMetric;
+use crate::casing::CasingCache;
+use crate::clock::Clock;
+use crate::db::QueryAnomaly;
+use crate::dedup::RecentIds;
+use crate::event_log::EventLog;
+use crate::ingest_stats::IngestStats;
+use crate::live_rollup::{LiveAggregate, LiveRollups, SecondRollups};
+use crate::models::{AnomalySettings, IdGenerator, QueryMetric};
 use crate::routes::metrics::Metrics;
-use crate::services::embedding::EmbeddingService;
+use crate::services::embedder::Embedder;
+use crate::services::embedding_cache::{self, EmbeddingCache};
+use crate::services::fingerprint::DialectConfig;
+use crate::services::jwt::JwtVerifier;
+use crate::services::notify::Notifier;
+use crate::services::quarantine::Quarantine;
+use crate::services::redaction::RedactionCache;
+use crate::services::usage::QuotaCache;
+use crate::store::MetricsStore;
+use parking_lot::Mutex;
 use std::sync::Arc;
-use tokio::sync::broadcast;
+use std::time::Duration;
+use tokio::sync::{broadcast, Notify};
 use uuid::Uuid;
 
+/// Unreported dropped messages after which a slow WebSocket client is
+/// disconnected, unless `WS_MAX_DROPPED` says otherwise
+pub const DEFAULT_WS_MAX_DROPPED: u64 = 10_000;
+
 /// Shared application state
 #[derive(Clone)]
 pub struct AppState {
-    /// Database connection pool
-    pub db: Arc<Database>,
+    /// Storage backend
+    pub db: Arc<dyn MetricsStore>,
     /// Lock-free metrics buffer for high-throughput ingestion
     pub metrics_buffer: MetricsBuffer,
     /// Broadcast channel for real-time metric streaming
     pub broadcast_tx: broadcast::Sender<(Uuid, QueryMetric)>,
-    /// Optional embedding service (loaded if EMBEDDING_MODEL_PATH is set)
-    pub embedding_service: Option<Arc<EmbeddingService>>,
+    /// Broadcast channel for anomalies as they are stored
+    pub anomaly_tx: broadcast::Sender<QueryAnomaly>,
+    /// Broadcast channel for per-second aggregates of each workspace's metrics
+    pub aggregate_tx: broadcast::Sender<LiveAggregate>,
+    /// Recently ingested metrics, for long-polling clients
+    pub events: EventLog,
+    /// Ingested metrics of the current second, closed by the broadcast task
+    pub second_rollups: Arc<Mutex<SecondRollups>>,
+    /// 1-minute aggregates of the last hour of metrics, ahead of the continuous aggregates
+    pub live_rollups: LiveRollups,
+    /// Optional embedding providers (configured with a local model and/or a remote API)
+    pub embedder: Option<Arc<Embedder>>,
+    /// Recently embedded similarity search text
+    pub search_embeddings: Arc<EmbeddingCache>,
+    /// Wakes the re-embedding task (`POST /admin/embeddings/reembed`)
+    pub reembed: Arc<Notify>,
     /// Application metrics for Prometheus
     pub metrics: Arc<Metrics>,
+    /// SQL dialect per service, used to fingerprint ingested queries
+    pub dialects: Arc<DialectConfig>,
+    /// Recently looked-up workspace JSON casing settings
+    pub casing_cache: Arc<CasingCache>,
+    /// Recently verified API keys
+    pub key_cache: Arc<KeyCache>,
+    /// Verifier for JWTs accepted on read routes (`None` = API keys only)
+    pub jwt: Option<Arc<JwtVerifier>>,
+    /// Recently looked-up workspace quotas and month-to-date usage
+    pub quota_cache: Arc<QuotaCache>,
+    /// Recently looked-up workspace literal redaction settings
+    pub redaction_cache: Arc<RedactionCache>,
+    /// Sends alerts to destinations and records each attempt
+    pub notifier: Arc<Notifier>,
+    /// Counts and reports metrics quarantined on schema drift
+    pub quarantine: Arc<Quarantine>,
+    /// Generates IDs for metrics the server creates (browser beacons)
+    pub ids: IdGenerator,
+    /// Recently ingested metric IDs, to skip resent metrics
+    pub recent_ids: RecentIds,
+    /// Per-minute ingest outcomes not yet written to the database
+    pub ingest_stats: IngestStats,
+    /// Anomaly detection settings of workspaces that don't set their own
+    pub anomaly_defaults: AnomalySettings,
+    /// Unreported dropped messages after which a slow WebSocket client is
+    /// disconnected (0 = never)
+    pub ws_max_dropped: u64,
+    /// Times handlers and background tasks
+    pub clock: Clock,
 }
 
 impl AppState {
     /// Create new application state
     ///
     /// # Arguments
-    /// * `db` - Database connection
+    /// * `db` - Storage backend
     /// * `buffer_capacity` - Capacity of the metrics buffer
-    /// * `broadcast_capacity` - Capacity of the broadcast channel
-    /// * `embedding_service` - Optional embedding service
+    /// * `broadcast_capacity` - Capacity of the broadcast channel and the long-poll event log
+    /// * `embedder` - Optional embedding providers
+    /// * `dialects` - SQL dialect per service
+    /// * `key_cache` - Cache of verified API keys
+    /// * `jwt` - Optional verifier for read-only JWTs
+    /// * `ids` - ID generator for server-created metrics
+    /// * `dedup_capacity` - Recent metric IDs remembered to skip resends (0 = off)
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        db: Database,
+        db: Arc<dyn MetricsStore>,
         buffer_capacity: usize,
         broadcast_capacity: usize,
-        embedding_service: Option<EmbeddingService>,
+        embedder: Option<Embedder>,
+        dialects: DialectConfig,
+        key_cache: KeyCache,
+        jwt: Option<JwtVerifier>,
+        ids: IdGenerator,
+        dedup_capacity: usize,
     ) -> Self {
         let (broadcast_tx, _) = broadcast::channel(broadcast_capacity);
+        let (anomaly_tx, _) = broadcast::channel(broadcast_capacity);
+        let (aggregate_tx, _) = broadcast::channel(broadcast_capacity);
+        let notifier = Arc::new(Notifier::new(db.clone()));
+        let quarantine = Arc::new(Quarantine::new(db.clone(), Arc::clone(&notifier)));
         Self {
-            db: Arc::new(db),
+            db,
             metrics_buffer: MetricsBuffer::new(buffer_capacity),
             broadcast_tx,
-            embedding_service: embedding_service.map(Arc::new),
+            anomaly_tx,
+            aggregate_tx,
+            events: EventLog::new(broadcast_capacity),
+            second_rollups: Arc::new(Mutex::new(SecondRollups::new())),
+            live_rollups: LiveRollups::new(),
+            embedder: embedder.map(Arc::new),
+            search_embeddings: Arc::new(EmbeddingCache::new(embedding_cache::DEFAULT_CAPACITY)),
+            reembed: Arc::new(Notify::new()),
             metrics: Arc::new(Metrics::new()),
+            dialects: Arc::new(dialects),
+            casing_cache: Arc::new(CasingCache::new(Duration::from_secs(30))),
+            key_cache: Arc::new(key_cache),
+            jwt: jwt.map(Arc::new),
+            quota_cache: Arc::new(QuotaCache::new(Duration::from_secs(30))),
+            redaction_cache: Arc::new(RedactionCache::new(Duration::from_secs(30))),
+            notifier,
+            quarantine,
+            ids,
+            recent_ids: RecentIds::new(dedup_capacity),
+            ingest_stats: IngestStats::new(),
+            anomaly_defaults: AnomalySettings::default(),
+            ws_max_dropped: DEFAULT_WS_MAX_DROPPED,
+            clock: Clock::System,
         }
     }
+
+    /// Time handlers and background tasks by `clock`
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Use `defaults` for workspaces that don't set their own anomaly
+    /// detection settings
+    pub fn with_anomaly_defaults(mut self, defaults: AnomalySettings) -> Self {
+        self.anomaly_defaults = defaults;
+        self
+    }
+
+    /// Disconnect WebSocket clients once `max_dropped` messages they were
+    /// too slow to receive go unreported (0 = never)
+    pub fn with_ws_max_dropped(mut self, max_dropped: u64) -> Self {
+        self.ws_max_dropped = max_dropped;
+        self
+    }
+
+    /// Cache up to `capacity` similarity search embeddings (0 = off)
+    pub fn with_search_embedding_cache(mut self, capacity: usize) -> Self {
+        self.search_embeddings = Arc::new(EmbeddingCache::new(capacity));
+        self
+    }
+
+    /// Link Slack and PagerDuty alerts to QueryVault at `public_url`
+    pub fn with_public_url(mut self, public_url: Option<String>) -> Self {
+        self.notifier = Arc::new(Notifier::new(self.db.clone()).with_public_url(public_url));
+        self.quarantine = Arc::new(Quarantine::new(self.db.clone(), Arc::clone(&self.notifier)));
+        self
+    }
 }
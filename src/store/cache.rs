@@ -0,0 +1,274 @@
+//! Read-through cache for aggregation queries
+//!
+//! Dashboards poll the aggregations endpoint every few seconds, and every
+//! viewer of a panel asks for the same buckets. Results are cached per
+//! `(workspace, window, timezone, filter, range)` for a short TTL. The range is first rounded up
+//! to bucket boundaries, which doesn't change which buckets it matches, so
+//! "the last hour" asked for a second apart hits the same entry.
+//!
+//! Buckets are keyed by insert time, so storing a batch drops its workspaces'
+//! entries whose range reaches the current bucket, as new rows may change it;
+//! ranges that ended earlier are kept. Erasing metrics drops all of a
+//! workspace's entries.
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use parking_lot::RwLock;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::db::{AggregatedMetric, MetricFilter};
+use crate::services::buckets::{bucket_start, ceil_bucket, window_width};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    workspace_id: Uuid,
+    window: String,
+    tz: Tz,
+    filter: MetricFilter,
+    /// Rounded up to a bucket boundary
+    from: DateTime<Utc>,
+    /// Rounded up to a bucket boundary
+    to: DateTime<Utc>,
+}
+
+struct CacheEntry {
+    buckets: Vec<AggregatedMetric>,
+    cached_at: Instant,
+}
+
+/// Short-lived cache of aggregation query results
+///
+/// Once `capacity` entries are cached, expired ones are dropped and, if it's
+/// still full, new results aren't cached until some expire. A zero `ttl` or
+/// `capacity` disables caching.
+pub struct AggregationCache {
+    entries: RwLock<HashMap<CacheKey, CacheEntry>>,
+    ttl: Duration,
+    capacity: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl AggregationCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            ttl,
+            capacity,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    fn key(
+        workspace_id: Uuid,
+        window: &str,
+        tz: Tz,
+        filter: &MetricFilter,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Option<CacheKey> {
+        let width = window_width(window)?;
+        Some(CacheKey {
+            workspace_id,
+            window: window.to_string(),
+            tz,
+            filter: filter.clone(),
+            from: ceil_bucket(from, width, tz),
+            to: ceil_bucket(to, width, tz),
+        })
+    }
+
+    pub fn get(
+        &self,
+        workspace_id: Uuid,
+        window: &str,
+        tz: Tz,
+        filter: &MetricFilter,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Option<Vec<AggregatedMetric>> {
+        let found = Self::key(workspace_id, window, tz, filter, from, to).and_then(|key| {
+            self.entries
+                .read()
+                .get(&key)
+                .filter(|entry| entry.cached_at.elapsed() < self.ttl)
+                .map(|entry| entry.buckets.clone())
+        });
+        let counter = if found.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn insert(
+        &self,
+        workspace_id: Uuid,
+        window: &str,
+        tz: Tz,
+        filter: &MetricFilter,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        buckets: Vec<AggregatedMetric>,
+    ) {
+        if self.ttl.is_zero() || self.capacity == 0 {
+            return;
+        }
+        let Some(key) = Self::key(workspace_id, window, tz, filter, from, to) else {
+            return;
+        };
+        let mut entries = self.entries.write();
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            entries.retain(|_, entry| entry.cached_at.elapsed() < self.ttl);
+            if entries.len() >= self.capacity {
+                return;
+            }
+        }
+        entries.insert(
+            key,
+            CacheEntry {
+                buckets,
+                cached_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Drop entries for these workspaces whose range reaches the bucket
+    /// containing `since`, after metrics were stored at or after it
+    pub fn invalidate_since(&self, workspace_ids: &HashSet<Uuid>, since: DateTime<Utc>) {
+        if workspace_ids.is_empty() {
+            return;
+        }
+        self.entries.write().retain(|key, _| {
+            if !workspace_ids.contains(&key.workspace_id) {
+                return true;
+            }
+            match window_width(&key.window) {
+                Some(width) => key.to <= bucket_start(since, width, key.tz),
+                None => false,
+            }
+        });
+    }
+
+    /// Drop every entry for a workspace
+    pub fn invalidate_workspace(&self, workspace_id: Uuid) {
+        self.entries
+            .write()
+            .retain(|key, _| key.workspace_id != workspace_id);
+    }
+
+    /// `(hits, misses)` since startup
+    pub fn stats(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aligned_ranges_share_entries_until_invalidated() {
+        let cache = AggregationCache::new(Duration::from_secs(60), 10);
+        let ws = Uuid::new_v4();
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let all = MetricFilter::default();
+
+        cache.insert(
+            ws,
+            "1m",
+            Tz::UTC,
+            &all,
+            at("2026-01-01T11:00:10Z"),
+            at("2026-01-01T12:00:10Z"),
+            Vec::new(),
+        );
+        cache.insert(
+            ws,
+            "1m",
+            Tz::UTC,
+            &all,
+            at("2026-01-01T10:00:00Z"),
+            at("2026-01-01T11:00:00Z"),
+            Vec::new(),
+        );
+        // Same buckets as the first range
+        assert!(cache
+            .get(
+                ws,
+                "1m",
+                Tz::UTC,
+                &all,
+                at("2026-01-01T11:00:40Z"),
+                at("2026-01-01T12:00:59Z")
+            )
+            .is_some());
+        assert!(cache
+            .get(
+                ws,
+                "5m",
+                Tz::UTC,
+                &all,
+                at("2026-01-01T11:00:40Z"),
+                at("2026-01-01T12:00:59Z")
+            )
+            .is_none());
+        assert!(cache
+            .get(
+                ws,
+                "1m",
+                "Asia/Kolkata".parse().unwrap(),
+                &all,
+                at("2026-01-01T11:00:40Z"),
+                at("2026-01-01T12:00:59Z")
+            )
+            .is_none());
+        assert_eq!(cache.stats(), (1, 2));
+
+        // Rows stored at 12:00:30 land in the 12:00 bucket, which only the
+        // first range covers
+        cache.invalidate_since(&HashSet::from([ws]), at("2026-01-01T12:00:30Z"));
+        assert!(cache
+            .get(
+                ws,
+                "1m",
+                Tz::UTC,
+                &all,
+                at("2026-01-01T11:00:10Z"),
+                at("2026-01-01T12:00:10Z")
+            )
+            .is_none());
+        assert!(cache
+            .get(
+                ws,
+                "1m",
+                Tz::UTC,
+                &all,
+                at("2026-01-01T10:00:00Z"),
+                at("2026-01-01T11:00:00Z")
+            )
+            .is_some());
+
+        cache.invalidate_workspace(ws);
+        assert!(cache
+            .get(
+                ws,
+                "1m",
+                Tz::UTC,
+                &all,
+                at("2026-01-01T10:00:00Z"),
+                at("2026-01-01T11:00:00Z")
+            )
+            .is_none());
+    }
+}
@@ -0,0 +1,4260 @@
+//! In-memory storage backend for local development, demos, and tests
+//!
+//! Everything lives in process memory and is lost on restart. Aggregations and
+//! statistics are computed in Rust on read, which is fine for the small volumes
+//! this backend is meant for.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use md5::{Digest, Md5};
+use parking_lot::RwLock;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use uuid::Uuid;
+
+use crate::clock::Clock;
+use crate::db::{
+    modified_z_score, AggregatedMetric, AlertRuleState, AnnTuning, AnomalyContext, AnomalyRecord,
+    AnomalyThreshold, BatchInsert, CatalogEntry, DeadLetter, DeadLetterReason, DurationGroupBy,
+    DurationStats, DurationStatsQuery, EmbeddedQuery, EmbeddingModel, EmbeddingSkip, ErasureCounts,
+    ErrorGroup, ErrorGroupsQuery, ErrorSummary, FingerprintStats, FingerprintStatsQuery,
+    FingerprintSummary, IngestionMinute, MetricErasure, MetricFilter, MetricSimilarity,
+    MetricsStats, NewQueryCluster, NewQueryEmbedding, NewQueryShape, QueryAnomaly, QueryCluster,
+    QueryClusters, QuerySort, QueryTextSearch, ServiceThroughput, SimilarQuery, SimilarQueryStats,
+    SloStatus, StatementTotals, ThroughputDrop, UsageDay, ERROR_GROUP_FINGERPRINTS,
+    NEW_SHAPE_LEARNING_PERIOD,
+};
+use crate::error::{AppError, Result};
+use crate::models::{
+    AlertDelivery, AlertDestination, AlertRule, Annotation, AnomalyMethod, AnomalyOverrides,
+    AnomalyStatus, ApiKey, ApiScope, AuditAction, AuditEntry, JsonCasing, LifecycleAction,
+    LifecycleStage, QueryKind, QueryMetric, QueryMute, QueryOwnership, QueryStatus, Seasonality,
+    Service, Slo, Workspace,
+};
+use crate::services::anomaly::seasonal_slot;
+use crate::services::buckets::{bucket_start, window_width};
+use crate::services::embedding::{cosine_similarity, normalize_query};
+use crate::services::error_fingerprint::{metric_error_fingerprint, normalize_error};
+use crate::services::fingerprint::metric_fingerprint;
+use crate::services::hybrid_search;
+use crate::services::keys::{hash_matches, hash_secret};
+use crate::services::severity::SeverityLevel;
+use crate::services::slo::SloCounts;
+use crate::services::usage;
+use crate::store::MetricsStore;
+
+/// Oldest metrics are evicted beyond this many rows
+const MAX_METRICS: usize = 100_000;
+
+/// Workspace seeded on startup, matching the seed data in `migrations/001_init.sql`
+pub const DEFAULT_WORKSPACE_ID: Uuid = Uuid::from_u128(0x550e8400_e29b_41d4_a716_446655440000);
+/// API key of the default workspace, with every scope
+pub const DEFAULT_API_KEY: &str = "test-api-key-12345";
+
+/// A metric plus the server-side time it was stored
+#[derive(Clone)]
+struct StoredMetric {
+    metric: QueryMetric,
+    created_at: DateTime<Utc>,
+}
+
+struct StoredEmbedding {
+    id: Uuid,
+    sql_query: String,
+    embedding: Vec<f32>,
+    model: EmbeddingModel,
+}
+
+struct StoredClusters {
+    model: EmbeddingModel,
+    computed_at: DateTime<Utc>,
+    /// By cluster ID
+    clusters: Vec<(i32, NewQueryCluster)>,
+}
+
+struct StoredAnomaly {
+    id: Uuid,
+    anomaly: QueryAnomaly,
+    detected_at: DateTime<Utc>,
+    status: AnomalyStatus,
+    acknowledged_by: Option<String>,
+    resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    workspaces: Vec<Workspace>,
+    /// `(hash of secret, key)`
+    api_keys: Vec<(String, ApiKey)>,
+    metrics: VecDeque<StoredMetric>,
+    embeddings: HashMap<(Uuid, String), StoredEmbedding>,
+    /// Bytes of queries skipped for being too long
+    embedding_skips: HashMap<(Uuid, String), usize>,
+    query_clusters: HashMap<Uuid, StoredClusters>,
+    anomalies: Vec<StoredAnomaly>,
+    catalog: HashMap<(Uuid, String), CatalogEntry>,
+    ownership: HashMap<(Uuid, String), QueryOwnership>,
+    mutes: HashMap<(Uuid, String), QueryMute>,
+    dead_letters: HashMap<Uuid, DeadLetter>,
+    annotations: HashMap<Uuid, (Uuid, Annotation)>,
+    audit_log: Vec<AuditEntry>,
+    alert_rules: HashMap<Uuid, AlertRule>,
+    alert_rule_states: HashMap<Uuid, AlertRuleState>,
+    alert_destinations: HashMap<Uuid, AlertDestination>,
+    alert_deliveries: Vec<AlertDelivery>,
+    json_casing: HashMap<Uuid, JsonCasing>,
+    monthly_quotas: HashMap<Uuid, i64>,
+    redact_literals: HashSet<Uuid>,
+    anomaly_methods: HashMap<Uuid, AnomalyMethod>,
+    anomaly_overrides: HashMap<Uuid, AnomalyOverrides>,
+    /// Seasonal baselines by workspace and slot
+    seasonal_baselines: HashMap<(Uuid, i16), (Seasonality, MetricsStats)>,
+    /// `(metric count, stored bytes)` per workspace and day
+    usage: HashMap<(Uuid, NaiveDate), (i64, i64)>,
+    ingestion_stats: HashMap<(Uuid, DateTime<Utc>), IngestionMinute>,
+    services: HashMap<Uuid, Service>,
+    slos: HashMap<Uuid, Slo>,
+    slo_statuses: HashMap<Uuid, SloStatus>,
+    lifecycle_policies: HashMap<Uuid, Vec<LifecycleStage>>,
+    /// Archived metrics by workspace and metric ID
+    archive: HashMap<(Uuid, Uuid), StoredMetric>,
+    throughput_drops: Vec<ThroughputDrop>,
+}
+
+impl Inner {
+    /// Metrics for one service created within `[from, to)`
+    fn service_metrics(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> impl Iterator<Item = &StoredMetric> {
+        self.metrics.iter().filter(move |m| {
+            m.metric.workspace_id == workspace_id
+                && m.metric.service_id == service_id
+                && m.created_at >= from
+                && m.created_at < to
+        })
+    }
+
+    /// Register a metric's service on first sight, or bump its last-seen time
+    fn register_service(&mut self, metric: &QueryMetric, now: DateTime<Utc>) {
+        if let Some(service) = self.services.get_mut(&metric.service_id) {
+            if service.workspace_id == metric.workspace_id {
+                service.last_seen_at = service.last_seen_at.max(Some(metric.started_at));
+            }
+            return;
+        }
+        let name = metric
+            .service_name
+            .clone()
+            .unwrap_or_else(|| metric.service_id.to_string());
+        if !self.workspaces.iter().any(|w| w.id == metric.workspace_id)
+            || self.service_name_taken(metric.workspace_id, &name, None)
+        {
+            return;
+        }
+        self.services.insert(
+            metric.service_id,
+            Service {
+                id: metric.service_id,
+                workspace_id: metric.workspace_id,
+                name,
+                description: None,
+                created_at: now,
+                updated_at: now,
+                last_seen_at: Some(metric.started_at),
+            },
+        );
+    }
+
+    /// Whether another service in the workspace already uses `name`
+    fn service_name_taken(&self, workspace_id: Uuid, name: &str, except: Option<Uuid>) -> bool {
+        self.services
+            .values()
+            .any(|s| s.workspace_id == workspace_id && s.name == name && Some(s.id) != except)
+    }
+
+    /// Stored anomaly with its current ownership annotation
+    fn anomaly_record(&self, a: &StoredAnomaly) -> AnomalyRecord {
+        AnomalyRecord {
+            id: a.id,
+            workspace_id: a.anomaly.workspace_id,
+            service_id: a.anomaly.service_id,
+            metric_id: a.anomaly.metric_id,
+            fingerprint: a.anomaly.fingerprint.clone(),
+            query_text: a.anomaly.query_text.clone(),
+            duration_ms: a.anomaly.duration_ms,
+            mean_duration_ms: a.anomaly.mean_duration_ms,
+            stddev_duration_ms: a.anomaly.stddev_duration_ms,
+            z_score: a.anomaly.z_score,
+            method: a.anomaly.method,
+            severity: a.anomaly.severity,
+            severity_level: a.anomaly.severity_level,
+            estimated_extra_ms: Some(a.anomaly.estimated_extra_ms),
+            detected_at: a.detected_at,
+            status: a.status,
+            acknowledged_by: a.acknowledged_by.clone(),
+            resolved_at: a.resolved_at,
+            ownership: self
+                .ownership
+                .get(&(a.anomaly.workspace_id, a.anomaly.fingerprint.clone()))
+                .cloned(),
+        }
+    }
+
+    fn anomaly_method(&self, workspace_id: Uuid) -> AnomalyMethod {
+        self.anomaly_methods
+            .get(&workspace_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn is_muted(&self, workspace_id: Uuid, fingerprint: &str, now: DateTime<Utc>) -> bool {
+        self.mutes
+            .get(&(workspace_id, fingerprint.to_string()))
+            .is_some_and(|m| !matches!(m.expires_at, Some(e) if e <= now))
+    }
+}
+
+/// In-memory [`MetricsStore`] implementation
+pub struct MemoryStore {
+    inner: RwLock<Inner>,
+    clock: Clock,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryStore {
+    /// Create a store seeded with the default test workspace
+    pub fn new() -> Self {
+        Self::with_clock(Clock::System)
+    }
+
+    /// Create a store seeded with the default test workspace that reads the
+    /// time from `clock`
+    pub fn with_clock(clock: Clock) -> Self {
+        let store = Self {
+            inner: RwLock::new(Inner::default()),
+            clock,
+        };
+        store.add_workspace_with_id(DEFAULT_WORKSPACE_ID, "Default Workspace", DEFAULT_API_KEY);
+        store
+    }
+
+    /// Register a workspace with the given API key
+    #[allow(dead_code)]
+    pub fn add_workspace(&self, name: &str, api_key: &str) -> Workspace {
+        self.add_workspace_with_id(Uuid::new_v4(), name, api_key)
+    }
+
+    fn add_workspace_with_id(&self, id: Uuid, name: &str, api_key: &str) -> Workspace {
+        let now = self.clock.now();
+        let workspace = Workspace {
+            id,
+            name: name.to_string(),
+            created_at: now,
+            updated_at: now,
+        };
+        let primary = ApiKey {
+            id: Uuid::new_v4(),
+            workspace_id: id,
+            name: "primary".to_string(),
+            scopes: vec![ApiScope::Ingest, ApiScope::Read, ApiScope::Admin],
+            expires_at: None,
+            created_at: now,
+        };
+        let mut inner = self.inner.write();
+        inner.workspaces.push(workspace.clone());
+        inner.api_keys.push((hash_secret(api_key), primary));
+        workspace
+    }
+}
+
+/// Same embedding key as the Postgres backend's `query_hash`
+fn query_hash(query: &str) -> String {
+    format!("{:x}", Md5::digest(normalize_query(query).as_bytes()))
+}
+
+/// Whether a row recorded at `at` for `service_id` with `query_text` matches
+/// every set field of an erasure filter
+fn erasure_matches(
+    filter: &MetricErasure,
+    at: DateTime<Utc>,
+    service_id: Uuid,
+    query_text: &str,
+) -> bool {
+    !matches!(filter.from, Some(from) if at < from)
+        && !matches!(filter.to, Some(to) if at >= to)
+        && !matches!(filter.service_id, Some(id) if id != service_id)
+        && !matches!(&filter.query_contains, Some(text) if !contains_ignore_case(query_text, text))
+}
+
+/// Case-insensitive substring match, like `strpos(lower(..), lower(..)) > 0`
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+/// Whether a metric passes an aggregation/recent-metrics filter
+fn matches_filter(metric: &QueryMetric, filter: &MetricFilter) -> bool {
+    filter.service_id.is_none_or(|id| id == metric.service_id)
+        && filter.tags.iter().all(|tag| metric.tags.contains(tag))
+        && filter
+            .labels
+            .iter()
+            .all(|(key, value)| metric.labels.get(key) == Some(value))
+        && filter
+            .query_kind
+            .is_none_or(|kind| metric.query_kind == Some(kind))
+}
+
+/// Continuous percentile over sorted values (matches `PERCENTILE_CONT`)
+fn percentile_cont(sorted: &[i64], p: f64) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = p * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let value =
+        sorted[lower] as f64 + (sorted[upper] - sorted[lower]) as f64 * (rank - lower as f64);
+    Some(value.round() as i64)
+}
+
+/// Duration statistics over non-empty durations, like the database computes
+/// them, with the default anomaly method
+fn duration_stats(durations: Vec<f64>) -> MetricsStats {
+    let count = durations.len();
+    let mean = durations.iter().sum::<f64>() / count as f64;
+    // Sample standard deviation, like Postgres STDDEV
+    let stddev = if count > 1 {
+        (durations.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / (count - 1) as f64).sqrt()
+    } else {
+        0.0
+    };
+    let mut sorted = durations;
+    sorted.sort_by(f64::total_cmp);
+    let median = percentile_cont_f64(&sorted, 0.5);
+    let mut deviations: Vec<f64> = sorted.iter().map(|d| (d - median).abs()).collect();
+    deviations.sort_by(f64::total_cmp);
+    MetricsStats {
+        mean,
+        stddev,
+        median,
+        mad: percentile_cont_f64(&deviations, 0.5),
+        count: count as i64,
+        method: AnomalyMethod::default(),
+    }
+}
+
+/// Continuous percentile over sorted, non-empty values, unrounded
+fn percentile_cont_f64(sorted: &[f64], p: f64) -> f64 {
+    let rank = p * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+#[async_trait]
+impl MetricsStore for MemoryStore {
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn verify_api_key(&self, api_key: &str) -> Result<ApiKey> {
+        let key = self
+            .inner
+            .read()
+            .api_keys
+            .iter()
+            .find(|(hash, _)| hash_matches(api_key, hash))
+            .map(|(_, key)| key.clone())
+            .ok_or_else(|| AppError::Unauthorized("Invalid API key".into()))?;
+
+        if key.is_expired(self.clock.now()) {
+            return Err(AppError::Unauthorized("API key has expired".into()));
+        }
+        Ok(key)
+    }
+
+    async fn get_json_casing(&self, workspace_id: Uuid) -> Result<Option<JsonCasing>> {
+        let inner = self.inner.read();
+        if !inner.workspaces.iter().any(|w| w.id == workspace_id) {
+            return Ok(None);
+        }
+        Ok(Some(
+            inner
+                .json_casing
+                .get(&workspace_id)
+                .copied()
+                .unwrap_or_default(),
+        ))
+    }
+
+    async fn set_json_casing(&self, workspace_id: Uuid, casing: JsonCasing) -> Result<bool> {
+        let mut inner = self.inner.write();
+        if !inner.workspaces.iter().any(|w| w.id == workspace_id) {
+            return Ok(false);
+        }
+        inner.json_casing.insert(workspace_id, casing);
+        Ok(true)
+    }
+
+    async fn get_monthly_quota(&self, workspace_id: Uuid) -> Result<Option<i64>> {
+        Ok(self.inner.read().monthly_quotas.get(&workspace_id).copied())
+    }
+
+    async fn set_monthly_quota(&self, workspace_id: Uuid, quota: Option<i64>) -> Result<bool> {
+        let mut inner = self.inner.write();
+        if !inner.workspaces.iter().any(|w| w.id == workspace_id) {
+            return Ok(false);
+        }
+        match quota {
+            Some(quota) => inner.monthly_quotas.insert(workspace_id, quota),
+            None => inner.monthly_quotas.remove(&workspace_id),
+        };
+        Ok(true)
+    }
+
+    async fn get_literal_redaction(&self, workspace_id: Uuid) -> Result<Option<bool>> {
+        let inner = self.inner.read();
+        if !inner.workspaces.iter().any(|w| w.id == workspace_id) {
+            return Ok(None);
+        }
+        Ok(Some(inner.redact_literals.contains(&workspace_id)))
+    }
+
+    async fn set_literal_redaction(&self, workspace_id: Uuid, enabled: bool) -> Result<bool> {
+        let mut inner = self.inner.write();
+        if !inner.workspaces.iter().any(|w| w.id == workspace_id) {
+            return Ok(false);
+        }
+        if enabled {
+            inner.redact_literals.insert(workspace_id);
+        } else {
+            inner.redact_literals.remove(&workspace_id);
+        }
+        Ok(true)
+    }
+
+    async fn get_anomaly_method(&self, workspace_id: Uuid) -> Result<Option<AnomalyMethod>> {
+        let inner = self.inner.read();
+        if !inner.workspaces.iter().any(|w| w.id == workspace_id) {
+            return Ok(None);
+        }
+        Ok(Some(
+            inner
+                .anomaly_methods
+                .get(&workspace_id)
+                .copied()
+                .unwrap_or_default(),
+        ))
+    }
+
+    async fn set_anomaly_method(&self, workspace_id: Uuid, method: AnomalyMethod) -> Result<bool> {
+        let mut inner = self.inner.write();
+        if !inner.workspaces.iter().any(|w| w.id == workspace_id) {
+            return Ok(false);
+        }
+        inner.anomaly_methods.insert(workspace_id, method);
+        Ok(true)
+    }
+
+    async fn get_anomaly_overrides(
+        &self,
+        workspace_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, AnomalyOverrides>> {
+        let inner = self.inner.read();
+        Ok(inner
+            .workspaces
+            .iter()
+            .filter(|w| workspace_ids.contains(&w.id))
+            .map(|w| {
+                let overrides = inner
+                    .anomaly_overrides
+                    .get(&w.id)
+                    .copied()
+                    .unwrap_or_default();
+                (w.id, overrides)
+            })
+            .collect())
+    }
+
+    async fn set_anomaly_overrides(
+        &self,
+        workspace_id: Uuid,
+        overrides: &AnomalyOverrides,
+    ) -> Result<bool> {
+        let mut inner = self.inner.write();
+        if !inner.workspaces.iter().any(|w| w.id == workspace_id) {
+            return Ok(false);
+        }
+        inner.anomaly_overrides.insert(workspace_id, *overrides);
+        Ok(true)
+    }
+
+    async fn create_api_key(&self, key: &ApiKey, secret: &str) -> Result<()> {
+        let mut inner = self.inner.write();
+        let hash = hash_secret(secret);
+        if inner.api_keys.iter().any(|(h, _)| *h == hash) {
+            return Err(AppError::DatabaseError("Duplicate API key".into()));
+        }
+        inner.api_keys.push((hash, key.clone()));
+        Ok(())
+    }
+
+    async fn list_api_keys(&self, workspace_id: Uuid) -> Result<Vec<ApiKey>> {
+        let mut keys: Vec<ApiKey> = self
+            .inner
+            .read()
+            .api_keys
+            .iter()
+            .filter(|(_, key)| key.workspace_id == workspace_id)
+            .map(|(_, key)| key.clone())
+            .collect();
+        keys.sort_by_key(|key| Reverse(key.created_at));
+        Ok(keys)
+    }
+
+    async fn delete_api_key(&self, workspace_id: Uuid, key_id: Uuid) -> Result<bool> {
+        let mut inner = self.inner.write();
+        let before = inner.api_keys.len();
+        inner
+            .api_keys
+            .retain(|(_, key)| !(key.workspace_id == workspace_id && key.id == key_id));
+        Ok(inner.api_keys.len() < before)
+    }
+
+    async fn insert_metrics_batch(&self, metrics: &[QueryMetric]) -> Result<BatchInsert> {
+        let now = self.clock.now();
+        let mut inner = self.inner.write();
+
+        // Workspaces past their learning period, before this batch
+        let learned_before = now - NEW_SHAPE_LEARNING_PERIOD;
+        let established: HashSet<Uuid> = inner
+            .catalog
+            .iter()
+            .filter(|(_, entry)| entry.first_seen < learned_before)
+            .map(|((workspace_id, _), _)| *workspace_id)
+            .collect();
+        let mut new_shapes = Vec::new();
+
+        for metric in metrics {
+            let key = (metric.workspace_id, metric_fingerprint(metric));
+            let entry = inner.catalog.entry(key.clone()).or_insert_with(|| {
+                if established.contains(&metric.workspace_id) {
+                    new_shapes.push(NewQueryShape {
+                        workspace_id: metric.workspace_id,
+                        fingerprint: key.1.clone(),
+                        query_text: metric.query_text.clone(),
+                        first_seen: metric.started_at,
+                    });
+                }
+                CatalogEntry {
+                    fingerprint: key.1.clone(),
+                    query_text: metric.query_text.clone(),
+                    first_seen: metric.started_at,
+                    last_seen: metric.started_at,
+                    total_count: 0,
+                    ownership: None,
+                }
+            });
+            entry.first_seen = entry.first_seen.min(metric.started_at);
+            entry.last_seen = entry.last_seen.max(metric.started_at);
+            entry.total_count += 1;
+
+            inner.register_service(metric, now);
+
+            let usage = inner
+                .usage
+                .entry((metric.workspace_id, now.date_naive()))
+                .or_default();
+            usage.0 += 1;
+            usage.1 += usage::estimated_bytes(metric);
+
+            inner.metrics.push_back(StoredMetric {
+                metric: metric.clone(),
+                created_at: now,
+            });
+        }
+
+        while inner.metrics.len() > MAX_METRICS {
+            inner.metrics.pop_front();
+        }
+
+        Ok(BatchInsert {
+            inserted: metrics.len(),
+            new_shapes,
+            ..BatchInsert::default()
+        })
+    }
+
+    async fn get_recent_metrics(
+        &self,
+        workspace_id: Uuid,
+        filter: &MetricFilter,
+        limit: i64,
+    ) -> Result<Vec<QueryMetric>> {
+        let inner = self.inner.read();
+        Ok(inner
+            .metrics
+            .iter()
+            .rev()
+            .filter(|m| m.metric.workspace_id == workspace_id && matches_filter(&m.metric, filter))
+            .take(limit.max(0) as usize)
+            .map(|m| m.metric.clone())
+            .collect())
+    }
+
+    async fn get_aggregations(
+        &self,
+        workspace_id: Uuid,
+        window: &str,
+        tz: Tz,
+        filter: &MetricFilter,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<AggregatedMetric>> {
+        let width = window_width(window)
+            .ok_or_else(|| AppError::InvalidRequest(format!("Invalid window: {}", window)))?;
+
+        let inner = self.inner.read();
+        let mut groups: HashMap<(Uuid, DateTime<Utc>), Vec<&StoredMetric>> = HashMap::new();
+        for stored in inner.metrics.iter() {
+            if stored.metric.workspace_id != workspace_id || !matches_filter(&stored.metric, filter)
+            {
+                continue;
+            }
+            let bucket = bucket_start(stored.created_at, width, tz);
+            groups
+                .entry((stored.metric.service_id, bucket))
+                .or_default()
+                .push(stored);
+        }
+
+        let mut aggregations: Vec<AggregatedMetric> = groups
+            .into_iter()
+            .filter_map(|((service_id, bucket), rows)| {
+                if bucket < from || bucket >= to {
+                    return None;
+                }
+                let mut durations: Vec<i64> =
+                    rows.iter().map(|r| r.metric.duration_ms as i64).collect();
+                durations.sort_unstable();
+                let count = durations.len() as i64;
+                let count_status = |status: QueryStatus| {
+                    rows.iter().filter(|r| r.metric.status == status).count() as i64
+                };
+                Some(AggregatedMetric {
+                    workspace_id,
+                    service_id,
+                    bucket,
+                    query_count: count,
+                    avg_duration_ms: Some(durations.iter().sum::<i64>() / count),
+                    min_duration_ms: durations.first().copied(),
+                    max_duration_ms: durations.last().copied(),
+                    p95_duration_ms: percentile_cont(&durations, 0.95),
+                    p99_duration_ms: percentile_cont(&durations, 0.99),
+                    success_count: Some(count_status(QueryStatus::Success)),
+                    failed_count: Some(count_status(QueryStatus::Failed)),
+                    total_rows_affected: Some(
+                        rows.iter().filter_map(|r| r.metric.rows_affected).sum(),
+                    ),
+                })
+            })
+            .collect();
+
+        aggregations.sort_by_key(|a| (a.bucket, a.service_id));
+        Ok(aggregations)
+    }
+
+    async fn prune_old_metrics(&self, older_than_days: i32) -> Result<u64> {
+        let cutoff = self.clock.now() - Duration::days(older_than_days as i64);
+        let mut inner = self.inner.write();
+        let before = inner.metrics.len();
+        inner.metrics.retain(|m| m.created_at >= cutoff);
+        Ok((before - inner.metrics.len()) as u64)
+    }
+
+    async fn erase_metrics(
+        &self,
+        workspace_id: Uuid,
+        filter: &MetricErasure,
+    ) -> Result<ErasureCounts> {
+        let mut inner = self.inner.write();
+        let mut erased_ids = HashSet::new();
+        let mut erased_hashes = HashSet::new();
+        let mut erased_fingerprints = HashSet::new();
+        inner.metrics.retain(|m| {
+            let erase = m.metric.workspace_id == workspace_id
+                && erasure_matches(
+                    filter,
+                    m.created_at,
+                    m.metric.service_id,
+                    &m.metric.query_text,
+                );
+            if erase {
+                erased_ids.insert(m.metric.id);
+                erased_hashes.insert(query_hash(&m.metric.query_text));
+                erased_fingerprints.insert(metric_fingerprint(&m.metric));
+            }
+            !erase
+        });
+
+        let catalog_before = inner.catalog.len();
+        inner.catalog.retain(|(ws, fingerprint), entry| {
+            *ws != workspace_id
+                || !(erased_fingerprints.contains(fingerprint)
+                    || filter
+                        .query_contains
+                        .as_deref()
+                        .is_some_and(|text| contains_ignore_case(&entry.query_text, text)))
+        });
+        let dead_letters_before = inner.dead_letters.len();
+        inner.dead_letters.retain(|_, letter| {
+            letter.workspace_id != workspace_id
+                || !erasure_matches(
+                    filter,
+                    letter.first_failed_at,
+                    letter.metric.service_id,
+                    &letter.metric.query_text,
+                )
+        });
+
+        let mut erased_embeddings = HashSet::new();
+        inner.embeddings.retain(|(ws, hash), e| {
+            let erase = *ws == workspace_id
+                && (erased_hashes.contains(hash)
+                    || filter
+                        .query_contains
+                        .as_deref()
+                        .is_some_and(|text| contains_ignore_case(&e.sql_query, text)));
+            if erase {
+                erased_embeddings.insert(hash.clone());
+            }
+            !erase
+        });
+        if let Some(stored) = inner.query_clusters.get_mut(&workspace_id) {
+            stored
+                .clusters
+                .retain(|(_, c)| !erased_embeddings.contains(&c.representative_hash));
+            for (_, cluster) in &mut stored.clusters {
+                cluster
+                    .members
+                    .retain(|hash| !erased_embeddings.contains(hash));
+            }
+        }
+
+        let anomalies_before = inner.anomalies.len();
+        inner.anomalies.retain(|a| {
+            a.anomaly.workspace_id != workspace_id
+                || !(erased_ids.contains(&a.anomaly.metric_id)
+                    || erasure_matches(
+                        filter,
+                        a.detected_at,
+                        a.anomaly.service_id,
+                        &a.anomaly.query_text,
+                    ))
+        });
+
+        Ok(ErasureCounts {
+            metrics: erased_ids.len() as u64,
+            embeddings: erased_embeddings.len() as u64,
+            anomalies: (anomalies_before - inner.anomalies.len()) as u64,
+            catalog_entries: (catalog_before - inner.catalog.len()) as u64,
+            dead_letters: (dead_letters_before - inner.dead_letters.len()) as u64,
+        })
+    }
+
+    async fn refresh_fingerprint_rollups(&self, _since: DateTime<Utc>) -> Result<u64> {
+        // Nothing is materialized in memory; per-fingerprint stats are computed on read
+        Ok(0)
+    }
+
+    async fn get_new_queries(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<CatalogEntry>> {
+        let inner = self.inner.read();
+        let mut entries: Vec<CatalogEntry> = inner
+            .catalog
+            .iter()
+            .filter(|((ws, _), entry)| *ws == workspace_id && entry.first_seen >= since)
+            .map(|((ws, fp), entry)| CatalogEntry {
+                ownership: inner.ownership.get(&(*ws, fp.clone())).cloned(),
+                ..entry.clone()
+            })
+            .collect();
+
+        entries.sort_by_key(|e| Reverse(e.first_seen));
+        entries.truncate(limit.max(0) as usize);
+        Ok(entries)
+    }
+
+    async fn search_query_text(
+        &self,
+        workspace_id: Uuid,
+        search: &QueryTextSearch,
+    ) -> Result<Vec<CatalogEntry>> {
+        let needle = search.text.to_lowercase();
+        let words: Vec<&str> = hybrid_search::term_parts(&needle).collect();
+        let inner = self.inner.read();
+        let ran_in_range = |fingerprint: &str, service_id: Uuid| {
+            inner.metrics.iter().any(|m| {
+                m.metric.workspace_id == workspace_id
+                    && m.metric.service_id == service_id
+                    && search.from.is_none_or(|from| m.created_at >= from)
+                    && search.to.is_none_or(|to| m.created_at < to)
+                    && metric_fingerprint(&m.metric) == fingerprint
+            })
+        };
+
+        // Substring matches first, then word matches, like the Postgres ranking
+        let mut entries: Vec<(bool, CatalogEntry)> = inner
+            .catalog
+            .iter()
+            .filter(|((ws, _), entry)| {
+                *ws == workspace_id
+                    && search.from.is_none_or(|from| entry.last_seen >= from)
+                    && search.to.is_none_or(|to| entry.first_seen < to)
+            })
+            .filter_map(|((ws, fp), entry)| {
+                let text = entry.query_text.to_lowercase();
+                let substring = text.contains(&needle);
+                let lexemes: Vec<&str> = hybrid_search::term_parts(&text).collect();
+                let words_match = !words.is_empty()
+                    && words
+                        .iter()
+                        .all(|word| lexemes.iter().any(|lexeme| lexeme.starts_with(word)));
+                let matched = *fp == search.text || substring || words_match;
+                let entry = CatalogEntry {
+                    ownership: inner.ownership.get(&(*ws, fp.clone())).cloned(),
+                    ..entry.clone()
+                };
+                matched.then_some((substring, entry))
+            })
+            .filter(|(_, entry)| {
+                search
+                    .service_id
+                    .is_none_or(|id| ran_in_range(&entry.fingerprint, id))
+            })
+            .collect();
+
+        entries.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| b.1.last_seen.cmp(&a.1.last_seen))
+                .then_with(|| a.1.fingerprint.cmp(&b.1.fingerprint))
+        });
+        Ok(entries
+            .into_iter()
+            .skip(search.offset.max(0) as usize)
+            .take(search.limit.max(0) as usize)
+            .map(|(_, entry)| entry)
+            .collect())
+    }
+
+    async fn get_fingerprint_stats(
+        &self,
+        workspace_id: Uuid,
+        query: &FingerprintStatsQuery,
+    ) -> Result<Vec<FingerprintStats>> {
+        let now = self.clock.now();
+        let inner = self.inner.read();
+        let mut groups: HashMap<String, Vec<&StoredMetric>> = HashMap::new();
+        for stored in inner.metrics.iter().filter(|m| {
+            m.metric.workspace_id == workspace_id
+                && m.created_at >= query.from
+                && m.created_at < query.to
+                && !matches!(query.service_id, Some(id) if id != m.metric.service_id)
+                && query
+                    .query_kind
+                    .is_none_or(|kind| m.metric.query_kind == Some(kind))
+        }) {
+            groups
+                .entry(metric_fingerprint(&stored.metric))
+                .or_default()
+                .push(stored);
+        }
+        groups.retain(|fingerprint, _| !inner.is_muted(workspace_id, fingerprint, now));
+
+        let mut stats: Vec<FingerprintStats> = groups
+            .into_iter()
+            .map(|(fingerprint, stored)| {
+                let mut durations: Vec<i64> =
+                    stored.iter().map(|s| s.metric.duration_ms as i64).collect();
+                durations.sort_unstable();
+                let call_count = durations.len() as i64;
+                let error_count = stored
+                    .iter()
+                    .filter(|s| {
+                        matches!(s.metric.status, QueryStatus::Failed | QueryStatus::Timeout)
+                    })
+                    .count() as i64;
+                FingerprintStats {
+                    ownership: inner
+                        .ownership
+                        .get(&(workspace_id, fingerprint.clone()))
+                        .cloned(),
+                    fingerprint,
+                    query_text: stored
+                        .iter()
+                        .map(|s| &s.metric.query_text)
+                        .min()
+                        .cloned()
+                        .unwrap_or_default(),
+                    call_count,
+                    error_count,
+                    error_rate: error_count as f64 / call_count as f64,
+                    total_duration_ms: durations.iter().sum(),
+                    p50_duration_ms: percentile_cont(&durations, 0.5).unwrap_or(0),
+                    p95_duration_ms: percentile_cont(&durations, 0.95).unwrap_or(0),
+                    p99_duration_ms: percentile_cont(&durations, 0.99).unwrap_or(0),
+                    first_seen: stored
+                        .iter()
+                        .map(|s| s.created_at)
+                        .min()
+                        .unwrap_or_default(),
+                    last_seen: stored
+                        .iter()
+                        .map(|s| s.created_at)
+                        .max()
+                        .unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        let key = |s: &FingerprintStats| match query.sort {
+            QuerySort::TotalTime => s.total_duration_ms as f64,
+            QuerySort::Calls => s.call_count as f64,
+            QuerySort::P50 => s.p50_duration_ms as f64,
+            QuerySort::P95 => s.p95_duration_ms as f64,
+            QuerySort::P99 => s.p99_duration_ms as f64,
+            QuerySort::ErrorRate => s.error_rate,
+            QuerySort::LastSeen => s.last_seen.timestamp_micros() as f64,
+        };
+        stats.sort_by(|a, b| {
+            key(b)
+                .total_cmp(&key(a))
+                .then_with(|| a.fingerprint.cmp(&b.fingerprint))
+        });
+        Ok(stats
+            .into_iter()
+            .skip(query.offset.max(0) as usize)
+            .take(query.limit.max(0) as usize)
+            .collect())
+    }
+
+    async fn get_statement_totals(
+        &self,
+        workspace_id: Uuid,
+        service_id: Option<Uuid>,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<StatementTotals>> {
+        let inner = self.inner.read();
+        let mut groups: HashMap<(String, Uuid), StatementTotals> = HashMap::new();
+        for stored in inner.metrics.iter().filter(|m| {
+            m.metric.workspace_id == workspace_id
+                && m.created_at >= from
+                && m.created_at < to
+                && !matches!(service_id, Some(id) if id != m.metric.service_id)
+                && query_kind.is_none_or(|kind| m.metric.query_kind == Some(kind))
+        }) {
+            let metric = &stored.metric;
+            let fingerprint = metric_fingerprint(metric);
+            let totals = groups
+                .entry((fingerprint.clone(), metric.service_id))
+                .or_insert_with(|| StatementTotals {
+                    fingerprint,
+                    service_id: metric.service_id,
+                    query_text: metric.query_text.clone(),
+                    call_count: 0,
+                    error_count: 0,
+                    total_duration_ms: 0,
+                    max_duration_ms: 0,
+                });
+            if metric.query_text < totals.query_text {
+                totals.query_text = metric.query_text.clone();
+            }
+            totals.call_count += 1;
+            if matches!(metric.status, QueryStatus::Failed | QueryStatus::Timeout) {
+                totals.error_count += 1;
+            }
+            totals.total_duration_ms += metric.duration_ms as i64;
+            totals.max_duration_ms = totals.max_duration_ms.max(metric.duration_ms as i64);
+        }
+
+        let mut totals: Vec<StatementTotals> = groups.into_values().collect();
+        totals.sort_by(|a, b| {
+            b.call_count
+                .cmp(&a.call_count)
+                .then_with(|| a.fingerprint.cmp(&b.fingerprint))
+                .then_with(|| a.service_id.cmp(&b.service_id))
+        });
+        totals.truncate(limit.max(0) as usize);
+        Ok(totals)
+    }
+
+    async fn get_duration_stats(
+        &self,
+        workspace_id: Uuid,
+        query: &DurationStatsQuery,
+    ) -> Result<Vec<DurationStats>> {
+        let inner = self.inner.read();
+        // Group -> duration and ID of each execution
+        let mut groups: HashMap<String, Vec<(i64, Uuid)>> = HashMap::new();
+        for stored in inner.metrics.iter().filter(|m| {
+            m.metric.workspace_id == workspace_id
+                && m.created_at >= query.from
+                && m.created_at < query.to
+                && !matches!(query.service_id, Some(id) if id != m.metric.service_id)
+                && query
+                    .query_kind
+                    .is_none_or(|kind| m.metric.query_kind == Some(kind))
+        }) {
+            let metric = &stored.metric;
+            let group = match query.group_by {
+                DurationGroupBy::Service => metric.service_id.to_string(),
+                DurationGroupBy::Fingerprint => metric_fingerprint(metric),
+            };
+            groups
+                .entry(group)
+                .or_default()
+                .push((metric.duration_ms as i64, metric.id));
+        }
+
+        let mut stats: Vec<DurationStats> = groups
+            .into_iter()
+            .map(|(group, mut executions)| {
+                // Slowest first, ties broken by ID as in the database
+                executions.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+                let (max_ms, max_metric_id) = executions[0];
+                let durations: Vec<f64> = executions.iter().rev().map(|(d, _)| *d as f64).collect();
+                let median_ms = percentile_cont_f64(&durations, 0.5);
+                let mut deviations: Vec<f64> =
+                    durations.iter().map(|d| (d - median_ms).abs()).collect();
+                deviations.sort_by(f64::total_cmp);
+                let mad_ms = percentile_cont_f64(&deviations, 0.5);
+                DurationStats {
+                    group,
+                    query_count: durations.len() as i64,
+                    median_ms,
+                    mad_ms,
+                    p999_ms: percentile_cont_f64(&durations, 0.999),
+                    max_ms,
+                    max_metric_id,
+                    max_score: modified_z_score(max_ms as f64, median_ms, mad_ms),
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| b.max_ms.cmp(&a.max_ms).then_with(|| a.group.cmp(&b.group)));
+        stats.truncate(query.limit.max(0) as usize);
+        Ok(stats)
+    }
+
+    async fn get_error_groups(
+        &self,
+        workspace_id: Uuid,
+        query: &ErrorGroupsQuery,
+    ) -> Result<Vec<ErrorGroup>> {
+        let previous_from = query.from - (query.to - query.from);
+        let inner = self.inner.read();
+        let failed: Vec<(String, &StoredMetric)> = inner
+            .metrics
+            .iter()
+            .filter(|m| {
+                m.metric.workspace_id == workspace_id
+                    && !matches!(query.service_id, Some(id) if id != m.metric.service_id)
+                    && query
+                        .query_kind
+                        .is_none_or(|kind| m.metric.query_kind == Some(kind))
+            })
+            .filter_map(|m| metric_error_fingerprint(&m.metric).map(|fp| (fp, m)))
+            .collect();
+
+        let mut groups: HashMap<&str, Vec<&StoredMetric>> = HashMap::new();
+        for (fp, stored) in &failed {
+            groups.entry(fp.as_str()).or_default().push(stored);
+        }
+
+        let mut results: Vec<ErrorGroup> = groups
+            .into_iter()
+            .filter_map(|(fp, stored)| {
+                let in_range: Vec<&StoredMetric> = stored
+                    .iter()
+                    .copied()
+                    .filter(|m| m.created_at >= query.from && m.created_at < query.to)
+                    .collect();
+                if in_range.is_empty() {
+                    return None;
+                }
+
+                let mut by_query: HashMap<String, i64> = HashMap::new();
+                for m in &in_range {
+                    *by_query.entry(metric_fingerprint(&m.metric)).or_default() += 1;
+                }
+                let mut fingerprints: Vec<(String, i64)> = by_query.into_iter().collect();
+                fingerprints.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+                let example = in_range
+                    .iter()
+                    .filter_map(|m| m.metric.error_message.clone())
+                    .min();
+                Some(ErrorGroup {
+                    error_fingerprint: fp.to_string(),
+                    message: normalize_error(example.as_deref().unwrap_or_default()),
+                    example,
+                    count: in_range.len() as i64,
+                    previous_count: stored
+                        .iter()
+                        .filter(|m| m.created_at >= previous_from && m.created_at < query.from)
+                        .count() as i64,
+                    first_seen: stored
+                        .iter()
+                        .map(|m| m.created_at)
+                        .min()
+                        .unwrap_or_default(),
+                    last_seen: in_range
+                        .iter()
+                        .map(|m| m.created_at)
+                        .max()
+                        .unwrap_or_default(),
+                    query_count: fingerprints.len() as i64,
+                    fingerprints: fingerprints
+                        .into_iter()
+                        .take(ERROR_GROUP_FINGERPRINTS)
+                        .map(|(fingerprint, _)| fingerprint)
+                        .collect(),
+                })
+            })
+            .collect();
+
+        results.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| b.last_seen.cmp(&a.last_seen))
+        });
+        results.truncate(query.limit.max(0) as usize);
+        Ok(results)
+    }
+
+    async fn insert_dead_letters(&self, metrics: &[QueryMetric], error: &str) -> Result<()> {
+        let now = self.clock.now();
+        let mut inner = self.inner.write();
+        for metric in metrics {
+            let letter = inner
+                .dead_letters
+                .entry(metric.id)
+                .or_insert_with(|| DeadLetter {
+                    metric_id: metric.id,
+                    workspace_id: metric.workspace_id,
+                    metric: metric.clone(),
+                    error: String::new(),
+                    reason: DeadLetterReason::Rejected,
+                    attempts: 0,
+                    first_failed_at: now,
+                    last_failed_at: now,
+                });
+            letter.metric = metric.clone();
+            letter.error = error.to_string();
+            letter.attempts += 1;
+            letter.last_failed_at = now;
+        }
+        Ok(())
+    }
+
+    async fn list_dead_letters(
+        &self,
+        workspace_id: Option<Uuid>,
+        reason: Option<DeadLetterReason>,
+        limit: i64,
+    ) -> Result<Vec<DeadLetter>> {
+        let inner = self.inner.read();
+        let mut letters: Vec<DeadLetter> = inner
+            .dead_letters
+            .values()
+            .filter(|l| !matches!(workspace_id, Some(id) if l.workspace_id != id))
+            .filter(|l| reason.is_none_or(|reason| l.reason == reason))
+            .cloned()
+            .collect();
+        letters.sort_by_key(|l| Reverse(l.last_failed_at));
+        letters.truncate(limit.max(0) as usize);
+        Ok(letters)
+    }
+
+    async fn get_dead_letters(&self, metric_ids: &[Uuid]) -> Result<Vec<DeadLetter>> {
+        let inner = self.inner.read();
+        Ok(metric_ids
+            .iter()
+            .filter_map(|id| inner.dead_letters.get(id).cloned())
+            .collect())
+    }
+
+    async fn delete_dead_letters(&self, letters: &[(Uuid, i32)]) -> Result<u64> {
+        let mut inner = self.inner.write();
+        let mut deleted = 0;
+        for (id, attempts) in letters {
+            if inner
+                .dead_letters
+                .get(id)
+                .is_some_and(|l| l.attempts == *attempts)
+            {
+                inner.dead_letters.remove(id);
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    async fn list_services(&self, workspace_id: Uuid) -> Result<Vec<Service>> {
+        let inner = self.inner.read();
+        let mut services: Vec<Service> = inner
+            .services
+            .values()
+            .filter(|s| s.workspace_id == workspace_id)
+            .cloned()
+            .collect();
+
+        services.sort_by(|a, b| {
+            b.last_seen_at
+                .cmp(&a.last_seen_at)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        Ok(services)
+    }
+
+    async fn get_service(&self, workspace_id: Uuid, service_id: Uuid) -> Result<Option<Service>> {
+        Ok(self
+            .inner
+            .read()
+            .services
+            .get(&service_id)
+            .filter(|s| s.workspace_id == workspace_id)
+            .cloned())
+    }
+
+    async fn create_service(&self, service: &Service) -> Result<Service> {
+        let mut inner = self.inner.write();
+        if inner.services.contains_key(&service.id)
+            || inner.service_name_taken(service.workspace_id, &service.name, None)
+        {
+            return Err(AppError::Conflict(format!(
+                "Service '{}' already exists",
+                service.name
+            )));
+        }
+        let now = self.clock.now();
+        let stored = Service {
+            created_at: now,
+            updated_at: now,
+            last_seen_at: None,
+            ..service.clone()
+        };
+        inner.services.insert(stored.id, stored.clone());
+        Ok(stored)
+    }
+
+    async fn update_service(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<Option<Service>> {
+        let mut inner = self.inner.write();
+        if inner.service_name_taken(workspace_id, name, Some(service_id)) {
+            return Err(AppError::Conflict(format!(
+                "Service '{}' already exists",
+                name
+            )));
+        }
+        let Some(service) = inner
+            .services
+            .get_mut(&service_id)
+            .filter(|s| s.workspace_id == workspace_id)
+        else {
+            return Ok(None);
+        };
+        service.name = name.to_string();
+        service.description = description.map(str::to_string);
+        service.updated_at = self.clock.now();
+        Ok(Some(service.clone()))
+    }
+
+    async fn delete_service(&self, workspace_id: Uuid, service_id: Uuid) -> Result<bool> {
+        let mut inner = self.inner.write();
+        if !matches!(inner.services.get(&service_id), Some(s) if s.workspace_id == workspace_id) {
+            return Ok(false);
+        }
+        Ok(inner.services.remove(&service_id).is_some())
+    }
+
+    async fn get_top_fingerprints(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<FingerprintSummary>> {
+        let now = self.clock.now();
+        let inner = self.inner.read();
+        let mut groups: HashMap<String, Vec<&QueryMetric>> = HashMap::new();
+        for stored in inner
+            .service_metrics(workspace_id, service_id, from, to)
+            .filter(|m| query_kind.is_none_or(|kind| m.metric.query_kind == Some(kind)))
+        {
+            groups
+                .entry(metric_fingerprint(&stored.metric))
+                .or_default()
+                .push(&stored.metric);
+        }
+        groups.retain(|fingerprint, _| !inner.is_muted(workspace_id, fingerprint, now));
+
+        let mut summaries: Vec<FingerprintSummary> = groups
+            .into_iter()
+            .map(|(fingerprint, metrics)| {
+                let mut durations: Vec<i64> =
+                    metrics.iter().map(|m| m.duration_ms as i64).collect();
+                durations.sort_unstable();
+                let call_count = durations.len() as i64;
+                let total_duration_ms = durations.iter().sum::<i64>();
+                FingerprintSummary {
+                    ownership: inner
+                        .ownership
+                        .get(&(workspace_id, fingerprint.clone()))
+                        .cloned(),
+                    fingerprint,
+                    query_text: metrics
+                        .iter()
+                        .map(|m| &m.query_text)
+                        .min()
+                        .cloned()
+                        .unwrap_or_default(),
+                    call_count,
+                    total_duration_ms,
+                    avg_duration_ms: total_duration_ms / call_count,
+                    p95_duration_ms: percentile_cont(&durations, 0.95).unwrap_or(0),
+                    failed_count: metrics
+                        .iter()
+                        .filter(|m| m.status == QueryStatus::Failed)
+                        .count() as i64,
+                }
+            })
+            .collect();
+
+        summaries.sort_by_key(|s| Reverse(s.total_duration_ms));
+        summaries.truncate(limit.max(0) as usize);
+        Ok(summaries)
+    }
+
+    async fn get_top_errors(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ErrorSummary>> {
+        let inner = self.inner.read();
+        let mut groups: HashMap<(String, Option<String>), ErrorSummary> = HashMap::new();
+        for stored in inner.service_metrics(workspace_id, service_id, from, to) {
+            let metric = &stored.metric;
+            if !matches!(metric.status, QueryStatus::Failed | QueryStatus::Timeout)
+                || query_kind.is_some_and(|kind| metric.query_kind != Some(kind))
+            {
+                continue;
+            }
+            let fingerprint = metric_fingerprint(metric);
+            let summary = groups
+                .entry((fingerprint.clone(), metric.error_message.clone()))
+                .or_insert_with(|| ErrorSummary {
+                    fingerprint,
+                    query_text: metric.query_text.clone(),
+                    error_message: metric.error_message.clone(),
+                    count: 0,
+                    last_seen: stored.created_at,
+                });
+            summary.count += 1;
+            summary.last_seen = summary.last_seen.max(stored.created_at);
+            if metric.query_text < summary.query_text {
+                summary.query_text = metric.query_text.clone();
+            }
+        }
+
+        let mut errors: Vec<ErrorSummary> = groups.into_values().collect();
+        errors.sort_by_key(|e| Reverse((e.count, e.last_seen)));
+        errors.truncate(limit.max(0) as usize);
+        Ok(errors)
+    }
+
+    async fn get_service_anomalies(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<AnomalyRecord>> {
+        let inner = self.inner.read();
+        // A kind matches anomalies whose metric is still stored
+        let kind_matches = |metric_id: Uuid| {
+            query_kind.is_none_or(|kind| {
+                inner
+                    .metrics
+                    .iter()
+                    .any(|m| m.metric.id == metric_id && m.metric.query_kind == Some(kind))
+            })
+        };
+        Ok(inner
+            .anomalies
+            .iter()
+            .rev()
+            .filter(|a| {
+                a.anomaly.workspace_id == workspace_id
+                    && a.anomaly.service_id == service_id
+                    && a.detected_at >= from
+                    && a.detected_at < to
+                    && kind_matches(a.anomaly.metric_id)
+            })
+            .take(limit.max(0) as usize)
+            .map(|a| inner.anomaly_record(a))
+            .collect())
+    }
+
+    async fn upsert_ownership(
+        &self,
+        workspace_id: Uuid,
+        ownership: &QueryOwnership,
+    ) -> Result<QueryOwnership> {
+        let stored = QueryOwnership {
+            updated_at: self.clock.now(),
+            ..ownership.clone()
+        };
+        self.inner.write().ownership.insert(
+            (workspace_id, ownership.fingerprint.clone()),
+            stored.clone(),
+        );
+        Ok(stored)
+    }
+
+    async fn get_ownership(
+        &self,
+        workspace_id: Uuid,
+        fingerprint: &str,
+    ) -> Result<Option<QueryOwnership>> {
+        Ok(self
+            .inner
+            .read()
+            .ownership
+            .get(&(workspace_id, fingerprint.to_string()))
+            .cloned())
+    }
+
+    async fn list_ownership(&self, workspace_id: Uuid) -> Result<Vec<QueryOwnership>> {
+        let mut list: Vec<QueryOwnership> = self
+            .inner
+            .read()
+            .ownership
+            .iter()
+            .filter(|((ws, _), _)| *ws == workspace_id)
+            .map(|(_, o)| o.clone())
+            .collect();
+        list.sort_by_key(|o| Reverse(o.updated_at));
+        Ok(list)
+    }
+
+    async fn delete_ownership(&self, workspace_id: Uuid, fingerprint: &str) -> Result<bool> {
+        Ok(self
+            .inner
+            .write()
+            .ownership
+            .remove(&(workspace_id, fingerprint.to_string()))
+            .is_some())
+    }
+
+    async fn upsert_mute(&self, workspace_id: Uuid, mute: &QueryMute) -> Result<QueryMute> {
+        let stored = QueryMute {
+            created_at: self.clock.now(),
+            ..mute.clone()
+        };
+        self.inner
+            .write()
+            .mutes
+            .insert((workspace_id, mute.fingerprint.clone()), stored.clone());
+        Ok(stored)
+    }
+
+    async fn list_active_mutes(&self, workspace_id: Uuid) -> Result<Vec<QueryMute>> {
+        let now = self.clock.now();
+        let inner = self.inner.read();
+        let mut list: Vec<QueryMute> = inner
+            .mutes
+            .iter()
+            .filter(|((ws, fp), _)| *ws == workspace_id && inner.is_muted(*ws, fp, now))
+            .map(|(_, m)| m.clone())
+            .collect();
+        list.sort_by_key(|m| Reverse(m.created_at));
+        Ok(list)
+    }
+
+    async fn delete_mute(&self, workspace_id: Uuid, fingerprint: &str) -> Result<bool> {
+        Ok(self
+            .inner
+            .write()
+            .mutes
+            .remove(&(workspace_id, fingerprint.to_string()))
+            .is_some())
+    }
+
+    async fn insert_annotation(
+        &self,
+        workspace_id: Uuid,
+        annotation: &Annotation,
+    ) -> Result<Annotation> {
+        let stored = Annotation {
+            created_at: self.clock.now(),
+            ..annotation.clone()
+        };
+        self.inner
+            .write()
+            .annotations
+            .insert(stored.id, (workspace_id, stored.clone()));
+        Ok(stored)
+    }
+
+    async fn list_annotations(
+        &self,
+        workspace_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Annotation>> {
+        let inner = self.inner.read();
+        let mut annotations: Vec<Annotation> = inner
+            .annotations
+            .values()
+            .filter(|(ws, a)| {
+                *ws == workspace_id && a.starts_at < to && a.ends_at.unwrap_or(a.starts_at) >= from
+            })
+            .map(|(_, a)| a.clone())
+            .collect();
+
+        annotations.sort_by_key(|a| a.starts_at);
+        Ok(annotations)
+    }
+
+    async fn delete_annotation(&self, workspace_id: Uuid, annotation_id: Uuid) -> Result<bool> {
+        let mut inner = self.inner.write();
+        if !matches!(inner.annotations.get(&annotation_id), Some((ws, _)) if *ws == workspace_id) {
+            return Ok(false);
+        }
+        Ok(inner.annotations.remove(&annotation_id).is_some())
+    }
+
+    async fn insert_audit_entry(&self, entry: &AuditEntry) -> Result<()> {
+        self.inner.write().audit_log.push(entry.clone());
+        Ok(())
+    }
+
+    async fn list_audit_entries(
+        &self,
+        workspace_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        action: Option<AuditAction>,
+        limit: i64,
+    ) -> Result<Vec<AuditEntry>> {
+        let inner = self.inner.read();
+        Ok(inner
+            .audit_log
+            .iter()
+            .rev()
+            .filter(|e| {
+                e.workspace_id == workspace_id
+                    && e.created_at >= from
+                    && e.created_at < to
+                    && (action.is_none() || action == Some(e.action))
+            })
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_usage(
+        &self,
+        workspace_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<UsageDay>> {
+        let inner = self.inner.read();
+        let mut days: Vec<UsageDay> = inner
+            .usage
+            .iter()
+            .filter(|((ws, day), _)| *ws == workspace_id && *day >= from && *day <= to)
+            .map(|((_, day), (metric_count, stored_bytes))| UsageDay {
+                day: *day,
+                metric_count: *metric_count,
+                stored_bytes: *stored_bytes,
+            })
+            .collect();
+        days.sort_by_key(|d| d.day);
+        Ok(days)
+    }
+
+    async fn record_ingestion_stats(&self, rows: &[(Uuid, IngestionMinute)]) -> Result<()> {
+        let mut inner = self.inner.write();
+        for (workspace_id, row) in rows {
+            let stored = inner
+                .ingestion_stats
+                .entry((*workspace_id, row.minute))
+                .or_insert_with(|| IngestionMinute {
+                    minute: row.minute,
+                    accepted: 0,
+                    dropped: 0,
+                    deduplicated: 0,
+                });
+            stored.accepted += row.accepted;
+            stored.dropped += row.dropped;
+            stored.deduplicated += row.deduplicated;
+        }
+        Ok(())
+    }
+
+    async fn get_ingestion_stats(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<IngestionMinute>> {
+        let inner = self.inner.read();
+        let mut minutes: Vec<IngestionMinute> = inner
+            .ingestion_stats
+            .iter()
+            .filter(|((ws, minute), _)| *ws == workspace_id && *minute >= since)
+            .map(|(_, row)| row.clone())
+            .collect();
+        minutes.sort_by_key(|m| m.minute);
+        Ok(minutes)
+    }
+
+    async fn get_last_ingest(&self, since: DateTime<Utc>) -> Result<HashMap<Uuid, DateTime<Utc>>> {
+        let inner = self.inner.read();
+        let mut last: HashMap<Uuid, DateTime<Utc>> = HashMap::new();
+        for ((workspace_id, minute), row) in &inner.ingestion_stats {
+            if *minute >= since && row.accepted > 0 {
+                let latest = last.entry(*workspace_id).or_insert(*minute);
+                *latest = (*latest).max(*minute);
+            }
+        }
+        Ok(last)
+    }
+
+    async fn prune_ingestion_stats(&self, older_than_days: i32) -> Result<u64> {
+        let cutoff = self.clock.now() - Duration::days(older_than_days as i64);
+        let mut inner = self.inner.write();
+        let before = inner.ingestion_stats.len();
+        inner
+            .ingestion_stats
+            .retain(|(_, minute), _| *minute >= cutoff);
+        Ok((before - inner.ingestion_stats.len()) as u64)
+    }
+
+    async fn create_alert_rule(&self, rule: &AlertRule) -> Result<AlertRule> {
+        let now = self.clock.now();
+        let stored = AlertRule {
+            created_at: now,
+            updated_at: now,
+            ..rule.clone()
+        };
+        self.inner
+            .write()
+            .alert_rules
+            .insert(stored.id, stored.clone());
+        Ok(stored)
+    }
+
+    async fn list_alert_rules(&self, workspace_id: Uuid) -> Result<Vec<AlertRule>> {
+        let inner = self.inner.read();
+        let mut rules: Vec<AlertRule> = inner
+            .alert_rules
+            .values()
+            .filter(|r| r.workspace_id == workspace_id)
+            .cloned()
+            .collect();
+        rules.sort_by_key(|r| r.created_at);
+        Ok(rules)
+    }
+
+    async fn get_alert_rule(&self, workspace_id: Uuid, rule_id: Uuid) -> Result<Option<AlertRule>> {
+        let inner = self.inner.read();
+        Ok(inner
+            .alert_rules
+            .get(&rule_id)
+            .filter(|r| r.workspace_id == workspace_id)
+            .cloned())
+    }
+
+    async fn update_alert_rule(&self, rule: &AlertRule) -> Result<Option<AlertRule>> {
+        let mut inner = self.inner.write();
+        let Some(existing) = inner
+            .alert_rules
+            .get_mut(&rule.id)
+            .filter(|r| r.workspace_id == rule.workspace_id)
+        else {
+            return Ok(None);
+        };
+        *existing = AlertRule {
+            created_at: existing.created_at,
+            updated_at: self.clock.now(),
+            ..rule.clone()
+        };
+        Ok(Some(existing.clone()))
+    }
+
+    async fn delete_alert_rule(&self, workspace_id: Uuid, rule_id: Uuid) -> Result<bool> {
+        let mut inner = self.inner.write();
+        if inner
+            .alert_rules
+            .get(&rule_id)
+            .is_some_and(|r| r.workspace_id == workspace_id)
+        {
+            inner.alert_rules.remove(&rule_id);
+            inner.alert_rule_states.remove(&rule_id);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    async fn list_enabled_alert_rules(&self) -> Result<Vec<AlertRule>> {
+        let inner = self.inner.read();
+        Ok(inner
+            .alert_rules
+            .values()
+            .filter(|r| r.enabled)
+            .cloned()
+            .collect())
+    }
+
+    async fn list_alert_rule_states(&self) -> Result<Vec<AlertRuleState>> {
+        Ok(self
+            .inner
+            .read()
+            .alert_rule_states
+            .values()
+            .cloned()
+            .collect())
+    }
+
+    async fn upsert_alert_rule_state(&self, state: &AlertRuleState) -> Result<()> {
+        let mut inner = self.inner.write();
+        if inner.alert_rules.contains_key(&state.rule_id) {
+            inner.alert_rule_states.insert(state.rule_id, state.clone());
+        }
+        Ok(())
+    }
+
+    async fn create_alert_destination(
+        &self,
+        destination: &AlertDestination,
+    ) -> Result<AlertDestination> {
+        let stored = AlertDestination {
+            created_at: self.clock.now(),
+            ..destination.clone()
+        };
+        self.inner
+            .write()
+            .alert_destinations
+            .insert(stored.id, stored.clone());
+        Ok(stored)
+    }
+
+    async fn list_alert_destinations(&self, workspace_id: Uuid) -> Result<Vec<AlertDestination>> {
+        let inner = self.inner.read();
+        let mut destinations: Vec<AlertDestination> = inner
+            .alert_destinations
+            .values()
+            .filter(|d| d.workspace_id == workspace_id)
+            .cloned()
+            .collect();
+        destinations.sort_by_key(|d| d.created_at);
+        Ok(destinations)
+    }
+
+    async fn get_alert_destination(
+        &self,
+        workspace_id: Uuid,
+        destination_id: Uuid,
+    ) -> Result<Option<AlertDestination>> {
+        let inner = self.inner.read();
+        Ok(inner
+            .alert_destinations
+            .get(&destination_id)
+            .filter(|d| d.workspace_id == workspace_id)
+            .cloned())
+    }
+
+    async fn delete_alert_destination(
+        &self,
+        workspace_id: Uuid,
+        destination_id: Uuid,
+    ) -> Result<bool> {
+        let mut inner = self.inner.write();
+        if inner
+            .alert_destinations
+            .get(&destination_id)
+            .is_some_and(|d| d.workspace_id == workspace_id)
+        {
+            inner.alert_destinations.remove(&destination_id);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    async fn insert_alert_delivery(&self, delivery: &AlertDelivery) -> Result<()> {
+        self.inner.write().alert_deliveries.push(delivery.clone());
+        Ok(())
+    }
+
+    async fn list_alert_deliveries(
+        &self,
+        workspace_id: Uuid,
+        destination_id: Option<Uuid>,
+        failed_only: bool,
+        limit: i64,
+    ) -> Result<Vec<AlertDelivery>> {
+        let inner = self.inner.read();
+        Ok(inner
+            .alert_deliveries
+            .iter()
+            .rev()
+            .filter(|d| d.workspace_id == workspace_id)
+            .filter(|d| destination_id.is_none() || destination_id == Some(d.destination_id))
+            .filter(|d| !(failed_only && d.delivered))
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn prune_alert_deliveries(&self, older_than_days: i32) -> Result<u64> {
+        let cutoff = self.clock.now() - Duration::days(older_than_days as i64);
+        let mut inner = self.inner.write();
+        let before = inner.alert_deliveries.len();
+        inner.alert_deliveries.retain(|d| d.created_at >= cutoff);
+        Ok((before - inner.alert_deliveries.len()) as u64)
+    }
+
+    async fn create_slo(&self, slo: &Slo) -> Result<Slo> {
+        let now = self.clock.now();
+        let stored = Slo {
+            created_at: now,
+            updated_at: now,
+            ..slo.clone()
+        };
+        self.inner.write().slos.insert(stored.id, stored.clone());
+        Ok(stored)
+    }
+
+    async fn list_slos(&self, workspace_id: Uuid) -> Result<Vec<Slo>> {
+        let inner = self.inner.read();
+        let mut slos: Vec<Slo> = inner
+            .slos
+            .values()
+            .filter(|s| s.workspace_id == workspace_id)
+            .cloned()
+            .collect();
+        slos.sort_by_key(|s| s.created_at);
+        Ok(slos)
+    }
+
+    async fn get_slo(&self, workspace_id: Uuid, slo_id: Uuid) -> Result<Option<Slo>> {
+        let inner = self.inner.read();
+        Ok(inner
+            .slos
+            .get(&slo_id)
+            .filter(|s| s.workspace_id == workspace_id)
+            .cloned())
+    }
+
+    async fn update_slo(&self, slo: &Slo) -> Result<Option<Slo>> {
+        let mut inner = self.inner.write();
+        let Some(existing) = inner
+            .slos
+            .get_mut(&slo.id)
+            .filter(|s| s.workspace_id == slo.workspace_id)
+        else {
+            return Ok(None);
+        };
+        *existing = Slo {
+            created_at: existing.created_at,
+            updated_at: self.clock.now(),
+            ..slo.clone()
+        };
+        Ok(Some(existing.clone()))
+    }
+
+    async fn delete_slo(&self, workspace_id: Uuid, slo_id: Uuid) -> Result<bool> {
+        let mut inner = self.inner.write();
+        if inner
+            .slos
+            .get(&slo_id)
+            .is_some_and(|s| s.workspace_id == workspace_id)
+        {
+            inner.slos.remove(&slo_id);
+            inner.slo_statuses.remove(&slo_id);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    async fn list_enabled_slos(&self) -> Result<Vec<Slo>> {
+        let inner = self.inner.read();
+        Ok(inner.slos.values().filter(|s| s.enabled).cloned().collect())
+    }
+
+    async fn get_slo_counts(
+        &self,
+        workspace_id: Uuid,
+        service_id: Option<Uuid>,
+        threshold_ms: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<SloCounts> {
+        let inner = self.inner.read();
+        let mut counts = SloCounts::default();
+        for stored in inner.metrics.iter().filter(|m| {
+            m.metric.workspace_id == workspace_id
+                && service_id.is_none_or(|id| m.metric.service_id == id)
+                && m.created_at >= from
+                && m.created_at < to
+        }) {
+            counts.total += 1;
+            if stored.metric.status != QueryStatus::Success {
+                continue;
+            }
+            let duration = stored.metric.duration_ms as i64;
+            if duration <= threshold_ms {
+                counts.satisfied += 1;
+            } else if duration <= threshold_ms * 4 {
+                counts.tolerating += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn list_slo_statuses(&self, workspace_id: Uuid) -> Result<Vec<SloStatus>> {
+        let inner = self.inner.read();
+        Ok(inner
+            .slo_statuses
+            .values()
+            .filter(|s| {
+                inner
+                    .slos
+                    .get(&s.slo_id)
+                    .is_some_and(|slo| slo.workspace_id == workspace_id)
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn list_all_slo_statuses(&self) -> Result<Vec<SloStatus>> {
+        Ok(self.inner.read().slo_statuses.values().cloned().collect())
+    }
+
+    async fn upsert_slo_status(&self, status: &SloStatus) -> Result<()> {
+        let mut inner = self.inner.write();
+        if inner.slos.contains_key(&status.slo_id) {
+            inner.slo_statuses.insert(status.slo_id, status.clone());
+        }
+        Ok(())
+    }
+
+    async fn get_lifecycle_policy(
+        &self,
+        workspace_id: Uuid,
+    ) -> Result<Option<Vec<LifecycleStage>>> {
+        let inner = self.inner.read();
+        if !inner.workspaces.iter().any(|w| w.id == workspace_id) {
+            return Ok(None);
+        }
+        Ok(Some(
+            inner
+                .lifecycle_policies
+                .get(&workspace_id)
+                .cloned()
+                .unwrap_or_default(),
+        ))
+    }
+
+    async fn set_lifecycle_policy(
+        &self,
+        workspace_id: Uuid,
+        stages: &[LifecycleStage],
+    ) -> Result<bool> {
+        let mut inner = self.inner.write();
+        if !inner.workspaces.iter().any(|w| w.id == workspace_id) {
+            return Ok(false);
+        }
+        let previous = inner
+            .lifecycle_policies
+            .remove(&workspace_id)
+            .unwrap_or_default();
+        let mut stages: Vec<LifecycleStage> = stages
+            .iter()
+            .map(
+                |stage| match previous.iter().find(|p| p.action == stage.action) {
+                    Some(p) => LifecycleStage {
+                        after_days: stage.after_days,
+                        ..p.clone()
+                    },
+                    None => LifecycleStage::new(stage.action, stage.after_days),
+                },
+            )
+            .collect();
+        if !stages.is_empty() {
+            stages.sort_by_key(|stage| stage.action);
+            inner.lifecycle_policies.insert(workspace_id, stages);
+        }
+        Ok(true)
+    }
+
+    async fn list_lifecycle_policies(&self) -> Result<HashMap<Uuid, Vec<LifecycleStage>>> {
+        Ok(self.inner.read().lifecycle_policies.clone())
+    }
+
+    async fn apply_lifecycle_action(
+        &self,
+        workspace_id: Uuid,
+        action: LifecycleAction,
+        before: DateTime<Utc>,
+    ) -> Result<u64> {
+        let mut inner = self.inner.write();
+        let affected = match action {
+            LifecycleAction::StripText => {
+                let mut stripped = 0;
+                for stored in inner.metrics.iter_mut().filter(|m| {
+                    m.metric.workspace_id == workspace_id
+                        && m.created_at < before
+                        && m.metric.fingerprint.is_some()
+                        && !m.metric.query_text.is_empty()
+                }) {
+                    stored.metric.query_text.clear();
+                    stripped += 1;
+                }
+                stripped
+            }
+            LifecycleAction::Archive => {
+                let due: Vec<StoredMetric> = inner
+                    .metrics
+                    .iter()
+                    .filter(|m| m.metric.workspace_id == workspace_id && m.created_at < before)
+                    .cloned()
+                    .collect();
+                let mut archived = 0;
+                for stored in due {
+                    let key = (workspace_id, stored.metric.id);
+                    if let std::collections::hash_map::Entry::Vacant(entry) =
+                        inner.archive.entry(key)
+                    {
+                        entry.insert(stored);
+                        archived += 1;
+                    }
+                }
+                archived
+            }
+            LifecycleAction::Downsample | LifecycleAction::Delete => {
+                let metrics_before = inner.metrics.len();
+                inner
+                    .metrics
+                    .retain(|m| m.metric.workspace_id != workspace_id || m.created_at >= before);
+                let mut removed = metrics_before - inner.metrics.len();
+                if action == LifecycleAction::Delete {
+                    let archive_before = inner.archive.len();
+                    inner
+                        .archive
+                        .retain(|&(ws, _), m| ws != workspace_id || m.created_at >= before);
+                    let anomalies_before = inner.anomalies.len() + inner.throughput_drops.len();
+                    inner.anomalies.retain(|a| {
+                        a.anomaly.workspace_id != workspace_id || a.detected_at >= before
+                    });
+                    inner.throughput_drops.retain(|d| {
+                        d.workspace_id != workspace_id
+                            || d.recovered_at.is_none_or(|at| at >= before)
+                    });
+                    removed += archive_before + anomalies_before
+                        - inner.archive.len()
+                        - inner.anomalies.len()
+                        - inner.throughput_drops.len();
+                }
+                removed
+            }
+        };
+        Ok(affected as u64)
+    }
+
+    async fn record_lifecycle_run(&self, workspace_id: Uuid, stage: &LifecycleStage) -> Result<()> {
+        let mut inner = self.inner.write();
+        if let Some(current) = inner
+            .lifecycle_policies
+            .get_mut(&workspace_id)
+            .and_then(|stages| stages.iter_mut().find(|s| s.action == stage.action))
+        {
+            current.last_run_at = stage.last_run_at;
+            current.last_affected = stage.last_affected;
+            current.last_error = stage.last_error.clone();
+        }
+        Ok(())
+    }
+
+    async fn insert_query_embeddings(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        embeddings: &[NewQueryEmbedding],
+    ) -> Result<()> {
+        let mut inner = self.inner.write();
+        for new in embeddings {
+            let entry = inner
+                .embeddings
+                .entry((workspace_id, new.query_hash.clone()))
+                .or_insert_with(|| StoredEmbedding {
+                    id: Uuid::new_v4(),
+                    sql_query: new.sql_query.clone(),
+                    embedding: Vec::new(),
+                    model: model.clone(),
+                });
+            entry.embedding = new.embedding.clone();
+            entry.model = model.clone();
+        }
+        Ok(())
+    }
+
+    async fn search_similar_queries(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        embedding: &[f32],
+        limit: i32,
+        threshold: f32,
+        _tuning: AnnTuning,
+    ) -> Result<Vec<SimilarQuery>> {
+        // Searches are exact, so there's no index to tune
+        let inner = self.inner.read();
+        let mut results: Vec<SimilarQuery> = inner
+            .embeddings
+            .iter()
+            .filter(|((ws, _), e)| *ws == workspace_id && e.model == *model)
+            .map(|(_, e)| SimilarQuery {
+                id: e.id,
+                sql_query: e.sql_query.clone(),
+                similarity: cosine_similarity(&e.embedding, embedding) as f64,
+            })
+            .filter(|r| r.similarity >= threshold as f64)
+            .collect();
+
+        results.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+        results.truncate(limit.max(0) as usize);
+        Ok(results)
+    }
+
+    async fn search_lexical_queries(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        embedding: &[f32],
+        terms: &[String],
+        limit: i32,
+    ) -> Result<Vec<SimilarQuery>> {
+        let phrases: Vec<Vec<&str>> = terms
+            .iter()
+            .map(|term| hybrid_search::term_parts(term).collect::<Vec<_>>())
+            .filter(|parts| !parts.is_empty())
+            .collect();
+
+        // Ranked by how many of the terms a query contains, each as a run of
+        // its parts, like Postgres phrase matching
+        let inner = self.inner.read();
+        let mut results: Vec<(usize, SimilarQuery)> = inner
+            .embeddings
+            .iter()
+            .filter(|((ws, _), e)| *ws == workspace_id && e.model == *model)
+            .filter_map(|(_, e)| {
+                let lowered = e.sql_query.to_lowercase();
+                let words: Vec<&str> = hybrid_search::term_parts(&lowered).collect();
+                let matched = phrases
+                    .iter()
+                    .filter(|phrase| words.windows(phrase.len()).any(|w| w == phrase.as_slice()))
+                    .count();
+                (matched > 0).then(|| {
+                    let query = SimilarQuery {
+                        id: e.id,
+                        sql_query: e.sql_query.clone(),
+                        similarity: cosine_similarity(&e.embedding, embedding) as f64,
+                    };
+                    (matched, query)
+                })
+            })
+            .collect();
+
+        results.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.id.cmp(&b.1.id)));
+        results.truncate(limit.max(0) as usize);
+        Ok(results.into_iter().map(|(_, query)| query).collect())
+    }
+
+    async fn get_unembedded_queries(
+        &self,
+        workspace_id: Uuid,
+        limit: i64,
+        max_bytes: usize,
+    ) -> Result<Vec<(String, String)>> {
+        let inner = self.inner.read();
+        // Per hash: the first text, call count and maximum duration
+        let mut executed: HashMap<String, (&str, usize, i64)> = HashMap::new();
+        for m in inner
+            .metrics
+            .iter()
+            .filter(|m| m.metric.workspace_id == workspace_id)
+        {
+            let entry = executed.entry(query_hash(&m.metric.query_text)).or_insert((
+                &m.metric.query_text,
+                0,
+                i64::MIN,
+            ));
+            entry.1 += 1;
+            entry.2 = entry.2.max(m.metric.duration_ms as i64);
+        }
+
+        let mut backlog: Vec<(String, (&str, usize, i64))> = executed
+            .into_iter()
+            .filter(|(hash, _)| {
+                let key = (workspace_id, hash.clone());
+                !inner.embeddings.contains_key(&key)
+                    && !matches!(inner.embedding_skips.get(&key), Some(bytes) if *bytes > max_bytes)
+            })
+            .collect();
+        backlog.sort_by(|(a_hash, a), (b_hash, b)| {
+            b.1.cmp(&a.1)
+                .then_with(|| b.2.cmp(&a.2))
+                .then_with(|| a_hash.cmp(b_hash))
+        });
+        Ok(backlog
+            .into_iter()
+            .take(limit.max(0) as usize)
+            .map(|(hash, (text, _, _))| (text.to_string(), hash))
+            .collect())
+    }
+
+    async fn insert_embedding_skips(
+        &self,
+        workspace_id: Uuid,
+        skips: &[EmbeddingSkip],
+    ) -> Result<()> {
+        let mut inner = self.inner.write();
+        for skip in skips {
+            inner
+                .embedding_skips
+                .insert((workspace_id, skip.query_hash.clone()), skip.query_bytes);
+        }
+        Ok(())
+    }
+
+    async fn get_stale_embedding_workspaces(&self, model: &EmbeddingModel) -> Result<Vec<Uuid>> {
+        let inner = self.inner.read();
+        let workspaces: HashSet<Uuid> = inner
+            .embeddings
+            .iter()
+            .filter(|(_, e)| e.model != *model)
+            .map(|((ws, _), _)| *ws)
+            .collect();
+        Ok(workspaces.into_iter().collect())
+    }
+
+    async fn get_stale_embeddings(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        limit: i64,
+    ) -> Result<Vec<(String, String)>> {
+        let inner = self.inner.read();
+        let mut stale: Vec<(String, String)> = inner
+            .embeddings
+            .iter()
+            .filter(|((ws, _), e)| *ws == workspace_id && e.model != *model)
+            .map(|((_, hash), e)| (e.sql_query.clone(), hash.clone()))
+            .collect();
+        stale.sort_by(|a, b| a.1.cmp(&b.1));
+        stale.truncate(limit.max(0) as usize);
+        Ok(stale)
+    }
+
+    async fn get_query_embedding(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        query_text: &str,
+    ) -> Result<Option<Vec<f32>>> {
+        Ok(self
+            .inner
+            .read()
+            .embeddings
+            .get(&(workspace_id, query_hash(query_text)))
+            .filter(|e| e.model == *model)
+            .map(|e| e.embedding.clone()))
+    }
+
+    async fn list_query_embeddings(
+        &self,
+        workspace_id: Uuid,
+        model: Option<&EmbeddingModel>,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<EmbeddedQuery>> {
+        let inner = self.inner.read();
+        let mut durations: HashMap<String, Vec<i64>> = HashMap::new();
+        for m in inner
+            .metrics
+            .iter()
+            .filter(|m| m.metric.workspace_id == workspace_id && m.created_at >= since)
+        {
+            durations
+                .entry(query_hash(&m.metric.query_text))
+                .or_default()
+                .push(m.metric.duration_ms as i64);
+        }
+
+        let mut results: Vec<EmbeddedQuery> = inner
+            .embeddings
+            .iter()
+            .filter(|((ws, _), e)| *ws == workspace_id && model.is_none_or(|m| e.model == *m))
+            .map(|((_, hash), e)| {
+                let mut calls = durations.get(hash).cloned().unwrap_or_default();
+                calls.sort_unstable();
+                EmbeddedQuery {
+                    query_hash: hash.clone(),
+                    sql_query: e.sql_query.clone(),
+                    embedding: e.embedding.clone(),
+                    call_count: calls.len() as i64,
+                    avg_duration_ms: (!calls.is_empty())
+                        .then(|| calls.iter().sum::<i64>() / calls.len() as i64),
+                    p95_duration_ms: percentile_cont(&calls, 0.95),
+                }
+            })
+            .collect();
+
+        results.sort_by(|a, b| {
+            b.call_count
+                .cmp(&a.call_count)
+                .then_with(|| a.query_hash.cmp(&b.query_hash))
+        });
+        results.truncate(limit.max(0) as usize);
+        Ok(results)
+    }
+
+    async fn get_embedded_workspaces(&self, model: &EmbeddingModel) -> Result<Vec<Uuid>> {
+        let inner = self.inner.read();
+        let workspaces: HashSet<Uuid> = inner
+            .embeddings
+            .iter()
+            .filter(|(_, e)| e.model == *model)
+            .map(|((ws, _), _)| *ws)
+            .collect();
+        Ok(workspaces.into_iter().collect())
+    }
+
+    async fn replace_query_clusters(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        computed_at: DateTime<Utc>,
+        clusters: &[NewQueryCluster],
+    ) -> Result<()> {
+        let stored = StoredClusters {
+            model: model.clone(),
+            computed_at,
+            clusters: (0..).zip(clusters.iter().cloned()).collect(),
+        };
+        self.inner
+            .write()
+            .query_clusters
+            .insert(workspace_id, stored);
+        Ok(())
+    }
+
+    async fn get_query_clusters(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Option<QueryClusters>> {
+        let inner = self.inner.read();
+        let Some(stored) = inner.query_clusters.get(&workspace_id) else {
+            return Ok(None);
+        };
+
+        let mut calls: HashMap<String, Vec<&QueryMetric>> = HashMap::new();
+        for m in inner
+            .metrics
+            .iter()
+            .filter(|m| m.metric.workspace_id == workspace_id && m.created_at >= since)
+        {
+            calls
+                .entry(query_hash(&m.metric.query_text))
+                .or_default()
+                .push(&m.metric);
+        }
+
+        let mut clusters: Vec<QueryCluster> = stored
+            .clusters
+            .iter()
+            .map(|(id, cluster)| {
+                let metrics: Vec<&QueryMetric> = cluster
+                    .members
+                    .iter()
+                    .filter_map(|hash| calls.get(hash))
+                    .flatten()
+                    .copied()
+                    .collect();
+                let mut durations: Vec<i64> =
+                    metrics.iter().map(|m| m.duration_ms as i64).collect();
+                durations.sort_unstable();
+                let total: i64 = durations.iter().sum();
+                QueryCluster {
+                    id: *id,
+                    label: cluster.label.clone(),
+                    size: cluster.members.len() as i64,
+                    representative_hash: cluster.representative_hash.clone(),
+                    representative_query: cluster.representative_query.clone(),
+                    call_count: metrics.len() as i64,
+                    error_count: metrics
+                        .iter()
+                        .filter(|m| matches!(m.status, QueryStatus::Failed | QueryStatus::Timeout))
+                        .count() as i64,
+                    total_duration_ms: total,
+                    avg_duration_ms: (!durations.is_empty())
+                        .then(|| total / durations.len() as i64),
+                    p95_duration_ms: percentile_cont(&durations, 0.95),
+                }
+            })
+            .collect();
+
+        clusters.sort_by(|a, b| {
+            b.total_duration_ms
+                .cmp(&a.total_duration_ms)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        clusters.truncate(limit.max(0) as usize);
+        Ok(Some(QueryClusters {
+            model: stored.model.clone(),
+            computed_at: stored.computed_at,
+            clusters,
+        }))
+    }
+
+    async fn get_metric_similarity(
+        &self,
+        workspace_id: Uuid,
+        metric_id: Uuid,
+        since: DateTime<Utc>,
+        limit: i64,
+        threshold: f32,
+    ) -> Result<Option<MetricSimilarity>> {
+        let inner = self.inner.read();
+        let Some(source) = inner.metrics.iter().find(|m| {
+            m.metric.workspace_id == workspace_id
+                && m.metric.id == metric_id
+                && m.created_at >= since
+        }) else {
+            return Ok(None);
+        };
+        let source_hash = query_hash(&source.metric.query_text);
+        let mut similarity = MetricSimilarity {
+            query_hash: source_hash.clone(),
+            query_text: source.metric.query_text.clone(),
+            model: None,
+            similar: Vec::new(),
+        };
+        let Some(embedded) = inner.embeddings.get(&(workspace_id, source_hash)) else {
+            return Ok(Some(similarity));
+        };
+
+        let mut similar: Vec<(String, SimilarQuery)> = inner
+            .embeddings
+            .iter()
+            .filter(|((ws, _), e)| {
+                *ws == workspace_id && e.model == embedded.model && e.id != embedded.id
+            })
+            .map(|((_, hash), e)| {
+                let query = SimilarQuery {
+                    id: e.id,
+                    sql_query: e.sql_query.clone(),
+                    similarity: cosine_similarity(&e.embedding, &embedded.embedding) as f64,
+                };
+                (hash.clone(), query)
+            })
+            .filter(|(_, q)| q.similarity >= threshold as f64)
+            .collect();
+        similar.sort_by(|a, b| {
+            b.1.similarity
+                .total_cmp(&a.1.similarity)
+                .then_with(|| a.1.id.cmp(&b.1.id))
+        });
+        similar.truncate(limit.max(0) as usize);
+
+        similarity.similar = similar
+            .into_iter()
+            .map(|(hash, query)| {
+                let calls: Vec<&QueryMetric> = inner
+                    .metrics
+                    .iter()
+                    .filter(|m| {
+                        m.metric.workspace_id == workspace_id
+                            && m.created_at >= since
+                            && query_hash(&m.metric.query_text) == hash
+                    })
+                    .map(|m| &m.metric)
+                    .collect();
+                let mut durations: Vec<i64> = calls.iter().map(|m| m.duration_ms as i64).collect();
+                durations.sort_unstable();
+                SimilarQueryStats {
+                    query,
+                    query_hash: hash,
+                    call_count: calls.len() as i64,
+                    error_count: calls
+                        .iter()
+                        .filter(|m| matches!(m.status, QueryStatus::Failed | QueryStatus::Timeout))
+                        .count() as i64,
+                    avg_duration_ms: (!durations.is_empty())
+                        .then(|| durations.iter().sum::<i64>() / durations.len() as i64),
+                    p50_duration_ms: percentile_cont(&durations, 0.5),
+                    p95_duration_ms: percentile_cont(&durations, 0.95),
+                    p99_duration_ms: percentile_cont(&durations, 0.99),
+                }
+            })
+            .collect();
+        similarity.model = Some(embedded.model.clone());
+        Ok(Some(similarity))
+    }
+
+    async fn get_metrics_stats(
+        &self,
+        limits: &HashMap<Uuid, i64>,
+    ) -> Result<HashMap<Uuid, MetricsStats>> {
+        let now = self.clock.now();
+        let inner = self.inner.read();
+        let mut durations: HashMap<Uuid, Vec<f64>> = HashMap::new();
+        for m in inner.metrics.iter().rev() {
+            let workspace_id = m.metric.workspace_id;
+            let Some(limit) = limits.get(&workspace_id) else {
+                continue;
+            };
+            let recent = durations.entry(workspace_id).or_default();
+            if recent.len() < (*limit).max(0) as usize
+                && !inner.is_muted(workspace_id, &metric_fingerprint(&m.metric), now)
+            {
+                recent.push(m.metric.duration_ms as f64);
+            }
+        }
+
+        Ok(durations
+            .into_iter()
+            .filter(|(_, durations)| !durations.is_empty())
+            .map(|(workspace_id, durations)| {
+                let mut stats = duration_stats(durations);
+                stats.method = inner.anomaly_method(workspace_id);
+                (workspace_id, stats)
+            })
+            .collect())
+    }
+
+    async fn refresh_seasonal_baselines(
+        &self,
+        workspace_id: Uuid,
+        seasonality: Seasonality,
+        since: DateTime<Utc>,
+    ) -> Result<u64> {
+        let now = self.clock.now();
+        let mut inner = self.inner.write();
+        inner
+            .seasonal_baselines
+            .retain(|(id, _), _| *id != workspace_id);
+
+        let mut durations: BTreeMap<i16, Vec<f64>> = BTreeMap::new();
+        for m in &inner.metrics {
+            if m.metric.workspace_id != workspace_id
+                || m.created_at < since
+                || inner.is_muted(workspace_id, &metric_fingerprint(&m.metric), now)
+            {
+                continue;
+            }
+            if let Some(slot) = seasonal_slot(seasonality, m.created_at) {
+                durations
+                    .entry(slot)
+                    .or_default()
+                    .push(m.metric.duration_ms as f64);
+            }
+        }
+
+        let slots = durations.len() as u64;
+        for (slot, durations) in durations {
+            inner.seasonal_baselines.insert(
+                (workspace_id, slot),
+                (seasonality, duration_stats(durations)),
+            );
+        }
+        Ok(slots)
+    }
+
+    async fn get_seasonal_baselines(
+        &self,
+        slots: &HashMap<Uuid, (Seasonality, i16)>,
+    ) -> Result<HashMap<Uuid, MetricsStats>> {
+        let inner = self.inner.read();
+        Ok(slots
+            .iter()
+            .filter_map(|(workspace_id, (seasonality, slot))| {
+                let (stored, stats) = inner.seasonal_baselines.get(&(*workspace_id, *slot))?;
+                (stored == seasonality).then(|| {
+                    let mut stats = stats.clone();
+                    stats.method = inner.anomaly_method(*workspace_id);
+                    (*workspace_id, stats)
+                })
+            })
+            .collect())
+    }
+
+    async fn get_recent_metrics_for_anomaly(
+        &self,
+        thresholds: &HashMap<Uuid, AnomalyThreshold>,
+    ) -> Result<Vec<QueryMetric>> {
+        let now = self.clock.now();
+        let inner = self.inner.read();
+        let mut metrics: Vec<QueryMetric> = inner
+            .metrics
+            .iter()
+            .filter(|m| {
+                let workspace_id = m.metric.workspace_id;
+                thresholds.get(&workspace_id).is_some_and(|t| {
+                    m.metric.duration_ms as i64 > t.duration_ms
+                        && m.created_at > now - Duration::seconds(t.since_seconds)
+                }) && !inner.is_muted(workspace_id, &metric_fingerprint(&m.metric), now)
+            })
+            .map(|m| m.metric.clone())
+            .collect();
+
+        metrics.sort_by_key(|m| Reverse(m.duration_ms));
+        Ok(metrics)
+    }
+
+    async fn insert_anomalies(&self, anomalies: &[QueryAnomaly]) -> Result<u64> {
+        let detected_at = self.clock.now();
+        self.inner
+            .write()
+            .anomalies
+            .extend(anomalies.iter().map(|anomaly| StoredAnomaly {
+                id: anomaly.id,
+                anomaly: anomaly.clone(),
+                detected_at,
+                status: AnomalyStatus::Open,
+                acknowledged_by: None,
+                resolved_at: None,
+            }));
+        Ok(anomalies.len() as u64)
+    }
+
+    async fn get_anomaly_context(
+        &self,
+        workspace_id: Uuid,
+        anomaly_id: Uuid,
+    ) -> Result<Option<AnomalyContext>> {
+        let inner = self.inner.read();
+        Ok(inner
+            .anomalies
+            .iter()
+            .find(|a| a.id == anomaly_id && a.anomaly.workspace_id == workspace_id)
+            .map(|a| AnomalyContext {
+                anomaly: inner.anomaly_record(a),
+                hints: a.anomaly.hints.clone(),
+            }))
+    }
+
+    async fn get_fingerprint_call_counts(
+        &self,
+        workspace_id: Uuid,
+        fingerprints: &[String],
+        since: DateTime<Utc>,
+    ) -> Result<HashMap<String, i64>> {
+        let inner = self.inner.read();
+        let mut counts = HashMap::new();
+        for m in inner
+            .metrics
+            .iter()
+            .filter(|m| m.metric.workspace_id == workspace_id && m.created_at >= since)
+        {
+            let fingerprint = metric_fingerprint(&m.metric);
+            if fingerprints.contains(&fingerprint) {
+                *counts.entry(fingerprint).or_insert(0) += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn get_anomalies(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+        min_severity: SeverityLevel,
+        status: Option<AnomalyStatus>,
+        limit: i64,
+    ) -> Result<Vec<AnomalyRecord>> {
+        let inner = self.inner.read();
+        let mut anomalies: Vec<&StoredAnomaly> = inner
+            .anomalies
+            .iter()
+            .rev()
+            .filter(|a| {
+                a.anomaly.workspace_id == workspace_id
+                    && a.detected_at >= since
+                    && a.anomaly.severity_level >= min_severity
+                    && status.is_none_or(|status| a.status == status)
+            })
+            .collect();
+        // Stable, so equal severities stay newest first
+        anomalies.sort_by(|a, b| {
+            (b.anomaly.severity_level.cmp(&a.anomaly.severity_level))
+                .then(b.anomaly.severity.total_cmp(&a.anomaly.severity))
+        });
+        Ok(anomalies
+            .into_iter()
+            .take(limit.max(0) as usize)
+            .map(|a| inner.anomaly_record(a))
+            .collect())
+    }
+
+    async fn acknowledge_anomaly(
+        &self,
+        workspace_id: Uuid,
+        anomaly_id: Uuid,
+        acknowledged_by: &str,
+    ) -> Result<Option<AnomalyRecord>> {
+        let mut inner = self.inner.write();
+        let Some(index) = inner.anomalies.iter().position(|a| {
+            a.id == anomaly_id
+                && a.anomaly.workspace_id == workspace_id
+                && a.status == AnomalyStatus::Open
+        }) else {
+            return Ok(None);
+        };
+        let a = &mut inner.anomalies[index];
+        a.status = AnomalyStatus::Acknowledged;
+        a.acknowledged_by = Some(acknowledged_by.to_string());
+        Ok(Some(inner.anomaly_record(&inner.anomalies[index])))
+    }
+
+    async fn resolve_anomaly(
+        &self,
+        workspace_id: Uuid,
+        anomaly_id: Uuid,
+        resolved_by: &str,
+        resolved_at: DateTime<Utc>,
+    ) -> Result<Option<AnomalyRecord>> {
+        let mut inner = self.inner.write();
+        let Some(index) = inner.anomalies.iter().position(|a| {
+            a.id == anomaly_id
+                && a.anomaly.workspace_id == workspace_id
+                && a.status != AnomalyStatus::Resolved
+        }) else {
+            return Ok(None);
+        };
+        let a = &mut inner.anomalies[index];
+        a.status = AnomalyStatus::Resolved;
+        a.acknowledged_by
+            .get_or_insert_with(|| resolved_by.to_string());
+        a.resolved_at = Some(resolved_at);
+        Ok(Some(inner.anomaly_record(&inner.anomalies[index])))
+    }
+
+    async fn get_service_throughput(
+        &self,
+        workspace_ids: &[Uuid],
+        baseline_from: DateTime<Utc>,
+        recent_from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<ServiceThroughput>> {
+        let inner = self.inner.read();
+        let mut counts: HashMap<(Uuid, Uuid), (i64, i64)> = HashMap::new();
+        for stored in inner.metrics.iter().filter(|m| {
+            workspace_ids.contains(&m.metric.workspace_id)
+                && m.created_at >= baseline_from
+                && m.created_at < to
+        }) {
+            let entry = counts
+                .entry((stored.metric.workspace_id, stored.metric.service_id))
+                .or_default();
+            if stored.created_at < recent_from {
+                entry.0 += 1;
+            } else {
+                entry.1 += 1;
+            }
+        }
+        Ok(counts
+            .into_iter()
+            .map(
+                |((workspace_id, service_id), (baseline, recent))| ServiceThroughput {
+                    workspace_id,
+                    service_id,
+                    baseline,
+                    recent,
+                },
+            )
+            .collect())
+    }
+
+    async fn insert_throughput_drop(&self, drop: &ThroughputDrop) -> Result<()> {
+        let mut inner = self.inner.write();
+        let open = inner.throughput_drops.iter().any(|d| {
+            d.workspace_id == drop.workspace_id
+                && d.service_id == drop.service_id
+                && d.recovered_at.is_none()
+        });
+        if !open {
+            inner.throughput_drops.push(drop.clone());
+        }
+        Ok(())
+    }
+
+    async fn resolve_throughput_drop(
+        &self,
+        drop_id: Uuid,
+        recovered_at: DateTime<Utc>,
+    ) -> Result<()> {
+        let mut inner = self.inner.write();
+        if let Some(drop) = inner
+            .throughput_drops
+            .iter_mut()
+            .find(|d| d.id == drop_id && d.recovered_at.is_none())
+        {
+            drop.recovered_at = Some(recovered_at);
+        }
+        Ok(())
+    }
+
+    async fn list_open_throughput_drops(&self) -> Result<Vec<ThroughputDrop>> {
+        Ok(self
+            .inner
+            .read()
+            .throughput_drops
+            .iter()
+            .filter(|d| d.recovered_at.is_none())
+            .cloned()
+            .collect())
+    }
+
+    async fn list_throughput_drops(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ThroughputDrop>> {
+        let mut drops: Vec<ThroughputDrop> = self
+            .inner
+            .read()
+            .throughput_drops
+            .iter()
+            .filter(|d| {
+                d.workspace_id == workspace_id
+                    && (d.started_at >= since || d.recovered_at.is_none())
+            })
+            .cloned()
+            .collect();
+        drops.sort_by_key(|d| Reverse(d.started_at));
+        drops.truncate(limit.max(0) as usize);
+        Ok(drops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QueryKind;
+    use crate::services::fingerprint::{fingerprint, SqlDialect};
+    use std::collections::BTreeMap;
+
+    fn make_metric(workspace_id: Uuid, query: &str, duration_ms: u64) -> QueryMetric {
+        QueryMetric::new(
+            workspace_id,
+            Uuid::new_v4(),
+            query.to_string(),
+            QueryStatus::Success,
+            duration_ms,
+            Utc::now(),
+        )
+    }
+
+    #[test]
+    fn test_query_hash_matches_sql_normalization() {
+        // md5('select 1'), as computed by Postgres
+        assert_eq!(
+            query_hash("SELECT  1\n"),
+            "95adb6e77a0884d9e50232cb8c5c969d"
+        );
+    }
+
+    #[test]
+    fn test_percentile_cont() {
+        assert_eq!(percentile_cont(&[], 0.95), None);
+        assert_eq!(percentile_cont(&[10], 0.95), Some(10));
+        assert_eq!(percentile_cont(&[0, 100], 0.5), Some(50));
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_aggregate() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let batch: Vec<_> = (1..=10)
+            .map(|i| make_metric(ws.id, "SELECT 1", i * 10))
+            .collect();
+
+        assert_eq!(
+            store.insert_metrics_batch(&batch).await.unwrap().inserted,
+            10
+        );
+        assert_eq!(
+            store
+                .get_recent_metrics(ws.id, &MetricFilter::default(), 5)
+                .await
+                .unwrap()
+                .len(),
+            5
+        );
+
+        let now = Utc::now();
+        let buckets = store
+            .get_aggregations(
+                ws.id,
+                "5m",
+                Tz::UTC,
+                &MetricFilter::default(),
+                now - Duration::hours(1),
+                now + Duration::hours(1),
+            )
+            .await
+            .unwrap();
+        let total: i64 = buckets.iter().map(|b| b.query_count).sum();
+        assert_eq!(total, 10);
+        assert!(buckets
+            .iter()
+            .all(|b| b.success_count == Some(b.query_count)));
+
+        let daily = store
+            .get_aggregations(
+                ws.id,
+                "1d",
+                Tz::UTC,
+                &MetricFilter::default(),
+                now - Duration::days(2),
+                now + Duration::days(1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(daily.iter().map(|b| b.query_count).sum::<i64>(), 10);
+        assert!(daily
+            .iter()
+            .all(|b| b.bucket.time() == chrono::NaiveTime::MIN));
+
+        let kolkata: Tz = "Asia/Kolkata".parse().unwrap();
+        let local = store
+            .get_aggregations(
+                ws.id,
+                "1d",
+                kolkata,
+                &MetricFilter::default(),
+                now - Duration::days(2),
+                now + Duration::days(1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(local.iter().map(|b| b.query_count).sum::<i64>(), 10);
+        assert!(local
+            .iter()
+            .all(|b| b.bucket.with_timezone(&kolkata).time() == chrono::NaiveTime::MIN));
+    }
+
+    #[tokio::test]
+    async fn test_filter_by_service_tags_labels_and_kind() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let mut checkout = make_metric(ws.id, "SELECT 1", 10);
+        checkout.tags = vec!["checkout".into(), "eu".into()];
+        checkout.labels = HashMap::from([("env".into(), "prod".into())]);
+        let mut search = make_metric(ws.id, "SELECT 2", 20);
+        search.tags = vec!["search".into(), "eu".into()];
+        search.labels = HashMap::from([("env".into(), "staging".into())]);
+        search.service_id = checkout.service_id;
+        search.query_kind = Some(QueryKind::Select);
+        let mut other = make_metric(ws.id, "DELETE FROM carts", 30);
+        other.query_kind = Some(QueryKind::Delete);
+        store
+            .insert_metrics_batch(&[checkout.clone(), search, other.clone()])
+            .await
+            .unwrap();
+
+        let eu = MetricFilter {
+            service_id: Some(checkout.service_id),
+            tags: vec!["eu".into()],
+            ..MetricFilter::default()
+        };
+        assert_eq!(
+            store
+                .get_recent_metrics(ws.id, &eu, 10)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        let prod = MetricFilter {
+            labels: BTreeMap::from([("env".into(), "prod".into())]),
+            ..MetricFilter::default()
+        };
+        let recent = store.get_recent_metrics(ws.id, &prod, 10).await.unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].id, checkout.id);
+
+        let deletes = MetricFilter {
+            query_kind: Some(QueryKind::Delete),
+            ..MetricFilter::default()
+        };
+        let recent = store.get_recent_metrics(ws.id, &deletes, 10).await.unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].id, other.id);
+
+        let checkout_only = MetricFilter {
+            tags: vec!["checkout".into(), "eu".into()],
+            ..MetricFilter::default()
+        };
+        let now = Utc::now();
+        let buckets = store
+            .get_aggregations(
+                ws.id,
+                "5m",
+                Tz::UTC,
+                &checkout_only,
+                now - Duration::hours(1),
+                now + Duration::hours(1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].query_count, 1);
+        assert_eq!(buckets[0].max_duration_ms, Some(10));
+    }
+
+    #[tokio::test]
+    async fn test_embeddings_of_other_models_are_stale() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let old = EmbeddingModel {
+            name: "all-MiniLM-L6-v2".into(),
+            version: "1".into(),
+        };
+        let new = EmbeddingModel {
+            version: "2".into(),
+            ..old.clone()
+        };
+        let embedding = |query: &str| NewQueryEmbedding {
+            query_hash: query_hash(query),
+            sql_query: query.into(),
+            embedding: vec![1.0, 0.0],
+        };
+        store
+            .insert_query_embeddings(ws.id, &old, &[embedding("SELECT 1"), embedding("SELECT 2")])
+            .await
+            .unwrap();
+        store
+            .insert_query_embeddings(ws.id, &new, &[embedding("SELECT 2")])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get_stale_embedding_workspaces(&new).await.unwrap(),
+            [ws.id]
+        );
+        let stale = store.get_stale_embeddings(ws.id, &new, 10).await.unwrap();
+        assert_eq!(stale, [("SELECT 1".to_string(), query_hash("SELECT 1"))]);
+
+        // Search never compares vectors of different models
+        let similar = store
+            .search_similar_queries(ws.id, &new, &[1.0, 0.0], 10, 0.5, AnnTuning::default())
+            .await
+            .unwrap();
+        assert_eq!(similar.len(), 1);
+        assert_eq!(similar[0].sql_query, "SELECT 2");
+        assert!(store
+            .get_query_embedding(ws.id, &new, "SELECT 1")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            store
+                .get_query_embedding(ws.id, &new, " select  2")
+                .await
+                .unwrap(),
+            Some(vec![1.0, 0.0])
+        );
+
+        store
+            .insert_query_embeddings(ws.id, &new, &[embedding("SELECT 1")])
+            .await
+            .unwrap();
+        assert!(store
+            .get_stale_embedding_workspaces(&new)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_skipped_queries_wait_for_a_higher_limit() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let long = format!(
+            "SELECT * FROM t WHERE id IN ({})",
+            vec!["1"; 100].join(", ")
+        );
+        store
+            .insert_metrics_batch(&[
+                make_metric(ws.id, &long, 5),
+                make_metric(ws.id, "SELECT 1", 5),
+            ])
+            .await
+            .unwrap();
+        store
+            .insert_embedding_skips(
+                ws.id,
+                &[EmbeddingSkip {
+                    query_hash: query_hash(&long),
+                    query_bytes: long.len(),
+                }],
+            )
+            .await
+            .unwrap();
+
+        let unembedded = store.get_unembedded_queries(ws.id, 10, 64).await.unwrap();
+        assert_eq!(
+            unembedded,
+            [("SELECT 1".to_string(), query_hash("SELECT 1"))]
+        );
+        let unembedded = store
+            .get_unembedded_queries(ws.id, 10, long.len())
+            .await
+            .unwrap();
+        assert_eq!(unembedded.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unembedded_queries_hottest_first() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let metrics = [
+            make_metric(ws.id, "SELECT 2", 50),
+            make_metric(ws.id, "SELECT 1", 5),
+            make_metric(ws.id, "SELECT 3", 900),
+            make_metric(ws.id, "select  1", 5),
+            make_metric(ws.id, "SELECT 1", 5),
+        ];
+        store.insert_metrics_batch(&metrics).await.unwrap();
+
+        // Most-called first, then slowest
+        let texts = |queries: Vec<(String, String)>| -> Vec<String> {
+            queries.into_iter().map(|(text, _)| text).collect()
+        };
+        let unembedded = store.get_unembedded_queries(ws.id, 10, 1024).await.unwrap();
+        assert_eq!(texts(unembedded), ["SELECT 1", "SELECT 3", "SELECT 2"]);
+        let unembedded = store.get_unembedded_queries(ws.id, 2, 1024).await.unwrap();
+        assert_eq!(texts(unembedded), ["SELECT 1", "SELECT 3"]);
+    }
+
+    #[tokio::test]
+    async fn test_text_search_ranks_substring_matches_first() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let joined = make_metric(
+            ws.id,
+            "SELECT * FROM orders o JOIN users u ON u.id = o.user_id",
+            5,
+        );
+        let substring = make_metric(ws.id, "SELECT count(*) FROM orders join_log", 5);
+        let other = make_metric(ws.id, "SELECT * FROM users", 5);
+        store
+            .insert_metrics_batch(&[joined.clone(), substring.clone(), other])
+            .await
+            .unwrap();
+
+        let mut search = QueryTextSearch {
+            text: "orders join".into(),
+            from: None,
+            to: None,
+            service_id: None,
+            limit: 10,
+            offset: 0,
+        };
+        let found = store.search_query_text(ws.id, &search).await.unwrap();
+        let texts: Vec<&str> = found.iter().map(|e| e.query_text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [substring.query_text.as_str(), joined.query_text.as_str()]
+        );
+
+        // Words match as prefixes, in any order
+        search.text = "JOIN ord".into();
+        assert_eq!(
+            store.search_query_text(ws.id, &search).await.unwrap().len(),
+            2
+        );
+
+        search.service_id = Some(joined.service_id);
+        let found = store.search_query_text(ws.id, &search).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].query_text, joined.query_text);
+
+        search.service_id = None;
+        search.offset = 1;
+        assert_eq!(
+            store.search_query_text(ws.id, &search).await.unwrap().len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_metric_similarity_uses_the_stored_embedding() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let model = EmbeddingModel {
+            name: "all-MiniLM-L6-v2".into(),
+            version: "1".into(),
+        };
+        let slow = make_metric(ws.id, "SELECT * FROM orders WHERE id = $1", 900);
+        let mut failed = make_metric(ws.id, "SELECT * FROM orders WHERE user_id = $1", 100);
+        failed.status = QueryStatus::Failed;
+        store
+            .insert_metrics_batch(&[
+                slow.clone(),
+                failed,
+                make_metric(ws.id, "SELECT * FROM orders WHERE user_id = $1", 300),
+            ])
+            .await
+            .unwrap();
+
+        let since = Utc::now() - Duration::hours(1);
+        let pending = store
+            .get_metric_similarity(ws.id, slow.id, since, 10, 0.5)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(pending.model.is_none());
+        assert!(store
+            .get_metric_similarity(ws.id, Uuid::new_v4(), since, 10, 0.5)
+            .await
+            .unwrap()
+            .is_none());
+
+        let embedding = |query: &str, embedding: Vec<f32>| NewQueryEmbedding {
+            query_hash: query_hash(query),
+            sql_query: query.into(),
+            embedding,
+        };
+        store
+            .insert_query_embeddings(
+                ws.id,
+                &model,
+                &[
+                    embedding(&slow.query_text, vec![1.0, 0.0]),
+                    embedding("SELECT * FROM orders WHERE user_id = $1", vec![0.9, 0.1]),
+                    embedding("DELETE FROM sessions", vec![0.0, 1.0]),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let similarity = store
+            .get_metric_similarity(ws.id, slow.id, since, 10, 0.5)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(similarity.model, Some(model));
+        assert_eq!(similarity.similar.len(), 1);
+        let similar = &similarity.similar[0];
+        assert_eq!(
+            similar.query.sql_query,
+            "SELECT * FROM orders WHERE user_id = $1"
+        );
+        assert_eq!(similar.call_count, 2);
+        assert_eq!(similar.error_count, 1);
+        assert_eq!(similar.p50_duration_ms, Some(200));
+    }
+
+    #[tokio::test]
+    async fn test_lexical_search_matches_whole_identifiers() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let model = EmbeddingModel {
+            name: "all-MiniLM-L6-v2".into(),
+            version: "1".into(),
+        };
+        let embedding = |query: &str| NewQueryEmbedding {
+            query_hash: query_hash(query),
+            sql_query: query.into(),
+            embedding: vec![1.0, 0.0],
+        };
+        store
+            .insert_query_embeddings(
+                ws.id,
+                &model,
+                &[
+                    embedding("SELECT * FROM order_items"),
+                    embedding("SELECT * FROM orders ORDER BY items"),
+                    embedding("SELECT u.id FROM users u JOIN order_items i ON i.user_id = u.id"),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let terms = ["order_items".to_string(), "users".to_string()];
+        let matches = store
+            .search_lexical_queries(ws.id, &model, &[1.0, 0.0], &terms, 10)
+            .await
+            .unwrap();
+        let queries: Vec<&str> = matches.iter().map(|m| m.sql_query.as_str()).collect();
+        assert_eq!(
+            queries,
+            [
+                "SELECT u.id FROM users u JOIN order_items i ON i.user_id = u.id",
+                "SELECT * FROM order_items",
+            ]
+        );
+        assert!((matches[0].similarity - 1.0).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn test_erase_metrics_with_anomalies_and_embeddings() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let personal = make_metric(
+            ws.id,
+            "SELECT * FROM users WHERE email = 'Alice@example.com'",
+            5,
+        );
+        let other = make_metric(ws.id, "SELECT 1", 5);
+        store
+            .insert_metrics_batch(&[personal.clone(), other])
+            .await
+            .unwrap();
+        let model = EmbeddingModel {
+            name: "all-MiniLM-L6-v2".into(),
+            version: "1".into(),
+        };
+        store
+            .insert_query_embeddings(
+                ws.id,
+                &model,
+                &[NewQueryEmbedding {
+                    query_hash: query_hash(&personal.query_text),
+                    sql_query: personal.query_text.clone(),
+                    embedding: vec![1.0],
+                }],
+            )
+            .await
+            .unwrap();
+        store
+            .insert_anomalies(&[QueryAnomaly {
+                id: Uuid::now_v7(),
+                workspace_id: ws.id,
+                service_id: personal.service_id,
+                metric_id: personal.id,
+                fingerprint: "fp".into(),
+                query_text: personal.query_text.clone(),
+                duration_ms: 5,
+                mean_duration_ms: 1,
+                stddev_duration_ms: 1,
+                z_score: 4.0,
+                method: AnomalyMethod::ZScore,
+                severity: 50.0,
+                severity_level: SeverityLevel::High,
+                estimated_extra_ms: 4,
+                hints: Vec::new(),
+            }])
+            .await
+            .unwrap();
+        let cluster = NewQueryCluster {
+            label: Some("users".into()),
+            representative_hash: query_hash(&personal.query_text),
+            representative_query: personal.query_text.clone(),
+            members: vec![query_hash(&personal.query_text)],
+        };
+        store
+            .replace_query_clusters(ws.id, &model, Utc::now(), &[cluster])
+            .await
+            .unwrap();
+        let rejected = make_metric(
+            ws.id,
+            "UPDATE users SET name = 'x' WHERE email = 'alice@example.com'",
+            5,
+        );
+        store
+            .insert_dead_letters(&[rejected], "value too long")
+            .await
+            .unwrap();
+
+        let filter = MetricErasure {
+            query_contains: Some("alice@EXAMPLE.com".into()),
+            ..Default::default()
+        };
+        let erased = store.erase_metrics(ws.id, &filter).await.unwrap();
+        assert_eq!(
+            (
+                erased.metrics,
+                erased.embeddings,
+                erased.anomalies,
+                erased.catalog_entries,
+                erased.dead_letters
+            ),
+            (1, 1, 1, 1, 1)
+        );
+        // Neither the catalog nor the dead-letter queue can still show it
+        let search = QueryTextSearch {
+            text: "alice".into(),
+            from: None,
+            to: None,
+            service_id: None,
+            limit: 10,
+            offset: 0,
+        };
+        assert!(store
+            .search_query_text(ws.id, &search)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(store
+            .list_dead_letters(Some(ws.id), None, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        // Its cluster no longer shows the erased query
+        let clustered = store
+            .get_query_clusters(ws.id, Utc::now() - Duration::hours(1), 10)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(clustered.clusters.is_empty());
+        let remaining = store
+            .get_recent_metrics(ws.id, &MetricFilter::default(), 10)
+            .await
+            .unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].query_text, "SELECT 1");
+
+        // Other workspaces are untouched
+        let other_ws = store.add_workspace("other", "key-2");
+        store
+            .insert_metrics_batch(&[make_metric(other_ws.id, "SELECT 1", 5)])
+            .await
+            .unwrap();
+        let filter = MetricErasure {
+            service_id: Some(personal.service_id),
+            ..Default::default()
+        };
+        assert_eq!(
+            store.erase_metrics(ws.id, &filter).await.unwrap().metrics,
+            0
+        );
+        assert_eq!(
+            store
+                .get_recent_metrics(other_ws.id, &MetricFilter::default(), 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_anomaly_triage() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let metric = make_metric(ws.id, "SELECT 1", 5);
+        let anomaly = |id| QueryAnomaly {
+            id,
+            workspace_id: ws.id,
+            service_id: metric.service_id,
+            metric_id: metric.id,
+            fingerprint: "fp".into(),
+            query_text: metric.query_text.clone(),
+            duration_ms: 5,
+            mean_duration_ms: 1,
+            stddev_duration_ms: 1,
+            z_score: 4.0,
+            method: AnomalyMethod::ZScore,
+            severity: 50.0,
+            severity_level: SeverityLevel::High,
+            estimated_extra_ms: 4,
+            hints: Vec::new(),
+        };
+        let (first, second) = (Uuid::now_v7(), Uuid::now_v7());
+        store
+            .insert_anomalies(&[anomaly(first), anomaly(second)])
+            .await
+            .unwrap();
+        let since = Utc::now() - Duration::hours(1);
+        let with_status =
+            |status| store.get_anomalies(ws.id, since, SeverityLevel::Low, Some(status), 10);
+
+        let acked = store
+            .acknowledge_anomaly(ws.id, first, "oncall")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(acked.status, AnomalyStatus::Acknowledged);
+        assert_eq!(acked.acknowledged_by.as_deref(), Some("oncall"));
+        // Only open anomalies can be acknowledged
+        assert!(store
+            .acknowledge_anomaly(ws.id, first, "other")
+            .await
+            .unwrap()
+            .is_none());
+
+        // Resolving keeps who acknowledged it, or records the resolver
+        let resolved_at = Utc::now();
+        for (id, by) in [(first, "oncall"), (second, "lead")] {
+            let resolved = store
+                .resolve_anomaly(ws.id, id, "lead", resolved_at)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(resolved.status, AnomalyStatus::Resolved);
+            assert_eq!(resolved.acknowledged_by.as_deref(), Some(by));
+            assert_eq!(resolved.resolved_at, Some(resolved_at));
+        }
+        assert!(store
+            .resolve_anomaly(ws.id, first, "lead", resolved_at)
+            .await
+            .unwrap()
+            .is_none());
+
+        assert!(with_status(AnomalyStatus::Open).await.unwrap().is_empty());
+        assert_eq!(with_status(AnomalyStatus::Resolved).await.unwrap().len(), 2);
+
+        // Other workspaces can't triage them
+        let other = store.add_workspace("other", "key-2");
+        let third = Uuid::now_v7();
+        store.insert_anomalies(&[anomaly(third)]).await.unwrap();
+        assert!(store
+            .acknowledge_anomaly(other.id, third, "oncall")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_usage_metered_per_workspace() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let other = store.add_workspace("other", "other-key");
+        let batch: Vec<_> = (0..4).map(|_| make_metric(ws.id, "SELECT 1", 10)).collect();
+        store.insert_metrics_batch(&batch).await.unwrap();
+        store
+            .insert_metrics_batch(&[make_metric(other.id, "SELECT 2", 10)])
+            .await
+            .unwrap();
+
+        let today = Utc::now().date_naive();
+        let days = store.get_usage(ws.id, today, today).await.unwrap();
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].metric_count, 4);
+        assert_eq!(
+            days[0].stored_bytes,
+            batch.iter().map(usage::estimated_bytes).sum::<i64>()
+        );
+        let yesterday = today - Duration::days(1);
+        assert!(store
+            .get_usage(ws.id, yesterday, yesterday)
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert!(store.set_monthly_quota(ws.id, Some(1000)).await.unwrap());
+        assert_eq!(store.get_monthly_quota(ws.id).await.unwrap(), Some(1000));
+        assert_eq!(store.get_monthly_quota(other.id).await.unwrap(), None);
+        assert!(!store.set_monthly_quota(Uuid::new_v4(), None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_audit_log_filters() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let now = Utc::now();
+        for (i, action) in [
+            AuditAction::ApiKeyCreate,
+            AuditAction::ServiceDelete,
+            AuditAction::ApiKeyCreate,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let entry = AuditEntry {
+                id: Uuid::new_v4(),
+                workspace_id: ws.id,
+                action,
+                actor_key_id: Uuid::new_v4(),
+                actor_name: "deploy-bot".into(),
+                target: Some(i.to_string()),
+                payload: serde_json::json!({}),
+                created_at: now + Duration::seconds(i as i64),
+            };
+            store.insert_audit_entry(&entry).await.unwrap();
+            assert_eq!(action.as_str().parse::<AuditAction>(), Ok(action));
+        }
+
+        let from = now - Duration::hours(1);
+        let to = now + Duration::hours(1);
+        let keys = store
+            .list_audit_entries(ws.id, from, to, Some(AuditAction::ApiKeyCreate), 10)
+            .await
+            .unwrap();
+        let targets: Vec<_> = keys.iter().filter_map(|e| e.target.as_deref()).collect();
+        assert_eq!(targets, ["2", "0"]);
+        let all = store
+            .list_audit_entries(ws.id, from, to, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 3);
+        assert!(store
+            .list_audit_entries(Uuid::new_v4(), from, to, None, 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_muted_queries_excluded_from_anomaly_inputs() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        store
+            .insert_metrics_batch(&[
+                make_metric(ws.id, "SELECT slow_etl()", 60_000),
+                make_metric(ws.id, "SELECT 1", 5),
+            ])
+            .await
+            .unwrap();
+
+        let mute = QueryMute {
+            fingerprint: fingerprint("SELECT slow_etl()", SqlDialect::Generic),
+            reason: Some("nightly ETL".into()),
+            expires_at: None,
+            created_at: Utc::now(),
+        };
+        store.upsert_mute(ws.id, &mute).await.unwrap();
+
+        let limits = HashMap::from([(ws.id, 1000)]);
+        let stats = store.get_metrics_stats(&limits).await.unwrap();
+        assert_eq!(stats[&ws.id].count, 1);
+        let threshold = AnomalyThreshold {
+            duration_ms: 1000,
+            since_seconds: 60,
+        };
+        let thresholds = HashMap::from([(ws.id, threshold)]);
+        let slow = store
+            .get_recent_metrics_for_anomaly(&thresholds)
+            .await
+            .unwrap();
+        assert!(slow.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dead_letters_failed_again_are_kept() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let fixed = make_metric(ws.id, "SELECT 1", 5);
+        let broken = make_metric(ws.id, "SELECT 2", 5);
+        store
+            .insert_dead_letters(&[fixed.clone(), broken.clone()], "value too long")
+            .await
+            .unwrap();
+        let read: Vec<(Uuid, i32)> = store
+            .list_dead_letters(Some(ws.id), None, 10)
+            .await
+            .unwrap()
+            .iter()
+            .map(|l| (l.metric_id, l.attempts))
+            .collect();
+
+        // Same clock reading, but the attempt count moved on
+        store
+            .insert_dead_letters(std::slice::from_ref(&broken), "value too long")
+            .await
+            .unwrap();
+        assert_eq!(store.delete_dead_letters(&read).await.unwrap(), 1);
+        let left = store
+            .list_dead_letters(Some(ws.id), None, 10)
+            .await
+            .unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].metric_id, broken.id);
+        assert_eq!(left[0].attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_muted_queries_excluded_from_top_lists() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let mut batch = vec![
+            make_metric(ws.id, "SELECT slow_etl()", 60_000),
+            make_metric(ws.id, "SELECT 1", 5),
+        ];
+        let service_id = batch[0].service_id;
+        batch[1].service_id = service_id;
+        store.insert_metrics_batch(&batch).await.unwrap();
+
+        let mute = QueryMute {
+            fingerprint: fingerprint("SELECT slow_etl()", SqlDialect::Generic),
+            reason: Some("nightly ETL".into()),
+            expires_at: None,
+            created_at: Utc::now(),
+        };
+        store.upsert_mute(ws.id, &mute).await.unwrap();
+
+        let now = Utc::now();
+        let (from, to) = (now - Duration::hours(1), now + Duration::hours(1));
+        let top = store
+            .get_top_fingerprints(ws.id, service_id, None, from, to, 10)
+            .await
+            .unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].query_text, "SELECT 1");
+
+        let query = FingerprintStatsQuery {
+            from,
+            to,
+            service_id: None,
+            query_kind: None,
+            sort: QuerySort::TotalTime,
+            limit: 10,
+            offset: 0,
+        };
+        let stats = store.get_fingerprint_stats(ws.id, &query).await.unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].query_text, "SELECT 1");
+    }
+
+    #[tokio::test]
+    async fn test_anomaly_inputs_grouped_by_workspace() {
+        let store = MemoryStore::new();
+        let busy = store.add_workspace("busy", "key-busy");
+        let quiet = store.add_workspace("quiet", "key-quiet");
+        let idle = store.add_workspace("idle", "key-idle");
+        store
+            .insert_metrics_batch(&[
+                make_metric(busy.id, "SELECT 1", 10),
+                make_metric(busy.id, "SELECT 2", 30),
+                make_metric(busy.id, "SELECT 3", 500),
+                make_metric(quiet.id, "SELECT 4", 500),
+            ])
+            .await
+            .unwrap();
+
+        // Only as many recent metrics as each workspace asks for
+        let limits = HashMap::from([(busy.id, 2), (quiet.id, 1000), (idle.id, 1000)]);
+        let stats = store.get_metrics_stats(&limits).await.unwrap();
+        assert_eq!(stats.len(), 2);
+        assert!(!stats.contains_key(&idle.id));
+        assert_eq!(stats[&busy.id].count, 2);
+        assert_eq!(stats[&busy.id].mean, 265.0);
+        assert_eq!(stats[&quiet.id].count, 1);
+        assert_eq!(stats[&quiet.id].stddev, 0.0);
+
+        // Each workspace is held to its own threshold
+        let threshold = |duration_ms| AnomalyThreshold {
+            duration_ms,
+            since_seconds: 60,
+        };
+        let thresholds = HashMap::from([(busy.id, threshold(100)), (quiet.id, threshold(1000))]);
+        let slow = store
+            .get_recent_metrics_for_anomaly(&thresholds)
+            .await
+            .unwrap();
+        assert_eq!(slow.len(), 1);
+        assert_eq!(slow[0].workspace_id, busy.id);
+        assert_eq!(slow[0].duration_ms, 500);
+    }
+
+    #[tokio::test]
+    async fn test_last_ingest_counts_accepted_metrics() {
+        let store = MemoryStore::new();
+        let (busy, dropping) = (Uuid::new_v4(), Uuid::new_v4());
+        let start: DateTime<Utc> = "2026-01-01T12:00:00Z".parse().unwrap();
+        let minute = |m| start + Duration::minutes(m);
+        let row = |m, accepted, dropped| IngestionMinute {
+            minute: minute(m),
+            accepted,
+            dropped,
+            deduplicated: 0,
+        };
+        store
+            .record_ingestion_stats(&[
+                (busy, row(1, 10, 0)),
+                (busy, row(3, 5, 0)),
+                // Only dropped metrics aren't ingest
+                (dropping, row(2, 0, 7)),
+            ])
+            .await
+            .unwrap();
+
+        let last = store.get_last_ingest(minute(0)).await.unwrap();
+        assert_eq!(last, HashMap::from([(busy, minute(3))]));
+        assert!(store.get_last_ingest(minute(4)).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_service_top_lists() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let mut batch = vec![
+            make_metric(ws.id, "SELECT * FROM a WHERE id IN (1, 2)", 10),
+            make_metric(ws.id, "select * from a where id in (3)", 10),
+            make_metric(ws.id, "SELECT * FROM b", 500),
+            make_metric(ws.id, "UPDATE c SET x = 1", 5),
+            make_metric(ws.id, "update c  set x = 1", 5),
+        ];
+        let service_id = batch[0].service_id;
+        for metric in batch.iter_mut() {
+            metric.service_id = service_id;
+        }
+        for metric in batch.iter_mut().skip(3) {
+            metric.status = QueryStatus::Failed;
+            metric.error_message = Some("deadlock detected".into());
+        }
+        // Another service's traffic stays out of the overview
+        batch.push(make_metric(ws.id, "SELECT * FROM b", 9_000));
+        store.insert_metrics_batch(&batch).await.unwrap();
+
+        let now = Utc::now();
+        let (from, to) = (now - Duration::hours(1), now + Duration::hours(1));
+        let top = store
+            .get_top_fingerprints(ws.id, service_id, None, from, to, 10)
+            .await
+            .unwrap();
+        assert_eq!(top.len(), 3);
+        assert_eq!(top[0].total_duration_ms, 500);
+        assert_eq!(top[1].call_count, 2);
+        assert_eq!(top[1].failed_count, 0);
+
+        let errors = store
+            .get_top_errors(ws.id, service_id, None, from, to, 10)
+            .await
+            .unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].count, 2);
+        assert_eq!(
+            errors[0].error_message.as_deref(),
+            Some("deadlock detected")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_endpoints_filter_by_kind() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let mut select = make_metric(ws.id, "SELECT * FROM a", 10);
+        select.query_kind = Some(QueryKind::Select);
+        let mut update = make_metric(ws.id, "UPDATE b SET x = 1", 500);
+        update.query_kind = Some(QueryKind::Update);
+        update.service_id = select.service_id;
+        update.status = QueryStatus::Failed;
+        update.error_message = Some("deadlock detected".into());
+        let service_id = select.service_id;
+        store
+            .insert_metrics_batch(&[select.clone(), update.clone()])
+            .await
+            .unwrap();
+        store
+            .insert_anomalies(&[QueryAnomaly {
+                id: Uuid::now_v7(),
+                workspace_id: ws.id,
+                service_id,
+                metric_id: update.id,
+                fingerprint: metric_fingerprint(&update),
+                query_text: update.query_text.clone(),
+                duration_ms: 500,
+                mean_duration_ms: 10,
+                stddev_duration_ms: 1,
+                z_score: 490.0,
+                method: AnomalyMethod::ZScore,
+                severity: 90.0,
+                severity_level: SeverityLevel::Critical,
+                estimated_extra_ms: 490,
+                hints: Vec::new(),
+            }])
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        let (from, to) = (now - Duration::hours(1), now + Duration::hours(1));
+        let selects = Some(QueryKind::Select);
+        let stats = store
+            .get_fingerprint_stats(
+                ws.id,
+                &FingerprintStatsQuery {
+                    from,
+                    to,
+                    service_id: None,
+                    query_kind: selects,
+                    sort: QuerySort::TotalTime,
+                    limit: 10,
+                    offset: 0,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].total_duration_ms, 10);
+
+        let totals = store
+            .get_statement_totals(ws.id, None, Some(QueryKind::Update), from, to, 10)
+            .await
+            .unwrap();
+        assert_eq!(totals.len(), 1);
+        assert_eq!(totals[0].query_text, update.query_text);
+
+        let durations = store
+            .get_duration_stats(
+                ws.id,
+                &DurationStatsQuery {
+                    from,
+                    to,
+                    group_by: DurationGroupBy::Service,
+                    service_id: None,
+                    query_kind: selects,
+                    limit: 10,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(durations[0].max_ms, 10);
+
+        let errors = store
+            .get_error_groups(
+                ws.id,
+                &ErrorGroupsQuery {
+                    from,
+                    to,
+                    service_id: None,
+                    query_kind: selects,
+                    limit: 10,
+                },
+            )
+            .await
+            .unwrap();
+        assert!(errors.is_empty());
+
+        let top = store
+            .get_top_fingerprints(ws.id, service_id, selects, from, to, 10)
+            .await
+            .unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].total_duration_ms, 10);
+        let top_errors = store
+            .get_top_errors(ws.id, service_id, selects, from, to, 10)
+            .await
+            .unwrap();
+        assert!(top_errors.is_empty());
+        let anomalies = store
+            .get_service_anomalies(ws.id, service_id, selects, from, to, 10)
+            .await
+            .unwrap();
+        assert!(anomalies.is_empty());
+        let anomalies = store
+            .get_service_anomalies(ws.id, service_id, Some(QueryKind::Update), from, to, 10)
+            .await
+            .unwrap();
+        assert_eq!(anomalies.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_service_anomalies_within_range() {
+        let clock = Clock::simulated("2026-01-01T00:00:00Z".parse().unwrap());
+        let store = MemoryStore::with_clock(clock.clone());
+        let ws = store.add_workspace("test", "key");
+        let metric = make_metric(ws.id, "SELECT 1", 5);
+        let anomaly = || QueryAnomaly {
+            id: Uuid::now_v7(),
+            workspace_id: ws.id,
+            service_id: metric.service_id,
+            metric_id: metric.id,
+            fingerprint: "fp".into(),
+            query_text: metric.query_text.clone(),
+            duration_ms: 5,
+            mean_duration_ms: 1,
+            stddev_duration_ms: 1,
+            z_score: 4.0,
+            method: AnomalyMethod::ZScore,
+            severity: 50.0,
+            severity_level: SeverityLevel::High,
+            estimated_extra_ms: 4,
+            hints: Vec::new(),
+        };
+        let from = clock.now();
+        store.insert_anomalies(&[anomaly()]).await.unwrap();
+        clock.advance(Duration::hours(2));
+        store.insert_anomalies(&[anomaly()]).await.unwrap();
+
+        // Anomalies detected after the range are left out
+        let to = from + Duration::hours(1);
+        let anomalies = store
+            .get_service_anomalies(ws.id, metric.service_id, None, from, to, 10)
+            .await
+            .unwrap();
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].detected_at, from);
+    }
+
+    #[tokio::test]
+    async fn test_fingerprint_stats_sorted_and_paged() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let mut batch: Vec<QueryMetric> = (1..=4)
+            .map(|i| make_metric(ws.id, &format!("SELECT * FROM a WHERE id = {}", i), i * 10))
+            .collect();
+        batch.push(make_metric(ws.id, "SELECT * FROM b", 500));
+        batch.push(make_metric(ws.id, "UPDATE c SET x = 1", 5));
+        batch[5].status = QueryStatus::Timeout;
+        store.insert_metrics_batch(&batch).await.unwrap();
+
+        let now = Utc::now();
+        let mut query = FingerprintStatsQuery {
+            from: now - Duration::hours(1),
+            to: now + Duration::hours(1),
+            service_id: None,
+            query_kind: None,
+            sort: QuerySort::TotalTime,
+            limit: 2,
+            offset: 0,
+        };
+        let page = store.get_fingerprint_stats(ws.id, &query).await.unwrap();
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].total_duration_ms, 500);
+        assert_eq!(page[1].call_count, 4);
+        assert_eq!(page[1].p50_duration_ms, 25);
+        assert_eq!(page[1].p99_duration_ms, 40);
+
+        query.offset = 2;
+        let rest = store.get_fingerprint_stats(ws.id, &query).await.unwrap();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].error_rate, 1.0);
+
+        query.sort = QuerySort::Calls;
+        query.offset = 0;
+        let by_calls = store.get_fingerprint_stats(ws.id, &query).await.unwrap();
+        assert_eq!(by_calls[0].call_count, 4);
+    }
+
+    #[tokio::test]
+    async fn test_errors_grouped_by_normalized_message() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let failure = |query: &str, message: &str| {
+            let mut metric = make_metric(ws.id, query, 10);
+            metric.status = QueryStatus::Failed;
+            metric.error_message = Some(message.into());
+            metric
+        };
+        let batch = vec![
+            failure(
+                "INSERT INTO users (email) VALUES ($1)",
+                "duplicate key value violates unique constraint \"users_email_key\" Key (email)=(a@example.com)",
+            ),
+            failure(
+                "INSERT INTO users (email) VALUES ($1)",
+                "duplicate key value violates unique constraint \"users_email_key\" Key (email)=(b@example.com)",
+            ),
+            failure(
+                "UPDATE users SET email = $1",
+                "duplicate key value violates unique constraint \"users_email_key\" Key (email)=(c@example.com)",
+            ),
+            failure("SELECT * FROM orders", "deadlock detected: process 12"),
+            make_metric(ws.id, "SELECT * FROM orders", 10),
+        ];
+        store.insert_metrics_batch(&batch).await.unwrap();
+
+        let now = Utc::now();
+        let query = ErrorGroupsQuery {
+            from: now - Duration::hours(1),
+            to: now + Duration::hours(1),
+            service_id: None,
+            query_kind: None,
+            limit: 10,
+        };
+        let groups = store.get_error_groups(ws.id, &query).await.unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].count, 3);
+        assert_eq!(groups[0].previous_count, 0);
+        assert_eq!(
+            groups[0].message,
+            "duplicate key value violates unique constraint \"users_email_key\" Key (email)=(?)"
+        );
+        assert_eq!(groups[0].query_count, 2);
+        assert_eq!(groups[0].fingerprints[0], metric_fingerprint(&batch[0]));
+        assert_eq!(groups[1].message, "deadlock detected: process ?");
+    }
+
+    #[tokio::test]
+    async fn test_duration_stats_per_service() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let (api, worker) = (Uuid::new_v4(), Uuid::new_v4());
+        let metric = |service_id, duration_ms| {
+            let mut metric = make_metric(ws.id, "SELECT 1", duration_ms);
+            metric.service_id = service_id;
+            metric
+        };
+        let slow = metric(api, 500);
+        let mut batch = vec![slow.clone()];
+        batch.extend([10, 12, 11, 13].map(|ms| metric(api, ms)));
+        batch.extend([20, 20, 20].map(|ms| metric(worker, ms)));
+        store.insert_metrics_batch(&batch).await.unwrap();
+
+        let now = Utc::now();
+        let query = DurationStatsQuery {
+            from: now - Duration::hours(1),
+            to: now + Duration::hours(1),
+            group_by: DurationGroupBy::Service,
+            service_id: None,
+            query_kind: None,
+            limit: 10,
+        };
+        let stats = store.get_duration_stats(ws.id, &query).await.unwrap();
+        assert_eq!(stats.len(), 2);
+
+        // Median 12, absolute deviations 0, 1, 1, 2, 488
+        assert_eq!(stats[0].group, api.to_string());
+        assert_eq!(stats[0].query_count, 5);
+        assert_eq!(stats[0].median_ms, 12.0);
+        assert_eq!(stats[0].mad_ms, 1.0);
+        assert_eq!(stats[0].max_ms, 500);
+        assert_eq!(stats[0].max_metric_id, slow.id);
+        assert!(stats[0].max_score.unwrap() > 3.5);
+
+        // No spread, so no score
+        assert_eq!(stats[1].group, worker.to_string());
+        assert_eq!(stats[1].mad_ms, 0.0);
+        assert_eq!(stats[1].max_score, None);
+    }
+
+    #[tokio::test]
+    async fn test_services_registered_on_ingest() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let mut named = make_metric(ws.id, "SELECT 1", 1);
+        named.service_name = Some("checkout".into());
+        // Same name from another service ID is not registered
+        let mut clash = make_metric(ws.id, "SELECT 1", 1);
+        clash.service_name = Some("checkout".into());
+        let unnamed = make_metric(ws.id, "SELECT 1", 1);
+        store
+            .insert_metrics_batch(&[named.clone(), clash, unnamed.clone()])
+            .await
+            .unwrap();
+
+        let services = store.list_services(ws.id).await.unwrap();
+        assert_eq!(services.len(), 2);
+        let service = store
+            .get_service(ws.id, named.service_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(service.name, "checkout");
+        assert!(service.last_seen_at.is_some());
+        let service = store
+            .get_service(ws.id, unnamed.service_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(service.name, unnamed.service_id.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_new_shapes_after_learning_period() {
+        let clock = Clock::simulated("2026-01-01T00:00:00Z".parse().unwrap());
+        let store = MemoryStore::with_clock(clock.clone());
+        let ws = store.add_workspace("test", "key");
+        let at_now = |query: &str| QueryMetric {
+            started_at: clock.now(),
+            ..make_metric(ws.id, query, 1)
+        };
+
+        // A new workspace's first queries are its baseline
+        let first = store
+            .insert_metrics_batch(&[at_now("SELECT 1"), at_now("SELECT 2")])
+            .await
+            .unwrap();
+        assert!(first.new_shapes.is_empty());
+
+        clock.advance(Duration::days(2));
+        let later = store
+            .insert_metrics_batch(&[
+                at_now("SELECT 1"),
+                at_now("DELETE FROM users WHERE 1 = 1"),
+                at_now("DELETE FROM users WHERE 2 = 2"),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(later.new_shapes.len(), 1);
+        assert_eq!(later.new_shapes[0].workspace_id, ws.id);
+        assert_eq!(
+            later.new_shapes[0].query_text,
+            "DELETE FROM users WHERE 1 = 1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_api_keys_scoped_and_expiring() {
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let primary = store.verify_api_key("key").await.unwrap();
+        assert_eq!(primary.workspace_id, ws.id);
+        assert!(primary.has_scope(ApiScope::Admin));
+
+        let mut key = ApiKey {
+            id: Uuid::new_v4(),
+            workspace_id: ws.id,
+            name: "dashboard".into(),
+            scopes: vec![ApiScope::Read],
+            expires_at: Some(Utc::now() + Duration::hours(1)),
+            created_at: Utc::now(),
+        };
+        store.create_api_key(&key, "dashboard-key").await.unwrap();
+        let verified = store.verify_api_key("dashboard-key").await.unwrap();
+        assert!(verified.has_scope(ApiScope::Read));
+        assert!(!verified.has_scope(ApiScope::Ingest));
+        assert_eq!(store.list_api_keys(ws.id).await.unwrap().len(), 2);
+
+        key.id = Uuid::new_v4();
+        key.expires_at = Some(Utc::now() - Duration::seconds(1));
+        store.create_api_key(&key, "expired-key").await.unwrap();
+        assert!(matches!(
+            store.verify_api_key("expired-key").await,
+            Err(AppError::Unauthorized(_))
+        ));
+
+        assert!(store.delete_api_key(ws.id, verified.id).await.unwrap());
+        assert!(store.verify_api_key("dashboard-key").await.is_err());
+    }
+}
@@ -0,0 +1,813 @@
+//! Storage backend abstraction
+//!
+//! Route handlers and background tasks depend on [`MetricsStore`] rather than on
+//! a concrete database, so alternative backends can be plugged in (and handlers
+//! can be exercised without a live TimescaleDB).
+
+pub mod cache;
+#[cfg(feature = "memory-store")]
+pub mod memory;
+pub mod resilient;
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::db::{
+    AggregatedMetric, AlertRuleState, AnnTuning, AnomalyContext, AnomalyRecord, AnomalyThreshold,
+    BatchInsert, CatalogEntry, DeadLetter, DeadLetterReason, DurationStats, DurationStatsQuery,
+    EmbeddedQuery, EmbeddingModel, EmbeddingSkip, ErasureCounts, ErrorGroup, ErrorGroupsQuery,
+    ErrorSummary, FingerprintStats, FingerprintStatsQuery, FingerprintSummary, IngestionMinute,
+    MetricErasure, MetricFilter, MetricSimilarity, MetricsStats, NewQueryCluster,
+    NewQueryEmbedding, QueryAnomaly, QueryClusters, QueryTextSearch, ServiceThroughput,
+    SimilarQuery, SloStatus, StatementTotals, ThroughputDrop, UsageDay,
+};
+use crate::error::Result;
+use crate::models::{
+    AlertDelivery, AlertDestination, AlertRule, Annotation, AnomalyMethod, AnomalyOverrides,
+    AnomalyStatus, ApiKey, AuditAction, AuditEntry, JsonCasing, LifecycleAction, LifecycleStage,
+    QueryKind, QueryMetric, QueryMute, QueryOwnership, Seasonality, Service, Slo,
+};
+use crate::services::severity::SeverityLevel;
+use crate::services::slo::SloCounts;
+
+/// Connection pool utilization snapshot
+#[derive(Debug, Clone, Copy)]
+pub struct PoolStats {
+    /// Open connections (idle + in use)
+    pub size: u32,
+    /// Open connections not currently in use
+    pub idle: u32,
+    /// Configured maximum
+    pub max: u32,
+}
+
+/// Storage backend for metrics, aggregations, anomalies, and embeddings
+#[async_trait]
+pub trait MetricsStore: Send + Sync {
+    /// Check that the backend is reachable
+    async fn ping(&self) -> Result<()>;
+
+    /// Connection pool utilization, for backends that pool connections
+    fn pool_stats(&self) -> Option<PoolStats> {
+        None
+    }
+
+    /// Read-replica pool utilization, when reads are routed to a separate pool
+    fn read_pool_stats(&self) -> Option<PoolStats> {
+        None
+    }
+
+    /// `(hits, misses)` of the aggregation cache, when one is configured
+    fn aggregation_cache_stats(&self) -> Option<(u64, u64)> {
+        None
+    }
+
+    /// Verify an API key, returning it if it exists and hasn't expired
+    async fn verify_api_key(&self, api_key: &str) -> Result<ApiKey>;
+
+    /// Get a workspace's JSON casing setting (`None` if the workspace doesn't exist)
+    async fn get_json_casing(&self, workspace_id: Uuid) -> Result<Option<JsonCasing>>;
+
+    /// Set a workspace's JSON casing, returning whether the workspace exists
+    async fn set_json_casing(&self, workspace_id: Uuid, casing: JsonCasing) -> Result<bool>;
+
+    /// Get a workspace's monthly metric quota (`None` if unlimited or the
+    /// workspace doesn't exist)
+    async fn get_monthly_quota(&self, workspace_id: Uuid) -> Result<Option<i64>>;
+
+    /// Set or clear a workspace's monthly metric quota, returning whether the
+    /// workspace exists
+    async fn set_monthly_quota(&self, workspace_id: Uuid, quota: Option<i64>) -> Result<bool>;
+
+    /// Get whether a workspace redacts literals in ingested query text
+    /// (`None` if the workspace doesn't exist)
+    async fn get_literal_redaction(&self, workspace_id: Uuid) -> Result<Option<bool>>;
+
+    /// Turn literal redaction on or off, returning whether the workspace exists
+    async fn set_literal_redaction(&self, workspace_id: Uuid, enabled: bool) -> Result<bool>;
+
+    /// Get a workspace's anomaly detection method (`None` if the workspace
+    /// doesn't exist)
+    async fn get_anomaly_method(&self, workspace_id: Uuid) -> Result<Option<AnomalyMethod>>;
+
+    /// Set a workspace's anomaly detection method, returning whether the
+    /// workspace exists
+    async fn set_anomaly_method(&self, workspace_id: Uuid, method: AnomalyMethod) -> Result<bool>;
+
+    /// Get the anomaly detection settings the given workspaces override;
+    /// workspaces that don't exist are left out
+    async fn get_anomaly_overrides(
+        &self,
+        workspace_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, AnomalyOverrides>>;
+
+    /// Replace a workspace's anomaly detection overrides, returning whether
+    /// the workspace exists
+    async fn set_anomaly_overrides(
+        &self,
+        workspace_id: Uuid,
+        overrides: &AnomalyOverrides,
+    ) -> Result<bool>;
+
+    // =========================================================================
+    // API KEYS
+    // =========================================================================
+
+    /// Store a new API key under its secret
+    async fn create_api_key(&self, key: &ApiKey, secret: &str) -> Result<()>;
+
+    /// List a workspace's API keys, newest first
+    async fn list_api_keys(&self, workspace_id: Uuid) -> Result<Vec<ApiKey>>;
+
+    /// Revoke an API key, returning whether it existed
+    async fn delete_api_key(&self, workspace_id: Uuid, key_id: Uuid) -> Result<bool>;
+
+    // =========================================================================
+    // METRICS
+    // =========================================================================
+
+    /// Batch insert metrics, returning the number of rows stored
+    ///
+    /// Also registers services seen for the first time and updates their last-seen time.
+    async fn insert_metrics_batch(&self, metrics: &[QueryMetric]) -> Result<BatchInsert>;
+
+    /// Get recent metrics for a workspace
+    async fn get_recent_metrics(
+        &self,
+        workspace_id: Uuid,
+        filter: &MetricFilter,
+        limit: i64,
+    ) -> Result<Vec<QueryMetric>>;
+
+    /// Get aggregated metrics for a window ("5s", "1m", "5m", "1h", "1d"),
+    /// bucketed by local time in `tz`
+    async fn get_aggregations(
+        &self,
+        workspace_id: Uuid,
+        window: &str,
+        tz: Tz,
+        filter: &MetricFilter,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<AggregatedMetric>>;
+
+    /// Delete raw metrics older than the given number of days
+    async fn prune_old_metrics(&self, older_than_days: i32) -> Result<u64>;
+
+    /// Erase a workspace's metrics matching `filter`, with the anomalies raised
+    /// for them, the embeddings, catalog entries, and rollups of their query
+    /// text, and the dead letters matching `filter`
+    async fn erase_metrics(
+        &self,
+        workspace_id: Uuid,
+        filter: &MetricErasure,
+    ) -> Result<ErasureCounts>;
+
+    /// Recompute hourly fingerprint rollups for buckets starting at or after `since`
+    async fn refresh_fingerprint_rollups(&self, since: DateTime<Utc>) -> Result<u64>;
+
+    /// Get catalog entries for fingerprints first seen at or after `since`
+    async fn get_new_queries(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<CatalogEntry>>;
+
+    /// Find catalog entries by text, substring matches first, then the best
+    /// word matches, then the most recently seen
+    async fn search_query_text(
+        &self,
+        workspace_id: Uuid,
+        search: &QueryTextSearch,
+    ) -> Result<Vec<CatalogEntry>>;
+
+    /// Get per-fingerprint execution statistics, sorted and paged as `query` says
+    async fn get_fingerprint_stats(
+        &self,
+        workspace_id: Uuid,
+        query: &FingerprintStatsQuery,
+    ) -> Result<Vec<FingerprintStats>>;
+
+    /// Get execution totals per fingerprint and service, most called first
+    async fn get_statement_totals(
+        &self,
+        workspace_id: Uuid,
+        service_id: Option<Uuid>,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<StatementTotals>>;
+
+    /// Get robust duration statistics per service or fingerprint, slowest
+    /// maximum first
+    async fn get_duration_stats(
+        &self,
+        workspace_id: Uuid,
+        query: &DurationStatsQuery,
+    ) -> Result<Vec<DurationStats>>;
+
+    /// Get failures grouped by error fingerprint, most frequent first
+    async fn get_error_groups(
+        &self,
+        workspace_id: Uuid,
+        query: &ErrorGroupsQuery,
+    ) -> Result<Vec<ErrorGroup>>;
+
+    // =========================================================================
+    // DEAD LETTERS
+    // =========================================================================
+
+    /// Record metrics that could not be inserted, bumping attempts for known ones
+    async fn insert_dead_letters(&self, metrics: &[QueryMetric], error: &str) -> Result<()>;
+
+    /// List dead letters, optionally for one workspace or failure reason,
+    /// most recently failed first
+    async fn list_dead_letters(
+        &self,
+        workspace_id: Option<Uuid>,
+        reason: Option<DeadLetterReason>,
+        limit: i64,
+    ) -> Result<Vec<DeadLetter>>;
+
+    /// Get dead letters by metric ID
+    async fn get_dead_letters(&self, metric_ids: &[Uuid]) -> Result<Vec<DeadLetter>>;
+
+    /// Delete dead letters by metric ID, each only while its attempt count
+    /// is still the one given, so rows that failed again since they were
+    /// read are kept
+    async fn delete_dead_letters(&self, letters: &[(Uuid, i32)]) -> Result<u64>;
+
+    // =========================================================================
+    // SERVICE REGISTRY
+    // =========================================================================
+
+    /// List registered services, most recently seen first
+    async fn list_services(&self, workspace_id: Uuid) -> Result<Vec<Service>>;
+
+    /// Get a registered service
+    async fn get_service(&self, workspace_id: Uuid, service_id: Uuid) -> Result<Option<Service>>;
+
+    /// Register a service; fails with a conflict if the ID or name is taken
+    async fn create_service(&self, service: &Service) -> Result<Service>;
+
+    /// Rename or re-describe a service, returning `None` if it doesn't exist
+    async fn update_service(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<Option<Service>>;
+
+    /// Delete a service registration, returning whether it existed
+    async fn delete_service(&self, workspace_id: Uuid, service_id: Uuid) -> Result<bool>;
+
+    // =========================================================================
+    // SERVICE OVERVIEW
+    // =========================================================================
+
+    /// Get the fingerprints with the most total execution time for a service
+    async fn get_top_fingerprints(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<FingerprintSummary>>;
+
+    /// Get the most frequent failed or timed-out queries for a service
+    async fn get_top_errors(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ErrorSummary>>;
+
+    /// Get the most recent anomalies for a service detected in the range
+    async fn get_service_anomalies(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<AnomalyRecord>>;
+
+    // =========================================================================
+    // OWNERSHIP
+    // =========================================================================
+
+    /// Create or replace the ownership annotation for a fingerprint
+    async fn upsert_ownership(
+        &self,
+        workspace_id: Uuid,
+        ownership: &QueryOwnership,
+    ) -> Result<QueryOwnership>;
+
+    /// Get the ownership annotation for a fingerprint
+    async fn get_ownership(
+        &self,
+        workspace_id: Uuid,
+        fingerprint: &str,
+    ) -> Result<Option<QueryOwnership>>;
+
+    /// List all ownership annotations in a workspace
+    async fn list_ownership(&self, workspace_id: Uuid) -> Result<Vec<QueryOwnership>>;
+
+    /// Delete the ownership annotation for a fingerprint, returning whether it existed
+    async fn delete_ownership(&self, workspace_id: Uuid, fingerprint: &str) -> Result<bool>;
+
+    // =========================================================================
+    // MUTES
+    // =========================================================================
+
+    /// Mute a fingerprint, replacing any existing mute
+    async fn upsert_mute(&self, workspace_id: Uuid, mute: &QueryMute) -> Result<QueryMute>;
+
+    /// List mutes in a workspace that have not expired
+    async fn list_active_mutes(&self, workspace_id: Uuid) -> Result<Vec<QueryMute>>;
+
+    /// Remove a mute, returning whether it existed
+    async fn delete_mute(&self, workspace_id: Uuid, fingerprint: &str) -> Result<bool>;
+
+    // =========================================================================
+    // ANNOTATIONS
+    // =========================================================================
+
+    /// Record a workspace event annotation
+    async fn insert_annotation(
+        &self,
+        workspace_id: Uuid,
+        annotation: &Annotation,
+    ) -> Result<Annotation>;
+
+    /// List annotations overlapping `[from, to)`, oldest first
+    async fn list_annotations(
+        &self,
+        workspace_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Annotation>>;
+
+    /// Delete an annotation, returning whether it existed
+    async fn delete_annotation(&self, workspace_id: Uuid, annotation_id: Uuid) -> Result<bool>;
+
+    // =========================================================================
+    // AUDIT LOG
+    // =========================================================================
+
+    /// Record an administrative change in the audit log
+    async fn insert_audit_entry(&self, entry: &AuditEntry) -> Result<()>;
+
+    /// List audit entries created within `[from, to)`, optionally only one
+    /// action, newest first
+    async fn list_audit_entries(
+        &self,
+        workspace_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        action: Option<AuditAction>,
+        limit: i64,
+    ) -> Result<Vec<AuditEntry>>;
+
+    // =========================================================================
+    // USAGE
+    // =========================================================================
+
+    /// Get daily usage for the days in `[from, to]`, oldest first; days
+    /// without stored metrics are omitted
+    async fn get_usage(
+        &self,
+        workspace_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<UsageDay>>;
+
+    // =========================================================================
+    // INGESTION STATS
+    // =========================================================================
+
+    /// Add per-minute ingest counts to the stored ones
+    async fn record_ingestion_stats(&self, rows: &[(Uuid, IngestionMinute)]) -> Result<()>;
+
+    /// Get a workspace's ingest counts for minutes at or after `since`,
+    /// oldest first; minutes without ingest calls are omitted
+    async fn get_ingestion_stats(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<IngestionMinute>>;
+
+    /// Get the last minute each workspace had metrics accepted, for
+    /// workspaces with accepted metrics at or after `since`
+    async fn get_last_ingest(&self, since: DateTime<Utc>) -> Result<HashMap<Uuid, DateTime<Utc>>>;
+
+    /// Delete ingest counts older than the given number of days
+    async fn prune_ingestion_stats(&self, older_than_days: i32) -> Result<u64>;
+
+    // =========================================================================
+    // ALERT RULES
+    // =========================================================================
+
+    /// Store a new alert rule
+    async fn create_alert_rule(&self, rule: &AlertRule) -> Result<AlertRule>;
+
+    /// List a workspace's alert rules, oldest first
+    async fn list_alert_rules(&self, workspace_id: Uuid) -> Result<Vec<AlertRule>>;
+
+    /// Get an alert rule
+    async fn get_alert_rule(&self, workspace_id: Uuid, rule_id: Uuid) -> Result<Option<AlertRule>>;
+
+    /// Replace an alert rule's definition, returning `None` if it doesn't exist
+    async fn update_alert_rule(&self, rule: &AlertRule) -> Result<Option<AlertRule>>;
+
+    /// Delete an alert rule, returning whether it existed
+    async fn delete_alert_rule(&self, workspace_id: Uuid, rule_id: Uuid) -> Result<bool>;
+
+    /// List enabled alert rules across all workspaces
+    async fn list_enabled_alert_rules(&self) -> Result<Vec<AlertRule>>;
+
+    /// Get the persisted evaluation state of every alert rule
+    async fn list_alert_rule_states(&self) -> Result<Vec<AlertRuleState>>;
+
+    /// Save an alert rule's evaluation state; ignored if the rule no longer exists
+    async fn upsert_alert_rule_state(&self, state: &AlertRuleState) -> Result<()>;
+
+    // =========================================================================
+    // ALERT DESTINATIONS
+    // =========================================================================
+
+    /// Store a new alert destination
+    async fn create_alert_destination(
+        &self,
+        destination: &AlertDestination,
+    ) -> Result<AlertDestination>;
+
+    /// List a workspace's alert destinations, oldest first
+    async fn list_alert_destinations(&self, workspace_id: Uuid) -> Result<Vec<AlertDestination>>;
+
+    /// Get an alert destination
+    async fn get_alert_destination(
+        &self,
+        workspace_id: Uuid,
+        destination_id: Uuid,
+    ) -> Result<Option<AlertDestination>>;
+
+    /// Delete an alert destination, returning whether it existed
+    async fn delete_alert_destination(
+        &self,
+        workspace_id: Uuid,
+        destination_id: Uuid,
+    ) -> Result<bool>;
+
+    /// Record an attempt to send an alert
+    async fn insert_alert_delivery(&self, delivery: &AlertDelivery) -> Result<()>;
+
+    /// List delivery attempts, newest first, optionally only to one
+    /// destination or only failed ones
+    async fn list_alert_deliveries(
+        &self,
+        workspace_id: Uuid,
+        destination_id: Option<Uuid>,
+        failed_only: bool,
+        limit: i64,
+    ) -> Result<Vec<AlertDelivery>>;
+
+    /// Delete delivery attempts older than the given number of days
+    async fn prune_alert_deliveries(&self, older_than_days: i32) -> Result<u64>;
+
+    // =========================================================================
+    // SLOS
+    // =========================================================================
+
+    /// Store a new SLO
+    async fn create_slo(&self, slo: &Slo) -> Result<Slo>;
+
+    /// List a workspace's SLOs, oldest first
+    async fn list_slos(&self, workspace_id: Uuid) -> Result<Vec<Slo>>;
+
+    /// Get an SLO
+    async fn get_slo(&self, workspace_id: Uuid, slo_id: Uuid) -> Result<Option<Slo>>;
+
+    /// Replace an SLO's definition, returning `None` if it doesn't exist
+    async fn update_slo(&self, slo: &Slo) -> Result<Option<Slo>>;
+
+    /// Delete an SLO, returning whether it existed
+    async fn delete_slo(&self, workspace_id: Uuid, slo_id: Uuid) -> Result<bool>;
+
+    /// List enabled SLOs across all workspaces
+    async fn list_enabled_slos(&self) -> Result<Vec<Slo>>;
+
+    /// Count queries created within `[from, to)` by how they met a latency
+    /// threshold, for one service or the whole workspace
+    async fn get_slo_counts(
+        &self,
+        workspace_id: Uuid,
+        service_id: Option<Uuid>,
+        threshold_ms: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<SloCounts>;
+
+    /// Get the last computed status of a workspace's SLOs
+    async fn list_slo_statuses(&self, workspace_id: Uuid) -> Result<Vec<SloStatus>>;
+
+    /// Get the last computed status of every SLO
+    async fn list_all_slo_statuses(&self) -> Result<Vec<SloStatus>>;
+
+    /// Save an SLO's computed status; ignored if the SLO no longer exists
+    async fn upsert_slo_status(&self, status: &SloStatus) -> Result<()>;
+
+    // =========================================================================
+    // LIFECYCLE POLICIES
+    // =========================================================================
+
+    /// Get a workspace's lifecycle stages in the order they run; empty when
+    /// it uses the default policy, `None` if the workspace doesn't exist
+    async fn get_lifecycle_policy(&self, workspace_id: Uuid)
+        -> Result<Option<Vec<LifecycleStage>>>;
+
+    /// Replace a workspace's lifecycle stages, keeping how the stages whose
+    /// action stays last ran; returns `false` if the workspace doesn't exist
+    async fn set_lifecycle_policy(
+        &self,
+        workspace_id: Uuid,
+        stages: &[LifecycleStage],
+    ) -> Result<bool>;
+
+    /// Get the lifecycle stages of every workspace with a policy of its own
+    async fn list_lifecycle_policies(&self) -> Result<HashMap<Uuid, Vec<LifecycleStage>>>;
+
+    /// Apply a lifecycle action to a workspace's data stored before
+    /// `before`, returning the rows changed or removed
+    async fn apply_lifecycle_action(
+        &self,
+        workspace_id: Uuid,
+        action: LifecycleAction,
+        before: DateTime<Utc>,
+    ) -> Result<u64>;
+
+    /// Save how a stage last ran; ignored if the stage no longer exists
+    async fn record_lifecycle_run(&self, workspace_id: Uuid, stage: &LifecycleStage) -> Result<()>;
+
+    // =========================================================================
+    // EMBEDDINGS
+    // =========================================================================
+
+    /// Insert or update a workspace's query embeddings, all produced by
+    /// `model`, in one statement
+    async fn insert_query_embeddings(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        embeddings: &[NewQueryEmbedding],
+    ) -> Result<()>;
+
+    /// Search `model`'s embeddings for similar queries using cosine similarity
+    ///
+    /// Stores with an approximate nearest-neighbor index apply `tuning` to
+    /// this search only; exact stores ignore it.
+    async fn search_similar_queries(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        embedding: &[f32],
+        limit: i32,
+        threshold: f32,
+        tuning: AnnTuning,
+    ) -> Result<Vec<SimilarQuery>>;
+
+    /// Search `model`'s embeddings for queries containing any of `terms`
+    /// (lowercase identifiers, see `hybrid_search::lexical_terms`), best
+    /// full-text match first, with each one's cosine similarity to
+    /// `embedding`
+    async fn search_lexical_queries(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        embedding: &[f32],
+        terms: &[String],
+        limit: i32,
+    ) -> Result<Vec<SimilarQuery>>;
+
+    /// Get `(query_text, query_hash)` pairs that haven't been embedded yet,
+    /// most-executed first and then slowest (by maximum duration), leaving
+    /// out those skipped for being over `max_bytes`
+    async fn get_unembedded_queries(
+        &self,
+        workspace_id: Uuid,
+        limit: i64,
+        max_bytes: usize,
+    ) -> Result<Vec<(String, String)>>;
+
+    /// Record queries left unembedded for being too long; a query stays
+    /// skipped while it is over the `max_bytes` it is fetched with
+    async fn insert_embedding_skips(
+        &self,
+        workspace_id: Uuid,
+        skips: &[EmbeddingSkip],
+    ) -> Result<()>;
+
+    /// Get workspaces with embeddings from a model other than `model`
+    async fn get_stale_embedding_workspaces(&self, model: &EmbeddingModel) -> Result<Vec<Uuid>>;
+
+    /// Get up to `limit` `(query_text, query_hash)` pairs embedded by a model
+    /// other than `model`, least recently embedded first
+    async fn get_stale_embeddings(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        limit: i64,
+    ) -> Result<Vec<(String, String)>>;
+
+    /// Get the embedding `model` made of `query_text` for a workspace, if
+    /// the query was embedded; queries differing only in case and
+    /// whitespace share an embedding
+    async fn get_query_embedding(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        query_text: &str,
+    ) -> Result<Option<Vec<f32>>>;
+
+    /// Get up to `limit` embedded queries, most-called since `since` first,
+    /// with their call count and latency over that window; only `model`'s
+    /// embeddings when given
+    async fn list_query_embeddings(
+        &self,
+        workspace_id: Uuid,
+        model: Option<&EmbeddingModel>,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<EmbeddedQuery>>;
+
+    /// Get workspaces with at least one embedding made by `model`
+    async fn get_embedded_workspaces(&self, model: &EmbeddingModel) -> Result<Vec<Uuid>>;
+
+    /// Replace a workspace's query clusters with `clusters`, numbered in
+    /// order from 0
+    async fn replace_query_clusters(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        computed_at: DateTime<Utc>,
+        clusters: &[NewQueryCluster],
+    ) -> Result<()>;
+
+    /// Get up to `limit` of a workspace's query clusters, most total time
+    /// since `since` first, with their members' traffic over that window;
+    /// `None` if it has never been clustered
+    async fn get_query_clusters(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Option<QueryClusters>>;
+
+    /// Find up to `limit` queries at least `threshold` similar to a stored
+    /// metric's, compared by the embedding already stored for its query, each
+    /// with its calls and latency since `since`; `None` if there's no such
+    /// metric stored since `since`
+    async fn get_metric_similarity(
+        &self,
+        workspace_id: Uuid,
+        metric_id: Uuid,
+        since: DateTime<Utc>,
+        limit: i64,
+        threshold: f32,
+    ) -> Result<Option<MetricSimilarity>>;
+
+    // =========================================================================
+    // ANOMALIES
+    // =========================================================================
+
+    /// Get duration statistics over the most recent metrics of each workspace
+    /// in `limits`, up to its limit, excluding muted fingerprints, with the
+    /// method each workspace detects anomalies by; workspaces without recent
+    /// metrics are left out
+    async fn get_metrics_stats(
+        &self,
+        limits: &HashMap<Uuid, i64>,
+    ) -> Result<HashMap<Uuid, MetricsStats>>;
+
+    /// Replace a workspace's seasonal baselines with duration statistics per
+    /// slot of `seasonality` over its metrics since `since`, excluding muted
+    /// fingerprints, returning how many slots were stored; without
+    /// seasonality the baselines are only cleared
+    async fn refresh_seasonal_baselines(
+        &self,
+        workspace_id: Uuid,
+        seasonality: Seasonality,
+        since: DateTime<Utc>,
+    ) -> Result<u64>;
+
+    /// Get the stored baseline of each workspace's slot in `slots`, with the
+    /// method the workspace detects anomalies by; workspaces without one are
+    /// left out
+    async fn get_seasonal_baselines(
+        &self,
+        slots: &HashMap<Uuid, (Seasonality, i16)>,
+    ) -> Result<HashMap<Uuid, MetricsStats>>;
+
+    /// Get metrics slower and more recent than their workspace's threshold,
+    /// across the workspaces in `thresholds`, excluding muted fingerprints
+    async fn get_recent_metrics_for_anomaly(
+        &self,
+        thresholds: &HashMap<Uuid, AnomalyThreshold>,
+    ) -> Result<Vec<QueryMetric>>;
+
+    /// Record detected anomalies, returning how many were stored
+    async fn insert_anomalies(&self, anomalies: &[QueryAnomaly]) -> Result<u64>;
+
+    /// Get one anomaly with its root-cause hints
+    async fn get_anomaly_context(
+        &self,
+        workspace_id: Uuid,
+        anomaly_id: Uuid,
+    ) -> Result<Option<AnomalyContext>>;
+
+    /// Get call counts per fingerprint since `since`; fingerprints without
+    /// calls are omitted
+    async fn get_fingerprint_call_counts(
+        &self,
+        workspace_id: Uuid,
+        fingerprints: &[String],
+        since: DateTime<Utc>,
+    ) -> Result<HashMap<String, i64>>;
+
+    /// Get anomalies detected at or after `since` at `min_severity` or
+    /// above, with `status` if given, most severe first (by level, then
+    /// score)
+    async fn get_anomalies(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+        min_severity: SeverityLevel,
+        status: Option<AnomalyStatus>,
+        limit: i64,
+    ) -> Result<Vec<AnomalyRecord>>;
+
+    /// Mark an open anomaly acknowledged by `acknowledged_by`, returning it;
+    /// `None` if the workspace has no open anomaly with the ID
+    async fn acknowledge_anomaly(
+        &self,
+        workspace_id: Uuid,
+        anomaly_id: Uuid,
+        acknowledged_by: &str,
+    ) -> Result<Option<AnomalyRecord>>;
+
+    /// Mark an open or acknowledged anomaly resolved, returning it; one that
+    /// wasn't acknowledged is recorded as acknowledged by `resolved_by`.
+    /// `None` if the workspace has no unresolved anomaly with the ID
+    async fn resolve_anomaly(
+        &self,
+        workspace_id: Uuid,
+        anomaly_id: Uuid,
+        resolved_by: &str,
+        resolved_at: DateTime<Utc>,
+    ) -> Result<Option<AnomalyRecord>>;
+
+    /// Count each service's queries in `[baseline_from, recent_from)` and
+    /// `[recent_from, to)`, across the given workspaces; services without
+    /// queries in either window are omitted
+    async fn get_service_throughput(
+        &self,
+        workspace_ids: &[Uuid],
+        baseline_from: DateTime<Utc>,
+        recent_from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<ServiceThroughput>>;
+
+    /// Record a newly detected throughput drop
+    async fn insert_throughput_drop(&self, drop: &ThroughputDrop) -> Result<()>;
+
+    /// Close an open throughput drop
+    async fn resolve_throughput_drop(
+        &self,
+        drop_id: Uuid,
+        recovered_at: DateTime<Utc>,
+    ) -> Result<()>;
+
+    /// Get every workspace's open throughput drops
+    async fn list_open_throughput_drops(&self) -> Result<Vec<ThroughputDrop>>;
+
+    /// Get a workspace's throughput drops that started at or after `since`
+    /// or are still open, newest first
+    async fn list_throughput_drops(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ThroughputDrop>>;
+}
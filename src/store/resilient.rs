@@ -0,0 +1,1306 @@
+//! Retry and circuit-breaker wrapper for storage backends
+//!
+//! [`ResilientStore`] wraps any [`MetricsStore`] and retries write paths with
+//! jittered exponential backoff when they fail with a transient error
+//! ([`AppError::ServiceUnavailable`]). Writes that still fail after every retry
+//! count against a shared [`CircuitBreaker`]; once it opens, writes fail fast
+//! until the cooldown elapses, and the aggregation task stops draining the
+//! ingest buffer so metrics wait there instead of being lost. Reads are passed
+//! through unchanged.
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use parking_lot::Mutex;
+use rand::Rng;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::clock::Clock;
+use crate::db::{
+    AggregatedMetric, AlertRuleState, AnnTuning, AnomalyContext, AnomalyRecord, AnomalyThreshold,
+    BatchInsert, CatalogEntry, DeadLetter, DeadLetterReason, DurationStats, DurationStatsQuery,
+    EmbeddedQuery, EmbeddingModel, EmbeddingSkip, ErasureCounts, ErrorGroup, ErrorGroupsQuery,
+    ErrorSummary, FingerprintStats, FingerprintStatsQuery, FingerprintSummary, IngestionMinute,
+    MetricErasure, MetricFilter, MetricSimilarity, MetricsStats, NewQueryCluster,
+    NewQueryEmbedding, QueryAnomaly, QueryClusters, QueryTextSearch, ServiceThroughput,
+    SimilarQuery, SloStatus, StatementTotals, ThroughputDrop, UsageDay,
+};
+use crate::error::{AppError, Result};
+use crate::models::{
+    AlertDelivery, AlertDestination, AlertRule, Annotation, AnomalyMethod, AnomalyOverrides,
+    AnomalyStatus, ApiKey, AuditAction, AuditEntry, JsonCasing, LifecycleAction, LifecycleStage,
+    QueryKind, QueryMetric, QueryMute, QueryOwnership, Seasonality, Service, Slo,
+};
+use crate::services::severity::SeverityLevel;
+use crate::services::slo::SloCounts;
+use crate::store::{MetricsStore, PoolStats};
+
+/// How many times, and how patiently, to retry a failed write
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    /// Backoff ceiling for the first retry, doubled on each subsequent one
+    pub base_delay: Duration,
+    /// Upper bound on any single backoff
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Backoff before retry number `attempt` (0-based), with full jitter
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let millis = ceiling.as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(0..=millis))
+    }
+
+    /// Run `op`, retrying transient failures after backoff on `clock`
+    pub async fn run<T, F, Fut>(&self, clock: &Clock, op_name: &str, mut op: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match op().await {
+                Err(AppError::ServiceUnavailable(msg)) if attempt < self.max_retries => {
+                    let delay = self.backoff(attempt);
+                    warn!(
+                        op = op_name,
+                        attempt = attempt + 1,
+                        delay_ms = delay.as_millis() as u64,
+                        error = %msg,
+                        "Transient database error, retrying"
+                    );
+                    clock.sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<DateTime<Utc>>,
+}
+
+/// Opens after `failure_threshold` consecutive failed writes and stays open for `cooldown`
+///
+/// After the cooldown, writes are let through again; the first success closes
+/// the breaker, while another failure re-opens it immediately.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+    clock: Clock,
+}
+
+impl CircuitBreaker {
+    /// Create a closed circuit breaker whose cooldown runs on `clock`
+    pub fn new(failure_threshold: u32, cooldown: Duration, clock: Clock) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+            clock,
+        }
+    }
+
+    /// Whether writes are currently being rejected
+    pub fn is_open(&self) -> bool {
+        let now = self.clock.now();
+        self.state
+            .lock()
+            .open_until
+            .is_some_and(|until| now < until)
+    }
+
+    /// Record a successful write, closing the breaker
+    pub fn record_success(&self) {
+        let mut state = self.state.lock();
+        if state.open_until.is_some() {
+            info!("Database circuit breaker closed");
+        }
+        *state = BreakerState::default();
+    }
+
+    /// Record a write that failed after all retries
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock();
+        state.consecutive_failures += 1;
+        if state.consecutive_failures >= self.failure_threshold {
+            warn!(
+                failures = state.consecutive_failures,
+                cooldown_secs = self.cooldown.as_secs(),
+                "Database circuit breaker open, pausing writes"
+            );
+            state.open_until = Some(self.clock.now() + self.cooldown);
+        }
+    }
+}
+
+/// [`MetricsStore`] decorator adding retries and a circuit breaker to write paths
+pub struct ResilientStore {
+    inner: Arc<dyn MetricsStore>,
+    policy: RetryPolicy,
+    breaker: Arc<CircuitBreaker>,
+    clock: Clock,
+}
+
+impl ResilientStore {
+    /// Wrap a store; `breaker` is shared with tasks that should back off while
+    /// it is open, and retries wait on `clock`
+    pub fn new(
+        inner: Arc<dyn MetricsStore>,
+        policy: RetryPolicy,
+        breaker: Arc<CircuitBreaker>,
+        clock: Clock,
+    ) -> Self {
+        Self {
+            inner,
+            policy,
+            breaker,
+            clock,
+        }
+    }
+
+    async fn write<T, F, Fut>(&self, op_name: &str, op: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if self.breaker.is_open() {
+            return Err(AppError::ServiceUnavailable(
+                "Database circuit breaker is open".into(),
+            ));
+        }
+
+        let result = self.policy.run(&self.clock, op_name, op).await;
+        match &result {
+            Err(AppError::ServiceUnavailable(_)) => self.breaker.record_failure(),
+            _ => self.breaker.record_success(),
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl MetricsStore for ResilientStore {
+    async fn ping(&self) -> Result<()> {
+        self.inner.ping().await
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        self.inner.pool_stats()
+    }
+
+    fn read_pool_stats(&self) -> Option<PoolStats> {
+        self.inner.read_pool_stats()
+    }
+
+    fn aggregation_cache_stats(&self) -> Option<(u64, u64)> {
+        self.inner.aggregation_cache_stats()
+    }
+
+    async fn verify_api_key(&self, api_key: &str) -> Result<ApiKey> {
+        self.inner.verify_api_key(api_key).await
+    }
+
+    async fn get_json_casing(&self, workspace_id: Uuid) -> Result<Option<JsonCasing>> {
+        self.inner.get_json_casing(workspace_id).await
+    }
+
+    async fn set_json_casing(&self, workspace_id: Uuid, casing: JsonCasing) -> Result<bool> {
+        self.write("set_json_casing", || {
+            self.inner.set_json_casing(workspace_id, casing)
+        })
+        .await
+    }
+
+    async fn get_monthly_quota(&self, workspace_id: Uuid) -> Result<Option<i64>> {
+        self.inner.get_monthly_quota(workspace_id).await
+    }
+
+    async fn set_monthly_quota(&self, workspace_id: Uuid, quota: Option<i64>) -> Result<bool> {
+        self.write("set_monthly_quota", || {
+            self.inner.set_monthly_quota(workspace_id, quota)
+        })
+        .await
+    }
+
+    async fn get_literal_redaction(&self, workspace_id: Uuid) -> Result<Option<bool>> {
+        self.inner.get_literal_redaction(workspace_id).await
+    }
+
+    async fn set_literal_redaction(&self, workspace_id: Uuid, enabled: bool) -> Result<bool> {
+        self.write("set_literal_redaction", || {
+            self.inner.set_literal_redaction(workspace_id, enabled)
+        })
+        .await
+    }
+
+    async fn get_anomaly_method(&self, workspace_id: Uuid) -> Result<Option<AnomalyMethod>> {
+        self.inner.get_anomaly_method(workspace_id).await
+    }
+
+    async fn set_anomaly_method(&self, workspace_id: Uuid, method: AnomalyMethod) -> Result<bool> {
+        self.write("set_anomaly_method", || {
+            self.inner.set_anomaly_method(workspace_id, method)
+        })
+        .await
+    }
+
+    async fn get_anomaly_overrides(
+        &self,
+        workspace_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, AnomalyOverrides>> {
+        self.inner.get_anomaly_overrides(workspace_ids).await
+    }
+
+    async fn set_anomaly_overrides(
+        &self,
+        workspace_id: Uuid,
+        overrides: &AnomalyOverrides,
+    ) -> Result<bool> {
+        self.write("set_anomaly_overrides", || {
+            self.inner.set_anomaly_overrides(workspace_id, overrides)
+        })
+        .await
+    }
+
+    // =========================================================================
+    // API KEYS
+    // =========================================================================
+
+    async fn create_api_key(&self, key: &ApiKey, secret: &str) -> Result<()> {
+        self.write("create_api_key", || self.inner.create_api_key(key, secret))
+            .await
+    }
+
+    async fn list_api_keys(&self, workspace_id: Uuid) -> Result<Vec<ApiKey>> {
+        self.inner.list_api_keys(workspace_id).await
+    }
+
+    async fn delete_api_key(&self, workspace_id: Uuid, key_id: Uuid) -> Result<bool> {
+        self.write("delete_api_key", || {
+            self.inner.delete_api_key(workspace_id, key_id)
+        })
+        .await
+    }
+
+    // =========================================================================
+    // METRICS
+    // =========================================================================
+
+    async fn insert_metrics_batch(&self, metrics: &[QueryMetric]) -> Result<BatchInsert> {
+        self.write("insert_metrics_batch", || {
+            self.inner.insert_metrics_batch(metrics)
+        })
+        .await
+    }
+
+    async fn get_recent_metrics(
+        &self,
+        workspace_id: Uuid,
+        filter: &MetricFilter,
+        limit: i64,
+    ) -> Result<Vec<QueryMetric>> {
+        self.inner
+            .get_recent_metrics(workspace_id, filter, limit)
+            .await
+    }
+
+    async fn get_aggregations(
+        &self,
+        workspace_id: Uuid,
+        window: &str,
+        tz: Tz,
+        filter: &MetricFilter,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<AggregatedMetric>> {
+        self.inner
+            .get_aggregations(workspace_id, window, tz, filter, from, to)
+            .await
+    }
+
+    async fn prune_old_metrics(&self, older_than_days: i32) -> Result<u64> {
+        self.write("prune_old_metrics", || {
+            self.inner.prune_old_metrics(older_than_days)
+        })
+        .await
+    }
+
+    async fn erase_metrics(
+        &self,
+        workspace_id: Uuid,
+        filter: &MetricErasure,
+    ) -> Result<ErasureCounts> {
+        self.write("erase_metrics", || {
+            self.inner.erase_metrics(workspace_id, filter)
+        })
+        .await
+    }
+
+    async fn refresh_fingerprint_rollups(&self, since: DateTime<Utc>) -> Result<u64> {
+        self.write("refresh_fingerprint_rollups", || {
+            self.inner.refresh_fingerprint_rollups(since)
+        })
+        .await
+    }
+
+    async fn get_new_queries(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<CatalogEntry>> {
+        self.inner.get_new_queries(workspace_id, since, limit).await
+    }
+
+    async fn search_query_text(
+        &self,
+        workspace_id: Uuid,
+        search: &QueryTextSearch,
+    ) -> Result<Vec<CatalogEntry>> {
+        self.inner.search_query_text(workspace_id, search).await
+    }
+
+    async fn get_fingerprint_stats(
+        &self,
+        workspace_id: Uuid,
+        query: &FingerprintStatsQuery,
+    ) -> Result<Vec<FingerprintStats>> {
+        self.inner.get_fingerprint_stats(workspace_id, query).await
+    }
+
+    async fn get_statement_totals(
+        &self,
+        workspace_id: Uuid,
+        service_id: Option<Uuid>,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<StatementTotals>> {
+        self.inner
+            .get_statement_totals(workspace_id, service_id, query_kind, from, to, limit)
+            .await
+    }
+
+    async fn get_duration_stats(
+        &self,
+        workspace_id: Uuid,
+        query: &DurationStatsQuery,
+    ) -> Result<Vec<DurationStats>> {
+        self.inner.get_duration_stats(workspace_id, query).await
+    }
+
+    async fn get_error_groups(
+        &self,
+        workspace_id: Uuid,
+        query: &ErrorGroupsQuery,
+    ) -> Result<Vec<ErrorGroup>> {
+        self.inner.get_error_groups(workspace_id, query).await
+    }
+
+    // =========================================================================
+    // DEAD LETTERS
+    // =========================================================================
+
+    async fn insert_dead_letters(&self, metrics: &[QueryMetric], error: &str) -> Result<()> {
+        self.write("insert_dead_letters", || {
+            self.inner.insert_dead_letters(metrics, error)
+        })
+        .await
+    }
+
+    async fn list_dead_letters(
+        &self,
+        workspace_id: Option<Uuid>,
+        reason: Option<DeadLetterReason>,
+        limit: i64,
+    ) -> Result<Vec<DeadLetter>> {
+        self.inner
+            .list_dead_letters(workspace_id, reason, limit)
+            .await
+    }
+
+    async fn get_dead_letters(&self, metric_ids: &[Uuid]) -> Result<Vec<DeadLetter>> {
+        self.inner.get_dead_letters(metric_ids).await
+    }
+
+    async fn delete_dead_letters(&self, letters: &[(Uuid, i32)]) -> Result<u64> {
+        self.write("delete_dead_letters", || {
+            self.inner.delete_dead_letters(letters)
+        })
+        .await
+    }
+
+    // =========================================================================
+    // SERVICE REGISTRY
+    // =========================================================================
+
+    async fn list_services(&self, workspace_id: Uuid) -> Result<Vec<Service>> {
+        self.inner.list_services(workspace_id).await
+    }
+
+    async fn get_service(&self, workspace_id: Uuid, service_id: Uuid) -> Result<Option<Service>> {
+        self.inner.get_service(workspace_id, service_id).await
+    }
+
+    async fn create_service(&self, service: &Service) -> Result<Service> {
+        self.write("create_service", || self.inner.create_service(service))
+            .await
+    }
+
+    async fn update_service(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<Option<Service>> {
+        self.write("update_service", || {
+            self.inner
+                .update_service(workspace_id, service_id, name, description)
+        })
+        .await
+    }
+
+    async fn delete_service(&self, workspace_id: Uuid, service_id: Uuid) -> Result<bool> {
+        self.write("delete_service", || {
+            self.inner.delete_service(workspace_id, service_id)
+        })
+        .await
+    }
+
+    // =========================================================================
+    // SERVICE OVERVIEW
+    // =========================================================================
+
+    async fn get_top_fingerprints(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<FingerprintSummary>> {
+        self.inner
+            .get_top_fingerprints(workspace_id, service_id, query_kind, from, to, limit)
+            .await
+    }
+
+    async fn get_top_errors(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ErrorSummary>> {
+        self.inner
+            .get_top_errors(workspace_id, service_id, query_kind, from, to, limit)
+            .await
+    }
+
+    async fn get_service_anomalies(
+        &self,
+        workspace_id: Uuid,
+        service_id: Uuid,
+        query_kind: Option<QueryKind>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<AnomalyRecord>> {
+        self.inner
+            .get_service_anomalies(workspace_id, service_id, query_kind, from, to, limit)
+            .await
+    }
+
+    // =========================================================================
+    // OWNERSHIP
+    // =========================================================================
+
+    async fn upsert_ownership(
+        &self,
+        workspace_id: Uuid,
+        ownership: &QueryOwnership,
+    ) -> Result<QueryOwnership> {
+        self.write("upsert_ownership", || {
+            self.inner.upsert_ownership(workspace_id, ownership)
+        })
+        .await
+    }
+
+    async fn get_ownership(
+        &self,
+        workspace_id: Uuid,
+        fingerprint: &str,
+    ) -> Result<Option<QueryOwnership>> {
+        self.inner.get_ownership(workspace_id, fingerprint).await
+    }
+
+    async fn list_ownership(&self, workspace_id: Uuid) -> Result<Vec<QueryOwnership>> {
+        self.inner.list_ownership(workspace_id).await
+    }
+
+    async fn delete_ownership(&self, workspace_id: Uuid, fingerprint: &str) -> Result<bool> {
+        self.write("delete_ownership", || {
+            self.inner.delete_ownership(workspace_id, fingerprint)
+        })
+        .await
+    }
+
+    // =========================================================================
+    // MUTES
+    // =========================================================================
+
+    async fn upsert_mute(&self, workspace_id: Uuid, mute: &QueryMute) -> Result<QueryMute> {
+        self.write("upsert_mute", || self.inner.upsert_mute(workspace_id, mute))
+            .await
+    }
+
+    async fn list_active_mutes(&self, workspace_id: Uuid) -> Result<Vec<QueryMute>> {
+        self.inner.list_active_mutes(workspace_id).await
+    }
+
+    async fn delete_mute(&self, workspace_id: Uuid, fingerprint: &str) -> Result<bool> {
+        self.write("delete_mute", || {
+            self.inner.delete_mute(workspace_id, fingerprint)
+        })
+        .await
+    }
+
+    // =========================================================================
+    // ANNOTATIONS
+    // =========================================================================
+
+    async fn insert_annotation(
+        &self,
+        workspace_id: Uuid,
+        annotation: &Annotation,
+    ) -> Result<Annotation> {
+        self.write("insert_annotation", || {
+            self.inner.insert_annotation(workspace_id, annotation)
+        })
+        .await
+    }
+
+    async fn list_annotations(
+        &self,
+        workspace_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Annotation>> {
+        self.inner.list_annotations(workspace_id, from, to).await
+    }
+
+    async fn delete_annotation(&self, workspace_id: Uuid, annotation_id: Uuid) -> Result<bool> {
+        self.write("delete_annotation", || {
+            self.inner.delete_annotation(workspace_id, annotation_id)
+        })
+        .await
+    }
+
+    // =========================================================================
+    // AUDIT LOG
+    // =========================================================================
+
+    async fn insert_audit_entry(&self, entry: &AuditEntry) -> Result<()> {
+        self.write("insert_audit_entry", || {
+            self.inner.insert_audit_entry(entry)
+        })
+        .await
+    }
+
+    async fn list_audit_entries(
+        &self,
+        workspace_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        action: Option<AuditAction>,
+        limit: i64,
+    ) -> Result<Vec<AuditEntry>> {
+        self.inner
+            .list_audit_entries(workspace_id, from, to, action, limit)
+            .await
+    }
+
+    // =========================================================================
+    // USAGE
+    // =========================================================================
+
+    async fn get_usage(
+        &self,
+        workspace_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<UsageDay>> {
+        self.inner.get_usage(workspace_id, from, to).await
+    }
+
+    // =========================================================================
+    // INGESTION STATS
+    // =========================================================================
+
+    async fn record_ingestion_stats(&self, rows: &[(Uuid, IngestionMinute)]) -> Result<()> {
+        self.write("record_ingestion_stats", || {
+            self.inner.record_ingestion_stats(rows)
+        })
+        .await
+    }
+
+    async fn get_ingestion_stats(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<IngestionMinute>> {
+        self.inner.get_ingestion_stats(workspace_id, since).await
+    }
+
+    async fn get_last_ingest(&self, since: DateTime<Utc>) -> Result<HashMap<Uuid, DateTime<Utc>>> {
+        self.inner.get_last_ingest(since).await
+    }
+
+    async fn prune_ingestion_stats(&self, older_than_days: i32) -> Result<u64> {
+        self.write("prune_ingestion_stats", || {
+            self.inner.prune_ingestion_stats(older_than_days)
+        })
+        .await
+    }
+
+    // =========================================================================
+    // ALERT RULES
+    // =========================================================================
+
+    async fn create_alert_rule(&self, rule: &AlertRule) -> Result<AlertRule> {
+        self.write("create_alert_rule", || self.inner.create_alert_rule(rule))
+            .await
+    }
+
+    async fn list_alert_rules(&self, workspace_id: Uuid) -> Result<Vec<AlertRule>> {
+        self.inner.list_alert_rules(workspace_id).await
+    }
+
+    async fn get_alert_rule(&self, workspace_id: Uuid, rule_id: Uuid) -> Result<Option<AlertRule>> {
+        self.inner.get_alert_rule(workspace_id, rule_id).await
+    }
+
+    async fn update_alert_rule(&self, rule: &AlertRule) -> Result<Option<AlertRule>> {
+        self.write("update_alert_rule", || self.inner.update_alert_rule(rule))
+            .await
+    }
+
+    async fn delete_alert_rule(&self, workspace_id: Uuid, rule_id: Uuid) -> Result<bool> {
+        self.write("delete_alert_rule", || {
+            self.inner.delete_alert_rule(workspace_id, rule_id)
+        })
+        .await
+    }
+
+    async fn list_enabled_alert_rules(&self) -> Result<Vec<AlertRule>> {
+        self.inner.list_enabled_alert_rules().await
+    }
+
+    async fn list_alert_rule_states(&self) -> Result<Vec<AlertRuleState>> {
+        self.inner.list_alert_rule_states().await
+    }
+
+    async fn upsert_alert_rule_state(&self, state: &AlertRuleState) -> Result<()> {
+        self.write("upsert_alert_rule_state", || {
+            self.inner.upsert_alert_rule_state(state)
+        })
+        .await
+    }
+
+    // =========================================================================
+    // ALERT DESTINATIONS
+    // =========================================================================
+
+    async fn create_alert_destination(
+        &self,
+        destination: &AlertDestination,
+    ) -> Result<AlertDestination> {
+        self.write("create_alert_destination", || {
+            self.inner.create_alert_destination(destination)
+        })
+        .await
+    }
+
+    async fn list_alert_destinations(&self, workspace_id: Uuid) -> Result<Vec<AlertDestination>> {
+        self.inner.list_alert_destinations(workspace_id).await
+    }
+
+    async fn get_alert_destination(
+        &self,
+        workspace_id: Uuid,
+        destination_id: Uuid,
+    ) -> Result<Option<AlertDestination>> {
+        self.inner
+            .get_alert_destination(workspace_id, destination_id)
+            .await
+    }
+
+    async fn delete_alert_destination(
+        &self,
+        workspace_id: Uuid,
+        destination_id: Uuid,
+    ) -> Result<bool> {
+        self.write("delete_alert_destination", || {
+            self.inner
+                .delete_alert_destination(workspace_id, destination_id)
+        })
+        .await
+    }
+
+    async fn insert_alert_delivery(&self, delivery: &AlertDelivery) -> Result<()> {
+        self.write("insert_alert_delivery", || {
+            self.inner.insert_alert_delivery(delivery)
+        })
+        .await
+    }
+
+    async fn list_alert_deliveries(
+        &self,
+        workspace_id: Uuid,
+        destination_id: Option<Uuid>,
+        failed_only: bool,
+        limit: i64,
+    ) -> Result<Vec<AlertDelivery>> {
+        self.inner
+            .list_alert_deliveries(workspace_id, destination_id, failed_only, limit)
+            .await
+    }
+
+    async fn prune_alert_deliveries(&self, older_than_days: i32) -> Result<u64> {
+        self.write("prune_alert_deliveries", || {
+            self.inner.prune_alert_deliveries(older_than_days)
+        })
+        .await
+    }
+
+    // =========================================================================
+    // SLOS
+    // =========================================================================
+
+    async fn create_slo(&self, slo: &Slo) -> Result<Slo> {
+        self.write("create_slo", || self.inner.create_slo(slo))
+            .await
+    }
+
+    async fn list_slos(&self, workspace_id: Uuid) -> Result<Vec<Slo>> {
+        self.inner.list_slos(workspace_id).await
+    }
+
+    async fn get_slo(&self, workspace_id: Uuid, slo_id: Uuid) -> Result<Option<Slo>> {
+        self.inner.get_slo(workspace_id, slo_id).await
+    }
+
+    async fn update_slo(&self, slo: &Slo) -> Result<Option<Slo>> {
+        self.write("update_slo", || self.inner.update_slo(slo))
+            .await
+    }
+
+    async fn delete_slo(&self, workspace_id: Uuid, slo_id: Uuid) -> Result<bool> {
+        self.write("delete_slo", || self.inner.delete_slo(workspace_id, slo_id))
+            .await
+    }
+
+    async fn list_enabled_slos(&self) -> Result<Vec<Slo>> {
+        self.inner.list_enabled_slos().await
+    }
+
+    async fn get_slo_counts(
+        &self,
+        workspace_id: Uuid,
+        service_id: Option<Uuid>,
+        threshold_ms: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<SloCounts> {
+        self.inner
+            .get_slo_counts(workspace_id, service_id, threshold_ms, from, to)
+            .await
+    }
+
+    async fn list_slo_statuses(&self, workspace_id: Uuid) -> Result<Vec<SloStatus>> {
+        self.inner.list_slo_statuses(workspace_id).await
+    }
+
+    async fn list_all_slo_statuses(&self) -> Result<Vec<SloStatus>> {
+        self.inner.list_all_slo_statuses().await
+    }
+
+    async fn upsert_slo_status(&self, status: &SloStatus) -> Result<()> {
+        self.write("upsert_slo_status", || self.inner.upsert_slo_status(status))
+            .await
+    }
+
+    // =========================================================================
+    // LIFECYCLE POLICIES
+    // =========================================================================
+
+    async fn get_lifecycle_policy(
+        &self,
+        workspace_id: Uuid,
+    ) -> Result<Option<Vec<LifecycleStage>>> {
+        self.inner.get_lifecycle_policy(workspace_id).await
+    }
+
+    async fn set_lifecycle_policy(
+        &self,
+        workspace_id: Uuid,
+        stages: &[LifecycleStage],
+    ) -> Result<bool> {
+        self.write("set_lifecycle_policy", || {
+            self.inner.set_lifecycle_policy(workspace_id, stages)
+        })
+        .await
+    }
+
+    async fn list_lifecycle_policies(&self) -> Result<HashMap<Uuid, Vec<LifecycleStage>>> {
+        self.inner.list_lifecycle_policies().await
+    }
+
+    async fn apply_lifecycle_action(
+        &self,
+        workspace_id: Uuid,
+        action: LifecycleAction,
+        before: DateTime<Utc>,
+    ) -> Result<u64> {
+        self.write("apply_lifecycle_action", || {
+            self.inner
+                .apply_lifecycle_action(workspace_id, action, before)
+        })
+        .await
+    }
+
+    async fn record_lifecycle_run(&self, workspace_id: Uuid, stage: &LifecycleStage) -> Result<()> {
+        self.write("record_lifecycle_run", || {
+            self.inner.record_lifecycle_run(workspace_id, stage)
+        })
+        .await
+    }
+
+    // =========================================================================
+    // EMBEDDINGS
+    // =========================================================================
+
+    async fn insert_query_embeddings(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        embeddings: &[NewQueryEmbedding],
+    ) -> Result<()> {
+        self.write("insert_query_embeddings", || {
+            self.inner
+                .insert_query_embeddings(workspace_id, model, embeddings)
+        })
+        .await
+    }
+
+    async fn search_similar_queries(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        embedding: &[f32],
+        limit: i32,
+        threshold: f32,
+        tuning: AnnTuning,
+    ) -> Result<Vec<SimilarQuery>> {
+        self.inner
+            .search_similar_queries(workspace_id, model, embedding, limit, threshold, tuning)
+            .await
+    }
+
+    async fn search_lexical_queries(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        embedding: &[f32],
+        terms: &[String],
+        limit: i32,
+    ) -> Result<Vec<SimilarQuery>> {
+        self.inner
+            .search_lexical_queries(workspace_id, model, embedding, terms, limit)
+            .await
+    }
+
+    async fn get_unembedded_queries(
+        &self,
+        workspace_id: Uuid,
+        limit: i64,
+        max_bytes: usize,
+    ) -> Result<Vec<(String, String)>> {
+        self.inner
+            .get_unembedded_queries(workspace_id, limit, max_bytes)
+            .await
+    }
+
+    async fn insert_embedding_skips(
+        &self,
+        workspace_id: Uuid,
+        skips: &[EmbeddingSkip],
+    ) -> Result<()> {
+        self.write("insert_embedding_skips", || {
+            self.inner.insert_embedding_skips(workspace_id, skips)
+        })
+        .await
+    }
+
+    async fn get_stale_embedding_workspaces(&self, model: &EmbeddingModel) -> Result<Vec<Uuid>> {
+        self.inner.get_stale_embedding_workspaces(model).await
+    }
+
+    async fn get_stale_embeddings(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        limit: i64,
+    ) -> Result<Vec<(String, String)>> {
+        self.inner
+            .get_stale_embeddings(workspace_id, model, limit)
+            .await
+    }
+
+    async fn get_query_embedding(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        query_text: &str,
+    ) -> Result<Option<Vec<f32>>> {
+        self.inner
+            .get_query_embedding(workspace_id, model, query_text)
+            .await
+    }
+
+    async fn list_query_embeddings(
+        &self,
+        workspace_id: Uuid,
+        model: Option<&EmbeddingModel>,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<EmbeddedQuery>> {
+        self.inner
+            .list_query_embeddings(workspace_id, model, since, limit)
+            .await
+    }
+
+    async fn get_embedded_workspaces(&self, model: &EmbeddingModel) -> Result<Vec<Uuid>> {
+        self.inner.get_embedded_workspaces(model).await
+    }
+
+    async fn replace_query_clusters(
+        &self,
+        workspace_id: Uuid,
+        model: &EmbeddingModel,
+        computed_at: DateTime<Utc>,
+        clusters: &[NewQueryCluster],
+    ) -> Result<()> {
+        self.write("replace_query_clusters", || {
+            self.inner
+                .replace_query_clusters(workspace_id, model, computed_at, clusters)
+        })
+        .await
+    }
+
+    async fn get_query_clusters(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Option<QueryClusters>> {
+        self.inner
+            .get_query_clusters(workspace_id, since, limit)
+            .await
+    }
+
+    async fn get_metric_similarity(
+        &self,
+        workspace_id: Uuid,
+        metric_id: Uuid,
+        since: DateTime<Utc>,
+        limit: i64,
+        threshold: f32,
+    ) -> Result<Option<MetricSimilarity>> {
+        self.inner
+            .get_metric_similarity(workspace_id, metric_id, since, limit, threshold)
+            .await
+    }
+
+    // =========================================================================
+    // ANOMALIES
+    // =========================================================================
+
+    async fn get_metrics_stats(
+        &self,
+        limits: &HashMap<Uuid, i64>,
+    ) -> Result<HashMap<Uuid, MetricsStats>> {
+        self.inner.get_metrics_stats(limits).await
+    }
+
+    async fn refresh_seasonal_baselines(
+        &self,
+        workspace_id: Uuid,
+        seasonality: Seasonality,
+        since: DateTime<Utc>,
+    ) -> Result<u64> {
+        self.write("refresh_seasonal_baselines", || {
+            self.inner
+                .refresh_seasonal_baselines(workspace_id, seasonality, since)
+        })
+        .await
+    }
+
+    async fn get_seasonal_baselines(
+        &self,
+        slots: &HashMap<Uuid, (Seasonality, i16)>,
+    ) -> Result<HashMap<Uuid, MetricsStats>> {
+        self.inner.get_seasonal_baselines(slots).await
+    }
+
+    async fn get_recent_metrics_for_anomaly(
+        &self,
+        thresholds: &HashMap<Uuid, AnomalyThreshold>,
+    ) -> Result<Vec<QueryMetric>> {
+        self.inner.get_recent_metrics_for_anomaly(thresholds).await
+    }
+
+    async fn insert_anomalies(&self, anomalies: &[QueryAnomaly]) -> Result<u64> {
+        self.write("insert_anomalies", || {
+            self.inner.insert_anomalies(anomalies)
+        })
+        .await
+    }
+
+    async fn get_anomaly_context(
+        &self,
+        workspace_id: Uuid,
+        anomaly_id: Uuid,
+    ) -> Result<Option<AnomalyContext>> {
+        self.inner
+            .get_anomaly_context(workspace_id, anomaly_id)
+            .await
+    }
+
+    async fn get_fingerprint_call_counts(
+        &self,
+        workspace_id: Uuid,
+        fingerprints: &[String],
+        since: DateTime<Utc>,
+    ) -> Result<HashMap<String, i64>> {
+        self.inner
+            .get_fingerprint_call_counts(workspace_id, fingerprints, since)
+            .await
+    }
+
+    async fn get_anomalies(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+        min_severity: SeverityLevel,
+        status: Option<AnomalyStatus>,
+        limit: i64,
+    ) -> Result<Vec<AnomalyRecord>> {
+        self.inner
+            .get_anomalies(workspace_id, since, min_severity, status, limit)
+            .await
+    }
+
+    async fn acknowledge_anomaly(
+        &self,
+        workspace_id: Uuid,
+        anomaly_id: Uuid,
+        acknowledged_by: &str,
+    ) -> Result<Option<AnomalyRecord>> {
+        self.write("acknowledge_anomaly", || {
+            self.inner
+                .acknowledge_anomaly(workspace_id, anomaly_id, acknowledged_by)
+        })
+        .await
+    }
+
+    async fn resolve_anomaly(
+        &self,
+        workspace_id: Uuid,
+        anomaly_id: Uuid,
+        resolved_by: &str,
+        resolved_at: DateTime<Utc>,
+    ) -> Result<Option<AnomalyRecord>> {
+        self.write("resolve_anomaly", || {
+            self.inner
+                .resolve_anomaly(workspace_id, anomaly_id, resolved_by, resolved_at)
+        })
+        .await
+    }
+
+    async fn get_service_throughput(
+        &self,
+        workspace_ids: &[Uuid],
+        baseline_from: DateTime<Utc>,
+        recent_from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<ServiceThroughput>> {
+        self.inner
+            .get_service_throughput(workspace_ids, baseline_from, recent_from, to)
+            .await
+    }
+
+    async fn insert_throughput_drop(&self, drop: &ThroughputDrop) -> Result<()> {
+        self.write("insert_throughput_drop", || {
+            self.inner.insert_throughput_drop(drop)
+        })
+        .await
+    }
+
+    async fn resolve_throughput_drop(
+        &self,
+        drop_id: Uuid,
+        recovered_at: DateTime<Utc>,
+    ) -> Result<()> {
+        self.write("resolve_throughput_drop", || {
+            self.inner.resolve_throughput_drop(drop_id, recovered_at)
+        })
+        .await
+    }
+
+    async fn list_open_throughput_drops(&self) -> Result<Vec<ThroughputDrop>> {
+        self.inner.list_open_throughput_drops().await
+    }
+
+    async fn list_throughput_drops(
+        &self,
+        workspace_id: Uuid,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ThroughputDrop>> {
+        self.inner
+            .list_throughput_drops(workspace_id, since, limit)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+
+    /// Run `op` under `policy`, advancing `clock` until it's done, so the
+    /// backoff passes without real waiting
+    async fn run_on<T: Send + 'static, F, Fut>(
+        clock: &Clock,
+        policy: RetryPolicy,
+        op: F,
+    ) -> Result<T>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T>> + Send,
+    {
+        let task = tokio::spawn({
+            let clock = clock.clone();
+            async move { policy.run(&clock, "test", op).await }
+        });
+        while !task.is_finished() {
+            tokio::task::yield_now().await;
+            clock.advance(TimeDelta::milliseconds(100));
+        }
+        task.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_errors_only() {
+        let start = Utc::now();
+        let clock = Clock::simulated(start);
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&calls);
+        let result = run_on(&clock, policy(3), move || {
+            let counter = Arc::clone(&counter);
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                    Err(AppError::ServiceUnavailable("connection reset".into()))
+                } else {
+                    Ok(42)
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        // The backoff passed on the clock
+        assert!(clock.now() > start);
+
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&calls);
+        let result: Result<()> = run_on(&clock, policy(3), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async { Err(AppError::DatabaseError("unique violation".into())) }
+        })
+        .await;
+        assert!(matches!(result, Err(AppError::DatabaseError(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_circuit_breaker_opens_and_closes() {
+        let clock = Clock::simulated(Utc::now());
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60), clock.clone());
+
+        breaker.record_failure();
+        assert!(!breaker.is_open());
+        breaker.record_failure();
+        assert!(breaker.is_open());
+
+        breaker.record_success();
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn test_circuit_breaker_cooldown() {
+        let clock = Clock::simulated(Utc::now());
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60), clock.clone());
+
+        breaker.record_failure();
+        clock.advance(TimeDelta::seconds(59));
+        assert!(breaker.is_open());
+        clock.advance(TimeDelta::seconds(1));
+        assert!(!breaker.is_open());
+
+        // Failing again once let through re-opens it straight away
+        breaker.record_failure();
+        assert!(breaker.is_open());
+    }
+}
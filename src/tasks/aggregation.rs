@@ -1,61 +1,181 @@
 //! Aggregation task - moves metrics from buffer to database
 
 use crate::buffer::MetricsBuffer;
-use crate::db::Database;
+use crate::clock::Clock;
+use crate::error::AppError;
+use crate::ingest_stats::{IngestCounts, IngestStats};
+use crate::live_rollup::LiveRollups;
+use crate::models::QueryMetric;
+use crate::routes::metrics::Metrics;
+use crate::services::new_shapes;
+use crate::services::notify::Notifier;
+use crate::services::quarantine::Quarantine;
+use crate::store::resilient::CircuitBreaker;
+use crate::store::MetricsStore;
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
-use tracing::{debug, error, info};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+/// How often the buffer is flushed to the database
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Most metrics written per flush
+pub const MAX_FLUSH_BATCH: usize = 10_000;
 
 /// Background task that periodically flushes metrics from the buffer to the database.
 ///
 /// Runs every 5 seconds, pulls a batch from the buffer, and batch-inserts into TimescaleDB.
 /// TimescaleDB continuous aggregates handle the actual aggregation.
-pub async fn aggregation_task(buffer: MetricsBuffer, db: Arc<Database>) {
-    let mut interval = tokio::time::interval(Duration::from_secs(5));
+#[allow(clippy::too_many_arguments)]
+pub async fn aggregation_task(
+    buffer: MetricsBuffer,
+    db: Arc<dyn MetricsStore>,
+    breaker: Arc<CircuitBreaker>,
+    live_rollups: LiveRollups,
+    ingest_stats: IngestStats,
+    quarantine: Arc<Quarantine>,
+    notifier: Arc<Notifier>,
+    metrics: Arc<Metrics>,
+    clock: Clock,
+) {
+    let mut interval = clock.interval(FLUSH_INTERVAL);
 
     info!("Aggregation task started (5s interval)");
 
     loop {
         interval.tick().await;
+        flush_batch(
+            &buffer,
+            &db,
+            &breaker,
+            &live_rollups,
+            &ingest_stats,
+            &quarantine,
+            &notifier,
+            &metrics,
+            &clock,
+        )
+        .await;
+    }
+}
 
-        // Pop batch from buffer
-        let batch = buffer.pop_batch(10_000);
-        if batch.is_empty() {
-            continue;
-        }
-
-        let batch_size = batch.len();
+/// Store one batch from the buffer, returning how many metrics were stored,
+/// or `None` when the breaker is open or the buffer empty
+#[allow(clippy::too_many_arguments)]
+pub async fn flush_batch(
+    buffer: &MetricsBuffer,
+    db: &Arc<dyn MetricsStore>,
+    breaker: &CircuitBreaker,
+    live_rollups: &LiveRollups,
+    ingest_stats: &IngestStats,
+    quarantine: &Quarantine,
+    notifier: &Arc<Notifier>,
+    metrics: &Metrics,
+    clock: &Clock,
+) -> Option<usize> {
+    if breaker.is_open() {
         debug!(
-            batch_size = batch_size,
-            "Flushing metrics batch to database"
+            buffered = buffer.len(),
+            "Database circuit breaker open, leaving metrics in buffer"
         );
+        return None;
+    }
 
-        // Insert batch into database
-        match db.insert_metrics_batch(&batch).await {
-            Ok(inserted) => {
-                if inserted < batch_size {
-                    error!(
-                        inserted = inserted,
-                        expected = batch_size,
-                        "Some metrics failed to insert"
+    // Pop batch from buffer
+    let batch = buffer.pop_batch(MAX_FLUSH_BATCH);
+    if batch.is_empty() {
+        return None;
+    }
+
+    let batch_size = batch.len();
+    debug!(
+        batch_size = batch_size,
+        "Flushing metrics batch to database"
+    );
+
+    // Insert batch into database
+    let started = Instant::now();
+    let result = db.insert_metrics_batch(&batch).await;
+    metrics.observe_flush(batch_size, started.elapsed());
+    match result {
+        Ok(outcome) => {
+            // Requeued batches are counted once they are stored, and
+            // dead-lettered ones never, so `source=live` matches the
+            // stored aggregates
+            live_rollups.record(&batch, clock.now());
+            // Schema drift alerts admins, and new query shapes become
+            // informational events
+            quarantine.report(&outcome.quarantined, clock.now());
+            if !outcome.new_shapes.is_empty() {
+                new_shapes::report(db, notifier, &outcome.new_shapes);
+            }
+            let inserted = outcome.inserted;
+            if inserted < batch_size {
+                error!(
+                    inserted = inserted,
+                    expected = batch_size,
+                    "Some metrics failed to insert"
+                );
+            } else {
+                debug!(inserted = inserted, "Metrics batch inserted successfully");
+            }
+            Some(inserted)
+        }
+        Err(e @ AppError::ServiceUnavailable(_)) => {
+            error!(error = %e, batch_size = batch_size, "Database unavailable, requeueing metrics batch");
+            metrics.inc_insert_errors(true);
+
+            // The store already retried, so retry on a later flush; only
+            // what no longer fits is lost, counted as dropped per workspace
+            let dropped = requeue(buffer, batch);
+            if !dropped.is_empty() {
+                warn!(
+                    dropped = dropped.len(),
+                    "Buffer full, metrics dropped while requeueing failed batch"
+                );
+                let mut per_workspace: HashMap<Uuid, i64> = HashMap::new();
+                for metric in &dropped {
+                    *per_workspace.entry(metric.workspace_id).or_default() += 1;
+                }
+                for (workspace_id, dropped) in per_workspace {
+                    ingest_stats.record(
+                        workspace_id,
+                        IngestCounts {
+                            dropped,
+                            ..IngestCounts::default()
+                        },
+                        clock.now(),
                     );
-                } else {
-                    debug!(inserted = inserted, "Metrics batch inserted successfully");
                 }
             }
-            Err(e) => {
-                error!(error = %e, batch_size = batch_size, "Failed to insert metrics batch");
-                // Note: metrics are lost if insert fails
-                // In production, consider retry logic or dead-letter queue
+            Some(0)
+        }
+        Err(e) => {
+            // Not transient, so retrying the same batch would fail again
+            error!(error = %e, batch_size = batch_size, "Failed to insert metrics batch, moving to dead-letter queue");
+            metrics.inc_insert_errors(false);
+            if let Err(dlq_err) = db.insert_dead_letters(&batch, &e.to_string()).await {
+                error!(error = %dlq_err, batch_size = batch_size, "Failed to dead-letter metrics batch, metrics lost");
             }
+            Some(0)
         }
     }
 }
 
+/// Push a batch back into the buffer, returning the metrics that did not fit
+fn requeue(buffer: &MetricsBuffer, batch: Vec<QueryMetric>) -> Vec<QueryMetric> {
+    batch
+        .into_iter()
+        .filter_map(|metric| buffer.try_push(metric).err())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::{QueryMetric, QueryStatus};
+    use crate::models::QueryStatus;
     use chrono::Utc;
     use uuid::Uuid;
 
@@ -82,4 +202,84 @@ mod tests {
         assert_eq!(batch.len(), 50);
         assert_eq!(buffer.len(), 50);
     }
+
+    #[test]
+    fn test_requeue_reports_overflow() {
+        let buffer = MetricsBuffer::new(3);
+        let batch: Vec<_> = (0..5).map(|_| create_test_metric()).collect();
+
+        assert_eq!(requeue(&buffer, batch).len(), 2);
+        assert_eq!(buffer.len(), 3);
+    }
+
+    #[cfg(feature = "memory-store")]
+    #[tokio::test]
+    async fn test_buffer_kept_while_breaker_open() {
+        use crate::auth::KeyCache;
+        use crate::models::IdGenerator;
+        use crate::routes::ws::broadcast_task;
+        use crate::services::fingerprint::DialectConfig;
+        use crate::state::AppState;
+        use crate::store::memory::MemoryStore;
+        use chrono::{DateTime, TimeDelta};
+
+        let clock = Clock::simulated("2026-01-01T00:00:00Z".parse().unwrap());
+        let state = AppState::new(
+            Arc::new(MemoryStore::with_clock(clock.clone())),
+            1000,
+            100,
+            None,
+            DialectConfig::default(),
+            KeyCache::new(Duration::ZERO, 10),
+            None,
+            IdGenerator::default(),
+            0,
+        );
+        let ws = Uuid::new_v4();
+        for _ in 0..10 {
+            let metric = QueryMetric {
+                workspace_id: ws,
+                ..create_test_metric()
+            };
+            state.metrics_buffer.try_push(metric).unwrap();
+        }
+        let breaker = Arc::new(CircuitBreaker::new(
+            1,
+            Duration::from_secs(60),
+            clock.clone(),
+        ));
+        breaker.record_failure();
+
+        let tasks = [
+            tokio::spawn(broadcast_task(state.clone(), clock.clone())),
+            tokio::spawn(aggregation_task(
+                state.metrics_buffer.clone(),
+                Arc::clone(&state.db),
+                breaker,
+                state.live_rollups.clone(),
+                state.ingest_stats.clone(),
+                Arc::clone(&state.quarantine),
+                Arc::clone(&state.notifier),
+                Arc::clone(&state.metrics),
+                clock.clone(),
+            )),
+        ];
+        // Two flush intervals of broadcast ticks
+        for _ in 0..100 {
+            clock.advance(TimeDelta::milliseconds(100));
+            for _ in 0..10 {
+                tokio::task::yield_now().await;
+            }
+        }
+
+        assert_eq!(state.metrics_buffer.len(), 10);
+        // Nothing was stored, so nothing is live either
+        assert!(state
+            .live_rollups
+            .get(ws, None, DateTime::<Utc>::MIN_UTC, DateTime::<Utc>::MAX_UTC)
+            .is_empty());
+        for task in tasks {
+            task.abort();
+        }
+    }
 }
@@ -1,131 +1,722 @@
 //! Anomaly detection background task
 
-use crate::db::{Database, QueryAnomaly};
-use crate::models::QueryMetric;
+use crate::clock::Clock;
+use crate::db::{
+    modified_z_score, AnomalyHint, AnomalyThreshold, HintKind, MetricsStats, QueryAnomaly,
+};
+use crate::models::{
+    AlertDestination, AnomalyMethod, AnomalySettings, IdGenerator, QueryMetric, Seasonality,
+};
+use crate::routes::metrics::Metrics;
+use crate::services::anomaly::{seasonal_slot, MAX_INTERVAL_SECS, MIN_INTERVAL_SECS};
+use crate::services::fingerprint::{metric_fingerprint, referenced_tables, SqlDialect};
+use crate::services::notify::{self, Notifier};
+use crate::services::severity::{self, SeverityLevel};
+use crate::store::MetricsStore;
+use crate::tasks::shards::{Shard, WorkspaceShards, INGEST_LAG};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::broadcast;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+/// Hours of call history used to estimate a query's call rate and the
+/// extra time an anomaly caused
+const CALL_RATE_WINDOW_HOURS: i64 = 24;
+
+/// Minutes before detection searched for correlated signals
+const HINT_WINDOW_MINUTES: i64 = 30;
+
+/// Failed queries needed in the hint window for an error spike
+const MIN_SPIKE_ERRORS: i64 = 5;
+
+/// Multiple of the usual error rate that counts as a spike
+const SPIKE_FACTOR: f64 = 3.0;
+
+/// Most related-anomaly hints attached to one anomaly
+const MAX_RELATED_HINTS: usize = 5;
+
+/// How early a visit may come and still be a full interval after the last
+/// run, so timer jitter doesn't put a run off by a whole visit
+const VISIT_SLACK: TimeDelta = TimeDelta::seconds(5);
+
+/// Ratio of the MAD to the standard deviation of normally distributed data
+const MAD_SCALE: f64 = 0.6745;
+
 /// Anomaly event for WebSocket broadcast
-#[allow(dead_code)]
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct AnomalyEvent {
     pub event_type: &'static str,
     pub anomaly: QueryAnomaly,
 }
 
-/// Background task that detects query anomalies based on execution time.
+/// Background task that flags queries far slower than usual for their workspace
 ///
-/// Runs every 60 seconds, computes mean and stddev of recent metrics,
-/// flags queries with z-score > 3, broadcasts to WebSocket clients,
-/// and stores anomalies in the database.
+/// Every 60 seconds it visits one shard of workspaces and runs detection for
+/// each whose interval has passed, storing, broadcasting, and alerting on
+/// the anomalies found.
+#[allow(clippy::too_many_arguments)]
 pub async fn anomaly_detection_task(
-    db: Arc<Database>,
-    broadcast_tx: broadcast::Sender<(Uuid, QueryMetric)>,
+    db: Arc<dyn MetricsStore>,
+    anomaly_tx: broadcast::Sender<QueryAnomaly>,
+    notifier: Arc<Notifier>,
+    ids: IdGenerator,
+    shards: WorkspaceShards,
+    defaults: AnomalySettings,
+    metrics: Arc<Metrics>,
+    clock: Clock,
 ) {
-    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    let mut ticks = shards.ticks(&clock, Duration::from_secs(MIN_INTERVAL_SECS as u64));
+    let mut last_runs: HashMap<Uuid, DateTime<Utc>> = HashMap::new();
 
-    info!("Anomaly detection task started (60s interval)");
+    info!(
+        shards = shards.count(),
+        interval_secs = defaults.interval_secs,
+        "Anomaly detection task started"
+    );
 
     loop {
-        interval.tick().await;
+        let shard = ticks.tick().await;
+        let now = clock.now();
+
+        if let Err(e) = detect_anomalies(
+            db.as_ref(),
+            &anomaly_tx,
+            &notifier,
+            ids,
+            shard,
+            &defaults,
+            &metrics,
+            &mut last_runs,
+            now,
+        )
+        .await
+        {
+            error!(error = %e, "Anomaly detection failed");
+        }
+    }
+}
 
-        // Get all workspaces
-        let workspaces = match db.get_all_workspace_ids().await {
-            Ok(w) => w,
+/// Detect and store anomalies across the shard's workspaces that are due a
+/// run and ingested since their last one
+#[allow(clippy::too_many_arguments)]
+async fn detect_anomalies(
+    db: &dyn MetricsStore,
+    anomaly_tx: &broadcast::Sender<QueryAnomaly>,
+    notifier: &Arc<Notifier>,
+    ids: IdGenerator,
+    shard: Shard,
+    defaults: &AnomalySettings,
+    metrics: &Metrics,
+    last_runs: &mut HashMap<Uuid, DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let longest = TimeDelta::seconds(MAX_INTERVAL_SECS as i64);
+    let last_ingest: HashMap<Uuid, DateTime<Utc>> = db
+        .get_last_ingest(now - longest - VISIT_SLACK - INGEST_LAG)
+        .await?
+        .into_iter()
+        .filter(|(id, _)| shard.contains(*id))
+        .collect();
+    // Workspaces that haven't ingested since their last run can't have new
+    // anomalies; those quiet for longer than any interval start over
+    last_runs.retain(|id, _| !shard.contains(*id) || last_ingest.contains_key(id));
+    if last_ingest.is_empty() {
+        return Ok(());
+    }
+
+    // Each due workspace's settings and the time since its last run, which
+    // its slow queries are looked for in
+    let workspace_ids: Vec<Uuid> = last_ingest.keys().copied().collect();
+    let overrides = db.get_anomaly_overrides(&workspace_ids).await?;
+    let mut due: HashMap<Uuid, (AnomalySettings, TimeDelta)> = HashMap::new();
+    for (workspace_id, ingested_at) in &last_ingest {
+        let settings =
+            defaults.with_overrides(&overrides.get(workspace_id).copied().unwrap_or_default());
+        let interval = TimeDelta::seconds(settings.interval_secs as i64);
+        let window = last_runs
+            .get(workspace_id)
+            .map_or(interval, |last| now - *last);
+        if window + VISIT_SLACK < interval {
+            continue;
+        }
+        last_runs.insert(*workspace_id, now);
+        if *ingested_at >= now - window - INGEST_LAG {
+            due.insert(*workspace_id, (settings, window));
+        }
+    }
+    if due.is_empty() {
+        return Ok(());
+    }
+
+    // Statistics from each workspace's most recent metrics, fetched for the
+    // whole shard at once so a run costs a fixed number of round trips plus
+    // the lookups for workspaces that actually have anomalies
+    let limits: HashMap<Uuid, i64> = due
+        .iter()
+        .map(|(id, (settings, _))| (*id, settings.lookback as i64))
+        .collect();
+    let mut stats = db.get_metrics_stats(&limits).await?;
+
+    // Seasonal workspaces are judged against the baseline task's statistics
+    // for the current UTC hour of the day or week instead, once it has
+    // enough samples
+    let slots: HashMap<Uuid, (Seasonality, i16)> = due
+        .iter()
+        .filter_map(|(id, (settings, _))| {
+            seasonal_slot(settings.seasonality, now).map(|slot| (*id, (settings.seasonality, slot)))
+        })
+        .collect();
+    if !slots.is_empty() {
+        for (workspace_id, baseline) in db.get_seasonal_baselines(&slots).await? {
+            let (settings, _) = &due[&workspace_id];
+            if baseline.count >= settings.min_samples as i64 {
+                stats.insert(workspace_id, baseline);
+            }
+        }
+    }
+
+    let mut thresholds: HashMap<Uuid, AnomalyThreshold> = HashMap::new();
+    for (workspace_id, stats) in &stats {
+        let (settings, window) = &due[workspace_id];
+        if stats.count < settings.min_samples as i64 {
+            // Not enough data for meaningful statistics
+            debug!(workspace_id = %workspace_id, count = stats.count, "Not enough data for anomaly detection");
+            continue;
+        }
+
+        let Some(threshold_ms) = threshold_ms(stats, settings) else {
+            // No spread, can't detect anomalies
+            continue;
+        };
+
+        debug!(
+            workspace_id = %workspace_id,
+            method = stats.method.as_str(),
+            mean = stats.mean,
+            stddev = stats.stddev,
+            median = stats.median,
+            mad = stats.mad,
+            threshold_ms = threshold_ms,
+            "Anomaly detection thresholds"
+        );
+        thresholds.insert(
+            *workspace_id,
+            AnomalyThreshold {
+                duration_ms: threshold_ms as i64,
+                since_seconds: window.num_seconds(),
+            },
+        );
+    }
+
+    if thresholds.is_empty() {
+        return Ok(());
+    }
+
+    // Metrics above their workspace's threshold since its last run
+    let mut slow_queries: HashMap<Uuid, Vec<QueryMetric>> = HashMap::new();
+    for metric in db.get_recent_metrics_for_anomaly(&thresholds).await? {
+        slow_queries
+            .entry(metric.workspace_id)
+            .or_default()
+            .push(metric);
+    }
+
+    let mut anomalies: Vec<QueryAnomaly> = Vec::new();
+    for (workspace_id, slow_queries) in slow_queries {
+        info!(
+            workspace_id = %workspace_id,
+            count = slow_queries.len(),
+            "Detected slow query anomalies"
+        );
+
+        match workspace_anomalies(
+            db,
+            workspace_id,
+            &stats[&workspace_id],
+            slow_queries,
+            ids,
+            now,
+        )
+        .await
+        {
+            Ok(found) => anomalies.extend(found),
             Err(e) => {
-                error!(error = %e, "Failed to get workspaces for anomaly detection");
+                error!(error = %e, workspace_id = %workspace_id, "Anomaly detection failed")
+            }
+        }
+    }
+
+    if anomalies.is_empty() {
+        return Ok(());
+    }
+
+    // Store every workspace's anomalies in one statement
+    match db.insert_anomalies(&anomalies).await {
+        Ok(_) => {
+            metrics.inc_anomalies(anomalies.len() as u64);
+            // Broadcast to WebSocket clients; sending fails only when none
+            // are subscribed
+            for anomaly in &anomalies {
+                let _ = anomaly_tx.send(anomaly.clone());
+            }
+            notify_destinations(db, notifier, &anomalies).await;
+        }
+        Err(e) => warn!(error = %e, count = anomalies.len(), "Failed to store anomalies"),
+    }
+
+    // Severe anomalies are logged at warn level for log-based alert routing
+    for anomaly in &anomalies {
+        let level = anomaly.severity_level;
+        if level >= SeverityLevel::High {
+            warn!(
+                workspace_id = %anomaly.workspace_id,
+                metric_id = %anomaly.metric_id,
+                fingerprint = %anomaly.fingerprint,
+                severity = anomaly.severity,
+                severity_level = level.as_str(),
+                estimated_extra_ms = anomaly.estimated_extra_ms,
+                z_score = anomaly.z_score,
+                duration_ms = anomaly.duration_ms,
+                hints = anomaly.hints.len(),
+                "Severe query anomaly"
+            );
+        } else {
+            debug!(
+                workspace_id = %anomaly.workspace_id,
+                metric_id = %anomaly.metric_id,
+                severity = anomaly.severity,
+                z_score = anomaly.z_score,
+                duration_ms = anomaly.duration_ms,
+                "Anomaly detected and recorded"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Send anomalies to their workspaces' enabled alert destinations whose
+/// `min_severity` they reach, in the background so retries don't hold up
+/// detection
+async fn notify_destinations(
+    db: &dyn MetricsStore,
+    notifier: &Arc<Notifier>,
+    anomalies: &[QueryAnomaly],
+) {
+    let mut by_workspace: HashMap<Uuid, Vec<&QueryAnomaly>> = HashMap::new();
+    for anomaly in anomalies {
+        by_workspace
+            .entry(anomaly.workspace_id)
+            .or_default()
+            .push(anomaly);
+    }
+
+    for (workspace_id, anomalies) in by_workspace {
+        let destinations: Vec<AlertDestination> = match db
+            .list_alert_destinations(workspace_id)
+            .await
+        {
+            Ok(d) => d.into_iter().filter(|d| d.enabled).collect(),
+            Err(e) => {
+                error!(error = %e, workspace_id = %workspace_id, "Failed to list alert destinations for anomalies");
                 continue;
             }
         };
+        if destinations.is_empty() {
+            continue;
+        }
 
-        for workspace_id in workspaces {
-            if let Err(e) = detect_anomalies_for_workspace(&db, workspace_id, &broadcast_tx).await {
-                error!(error = %e, workspace_id = %workspace_id, "Anomaly detection failed");
+        let payloads: Vec<(SeverityLevel, Value)> = anomalies
+            .iter()
+            .map(|anomaly| {
+                (
+                    anomaly.severity_level,
+                    notify::anomaly_payload(anomaly, false),
+                )
+            })
+            .collect();
+        let notifier = Arc::clone(notifier);
+        tokio::spawn(async move {
+            for (level, payload) in &payloads {
+                for destination in destinations.iter().filter(|d| *level >= d.min_severity) {
+                    notifier
+                        .deliver(destination, notify::ANOMALY_EVENT, payload, false)
+                        .await;
+                }
             }
-        }
+        });
     }
 }
 
-/// Detect anomalies for a single workspace
-async fn detect_anomalies_for_workspace(
-    db: &Database,
+/// Score a workspace's slow queries and attach hints, without storing them
+async fn workspace_anomalies(
+    db: &dyn MetricsStore,
     workspace_id: Uuid,
-    _broadcast_tx: &broadcast::Sender<(Uuid, QueryMetric)>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    // Get statistics from last 1000 metrics
-    let stats = db.get_metrics_stats(workspace_id, 1000).await?;
+    stats: &MetricsStats,
+    slow_queries: Vec<QueryMetric>,
+    ids: IdGenerator,
+    now: DateTime<Utc>,
+) -> crate::error::Result<Vec<QueryAnomaly>> {
+    // Call rates over the last day weigh each anomaly's severity
+    let fingerprints: Vec<String> = slow_queries.iter().map(metric_fingerprint).collect();
+    let mut unique = fingerprints.clone();
+    unique.sort();
+    unique.dedup();
+    let since = now - chrono::Duration::hours(CALL_RATE_WINDOW_HOURS);
+    let call_counts = db
+        .get_fingerprint_call_counts(workspace_id, &unique, since)
+        .await?;
 
-    if stats.count < 100 {
-        // Not enough data for meaningful statistics
-        debug!(workspace_id = %workspace_id, count = stats.count, "Not enough data for anomaly detection");
-        return Ok(());
+    let (baseline, spread) = match stats.method {
+        AnomalyMethod::ZScore => (stats.mean, stats.stddev),
+        AnomalyMethod::Mad => (stats.median, stats.mad),
+    };
+    let mut anomalies: Vec<QueryAnomaly> = slow_queries
+        .into_iter()
+        .zip(fingerprints)
+        .map(|(metric, fingerprint)| {
+            let duration = metric.duration_ms as f64;
+            let z_score = match stats.method {
+                AnomalyMethod::ZScore => (duration - baseline) / spread,
+                AnomalyMethod::Mad => modified_z_score(duration, baseline, spread).unwrap_or(0.0),
+            };
+            // Rollups lag behind ingest; the anomaly itself is at least one call
+            let calls = call_counts.get(&fingerprint).copied().unwrap_or(0).max(1);
+            let score = severity::score(
+                z_score,
+                duration,
+                baseline,
+                calls as f64 / CALL_RATE_WINDOW_HOURS as f64,
+            );
+            QueryAnomaly {
+                id: ids.generate(),
+                workspace_id: metric.workspace_id,
+                service_id: metric.service_id,
+                metric_id: metric.id,
+                fingerprint,
+                query_text: metric.query_text,
+                duration_ms: metric.duration_ms as i64,
+                mean_duration_ms: baseline as i64,
+                stddev_duration_ms: spread as i64,
+                z_score,
+                method: stats.method,
+                severity: score,
+                severity_level: severity::level(score, z_score, duration),
+                // This execution's own excess: the query's other calls ran
+                // at their usual speed, or are anomalies of their own
+                estimated_extra_ms: severity::extra_time_ms(duration, baseline, 1.0) as i64,
+                hints: Vec::new(),
+            }
+        })
+        .collect();
+
+    attach_hints(db, workspace_id, &mut anomalies, now).await;
+    Ok(anomalies)
+}
+
+/// Duration in milliseconds above which a query is anomalous under the
+/// workspace's method, `None` if its durations have no spread to measure by
+///
+/// The MAD method measures from the median and median absolute deviation,
+/// so a few extreme outliers can't inflate the spread and hide the
+/// anomalies after them.
+fn threshold_ms(stats: &MetricsStats, settings: &AnomalySettings) -> Option<f64> {
+    match stats.method {
+        AnomalyMethod::ZScore => {
+            (stats.stddev > 0.0).then_some(stats.mean + settings.z_threshold * stats.stddev)
+        }
+        AnomalyMethod::Mad => (stats.mad > 0.0)
+            .then_some(stats.median + settings.mad_threshold * stats.mad / MAD_SCALE),
     }
+}
 
-    if stats.stddev <= 0.0 {
-        // No variance, can't detect anomalies
-        return Ok(());
+/// Attach root-cause hints to freshly detected anomalies
+///
+/// Looks for correlated signals in the hint window: workspace annotations
+/// (deploys, config changes, incidents), error spikes in the anomaly's
+/// service, and other anomalous fingerprints touching the same tables.
+/// Lookups that fail only cost their hints.
+async fn attach_hints(
+    db: &dyn MetricsStore,
+    workspace_id: Uuid,
+    anomalies: &mut [QueryAnomaly],
+    now: DateTime<Utc>,
+) {
+    let window_start = now - chrono::Duration::minutes(HINT_WINDOW_MINUTES);
+
+    let annotation_hints: Vec<AnomalyHint> = match db
+        .list_annotations(workspace_id, window_start, now)
+        .await
+    {
+        Ok(annotations) => annotations
+            .into_iter()
+            .map(|a| AnomalyHint {
+                kind: HintKind::Annotation,
+                message: format!("Annotation \"{}\" at {}", a.title, a.starts_at.to_rfc3339()),
+                reference: Some(a.id.to_string()),
+            })
+            .collect(),
+        Err(e) => {
+            warn!(error = %e, workspace_id = %workspace_id, "Failed to load annotations for hints");
+            Vec::new()
+        }
+    };
+
+    let mut spikes: HashMap<Uuid, Option<AnomalyHint>> = HashMap::new();
+    for anomaly in anomalies.iter() {
+        if spikes.contains_key(&anomaly.service_id) {
+            continue;
+        }
+        let hint = error_spike(db, workspace_id, anomaly.service_id, window_start, now)
+            .await
+            .unwrap_or_else(|e| {
+                warn!(error = %e, service_id = %anomaly.service_id, "Failed to check error spike");
+                None
+            });
+        spikes.insert(anomaly.service_id, hint);
     }
 
-    // Calculate threshold: mean + 3 * stddev
-    let threshold_ms = (stats.mean + 3.0 * stats.stddev) as i64;
+    // Fingerprints anomalous in this run or earlier in the window
+    let mut texts: Vec<(String, String)> = Vec::new();
+    let recent = db
+        .get_anomalies(workspace_id, window_start, SeverityLevel::Low, None, 100)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(error = %e, workspace_id = %workspace_id, "Failed to load recent anomalies for hints");
+            Vec::new()
+        });
+    let found = anomalies
+        .iter()
+        .map(|a| (&a.fingerprint, &a.query_text))
+        .chain(recent.iter().map(|a| (&a.fingerprint, &a.query_text)));
+    for (fingerprint, query_text) in found {
+        if !texts.iter().any(|(f, _)| f == fingerprint) {
+            texts.push((fingerprint.clone(), query_text.clone()));
+        }
+    }
+    // Parsing is CPU-bound, so keep it off the async workers
+    let anomalous: Vec<(String, Vec<String>)> = tokio::task::spawn_blocking(move || {
+        texts
+            .into_iter()
+            .map(|(fingerprint, query_text)| {
+                let tables = referenced_tables(&query_text, SqlDialect::Generic);
+                (fingerprint, tables)
+            })
+            .collect()
+    })
+    .await
+    .unwrap_or_else(|e| {
+        warn!(error = %e, workspace_id = %workspace_id, "Failed to parse anomalous queries for hints");
+        Vec::new()
+    });
 
-    debug!(
-        workspace_id = %workspace_id,
-        mean = stats.mean,
-        stddev = stats.stddev,
-        threshold_ms = threshold_ms,
-        "Anomaly detection thresholds"
-    );
+    for anomaly in anomalies.iter_mut() {
+        let mut hints = annotation_hints.clone();
+        hints.extend(spikes.get(&anomaly.service_id).cloned().flatten());
+        hints.extend(related_anomaly_hints(&anomaly.fingerprint, &anomalous));
+        anomaly.hints = hints;
+    }
+}
 
-    // Get recent metrics above threshold (last 60 seconds)
-    let slow_queries = db
-        .get_recent_metrics_for_anomaly(workspace_id, 60, threshold_ms)
+/// Hint for failed queries in a service running well above their usual rate
+async fn error_spike(
+    db: &dyn MetricsStore,
+    workspace_id: Uuid,
+    service_id: Uuid,
+    window_start: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> crate::error::Result<Option<AnomalyHint>> {
+    let recent = db
+        .get_top_errors(workspace_id, service_id, None, window_start, now, 100)
         .await?;
+    let recent_count: i64 = recent.iter().map(|e| e.count).sum();
+    if recent_count < MIN_SPIKE_ERRORS {
+        return Ok(None);
+    }
 
-    if slow_queries.is_empty() {
-        return Ok(());
+    let baseline_start = now - chrono::Duration::hours(CALL_RATE_WINDOW_HOURS);
+    let baseline: i64 = db
+        .get_top_errors(
+            workspace_id,
+            service_id,
+            None,
+            baseline_start,
+            window_start,
+            100,
+        )
+        .await?
+        .iter()
+        .map(|e| e.count)
+        .sum();
+    let windows = (window_start - baseline_start).num_minutes() as f64 / HINT_WINDOW_MINUTES as f64;
+    let usual = baseline as f64 / windows;
+    if !is_error_spike(recent_count, usual) {
+        return Ok(None);
     }
 
-    info!(
-        workspace_id = %workspace_id,
-        count = slow_queries.len(),
-        "Detected slow query anomalies"
-    );
+    Ok(recent.first().map(|top| AnomalyHint {
+        kind: HintKind::ErrorSpike,
+        message: format!(
+            "{} failed queries in this service in the last {} minutes (usually about {:.0}); most common error: {}",
+            recent_count,
+            HINT_WINDOW_MINUTES,
+            usual,
+            top.error_message.as_deref().unwrap_or("unknown")
+        ),
+        reference: Some(top.fingerprint.clone()),
+    }))
+}
 
-    // Process each anomaly
-    for metric in slow_queries {
-        let z_score = (metric.duration_ms as f64 - stats.mean) / stats.stddev;
-
-        let anomaly = QueryAnomaly {
-            workspace_id: metric.workspace_id,
-            service_id: metric.service_id,
-            metric_id: metric.id,
-            query_text: metric.query_text.clone(),
-            duration_ms: metric.duration_ms as i64,
-            mean_duration_ms: stats.mean as i64,
-            stddev_duration_ms: stats.stddev as i64,
-            z_score,
+/// Whether `recent` errors in the hint window are a spike over `usual`
+fn is_error_spike(recent: i64, usual: f64) -> bool {
+    recent >= MIN_SPIKE_ERRORS && recent as f64 > SPIKE_FACTOR * usual.max(1.0)
+}
+
+/// Hints for other anomalous fingerprints sharing a table with `fingerprint`
+fn related_anomaly_hints(
+    fingerprint: &str,
+    anomalous: &[(String, Vec<String>)],
+) -> Vec<AnomalyHint> {
+    let Some((_, tables)) = anomalous.iter().find(|(f, _)| f == fingerprint) else {
+        return Vec::new();
+    };
+    anomalous
+        .iter()
+        .filter(|(other, _)| other != fingerprint)
+        .filter_map(|(other, other_tables)| {
+            let shared: Vec<&str> = tables
+                .iter()
+                .filter(|t| other_tables.contains(t))
+                .map(String::as_str)
+                .collect();
+            (!shared.is_empty()).then(|| AnomalyHint {
+                kind: HintKind::RelatedAnomaly,
+                message: format!(
+                    "Query {} is also anomalous and uses {}",
+                    other,
+                    shared.join(", ")
+                ),
+                reference: Some(other.clone()),
+            })
+        })
+        .take(MAX_RELATED_HINTS)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mad_threshold_ignores_outliers() {
+        // 99 queries around 100ms and one 100-second outlier
+        let mut durations: Vec<f64> = (0..99).map(|i| 90.0 + (i % 21) as f64).collect();
+        durations.push(100_000.0);
+        let count = durations.len() as f64;
+        let mean = durations.iter().sum::<f64>() / count;
+        let stddev =
+            (durations.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / (count - 1.0)).sqrt();
+        let mut stats = MetricsStats {
+            mean,
+            stddev,
+            median: 100.0,
+            mad: 5.0,
+            count: count as i64,
+            method: AnomalyMethod::ZScore,
         };
 
-        // Store anomaly in database
-        if let Err(e) = db.insert_anomaly(&anomaly).await {
-            warn!(error = %e, metric_id = %metric.id, "Failed to store anomaly");
-        }
+        let settings = AnomalySettings::default();
 
-        // Broadcast to WebSocket clients
-        // Note: We reuse the existing broadcast channel, but in a more complete
-        // implementation, we might have a separate anomaly broadcast channel
-        debug!(
-            workspace_id = %workspace_id,
-            metric_id = %metric.id,
-            z_score = z_score,
-            duration_ms = metric.duration_ms,
-            "Anomaly detected and recorded"
-        );
+        // The outlier pushes the z-score threshold past 30 seconds
+        assert!(threshold_ms(&stats, &settings).unwrap() > 30_000.0);
+
+        // The MAD threshold stays close to the usual durations
+        stats.method = AnomalyMethod::Mad;
+        let threshold = threshold_ms(&stats, &settings).unwrap();
+        assert!((threshold - (100.0 + 3.5 * 5.0 / 0.6745)).abs() < 1e-9);
+
+        // A workspace's own threshold moves it
+        let strict = AnomalySettings {
+            mad_threshold: 7.0,
+            ..settings
+        };
+        assert!(threshold_ms(&stats, &strict).unwrap() > threshold);
+
+        // No spread, nothing to measure by
+        stats.mad = 0.0;
+        assert_eq!(threshold_ms(&stats, &settings), None);
     }
 
-    Ok(())
+    #[test]
+    fn test_error_spike_threshold() {
+        assert!(!is_error_spike(4, 0.0));
+        assert!(is_error_spike(5, 0.0));
+        assert!(!is_error_spike(30, 10.0));
+        assert!(is_error_spike(31, 10.0));
+    }
+
+    #[test]
+    fn test_related_anomalies_share_a_table() {
+        let anomalous = vec![
+            (
+                "a".to_string(),
+                vec!["orders".to_string(), "users".to_string()],
+            ),
+            ("b".to_string(), vec!["orders".to_string()]),
+            ("c".to_string(), vec!["invoices".to_string()]),
+        ];
+        let hints = related_anomaly_hints("a", &anomalous);
+        assert_eq!(hints.len(), 1);
+        assert_eq!(hints[0].kind, HintKind::RelatedAnomaly);
+        assert_eq!(hints[0].reference.as_deref(), Some("b"));
+        assert!(hints[0].message.ends_with("uses orders"));
+    }
+
+    #[cfg(feature = "memory-store")]
+    #[tokio::test]
+    async fn test_extra_time_counts_the_anomalous_execution_only() {
+        use crate::models::QueryStatus;
+        use crate::store::memory::MemoryStore;
+
+        let store = MemoryStore::new();
+        let ws = store.add_workspace("test", "key");
+        let metric = |duration_ms| {
+            QueryMetric::new(
+                ws.id,
+                Uuid::new_v4(),
+                "SELECT * FROM orders WHERE id = 1".to_string(),
+                QueryStatus::Success,
+                duration_ms,
+                Utc::now(),
+            )
+        };
+        // A hot query: a thousand calls at its usual speed, then one slow one
+        let usual: Vec<QueryMetric> = (0..1000).map(|_| metric(10)).collect();
+        store.insert_metrics_batch(&usual).await.unwrap();
+        let stats = MetricsStats {
+            mean: 10.0,
+            stddev: 2.0,
+            median: 10.0,
+            mad: 1.0,
+            count: 1000,
+            method: AnomalyMethod::ZScore,
+        };
+
+        let anomalies = workspace_anomalies(
+            &store,
+            ws.id,
+            &stats,
+            vec![metric(510)],
+            IdGenerator::default(),
+            Utc::now(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].estimated_extra_ms, 500);
+    }
 }
@@ -0,0 +1,197 @@
+//! Baseline task - recomputes seasonal anomaly baselines
+
+use crate::clock::Clock;
+use crate::error::Result;
+use crate::models::AnomalySettings;
+use crate::services::anomaly::baseline_window;
+use crate::store::MetricsStore;
+use crate::tasks::shards::{Shard, WorkspaceShards};
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+/// Background task that keeps seasonal anomaly baselines current.
+///
+/// Visits every workspace every hour, one shard of workspaces at a time.
+/// Each one that ingested since its last visit has the duration statistics
+/// of every hour of the day (over the last 14 days) or of the week (over the
+/// last 28 days) recomputed, whichever its seasonality asks for; workspaces
+/// without seasonality have their baselines cleared.
+pub async fn baseline_task(
+    db: Arc<dyn MetricsStore>,
+    defaults: AnomalySettings,
+    shards: WorkspaceShards,
+    clock: Clock,
+) {
+    let mut ticks = shards.ticks(&clock, Duration::from_secs(60 * 60));
+
+    info!(
+        shards = shards.count(),
+        "Baseline task started (1h interval)"
+    );
+
+    loop {
+        let shard = ticks.tick().await;
+        let now = clock.now();
+
+        if let Err(e) =
+            refresh_baselines(db.as_ref(), &defaults, shard, ticks.active_since(now), now).await
+        {
+            error!(error = %e, "Failed to refresh seasonal baselines");
+        }
+    }
+}
+
+/// Recompute the baselines of the shard's workspaces that ingested since
+/// `active_since`
+async fn refresh_baselines(
+    db: &dyn MetricsStore,
+    defaults: &AnomalySettings,
+    shard: Shard,
+    active_since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    // A quiet workspace keeps the baselines of its last visit. Only anomaly
+    // detection reads them, and it has nothing to judge until the workspace
+    // ingests again, after which the next visit brings them up to date.
+    let workspace_ids: Vec<Uuid> = db
+        .get_last_ingest(active_since)
+        .await?
+        .into_keys()
+        .filter(|id| shard.contains(*id))
+        .collect();
+    let overrides = db.get_anomaly_overrides(&workspace_ids).await?;
+
+    for (workspace_id, overrides) in overrides {
+        let seasonality = defaults.with_overrides(&overrides).seasonality;
+        match db
+            .refresh_seasonal_baselines(
+                workspace_id,
+                seasonality,
+                now - baseline_window(seasonality),
+            )
+            .await
+        {
+            Ok(slots) => debug!(
+                workspace_id = %workspace_id,
+                seasonality = seasonality.as_str(),
+                slots,
+                "Seasonal baselines refreshed"
+            ),
+            Err(e) => {
+                error!(error = %e, workspace_id = %workspace_id, "Failed to refresh seasonal baselines")
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "memory-store"))]
+mod tests {
+    use super::*;
+    use crate::db::IngestionMinute;
+    use crate::models::{AnomalyOverrides, QueryMetric, QueryStatus, Seasonality};
+    use crate::store::memory::{MemoryStore, DEFAULT_WORKSPACE_ID};
+    use chrono::TimeDelta;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_baselines_follow_seasonality() {
+        let clock = Clock::simulated("2026-01-01T00:00:00Z".parse().unwrap());
+        let store = MemoryStore::with_clock(clock.clone());
+        let service_id = Uuid::new_v4();
+
+        // A day of quick queries, except for a slow batch window at 02:00
+        for hour in 0..24 {
+            let duration_ms = if hour == 2 { 5_000 } else { 10 };
+            let metrics: Vec<QueryMetric> = (0..10)
+                .map(|_| {
+                    QueryMetric::new(
+                        DEFAULT_WORKSPACE_ID,
+                        service_id,
+                        "SELECT 1".to_string(),
+                        QueryStatus::Success,
+                        duration_ms,
+                        clock.now(),
+                    )
+                })
+                .collect();
+            store.insert_metrics_batch(&metrics).await.unwrap();
+            let stats = IngestionMinute {
+                minute: clock.now(),
+                accepted: 10,
+                dropped: 0,
+                deduplicated: 0,
+            };
+            store
+                .record_ingestion_stats(&[(DEFAULT_WORKSPACE_ID, stats)])
+                .await
+                .unwrap();
+            clock.advance(TimeDelta::hours(1));
+        }
+
+        let shard = WorkspaceShards::new(1)
+            .ticks(&clock, Duration::from_secs(60 * 60))
+            .tick()
+            .await;
+        let refresh = || {
+            let now = clock.now();
+            let store = &store;
+            async move {
+                refresh_baselines(
+                    store,
+                    &AnomalySettings::default(),
+                    shard,
+                    now - TimeDelta::hours(2),
+                    now,
+                )
+                .await
+                .unwrap()
+            }
+        };
+        let daily = AnomalyOverrides {
+            seasonality: Some(Seasonality::Daily),
+            ..Default::default()
+        };
+        store
+            .set_anomaly_overrides(DEFAULT_WORKSPACE_ID, &daily)
+            .await
+            .unwrap();
+        refresh().await;
+
+        let slot = |hour| HashMap::from([(DEFAULT_WORKSPACE_ID, (Seasonality::Daily, hour))]);
+        let batch = store.get_seasonal_baselines(&slot(2)).await.unwrap();
+        assert_eq!(batch[&DEFAULT_WORKSPACE_ID].count, 10);
+        assert_eq!(batch[&DEFAULT_WORKSPACE_ID].median, 5_000.0);
+        let quiet = store.get_seasonal_baselines(&slot(3)).await.unwrap();
+        assert_eq!(quiet[&DEFAULT_WORKSPACE_ID].median, 10.0);
+
+        // Turning seasonality off clears them
+        store
+            .set_anomaly_overrides(DEFAULT_WORKSPACE_ID, &AnomalyOverrides::default())
+            .await
+            .unwrap();
+        refresh().await;
+        assert!(store
+            .get_seasonal_baselines(&slot(2))
+            .await
+            .unwrap()
+            .is_empty());
+
+        // A workspace that stopped ingesting keeps its baselines as they are
+        clock.advance(TimeDelta::hours(3));
+        store
+            .set_anomaly_overrides(DEFAULT_WORKSPACE_ID, &daily)
+            .await
+            .unwrap();
+        refresh().await;
+        assert!(store
+            .get_seasonal_baselines(&slot(2))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+//! Clustering task - groups embedded queries into workload families
+
+use crate::clock::Clock;
+use crate::db::{EmbeddedQuery, EmbeddingModel, NewQueryCluster};
+use crate::error::{AppError, Result};
+use crate::services::embedder::Embedder;
+use crate::services::projection;
+use crate::store::MetricsStore;
+use crate::tasks::shards::{Shard, WorkspaceShards};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+/// Default number of clusters per workspace
+pub const DEFAULT_CLUSTERS: usize = 50;
+
+/// Most embedded queries clustered per workspace; the most-called are kept
+const MAX_QUERIES: i64 = 50_000;
+
+/// Window call counts are taken over to pick and order the queries
+const CALL_WINDOW: TimeDelta = TimeDelta::days(7);
+
+/// Background task that clusters each workspace's embedded queries.
+///
+/// Visits every workspace every 6 hours, one shard of workspaces at a time
+/// starting at startup. Every workspace with embeddings from the primary
+/// provider's model has up to 50,000 of them (the most-called over the last
+/// week first) grouped by k-means into at most `clusters` families, each
+/// labeled with the tables its queries reference most and represented by
+/// the query nearest its center. The result replaces the workspace's
+/// previous clusters; traffic per cluster is computed when they are read.
+/// Workspaces are re-clustered whether or not they ingested: re-embedding
+/// moves their queries to a new model without any ingest, and the week of
+/// calls that picks and orders them keeps moving.
+pub async fn clustering_task(
+    db: Arc<dyn MetricsStore>,
+    embedder: Option<Arc<Embedder>>,
+    clusters: usize,
+    shards: WorkspaceShards,
+    clock: Clock,
+) {
+    let Some(embedder) = embedder else {
+        warn!("Embedding service not configured, clustering task disabled");
+        return;
+    };
+
+    let mut ticks = shards.ticks(&clock, Duration::from_secs(6 * 60 * 60));
+    let clusters = clusters.max(1);
+    info!(
+        clusters,
+        shards = shards.count(),
+        "Clustering task started (6h interval)"
+    );
+
+    loop {
+        let shard = ticks.tick().await;
+
+        if let Err(e) =
+            cluster_workspaces(db.as_ref(), &embedder.model(), clusters, shard, clock.now()).await
+        {
+            error!(error = %e, "Failed to cluster queries");
+        }
+    }
+}
+
+/// Re-cluster the shard's workspaces with embeddings from `model`,
+/// returning how many were clustered
+pub async fn cluster_workspaces(
+    db: &dyn MetricsStore,
+    model: &EmbeddingModel,
+    clusters: usize,
+    shard: Shard,
+    now: DateTime<Utc>,
+) -> Result<usize> {
+    let workspaces = db.get_embedded_workspaces(model).await?;
+    let mut clustered = 0;
+    for workspace_id in workspaces.into_iter().filter(|id| shard.contains(*id)) {
+        match cluster_workspace(db, workspace_id, model, clusters, now).await {
+            Ok(count) => {
+                clustered += 1;
+                debug!(workspace_id = %workspace_id, clusters = count, "Queries clustered");
+            }
+            Err(e) => {
+                error!(error = %e, workspace_id = %workspace_id, "Failed to cluster queries")
+            }
+        }
+    }
+
+    info!(workspaces = clustered, "Clustering finished");
+    Ok(clustered)
+}
+
+/// Cluster one workspace's embedded queries, returning how many clusters
+/// were stored
+async fn cluster_workspace(
+    db: &dyn MetricsStore,
+    workspace_id: Uuid,
+    model: &EmbeddingModel,
+    clusters: usize,
+    now: DateTime<Utc>,
+) -> Result<usize> {
+    let queries = db
+        .list_query_embeddings(workspace_id, Some(model), now - CALL_WINDOW, MAX_QUERIES)
+        .await?;
+
+    // k-means over tens of thousands of vectors is CPU-bound, so keep it off
+    // the async workers
+    let found = tokio::task::spawn_blocking(move || families(queries, clusters))
+        .await
+        .map_err(|e| AppError::InternalError(format!("Clustering failed: {}", e)))?;
+
+    db.replace_query_clusters(workspace_id, model, now, &found)
+        .await?;
+    Ok(found.len())
+}
+
+/// Group queries, most important first, into at most `k` clusters, largest
+/// first
+fn families(queries: Vec<EmbeddedQuery>, k: usize) -> Vec<NewQueryCluster> {
+    let vectors: Vec<Vec<f32>> = queries.iter().map(|q| q.embedding.clone()).collect();
+    let assignments = projection::kmeans(&vectors, k);
+    let representatives = projection::representatives(&vectors, &assignments);
+    drop(vectors);
+
+    representatives
+        .into_iter()
+        .enumerate()
+        .map(|(id, representative)| {
+            let members: Vec<&EmbeddedQuery> = queries
+                .iter()
+                .zip(&assignments)
+                .filter(|(_, c)| **c == id)
+                .map(|(q, _)| q)
+                .collect();
+            NewQueryCluster {
+                label: projection::cluster_label(members.iter().map(|q| q.sql_query.as_str())),
+                representative_hash: queries[representative].query_hash.clone(),
+                representative_query: queries[representative].sql_query.clone(),
+                members: members.iter().map(|q| q.query_hash.clone()).collect(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "memory-store"))]
+mod tests {
+    use super::*;
+    use crate::db::NewQueryEmbedding;
+    use crate::store::memory::{MemoryStore, DEFAULT_WORKSPACE_ID};
+
+    #[tokio::test]
+    async fn test_queries_are_grouped_into_families() {
+        let store = MemoryStore::new();
+        let model = EmbeddingModel {
+            name: "test".into(),
+            version: "1".into(),
+        };
+        let embedding = |query: &str, embedding: Vec<f32>| NewQueryEmbedding {
+            query_hash: format!("hash-{}", query),
+            sql_query: query.into(),
+            embedding,
+        };
+        store
+            .insert_query_embeddings(
+                DEFAULT_WORKSPACE_ID,
+                &model,
+                &[
+                    embedding("SELECT * FROM orders WHERE id = $1", vec![1.0, 0.0]),
+                    embedding("SELECT * FROM orders WHERE user_id = $1", vec![0.9, 0.1]),
+                    embedding("UPDATE orders SET status = $1", vec![0.95, 0.05]),
+                    embedding("DELETE FROM sessions WHERE expires_at < $1", vec![0.0, 1.0]),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let now = Utc::now();
+        let shard = WorkspaceShards::new(1)
+            .ticks(&Clock::System, Duration::from_secs(60))
+            .tick()
+            .await;
+        assert_eq!(
+            cluster_workspaces(&store, &model, 2, shard, now)
+                .await
+                .unwrap(),
+            1
+        );
+        let clustered = store
+            .get_query_clusters(DEFAULT_WORKSPACE_ID, now - CALL_WINDOW, 10)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(clustered.model, model);
+
+        let mut clusters = clustered.clusters;
+        clusters.sort_by_key(|c| c.id);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].size, 3);
+        assert_eq!(clusters[0].label.as_deref(), Some("orders"));
+        assert_eq!(
+            clusters[0].representative_query,
+            "UPDATE orders SET status = $1"
+        );
+        assert_eq!(clusters[1].size, 1);
+        assert_eq!(clusters[1].label.as_deref(), Some("sessions"));
+    }
+}
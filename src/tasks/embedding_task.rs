@@ -1,17 +1,57 @@
 //! Embedding background task - processes queries and generates embeddings
 
-use crate::db::Database;
-use crate::services::embedding::EmbeddingService;
+use crate::clock::Clock;
+use crate::db::{EmbeddingModel, EmbeddingSkip, NewQueryEmbedding};
+use crate::routes::metrics::Metrics;
+use crate::services::embedder::Embedder;
+use crate::store::MetricsStore;
+use crate::tasks::shards::WorkspaceShards;
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+/// Most queries embedded per workspace per run, unless `EMBEDDING_RUN_LIMIT`
+/// says otherwise
+pub const DEFAULT_RUN_LIMIT: i64 = 100;
+
+/// How often every workspace is visited, unless `EMBEDDING_INTERVAL_SECS`
+/// says otherwise
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Queries embedded per provider call, unless `EMBEDDING_BATCH_SIZE` says otherwise
+pub const DEFAULT_BATCH_SIZE: usize = 32;
 
 /// Background task that embeds queries that haven't been processed yet.
 ///
-/// Runs every 30 seconds, fetches unembedded queries, generates embeddings,
-/// and stores them in the database for similarity search.
-pub async fn embedding_task(db: Arc<Database>, embedding_service: Option<Arc<EmbeddingService>>) {
-    let service = match embedding_service {
+/// Queries over the embedder's byte limit are skipped: they are recorded in
+/// `query_embedding_skips`, counted in `queryvault_embedding_skipped_total`,
+/// and not fetched again while they stay over the limit.
+///
+/// Visits every workspace every `interval`, one shard at a time, fetches up
+/// to `run_limit` unembedded queries, most-executed and then slowest first so
+/// hot queries aren't stuck behind a burst of one-off statements, embeds
+/// them `batch_size` at a time with one provider
+/// call per chunk, and stores the workspace's embeddings in the database in
+/// one statement per model (normally one; the fallback's model too when it
+/// stood in) for similarity search. A workspace's run stops at the first
+/// chunk no provider could embed, keeping the chunks before it. Only
+/// workspaces that ingested since their last visit, or still had queries
+/// left over from it, are looked at. Those with queries left over are the
+/// backlog reported in `queryvault_embedding_backlog_workspaces`.
+#[allow(clippy::too_many_arguments)]
+pub async fn embedding_task(
+    db: Arc<dyn MetricsStore>,
+    embedder: Option<Arc<Embedder>>,
+    shards: WorkspaceShards,
+    batch_size: usize,
+    run_limit: i64,
+    interval: Duration,
+    metrics: Arc<Metrics>,
+    clock: Clock,
+) {
+    let embedder = match embedder {
         Some(s) => s,
         None => {
             warn!("Embedding service not configured, embedding task disabled");
@@ -19,33 +59,58 @@ pub async fn embedding_task(db: Arc<Database>, embedding_service: Option<Arc<Emb
         }
     };
 
-    let mut interval = tokio::time::interval(Duration::from_secs(30));
+    let interval = interval.max(Duration::from_secs(1));
+    let mut ticks = shards.ticks(&clock, interval);
+    // Workspaces whose last visit didn't embed everything
+    let mut unfinished: HashSet<Uuid> = HashSet::new();
 
-    info!("Embedding task started (30s interval)");
+    let batch_size = batch_size.max(1);
+    let run_limit = run_limit.max(1);
+    let max_query_bytes = embedder.max_query_bytes();
+    info!(
+        shards = shards.count(),
+        batch_size = batch_size,
+        run_limit = run_limit,
+        interval_secs = interval.as_secs(),
+        "Embedding task started"
+    );
 
     loop {
-        interval.tick().await;
+        let shard = ticks.tick().await;
 
-        // Get all workspaces
-        let workspaces = match db.get_all_workspace_ids().await {
-            Ok(w) => w,
+        let active = match db.get_last_ingest(ticks.active_since(clock.now())).await {
+            Ok(active) => active,
             Err(e) => {
-                error!(error = %e, "Failed to get workspaces for embedding");
+                error!(error = %e, "Failed to get active workspaces for embedding");
                 continue;
             }
         };
+        let mut workspaces: Vec<Uuid> = active
+            .into_keys()
+            .chain(unfinished.iter().copied())
+            .filter(|id| shard.contains(*id))
+            .collect();
+        workspaces.sort();
+        workspaces.dedup();
 
         for workspace_id in workspaces {
             // Get unembedded queries for this workspace
-            let queries = match db.get_unembedded_queries(workspace_id, 100).await {
+            let queries = match db
+                .get_unembedded_queries(workspace_id, run_limit, max_query_bytes)
+                .await
+            {
                 Ok(q) => q,
                 Err(e) => {
                     error!(error = %e, workspace_id = %workspace_id, "Failed to get unembedded queries");
+                    unfinished.insert(workspace_id);
                     continue;
                 }
             };
 
+            // A full run may have more behind it
+            let mut done = (queries.len() as i64) < run_limit;
             if queries.is_empty() {
+                unfinished.remove(&workspace_id);
                 continue;
             }
 
@@ -55,27 +120,82 @@ pub async fn embedding_task(db: Arc<Database>, embedding_service: Option<Arc<Emb
                 "Processing unembedded queries"
             );
 
-            // Embed each query
-            for (query_text, query_hash) in queries {
-                match service.embed_query(&query_text) {
-                    Ok(embedding) => {
-                        if let Err(e) = db
-                            .insert_query_embedding(
-                                workspace_id,
-                                &query_hash,
-                                &query_text,
-                                &embedding,
-                            )
-                            .await
-                        {
-                            error!(error = %e, query_hash = %query_hash, "Failed to store embedding");
+            let (queries, too_long): (Vec<_>, Vec<_>) = queries
+                .into_iter()
+                .partition(|(text, _)| embedder.fits(text));
+            if !too_long.is_empty() {
+                let skips: Vec<EmbeddingSkip> = too_long
+                    .iter()
+                    .map(|(text, hash)| EmbeddingSkip {
+                        query_hash: hash.clone(),
+                        query_bytes: text.len(),
+                    })
+                    .collect();
+                match db.insert_embedding_skips(workspace_id, &skips).await {
+                    Ok(()) => {
+                        embedder.record_skipped(skips.len());
+                        warn!(
+                            workspace_id = %workspace_id,
+                            count = skips.len(),
+                            max_bytes = max_query_bytes,
+                            "Skipped queries too long to embed"
+                        );
+                    }
+                    Err(e) => {
+                        error!(error = %e, workspace_id = %workspace_id, "Failed to record skipped queries");
+                        done = false;
+                    }
+                }
+            }
+
+            // Embed a chunk per provider call
+            let mut embedded: Vec<(EmbeddingModel, Vec<NewQueryEmbedding>)> = Vec::new();
+            for chunk in queries.chunks(batch_size) {
+                let texts: Vec<&str> = chunk.iter().map(|(text, _)| text.as_str()).collect();
+                match embedder.embed_batch(&texts).await {
+                    Ok((model, embeddings)) => {
+                        let rows = chunk.iter().zip(embeddings).map(
+                            |((query_text, query_hash), embedding)| NewQueryEmbedding {
+                                query_hash: query_hash.clone(),
+                                sql_query: query_text.clone(),
+                                embedding,
+                            },
+                        );
+                        match embedded.last_mut() {
+                            Some((last, group)) if *last == model => group.extend(rows),
+                            _ => embedded.push((model, rows.collect())),
                         }
                     }
                     Err(e) => {
-                        error!(error = %e, "Failed to embed query");
+                        // Every provider failed; retry on the next visit
+                        error!(error = %e, count = chunk.len(), "Failed to embed queries");
+                        done = false;
+                        break;
+                    }
+                }
+            }
+
+            for (model, group) in &embedded {
+                match db.insert_query_embeddings(workspace_id, model, group).await {
+                    Ok(()) => metrics.inc_embedded(group.len() as u64),
+                    Err(e) => {
+                        error!(
+                            error = %e,
+                            workspace_id = %workspace_id,
+                            count = group.len(),
+                            "Failed to store embeddings"
+                        );
+                        done = false;
                     }
                 }
             }
+
+            if done {
+                unfinished.remove(&workspace_id);
+            } else {
+                unfinished.insert(workspace_id);
+            }
         }
+        metrics.set_embedding_backlog(unfinished.len() as u64);
     }
 }
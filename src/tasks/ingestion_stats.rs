@@ -0,0 +1,41 @@
+//! Ingestion stats task - writes per-minute ingest counts to the database
+
+use crate::clock::Clock;
+use crate::ingest_stats::IngestStats;
+use crate::store::MetricsStore;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// How often counts are written
+pub const STATS_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Background task that adds the ingest path's counts to `ingestion_stats`.
+///
+/// Runs every 10 seconds. Counts that fail to store are kept and retried on
+/// the next run.
+pub async fn ingestion_stats_task(stats: IngestStats, db: Arc<dyn MetricsStore>, clock: Clock) {
+    let mut interval = clock.interval(STATS_FLUSH_INTERVAL);
+
+    info!("Ingestion stats task started (10s interval)");
+
+    loop {
+        interval.tick().await;
+        record_stats(&stats, db.as_ref()).await;
+    }
+}
+
+/// Write the pending counts, keeping them for the next run if that fails
+pub async fn record_stats(stats: &IngestStats, db: &dyn MetricsStore) {
+    let rows = stats.take();
+    if rows.is_empty() {
+        return;
+    }
+    match db.record_ingestion_stats(&rows).await {
+        Ok(()) => debug!(rows = rows.len(), "Ingestion stats recorded"),
+        Err(e) => {
+            warn!(error = %e, rows = rows.len(), "Failed to record ingestion stats, retrying later");
+            stats.restore(rows);
+        }
+    }
+}
@@ -0,0 +1,254 @@
+//! Lifecycle task - applies data lifecycle policies and prunes old data
+
+use crate::clock::Clock;
+use crate::db::RAW_METRICS_RETENTION_DAYS;
+use crate::models::LifecycleStage;
+use crate::store::MetricsStore;
+use crate::tasks::shards::WorkspaceShards;
+use chrono::{DateTime, TimeDelta, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+use uuid::Uuid;
+
+/// Background task that periodically applies data lifecycle policies.
+///
+/// Visits every workspace every 6 hours, one shard of workspaces at a time.
+/// Each workspace with a policy of its own has its stages applied in order
+/// and their outcome recorded, whether or not it ingested lately, since its
+/// data ages all the same. At the start of each pass, as a backup to
+/// TimescaleDB's built-in retention policies and the default policy of every
+/// other workspace, raw metrics and alert delivery attempts older than 30
+/// days and ingestion stats older than a day are deleted.
+pub async fn lifecycle_task(db: Arc<dyn MetricsStore>, shards: WorkspaceShards, clock: Clock) {
+    // Wait 1 minute before starting to allow system to stabilize
+    clock.sleep(Duration::from_secs(60)).await;
+
+    let mut ticks = shards.ticks(&clock, Duration::from_secs(6 * 60 * 60)); // 6 hours
+
+    info!(
+        shards = shards.count(),
+        "Lifecycle task started (6h interval)"
+    );
+
+    loop {
+        let shard = ticks.tick().await;
+
+        info!("Running lifecycle policies...");
+
+        match db.list_lifecycle_policies().await {
+            Ok(policies) => {
+                for (workspace_id, stages) in policies {
+                    if shard.contains(workspace_id) {
+                        run_policy(db.as_ref(), workspace_id, stages, clock.now()).await;
+                    }
+                }
+            }
+            Err(e) => error!(error = %e, "Failed to list lifecycle policies"),
+        }
+
+        // Workspace-wide, so once per pass
+        if !shard.is_first() {
+            continue;
+        }
+
+        match db
+            .prune_old_metrics(RAW_METRICS_RETENTION_DAYS as i32)
+            .await
+        {
+            Ok(deleted) => {
+                if deleted > 0 {
+                    info!(deleted = deleted, "Pruned old metrics");
+                } else {
+                    info!("No old metrics to prune");
+                }
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to prune old metrics");
+            }
+        }
+
+        match db.prune_alert_deliveries(30).await {
+            Ok(deleted) if deleted > 0 => info!(deleted = deleted, "Pruned old alert deliveries"),
+            Ok(_) => {}
+            Err(e) => error!(error = %e, "Failed to prune old alert deliveries"),
+        }
+
+        match db.prune_ingestion_stats(1).await {
+            Ok(deleted) if deleted > 0 => info!(deleted = deleted, "Pruned old ingestion stats"),
+            Ok(_) => {}
+            Err(e) => error!(error = %e, "Failed to prune old ingestion stats"),
+        }
+    }
+}
+
+/// Apply a workspace's stages in order, recording how each went
+///
+/// A failed stage doesn't stop the ones after it: each acts on its own age
+/// range, and the failure is reported on the stage.
+async fn run_policy(
+    db: &dyn MetricsStore,
+    workspace_id: Uuid,
+    stages: Vec<LifecycleStage>,
+    now: DateTime<Utc>,
+) {
+    for mut stage in stages {
+        let before = now - TimeDelta::days(stage.after_days as i64);
+        let result = db
+            .apply_lifecycle_action(workspace_id, stage.action, before)
+            .await;
+        stage.last_run_at = Some(now);
+        match result {
+            Ok(affected) => {
+                if affected > 0 {
+                    info!(
+                        workspace_id = %workspace_id,
+                        action = stage.action.as_str(),
+                        affected = affected,
+                        "Applied lifecycle stage"
+                    );
+                }
+                stage.last_affected = Some(affected as i64);
+                stage.last_error = None;
+            }
+            Err(e) => {
+                error!(
+                    workspace_id = %workspace_id,
+                    action = stage.action.as_str(),
+                    error = %e,
+                    "Failed to apply lifecycle stage"
+                );
+                stage.last_affected = None;
+                stage.last_error = Some(e.to_string());
+            }
+        }
+        if let Err(e) = db.record_lifecycle_run(workspace_id, &stage).await {
+            error!(error = %e, "Failed to record lifecycle stage run");
+        }
+    }
+}
+
+#[cfg(all(test, feature = "memory-store"))]
+mod tests {
+    use super::*;
+    use crate::db::MetricFilter;
+    use crate::models::{LifecycleAction, QueryMetric, QueryStatus};
+    use crate::store::memory::{MemoryStore, DEFAULT_WORKSPACE_ID};
+    use chrono::TimeDelta;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_prunes_on_simulated_schedule() {
+        let clock = Clock::simulated("2026-01-01T00:00:00Z".parse().unwrap());
+        let store = Arc::new(MemoryStore::with_clock(clock.clone()));
+        let metric = || {
+            QueryMetric::new(
+                DEFAULT_WORKSPACE_ID,
+                Uuid::new_v4(),
+                "SELECT 1".to_string(),
+                QueryStatus::Success,
+                10,
+                clock.now(),
+            )
+        };
+        store.insert_metrics_batch(&[metric()]).await.unwrap();
+        clock.advance(TimeDelta::days(25));
+        store.insert_metrics_batch(&[metric()]).await.unwrap();
+
+        let task = tokio::spawn(lifecycle_task(
+            store.clone(),
+            WorkspaceShards::new(1),
+            clock.clone(),
+        ));
+        let stored = || async {
+            for _ in 0..10 {
+                tokio::task::yield_now().await;
+            }
+            store
+                .get_recent_metrics(DEFAULT_WORKSPACE_ID, &MetricFilter::default(), 10)
+                .await
+                .unwrap()
+                .len()
+        };
+
+        // The first run, after the startup delay, finds nothing old enough
+        clock.advance(TimeDelta::seconds(60));
+        assert_eq!(stored().await, 2);
+        // A later run drops the first metric once it's 30 days old
+        clock.advance(TimeDelta::days(5));
+        assert_eq!(stored().await, 1);
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_stages_applied_in_order_with_status() {
+        let clock = Clock::simulated("2026-01-01T00:00:00Z".parse().unwrap());
+        let store = MemoryStore::with_clock(clock.clone());
+        let mut metric = QueryMetric::new(
+            DEFAULT_WORKSPACE_ID,
+            Uuid::new_v4(),
+            "SELECT * FROM users WHERE id = 7".to_string(),
+            QueryStatus::Success,
+            10,
+            clock.now(),
+        );
+        metric.fingerprint = Some("fp".to_string());
+        store.insert_metrics_batch(&[metric]).await.unwrap();
+        assert!(store
+            .set_lifecycle_policy(
+                DEFAULT_WORKSPACE_ID,
+                &[
+                    LifecycleStage::new(LifecycleAction::StripText, 1),
+                    LifecycleStage::new(LifecycleAction::Archive, 2),
+                    LifecycleStage::new(LifecycleAction::Downsample, 8),
+                ],
+            )
+            .await
+            .unwrap());
+        let run = || async {
+            let stages = store
+                .get_lifecycle_policy(DEFAULT_WORKSPACE_ID)
+                .await
+                .unwrap()
+                .unwrap();
+            run_policy(&store, DEFAULT_WORKSPACE_ID, stages, clock.now()).await;
+            store
+                .get_lifecycle_policy(DEFAULT_WORKSPACE_ID)
+                .await
+                .unwrap()
+                .unwrap()
+                .iter()
+                .map(|stage| stage.last_affected)
+                .collect::<Vec<_>>()
+        };
+        let stored = || async {
+            store
+                .get_recent_metrics(DEFAULT_WORKSPACE_ID, &MetricFilter::default(), 10)
+                .await
+                .unwrap()
+        };
+
+        // Past the strip_text stage only: the text goes, the metric stays
+        clock.advance(TimeDelta::days(1) + TimeDelta::seconds(1));
+        assert_eq!(run().await, vec![Some(1), Some(0), Some(0)]);
+        let metrics = stored().await;
+        assert_eq!(metrics.len(), 1);
+        assert!(metrics[0].query_text.is_empty());
+
+        // Archived once, then dropped from raw metrics
+        clock.advance(TimeDelta::days(1));
+        assert_eq!(run().await, vec![Some(0), Some(1), Some(0)]);
+        clock.advance(TimeDelta::days(6));
+        assert_eq!(run().await, vec![Some(0), Some(0), Some(1)]);
+        assert!(stored().await.is_empty());
+
+        let stages = store
+            .get_lifecycle_policy(DEFAULT_WORKSPACE_ID)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(stages
+            .iter()
+            .all(|s| s.last_run_at == Some(clock.now()) && s.last_error.is_none()));
+    }
+}
@@ -2,5 +2,160 @@
 
 pub mod aggregation;
 pub mod anomaly_detection;
+pub mod baselines;
+pub mod clustering;
 pub mod embedding_task;
-pub mod retention;
+pub mod ingestion_stats;
+pub mod lifecycle;
+pub mod reembedding;
+pub mod rollup;
+pub mod rules;
+pub mod shards;
+pub mod slo;
+pub mod throughput_drops;
+
+use crate::routes::ws;
+use crate::state::AppState;
+use crate::store::resilient::CircuitBreaker;
+use shards::WorkspaceShards;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Settings of the background tasks beyond those in [`AppState`]
+#[derive(Debug, Clone, Copy)]
+pub struct TaskConfig {
+    /// Shards the per-workspace tasks split workspaces into
+    pub shards: WorkspaceShards,
+    /// Queries sent to the embedding provider per call
+    pub embedding_batch_size: usize,
+    /// Most queries embedded per workspace per visit
+    pub embedding_run_limit: i64,
+    /// How often every workspace is visited for queries to embed
+    pub embedding_interval: Duration,
+    /// Most clusters per workspace
+    pub query_clusters: usize,
+}
+
+impl Default for TaskConfig {
+    fn default() -> Self {
+        Self {
+            shards: WorkspaceShards::default(),
+            embedding_batch_size: embedding_task::DEFAULT_BATCH_SIZE,
+            embedding_run_limit: embedding_task::DEFAULT_RUN_LIMIT,
+            embedding_interval: embedding_task::DEFAULT_INTERVAL,
+            query_clusters: clustering::DEFAULT_CLUSTERS,
+        }
+    }
+}
+
+/// Spawn every background task over `state`, timed by its clock, returning
+/// their handles
+///
+/// `breaker` should be the one the store trips, so the buffer is left alone
+/// while the database is down.
+pub fn spawn(
+    state: &AppState,
+    breaker: Arc<CircuitBreaker>,
+    config: TaskConfig,
+) -> Vec<JoinHandle<()>> {
+    let clock = &state.clock;
+    let shards = config.shards;
+    vec![
+        // 1. Broadcast task - sends per-second stats to WebSocket clients
+        tokio::spawn(ws::broadcast_task(state.clone(), clock.clone())),
+        // 2. Aggregation task - flushes buffer to database every 5s
+        tokio::spawn(aggregation::aggregation_task(
+            state.metrics_buffer.clone(),
+            Arc::clone(&state.db),
+            breaker,
+            state.live_rollups.clone(),
+            state.ingest_stats.clone(),
+            Arc::clone(&state.quarantine),
+            Arc::clone(&state.notifier),
+            Arc::clone(&state.metrics),
+            clock.clone(),
+        )),
+        // 3. Lifecycle task - applies data lifecycle policies every 6h
+        tokio::spawn(lifecycle::lifecycle_task(
+            Arc::clone(&state.db),
+            shards,
+            clock.clone(),
+        )),
+        // 4. Embedding task - embeds queries for vector search
+        tokio::spawn(embedding_task::embedding_task(
+            Arc::clone(&state.db),
+            state.embedder.clone(),
+            shards,
+            config.embedding_batch_size,
+            config.embedding_run_limit,
+            config.embedding_interval,
+            Arc::clone(&state.metrics),
+            clock.clone(),
+        )),
+        // 4b. Re-embedding task - rewrites embeddings from other models
+        tokio::spawn(reembedding::reembedding_task(
+            Arc::clone(&state.db),
+            state.embedder.clone(),
+            Arc::clone(&state.reembed),
+            config.embedding_batch_size,
+            clock.clone(),
+        )),
+        // 4c. Clustering task - groups embedded queries into workload families
+        tokio::spawn(clustering::clustering_task(
+            Arc::clone(&state.db),
+            state.embedder.clone(),
+            config.query_clusters,
+            shards,
+            clock.clone(),
+        )),
+        // 5. Anomaly detection task - detects slow queries
+        tokio::spawn(anomaly_detection::anomaly_detection_task(
+            Arc::clone(&state.db),
+            state.anomaly_tx.clone(),
+            Arc::clone(&state.notifier),
+            state.ids,
+            shards,
+            state.anomaly_defaults,
+            Arc::clone(&state.metrics),
+            clock.clone(),
+        )),
+        // 6. Baseline task - recomputes seasonal anomaly baselines hourly
+        tokio::spawn(baselines::baseline_task(
+            Arc::clone(&state.db),
+            state.anomaly_defaults,
+            shards,
+            clock.clone(),
+        )),
+        // 7. Rollup task - maintains hourly per-fingerprint stats
+        tokio::spawn(rollup::rollup_task(Arc::clone(&state.db), clock.clone())),
+        // 8. Alert rules task - evaluates alert rules each window
+        tokio::spawn(rules::rules_task(
+            Arc::clone(&state.db),
+            Arc::clone(&state.notifier),
+            shards,
+            clock.clone(),
+        )),
+        // 9. SLO task - computes SLO compliance and burn rates every 60s
+        tokio::spawn(slo::slo_task(
+            Arc::clone(&state.db),
+            Arc::clone(&state.notifier),
+            shards,
+            clock.clone(),
+        )),
+        // 10. Throughput drop task - detects services that stop querying
+        tokio::spawn(throughput_drops::throughput_drop_task(
+            Arc::clone(&state.db),
+            Arc::clone(&state.notifier),
+            state.ids,
+            shards,
+            clock.clone(),
+        )),
+        // 11. Ingestion stats task - writes per-minute ingest counts every 10s
+        tokio::spawn(ingestion_stats::ingestion_stats_task(
+            state.ingest_stats.clone(),
+            Arc::clone(&state.db),
+            clock.clone(),
+        )),
+    ]
+}
@@ -0,0 +1,239 @@
+//! Re-embedding task - rewrites embeddings made by another model
+
+use crate::clock::Clock;
+use crate::db::NewQueryEmbedding;
+use crate::error::Result;
+use crate::services::embedder::Embedder;
+use crate::store::MetricsStore;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Stale embeddings fetched per workspace at a time
+const PAGE_SIZE: i64 = 100;
+
+/// Background task that re-embeds queries whose stored vectors came from a
+/// model other than the primary provider's.
+///
+/// Runs at startup, every hour, and whenever `trigger` is notified (by
+/// `POST /admin/embeddings/reembed`), so a model change is picked up on the
+/// first run after the restart that configured it. Queries the fallback
+/// embedded while the primary was down are rewritten the same way. Vectors
+/// are replaced in place; until a workspace is done, similarity search
+/// only sees its queries already on the new model. Unlike the per-workspace
+/// tasks, it isn't sharded or limited to workspaces that ingested recently:
+/// stale embeddings are left by a model change, not by ingest, only the
+/// workspaces that have them are visited, and the admin trigger asks for
+/// all of them at once.
+pub async fn reembedding_task(
+    db: Arc<dyn MetricsStore>,
+    embedder: Option<Arc<Embedder>>,
+    trigger: Arc<Notify>,
+    batch_size: usize,
+    clock: Clock,
+) {
+    let Some(embedder) = embedder else {
+        warn!("Embedding service not configured, re-embedding task disabled");
+        return;
+    };
+
+    let mut interval = clock.interval(Duration::from_secs(60 * 60));
+    let batch_size = batch_size.max(1);
+    info!("Re-embedding task started (1h interval)");
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = trigger.notified() => {}
+        }
+
+        if let Err(e) = reembed_stale(db.as_ref(), &embedder, batch_size).await {
+            error!(error = %e, "Failed to look for stale embeddings");
+        }
+    }
+}
+
+/// Re-embed every workspace's stale embeddings, returning how many were
+/// rewritten
+///
+/// Stops early when the primary provider can't embed, rather than writing
+/// the fallback's vectors over other stale ones.
+pub async fn reembed_stale(
+    db: &dyn MetricsStore,
+    embedder: &Embedder,
+    batch_size: usize,
+) -> Result<usize> {
+    let model = embedder.model();
+    let workspaces = db.get_stale_embedding_workspaces(&model).await?;
+    if workspaces.is_empty() {
+        return Ok(0);
+    }
+    info!(
+        model = %model.name,
+        version = %model.version,
+        workspaces = workspaces.len(),
+        "Re-embedding queries from other models"
+    );
+
+    let mut total = 0;
+    for workspace_id in workspaces {
+        match reembed_workspace(db, embedder, workspace_id, batch_size).await {
+            Ok((count, true)) => total += count,
+            Ok((count, false)) => {
+                total += count;
+                warn!(
+                    reembedded = total,
+                    "Primary embedding provider unavailable, re-embedding paused"
+                );
+                return Ok(total);
+            }
+            Err(e) => {
+                error!(error = %e, workspace_id = %workspace_id, "Failed to re-embed queries")
+            }
+        }
+    }
+
+    info!(reembedded = total, "Re-embedding finished");
+    Ok(total)
+}
+
+/// Re-embed one workspace's stale embeddings, returning how many were
+/// rewritten and whether the primary provider embedded all of them
+async fn reembed_workspace(
+    db: &dyn MetricsStore,
+    embedder: &Embedder,
+    workspace_id: Uuid,
+    batch_size: usize,
+) -> Result<(usize, bool)> {
+    let model = embedder.model();
+    let mut count = 0;
+
+    loop {
+        let stale = db
+            .get_stale_embeddings(workspace_id, &model, PAGE_SIZE)
+            .await?;
+        if stale.is_empty() {
+            return Ok((count, true));
+        }
+
+        for chunk in stale.chunks(batch_size) {
+            let texts: Vec<&str> = chunk.iter().map(|(text, _)| text.as_str()).collect();
+            let embeddings = match embedder.embed_batch(&texts).await {
+                Ok((used, embeddings)) if used == model => embeddings,
+                Ok(_) => return Ok((count, false)),
+                Err(e) => {
+                    warn!(error = %e, count = chunk.len(), "Failed to re-embed queries");
+                    return Ok((count, false));
+                }
+            };
+            let rows: Vec<NewQueryEmbedding> = chunk
+                .iter()
+                .zip(embeddings)
+                .map(|((query_text, query_hash), embedding)| NewQueryEmbedding {
+                    query_hash: query_hash.clone(),
+                    sql_query: query_text.clone(),
+                    embedding,
+                })
+                .collect();
+            db.insert_query_embeddings(workspace_id, &model, &rows)
+                .await?;
+            count += rows.len();
+        }
+
+        // A short page was the last of them
+        if (stale.len() as i64) < PAGE_SIZE {
+            return Ok((count, true));
+        }
+    }
+}
+
+#[cfg(all(test, feature = "memory-store"))]
+mod tests {
+    use super::*;
+    use crate::db::{AnnTuning, EmbeddingModel};
+    use crate::services::embedder::{EmbeddingProvider, Provider, ProviderKind};
+    use crate::store::memory::{MemoryStore, DEFAULT_WORKSPACE_ID};
+    use async_trait::async_trait;
+
+    struct Fixed {
+        version: &'static str,
+    }
+
+    #[async_trait]
+    impl EmbeddingProvider for Fixed {
+        fn kind(&self) -> ProviderKind {
+            ProviderKind::Local
+        }
+
+        fn model(&self) -> &str {
+            "test"
+        }
+
+        fn model_version(&self) -> &str {
+            self.version
+        }
+
+        fn dimension(&self) -> usize {
+            2
+        }
+
+        async fn embed_query(&self, _query: &str) -> Result<Vec<f32>> {
+            Ok(vec![1.0, 0.0])
+        }
+    }
+
+    fn embedder(version: &'static str) -> Embedder {
+        Embedder::new(Box::new(Fixed { version }) as Provider, None)
+    }
+
+    #[tokio::test]
+    async fn test_stale_embeddings_are_rewritten_with_the_current_model() {
+        let store = MemoryStore::new();
+        let old = EmbeddingModel {
+            name: "test".into(),
+            version: "1".into(),
+        };
+        let rows: Vec<NewQueryEmbedding> = (0..5)
+            .map(|i| {
+                let query = format!("SELECT {}", i);
+                NewQueryEmbedding {
+                    query_hash: format!("hash-{}", i),
+                    sql_query: query,
+                    embedding: vec![0.0, 1.0],
+                }
+            })
+            .collect();
+        store
+            .insert_query_embeddings(DEFAULT_WORKSPACE_ID, &old, &rows)
+            .await
+            .unwrap();
+
+        // Nothing to do while the stored model is the configured one
+        let unchanged = embedder("1");
+        assert_eq!(reembed_stale(&store, &unchanged, 2).await.unwrap(), 0);
+
+        let upgraded = embedder("2");
+        assert_eq!(reembed_stale(&store, &upgraded, 2).await.unwrap(), 5);
+        let new = upgraded.model();
+        assert!(store
+            .get_stale_embedding_workspaces(&new)
+            .await
+            .unwrap()
+            .is_empty());
+        let similar = store
+            .search_similar_queries(
+                DEFAULT_WORKSPACE_ID,
+                &new,
+                &[1.0, 0.0],
+                10,
+                0.99,
+                AnnTuning::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(similar.len(), 5);
+        assert_eq!(reembed_stale(&store, &upgraded, 2).await.unwrap(), 0);
+    }
+}
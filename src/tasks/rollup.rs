@@ -0,0 +1,32 @@
+//! Rollup task - maintains hourly per-fingerprint statistics
+
+use crate::clock::Clock;
+use crate::store::MetricsStore;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info};
+
+/// Background task that keeps the `fingerprint_rollups` table current.
+///
+/// Runs every 5 minutes and rebuilds the current and previous hourly buckets,
+/// so late-arriving metrics from the previous hour are still counted.
+pub async fn rollup_task(db: Arc<dyn MetricsStore>, clock: Clock) {
+    let mut interval = clock.interval(Duration::from_secs(5 * 60));
+
+    info!("Rollup task started (5m interval)");
+
+    loop {
+        interval.tick().await;
+
+        let since = clock.now() - chrono::Duration::hours(1);
+
+        match db.refresh_fingerprint_rollups(since).await {
+            Ok(rows) => {
+                debug!(rows = rows, "Fingerprint rollups refreshed");
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to refresh fingerprint rollups");
+            }
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Alert rules task - evaluates alert rules over the continuous aggregates
+
+use crate::clock::Clock;
+use crate::db::{AggregatedMetric, AlertRuleState, MetricFilter};
+use crate::models::{AlertDestination, AlertRule};
+use crate::services::alerting::{self, RuleState, Transition};
+use crate::services::notify::{self, Notifier};
+use crate::store::MetricsStore;
+use crate::tasks::shards::{WorkspaceShards, INGEST_LAG};
+use chrono::{DateTime, TimeDelta, Utc};
+use chrono_tz::Tz;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+/// How far back the newest closed window of the widest alert window starts
+/// (two 5m windows before the current one)
+const MAX_WINDOW_AGE: TimeDelta = TimeDelta::minutes(15);
+
+/// Background task that evaluates enabled alert rules.
+///
+/// Visits every workspace's rules every 60 seconds, one shard of workspaces
+/// at a time. Each rule is evaluated once per closed window of its
+/// aggregation, firing after `for_windows` consecutive breaching windows and
+/// resolving after `resolve_windows` clear ones. Rules of workspaces that
+/// didn't ingest during the window are skipped when a quiet window couldn't
+/// change their state. Firing rules are logged at warn level and, like
+/// resolving ones, sent to the workspace's enabled alert destinations. State
+/// is saved after every evaluation and loaded on startup, so
+/// a restart neither re-fires firing rules nor loses their counters; it is
+/// reset when a rule is edited.
+pub async fn rules_task(
+    db: Arc<dyn MetricsStore>,
+    notifier: Arc<Notifier>,
+    shards: WorkspaceShards,
+    clock: Clock,
+) {
+    let mut ticks = shards.ticks(&clock, Duration::from_secs(60));
+    // Rule ID -> (definition version, evaluation state)
+    let mut states: HashMap<Uuid, (DateTime<Utc>, RuleState)> = HashMap::new();
+    let mut loaded = false;
+
+    info!(
+        shards = shards.count(),
+        "Alert rules task started (60s interval)"
+    );
+
+    loop {
+        let shard = ticks.tick().await;
+
+        // Don't evaluate from scratch until the saved state is back, or
+        // every firing rule would fire again
+        if !loaded {
+            match db.list_alert_rule_states().await {
+                Ok(saved) => {
+                    info!(count = saved.len(), "Loaded alert rule state");
+                    states = saved
+                        .into_iter()
+                        .map(|s| (s.rule_id, (s.rule_version, s.state)))
+                        .collect();
+                    loaded = true;
+                }
+                Err(e) => {
+                    error!(error = %e, "Failed to load alert rule state");
+                    continue;
+                }
+            }
+        }
+
+        let rules = match db.list_enabled_alert_rules().await {
+            Ok(r) => r,
+            Err(e) => {
+                error!(error = %e, "Failed to list alert rules");
+                continue;
+            }
+        };
+
+        states.retain(|id, _| rules.iter().any(|r| r.id == *id));
+        let now = clock.now();
+        let last_ingest = match db.get_last_ingest(now - MAX_WINDOW_AGE - INGEST_LAG).await {
+            Ok(last) => last,
+            Err(e) => {
+                error!(error = %e, "Failed to get active workspaces for alert rules");
+                continue;
+            }
+        };
+        // Rules sharing a workspace and window share one aggregation query
+        let mut buckets: HashMap<(Uuid, &str), Vec<AggregatedMetric>> = HashMap::new();
+
+        for rule in rules.iter().filter(|r| shard.contains(r.workspace_id)) {
+            let Some(width) = alerting::window_seconds(&rule.window) else {
+                continue;
+            };
+            let Some(bucket) = alerting::evaluation_bucket(now, width) else {
+                continue;
+            };
+
+            let (version, state) = states
+                .entry(rule.id)
+                .or_insert_with(|| (rule.updated_at, RuleState::default()));
+            if *version != rule.updated_at {
+                *version = rule.updated_at;
+                *state = RuleState::default();
+            }
+            if state.last_bucket == Some(bucket) {
+                continue;
+            }
+            let idle = last_ingest
+                .get(&rule.workspace_id)
+                .is_none_or(|last| *last < bucket - INGEST_LAG);
+            if idle && state.unchanged_by_idle_window(rule) {
+                continue;
+            }
+
+            let rows = match buckets.entry((rule.workspace_id, rule.window.as_str())) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let to = bucket + chrono::Duration::seconds(width);
+                    match db
+                        .get_aggregations(
+                            rule.workspace_id,
+                            &rule.window,
+                            Tz::UTC,
+                            &MetricFilter::default(),
+                            bucket,
+                            to,
+                        )
+                        .await
+                    {
+                        Ok(rows) => entry.insert(rows),
+                        Err(e) => {
+                            error!(error = %e, rule_id = %rule.id, "Failed to load aggregates for alert rule");
+                            continue;
+                        }
+                    }
+                }
+            };
+            let window: Vec<AggregatedMetric> = rows
+                .iter()
+                .filter(|m| m.bucket == bucket)
+                .filter(|m| rule.service_id.is_none() || rule.service_id == Some(m.service_id))
+                .cloned()
+                .collect();
+            let values: Vec<Option<f64>> = rule
+                .conditions
+                .iter()
+                .map(|c| alerting::signal_value(c.signal, &window))
+                .collect();
+
+            state.last_bucket = Some(bucket);
+            match state.observe(rule, &values) {
+                Some(transition) => {
+                    let fired = transition == Transition::Fired;
+                    report_transition(db.as_ref(), &notifier, rule, bucket, &values, fired).await
+                }
+                None => debug!(
+                    rule_id = %rule.id,
+                    bucket = %bucket,
+                    firing = state.firing,
+                    "Alert rule evaluated"
+                ),
+            }
+
+            let saved = AlertRuleState {
+                rule_id: rule.id,
+                rule_version: *version,
+                state: state.clone(),
+            };
+            if let Err(e) = db.upsert_alert_rule_state(&saved).await {
+                error!(error = %e, rule_id = %rule.id, "Failed to save alert rule state");
+            }
+        }
+    }
+}
+
+/// Log a rule firing or resolving with the window's signal values, and send
+/// it to the workspace's enabled alert destinations in the background, so a
+/// slow destination doesn't hold up the other rules
+async fn report_transition(
+    db: &dyn MetricsStore,
+    notifier: &Arc<Notifier>,
+    rule: &AlertRule,
+    bucket: DateTime<Utc>,
+    values: &[Option<f64>],
+    fired: bool,
+) {
+    let signals: Vec<String> = rule
+        .conditions
+        .iter()
+        .zip(values)
+        .map(|(c, v)| match v {
+            Some(v) => format!("{}={}", c.signal.as_str(), v),
+            None => format!("{}=none", c.signal.as_str()),
+        })
+        .collect();
+    let signals = signals.join(", ");
+
+    if fired {
+        warn!(
+            workspace_id = %rule.workspace_id,
+            rule_id = %rule.id,
+            rule = %rule.name,
+            bucket = %bucket,
+            signals = %signals,
+            "Alert rule firing"
+        );
+    } else {
+        info!(
+            workspace_id = %rule.workspace_id,
+            rule_id = %rule.id,
+            rule = %rule.name,
+            bucket = %bucket,
+            signals = %signals,
+            "Alert rule resolved"
+        );
+    }
+
+    let destinations: Vec<AlertDestination> = match db
+        .list_alert_destinations(rule.workspace_id)
+        .await
+    {
+        Ok(d) => d.into_iter().filter(|d| d.enabled).collect(),
+        Err(e) => {
+            error!(error = %e, rule_id = %rule.id, "Failed to list alert destinations for alert rule");
+            return;
+        }
+    };
+    if destinations.is_empty() {
+        return;
+    }
+
+    let payload = notify::alert_rule_payload(rule, bucket, values, fired);
+    let notifier = Arc::clone(notifier);
+    tokio::spawn(async move {
+        for destination in &destinations {
+            notifier
+                .deliver(destination, notify::ALERT_RULE_EVENT, &payload, false)
+                .await;
+        }
+    });
+}
@@ -0,0 +1,139 @@
+//! Workspace sharding for per-workspace background tasks
+//!
+//! Instead of working through every workspace at once, a task ticks once per
+//! shard, evenly spread across its interval, and handles only the workspaces
+//! whose ID falls in that shard. Every workspace is still visited once per
+//! interval, but each run does a fraction of the work, so runs stay short as
+//! tenants are added. Tasks also pass over workspaces that haven't ingested
+//! recently, when that can't change their outcome. Recent ingest is read from
+//! the stored ingestion stats, so metrics sent to other instances count too.
+
+use crate::clock::{Clock, Interval};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Default number of shards per task interval
+pub const DEFAULT_SHARDS: u32 = 6;
+
+/// How far ingestion stats may trail the metrics they count: they are keyed
+/// by the minute and written every 10 seconds
+pub const INGEST_LAG: TimeDelta = TimeDelta::minutes(2);
+
+/// How many shards a task splits the workspaces into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkspaceShards {
+    count: u32,
+}
+
+impl WorkspaceShards {
+    /// `count` shards, at least one
+    pub fn new(count: u32) -> Self {
+        Self {
+            count: count.max(1),
+        }
+    }
+
+    pub fn count(self) -> u32 {
+        self.count
+    }
+
+    /// Ticks once per shard, so all shards are visited every `period`; the
+    /// first tick is immediate
+    pub fn ticks(self, clock: &Clock, period: Duration) -> ShardTicks {
+        ShardTicks {
+            interval: clock.interval(period / self.count),
+            period,
+            count: self.count,
+            next: 0,
+        }
+    }
+}
+
+impl Default for WorkspaceShards {
+    fn default() -> Self {
+        Self::new(DEFAULT_SHARDS)
+    }
+}
+
+/// Ticks from [`WorkspaceShards::ticks`]
+pub struct ShardTicks {
+    interval: Interval,
+    period: Duration,
+    count: u32,
+    next: u32,
+}
+
+impl ShardTicks {
+    /// Wait for the next tick and return the shard it covers
+    pub async fn tick(&mut self) -> Shard {
+        self.interval.tick().await;
+        let shard = Shard {
+            index: self.next,
+            count: self.count,
+        };
+        self.next = (self.next + 1) % self.count;
+        shard
+    }
+
+    /// Earliest ingest that could be new to a workspace visited at `now`,
+    /// given it was last visited one period ago
+    pub fn active_since(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - TimeDelta::from_std(self.period).unwrap_or(TimeDelta::MAX) - INGEST_LAG
+    }
+}
+
+/// One of a task's shards of workspaces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    index: u32,
+    count: u32,
+}
+
+impl Shard {
+    /// Whether the workspace belongs to this shard
+    pub fn contains(self, workspace_id: Uuid) -> bool {
+        workspace_id.as_u128() % self.count as u128 == self.index as u128
+    }
+
+    /// Whether this shard starts a pass over the workspaces
+    pub fn is_first(self) -> bool {
+        self.index == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_every_workspace_visited_once_per_period() {
+        let start: DateTime<Utc> = "2026-01-01T00:00:00Z".parse().unwrap();
+        let clock = Clock::simulated(start);
+        let mut ticks = WorkspaceShards::new(4).ticks(&clock, Duration::from_secs(60));
+        let workspaces: Vec<Uuid> = (0..100).map(|_| Uuid::new_v4()).collect();
+
+        let mut visits = vec![0; workspaces.len()];
+        for _ in 0..4 {
+            let shard = ticks.tick().await;
+            for (i, ws) in workspaces.iter().enumerate() {
+                if shard.contains(*ws) {
+                    visits[i] += 1;
+                }
+            }
+            clock.advance(TimeDelta::seconds(15));
+        }
+        assert!(visits.iter().all(|v| *v == 1));
+
+        // The fifth tick starts the next pass over the first shard
+        let shard = ticks.tick().await;
+        assert_eq!(shard, Shard { index: 0, count: 4 });
+        assert!(shard.is_first());
+        assert_eq!(ticks.active_since(clock.now()), start - INGEST_LAG);
+    }
+
+    #[test]
+    fn test_at_least_one_shard() {
+        assert_eq!(WorkspaceShards::new(0).count(), 1);
+    }
+}
@@ -0,0 +1,269 @@
+//! SLO task - computes SLO compliance and alerts on error budget burn
+
+use crate::clock::Clock;
+use crate::db::SloStatus;
+use crate::error::Result;
+use crate::models::Slo;
+use crate::services::notify::{self, Notifier};
+use crate::services::slo::{self, BurnState, BURN_POLICIES};
+use crate::store::MetricsStore;
+use crate::tasks::shards::{WorkspaceShards, INGEST_LAG};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+/// Background task that computes the status of enabled SLOs.
+///
+/// Visits every workspace's SLOs every 60 seconds, one shard of workspaces
+/// at a time. Each SLO's compliance is counted over its rolling window, and
+/// its burn rate over the windows of each burn policy. When an SLO's burn
+/// state changes, it is logged (at warn level while burning) and sent to the
+/// workspace's enabled alert destinations. Statuses are saved after every
+/// computation and loaded on startup, so a restart doesn't alert again on a
+/// burn that was already reported. An SLO that isn't burning is left as it
+/// is while its workspace has ingested nothing for longer than the longest
+/// burn window, as its burn rates have no data to change on.
+pub async fn slo_task(
+    db: Arc<dyn MetricsStore>,
+    notifier: Arc<Notifier>,
+    shards: WorkspaceShards,
+    clock: Clock,
+) {
+    let mut ticks = shards.ticks(&clock, Duration::from_secs(60));
+    // SLO ID -> last burn state
+    let mut burn_states: HashMap<Uuid, BurnState> = HashMap::new();
+    let mut loaded = false;
+
+    info!(shards = shards.count(), "SLO task started (60s interval)");
+    let longest_burn_window = BURN_POLICIES
+        .iter()
+        .map(|p| p.long.max(p.short))
+        .max()
+        .unwrap_or_default();
+
+    loop {
+        let shard = ticks.tick().await;
+
+        // Don't compute from scratch until the saved statuses are back, or
+        // every burning SLO would alert again
+        if !loaded {
+            match db.list_all_slo_statuses().await {
+                Ok(saved) => {
+                    info!(count = saved.len(), "Loaded SLO status");
+                    burn_states = saved
+                        .into_iter()
+                        .map(|s| (s.slo_id, s.burn_state))
+                        .collect();
+                    loaded = true;
+                }
+                Err(e) => {
+                    error!(error = %e, "Failed to load SLO status");
+                    continue;
+                }
+            }
+        }
+
+        let slos = match db.list_enabled_slos().await {
+            Ok(s) => s,
+            Err(e) => {
+                error!(error = %e, "Failed to list SLOs");
+                continue;
+            }
+        };
+
+        burn_states.retain(|id, _| slos.iter().any(|s| s.id == *id));
+        let now = clock.now();
+        let active = match db
+            .get_last_ingest(now - longest_burn_window - INGEST_LAG)
+            .await
+        {
+            Ok(active) => active,
+            Err(e) => {
+                error!(error = %e, "Failed to get active workspaces for SLOs");
+                continue;
+            }
+        };
+
+        for slo in slos.iter().filter(|s| shard.contains(s.workspace_id)) {
+            let idle = !active.contains_key(&slo.workspace_id);
+            if idle && burn_states.get(&slo.id) == Some(&BurnState::Ok) {
+                continue;
+            }
+
+            let status = match compute_status(db.as_ref(), slo, now).await {
+                Ok(status) => status,
+                Err(e) => {
+                    error!(error = %e, slo_id = %slo.id, "Failed to compute SLO status");
+                    continue;
+                }
+            };
+
+            let previous = burn_states
+                .insert(slo.id, status.burn_state)
+                .unwrap_or_default();
+            if status.burn_state != previous {
+                report_burn(db.as_ref(), &notifier, slo, &status, previous).await;
+            } else {
+                debug!(
+                    slo_id = %slo.id,
+                    compliance = ?status.counts.compliance(),
+                    burn_state = status.burn_state.as_str(),
+                    "SLO computed"
+                );
+            }
+
+            if let Err(e) = db.upsert_slo_status(&status).await {
+                error!(error = %e, slo_id = %slo.id, "Failed to save SLO status");
+            }
+        }
+    }
+}
+
+/// Count an SLO's queries over its window and each burn policy's windows
+async fn compute_status(db: &dyn MetricsStore, slo: &Slo, now: DateTime<Utc>) -> Result<SloStatus> {
+    let counts_over = |window: TimeDelta| {
+        db.get_slo_counts(
+            slo.workspace_id,
+            slo.service_id,
+            slo.threshold_ms,
+            now - window,
+            now,
+        )
+    };
+
+    let counts = counts_over(TimeDelta::days(slo.window_days as i64)).await?;
+    let mut rates: HashMap<TimeDelta, Option<f64>> = HashMap::new();
+    for window in BURN_POLICIES.iter().flat_map(|p| [p.long, p.short]) {
+        let rate = counts_over(window).await?.burn_rate(slo.target_percent);
+        rates.insert(window, rate);
+    }
+    let rate_over = |window: TimeDelta| rates.get(&window).copied().flatten();
+
+    Ok(SloStatus {
+        slo_id: slo.id,
+        computed_at: now,
+        counts,
+        burn_rate_1h: rate_over(TimeDelta::hours(1)),
+        burn_rate_6h: rate_over(TimeDelta::hours(6)),
+        burn_state: slo::burn_state(rate_over),
+    })
+}
+
+/// Log a change in an SLO's burn state and send it to the workspace's
+/// enabled alert destinations
+async fn report_burn(
+    db: &dyn MetricsStore,
+    notifier: &Notifier,
+    slo: &Slo,
+    status: &SloStatus,
+    previous: BurnState,
+) {
+    if status.burn_state == BurnState::Ok {
+        info!(
+            workspace_id = %slo.workspace_id,
+            slo_id = %slo.id,
+            slo = %slo.name,
+            previous = previous.as_str(),
+            "SLO error budget no longer burning"
+        );
+    } else {
+        warn!(
+            workspace_id = %slo.workspace_id,
+            slo_id = %slo.id,
+            slo = %slo.name,
+            burn_state = status.burn_state.as_str(),
+            burn_rate_1h = ?status.burn_rate_1h,
+            burn_rate_6h = ?status.burn_rate_6h,
+            "SLO error budget burning"
+        );
+    }
+
+    let destinations = match db.list_alert_destinations(slo.workspace_id).await {
+        Ok(d) => d,
+        Err(e) => {
+            error!(error = %e, slo_id = %slo.id, "Failed to list alert destinations for SLO");
+            return;
+        }
+    };
+    let payload = notify::slo_burn_payload(slo, status, previous);
+    for destination in destinations.iter().filter(|d| d.enabled) {
+        notifier
+            .deliver(destination, notify::SLO_BURN_EVENT, &payload, false)
+            .await;
+    }
+}
+
+#[cfg(all(test, feature = "memory-store"))]
+mod tests {
+    use super::*;
+    use crate::models::{QueryMetric, QueryStatus};
+    use crate::store::memory::{MemoryStore, DEFAULT_WORKSPACE_ID};
+
+    /// Let the task run until it waits on the clock again
+    async fn settle() {
+        for _ in 0..20 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fast_burn_is_computed_and_saved() {
+        let start: DateTime<Utc> = "2026-01-01T12:00:00Z".parse().unwrap();
+        let clock = Clock::simulated(start);
+        let store = Arc::new(MemoryStore::with_clock(clock.clone()));
+        let slo = store
+            .create_slo(&Slo {
+                id: Uuid::new_v4(),
+                workspace_id: DEFAULT_WORKSPACE_ID,
+                name: "checkout under 200ms".into(),
+                service_id: None,
+                threshold_ms: 200,
+                target_percent: 99.0,
+                window_days: 7,
+                enabled: true,
+                created_at: start,
+                updated_at: start,
+            })
+            .await
+            .unwrap();
+
+        // A third of queries are too slow: 33x the sustainable burn rate
+        let metrics: Vec<QueryMetric> = (0..30)
+            .map(|i| {
+                QueryMetric::new(
+                    DEFAULT_WORKSPACE_ID,
+                    Uuid::new_v4(),
+                    "SELECT 1".to_string(),
+                    QueryStatus::Success,
+                    if i % 3 == 0 { 500 } else { 50 },
+                    start,
+                )
+            })
+            .collect();
+        store.insert_metrics_batch(&metrics).await.unwrap();
+        clock.advance(TimeDelta::minutes(1));
+
+        let db: Arc<dyn MetricsStore> = store.clone();
+        let notifier = Arc::new(Notifier::new(db.clone()));
+        let task = tokio::spawn(slo_task(
+            db,
+            notifier,
+            WorkspaceShards::new(1),
+            clock.clone(),
+        ));
+        settle().await;
+        task.abort();
+
+        let statuses = store.list_slo_statuses(DEFAULT_WORKSPACE_ID).await.unwrap();
+        assert_eq!(statuses.len(), 1);
+        let status = &statuses[0];
+        assert_eq!(status.slo_id, slo.id);
+        assert_eq!(status.counts.total, 30);
+        assert_eq!(status.counts.satisfied, 20);
+        assert_eq!(status.counts.tolerating, 10);
+        assert_eq!(status.burn_state, BurnState::FastBurn);
+    }
+}
@@ -0,0 +1,343 @@
+//! Throughput-drop task - detects services whose query volume collapses
+
+use crate::clock::Clock;
+use crate::db::ThroughputDrop;
+use crate::error::Result;
+use crate::models::IdGenerator;
+use crate::services::notify::{self, Notifier};
+use crate::store::MetricsStore;
+use crate::tasks::shards::{Shard, WorkspaceShards, INGEST_LAG};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+/// Window whose volume is compared with the baseline
+const RECENT_WINDOW: TimeDelta = TimeDelta::minutes(15);
+
+/// Window before the recent one that sets a service's usual volume
+const BASELINE_WINDOW: TimeDelta = TimeDelta::hours(3);
+
+/// How long before now the recent window ends, so metrics still on their way
+/// from the buffer to the database don't count as missing
+const FLUSH_LAG: TimeDelta = TimeDelta::minutes(1);
+
+/// Lowest usual volume, in queries per minute, a drop is detected against;
+/// quieter services come and go too much to tell
+const MIN_BASELINE_PER_MINUTE: f64 = 1.0;
+
+/// Share of the usual volume below which a service has dropped
+const DROP_RATIO: f64 = 0.1;
+
+/// Share of the volume before the drop at which a service has recovered
+const RECOVERY_RATIO: f64 = 0.5;
+
+/// Background task that detects throughput drops.
+///
+/// Visits every workspace every 60 seconds, one shard at a time, and
+/// compares each service's queries per minute over the last 15 minutes with
+/// the 3 hours before. A service running at under a tenth of its usual
+/// volume has dropped: an app that silently stopped querying trips no
+/// latency or error threshold, so this is the only signal it gives. A drop
+/// is recorded, logged at warn level, and sent to the workspace's enabled
+/// alert destinations, then stays open until the service is back to half
+/// its volume from before the drop, which is reported the same way. Open
+/// drops are saved and loaded on startup, so a restart doesn't report them
+/// again. Workspaces that ingested nothing in the last 3 hours have no
+/// baseline and are skipped.
+pub async fn throughput_drop_task(
+    db: Arc<dyn MetricsStore>,
+    notifier: Arc<Notifier>,
+    ids: IdGenerator,
+    shards: WorkspaceShards,
+    clock: Clock,
+) {
+    let mut ticks = shards.ticks(&clock, Duration::from_secs(60));
+    // (workspace ID, service ID) -> open drop
+    let mut open: HashMap<(Uuid, Uuid), ThroughputDrop> = HashMap::new();
+    let mut loaded = false;
+
+    info!(
+        shards = shards.count(),
+        "Throughput drop task started (60s interval)"
+    );
+
+    loop {
+        let shard = ticks.tick().await;
+
+        // Don't detect from scratch until the open drops are back, or every
+        // one of them would be reported again
+        if !loaded {
+            match db.list_open_throughput_drops().await {
+                Ok(saved) => {
+                    info!(count = saved.len(), "Loaded open throughput drops");
+                    open = saved
+                        .into_iter()
+                        .map(|d| ((d.workspace_id, d.service_id), d))
+                        .collect();
+                    loaded = true;
+                }
+                Err(e) => {
+                    error!(error = %e, "Failed to load open throughput drops");
+                    continue;
+                }
+            }
+        }
+
+        let now = clock.now();
+        if let Err(e) = detect_drops(db.as_ref(), &notifier, ids, shard, &mut open, now).await {
+            error!(error = %e, "Throughput drop detection failed");
+        }
+    }
+}
+
+/// Open and close drops for the services of the shard's workspaces
+async fn detect_drops(
+    db: &dyn MetricsStore,
+    notifier: &Notifier,
+    ids: IdGenerator,
+    shard: Shard,
+    open: &mut HashMap<(Uuid, Uuid), ThroughputDrop>,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    let to = now - FLUSH_LAG;
+    let recent_from = to - RECENT_WINDOW;
+    let baseline_from = recent_from - BASELINE_WINDOW;
+
+    let workspace_ids: Vec<Uuid> = db
+        .get_last_ingest(baseline_from - INGEST_LAG)
+        .await?
+        .into_keys()
+        .filter(|id| shard.contains(*id))
+        .collect();
+    if workspace_ids.is_empty() {
+        return Ok(());
+    }
+
+    let throughputs = db
+        .get_service_throughput(&workspace_ids, baseline_from, recent_from, to)
+        .await?;
+    for throughput in throughputs {
+        let key = (throughput.workspace_id, throughput.service_id);
+        let baseline = throughput.baseline as f64 / BASELINE_WINDOW.num_minutes() as f64;
+        let recent = throughput.recent as f64 / RECENT_WINDOW.num_minutes() as f64;
+
+        if let Some(drop) = open.get(&key) {
+            if !is_recovered(drop.baseline_per_minute, recent) {
+                continue;
+            }
+            if let Err(e) = db.resolve_throughput_drop(drop.id, now).await {
+                error!(error = %e, drop_id = %drop.id, "Failed to close throughput drop");
+                continue;
+            }
+            if let Some(mut drop) = open.remove(&key) {
+                drop.recovered_at = Some(now);
+                report(db, notifier, &drop, recent).await;
+            }
+        } else if is_drop(baseline, recent) {
+            let drop = ThroughputDrop {
+                id: ids.generate(),
+                workspace_id: throughput.workspace_id,
+                service_id: throughput.service_id,
+                baseline_per_minute: baseline,
+                recent_per_minute: recent,
+                started_at: now,
+                recovered_at: None,
+            };
+            if let Err(e) = db.insert_throughput_drop(&drop).await {
+                error!(error = %e, service_id = %drop.service_id, "Failed to record throughput drop");
+                continue;
+            }
+            report(db, notifier, &drop, recent).await;
+            open.insert(key, drop);
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `recent` queries per minute are far below a `baseline` busy
+/// enough to judge
+fn is_drop(baseline: f64, recent: f64) -> bool {
+    baseline >= MIN_BASELINE_PER_MINUTE && recent < DROP_RATIO * baseline
+}
+
+/// Whether `recent` queries per minute are back near the volume from before
+/// a drop
+fn is_recovered(baseline: f64, recent: f64) -> bool {
+    recent >= RECOVERY_RATIO * baseline
+}
+
+/// Log a drop or recovery and send it to the workspace's enabled alert
+/// destinations
+async fn report(db: &dyn MetricsStore, notifier: &Notifier, drop: &ThroughputDrop, recent: f64) {
+    let service_name = match db.get_service(drop.workspace_id, drop.service_id).await {
+        Ok(service) => service.map(|s| s.name),
+        Err(e) => {
+            warn!(error = %e, service_id = %drop.service_id, "Failed to look up service for throughput drop");
+            None
+        }
+    };
+
+    if drop.recovered_at.is_some() {
+        info!(
+            workspace_id = %drop.workspace_id,
+            service_id = %drop.service_id,
+            service = service_name.as_deref(),
+            baseline_per_minute = drop.baseline_per_minute,
+            recent_per_minute = recent,
+            "Service throughput recovered"
+        );
+    } else {
+        warn!(
+            workspace_id = %drop.workspace_id,
+            service_id = %drop.service_id,
+            service = service_name.as_deref(),
+            baseline_per_minute = drop.baseline_per_minute,
+            recent_per_minute = recent,
+            "Service throughput dropped"
+        );
+    }
+
+    let destinations = match db.list_alert_destinations(drop.workspace_id).await {
+        Ok(d) => d,
+        Err(e) => {
+            error!(error = %e, workspace_id = %drop.workspace_id, "Failed to list alert destinations for throughput drop");
+            return;
+        }
+    };
+    let payload = notify::throughput_drop_payload(drop, service_name.as_deref());
+    for destination in destinations.iter().filter(|d| d.enabled) {
+        notifier
+            .deliver(destination, notify::THROUGHPUT_DROP_EVENT, &payload, false)
+            .await;
+    }
+}
+
+#[cfg(all(test, feature = "memory-store"))]
+mod tests {
+    use super::*;
+    use crate::db::IngestionMinute;
+    use crate::models::{QueryMetric, QueryStatus};
+    use crate::store::memory::{MemoryStore, DEFAULT_WORKSPACE_ID};
+
+    #[test]
+    fn test_drop_and_recovery_thresholds() {
+        // Quiet services are left alone
+        assert!(!is_drop(0.5, 0.0));
+        assert!(is_drop(1.0, 0.0));
+        assert!(is_drop(100.0, 9.9));
+        assert!(!is_drop(100.0, 10.0));
+
+        // Between the thresholds a drop stays open
+        assert!(!is_recovered(100.0, 30.0));
+        assert!(is_recovered(100.0, 50.0));
+    }
+
+    #[tokio::test]
+    async fn test_drop_opens_once_and_closes_on_recovery() {
+        let clock = Clock::simulated("2026-01-01T00:00:00Z".parse().unwrap());
+        let store = MemoryStore::with_clock(clock.clone());
+        let notifier = Notifier::new(Arc::new(MemoryStore::new()));
+        let service_id = Uuid::new_v4();
+        let ingest = |count: usize| {
+            let metrics: Vec<QueryMetric> = (0..count)
+                .map(|_| {
+                    QueryMetric::new(
+                        DEFAULT_WORKSPACE_ID,
+                        service_id,
+                        "SELECT 1".to_string(),
+                        QueryStatus::Success,
+                        10,
+                        clock.now(),
+                    )
+                })
+                .collect();
+            let stats = (
+                DEFAULT_WORKSPACE_ID,
+                IngestionMinute {
+                    minute: clock.now(),
+                    accepted: count as i64,
+                    dropped: 0,
+                    deduplicated: 0,
+                },
+            );
+            let store = &store;
+            async move {
+                store.insert_metrics_batch(&metrics).await.unwrap();
+                store.record_ingestion_stats(&[stats]).await.unwrap();
+            }
+        };
+        let shard = WorkspaceShards::new(1)
+            .ticks(&clock, Duration::from_secs(60))
+            .tick()
+            .await;
+        let mut open = HashMap::new();
+
+        // Three hours at 10 queries a minute, then silence
+        for _ in 0..180 {
+            ingest(10).await;
+            clock.advance(TimeDelta::minutes(1));
+        }
+        clock.advance(TimeDelta::minutes(17));
+        for _ in 0..2 {
+            detect_drops(
+                &store,
+                &notifier,
+                IdGenerator::default(),
+                shard,
+                &mut open,
+                clock.now(),
+            )
+            .await
+            .unwrap();
+            clock.advance(TimeDelta::minutes(1));
+        }
+        let drops = store.list_open_throughput_drops().await.unwrap();
+        assert_eq!(drops.len(), 1);
+        assert_eq!(drops[0].service_id, service_id);
+        assert!((drops[0].baseline_per_minute - 10.0).abs() < 0.5);
+
+        // A trickle keeps it open; half the old volume closes it
+        for _ in 0..15 {
+            ingest(2).await;
+            clock.advance(TimeDelta::minutes(1));
+        }
+        detect_drops(
+            &store,
+            &notifier,
+            IdGenerator::default(),
+            shard,
+            &mut open,
+            clock.now(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(open.len(), 1);
+        for _ in 0..15 {
+            ingest(8).await;
+            clock.advance(TimeDelta::minutes(1));
+        }
+        detect_drops(
+            &store,
+            &notifier,
+            IdGenerator::default(),
+            shard,
+            &mut open,
+            clock.now(),
+        )
+        .await
+        .unwrap();
+        assert!(open.is_empty());
+        assert!(store.list_open_throughput_drops().await.unwrap().is_empty());
+
+        let drops = store
+            .list_throughput_drops(DEFAULT_WORKSPACE_ID, clock.now() - TimeDelta::days(1), 10)
+            .await
+            .unwrap();
+        assert_eq!(drops.len(), 1);
+        assert!(drops[0].recovered_at.is_some());
+    }
+}
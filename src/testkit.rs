@@ -0,0 +1,420 @@
+//! In-process QueryVault for integration tests
+//!
+//! [`TestServer`] runs the production router against the in-memory store
+//! with a stopped clock, so agents, SDKs, and dashboards can be tested
+//! against real routing, auth, validation, and storage without Postgres.
+//! Requests go straight to the router, or over HTTP through [`TestServer::serve`].
+//!
+//! The server runs the same background tasks as `main`, timed by the same
+//! stopped clock as the store and the handlers: a task runs once the test
+//! advances the clock past its interval. [`TestServer::flush`] stores the
+//! buffered metrics straight away, through the flush path of the
+//! aggregation task, without waiting for it.
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, Method, StatusCode},
+    Router,
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tower::Service;
+use uuid::Uuid;
+
+use crate::app;
+use crate::auth::KeyCache;
+use crate::clock::Clock;
+use crate::models::{IdGenerator, Workspace};
+use crate::services::fingerprint::DialectConfig;
+use crate::state::AppState;
+use crate::store::memory::{MemoryStore, DEFAULT_API_KEY, DEFAULT_WORKSPACE_ID};
+use crate::store::resilient::CircuitBreaker;
+use crate::tasks::aggregation::flush_batch;
+use crate::tasks::ingestion_stats::record_stats;
+use crate::tasks::{self, TaskConfig};
+
+/// Largest response body read by the request helpers
+const MAX_RESPONSE_BYTES: usize = 16 * 1024 * 1024;
+
+/// QueryVault in this process, backed by the in-memory store
+pub struct TestServer {
+    state: AppState,
+    store: Arc<MemoryStore>,
+    clock: Clock,
+    breaker: Arc<CircuitBreaker>,
+    app: Router,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Default for TestServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestServer {
+    /// A server whose clock is stopped at the current time; must be created
+    /// inside a Tokio runtime, which runs its background tasks
+    pub fn new() -> Self {
+        Self::at(Utc::now())
+    }
+
+    /// A server whose clock is stopped at `now`; must be created inside a
+    /// Tokio runtime, which runs its background tasks
+    pub fn at(now: DateTime<Utc>) -> Self {
+        let clock = Clock::simulated(now);
+        let store = Arc::new(MemoryStore::with_clock(clock.clone()));
+        let state = AppState::new(
+            store.clone(),
+            100_000,
+            10_000,
+            None,
+            DialectConfig::default(),
+            // Zero TTL so revoked keys stop working immediately
+            KeyCache::new(Duration::ZERO, 1_000),
+            None,
+            IdGenerator::default(),
+            100_000,
+        )
+        .with_clock(clock.clone());
+        let breaker = Arc::new(CircuitBreaker::new(
+            5,
+            Duration::from_secs(30),
+            clock.clone(),
+        ));
+        let tasks = tasks::spawn(&state, Arc::clone(&breaker), TaskConfig::default());
+        let app = Router::new().fallback_service(app::app(state.clone()));
+        Self {
+            state,
+            store,
+            clock,
+            breaker,
+            app,
+            tasks,
+        }
+    }
+
+    /// Workspace seeded on startup
+    pub fn workspace_id(&self) -> Uuid {
+        DEFAULT_WORKSPACE_ID
+    }
+
+    /// API key of the seeded workspace, with every scope
+    pub fn api_key(&self) -> &'static str {
+        DEFAULT_API_KEY
+    }
+
+    /// Register another workspace whose API key has every scope
+    pub fn add_workspace(&self, name: &str, api_key: &str) -> Workspace {
+        self.store.add_workspace(name, api_key)
+    }
+
+    /// Shared application state, e.g. to inspect the buffer or subscribe to
+    /// the live metric broadcast
+    pub fn state(&self) -> &AppState {
+        &self.state
+    }
+
+    /// The simulated clock, shared by the store, the handlers, and the
+    /// background tasks
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    /// The store's current time
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// Move the store's clock forward
+    pub fn advance(&self, by: TimeDelta) {
+        self.clock.advance(by);
+    }
+
+    /// Move the store's clock to `at`
+    pub fn set_time(&self, at: DateTime<Utc>) {
+        self.clock.set(at);
+    }
+
+    /// Store buffered metrics and pending ingestion stats, as the background
+    /// tasks would on their next run, returning how many metrics were stored
+    pub async fn flush(&self) -> usize {
+        let state = &self.state;
+        let mut stored = 0;
+        while let Some(inserted) = flush_batch(
+            &state.metrics_buffer,
+            &state.db,
+            &self.breaker,
+            &state.live_rollups,
+            &state.ingest_stats,
+            &state.quarantine,
+            &state.notifier,
+            &state.metrics,
+            &self.clock,
+        )
+        .await
+        {
+            stored += inserted;
+        }
+        record_stats(&state.ingest_stats, state.db.as_ref()).await;
+        stored
+    }
+
+    /// Send a request through the router
+    pub async fn request(&self, request: Request) -> axum::response::Response {
+        let mut app = self.app.clone();
+        std::future::poll_fn(|cx| Service::<Request>::poll_ready(&mut app, cx))
+            .await
+            .unwrap_or_else(|e| match e {});
+        app.call(request).await.unwrap_or_else(|e| match e {})
+    }
+
+    /// GET `path` with the seeded workspace's API key, returning the status
+    /// and JSON body (`null` when empty)
+    pub async fn get(&self, path: &str) -> (StatusCode, Value) {
+        self.send(Method::GET, path, None::<&Value>).await
+    }
+
+    /// POST `body` as JSON to `path` with the seeded workspace's API key
+    pub async fn post_json<T: Serialize>(&self, path: &str, body: &T) -> (StatusCode, Value) {
+        self.send(Method::POST, path, Some(body)).await
+    }
+
+    /// Send `body` as JSON with the seeded workspace's API key
+    pub async fn send<T: Serialize>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&T>,
+    ) -> (StatusCode, Value) {
+        let builder = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(header::AUTHORIZATION, format!("Bearer {}", DEFAULT_API_KEY));
+        let request = match body {
+            Some(body) => builder
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    serde_json::to_vec(body).expect("unserializable body"),
+                )),
+            None => builder.body(Body::empty()),
+        }
+        .expect("invalid request");
+
+        let response = self.request(request).await;
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), MAX_RESPONSE_BYTES)
+            .await
+            .expect("unreadable response body");
+        let body = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).expect("response body isn't JSON")
+        };
+        (status, body)
+    }
+
+    /// Serve over HTTP on an ephemeral localhost port until the runtime shuts
+    /// down, for clients that need a real socket
+    pub async fn serve(&self) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind test listener");
+        let addr = listener.local_addr().expect("listener has no address");
+        let app = self.app.clone();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.ok();
+        });
+        addr
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_ingested_metrics_reach_aggregations_after_flush() {
+        let server = TestServer::at("2026-01-01T12:00:30Z".parse().unwrap());
+        let ws = server.workspace_id();
+        let service = Uuid::new_v4();
+        let metric = |duration_ms: u64| {
+            json!({
+                "id": Uuid::new_v4(),
+                "workspace_id": ws,
+                "service_id": service,
+                "query_text": "SELECT * FROM users WHERE id = 1",
+                "status": "success",
+                "duration_ms": duration_ms,
+                "started_at": server.now(),
+                "completed_at": server.now(),
+            })
+        };
+
+        let (status, _) = server
+            .post_json(
+                "/api/v1/metrics/ingest",
+                &json!({ "metrics": [metric(10), metric(30)] }),
+            )
+            .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(server.flush().await, 2);
+        server.advance(TimeDelta::minutes(1));
+        server
+            .post_json(
+                "/api/v1/metrics/ingest",
+                &json!({ "metrics": [metric(50)] }),
+            )
+            .await;
+        assert_eq!(server.flush().await, 1);
+
+        let (status, body) = server
+            .get(&format!(
+                "/api/v1/workspaces/{ws}/aggregations?window=1m\
+                 &from=2026-01-01T12:00:00Z&to=2026-01-01T12:03:00Z&fill=zero"
+            ))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let counts: Vec<i64> = body["buckets"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|b| b["query_count"].as_i64().unwrap())
+            .collect();
+        assert_eq!(counts, [2, 1, 0]);
+
+        let (status, _) = server
+            .send(
+                Method::GET,
+                &format!("/api/v1/workspaces/{}/aggregations", Uuid::new_v4()),
+                None::<&Value>,
+            )
+            .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_long_poll_sees_every_stored_metric() {
+        let server = TestServer::new();
+        let ws = server.workspace_id();
+        let metrics: Vec<Value> = (0..3)
+            .map(|i| {
+                json!({
+                    "id": Uuid::new_v4(),
+                    "workspace_id": ws,
+                    "service_id": Uuid::new_v4(),
+                    "query_text": format!("SELECT {i}"),
+                    "status": "success",
+                    "duration_ms": 5,
+                    "started_at": server.now(),
+                    "completed_at": server.now(),
+                })
+            })
+            .collect();
+        server
+            .post_json("/api/v1/metrics/ingest", &json!({ "metrics": metrics }))
+            .await;
+        assert_eq!(server.flush().await, 3);
+
+        // Flushing drained the buffer, but not the event log
+        let (status, body) = server
+            .get(&format!(
+                "/api/v1/workspaces/{ws}/metrics/poll?since=0&timeout=0"
+            ))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["count"], 3);
+        assert_eq!(body["cursor"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_background_tasks_and_default_ranges_follow_the_clock() {
+        let server = TestServer::at("2026-01-01T12:00:30Z".parse().unwrap());
+        let ws = server.workspace_id();
+        let metrics: Vec<Value> = (0..2)
+            .map(|_| {
+                json!({
+                    "id": Uuid::new_v4(),
+                    "workspace_id": ws,
+                    "service_id": Uuid::new_v4(),
+                    "query_text": "SELECT 1",
+                    "status": "success",
+                    "duration_ms": 5,
+                    "started_at": server.now(),
+                    "completed_at": server.now(),
+                })
+            })
+            .collect();
+        server
+            .post_json("/api/v1/metrics/ingest", &json!({ "metrics": metrics }))
+            .await;
+
+        // The aggregation task flushes once its interval has passed
+        server.advance(TimeDelta::seconds(5));
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        assert!(server.state().metrics_buffer.is_empty());
+
+        // The last hour, by the server's clock rather than the system's
+        let (status, body) = server
+            .get(&format!("/api/v1/workspaces/{ws}/aggregations?window=1m"))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let total: i64 = body["buckets"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|b| b["query_count"].as_i64().unwrap())
+            .sum();
+        assert_eq!(total, 2);
+    }
+
+    #[tokio::test]
+    async fn test_request_latency_is_labeled_by_route_template() {
+        let server = TestServer::new();
+        let ws = server.workspace_id();
+        let (status, _) = server
+            .get(&format!("/api/v1/workspaces/{ws}/aggregations"))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        server.get("/no/such/path").await;
+
+        let response = server
+            .request(
+                Request::builder()
+                    .uri("/metrics")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+        let body = to_bytes(response.into_body(), MAX_RESPONSE_BYTES)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(
+            "queryvault_http_request_duration_seconds_count{method=\"GET\",\
+             route=\"/api/v1/workspaces/:workspace_id/aggregations\",status=\"200\"} 1"
+        ));
+        assert!(body.contains(
+            "queryvault_http_request_duration_seconds_count{method=\"GET\",\
+             route=\"unmatched\",status=\"404\"} 1"
+        ));
+        assert!(!body.contains(&ws.to_string()));
+    }
+}
@@ -0,0 +1,168 @@
+//! API version negotiation
+//!
+//! Every API route is served under `/api/v1` and `/api/v2`. Clients either
+//! call a versioned path directly or call the unversioned `/api/...` path
+//! and pick a version with the `X-Api-Version` header (default: v1), which
+//! the middleware resolves by rewriting the path before routing.
+
+use axum::{
+    extract::Request,
+    http::{HeaderValue, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::error::AppError;
+
+/// Request header selecting the API version for unversioned paths
+pub const VERSION_HEADER: &str = "x-api-version";
+
+/// Supported API versions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ApiVersion {
+    /// Original wire format
+    #[default]
+    V1,
+    /// Compact ingest payload with agent-supplied fingerprints
+    V2,
+}
+
+impl ApiVersion {
+    /// Path segment, e.g. `v1`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1",
+            ApiVersion::V2 => "v2",
+        }
+    }
+}
+
+impl std::str::FromStr for ApiVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "1" | "v1" => Ok(ApiVersion::V1),
+            "2" | "v2" => Ok(ApiVersion::V2),
+            other => Err(format!("Unsupported API version: {}", other)),
+        }
+    }
+}
+
+/// Middleware resolving the API version of `/api/...` requests
+///
+/// Must wrap the router (rather than be added with `Router::layer`) so the
+/// rewritten path is the one that gets routed.
+pub async fn negotiate_version(mut request: Request, next: Next) -> Response {
+    let header = request.headers().get(VERSION_HEADER).cloned();
+    let version = match resolve_version(request.uri(), header.as_ref()) {
+        Ok(Some(version)) => version,
+        Ok(None) => return next.run(request).await,
+        Err(e) => return e.into_response(),
+    };
+
+    if path_version(request.uri().path()).is_none() {
+        match versioned_uri(request.uri(), version) {
+            Ok(uri) => *request.uri_mut() = uri,
+            Err(e) => return e.into_response(),
+        }
+    }
+
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(VERSION_HEADER, HeaderValue::from_static(version.as_str()));
+    response
+}
+
+/// Version for an API request, or `None` for non-API paths
+///
+/// A versioned path wins over the header; the two must not disagree.
+fn resolve_version(
+    uri: &Uri,
+    header: Option<&HeaderValue>,
+) -> Result<Option<ApiVersion>, AppError> {
+    if uri.path() != "/api" && !uri.path().starts_with("/api/") {
+        return Ok(None);
+    }
+
+    let requested = header
+        .map(|value| {
+            value
+                .to_str()
+                .map_err(|_| AppError::InvalidRequest("Invalid X-Api-Version header".into()))?
+                .parse::<ApiVersion>()
+                .map_err(AppError::InvalidRequest)
+        })
+        .transpose()?;
+
+    match (path_version(uri.path()), requested) {
+        (Some(path), Some(requested)) if path != requested => {
+            Err(AppError::InvalidRequest(format!(
+                "X-Api-Version {} conflicts with the /api/{} path",
+                requested.as_str(),
+                path.as_str()
+            )))
+        }
+        (Some(path), _) => Ok(Some(path)),
+        (None, requested) => Ok(Some(requested.unwrap_or_default())),
+    }
+}
+
+/// Version named by an `/api/v{n}/...` path
+fn path_version(path: &str) -> Option<ApiVersion> {
+    let segment = path.strip_prefix("/api/")?.split('/').next()?;
+    match segment {
+        "v1" => Some(ApiVersion::V1),
+        "v2" => Some(ApiVersion::V2),
+        _ => None,
+    }
+}
+
+/// `/api/metrics/ingest?x=1` -> `/api/v2/metrics/ingest?x=1`
+fn versioned_uri(uri: &Uri, version: ApiVersion) -> Result<Uri, AppError> {
+    let rest = uri.path().strip_prefix("/api").unwrap_or_default();
+    let path_and_query = match uri.query() {
+        Some(query) => format!("/api/{}{}?{}", version.as_str(), rest, query),
+        None => format!("/api/{}{}", version.as_str(), rest),
+    };
+
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(
+        path_and_query
+            .parse()
+            .map_err(|_| AppError::InvalidRequest("Invalid request path".into()))?,
+    );
+    Uri::from_parts(parts).map_err(|_| AppError::InvalidRequest("Invalid request path".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_version() {
+        let v2 = HeaderValue::from_static("2");
+        let uri: Uri = "/api/metrics/ingest".parse().unwrap();
+        assert_eq!(resolve_version(&uri, None).unwrap(), Some(ApiVersion::V1));
+        assert_eq!(
+            resolve_version(&uri, Some(&v2)).unwrap(),
+            Some(ApiVersion::V2)
+        );
+
+        let uri: Uri = "/api/v1/metrics/ingest".parse().unwrap();
+        assert!(resolve_version(&uri, Some(&v2)).is_err());
+
+        let uri: Uri = "/health".parse().unwrap();
+        assert_eq!(resolve_version(&uri, Some(&v2)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_versioned_uri_keeps_query() {
+        let uri: Uri = "/api/workspaces/abc/metrics?limit=5".parse().unwrap();
+        assert_eq!(
+            versioned_uri(&uri, ApiVersion::V2).unwrap(),
+            "/api/v2/workspaces/abc/metrics?limit=5"
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! QueryVault release tooling
+//!
+//! Run through the cargo alias:
+//!
+//! ```text
+//! cargo xtask codegen [--spec openapi.json] [--out target/clients]
+//! ```
+//!
+//! `codegen` generates the Python and TypeScript client packages from the
+//! OpenAPI spec, versioned to match the server crate.
+
+mod python;
+mod spec;
+mod typescript;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::spec::Spec;
+
+pub type Result<T, E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+/// Header comment for every generated package
+pub const GENERATED_NOTICE: &str =
+    "Generated from openapi.json by `cargo xtask codegen`; do not edit by hand.";
+
+const USAGE: &str = "\
+Usage: cargo xtask <command>
+
+Commands:
+  codegen [--spec <path>] [--out <dir>]
+      Generate the Python and TypeScript clients
+      (default spec: openapi.json, default out: target/clients)";
+
+fn main() {
+    if let Err(e) = run(std::env::args().skip(1).collect()) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Vec<String>) -> Result<()> {
+    let root = workspace_root();
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        Some("codegen") => {
+            let mut spec = root.join("openapi.json");
+            let mut out = root.join("target").join("clients");
+            while let Some(flag) = args.next() {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("{} needs a value\n\n{}", flag, USAGE))?;
+                match flag.as_str() {
+                    "--spec" => spec = PathBuf::from(value),
+                    "--out" => out = PathBuf::from(value),
+                    _ => return Err(format!("unknown option {}\n\n{}", flag, USAGE).into()),
+                }
+            }
+            let version = crate_version(&root.join("Cargo.toml"))?;
+            codegen(&spec, &out, &version)?;
+            println!("Generated {} clients in {}", version, out.display());
+            Ok(())
+        }
+        Some("help" | "--help" | "-h") | None => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(other) => Err(format!("unknown command {}\n\n{}", other, USAGE).into()),
+    }
+}
+
+/// Generate both clients from the spec at `spec_path` into `out/{python,typescript}`
+pub fn codegen(spec_path: &Path, out: &Path, version: &str) -> Result<()> {
+    let text = fs::read_to_string(spec_path)
+        .map_err(|e| format!("reading {}: {}", spec_path.display(), e))?;
+    let doc: Value = serde_json::from_str(&text)
+        .map_err(|e| format!("parsing {}: {}", spec_path.display(), e))?;
+    let spec = Spec::parse(&doc)?;
+
+    for (name, generate) in [
+        (
+            "python",
+            python::generate as fn(&Spec, &str, &Path) -> Result<()>,
+        ),
+        ("typescript", typescript::generate),
+    ] {
+        let dir = out.join(name);
+        // Start clean so operations removed from the spec don't linger
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        generate(&spec, version, &dir)?;
+    }
+    Ok(())
+}
+
+/// Write a generated file, creating parent directories
+pub fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents).map_err(|e| format!("writing {}: {}", path.display(), e).into())
+}
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+/// `version` of the `[package]` in a Cargo manifest
+fn crate_version(manifest: &Path) -> Result<String> {
+    let text = fs::read_to_string(manifest)?;
+    let mut in_package = false;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+        } else if let (true, Some(value)) = (in_package, line.strip_prefix("version")) {
+            if let Some(value) = value.trim_start().strip_prefix('=') {
+                return Ok(value.trim().trim_matches('"').to_string());
+            }
+        }
+    }
+    Err(format!("no package version in {}", manifest.display()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+
+    /// Generate the clients for the repository spec into a fresh directory
+    /// and return the directory for one language
+    pub fn generate_fixture(language: &str) -> PathBuf {
+        let out = std::env::temp_dir().join(format!(
+            "queryvault-codegen-{}-{}",
+            language,
+            std::process::id()
+        ));
+        codegen(&workspace_root().join("openapi.json"), &out, "0.0.0-test").unwrap();
+        out.join(language)
+    }
+
+    pub fn command_available(program: &str) -> bool {
+        Command::new(program)
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false)
+    }
+
+    #[test]
+    fn test_crate_version() {
+        let version = crate_version(&workspace_root().join("Cargo.toml")).unwrap();
+        assert!(version.split('.').count() >= 3, "{}", version);
+    }
+
+    /// Every API route registered in `app.rs` must be described by the spec
+    #[test]
+    fn test_spec_covers_routes() {
+        let router = fs::read_to_string(workspace_root().join("src/app.rs")).unwrap();
+        let doc: Value = serde_json::from_str(
+            &fs::read_to_string(workspace_root().join("openapi.json")).unwrap(),
+        )
+        .unwrap();
+        let paths = doc["paths"].as_object().unwrap();
+
+        let routes: Vec<String> = router
+            .split('"')
+            .filter(|s| {
+                [
+                    "/workspaces/",
+                    "/admin/",
+                    "/metrics/",
+                    "/schemas/",
+                    "/beacon",
+                ]
+                .iter()
+                .any(|prefix| s.starts_with(prefix))
+            })
+            // WebSocket upgrades aren't expressible in OpenAPI
+            .filter(|s| !s.ends_with("/ws"))
+            // `:param` in the router is `{param}` in OpenAPI
+            .map(|s| {
+                s.split('/')
+                    .map(|seg| match seg.strip_prefix(':') {
+                        Some(param) => format!("{{{}}}", param),
+                        None => seg.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+            .collect();
+        assert!(!routes.is_empty());
+        for route in routes {
+            assert!(
+                paths.contains_key(&route),
+                "{} missing from openapi.json",
+                route
+            );
+        }
+    }
+}
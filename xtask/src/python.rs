@@ -0,0 +1,494 @@
+//! Python client emitter
+//!
+//! Generates a dependency-free package: `TypedDict` models and a client on
+//! top of `urllib`, so it works on any Python 3.8+.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::spec::{Field, ModelKind, Operation, Spec, Type};
+use crate::{write_file, Result, GENERATED_NOTICE};
+
+/// Import name of the generated package
+pub const PACKAGE: &str = "queryvault_client";
+
+const KEYWORDS: [&str; 35] = [
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+/// Write the Python package to `out`
+pub fn generate(spec: &Spec, version: &str, out: &Path) -> Result<()> {
+    write_file(&out.join("pyproject.toml"), &pyproject(spec, version))?;
+    write_file(
+        &out.join("README.md"),
+        &format!(
+            "# {} Python client\n\n{}\n\n```python\nfrom {} import QueryVaultClient\n\nclient = QueryVaultClient(\"http://localhost:8080\", \"<api key>\")\nservices = client.list_services(\"<workspace id>\")\n```\n",
+            spec.title, GENERATED_NOTICE, PACKAGE
+        ),
+    )?;
+    let package = out.join(PACKAGE);
+    write_file(&package.join("__init__.py"), &init(version))?;
+    write_file(&package.join("models.py"), &models(spec))?;
+    write_file(&package.join("client.py"), &client(spec))?;
+    write_file(&package.join("py.typed"), "")?;
+    Ok(())
+}
+
+fn pyproject(spec: &Spec, version: &str) -> String {
+    format!(
+        r#"[build-system]
+requires = ["setuptools>=61"]
+build-backend = "setuptools.build_meta"
+
+[project]
+name = "queryvault-client"
+version = "{version}"
+description = "{title} client"
+requires-python = ">=3.8"
+license = {{ text = "MIT" }}
+
+[tool.setuptools.package-data]
+{package} = ["py.typed"]
+"#,
+        version = version,
+        title = spec.title,
+        package = PACKAGE,
+    )
+}
+
+fn init(version: &str) -> String {
+    format!(
+        r#""""QueryVault API client
+
+{notice}
+"""
+
+from .client import ApiError, QueryVaultClient
+from . import models
+
+__version__ = "{version}"
+
+__all__ = ["ApiError", "QueryVaultClient", "models"]
+"#,
+        notice = GENERATED_NOTICE,
+        version = version,
+    )
+}
+
+fn models(spec: &Spec) -> String {
+    let mut out = format!(
+        "\"\"\"API models\n\n{}\n\"\"\"\n\nimport os\nimport time\nimport uuid\nfrom typing import Any, List, Literal, Optional, TypedDict\n",
+        GENERATED_NOTICE
+    );
+
+    for model in &spec.models {
+        out.push_str("\n\n");
+        match &model.kind {
+            ModelKind::Enum(values) => {
+                let values: Vec<String> = values.iter().map(|v| format!("{:?}", v)).collect();
+                let _ = writeln!(out, "{} = Literal[{}]", model.name, values.join(", "));
+                if let Some(description) = &model.description {
+                    let _ = writeln!(out, "\"\"\"{}\"\"\"", description);
+                }
+            }
+            ModelKind::Object(fields) => {
+                typed_dict(&mut out, &model.name, model.description.as_deref(), fields)
+            }
+        }
+    }
+    out.push_str(PY_UUID7);
+    out
+}
+
+/// Client-side twin of the server's default `IdGenerator`, for agents that
+/// assign their own metric IDs
+const PY_UUID7: &str = r#"
+
+
+def uuid7() -> str:
+    """Time-ordered UUIDv7, the server's default ID format"""
+    ms = time.time_ns() // 1_000_000
+    value = (ms & 0xFFFF_FFFF_FFFF) << 80 | int.from_bytes(os.urandom(10), "big")
+    value = value & ~(0xF << 76) | 0x7 << 76
+    value = value & ~(0x3 << 62) | 0x2 << 62
+    return str(uuid.UUID(int=value))
+"#;
+
+/// Emit a `TypedDict`, splitting required and optional keys across a base
+/// class since `NotRequired` needs Python 3.11
+fn typed_dict(out: &mut String, name: &str, description: Option<&str>, fields: &[Field]) {
+    let (required, optional): (Vec<&Field>, Vec<&Field>) = fields.iter().partition(|f| f.required);
+    // Keys such as `from` can't be class attributes, so those models use the
+    // functional syntax
+    let functional = fields.iter().any(|f| KEYWORDS.contains(&f.name.as_str()));
+
+    let body = |out: &mut String, fields: &[&Field]| {
+        if fields.is_empty() {
+            out.push_str("    pass\n");
+        }
+        for field in fields {
+            let _ = writeln!(out, "    {}: {}", field.name, py_type(&field.ty, true));
+            if let Some(description) = &field.description {
+                let _ = writeln!(out, "    \"\"\"{}\"\"\"", description);
+            }
+        }
+    };
+    let entries = |fields: &[&Field]| -> String {
+        fields
+            .iter()
+            .map(|f| format!("{:?}: {}", f.name, py_type(&f.ty, true)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    match (functional, optional.is_empty()) {
+        (true, true) => {
+            let _ = writeln!(
+                out,
+                "{} = TypedDict({:?}, {{{}}})",
+                name,
+                name,
+                entries(&required)
+            );
+        }
+        (true, false) => {
+            let base = format!("_{}Required", name);
+            let _ = writeln!(
+                out,
+                "{} = TypedDict({:?}, {{{}}})\n{} = TypedDict({:?}, {{{}}}, total=False)",
+                base,
+                base,
+                entries(&required),
+                name,
+                name,
+                entries(&optional)
+            );
+        }
+        (false, true) => {
+            let _ = writeln!(out, "class {}(TypedDict):", name);
+            if let Some(description) = description {
+                let _ = writeln!(out, "    \"\"\"{}\"\"\"\n", description);
+            }
+            body(out, &required);
+        }
+        (false, false) => {
+            let base = format!("_{}Required", name);
+            let _ = writeln!(out, "class {}(TypedDict):", base);
+            body(out, &required);
+            let _ = writeln!(out, "\n\nclass {}({}, total=False):", name, base);
+            if let Some(description) = description {
+                let _ = writeln!(out, "    \"\"\"{}\"\"\"\n", description);
+            }
+            body(out, &optional);
+        }
+    }
+}
+
+fn client(spec: &Spec) -> String {
+    let mut out = format!(
+        r#""""HTTP client
+
+{notice}
+"""
+
+import json
+from typing import Any, Dict, List, Optional, cast
+from urllib import error, parse, request
+
+from . import models
+
+API_PREFIX = "{base}"
+
+
+class ApiError(Exception):
+    """Error response from the API"""
+
+    def __init__(
+        self, status: int, message: str, errors: Optional[List["models.FieldError"]] = None
+    ) -> None:
+        super().__init__(f"{{status}}: {{message}}")
+        self.status = status
+        self.message = message
+        self.errors = errors or []
+
+
+def _path(value: Any) -> str:
+    return parse.quote(str(value), safe="")
+
+
+class QueryVaultClient:
+    """Client for the {title}
+
+    Every request is authenticated with `api_key`, sent as a Bearer token.
+    """
+
+    def __init__(self, base_url: str, api_key: str, timeout: float = 30.0) -> None:
+        self.base_url = base_url.rstrip("/")
+        self.api_key = api_key
+        self.timeout = timeout
+
+    def _request(
+        self,
+        method: str,
+        path: str,
+        query: Optional[Dict[str, Any]] = None,
+        body: Any = None,
+    ) -> Any:
+        url = self.base_url + API_PREFIX + path
+        params = {{k: v for k, v in (query or {{}}).items() if v is not None}}
+        if params:
+            url += "?" + parse.urlencode(params)
+        data = None if body is None else json.dumps(body).encode()
+        req = request.Request(url, data=data, method=method)
+        req.add_header("Authorization", f"Bearer {{self.api_key}}")
+        req.add_header("Accept", "application/json")
+        if data is not None:
+            req.add_header("Content-Type", "application/json")
+        try:
+            with request.urlopen(req, timeout=self.timeout) as response:
+                payload = response.read()
+        except error.HTTPError as e:
+            try:
+                detail = json.loads(e.read())
+            except ValueError:
+                detail = {{}}
+            raise ApiError(e.code, detail.get("error", e.reason), detail.get("errors")) from None
+        return json.loads(payload) if payload else None
+"#,
+        notice = GENERATED_NOTICE,
+        base = spec.base_path,
+        title = spec.title,
+    );
+
+    for op in &spec.operations {
+        method(&mut out, op);
+    }
+    out
+}
+
+fn method(out: &mut String, op: &Operation) {
+    let mut args = vec!["self".to_string()];
+    for param in &op.path_params {
+        args.push(format!(
+            "{}: {}",
+            ident(&param.name),
+            py_type(&param.ty, false)
+        ));
+    }
+    if let Some(body) = &op.body {
+        args.push(format!("body: {}", py_type(body, false)));
+    }
+    if !op.query_params.is_empty() {
+        args.push("*".into());
+        for param in &op.query_params {
+            args.push(format!(
+                "{}: {} = None",
+                ident(&param.name),
+                py_type(&Type::Nullable(Box::new(param.ty.clone())), false)
+            ));
+        }
+    }
+    let returns = op
+        .response
+        .as_ref()
+        .map(|ty| py_type(ty, false))
+        .unwrap_or_else(|| "None".into());
+
+    let _ = writeln!(out, "\n    def {}(", snake_case(&op.id));
+    for arg in &args {
+        let _ = writeln!(out, "        {},", arg);
+    }
+    let _ = writeln!(out, "    ) -> {}:", returns);
+
+    let mut doc: Vec<String> = op.summary.iter().cloned().collect();
+    if let Some(scope) = &op.scope {
+        doc.push(format!("Requires the `{}` scope.", scope));
+    }
+    let params: Vec<String> = op
+        .query_params
+        .iter()
+        .filter_map(|p| {
+            let description = p.description.as_deref()?;
+            Some(format!(":param {}: {}", ident(&p.name), description))
+        })
+        .collect();
+    if !params.is_empty() {
+        doc.push(params.join("\n        "));
+    }
+    match doc.len() {
+        0 => {}
+        1 => {
+            let _ = writeln!(out, "        \"\"\"{}\"\"\"", doc[0]);
+        }
+        _ => {
+            let _ = writeln!(
+                out,
+                "        \"\"\"{}\n        \"\"\"",
+                doc.join("\n\n        ")
+            );
+        }
+    }
+
+    let path = op.path_params.iter().fold(op.path.clone(), |path, p| {
+        path.replace(
+            &format!("{{{}}}", p.name),
+            &format!("{{_path({})}}", ident(&p.name)),
+        )
+    });
+    // Only interpolate when there are path parameters
+    let prefix = if op.path_params.is_empty() { "" } else { "f" };
+    let mut call = format!(
+        "self._request(\n            {:?},\n            {}\"{}\",\n",
+        op.method, prefix, path
+    );
+    if !op.query_params.is_empty() {
+        let query: Vec<String> = op
+            .query_params
+            .iter()
+            .map(|p| format!("{:?}: {}", p.name, ident(&p.name)))
+            .collect();
+        let _ = writeln!(call, "            query={{{}}},", query.join(", "));
+    }
+    if op.body.is_some() {
+        call.push_str("            body=body,\n");
+    }
+    call.push_str("        )");
+
+    match &op.response {
+        Some(_) => {
+            let _ = writeln!(out, "        return cast({}, {})", returns, call);
+        }
+        None => {
+            let _ = writeln!(out, "        {}", call);
+        }
+    }
+}
+
+/// Python type annotation; models are referenced within `models.py` by bare
+/// (quoted) name and from the client through the module
+fn py_type(ty: &Type, in_models: bool) -> String {
+    match ty {
+        Type::String => "str".into(),
+        Type::Integer => "int".into(),
+        Type::Number => "float".into(),
+        Type::Boolean => "bool".into(),
+        Type::Any => "Any".into(),
+        Type::Array(item) => format!("List[{}]", py_type(item, in_models)),
+        Type::Ref(name) if in_models => format!("{:?}", name),
+        Type::Ref(name) => format!("\"models.{}\"", name),
+        Type::Nullable(inner) => format!("Optional[{}]", py_type(inner, in_models)),
+    }
+}
+
+/// Parameter name, avoiding keywords (`from` -> `from_`)
+fn ident(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// `getJsonCasing` -> `get_json_casing`
+fn snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{command_available, generate_fixture};
+    use std::process::Command;
+
+    #[test]
+    fn test_snake_case() {
+        assert_eq!(snake_case("getJsonCasing"), "get_json_casing");
+        assert_eq!(snake_case("listApiKeys"), "list_api_keys");
+    }
+
+    /// Import the generated package and round-trip a request through a
+    /// local HTTP server
+    #[test]
+    fn test_generated_client_smoke() {
+        let dir = generate_fixture("python");
+        let client = std::fs::read_to_string(dir.join(PACKAGE).join("client.py")).unwrap();
+        assert!(client.contains("def ingest_metrics("));
+        assert!(client.contains("from_: Optional[str] = None"));
+
+        if !command_available("python3") {
+            eprintln!("python3 not found; skipping import check");
+            return;
+        }
+        let script = r#"
+import json, sys, threading
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+sys.path.insert(0, sys.argv[1])
+from queryvault_client import ApiError, QueryVaultClient
+
+seen = {}
+
+class Handler(BaseHTTPRequestHandler):
+    def do_GET(self):
+        seen["path"] = self.path
+        seen["auth"] = self.headers["Authorization"]
+        status = 200 if "services" in self.path else 404
+        body = {"workspace_id": "w", "count": 0, "services": []} if status == 200 else {"error": "nope", "code": 404}
+        payload = json.dumps(body).encode()
+        self.send_response(status)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(payload)))
+        self.end_headers()
+        self.wfile.write(payload)
+
+    def log_message(self, *args):
+        pass
+
+server = HTTPServer(("127.0.0.1", 0), Handler)
+threading.Thread(target=server.serve_forever, daemon=True).start()
+client = QueryVaultClient(f"http://127.0.0.1:{server.server_port}", "secret")
+
+assert client.list_services("w 1")["count"] == 0
+assert seen["path"] == "/api/v1/workspaces/w%201/services", seen
+assert seen["auth"] == "Bearer secret", seen
+try:
+    client.get_aggregations("w", window="5m", from_="2026-01-01T00:00:00Z")
+    raise AssertionError("expected ApiError")
+except ApiError as e:
+    assert e.status == 404 and e.message == "nope", e
+assert "window=5m" in seen["path"] and "from=2026" in seen["path"], seen
+
+import uuid
+from queryvault_client.models import uuid7
+ids = [uuid7() for _ in range(3)]
+assert all(uuid.UUID(i).version == 7 for i in ids), ids
+assert ids[0][:8] <= ids[-1][:8], ids
+server.shutdown()
+"#;
+        let output = Command::new("python3")
+            .arg("-c")
+            .arg(script)
+            .arg(&dir)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! OpenAPI document parsing
+//!
+//! Reduces `openapi.json` to the models and operations the client emitters
+//! need. Only the constructs the QueryVault spec uses are understood:
+//! component schemas that are objects or string enums, `$ref`s, nullable
+//! types (`["T", "null"]` or `oneOf` with `null`), arrays, and JSON request
+//! and response bodies.
+
+use serde_json::{Map, Value};
+
+use crate::Result;
+
+/// The parts of an OpenAPI document clients are generated from
+#[derive(Debug)]
+pub struct Spec {
+    pub title: String,
+    /// Path prefix every operation is served under, e.g. `/api/v1`
+    pub base_path: String,
+    /// Component schemas, sorted by name
+    pub models: Vec<Model>,
+    /// Operations, sorted by path then method
+    pub operations: Vec<Operation>,
+}
+
+/// A named component schema
+#[derive(Debug)]
+pub struct Model {
+    pub name: String,
+    pub description: Option<String>,
+    pub kind: ModelKind,
+}
+
+#[derive(Debug)]
+pub enum ModelKind {
+    /// String enum
+    Enum(Vec<String>),
+    /// Object with fields in declaration order
+    Object(Vec<Field>),
+}
+
+#[derive(Debug)]
+pub struct Field {
+    pub name: String,
+    pub ty: Type,
+    pub required: bool,
+    pub description: Option<String>,
+}
+
+/// Type of a field, parameter, or body
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    /// Free-form JSON
+    Any,
+    Array(Box<Type>),
+    /// Component schema by name
+    Ref(String),
+    Nullable(Box<Type>),
+}
+
+#[derive(Debug)]
+pub struct Operation {
+    /// `operationId`, e.g. `getAggregations`
+    pub id: String,
+    /// Upper-case HTTP method
+    pub method: String,
+    /// Path relative to [`Spec::base_path`], with `{param}` placeholders
+    pub path: String,
+    pub summary: Option<String>,
+    /// API key scope required (`x-scope`), if any
+    pub scope: Option<String>,
+    pub path_params: Vec<Param>,
+    pub query_params: Vec<Param>,
+    pub body: Option<Type>,
+    /// Success response body, `None` for empty responses
+    pub response: Option<Type>,
+}
+
+#[derive(Debug)]
+pub struct Param {
+    pub name: String,
+    pub ty: Type,
+    pub description: Option<String>,
+}
+
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+impl Spec {
+    /// Parse an OpenAPI 3.x document
+    pub fn parse(doc: &Value) -> Result<Spec> {
+        let title = str_at(doc, "/info/title").unwrap_or("API").to_string();
+        let base_path = str_at(doc, "/servers/0/url")
+            .unwrap_or_default()
+            .trim_end_matches('/')
+            .to_string();
+
+        let mut models = Vec::new();
+        if let Some(schemas) = doc
+            .pointer("/components/schemas")
+            .and_then(Value::as_object)
+        {
+            for (name, schema) in schemas {
+                models.push(parse_model(name, schema)?);
+            }
+        }
+        models.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut operations = Vec::new();
+        let paths = doc
+            .get("paths")
+            .and_then(Value::as_object)
+            .ok_or("spec has no paths")?;
+        for (path, item) in paths {
+            for method in METHODS {
+                match item.get(method) {
+                    // Request/response clients can't consume event streams
+                    Some(op) if is_event_stream(op) => {}
+                    Some(op) => operations.push(parse_operation(path, method, op)?),
+                    None => {}
+                }
+            }
+        }
+        operations.sort_by_key(|op| {
+            let rank = METHODS
+                .iter()
+                .position(|m| m.eq_ignore_ascii_case(&op.method));
+            (op.path.clone(), rank)
+        });
+
+        Ok(Spec {
+            title,
+            base_path,
+            models,
+            operations,
+        })
+    }
+}
+
+fn parse_model(name: &str, schema: &Value) -> Result<Model> {
+    let description = str_at(schema, "/description").map(String::from);
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        let values = values
+            .iter()
+            .map(|v| v.as_str().map(String::from))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| format!("{}: only string enums are supported", name))?;
+        return Ok(Model {
+            name: name.to_string(),
+            description,
+            kind: ModelKind::Enum(values),
+        });
+    }
+
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|r| r.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let empty = Map::new();
+    let properties = schema
+        .get("properties")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+    let fields = properties
+        .iter()
+        .map(|(field, property)| {
+            Ok(Field {
+                name: field.clone(),
+                ty: parse_type(property).map_err(|e| format!("{}.{}: {}", name, field, e))?,
+                required: required.contains(&field.as_str()),
+                description: str_at(property, "/description").map(String::from),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Model {
+        name: name.to_string(),
+        description,
+        kind: ModelKind::Object(fields),
+    })
+}
+
+fn parse_operation(path: &str, method: &str, op: &Value) -> Result<Operation> {
+    let id = str_at(op, "/operationId")
+        .ok_or_else(|| format!("{} {} has no operationId", method, path))?
+        .to_string();
+
+    let mut path_params = Vec::new();
+    let mut query_params = Vec::new();
+    for param in op
+        .get("parameters")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let name = str_at(param, "/name").ok_or_else(|| format!("{}: unnamed parameter", id))?;
+        let parsed = Param {
+            name: name.to_string(),
+            ty: parse_type(param.get("schema").unwrap_or(&Value::Null))
+                .map_err(|e| format!("{} parameter {}: {}", id, name, e))?,
+            description: str_at(param, "/description").map(String::from),
+        };
+        match str_at(param, "/in") {
+            Some("path") => path_params.push(parsed),
+            Some("query") => query_params.push(parsed),
+            other => {
+                return Err(format!("{}: unsupported parameter location {:?}", id, other).into())
+            }
+        }
+    }
+
+    let body = op
+        .pointer("/requestBody/content/application~1json/schema")
+        .map(parse_type)
+        .transpose()
+        .map_err(|e| format!("{} request body: {}", id, e))?;
+
+    let mut response = None;
+    if let Some(responses) = op.get("responses").and_then(Value::as_object) {
+        let success = responses.iter().find(|(status, _)| status.starts_with('2'));
+        if let Some(schema) =
+            success.and_then(|(_, r)| r.pointer("/content/application~1json/schema"))
+        {
+            response = Some(parse_type(schema).map_err(|e| format!("{} response: {}", id, e))?);
+        }
+    }
+
+    Ok(Operation {
+        id,
+        method: method.to_ascii_uppercase(),
+        path: path.to_string(),
+        summary: str_at(op, "/summary").map(String::from),
+        scope: str_at(op, "/x-scope")
+            .filter(|scope| *scope != "none")
+            .map(String::from),
+        path_params,
+        query_params,
+        body,
+        response,
+    })
+}
+
+fn parse_type(schema: &Value) -> Result<Type> {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let name = reference
+            .strip_prefix("#/components/schemas/")
+            .ok_or_else(|| format!("unsupported $ref {}", reference))?;
+        return Ok(Type::Ref(name.to_string()));
+    }
+
+    if let Some(variants) = schema.get("oneOf").and_then(Value::as_array) {
+        let (nulls, others): (Vec<&Value>, Vec<&Value>) = variants
+            .iter()
+            .partition(|v| v.get("type").and_then(Value::as_str) == Some("null"));
+        return match (nulls.len(), others.as_slice()) {
+            (0, [only]) => parse_type(only),
+            (1, [only]) => Ok(Type::Nullable(Box::new(parse_type(only)?))),
+            _ => Ok(Type::Any),
+        };
+    }
+
+    match schema.get("type") {
+        Some(Value::String(name)) => scalar_type(name, schema),
+        Some(Value::Array(names)) => {
+            let names: Vec<&str> = names.iter().filter_map(Value::as_str).collect();
+            match names.as_slice() {
+                [name, "null"] | ["null", name] => {
+                    Ok(Type::Nullable(Box::new(scalar_type(name, schema)?)))
+                }
+                [name] => scalar_type(name, schema),
+                _ => Ok(Type::Any),
+            }
+        }
+        _ => Ok(Type::Any),
+    }
+}
+
+fn scalar_type(name: &str, schema: &Value) -> Result<Type> {
+    Ok(match name {
+        "string" => Type::String,
+        "integer" => Type::Integer,
+        "number" => Type::Number,
+        "boolean" => Type::Boolean,
+        "array" => Type::Array(Box::new(match schema.get("items") {
+            Some(items) => parse_type(items)?,
+            None => Type::Any,
+        })),
+        "object" => Type::Any,
+        other => return Err(format!("unsupported type {}", other).into()),
+    })
+}
+
+/// Whether an operation answers with Server-Sent Events
+fn is_event_stream(op: &Value) -> bool {
+    op.get("responses")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .any(|(_, r)| r.pointer("/content/text~1event-stream").is_some())
+}
+
+fn str_at<'a>(value: &'a Value, pointer: &str) -> Option<&'a str> {
+    value.pointer(pointer).and_then(Value::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_types() {
+        let nullable_ref =
+            json!({"oneOf": [{"$ref": "#/components/schemas/Owner"}, {"type": "null"}]});
+        assert_eq!(
+            parse_type(&nullable_ref).unwrap(),
+            Type::Nullable(Box::new(Type::Ref("Owner".into())))
+        );
+        let ids = json!({"type": ["array", "null"], "items": {"type": "string", "format": "uuid"}});
+        assert_eq!(
+            parse_type(&ids).unwrap(),
+            Type::Nullable(Box::new(Type::Array(Box::new(Type::String))))
+        );
+    }
+
+    #[test]
+    fn test_skips_event_streams() {
+        let doc = json!({"paths": {
+            "/events": {"get": {
+                "operationId": "streamEvents",
+                "parameters": [{"name": "Last-Event-ID", "in": "header"}],
+                "responses": {"200": {"content": {"text/event-stream": {}}}}
+            }},
+            "/items": {"get": {"operationId": "listItems", "responses": {}}}
+        }});
+        let spec = Spec::parse(&doc).unwrap();
+        let ids: Vec<&str> = spec.operations.iter().map(|op| op.id.as_str()).collect();
+        assert_eq!(ids, vec!["listItems"]);
+    }
+}
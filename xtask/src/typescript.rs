@@ -0,0 +1,355 @@
+//! TypeScript client emitter
+//!
+//! Generates an npm package with interface models and a client built on the
+//! global `fetch` (Node 18+ and browsers).
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::spec::{ModelKind, Operation, Spec, Type};
+use crate::{write_file, Result, GENERATED_NOTICE};
+
+/// npm package name
+pub const PACKAGE: &str = "queryvault-client";
+
+/// Write the TypeScript package to `out`
+pub fn generate(spec: &Spec, version: &str, out: &Path) -> Result<()> {
+    write_file(&out.join("package.json"), &package_json(spec, version))?;
+    write_file(&out.join("tsconfig.json"), TSCONFIG)?;
+    write_file(
+        &out.join("README.md"),
+        &format!(
+            "# {} TypeScript client\n\n{}\n\n```ts\nimport {{ QueryVaultClient }} from \"{}\";\n\nconst client = new QueryVaultClient({{ baseUrl: \"http://localhost:8080\", apiKey: \"<api key>\" }});\nconst services = await client.listServices(\"<workspace id>\");\n```\n",
+            spec.title, GENERATED_NOTICE, PACKAGE
+        ),
+    )?;
+    let src = out.join("src");
+    write_file(&src.join("models.ts"), &models(spec))?;
+    write_file(&src.join("client.ts"), &client(spec))?;
+    write_file(
+        &src.join("index.ts"),
+        &format!(
+            "// {}\n\nexport * from \"./client\";\nexport * from \"./models\";\n",
+            GENERATED_NOTICE
+        ),
+    )?;
+    Ok(())
+}
+
+fn package_json(spec: &Spec, version: &str) -> String {
+    let package = serde_json::json!({
+        "name": PACKAGE,
+        "version": version,
+        "description": format!("{} client", spec.title),
+        "license": "MIT",
+        "main": "dist/index.js",
+        "types": "dist/index.d.ts",
+        "files": ["dist"],
+        "engines": { "node": ">=18" },
+        "scripts": { "build": "tsc", "prepublishOnly": "tsc" },
+        "devDependencies": { "typescript": "^5.4.0" }
+    });
+    serde_json::to_string_pretty(&package).unwrap_or_default() + "\n"
+}
+
+const TSCONFIG: &str = r#"{
+  "compilerOptions": {
+    "target": "ES2020",
+    "module": "CommonJS",
+    "lib": ["ES2020", "DOM"],
+    "declaration": true,
+    "outDir": "dist",
+    "rootDir": "src",
+    "strict": true,
+    "skipLibCheck": true
+  },
+  "include": ["src"]
+}
+"#;
+
+fn models(spec: &Spec) -> String {
+    let mut out = format!("// {}\n", GENERATED_NOTICE);
+    for model in &spec.models {
+        out.push('\n');
+        if let Some(description) = &model.description {
+            let _ = writeln!(out, "/** {} */", description);
+        }
+        match &model.kind {
+            ModelKind::Enum(values) => {
+                let values: Vec<String> = values.iter().map(|v| format!("{:?}", v)).collect();
+                let _ = writeln!(out, "export type {} = {};", model.name, values.join(" | "));
+            }
+            ModelKind::Object(fields) => {
+                let _ = writeln!(out, "export interface {} {{", model.name);
+                for field in fields {
+                    if let Some(description) = &field.description {
+                        let _ = writeln!(out, "  /** {} */", description);
+                    }
+                    let _ = writeln!(
+                        out,
+                        "  {}{}: {};",
+                        field.name,
+                        if field.required { "" } else { "?" },
+                        ts_type(&field.ty, true)
+                    );
+                }
+                out.push_str("}\n");
+            }
+        }
+    }
+    out.push_str(TS_UUID7);
+    out
+}
+
+/// Client-side twin of the server's default `IdGenerator`, for agents that
+/// assign their own metric IDs
+const TS_UUID7: &str = r#"
+/** Time-ordered UUIDv7, the server's default ID format */
+export function uuid7(): string {
+  const bytes = new Uint8Array(16);
+  crypto.getRandomValues(bytes);
+  let ms = Date.now();
+  for (let i = 5; i >= 0; i--) {
+    bytes[i] = ms % 256;
+    ms = Math.floor(ms / 256);
+  }
+  bytes[6] = (bytes[6] & 0x0f) | 0x70;
+  bytes[8] = (bytes[8] & 0x3f) | 0x80;
+  const hex = Array.from(bytes, (b) => b.toString(16).padStart(2, "0")).join("");
+  return `${hex.slice(0, 8)}-${hex.slice(8, 12)}-${hex.slice(12, 16)}-${hex.slice(16, 20)}-${hex.slice(20)}`;
+}
+"#;
+
+fn client(spec: &Spec) -> String {
+    let mut out = format!(
+        r#"// {notice}
+
+import type * as models from "./models";
+
+export const API_PREFIX = "{base}";
+
+/** Error response from the API */
+export class ApiError extends Error {{
+  constructor(
+    readonly status: number,
+    message: string,
+    readonly errors: models.FieldError[] = [],
+  ) {{
+    super(`${{status}}: ${{message}}`);
+    this.name = "ApiError";
+  }}
+}}
+
+export interface ClientOptions {{
+  /** Server URL, e.g. `http://localhost:8080` */
+  baseUrl: string;
+  /** Sent as a Bearer token */
+  apiKey: string;
+  /** Custom fetch implementation (default: the global `fetch`) */
+  fetch?: typeof fetch;
+}}
+
+type Query = Record<string, string | number | boolean | null | undefined>;
+
+/** Client for the {title} */
+export class QueryVaultClient {{
+  private readonly baseUrl: string;
+  private readonly apiKey: string;
+  private readonly fetchImpl: typeof fetch;
+
+  constructor(options: ClientOptions) {{
+    this.baseUrl = options.baseUrl.replace(/\/+$/, "");
+    this.apiKey = options.apiKey;
+    this.fetchImpl = options.fetch ?? globalThis.fetch.bind(globalThis);
+  }}
+
+  private async request<T>(method: string, path: string, query?: Query, body?: unknown): Promise<T> {{
+    const url = new URL(this.baseUrl + API_PREFIX + path);
+    for (const [key, value] of Object.entries(query ?? {{}})) {{
+      if (value !== undefined && value !== null) {{
+        url.searchParams.set(key, String(value));
+      }}
+    }}
+    const headers: Record<string, string> = {{
+      Authorization: `Bearer ${{this.apiKey}}`,
+      Accept: "application/json",
+    }};
+    if (body !== undefined) {{
+      headers["Content-Type"] = "application/json";
+    }}
+    const response = await this.fetchImpl(url, {{
+      method,
+      headers,
+      body: body === undefined ? undefined : JSON.stringify(body),
+    }});
+    const text = await response.text();
+    const payload = text ? JSON.parse(text) : undefined;
+    if (!response.ok) {{
+      throw new ApiError(response.status, payload?.error ?? response.statusText, payload?.errors ?? []);
+    }}
+    return payload as T;
+  }}
+"#,
+        notice = GENERATED_NOTICE,
+        base = spec.base_path,
+        title = spec.title,
+    );
+
+    for op in &spec.operations {
+        method(&mut out, op);
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn method(out: &mut String, op: &Operation) {
+    let mut args: Vec<String> = op
+        .path_params
+        .iter()
+        .map(|p| format!("{}: {}", camel_case(&p.name), ts_type(&p.ty, false)))
+        .collect();
+    if let Some(body) = &op.body {
+        args.push(format!("body: {}", ts_type(body, false)));
+    }
+    if !op.query_params.is_empty() {
+        let fields: Vec<String> = op
+            .query_params
+            .iter()
+            .map(|p| format!("{}?: {}", p.name, ts_type(&p.ty, false)))
+            .collect();
+        args.push(format!("params: {{ {} }} = {{}}", fields.join("; ")));
+    }
+    let returns = op
+        .response
+        .as_ref()
+        .map(|ty| ts_type(ty, false))
+        .unwrap_or_else(|| "void".into());
+
+    let mut doc: Vec<String> = op.summary.iter().cloned().collect();
+    if let Some(scope) = &op.scope {
+        doc.push(format!("Requires the `{}` scope.", scope));
+    }
+    let params: Vec<String> = op
+        .query_params
+        .iter()
+        .filter_map(|p| {
+            let description = p.description.as_deref()?;
+            Some(format!("@param params.{} {}", p.name, description))
+        })
+        .collect();
+    if !params.is_empty() {
+        doc.push(params.join("\n   * "));
+    }
+    out.push('\n');
+    if !doc.is_empty() {
+        let _ = writeln!(out, "  /**\n   * {}\n   */", doc.join("\n   *\n   * "));
+    }
+
+    let path = op.path_params.iter().fold(op.path.clone(), |path, p| {
+        path.replace(
+            &format!("{{{}}}", p.name),
+            &format!("${{encodeURIComponent(String({}))}}", camel_case(&p.name)),
+        )
+    });
+    let query = if op.query_params.is_empty() {
+        "undefined"
+    } else {
+        "params"
+    };
+    let mut call = format!("this.request<{}>({:?}, `{}`", returns, op.method, path);
+    match (&op.body, query) {
+        (Some(_), query) => {
+            let _ = write!(call, ", {}, body", query);
+        }
+        (None, "params") => call.push_str(", params"),
+        (None, _) => {}
+    }
+    call.push(')');
+
+    let _ = writeln!(
+        out,
+        "  {}({}): Promise<{}> {{\n    return {};\n  }}",
+        op.id,
+        args.join(", "),
+        returns,
+        call
+    );
+}
+
+/// TypeScript type; models are referenced within `models.ts` by bare name and
+/// from the client through the `models` namespace
+fn ts_type(ty: &Type, in_models: bool) -> String {
+    match ty {
+        Type::String => "string".into(),
+        Type::Integer | Type::Number => "number".into(),
+        Type::Boolean => "boolean".into(),
+        Type::Any => "unknown".into(),
+        Type::Array(item) => match item.as_ref() {
+            Type::Nullable(_) => format!("Array<{}>", ts_type(item, in_models)),
+            _ => format!("{}[]", ts_type(item, in_models)),
+        },
+        Type::Ref(name) if in_models => name.clone(),
+        Type::Ref(name) => format!("models.{}", name),
+        Type::Nullable(inner) => format!("{} | null", ts_type(inner, in_models)),
+    }
+}
+
+/// `workspace_id` -> `workspaceId`
+fn camel_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{command_available, generate_fixture};
+    use std::process::Command;
+
+    #[test]
+    fn test_generated_client_smoke() {
+        let dir = generate_fixture("typescript");
+        for file in [
+            "package.json",
+            "tsconfig.json",
+            "src/index.ts",
+            "src/models.ts",
+        ] {
+            assert!(dir.join(file).is_file(), "missing {}", file);
+        }
+        let client = std::fs::read_to_string(dir.join("src/client.ts")).unwrap();
+        assert!(client.contains("  ingestMetrics(body: models.IngestRequest)"));
+        assert!(client.contains("encodeURIComponent(String(workspaceId))"));
+        let models = std::fs::read_to_string(dir.join("src/models.ts")).unwrap();
+        assert!(models
+            .contains("export type ApiScope = \"ingest\" | \"read\" | \"admin\" | \"beacon\";"));
+        assert!(models.contains("export function uuid7(): string {"));
+
+        // Type-check when a compiler is installed (CI); the structure checks
+        // above still run without one
+        if !command_available("tsc") {
+            eprintln!("tsc not found; skipping type check");
+            return;
+        }
+        let output = Command::new("tsc")
+            .args(["--noEmit", "-p"])
+            .arg(&dir)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+}